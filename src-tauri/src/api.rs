@@ -0,0 +1,977 @@
+//! Opt-in local HTTP/JSON automation API.
+//!
+//! Binds to 127.0.0.1 only and requires a per-session token (`Authorization: Bearer <token>`,
+//! or `?token=` for EventSource clients). Endpoints call the same `commands::*_inner`
+//! functions as the Tauri commands against the app's shared `AppState`, so the GUI and
+//! external scripts can drive one session. `GET /api/events` streams the app's
+//! "uds-log", "job-progress" and "can-monitor" events as Server-Sent Events.
+//!
+//! One request per connection (`Connection: close`), which is all the scripts need.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Listener, Manager, Runtime};
+
+use crate::commands;
+use crate::ecu_emulator::EcuId;
+use crate::jobs::JobKind;
+use crate::monitor::MonitorOptions;
+use crate::reverse::{AnalysisOptions, CanEvent};
+use crate::state::AppState;
+
+/// Port used when the caller doesn't pick one
+pub const DEFAULT_PORT: u16 = 8534;
+
+/// Largest request body accepted
+const MAX_BODY: usize = 1 << 20;
+
+/// SSE keep-alive comment interval (also how often a stream notices shutdown)
+const SSE_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Address and token handed to API clients
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiInfo {
+    pub url: String,
+    pub token: String,
+}
+
+/// Running API server. Stops (and closes its listener) on `stop` or drop.
+pub struct ApiServer {
+    addr: SocketAddr,
+    token: String,
+    running: Arc<AtomicBool>,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl ApiServer {
+    /// Start listening on `127.0.0.1:port` (0 = any free port)
+    pub fn start<R: Runtime>(app: AppHandle<R>, port: u16, token: String) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("API bind on port {} failed: {}", port, e))?;
+        let addr = listener.local_addr().map_err(|e| e.to_string())?;
+        // Non-blocking accept so the loop can notice `stop`
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let ctx = Arc::new(ServerCtx {
+            app,
+            token: token.clone(),
+            running: running.clone(),
+        });
+        let accept = thread::spawn(move || accept_loop(listener, ctx));
+        log::info!("Automation API listening on http://{}", addr);
+
+        Ok(Self {
+            addr,
+            token,
+            running,
+            accept: Some(accept),
+        })
+    }
+
+    pub fn info(&self) -> ApiInfo {
+        ApiInfo {
+            url: format!("http://{}", self.addr),
+            token: self.token.clone(),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(h) = self.accept.take() {
+            let _ = h.join();
+            log::info!("Automation API on {} stopped", self.addr);
+        }
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// 256-bit hex token from the OS random number generator
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).expect("OS random number generator unavailable");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Token comparison that takes the same time wherever the first mismatch is, so a
+/// client can't guess the token byte by byte from response times
+pub fn token_matches(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    let diff = (0..given.len().max(expected.len())).fold(0u8, |acc, i| {
+        acc | (given.get(i).copied().unwrap_or(0) ^ expected.get(i).copied().unwrap_or(0))
+    });
+    diff == 0 && given.len() == expected.len()
+}
+
+// ─── Connection handling ────────────────────────────────────────────
+
+struct ServerCtx<R: Runtime> {
+    app: AppHandle<R>,
+    token: String,
+    running: Arc<AtomicBool>,
+}
+
+fn accept_loop<R: Runtime>(listener: TcpListener, ctx: Arc<ServerCtx<R>>) {
+    while ctx.running.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let ctx = ctx.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(&ctx, stream) {
+                        log::debug!("API connection from {}: {}", peer, e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => {
+                log::warn!("API accept failed: {}", e);
+                thread::sleep(Duration::from_millis(200));
+            }
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    bearer: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn token(&self) -> Option<&str> {
+        self.bearer.as_deref().or_else(|| {
+            self.query
+                .iter()
+                .find(|(k, _)| k == "token")
+                .map(|(_, v)| v.as_str())
+        })
+    }
+
+    /// Query parameter `key`, `+` and `%XX` decoded
+    fn param(&self, key: &str) -> Option<String> {
+        let (_, raw) = self.query.iter().find(|(k, _)| k == key)?;
+        let raw = raw.replace('+', " ");
+        let mut bytes = Vec::with_capacity(raw.len());
+        let mut rest = raw.as_bytes();
+        while let Some((&b, tail)) = rest.split_first() {
+            let hex = tail.get(..2).and_then(|h| std::str::from_utf8(h).ok());
+            match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                Some(decoded) if b == b'%' => {
+                    bytes.push(decoded);
+                    rest = &tail[2..];
+                }
+                _ => {
+                    bytes.push(b);
+                    rest = tail;
+                }
+            }
+        }
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, ApiError> {
+        let body: &[u8] = if self.body.is_empty() {
+            b"{}"
+        } else {
+            &self.body
+        };
+        serde_json::from_slice(body)
+            .map_err(|e| ApiError::bad_request(format!("Invalid JSON body: {}", e)))
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("Empty request")?.to_string();
+    let target = parts.next().ok_or("Missing request target")?.to_string();
+
+    let mut content_length = 0usize;
+    let mut bearer = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|e| e.to_string())? == 0 {
+            break;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => {
+                content_length = value.parse().map_err(|_| "Bad Content-Length")?;
+            }
+            "authorization" => {
+                bearer = value.strip_prefix("Bearer ").map(|t| t.trim().to_string());
+            }
+            _ => {}
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(format!("Body too large ({} bytes)", content_length));
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+    let (path, query) = match target.split_once('?') {
+        Some((p, q)) => (p.to_string(), q),
+        None => (target.clone(), ""),
+    };
+    let query = query
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    Ok(Request {
+        method,
+        path,
+        query,
+        bearer,
+        body,
+    })
+}
+
+fn handle_connection<R: Runtime>(ctx: &ServerCtx<R>, mut stream: TcpStream) -> Result<(), String> {
+    stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .map_err(|e| e.to_string())?;
+    let req = match read_request(&stream) {
+        Ok(req) => req,
+        Err(e) => {
+            return write_json(&mut stream, 400, &json!({ "error": e }));
+        }
+    };
+
+    let authorized = req
+        .token()
+        .is_some_and(|token| token_matches(token, &ctx.token));
+    if !authorized {
+        return write_json(
+            &mut stream,
+            401,
+            &json!({ "error": "Missing or invalid API token" }),
+        );
+    }
+
+    if req.method == "GET" && req.path == "/api/events" {
+        return stream_events(ctx, stream);
+    }
+
+    log::info!("[API] {} {}", req.method, req.path);
+    match route(&ctx.app, &req) {
+        Ok(value) => write_json(&mut stream, 200, &value),
+        Err(e) => {
+            log::warn!(
+                "[API] {} {} → {}: {}",
+                req.method,
+                req.path,
+                e.status,
+                e.message
+            );
+            write_json(&mut stream, e.status, &json!({ "error": e.message }))
+        }
+    }
+}
+
+fn write_json(stream: &mut TcpStream, status: u16, body: &Value) -> Result<(), String> {
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason,
+        body.len()
+    );
+    stream
+        .write_all(head.as_bytes())
+        .and_then(|_| stream.write_all(body.as_bytes()))
+        .map_err(|e| e.to_string())
+}
+
+/// Forward "uds-log" / "job-progress" / "can-monitor" app events to the client until it
+/// disconnects
+fn stream_events<R: Runtime>(ctx: &ServerCtx<R>, mut stream: TcpStream) -> Result<(), String> {
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let (tx, rx) = mpsc::channel::<(&'static str, String)>();
+    let ids: Vec<_> = ["uds-log", "job-progress", "can-monitor"]
+        .into_iter()
+        .map(|name| {
+            let tx = tx.clone();
+            ctx.app.listen_any(name, move |event| {
+                let _ = tx.send((name, event.payload().to_string()));
+            })
+        })
+        .collect();
+    drop(tx);
+
+    let result = loop {
+        if !ctx.running.load(Ordering::Relaxed) {
+            break Ok(());
+        }
+        let frame = match rx.recv_timeout(SSE_KEEPALIVE) {
+            Ok((name, payload)) => format!("event: {}\ndata: {}\n\n", name, payload),
+            Err(mpsc::RecvTimeoutError::Timeout) => ": keepalive\n\n".to_string(),
+            Err(mpsc::RecvTimeoutError::Disconnected) => break Ok(()),
+        };
+        if let Err(e) = stream
+            .write_all(frame.as_bytes())
+            .and_then(|_| stream.flush())
+        {
+            // Client went away
+            break Err(e.to_string());
+        }
+    };
+
+    for id in ids {
+        ctx.app.unlisten(id);
+    }
+    result
+}
+
+// ─── Routes ─────────────────────────────────────────────────────────
+
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: 400,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(req: &Request) -> Self {
+        Self {
+            status: 404,
+            message: format!("No route for {} {}", req.method, req.path),
+        }
+    }
+}
+
+/// Errors from the diagnostic operations themselves
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        Self {
+            status: 500,
+            message,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ConnectBody {
+    dll_path: Option<String>,
+    /// ECU names for an emulated-bus connection (no adapter)
+    emulate: Option<Vec<String>>,
+    /// Directory with `<ecu>_dump.json` scans to replay on the emulated bus
+    dump_dir: Option<String>,
+    /// Remote adapter bridge `host:port`, with `token`
+    remote: Option<String>,
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BenchBody {
+    enabled: bool,
+    ecus: Option<Vec<String>>,
+    faults: Option<String>,
+    schedule: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VehicleStateBody {
+    state: String,
+}
+
+#[derive(Deserialize)]
+struct ConvertCanLogBody {
+    input: String,
+    output: String,
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DecodeCanLogBody {
+    dbc: String,
+    log: String,
+}
+
+#[derive(Deserialize)]
+struct AnalyzeCanLogBody {
+    log: String,
+    #[serde(default)]
+    events: Vec<CanEvent>,
+    #[serde(default)]
+    options: AnalysisOptions,
+    dbc_out: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CcfEditBody {
+    ecu: String,
+    edits: Vec<commands::CcfEdit>,
+    /// Write even an image that breaks an error-severity CCF rule
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+struct CcfUndoBody {
+    backup: String,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+struct CcfRulesBody {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct SnapshotBody {
+    id: String,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+struct CcfImportBody {
+    path: String,
+    #[serde(default)]
+    vin_prefixes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SaveCanMonitorBody {
+    path: String,
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EcuBody {
+    ecu: String,
+}
+
+#[derive(Deserialize)]
+struct ReadDidBody {
+    ecu_tx: u32,
+    did_id: u16,
+}
+
+#[derive(Deserialize)]
+struct DtcBody {
+    ecu: String,
+    status_mask: Option<u8>,
+}
+
+#[derive(Deserialize)]
+struct RoutineBody {
+    routine_id: u16,
+    #[serde(default)]
+    data: Vec<u8>,
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, ApiError> {
+    serde_json::to_value(value).map_err(|e| ApiError::from(e.to_string()))
+}
+
+fn route<R: Runtime>(app: &AppHandle<R>, req: &Request) -> Result<Value, ApiError> {
+    let state = app.state::<AppState>();
+    let state: &AppState = &state;
+    let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+
+    match (req.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "status"]) => {
+            let bench = commands::get_bench_mode_status_inner(state).ok();
+            to_value(json!({
+                "connected": state.is_connected(),
+                "bench_mode": bench,
+            }))
+        }
+        ("GET", ["api", "devices"]) => to_value(commands::discover_devices()),
+        ("GET", ["api", "routines"]) => to_value(commands::list_routines()),
+        ("POST", ["api", "can-log", "convert"]) => {
+            let body: ConvertCanLogBody = req.json()?;
+            to_value(commands::convert_can_log_inner(
+                app,
+                &body.input,
+                &body.output,
+                body.format.as_deref(),
+            )?)
+        }
+        ("POST", ["api", "can-log", "decode"]) => {
+            let body: DecodeCanLogBody = req.json()?;
+            to_value(commands::decode_can_log_inner(app, &body.dbc, &body.log)?)
+        }
+        ("POST", ["api", "can-log", "analyze"]) => {
+            let body: AnalyzeCanLogBody = req.json()?;
+            to_value(commands::analyze_can_log_inner(
+                app,
+                &body.log,
+                &body.events,
+                &body.options,
+                body.dbc_out.as_deref(),
+            )?)
+        }
+        ("GET", ["api", "can-monitor"]) => to_value(commands::get_can_monitor_inner(state)),
+        ("POST", ["api", "can-monitor", "start"]) => {
+            let options: MonitorOptions = req.json()?;
+            let kind = JobKind::MonitorCan { options };
+            to_value(json!({ "job_id": commands::start_job_inner(app, kind)? }))
+        }
+        ("POST", ["api", "can-monitor", "stop"]) => {
+            to_value(commands::stop_can_monitor_inner(app, state)?)
+        }
+        ("POST", ["api", "can-monitor", "save"]) => {
+            let body: SaveCanMonitorBody = req.json()?;
+            to_value(commands::save_can_monitor_inner(
+                app,
+                state,
+                &body.path,
+                body.format.as_deref(),
+            )?)
+        }
+
+        ("POST", ["api", "connect"]) => {
+            let body: ConnectBody = req.json()?;
+            let info = match (body.emulate, body.remote) {
+                (Some(names), _) => {
+                    let ecus = names
+                        .iter()
+                        .map(|n| {
+                            EcuId::from_str(n)
+                                .ok_or_else(|| ApiError::bad_request(format!("Unknown ECU: {}", n)))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let dir = body.dump_dir.as_deref().map(std::path::Path::new);
+                    commands::connect_emulated_inner(app, state, &ecus, dir)?
+                }
+                (None, Some(addr)) => commands::connect_remote_inner(
+                    app,
+                    state,
+                    &addr,
+                    body.token.as_deref().unwrap_or_default(),
+                )?,
+                (None, None) => commands::connect_inner(app, state, body.dll_path)?,
+            };
+            to_value(info)
+        }
+        ("POST", ["api", "disconnect"]) => to_value(commands::disconnect_inner(app, state)?),
+        ("POST", ["api", "bench"]) => {
+            let body: BenchBody = req.json()?;
+            commands::toggle_bench_mode_inner(
+                app,
+                state,
+                body.enabled,
+                body.ecus,
+                body.faults.as_deref(),
+                body.schedule.as_deref(),
+            )?;
+            to_value(commands::get_bench_mode_status_inner(state)?)
+        }
+        ("POST", ["api", "vehicle-state"]) => {
+            let body: VehicleStateBody = req.json()?;
+            commands::set_vehicle_state_inner(app, state, &body.state)?;
+            to_value(commands::get_bench_mode_status_inner(state)?)
+        }
+
+        ("POST", ["api", "ecu-info"]) => {
+            let body: EcuBody = req.json()?;
+            to_value(commands::read_ecu_info_inner(app, state, &body.ecu)?)
+        }
+        ("POST", ["api", "read-did"]) => {
+            let body: ReadDidBody = req.json()?;
+            to_value(commands::read_did_inner(
+                app,
+                state,
+                body.ecu_tx,
+                body.did_id,
+            )?)
+        }
+        ("POST", ["api", "dtc"]) => {
+            let body: DtcBody = req.json()?;
+            let mask = body
+                .status_mask
+                .unwrap_or(crate::uds::services::DTC_STATUS_ALL);
+            to_value(commands::read_dtc_inner(app, state, &body.ecu, mask)?)
+        }
+        ("POST", ["api", "routine"]) => {
+            let body: RoutineBody = req.json()?;
+            to_value(commands::run_routine_inner(
+                app,
+                state,
+                body.routine_id,
+                &body.data,
+            )?)
+        }
+        ("POST", ["api", "read-ccf"]) => to_value(commands::read_ccf_inner(app, state)?),
+        ("POST", ["api", "compare-ccf"]) => to_value(commands::compare_ccf_inner(app, state)?),
+        ("GET", ["api", "ccf", "options"]) => {
+            let (query, table) = (req.param("q"), req.param("table"));
+            to_value(commands::search_ccf_options_inner(
+                state,
+                query.as_deref(),
+                table.as_deref(),
+            )?)
+        }
+        ("GET", ["api", "ccf", "tables"]) => to_value(state.ccf_tables.list()),
+        ("POST", ["api", "ccf", "tables"]) => {
+            let body: CcfImportBody = req.json()?;
+            to_value(commands::import_ccf_exml_inner(
+                state,
+                &body.path,
+                body.vin_prefixes,
+            )?)
+        }
+        ("GET", ["api", "ccf", "rules"]) => to_value(&*state.ccf_rules.current()),
+        ("POST", ["api", "ccf", "rules"]) => {
+            let body: CcfRulesBody = req.json()?;
+            to_value(commands::import_ccf_rules_inner(state, &body.path)?)
+        }
+        ("POST", ["api", "ccf", "check"]) => to_value(commands::check_ccf_inner(app, state)?),
+        ("POST", ["api", "ccf", "preview"]) => {
+            let body: CcfEditBody = req.json()?;
+            to_value(commands::preview_ccf_edit_inner(
+                app,
+                state,
+                &body.ecu,
+                &body.edits,
+            )?)
+        }
+        ("POST", ["api", "ccf", "write"]) => {
+            let body: CcfEditBody = req.json()?;
+            to_value(commands::write_ccf_edit_inner(
+                app,
+                state,
+                &body.ecu,
+                &body.edits,
+                body.force,
+            )?)
+        }
+        ("POST", ["api", "ccf", "undo"]) => {
+            let body: CcfUndoBody = req.json()?;
+            to_value(commands::undo_ccf_edit_inner(
+                app,
+                state,
+                &body.backup,
+                body.force,
+            )?)
+        }
+        ("GET", ["api", "archive"]) => to_value(state.archive.vehicles()),
+        ("GET", ["api", "archive", "diff"]) => {
+            let from = req
+                .param("from")
+                .ok_or_else(|| ApiError::bad_request("Missing ?from="))?;
+            let to = req
+                .param("to")
+                .ok_or_else(|| ApiError::bad_request("Missing ?to="))?;
+            to_value(commands::diff_snapshots_inner(state, &from, &to)?)
+        }
+        ("GET", ["api", "archive", "snapshots", id]) => to_value(state.archive.load(id)?),
+        ("GET", ["api", "archive", vin]) => to_value(state.archive.history(vin)),
+        ("POST", ["api", "archive", "rollback"]) => {
+            let body: SnapshotBody = req.json()?;
+            to_value(commands::rollback_ccf_inner(
+                app, state, &body.id, body.force,
+            )?)
+        }
+
+        // Long operations (restore CCF, sniff, full scans) run as background jobs
+        ("GET", ["api", "jobs"]) => to_value(state.jobs.list()),
+        ("POST", ["api", "jobs"]) => {
+            let kind: JobKind = req.json()?;
+            to_value(json!({ "job_id": commands::start_job_inner(app, kind)? }))
+        }
+        ("GET", ["api", "jobs", id]) => {
+            let id: u64 = id
+                .parse()
+                .map_err(|_| ApiError::bad_request("Invalid job id"))?;
+            state
+                .jobs
+                .get(id)
+                .map(to_value)
+                .unwrap_or_else(|| Err(ApiError::not_found(req)))
+        }
+        ("POST", ["api", "jobs", id, "cancel"]) => {
+            let id: u64 = id
+                .parse()
+                .map_err(|_| ApiError::bad_request("Invalid job id"))?;
+            to_value(commands::cancel_job_inner(app, state, id)?)
+        }
+
+        _ => Err(ApiError::not_found(req)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    struct TestServer {
+        _app: tauri::App<tauri::test::MockRuntime>,
+        server: ApiServer,
+    }
+
+    fn start_server() -> TestServer {
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        let server = ApiServer::start(app.handle().clone(), 0, "test-token".into()).unwrap();
+        TestServer { _app: app, server }
+    }
+
+    /// Minimal in-process HTTP client: one request, read until close
+    fn call(
+        server: &ApiServer,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (u16, Value) {
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let auth = token
+            .map(|t| format!("Authorization: Bearer {}\r\n", t))
+            .unwrap_or_default();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            auth,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, payload) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(payload).unwrap())
+    }
+
+    fn post(server: &ApiServer, path: &str, body: Value) -> (u16, Value) {
+        call(server, "POST", path, Some("test-token"), Some(body))
+    }
+
+    #[test]
+    fn test_rejects_missing_or_wrong_token() {
+        let t = start_server();
+        let (status, body) = call(&t.server, "GET", "/api/status", None, None);
+        assert_eq!(status, 401);
+        assert!(body["error"].as_str().unwrap().contains("token"));
+        let (status, _) = call(&t.server, "GET", "/api/status", Some("nope"), None);
+        assert_eq!(status, 401);
+        for near in ["test-toke", "test-tokens", "Test-token"] {
+            let (status, _) = call(&t.server, "GET", "/api/status", Some(near), None);
+            assert_eq!(status, 401, "{}", near);
+        }
+        let (status, _) = call(&t.server, "GET", "/api/status?token=test-token", None, None);
+        assert_eq!(status, 200);
+    }
+
+    #[test]
+    fn test_generated_tokens_are_random_hex() {
+        let token = generate_token();
+        assert_eq!(token.len(), 64);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn test_binds_localhost_only() {
+        let t = start_server();
+        assert!(t.server.addr().ip().is_loopback());
+        assert!(t.server.info().url.starts_with("http://127.0.0.1:"));
+    }
+
+    #[test]
+    fn test_emulated_session_over_http() {
+        let t = start_server();
+        let (status, body) = post(
+            &t.server,
+            "/api/read-did",
+            json!({ "ecu_tx": 0x726, "did_id": 0xF190 }),
+        );
+        assert_eq!(status, 500);
+        assert_eq!(body["error"], "Not connected");
+
+        let (status, body) = post(
+            &t.server,
+            "/api/connect",
+            json!({ "emulate": ["bcm", "gwm"] }),
+        );
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["dll_path"], commands::EMULATED_DLL_PATH);
+
+        let (_, body) = call(&t.server, "GET", "/api/status", Some("test-token"), None);
+        assert_eq!(body["connected"], true);
+
+        let (status, body) = post(
+            &t.server,
+            "/api/read-did",
+            json!({ "ecu_tx": 0x726, "did_id": 0xF190 }),
+        );
+        assert_eq!(status, 200);
+        let vin: Vec<u8> = serde_json::from_value(body).unwrap();
+        assert_eq!(vin, b"SAJBL4BVXGCY16353");
+
+        let (status, body) = post(&t.server, "/api/dtc", json!({ "ecu": "gwm" }));
+        assert_eq!(status, 200);
+        assert_eq!(body, json!([]));
+
+        let (status, _) = post(&t.server, "/api/read-did", json!({ "ecu_tx": "x" }));
+        assert_eq!(status, 400);
+        let (status, _) = post(&t.server, "/api/nope", json!({}));
+        assert_eq!(status, 404);
+
+        let (status, body) = call(
+            &t.server,
+            "GET",
+            "/api/ccf/options?q=Front+Display%20Variant",
+            Some("test-token"),
+            None,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body[0]["id"], 467);
+        assert_eq!(body[0]["width"], 4);
+
+        let (status, body) = call(&t.server, "GET", "/api/ccf/rules", Some("test-token"), None);
+        assert_eq!(status, 200);
+        assert_eq!(body["rules"][0]["id"], "display-10-inch");
+        assert_eq!(body["rules"][0]["require"][0]["check"], "option_in");
+
+        let (status, body) = call(
+            &t.server,
+            "GET",
+            "/api/archive/diff?from=x",
+            Some("test-token"),
+            None,
+        );
+        assert_eq!(status, 400, "{}", body);
+
+        let (status, _) = post(&t.server, "/api/disconnect", json!({}));
+        assert_eq!(status, 200);
+    }
+
+    #[test]
+    fn test_state_is_shared_with_app() {
+        let t = start_server();
+        // Connect through the app's own state (as the GUI would), query via HTTP
+        let state = t._app.state::<AppState>();
+        commands::connect_emulated_inner(&crate::sink::NullSink, &state, &[EcuId::Bcm], None)
+            .unwrap();
+        let (_, body) = call(&t.server, "GET", "/api/status", Some("test-token"), None);
+        assert_eq!(body["connected"], true);
+    }
+
+    #[test]
+    fn test_job_endpoints() {
+        let t = start_server();
+        post(&t.server, "/api/connect", json!({ "emulate": ["ipc"] }));
+        let (status, body) = post(&t.server, "/api/jobs", json!({ "type": "scan_ipc" }));
+        assert_eq!(status, 200, "{}", body);
+        let id = body["job_id"].as_u64().unwrap();
+
+        let (status, body) = call(
+            &t.server,
+            "GET",
+            &format!("/api/jobs/{}", id),
+            Some("test-token"),
+            None,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["id"], id);
+        let (status, _) = call(&t.server, "GET", "/api/jobs/999", Some("test-token"), None);
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn test_event_stream_delivers_log_entries() {
+        let t = start_server();
+        post(&t.server, "/api/connect", json!({ "emulate": ["bcm"] }));
+
+        let mut events = TcpStream::connect(t.server.addr()).unwrap();
+        write!(
+            events,
+            "GET /api/events?token=test-token HTTP/1.1\r\nHost: localhost\r\n\r\n"
+        )
+        .unwrap();
+        events
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(events);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert!(line.starts_with("HTTP/1.1 200"));
+        // Wait for the stream to subscribe before triggering traffic
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+
+        post(
+            &t.server,
+            "/api/read-did",
+            json!({ "ecu_tx": 0x726, "did_id": 0xF190 }),
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut seen = false;
+        while Instant::now() < deadline && !seen {
+            line.clear();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                break;
+            }
+            seen = line.starts_with("data: ") && line.contains("ReadDID VIN");
+        }
+        assert!(seen, "no uds-log event for the DID read");
+    }
+}
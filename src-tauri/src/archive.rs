@@ -0,0 +1,655 @@
+//! Per-vehicle archive of what was read from a car: CCF images, full DID
+//! scans, ECU identification and DTCs.
+//!
+//! Each read becomes a timestamped snapshot in `<dir>/<VIN>/<id>.json`, so the
+//! history of a car survives between visits instead of overwriting one dump
+//! file. A read identical to the latest snapshot of the same kind and ECU is
+//! not stored again. Two snapshots of one kind diff option by option, DID by
+//! DID or DTC by DTC (`diff`), and archived CCF images can be written back.
+//!
+//! Reads that don't carry the VIN themselves (DTCs) are filed under the VIN
+//! last seen in this session (`note_vin`), or `UNKNOWN` before any.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use crate::ccf::{Ccf, CcfTables};
+use crate::commands::{DtcEntry, EcuInfoEntry};
+
+/// Folder for snapshots taken before any VIN was seen
+pub const UNKNOWN_VIN: &str = "UNKNOWN";
+
+/// One DID of a full scan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScannedDid {
+    pub did: u16,
+    /// Session it was read in ("default" or "extended")
+    pub session: String,
+    /// Payload after the 0x62 DID header; `None` when the read failed
+    pub data: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What a snapshot holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SnapshotData {
+    /// CCF image; `source` is how it was read, e.g. "DID 0xEE00"
+    Ccf {
+        source: String,
+        raw_bytes: Vec<u8>,
+    },
+    DidScan {
+        dids: Vec<ScannedDid>,
+    },
+    Identification {
+        entries: Vec<EcuInfoEntry>,
+    },
+    Dtcs {
+        dtcs: Vec<DtcEntry>,
+    },
+}
+
+impl SnapshotData {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SnapshotData::Ccf { .. } => "ccf",
+            SnapshotData::DidScan { .. } => "did_scan",
+            SnapshotData::Identification { .. } => "identification",
+            SnapshotData::Dtcs { .. } => "dtcs",
+        }
+    }
+
+    /// One line for history listings
+    pub fn summary(&self) -> String {
+        match self {
+            SnapshotData::Ccf { source, raw_bytes } => {
+                format!("{} bytes from {}", raw_bytes.len(), source)
+            }
+            SnapshotData::DidScan { dids } => {
+                let ok = dids.iter().filter(|d| d.data.is_some()).count();
+                format!("{}/{} DIDs read", ok, dids.len())
+            }
+            SnapshotData::Identification { entries } => format!("{} entries", entries.len()),
+            SnapshotData::Dtcs { dtcs } => format!("{} DTCs", dtcs.len()),
+        }
+    }
+
+    /// The VIN the data itself carries (CCF image or DID 0xF190)
+    pub fn vin(&self) -> Option<String> {
+        let vin = match self {
+            SnapshotData::Ccf { raw_bytes, .. } => Ccf::parse(raw_bytes).ok()?.vin(),
+            SnapshotData::DidScan { dids } => dids
+                .iter()
+                .find(|d| d.did == 0xF190)
+                .and_then(|d| d.data.as_deref())
+                .map(|data| String::from_utf8_lossy(data).into_owned()),
+            SnapshotData::Identification { entries } => entries
+                .iter()
+                .find(|e| e.did_hex == "F190")
+                .and_then(|e| e.value.clone()),
+            SnapshotData::Dtcs { .. } => None,
+        };
+        normalize_vin(&vin?)
+    }
+}
+
+/// A stored snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub vin: String,
+    pub ecu: String,
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub data: SnapshotData,
+}
+
+impl Snapshot {
+    pub fn info(&self) -> SnapshotInfo {
+        SnapshotInfo {
+            id: self.id.clone(),
+            vin: self.vin.clone(),
+            ecu: self.ecu.clone(),
+            timestamp: self.timestamp.clone(),
+            kind: self.data.kind().to_string(),
+            summary: self.data.summary(),
+        }
+    }
+}
+
+/// A snapshot without its data, for listings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub vin: String,
+    pub ecu: String,
+    pub timestamp: String,
+    pub kind: String,
+    pub summary: String,
+}
+
+/// A vehicle with archived snapshots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedVehicle {
+    pub vin: String,
+    pub snapshots: usize,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// One option, DID, identification entry or DTC that differs; `None` where
+/// it was missing (or unreadable) on that side
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotChange {
+    /// Option ID, DID or DTC code
+    pub key: String,
+    pub label: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub from: SnapshotInfo,
+    pub to: SnapshotInfo,
+    /// Decode table CCF options were named with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    pub changes: Vec<SnapshotChange>,
+}
+
+pub struct VehicleArchive {
+    dir: PathBuf,
+    /// VIN of the vehicle being worked on, from the last read that carried one
+    current_vin: Mutex<Option<String>>,
+    /// Time of the last snapshot, so IDs keep increasing within a millisecond
+    last_saved: Mutex<Option<DateTime<Local>>>,
+}
+
+impl VehicleArchive {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            current_vin: Mutex::new(None),
+            last_saved: Mutex::new(None),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn current_vin(&self) -> Option<String> {
+        self.current_vin.lock().unwrap().clone()
+    }
+
+    /// Make `vin` the current vehicle, if it is a valid VIN
+    pub fn note_vin(&self, vin: &str) {
+        if let Some(vin) = normalize_vin(vin) {
+            *self.current_vin.lock().unwrap() = Some(vin);
+        }
+    }
+
+    /// Forget the current vehicle (on disconnect, the next car may differ)
+    pub fn forget_vin(&self) {
+        *self.current_vin.lock().unwrap() = None;
+    }
+
+    /// Store `data` read from `ecu`, filed under the VIN it carries or else the
+    /// current one. When it matches the latest snapshot of that kind and ECU,
+    /// that snapshot is returned and nothing is written.
+    pub fn save(&self, ecu: &str, data: SnapshotData) -> Result<SnapshotInfo, String> {
+        if let Some(vin) = data.vin() {
+            self.note_vin(&vin);
+        }
+        let vin = self
+            .current_vin()
+            .unwrap_or_else(|| UNKNOWN_VIN.to_string());
+        let ecu = ecu.to_lowercase();
+        let latest = self
+            .snapshots(&vin)
+            .into_iter()
+            .rev()
+            .find(|s| s.ecu == ecu && s.data.kind() == data.kind());
+        if let Some(latest) = latest.filter(|s| s.data == data) {
+            return Ok(latest.info());
+        }
+
+        let now = {
+            let mut last = self.last_saved.lock().unwrap();
+            let now = match *last {
+                Some(t) => Local::now().max(t + TimeDelta::milliseconds(1)),
+                None => Local::now(),
+            };
+            *last = Some(now);
+            now
+        };
+        let dir = self.dir.join(&vin);
+        let id = format!(
+            "{}_{}_{}",
+            now.format("%Y%m%d-%H%M%S%.3f"),
+            data.kind(),
+            ecu
+        );
+        let snapshot = Snapshot {
+            id,
+            vin,
+            ecu,
+            timestamp: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            data,
+        };
+        let json = serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        let path = dir.join(format!("{}.json", snapshot.id));
+        std::fs::write(&path, json)
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        Ok(snapshot.info())
+    }
+
+    /// Vehicles in the archive, by VIN
+    pub fn vehicles(&self) -> Vec<ArchivedVehicle> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut vins: Vec<String> = entries
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().into_string().ok())
+            .collect();
+        vins.sort();
+        vins.into_iter()
+            .filter_map(|vin| {
+                let history = self.history(&vin);
+                Some(ArchivedVehicle {
+                    snapshots: history.len(),
+                    first_seen: history.first()?.timestamp.clone(),
+                    last_seen: history.last()?.timestamp.clone(),
+                    vin,
+                })
+            })
+            .collect()
+    }
+
+    /// Snapshots of the vehicle with `vin`, oldest first. Anything but a valid VIN
+    /// or `UNKNOWN_VIN` has none, so `vin` never names a path outside the archive.
+    pub fn history(&self, vin: &str) -> Vec<SnapshotInfo> {
+        let vin = match normalize_vin(vin) {
+            Some(vin) => vin,
+            None if vin.trim().eq_ignore_ascii_case(UNKNOWN_VIN) => UNKNOWN_VIN.to_string(),
+            None => return Vec::new(),
+        };
+        self.snapshots(&vin).iter().map(Snapshot::info).collect()
+    }
+
+    /// The snapshot with `id`, whichever vehicle it belongs to
+    pub fn load(&self, id: &str) -> Result<Snapshot, String> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        let file = format!("{}.json", id);
+        let path = std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path().join(&file))
+            .find(|p| valid && p.is_file())
+            .ok_or_else(|| format!("No archived snapshot '{}'", id))?;
+        read_snapshot(&path)
+    }
+
+    /// Snapshots in `<dir>/<vin>`, oldest first; unreadable files are skipped
+    fn snapshots(&self, vin: &str) -> Vec<Snapshot> {
+        let Ok(entries) = std::fs::read_dir(self.dir.join(vin)) else {
+            return Vec::new();
+        };
+        let mut snapshots: Vec<Snapshot> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|x| x == "json"))
+            .filter_map(|path| match read_snapshot(&path) {
+                Ok(snapshot) => Some(snapshot),
+                Err(e) => {
+                    log::warn!("Skipping {}", e);
+                    None
+                }
+            })
+            .collect();
+        snapshots.sort_by(|a, b| a.id.cmp(&b.id));
+        snapshots
+    }
+}
+
+fn read_snapshot(path: &Path) -> Result<Snapshot, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{} is not a snapshot: {}", path.display(), e))
+}
+
+/// Upper-cased `vin` when it is a valid ISO 3779 VIN (17 characters, no I, O, Q)
+pub fn normalize_vin(vin: &str) -> Option<String> {
+    let vin = vin.trim().to_ascii_uppercase();
+    let valid = vin.len() == 17
+        && vin
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() && !matches!(c, 'I' | 'O' | 'Q'));
+    valid.then_some(vin)
+}
+
+/// What changed from `from` to `to`, which must be snapshots of the same kind;
+/// CCF options are named with the decode table for `to`'s VIN
+pub fn diff(tables: &CcfTables, from: &Snapshot, to: &Snapshot) -> Result<SnapshotDiff, String> {
+    let mut table = None;
+    let changes = match (&from.data, &to.data) {
+        (SnapshotData::Ccf { raw_bytes: a, .. }, SnapshotData::Ccf { raw_bytes: b, .. }) => {
+            let before = Ccf::parse(a).map_err(|e| format!("{}: {}", from.id, e))?;
+            let after = Ccf::parse(b).map_err(|e| format!("{}: {}", to.id, e))?;
+            let decode = tables.for_vin(after.vin().as_deref());
+            table = Some(decode.info().name.clone());
+            decode
+                .diff(&before, &after)
+                .into_iter()
+                .map(|c| SnapshotChange {
+                    key: c.option_id.to_string(),
+                    label: c.name,
+                    before: Some(c.before),
+                    after: Some(c.after),
+                })
+                .collect()
+        }
+        (SnapshotData::DidScan { dids: a }, SnapshotData::DidScan { dids: b }) => {
+            let values = |dids: &[ScannedDid]| -> BTreeMap<u16, Option<String>> {
+                dids.iter()
+                    .map(|d| (d.did, d.data.as_deref().map(format_did_value)))
+                    .collect()
+            };
+            changed(values(a), values(b))
+                .map(|(did, before, after)| SnapshotChange {
+                    key: format!("{:04X}", did),
+                    label: crate::commands::did_name(did).to_string(),
+                    before,
+                    after,
+                })
+                .collect()
+        }
+        (
+            SnapshotData::Identification { entries: a },
+            SnapshotData::Identification { entries: b },
+        ) => {
+            let labels: BTreeMap<&str, &str> = a
+                .iter()
+                .chain(b)
+                .map(|e| (e.did_hex.as_str(), e.label.as_str()))
+                .collect();
+            let values = |entries: &[EcuInfoEntry]| -> BTreeMap<String, Option<String>> {
+                entries
+                    .iter()
+                    .map(|e| (e.did_hex.clone(), e.value.clone()))
+                    .collect()
+            };
+            changed(values(a), values(b))
+                .map(|(did_hex, before, after)| SnapshotChange {
+                    label: labels
+                        .get(did_hex.as_str())
+                        .copied()
+                        .unwrap_or_default()
+                        .into(),
+                    key: did_hex,
+                    before,
+                    after,
+                })
+                .collect()
+        }
+        (SnapshotData::Dtcs { dtcs: a }, SnapshotData::Dtcs { dtcs: b }) => {
+            let values = |dtcs: &[DtcEntry]| -> BTreeMap<String, Option<String>> {
+                dtcs.iter()
+                    .map(|d| {
+                        let status = format!("0x{:02X} {}", d.status, d.flags.join(", "));
+                        (d.code.clone(), Some(status.trim_end().to_string()))
+                    })
+                    .collect()
+            };
+            changed(values(a), values(b))
+                .map(|(code, before, after)| SnapshotChange {
+                    key: code,
+                    label: "DTC".into(),
+                    before,
+                    after,
+                })
+                .collect()
+        }
+        _ => {
+            return Err(format!(
+                "Cannot diff a {} snapshot against a {} one",
+                from.data.kind(),
+                to.data.kind()
+            ))
+        }
+    };
+    Ok(SnapshotDiff {
+        from: from.info(),
+        to: to.info(),
+        table,
+        changes,
+    })
+}
+
+/// Keys whose value differs, missing keys counting as `None`
+fn changed<K: Ord>(
+    mut before: BTreeMap<K, Option<String>>,
+    after: BTreeMap<K, Option<String>>,
+) -> impl Iterator<Item = (K, Option<String>, Option<String>)> {
+    let mut merged: BTreeMap<K, (Option<String>, Option<String>)> = BTreeMap::new();
+    for (key, value) in after {
+        let old = before.remove(&key).flatten();
+        merged.insert(key, (old, value));
+    }
+    for (key, value) in before {
+        merged.insert(key, (value, None));
+    }
+    merged
+        .into_iter()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(key, (a, b))| (key, a, b))
+}
+
+/// Hex bytes, followed by the text when it is all printable
+fn format_did_value(data: &[u8]) -> String {
+    let hex: Vec<String> = data.iter().map(|b| format!("{:02X}", b)).collect();
+    let printable = !data.is_empty() && data.iter().all(|&b| (0x20..0x7F).contains(&b));
+    if printable {
+        format!(
+            "{} \"{}\"",
+            hex.join(" "),
+            String::from_utf8_lossy(data).trim()
+        )
+    } else {
+        hex.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ccf::CcfTable;
+
+    const VIN: &str = "SAJBL4BVXGCY16353";
+
+    fn temp_archive(name: &str) -> VehicleArchive {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        VehicleArchive::new(dir)
+    }
+
+    fn scan(vin: &str, serial: &[u8]) -> SnapshotData {
+        let did = |did: u16, data: Option<&[u8]>| ScannedDid {
+            did,
+            session: "default".into(),
+            data: data.map(<[u8]>::to_vec),
+            error: data.is_none().then(|| "NRC 0x31".to_string()),
+        };
+        SnapshotData::DidScan {
+            dids: vec![
+                did(0xF190, Some(vin.as_bytes())),
+                did(0xF120, Some(serial)),
+                did(0x2A00, None),
+            ],
+        }
+    }
+
+    fn dtcs(codes: &[(&str, u8)]) -> SnapshotData {
+        SnapshotData::Dtcs {
+            dtcs: codes
+                .iter()
+                .map(|&(code, status)| DtcEntry {
+                    code: code.into(),
+                    raw_hex: String::new(),
+                    status,
+                    flags: vec![],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_vin() {
+        assert_eq!(normalize_vin(" sajbl4bvxgcy16353 ").as_deref(), Some(VIN));
+        assert_eq!(normalize_vin("SAJBL4BVXGCY1635"), None);
+        assert_eq!(normalize_vin("SAJBL4BVXGCY1635O"), None);
+        assert_eq!(normalize_vin("SAJBL4BVXGCY1635."), None);
+    }
+
+    #[test]
+    fn test_save_history_and_dedup() {
+        let archive = temp_archive("vehicle_archive_test");
+        // Before any VIN is seen, DTCs go to UNKNOWN
+        let unknown = archive.save("BCM", dtcs(&[("B1A2B-01", 0x09)])).unwrap();
+        assert_eq!(unknown.vin, UNKNOWN_VIN);
+
+        let first = archive.save("BCM", scan(VIN, b"SN1")).unwrap();
+        assert_eq!((first.vin.as_str(), first.ecu.as_str()), (VIN, "bcm"));
+        assert_eq!(first.kind, "did_scan");
+        assert_eq!(first.summary, "2/3 DIDs read");
+        // The same read again is not stored twice
+        assert_eq!(archive.save("bcm", scan(VIN, b"SN1")).unwrap(), first);
+        // DTCs now follow the scanned car
+        let dtc = archive.save("bcm", dtcs(&[])).unwrap();
+        assert_eq!(dtc.vin, VIN);
+        let second = archive.save("bcm", scan(VIN, b"SN2")).unwrap();
+        assert_ne!(second.id, first.id);
+
+        let history = archive.history(&VIN.to_lowercase());
+        let ids: Vec<&str> = history.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, [&first.id, &dtc.id, &second.id]);
+        assert_eq!(archive.history("unknown").len(), 1);
+        // Only VINs name a vehicle directory: no way back out of the archive
+        assert!(archive.history(&format!("{}/../{}", VIN, VIN)).is_empty());
+        let vehicles = archive.vehicles();
+        assert_eq!(vehicles.len(), 2);
+        assert_eq!((vehicles[0].vin.as_str(), vehicles[0].snapshots), (VIN, 3));
+        assert_eq!(vehicles[1].vin, UNKNOWN_VIN);
+
+        assert_eq!(archive.load(&second.id).unwrap().data, scan(VIN, b"SN2"));
+        assert!(archive.load("../nope").is_err());
+        assert!(archive.load("nope").is_err());
+
+        archive.forget_vin();
+        assert_eq!(archive.save("bcm", dtcs(&[])).unwrap().vin, UNKNOWN_VIN);
+        archive.note_vin("not a vin");
+        assert_eq!(archive.current_vin(), None);
+        std::fs::remove_dir_all(archive.dir()).unwrap();
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let tables = CcfTables::new(std::env::temp_dir().join("vehicle_archive_no_tables"));
+        let snapshot = |id: &str, data: SnapshotData| Snapshot {
+            id: id.into(),
+            vin: VIN.into(),
+            ecu: "bcm".into(),
+            timestamp: String::new(),
+            data,
+        };
+
+        let a = snapshot("a", scan(VIN, b"SN1"));
+        let mut b = snapshot("b", scan(VIN, b"SN2"));
+        let SnapshotData::DidScan { dids } = &mut b.data else {
+            unreachable!()
+        };
+        dids.retain(|d| d.did != 0x2A00);
+        dids.push(ScannedDid {
+            did: 0x2A01,
+            session: "extended".into(),
+            data: Some(vec![0x01, 0xFF]),
+            error: None,
+        });
+        let diff = diff(&tables, &a, &b).unwrap();
+        assert_eq!(diff.table, None);
+        assert_eq!(
+            diff.changes,
+            [
+                SnapshotChange {
+                    key: "2A01".into(),
+                    label: String::new(),
+                    before: None,
+                    after: Some("01 FF".into()),
+                },
+                SnapshotChange {
+                    key: "F120".into(),
+                    label: "ECU Serial".into(),
+                    before: Some("53 4E 31 \"SN1\"".into()),
+                    after: Some("53 4E 32 \"SN2\"".into()),
+                },
+            ]
+        );
+
+        let before = snapshot("c", dtcs(&[("B1A2B-01", 0x09), ("U0100-00", 0x08)]));
+        let after = snapshot("d", dtcs(&[("U0100-00", 0x09), ("C1234-00", 0x01)]));
+        let changes = super::diff(&tables, &before, &after).unwrap().changes;
+        let summary: Vec<(&str, Option<&str>, Option<&str>)> = changes
+            .iter()
+            .map(|c| (c.key.as_str(), c.before.as_deref(), c.after.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("B1A2B-01", Some("0x09"), None),
+                ("C1234-00", None, Some("0x01")),
+                ("U0100-00", Some("0x08"), Some("0x09")),
+            ]
+        );
+
+        let gwm = Ccf::parse(include_bytes!("../assets/gwm_ccf.bin")).unwrap();
+        let mut edited = gwm.clone();
+        edited.set_field(CcfTable::builtin().field(467), 5).unwrap();
+        let ccf = |id: &str, ccf: &Ccf| {
+            snapshot(
+                id,
+                SnapshotData::Ccf {
+                    source: "DID 0xEE00".into(),
+                    raw_bytes: ccf.to_bytes(),
+                },
+            )
+        };
+        let (e, f) = (ccf("e", &gwm), ccf("f", &edited));
+        assert_eq!(f.data.vin().as_deref(), Some(VIN));
+        let diff = super::diff(&tables, &e, &f).unwrap();
+        assert_eq!(diff.table.as_deref(), Some("X260_201600"));
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].key, "467");
+        assert_eq!(
+            diff.changes[0].after.as_deref(),
+            Some("10_INCH_DUAL_VIEW (0x05)")
+        );
+
+        let err = super::diff(&tables, &a, &before).unwrap_err();
+        assert!(err.contains("did_scan") && err.contains("dtcs"), "{}", err);
+    }
+}
@@ -0,0 +1,1604 @@
+//! Headless front end for the diagnostic operations, for scripting and CI.
+//!
+//! Each invocation connects (J2534 adapter, `--remote` bridge, or `--emulate` for an
+//! emulated bus), runs one operation, prints the result to stdout and disconnects.
+//! UDS traffic and job progress go to stderr with `--verbose`. `bridge` instead
+//! serves the local adapter to a remote helper until interrupted.
+
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use udsapp_lib::broadcast::VehicleState;
+use udsapp_lib::ccf::{CcfTableInfo, RuleFinding};
+use udsapp_lib::commands::{
+    self, CanAnalysisReport, CanDecodeReport, CcfEdit, CcfEditResult, DeviceInfo, EcuInfoEntry,
+};
+use udsapp_lib::ecu_emulator::{load_dump_handlers, stateful, EcuId, EmulatedBus};
+use udsapp_lib::j2534::remote::{self, BridgeChannel, BridgeServer};
+use udsapp_lib::jobs::{JobKind, JobProgress, JobRegistry};
+use udsapp_lib::monitor::{MonitorOptions, MonitorSnapshot, MonitorUpdate};
+use udsapp_lib::replay::ReplayOptions;
+use udsapp_lib::reverse::{AnalysisOptions, CanEvent};
+use udsapp_lib::sink::LogSink;
+use udsapp_lib::sniff::{SniffAction, SniffOptions};
+use udsapp_lib::state::AppState;
+use udsapp_lib::uds::client::LogEntry;
+
+const USAGE: &str = "\
+Usage: udsapp-cli [OPTIONS] <COMMAND> [ARGS]
+
+Commands:
+  devices                        List J2534 devices from the registry
+  routines                       List known IMC routines
+  connect                        Connect and print adapter info
+  info <imc|bcm|gwm|ipc>         Read the ECU info DIDs
+  read-did <ecu|0xTX> <DID>      Read one DID (hex), e.g. read-did bcm F190
+  dtc <ecu> [MASK]               Read DTCs by status mask (hex, default FF)
+  run-routine <RID> [DATA]       Run an IMC routine with the SDD prerequisites
+  scan <bcm|gwm|ipc>             Full DID scan, writes <ecu>_dump.json
+  read-ccf                       Read the IMC CCF (0x0E08 → 0x0E06 → DID)
+  compare-ccf                    Compare CCF options across GWM, BCM and IMC
+  restore-ccf [--sniff]          Run the SDD CCF restore sequence
+  ccf-edit <gwm|bcm> <OPTION=VALUE>... [--write [--force]]
+                                 Preview CCF option changes (by ID or name, value by
+                                 number or label); --write backs up, writes, verifies,
+                                 refusing images that break an error rule unless --force
+  ccf-undo <BACKUP> [--force]    Write a ccf-edit backup back to its ECU
+  ccf-options [QUERY] [--table NAME]
+                                 Search a CCF decode table (default built-in) by
+                                 option ID, name or group
+  ccf-tables                     List the CCF decode tables
+  ccf-rules [FILE]               List the CCF consistency rules, or put those in FILE
+                                 in effect
+  ccf-check                      Check the GWM/BCM CCFs and the VIN against the rules
+  history [VIN]                  List archived vehicles, or the snapshots of one
+  snapshot-diff <FROM> <TO>      What changed between two archived snapshots
+  rollback-ccf <SNAPSHOT> [--force]
+                                 Write an archived GWM/BCM CCF back to its ECU
+  ccf-import <EXML> --vin PREFIX...
+                                 Import an SDD CCF_DATA_*.exml as a decode table
+                                 for VINs starting with PREFIX (at least one)
+  sniff                          CAN sniff around routine 0x6038
+  sniff <ecu|0xTX> <ACTION> [SNIFF OPTIONS]
+                                 Diff the bus before, during and after one request;
+                                 ACTION: routine RID [DATA], write-did DID DATA,
+                                 reset [TYPE], session S or raw HEX
+  replay <FILE> [REPLAY OPTIONS] Replay a CAN capture onto the bus with its timing
+  convert <IN> <OUT> [--format F] Convert a CAN capture (candump, asc, blf, pcap,
+                                 pcapng, dump_txt, sniff_json; default from OUT)
+  decode <DBC> <LOG>             Decode a CAN capture into signals with a DBC
+  analyze <LOG> [--event S=LABEL]... [--ids IDS] [--dbc OUT]
+                                 Find constant bytes, counters, checksums and
+                                 changing fields per ID; --event marks a moment
+                                 (seconds into the log), --dbc writes a draft DBC
+  monitor [SECONDS] [--save F]   Live CAN statistics per ID (default 10 s); --save
+                                 writes the captured frames (format from F)
+  bridge                         Serve the adapter (or --emulate bus) to a remote client
+
+Options:
+  --dll <PATH>          J2534 DLL to load (default: auto-detect)
+  --emulate[=ECUS]      No adapter: emulated bus with ECUS (default imc,bcm,gwm,ipc)
+  --dumps <DIR>         --emulate: replay <ecu>_dump.json scans found in DIR
+  --remote <HOST:PORT>  Use the adapter served by a remote `bridge`
+  --token <TOKEN>       Bridge token (--remote: required; bridge: default random)
+  --bind <ADDR>         bridge: listen address (default 127.0.0.1:7534)
+  --bench[=ECUS]        Enable bench mode emulation for ECUS (default bcm)
+  --faults <SCENARIO>   --bench: fault scenario preset or JSON for the emulated ECUs
+  --vehicle-state <S>   --bench: broadcast ignition state (off, accessory, on, crank)
+  --schedule <FILE>     --bench: DBC broadcast schedule to send instead of the defaults
+  --json                Print results as JSON
+  -v, --verbose         Print UDS traffic and progress to stderr
+  -h, --help            Show this help
+
+Replay options:
+  --speed <X>           Time scale (2 = twice as fast, default 1)
+  --loops <N>           Passes over the trace, 0 = until interrupted (default 1)
+  --include <IDS>       Only replay these CAN IDs (hex, comma-separated)
+  --exclude <IDS>       Never replay these CAN IDs
+
+Sniff options:
+  --baseline <S>        Seconds captured before the request (default 5)
+  --during <S>          Seconds right after the request (default 5)
+  --after <S>           Seconds after that (default 25)
+  --save <FILE>         Write all windows to a capture (format from FILE)";
+
+/// Options that belong to a command and are passed through in its args
+const COMMAND_FLAGS: &[&str] = &[
+    "--sniff",
+    "--speed",
+    "--loops",
+    "--include",
+    "--exclude",
+    "--format",
+    "--save",
+    "--baseline",
+    "--during",
+    "--after",
+    "--event",
+    "--ids",
+    "--dbc",
+    "--write",
+    "--force",
+    "--table",
+    "--vin",
+];
+
+// ─── Arguments ──────────────────────────────────────────────────────
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    dll: Option<String>,
+    emulate: Option<Vec<EcuId>>,
+    dumps: Option<String>,
+    remote: Option<String>,
+    token: Option<String>,
+    bind: Option<String>,
+    bench: Option<Vec<String>>,
+    faults: Option<String>,
+    vehicle_state: Option<String>,
+    schedule: Option<String>,
+    json: bool,
+    verbose: bool,
+    command: String,
+    args: Vec<String>,
+}
+
+fn parse_ecu_list(list: &str) -> Result<Vec<EcuId>, String> {
+    list.split(',')
+        .map(|s| EcuId::from_str(s.trim()).ok_or_else(|| format!("Unknown ECU: {}", s)))
+        .collect()
+}
+
+fn parse_args(argv: &[String]) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut positional = Vec::new();
+    let mut iter = argv.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Err(String::new()),
+            "--json" => opts.json = true,
+            "-v" | "--verbose" => opts.verbose = true,
+            "--dll" => opts.dll = Some(iter.next().ok_or("--dll needs a path")?.clone()),
+            "--dumps" => opts.dumps = Some(iter.next().ok_or("--dumps needs a directory")?.clone()),
+            "--remote" => {
+                opts.remote = Some(iter.next().ok_or("--remote needs HOST:PORT")?.clone())
+            }
+            "--token" => opts.token = Some(iter.next().ok_or("--token needs a value")?.clone()),
+            "--bind" => opts.bind = Some(iter.next().ok_or("--bind needs an address")?.clone()),
+            "--faults" => {
+                opts.faults = Some(iter.next().ok_or("--faults needs a scenario")?.clone())
+            }
+            "--vehicle-state" => {
+                let state = iter.next().ok_or("--vehicle-state needs a state")?;
+                opts.vehicle_state = Some(VehicleState::parse(state)?.name().to_string())
+            }
+            "--schedule" => {
+                opts.schedule = Some(iter.next().ok_or("--schedule needs a file")?.clone())
+            }
+            "--emulate" => opts.emulate = Some(EcuId::all().to_vec()),
+            "--bench" => opts.bench = Some(vec!["bcm".to_string()]),
+            a if a.starts_with("--emulate=") => {
+                opts.emulate = Some(parse_ecu_list(&a["--emulate=".len()..])?)
+            }
+            a if a.starts_with("--bench=") => {
+                let ecus = parse_ecu_list(&a["--bench=".len()..])?;
+                opts.bench = Some(ecus.iter().map(|e| e.name().to_lowercase()).collect());
+            }
+            a if a.starts_with('-') && !COMMAND_FLAGS.contains(&a) => {
+                return Err(format!("Unknown option: {}", a))
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let sources = [
+        opts.dll.is_some(),
+        opts.emulate.is_some(),
+        opts.remote.is_some(),
+    ];
+    if sources.iter().filter(|&&s| s).count() > 1 {
+        return Err("--dll, --emulate and --remote are mutually exclusive".into());
+    }
+    if opts.dumps.is_some() && opts.emulate.is_none() {
+        return Err("--dumps only applies to --emulate".into());
+    }
+    if opts.faults.is_some() && opts.bench.is_none() {
+        return Err("--faults only applies to --bench".into());
+    }
+    if opts.vehicle_state.is_some() && opts.bench.is_none() {
+        return Err("--vehicle-state only applies to --bench".into());
+    }
+    if opts.schedule.is_some() && opts.bench.is_none() {
+        return Err("--schedule only applies to --bench".into());
+    }
+    if opts.remote.is_some() && opts.token.is_none() {
+        return Err("--remote needs --token".into());
+    }
+    let mut positional = positional.into_iter();
+    opts.command = positional.next().ok_or("Missing command")?;
+    opts.args = positional.collect();
+    Ok(opts)
+}
+
+/// Hex number with optional 0x prefix
+fn parse_hex(s: &str) -> Result<u32, String> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).map_err(|_| format!("Invalid hex value: {}", s))
+}
+
+fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, String> {
+    let digits: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let digits = digits.trim_start_matches("0x");
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("Odd number of hex digits: {}", s));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("Invalid hex data: {}", s))
+        })
+        .collect()
+}
+
+/// `replay` args: the capture file and its replay options
+fn parse_replay_args(args: &[String]) -> Result<(String, ReplayOptions), String> {
+    let mut path = None;
+    let mut options = ReplayOptions::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--speed" => {
+                let speed = value("--speed")?;
+                options.speed = speed
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}", speed))?;
+            }
+            "--loops" => {
+                let loops = value("--loops")?;
+                options.loops = loops
+                    .parse()
+                    .map_err(|_| format!("Invalid loop count: {}", loops))?;
+            }
+            "--include" => options.include = parse_id_list(value("--include")?)?,
+            "--exclude" => options.exclude = parse_id_list(value("--exclude")?)?,
+            a if a.starts_with('-') => return Err(format!("replay: unknown option: {}", a)),
+            _ if path.is_some() => return Err(format!("replay: unexpected argument: {}", arg)),
+            _ => path = Some(arg.clone()),
+        }
+    }
+    options.validate()?;
+    Ok((path.ok_or("replay: missing <file>")?, options))
+}
+
+/// `monitor` args: the duration and where to save the frames
+fn parse_monitor_args(args: &[String]) -> Result<(MonitorOptions, Option<String>), String> {
+    let mut options = MonitorOptions {
+        seconds: 10,
+        ..MonitorOptions::default()
+    };
+    let mut save = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--save" => save = Some(iter.next().ok_or("--save needs a value")?.clone()),
+            a if a.starts_with('-') => return Err(format!("monitor: unknown option: {}", a)),
+            seconds => {
+                options.seconds = seconds
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("Invalid duration: {}", seconds))?
+            }
+        }
+    }
+    Ok((options, save))
+}
+
+/// `sniff` args: the target ECU, the action and the windows (None: the 0x6038 sniff)
+fn parse_sniff_args(
+    args: &[String],
+) -> Result<Option<(String, SniffAction, SniffOptions)>, String> {
+    let mut options = SniffOptions::default();
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().ok_or(format!("{} needs a value", name));
+        let mut seconds = |name: &str| -> Result<u32, String> {
+            let s = value(name)?;
+            s.parse().map_err(|_| format!("Invalid duration: {}", s))
+        };
+        match arg.as_str() {
+            "--baseline" => options.baseline_s = seconds("--baseline")?,
+            "--during" => options.during_s = seconds("--during")?,
+            "--after" => options.after_s = seconds("--after")?,
+            "--save" => options.save = Some(value("--save")?.clone()),
+            a if a.starts_with('-') => return Err(format!("sniff: unknown option: {}", a)),
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let Some((&ecu, rest)) = positional.split_first() else {
+        return Ok(None);
+    };
+    let missing = |name: &str| format!("sniff: missing <{}>", name);
+    let action = match rest {
+        ["routine", rid, data @ ..] if data.len() <= 1 => SniffAction::Routine {
+            routine_id: parse_hex(rid)? as u16,
+            data: data
+                .first()
+                .map(|d| parse_hex_bytes(d))
+                .transpose()?
+                .unwrap_or_default(),
+        },
+        ["write-did", did, data] => SniffAction::WriteDid {
+            did: parse_hex(did)? as u16,
+            data: parse_hex_bytes(data)?,
+        },
+        ["reset"] => SniffAction::Reset { reset_type: 0x01 },
+        ["reset", kind] => SniffAction::Reset {
+            reset_type: parse_hex(kind)? as u8,
+        },
+        ["session", session] => SniffAction::Session {
+            session: parse_hex(session)? as u8,
+        },
+        ["raw", request] => SniffAction::Raw {
+            request: parse_hex_bytes(request)?,
+        },
+        [] => return Err(missing("action")),
+        [action, ..] => return Err(format!("sniff: bad arguments for action '{}'", action)),
+    };
+    action.validate()?;
+    options.validate()?;
+    Ok(Some((ecu.to_string(), action, options)))
+}
+
+/// `analyze` args: the log, its events, the options and where to write the draft DBC
+fn parse_analyze_args(
+    args: &[String],
+) -> Result<(String, Vec<CanEvent>, AnalysisOptions, Option<String>), String> {
+    let mut path = None;
+    let mut events = Vec::new();
+    let mut options = AnalysisOptions::default();
+    let mut dbc = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--event" => {
+                let event = value("--event")?;
+                let (time, label) = event
+                    .split_once('=')
+                    .ok_or_else(|| format!("Event '{}' is not SECONDS=LABEL", event))?;
+                let seconds: f64 = time
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|s: &f64| *s >= 0.0)
+                    .ok_or_else(|| format!("Invalid event time: {}", time))?;
+                events.push(CanEvent {
+                    time_ms: (seconds * 1000.0).round() as u64,
+                    label: label.trim().to_string(),
+                });
+            }
+            "--ids" => options.ids = parse_id_list(value("--ids")?)?,
+            "--dbc" => dbc = Some(value("--dbc")?.clone()),
+            a if a.starts_with('-') => return Err(format!("analyze: unknown option: {}", a)),
+            _ if path.is_some() => return Err(format!("analyze: unexpected argument: {}", arg)),
+            _ => path = Some(arg.clone()),
+        }
+    }
+    Ok((path.ok_or("analyze: missing <log>")?, events, options, dbc))
+}
+
+/// `X260_201800  X260 MY2018  412 options  VIN SAJB*`
+fn format_rule_finding(f: &RuleFinding) -> String {
+    format!(
+        "{:<7}  {}: {}\n         {}",
+        f.severity, f.rule, f.detail, f.explanation
+    )
+}
+
+fn format_ccf_table(info: &CcfTableInfo) -> String {
+    let mut text = format!(
+        "{:<16} {} MY{}  {} options",
+        info.name,
+        info.vehicle_line.as_deref().unwrap_or("?"),
+        info.model_year.map_or("?".into(), |y| y.to_string()),
+        info.option_count
+    );
+    for prefix in &info.vin_prefixes {
+        text += &format!("  VIN {}*", prefix);
+    }
+    if info.builtin {
+        text += "  (built-in)";
+    }
+    text
+}
+
+/// `ccf-edit` args: the ECU, its OPTION=VALUE edits and whether to write them
+fn parse_ccf_edit_args(args: &[String]) -> Result<(String, Vec<CcfEdit>, bool, bool), String> {
+    let mut ecu = None;
+    let mut edits = Vec::new();
+    let (mut write, mut force) = (false, false);
+    for arg in args {
+        match arg.as_str() {
+            "--write" => write = true,
+            "--force" => force = true,
+            a if a.starts_with('-') => return Err(format!("ccf-edit: unknown option: {}", a)),
+            _ if ecu.is_none() => ecu = Some(arg.clone()),
+            _ => {
+                let (option, value) = arg
+                    .split_once('=')
+                    .ok_or_else(|| format!("Edit '{}' is not OPTION=VALUE", arg))?;
+                edits.push(CcfEdit {
+                    option: option.trim().to_string(),
+                    value: value.trim().to_string(),
+                });
+            }
+        }
+    }
+    let ecu = ecu.ok_or("ccf-edit: missing <ecu>")?;
+    if edits.is_empty() {
+        return Err("ccf-edit: no OPTION=VALUE edits given".into());
+    }
+    if force && !write {
+        return Err("ccf-edit: --force only applies to --write".into());
+    }
+    Ok((ecu, edits, write, force))
+}
+
+/// `ccf-undo`/`rollback-ccf` args: the one plain word, and whether `--force` was given
+fn parse_write_back_args<'a>(opts: &'a Options, name: &str) -> Result<(&'a str, bool), String> {
+    let force = opts.args.iter().any(|a| a == "--force");
+    let mut words = opts.args.iter().filter(|a| *a != "--force");
+    match (words.next(), words.next()) {
+        (Some(word), None) => Ok((word, force)),
+        (None, _) => Err(format!("{}: missing <{}>", opts.command, name)),
+        (Some(_), Some(extra)) => Err(format!("{}: unexpected argument: {}", opts.command, extra)),
+    }
+}
+
+/// `ccf-options`/`ccf-import` args: the plain words, and the values given to `flag`
+fn parse_ccf_table_args<'a>(
+    command: &str,
+    args: &'a [String],
+    flag: &str,
+) -> Result<(Vec<&'a str>, Vec<&'a str>), String> {
+    let (mut words, mut values) = (Vec::new(), Vec::new());
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            a if a == flag => values.push(
+                iter.next()
+                    .ok_or(format!("{} needs a value", flag))?
+                    .as_str(),
+            ),
+            a if a.starts_with('-') => return Err(format!("{}: unknown option: {}", command, a)),
+            word => words.push(word),
+        }
+    }
+    Ok((words, values))
+}
+
+fn parse_id_list(list: &str) -> Result<Vec<u32>, String> {
+    list.split(',').map(|id| parse_hex(id.trim())).collect()
+}
+
+fn arg<'a>(opts: &'a Options, index: usize, name: &str) -> Result<&'a str, String> {
+    opts.args
+        .get(index)
+        .map(String::as_str)
+        .ok_or_else(|| format!("{}: missing <{}>", opts.command, name))
+}
+
+// ─── Output ─────────────────────────────────────────────────────────
+
+/// Prints UDS traffic and job progress to stderr (only with --verbose)
+struct StderrSink {
+    verbose: bool,
+}
+
+impl LogSink for StderrSink {
+    fn log(&self, entry: LogEntry) {
+        if self.verbose {
+            eprintln!(
+                "{} {:<3} {}{}",
+                entry.timestamp,
+                entry.direction.to_string(),
+                entry.data_hex,
+                if entry.description.is_empty() {
+                    String::new()
+                } else {
+                    format!("  {}", entry.description)
+                }
+            );
+        }
+    }
+
+    fn progress(&self, p: &JobProgress) {
+        if self.verbose {
+            eprintln!("[{:>3}%] {} — {}", p.percent, p.phase, p.step);
+        }
+    }
+
+    fn can_monitor(&self, update: &MonitorUpdate) {
+        if self.verbose {
+            eprintln!("{}", format_monitor(&update.snapshot));
+        }
+    }
+}
+
+/// Result of one command: JSON value plus its human-readable rendering
+struct Output {
+    json: serde_json::Value,
+    text: String,
+}
+
+impl Output {
+    fn new<T: Serialize>(value: &T, text: String) -> Result<Self, String> {
+        Ok(Self {
+            json: serde_json::to_value(value).map_err(|e| e.to_string())?,
+            text,
+        })
+    }
+}
+
+fn format_entries(entries: &[EcuInfoEntry]) -> String {
+    let width = entries.iter().map(|e| e.label.len()).max().unwrap_or(0);
+    entries
+        .iter()
+        .map(|e| {
+            let value = match (&e.value, &e.error) {
+                (Some(v), _) => v.clone(),
+                (None, Some(err)) => format!("<{}>", err),
+                (None, None) => "-".to_string(),
+            };
+            format!(
+                "{:<width$}  {:>4}  {}",
+                e.label,
+                e.did_hex,
+                value,
+                width = width
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_device(info: &DeviceInfo) -> String {
+    format!(
+        "Connected: {}\nFirmware: {}\nDLL: {}\nAPI: {}",
+        info.dll_path, info.firmware_version, info.dll_version, info.api_version
+    )
+}
+
+/// One block per ID: its shape, then each finding on an indented line
+fn format_analysis(report: &CanAnalysisReport) -> String {
+    let mut lines = Vec::new();
+    for id in &report.analysis.ids {
+        let period = id.period_us.map_or("-".to_string(), |us| {
+            format!("{:.1} ms", us as f64 / 1000.0)
+        });
+        lines.push(format!(
+            "0x{:03X}  {} bytes  {} frames  {}",
+            id.can_id, id.dlc, id.frames, period
+        ));
+        if !id.constant.is_empty() {
+            let constant: Vec<String> = id
+                .constant
+                .iter()
+                .map(|c| format!("B{}=0x{:02X}", c.index, c.value))
+                .collect();
+            lines.push(format!("    constant  {}", constant.join(" ")));
+        }
+        for c in &id.counters {
+            lines.push(format!(
+                "    counter   B{} bits {}-{} ({}..{})",
+                c.byte,
+                c.bit,
+                c.bit + c.length - 1,
+                c.min,
+                c.max
+            ));
+        }
+        for c in &id.checksums {
+            lines.push(format!(
+                "    checksum  B{} = {} of bytes {}..{} ({}%)",
+                c.byte,
+                c.kind.name(),
+                c.from,
+                c.to,
+                c.match_pct
+            ));
+        }
+        for f in &id.fields {
+            lines.push(format!(
+                "    field     {}|{}  {} values, {} changes",
+                f.start_bit, f.length, f.distinct, f.changes
+            ));
+        }
+        for e in &id.events {
+            let mut what: Vec<String> = e
+                .fields
+                .iter()
+                .map(|s| {
+                    format!(
+                        "{}|{} {:?} -> {:?}",
+                        s.start_bit, s.length, s.before, s.after
+                    )
+                })
+                .collect();
+            if e.started {
+                what.push("starts".into());
+            }
+            if e.stopped {
+                what.push("stops".into());
+            }
+            lines.push(format!(
+                "    event     '{}' @ {} ms: {}",
+                e.event,
+                e.time_ms,
+                what.join(", ")
+            ));
+        }
+    }
+    match &report.dbc_path {
+        Some(path) => lines.push(format!("Draft DBC written to {}", path)),
+        None => lines.push("Draft DBC: use --dbc <OUT> or --json".into()),
+    }
+    lines.join("\n")
+}
+
+/// `12.345678 0x230 IgnitionStatus  IgnitionOn=1 (On)  Supply=12.6 V`
+fn format_decoded(report: &CanDecodeReport) -> String {
+    let mut lines: Vec<String> = report
+        .messages
+        .iter()
+        .map(|m| {
+            let signals = m
+                .signals
+                .iter()
+                .map(|s| {
+                    let mut text = format!("{}={}", s.name, s.value);
+                    if !s.unit.is_empty() {
+                        text.push_str(&format!(" {}", s.unit));
+                    }
+                    if let Some(label) = &s.label {
+                        text.push_str(&format!(" ({})", label));
+                    }
+                    text
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+            format!(
+                "{}.{:06} 0x{:03X} {}  {}",
+                m.timestamp_us / 1_000_000,
+                m.timestamp_us % 1_000_000,
+                m.can_id,
+                m.message,
+                signals
+            )
+        })
+        .collect();
+    lines.push(format!(
+        "{} of {} frames decoded; not in the DBC: {}",
+        report.decoded,
+        report.frames,
+        if report.unknown_ids.is_empty() {
+            "-".to_string()
+        } else {
+            report.unknown_ids.join(" ")
+        }
+    ));
+    lines.join("\n")
+}
+
+/// Header line plus one row per ID; bytes changed in the last second are marked `*`
+fn format_monitor(snapshot: &MonitorSnapshot) -> String {
+    let mut lines = vec![format!(
+        "{:.1} s  {} frames  {} IDs  {} frames/s  bus load {:.1}%",
+        snapshot.elapsed_ms as f64 / 1000.0,
+        snapshot.frames,
+        snapshot.ids.len(),
+        snapshot.frames_per_sec,
+        snapshot.bus_load_pct
+    )];
+    let ms =
+        |us: Option<u64>| us.map_or("-".to_string(), |us| format!("{:.1}", us as f64 / 1000.0));
+    for id in &snapshot.ids {
+        let data = id
+            .data
+            .iter()
+            .zip(&id.changed)
+            .map(|(b, changed)| format!("{:02X}{}", b, if *changed { "*" } else { " " }))
+            .collect::<String>();
+        lines.push(format!(
+            "0x{:03X}  {:>7}  {:>7} ms  ±{:>5} ms  {}",
+            id.can_id,
+            id.count,
+            ms(id.period_us),
+            ms(id.jitter_us),
+            data.trim_end()
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Summary, the exchange and one line per differing ID of a `sniff_action` report
+fn format_ccf_edit(result: &CcfEditResult) -> String {
+    let mut lines = vec![format!(
+        "{} CCF (DID 0x{:04X}, VIN {}): {} option(s) changed, CRC 0x{:04X} → 0x{:04X}",
+        result.ecu,
+        result.did,
+        result.vin.as_deref().unwrap_or("?"),
+        result.changes.len(),
+        result.checksum_before,
+        result.checksum_after
+    )];
+    let width = result
+        .changes
+        .iter()
+        .map(|c| c.name.len())
+        .max()
+        .unwrap_or(0);
+    for c in &result.changes {
+        lines.push(format!(
+            "  {:>4}  {:<width$}  {} → {}",
+            c.option_id,
+            c.name,
+            c.before,
+            c.after,
+            width = width
+        ));
+    }
+    lines.push(match (&result.backup, result.written) {
+        (Some(backup), true) => format!("Written and verified; undo with: ccf-undo {}", backup),
+        _ if result.changes.is_empty() => "Nothing to write".to_string(),
+        _ => "Preview only; add --write to apply".to_string(),
+    });
+    lines.join("\n")
+}
+
+fn format_sniff(report: &serde_json::Value) -> String {
+    let exchange = &report["exchange"];
+    let mut lines = vec![
+        report["summary"].as_str().unwrap_or_default().to_string(),
+        match exchange["response"].as_str() {
+            Some(resp) => format!(
+                "{} → {}",
+                exchange["request"].as_str().unwrap_or_default(),
+                resp
+            ),
+            None => format!(
+                "{} → {}",
+                exchange["request"].as_str().unwrap_or_default(),
+                exchange["error"].as_str().unwrap_or("no response")
+            ),
+        },
+    ];
+    let ms = |us: &serde_json::Value| {
+        us.as_u64()
+            .map_or("-".to_string(), |us| format!("{:.1}", us as f64 / 1000.0))
+    };
+    for id in report["ids"].as_array().into_iter().flatten() {
+        let bytes = id["bytes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|b| {
+                let new: Vec<u8> = b["new_values"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|v| v.as_u64().map(|v| v as u8))
+                    .collect();
+                if new.is_empty() {
+                    format!("  B{} frozen", b["index"])
+                } else {
+                    format!("  B{} +{}", b["index"], hex(&new))
+                }
+            })
+            .collect::<String>();
+        lines.push(format!(
+            "0x{:03X}  {:<8}  {:>5} {:>5} {:>5} frames  {:>7} {:>7} {:>7} ms{}",
+            id["can_id"].as_u64().unwrap_or_default(),
+            id["change"].as_str().unwrap_or_default(),
+            id["baseline_count"],
+            id["during_count"],
+            id["after_count"],
+            ms(&id["baseline_period_us"]),
+            ms(&id["during_period_us"]),
+            ms(&id["after_period_us"]),
+            bytes
+        ));
+    }
+    lines.join("\n")
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// ─── Commands ───────────────────────────────────────────────────────
+
+fn connect(sink: &dyn LogSink, state: &AppState, opts: &Options) -> Result<DeviceInfo, String> {
+    let info = match (&opts.emulate, &opts.remote) {
+        (Some(ecus), _) => {
+            let dir = opts.dumps.as_deref().map(std::path::Path::new);
+            commands::connect_emulated_inner(sink, state, ecus, dir)?
+        }
+        (None, Some(addr)) => commands::connect_remote_inner(
+            sink,
+            state,
+            addr,
+            opts.token.as_deref().unwrap_or_default(),
+        )?,
+        (None, None) => commands::connect_inner(sink, state, opts.dll.clone())?,
+    };
+    if let Some(ecus) = &opts.bench {
+        let (faults, schedule) = (opts.faults.as_deref(), opts.schedule.as_deref());
+        commands::toggle_bench_mode_inner(sink, state, true, Some(ecus.clone()), faults, schedule)?;
+        if let Some(vehicle_state) = &opts.vehicle_state {
+            commands::set_vehicle_state_inner(sink, state, vehicle_state)?;
+        }
+    }
+    Ok(info)
+}
+
+/// Run a long operation as a local job so its progress reaches the sink
+fn run_job(
+    sink: &dyn LogSink,
+    state: &AppState,
+    kind: JobKind,
+) -> Result<serde_json::Value, String> {
+    let registry = Arc::new(JobRegistry::new());
+    let job = registry.create(&kind);
+    let outcome = commands::run_job(sink, state, &kind, &job);
+    job.finish(sink, outcome.clone());
+    outcome
+}
+
+/// Serve the adapter (or an emulated bus) until the process is killed
+fn bridge(sink: &dyn LogSink, opts: &Options) -> Result<Output, String> {
+    if opts.remote.is_some() {
+        return Err("bridge: --remote makes no sense here".into());
+    }
+    // Device and library must outlive the channel the server owns
+    let mut _adapter = None;
+    let channel: Box<dyn BridgeChannel> = match &opts.emulate {
+        Some(ecus) => {
+            let mut bus = EmulatedBus::new(ecus);
+            if let Some(dir) = &opts.dumps {
+                for (ecu, handler) in load_dump_handlers(std::path::Path::new(dir), ecus)? {
+                    eprintln!(
+                        "{}: replaying {}_dump.json",
+                        ecu.name(),
+                        ecu.name().to_lowercase()
+                    );
+                    bus = bus.with_handler(ecu, stateful(ecu, Box::new(handler)));
+                }
+            }
+            Box::new(bus)
+        }
+        None => {
+            let (lib, device, path) = commands::open_device(sink, opts.dll.clone())?;
+            let channel = device.connect_iso15765(500000)?;
+            let _ = channel.set_iso15765_config(0, 0, 0);
+            eprintln!("Serving {}", path);
+            _adapter = Some((lib, device));
+            Box::new(channel)
+        }
+    };
+    let token = opts
+        .token
+        .clone()
+        .unwrap_or_else(udsapp_lib::api::generate_token);
+    let bind = opts
+        .bind
+        .clone()
+        .unwrap_or_else(|| format!("{}:{}", remote::DEFAULT_BIND, remote::DEFAULT_PORT));
+    let server = BridgeServer::start(channel, &bind, token.clone())?;
+
+    println!("Bridge listening on {}", server.addr());
+    println!("Token: {}", token);
+    println!(
+        "Connect with: udsapp-cli --remote <this-host>:{} --token {} <COMMAND>",
+        server.addr().port(),
+        token
+    );
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs(3600));
+    }
+}
+
+fn execute(sink: &dyn LogSink, state: &AppState, opts: &Options) -> Result<Output, String> {
+    // Commands that don't need a connection
+    match opts.command.as_str() {
+        "devices" => {
+            let devices = commands::discover_devices();
+            let text = devices
+                .iter()
+                .map(|d| format!("{}  {}", d.name, d.dll_path))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Output::new(&devices, text);
+        }
+        "routines" => {
+            let routines = commands::list_routines();
+            let text = routines
+                .iter()
+                .map(|r| format!("0x{:04X}  {:<28} {}", r.routine_id, r.name, r.description))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Output::new(&routines, text);
+        }
+        "ccf-options" => {
+            let (words, tables) = parse_ccf_table_args("ccf-options", &opts.args, "--table")?;
+            let query = words.join(" ");
+            let options =
+                commands::search_ccf_options_inner(state, Some(&query), tables.last().copied())?;
+            let text = options
+                .iter()
+                .map(|o| {
+                    let values: Vec<String> = o
+                        .values
+                        .iter()
+                        .map(|(v, l)| format!("{}={}", v, l))
+                        .collect();
+                    format!(
+                        "{:>4}  {:<32} byte {:>3} bits {}..{}  {}",
+                        o.id,
+                        o.name,
+                        o.field.byte,
+                        o.field.bit,
+                        o.field.bit + o.field.width - 1,
+                        values.join(", ")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Output::new(&options, text);
+        }
+        "ccf-tables" => {
+            let tables = state.ccf_tables.list();
+            let text = tables
+                .iter()
+                .map(format_ccf_table)
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Output::new(&tables, text);
+        }
+        "ccf-import" => {
+            let (files, prefixes) = parse_ccf_table_args("ccf-import", &opts.args, "--vin")?;
+            let [path] = files[..] else {
+                return Err("ccf-import: expected one <EXML> file".into());
+            };
+            let prefixes = prefixes.iter().map(|p| p.to_string()).collect();
+            let info = commands::import_ccf_exml_inner(state, path, prefixes)?;
+            let text = format!("Imported {}", format_ccf_table(&info));
+            return Output::new(&info, text);
+        }
+        "ccf-rules" => {
+            let rules = match &opts.args[..] {
+                [] => (*state.ccf_rules.current()).clone(),
+                [path] => commands::import_ccf_rules_inner(state, path)?,
+                _ => return Err("ccf-rules: expected at most one <FILE>".into()),
+            };
+            let mut text = format!("Rules: {}", rules.source);
+            for rule in &rules.rules {
+                text += &format!(
+                    "\n{:<7}  {:<28} {}",
+                    rule.severity, rule.id, rule.description
+                );
+            }
+            return Output::new(&rules, text);
+        }
+        "history" => {
+            let Some(vin) = opts.args.first() else {
+                let vehicles = state.archive.vehicles();
+                let text = vehicles
+                    .iter()
+                    .map(|v| {
+                        format!(
+                            "{:<17}  {:>4} snapshots  {} .. {}",
+                            v.vin, v.snapshots, v.first_seen, v.last_seen
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                return Output::new(&vehicles, text);
+            };
+            let history = state.archive.history(vin);
+            let text = history
+                .iter()
+                .map(|s| format!("{}  {:<4} {}", s.id, s.ecu.to_uppercase(), s.summary))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Output::new(&history, text);
+        }
+        "snapshot-diff" => {
+            let diff =
+                commands::diff_snapshots_inner(state, arg(opts, 0, "from")?, arg(opts, 1, "to")?)?;
+            let mut lines = vec![format!(
+                "{} → {}{}: {} changes",
+                diff.from.id,
+                diff.to.id,
+                diff.table
+                    .as_ref()
+                    .map(|t| format!(" (table {})", t))
+                    .unwrap_or_default(),
+                diff.changes.len()
+            )];
+            lines.extend(diff.changes.iter().map(|c| {
+                format!(
+                    "  {:>8}  {:<28} {} → {}",
+                    c.key,
+                    c.label,
+                    c.before.as_deref().unwrap_or("-"),
+                    c.after.as_deref().unwrap_or("-")
+                )
+            }));
+            return Output::new(&diff, lines.join("\n"));
+        }
+        "bridge" => return bridge(sink, opts),
+        "convert" => {
+            let format = opts
+                .args
+                .iter()
+                .position(|a| a == "--format")
+                .map(|i| {
+                    opts.args
+                        .get(i + 1)
+                        .map(String::as_str)
+                        .ok_or("--format needs a value")
+                })
+                .transpose()?;
+            let files: Vec<&String> = opts
+                .args
+                .iter()
+                .enumerate()
+                .filter(|(i, a)| *a != "--format" && (*i == 0 || opts.args[i - 1] != "--format"))
+                .map(|(_, a)| a)
+                .collect();
+            let [input, output] = files[..] else {
+                return Err("convert: expected <IN> <OUT>".into());
+            };
+            let info = commands::convert_can_log_inner(sink, input, output, format)?;
+            let text = format!(
+                "{} frames ({} IDs, {} ms) written to {} as {}",
+                info.frames,
+                info.unique_ids,
+                info.duration_ms,
+                info.path,
+                info.format.name()
+            );
+            return Output::new(&info, text);
+        }
+        "decode" => {
+            let report =
+                commands::decode_can_log_inner(sink, arg(opts, 0, "dbc")?, arg(opts, 1, "log")?)?;
+            return Output::new(&report, format_decoded(&report));
+        }
+        "analyze" => {
+            let (log, events, options, dbc) = parse_analyze_args(&opts.args)?;
+            let report =
+                commands::analyze_can_log_inner(sink, &log, &events, &options, dbc.as_deref())?;
+            return Output::new(&report, format_analysis(&report));
+        }
+        _ => {}
+    }
+
+    let device = connect(sink, state, opts)?;
+
+    match opts.command.as_str() {
+        "connect" => Output::new(&device, format_device(&device)),
+        "info" => {
+            let entries =
+                commands::read_ecu_info_inner(sink, state, &arg(opts, 0, "ecu")?.to_lowercase())?;
+            Output::new(&entries, format_entries(&entries))
+        }
+        "read-did" => {
+            let ecu = arg(opts, 0, "ecu")?;
+            let tx_id = match commands::ecu_ids_by_name(ecu) {
+                Some((tx, _)) => tx,
+                None => parse_hex(ecu)?,
+            };
+            let did = parse_hex(arg(opts, 1, "did")?)? as u16;
+            let data = commands::read_did_inner(sink, state, tx_id, did)?;
+            let ascii: String = data
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            Output::new(&data, format!("{}\n{}", hex(&data), ascii))
+        }
+        "dtc" => {
+            let ecu = arg(opts, 0, "ecu")?;
+            let mask = match opts.args.get(1) {
+                Some(m) => parse_hex(m)? as u8,
+                None => 0xFF,
+            };
+            let dtcs = commands::read_dtc_inner(sink, state, ecu, mask)?;
+            let text = if dtcs.is_empty() {
+                "No DTCs".to_string()
+            } else {
+                dtcs.iter()
+                    .map(|d| {
+                        format!(
+                            "{}  status 0x{:02X}  {}",
+                            d.code,
+                            d.status,
+                            d.flags.join(", ")
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            Output::new(&dtcs, text)
+        }
+        "run-routine" => {
+            let rid = parse_hex(arg(opts, 0, "rid")?)? as u16;
+            let data = match opts.args.get(1) {
+                Some(d) => parse_hex_bytes(d)?,
+                None => Vec::new(),
+            };
+            let resp = commands::run_routine_inner(sink, state, rid, &data)?;
+            let text = format!(
+                "{}: {}\nRaw: {}",
+                if resp.success { "OK" } else { "FAILED" },
+                resp.description,
+                hex(&resp.raw_data)
+            );
+            Output::new(&resp, text)
+        }
+        "scan" => {
+            let kind = match arg(opts, 0, "ecu")?.to_lowercase().as_str() {
+                "bcm" => JobKind::ScanBcm,
+                "gwm" => JobKind::ScanGwm,
+                "ipc" => JobKind::ScanIpc,
+                other => return Err(format!("scan: unsupported ECU: {}", other)),
+            };
+            let value = run_job(sink, state, kind)?;
+            let text = value.as_str().unwrap_or_default().to_string();
+            Ok(Output { json: value, text })
+        }
+        "read-ccf" => {
+            let entries = commands::read_ccf_inner(sink, state)?;
+            Output::new(&entries, format_entries(&entries))
+        }
+        "compare-ccf" => {
+            let entries = commands::compare_ccf_inner(sink, state)?;
+            let text = entries
+                .iter()
+                .map(|e| {
+                    format!(
+                        "{} {:>4}  {:<32} GWM={:<10} BCM={:<10} IMC={}",
+                        if e.mismatch { "!" } else { " " },
+                        e.option_id,
+                        e.name,
+                        e.gwm.as_deref().unwrap_or("-"),
+                        e.bcm.as_deref().unwrap_or("-"),
+                        e.imc.as_deref().unwrap_or("-")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut findings: Vec<&RuleFinding> = Vec::new();
+            for f in entries.iter().flat_map(|e| &e.findings) {
+                if !findings.iter().any(|seen| seen.rule == f.rule) {
+                    findings.push(f);
+                }
+            }
+            let text = std::iter::once(text)
+                .chain(findings.into_iter().map(format_rule_finding))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Output::new(&entries, text)
+        }
+        "ccf-check" => {
+            let check = commands::check_ccf_inner(sink, state)?;
+            let report = &check.report;
+            let summary = format!(
+                "{} broken, {} passed, {} skipped ({} rules, table {})",
+                report.findings.len(),
+                report.passed,
+                report.skipped.len(),
+                check.rules,
+                check.table
+            );
+            let text = report
+                .findings
+                .iter()
+                .map(format_rule_finding)
+                .chain(std::iter::once(summary))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Output::new(&check, text)
+        }
+        "restore-ccf" => {
+            let sniff = opts.args.iter().any(|a| a == "--sniff");
+            let value = run_job(sink, state, JobKind::RestoreCcf { sniff })?;
+            let text = value["steps"]
+                .as_array()
+                .map(|steps| {
+                    steps
+                        .iter()
+                        .map(|s| {
+                            format!(
+                                "[{}] {}  {}",
+                                if s["success"].as_bool() == Some(true) {
+                                    "ok"
+                                } else {
+                                    "!!"
+                                },
+                                s["name"].as_str().unwrap_or_default(),
+                                s["detail"].as_str().unwrap_or_default()
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+            Ok(Output { json: value, text })
+        }
+        "ccf-edit" => {
+            let (ecu, edits, write, force) = parse_ccf_edit_args(&opts.args)?;
+            let result = if write {
+                commands::write_ccf_edit_inner(sink, state, &ecu, &edits, force)?
+            } else {
+                commands::preview_ccf_edit_inner(sink, state, &ecu, &edits)?
+            };
+            Output::new(&result, format_ccf_edit(&result))
+        }
+        "ccf-undo" => {
+            let (backup, force) = parse_write_back_args(opts, "backup")?;
+            let result = commands::undo_ccf_edit_inner(sink, state, backup, force)?;
+            Output::new(&result, format_ccf_edit(&result))
+        }
+        "rollback-ccf" => {
+            let (snapshot, force) = parse_write_back_args(opts, "snapshot")?;
+            let result = commands::rollback_ccf_inner(sink, state, snapshot, force)?;
+            Output::new(&result, format_ccf_edit(&result))
+        }
+        "sniff" => {
+            let (kind, generic) = match parse_sniff_args(&opts.args)? {
+                Some((ecu, action, options)) => (
+                    JobKind::SniffAction {
+                        ecu,
+                        action,
+                        options,
+                    },
+                    true,
+                ),
+                None => (JobKind::CanSniffRoutine, false),
+            };
+            let value = run_job(sink, state, kind)?;
+            let text = if generic {
+                format_sniff(&value)
+            } else {
+                value["summary"].as_str().unwrap_or_default().to_string()
+            };
+            Ok(Output { json: value, text })
+        }
+        "replay" => {
+            let (path, options) = parse_replay_args(&opts.args)?;
+            let value = run_job(sink, state, JobKind::ReplayCan { path, options })?;
+            let text = format!(
+                "{} frames sent ({} errors), {} loop(s) in {} ms{}\n\
+                 Jitter: mean {} µs, p95 {} µs, max {} µs",
+                value["frames_sent"],
+                value["send_errors"],
+                value["loops_completed"],
+                value["duration_ms"],
+                if value["cancelled"] == true {
+                    " (cancelled)"
+                } else {
+                    ""
+                },
+                value["jitter"]["mean_us"],
+                value["jitter"]["p95_us"],
+                value["jitter"]["max_us"]
+            );
+            Ok(Output { json: value, text })
+        }
+        "monitor" => {
+            let (options, save) = parse_monitor_args(&opts.args)?;
+            run_job(sink, state, JobKind::MonitorCan { options })?;
+            let snapshot = commands::get_can_monitor_inner(state).ok_or("No monitor result")?;
+            let mut text = format_monitor(&snapshot);
+            if let Some(path) = save {
+                let info = commands::save_can_monitor_inner(sink, state, &path, None)?;
+                text.push_str(&format!(
+                    "\n{} frames saved to {} ({})",
+                    info.frames,
+                    info.path,
+                    info.format.name()
+                ));
+            }
+            Output::new(&snapshot, text)
+        }
+        other => Err(format!("Unknown command: {}", other)),
+    }
+}
+
+fn main() -> ExitCode {
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    let opts = match parse_args(&argv) {
+        Ok(opts) => opts,
+        Err(e) if e.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    // Library logging stays quiet unless RUST_LOG asks for it; --verbose shows UDS traffic
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
+        .format_timestamp_millis()
+        .init();
+
+    let sink = StderrSink {
+        verbose: opts.verbose,
+    };
+    let state = AppState::new();
+    state.set_background_sink(Arc::new(StderrSink {
+        verbose: opts.verbose,
+    }));
+    let result = execute(&sink, &state, &opts);
+    let _ = commands::disconnect_inner(&sink, &state);
+
+    match result {
+        Ok(out) if opts.json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&out.json).unwrap_or_default()
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(out) => {
+            println!("{}", out.text);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            if opts.json {
+                println!("{}", serde_json::json!({ "error": e }));
+            }
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args_options_and_command() {
+        let opts = parse_args(&args(&[
+            "--json",
+            "--emulate=bcm,gwm",
+            "read-did",
+            "bcm",
+            "F190",
+        ]))
+        .unwrap();
+        assert!(opts.json);
+        assert_eq!(opts.emulate, Some(vec![EcuId::Bcm, EcuId::Gwm]));
+        assert_eq!(opts.command, "read-did");
+        assert_eq!(opts.args, vec!["bcm", "F190"]);
+
+        let opts = parse_args(&args(&[
+            "restore-ccf",
+            "--sniff",
+            "--bench",
+            "--faults",
+            "busy",
+            "--vehicle-state",
+            "ACC",
+            "--schedule",
+            "bench.json",
+        ]))
+        .unwrap();
+        assert_eq!(opts.args, vec!["--sniff"]);
+        assert_eq!(opts.bench, Some(vec!["bcm".to_string()]));
+        assert_eq!(opts.faults.as_deref(), Some("busy"));
+        assert_eq!(opts.vehicle_state.as_deref(), Some("accessory"));
+        assert_eq!(opts.schedule.as_deref(), Some("bench.json"));
+    }
+
+    #[test]
+    fn test_parse_replay_args() {
+        let opts = parse_args(&args(&[
+            "replay",
+            "can_dump.txt",
+            "--speed",
+            "0.5",
+            "--loops",
+            "0",
+            "--exclude",
+            "0x400, 407",
+        ]))
+        .unwrap();
+        let (path, options) = parse_replay_args(&opts.args).unwrap();
+        assert_eq!(path, "can_dump.txt");
+        assert_eq!(options.speed, 0.5);
+        assert_eq!(options.loops, 0);
+        assert_eq!(options.exclude, vec![0x400, 0x407]);
+        assert!(options.include.is_empty());
+
+        let opts = parse_args(&args(&["convert", "a.txt", "--format", "pcap", "b.out"])).unwrap();
+        assert_eq!(opts.args, vec!["a.txt", "--format", "pcap", "b.out"]);
+
+        let (options, save) = parse_monitor_args(&args(&["30", "--save", "bus.blf"])).unwrap();
+        assert_eq!((options.seconds, save.as_deref()), (30, Some("bus.blf")));
+        assert_eq!(parse_monitor_args(&args(&[])).unwrap().0.seconds, 10);
+        assert!(parse_monitor_args(&args(&["0"])).is_err());
+
+        assert!(parse_replay_args(&args(&[])).is_err());
+        assert!(parse_replay_args(&args(&["a.txt", "--speed", "0"])).is_err());
+        assert!(parse_replay_args(&args(&["a.txt", "--loops"])).is_err());
+        assert!(parse_replay_args(&args(&["a.txt", "b.txt"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(parse_args(&args(&[])).is_err());
+        assert!(parse_args(&args(&["--bogus", "info"])).is_err());
+        assert!(parse_args(&args(&["--emulate=xyz", "info"])).is_err());
+        assert!(parse_args(&args(&["--dll", "a.dll", "--emulate", "info"])).is_err());
+        assert!(parse_args(&args(&["--remote", "host:7534", "info"])).is_err());
+        assert!(parse_args(&args(&["--dumps", ".", "info"])).is_err());
+        assert!(parse_args(&args(&["--faults", "busy", "info"])).is_err());
+        assert!(parse_args(&args(&["--bench", "--vehicle-state", "run", "info"])).is_err());
+        assert!(parse_args(&args(&["--vehicle-state", "on", "info"])).is_err());
+        assert!(parse_args(&args(&["--schedule", "bench.json", "info"])).is_err());
+        assert!(parse_args(&args(&[
+            "--remote",
+            "h:1",
+            "--emulate",
+            "--token",
+            "t",
+            "info"
+        ]))
+        .is_err());
+        assert_eq!(parse_args(&args(&["--help"])), Err(String::new()));
+    }
+
+    #[test]
+    fn test_parse_sniff_args() {
+        assert_eq!(parse_sniff_args(&[]), Ok(None));
+        let (ecu, action, options) = parse_sniff_args(&args(&[
+            "imc", "routine", "6038", "--during", "10", "--save", "out.asc",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(ecu, "imc");
+        assert_eq!(action.request(), vec![0x31, 0x01, 0x60, 0x38]);
+        assert_eq!((options.baseline_s, options.during_s), (5, 10));
+        assert_eq!(options.save.as_deref(), Some("out.asc"));
+        let (_, action, _) = parse_sniff_args(&args(&["0x726", "write-did", "F190", "0102"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(action.request(), vec![0x2E, 0xF1, 0x90, 0x01, 0x02]);
+        assert!(parse_sniff_args(&args(&["bcm"])).is_err());
+        assert!(parse_sniff_args(&args(&["bcm", "session"])).is_err());
+        assert!(parse_sniff_args(&args(&["bcm", "reset", "--baseline", "0"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_ccf_edit_args() {
+        let (ecu, edits, write, force) = parse_ccf_edit_args(&args(&[
+            "gwm",
+            "Front Display Variant = 5",
+            "468=2",
+            "--write",
+        ]))
+        .unwrap();
+        assert_eq!(ecu, "gwm");
+        assert_eq!(
+            edits
+                .iter()
+                .map(|e| (e.option.as_str(), e.value.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("Front Display Variant", "5"), ("468", "2")]
+        );
+        assert!(write && !force);
+        assert!(!parse_ccf_edit_args(&args(&["bcm", "1=3"])).unwrap().2);
+        let forced = parse_ccf_edit_args(&args(&["bcm", "1=3", "--write", "--force"])).unwrap();
+        assert!(forced.3);
+        assert!(parse_ccf_edit_args(&args(&["bcm", "1=3", "--force"])).is_err());
+        assert!(parse_ccf_edit_args(&args(&["gwm"])).is_err());
+        assert!(parse_ccf_edit_args(&args(&["gwm", "467"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_ccf_table_args() {
+        let a = args(&["front", "display", "--table", "X761_201800"]);
+        let (words, tables) = parse_ccf_table_args("ccf-options", &a, "--table").unwrap();
+        assert_eq!(
+            (words, tables),
+            (vec!["front", "display"], vec!["X761_201800"])
+        );
+        let a = args(&[
+            "CCF_DATA_X761_201800.exml",
+            "--vin",
+            "SADC",
+            "--vin",
+            "SADF",
+        ]);
+        let (files, prefixes) = parse_ccf_table_args("ccf-import", &a, "--vin").unwrap();
+        assert_eq!((files.len(), prefixes), (1, vec!["SADC", "SADF"]));
+        assert!(parse_ccf_table_args("ccf-import", &args(&["x", "--vin"]), "--vin").is_err());
+        assert!(parse_ccf_table_args("ccf-options", &args(&["--write"]), "--table").is_err());
+    }
+
+    #[test]
+    fn test_parse_analyze_args() {
+        let (log, events, options, dbc) = parse_analyze_args(&args(&[
+            "capture.asc",
+            "--event",
+            "12.5=ignition on",
+            "--ids",
+            "070,0x1D0",
+            "--dbc",
+            "draft.dbc",
+        ]))
+        .unwrap();
+        assert_eq!(log, "capture.asc");
+        assert_eq!(
+            events,
+            vec![CanEvent {
+                time_ms: 12_500,
+                label: "ignition on".into()
+            }]
+        );
+        assert_eq!(options.ids, vec![0x070, 0x1D0]);
+        assert_eq!(dbc.as_deref(), Some("draft.dbc"));
+        assert!(parse_analyze_args(&args(&[])).is_err());
+        assert!(parse_analyze_args(&args(&["a.log", "--event", "soon"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("0x7B3"), Ok(0x7B3));
+        assert_eq!(parse_hex("F190"), Ok(0xF190));
+        assert!(parse_hex("zz").is_err());
+        assert_eq!(parse_hex_bytes("01 0A"), Ok(vec![0x01, 0x0A]));
+        assert!(parse_hex_bytes("123").is_err());
+    }
+
+    #[test]
+    fn test_execute_against_emulated_bus() {
+        let sink = StderrSink { verbose: false };
+        let state = AppState::new();
+        let opts = parse_args(&args(&["--emulate", "read-did", "bcm", "F190"])).unwrap();
+        let out = execute(&sink, &state, &opts).unwrap();
+        assert!(out.text.ends_with("SAJBL4BVXGCY16353"));
+
+        commands::disconnect_inner(&sink, &state).unwrap();
+        let opts = parse_args(&args(&["--emulate", "dtc", "gwm"])).unwrap();
+        let out = execute(&sink, &state, &opts).unwrap();
+        assert_eq!(out.text, "No DTCs");
+        assert_eq!(out.json, serde_json::json!([]));
+    }
+}
@@ -0,0 +1,655 @@
+//! Bench-mode CAN broadcast: periodic frames that make a bench IMC believe the
+//! rest of the vehicle is on the bus.
+//!
+//! Every message has its own period, byte-update rules applied on each send
+//! (rolling alive counters, checksums, signals that follow the vehicle state) and
+//! the vehicle states in which it is transmitted. Payloads and state-dependent
+//! signals can also come from DBC signal values (`BroadcastMsg::from_signals`,
+//! `with_signal`), or from a schedule file naming DBC messages (`DbcSchedule`).
+//! `BroadcastScheduler` decides which frames are due; the emulator's broadcast
+//! thread only writes them out.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dbc::{BitField, Dbc, Message, SignalValue};
+
+// ─── Vehicle State ───────────────────────────────────────────────────
+
+/// Ignition position the broadcast simulates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VehicleState {
+    Off,
+    Accessory,
+    On,
+    Crank,
+}
+
+impl VehicleState {
+    pub fn all() -> &'static [VehicleState] {
+        &[
+            VehicleState::Off,
+            VehicleState::Accessory,
+            VehicleState::On,
+            VehicleState::Crank,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VehicleState::Off => "off",
+            VehicleState::Accessory => "accessory",
+            VehicleState::On => "on",
+            VehicleState::Crank => "crank",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Some(VehicleState::Off),
+            "accessory" | "acc" => Some(VehicleState::Accessory),
+            "on" | "ignition" => Some(VehicleState::On),
+            "crank" | "start" => Some(VehicleState::Crank),
+            _ => None,
+        }
+    }
+
+    /// Parse a state name, listing the valid ones on error
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::from_name(s).ok_or_else(|| {
+            let names: Vec<&str> = Self::all().iter().map(|v| v.name()).collect();
+            format!(
+                "Unknown vehicle state '{}' (expected {})",
+                s,
+                names.join(", ")
+            )
+        })
+    }
+}
+
+// ─── Messages ────────────────────────────────────────────────────────
+
+/// Update applied to a frame's bytes on every send, in the order listed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ByteRule {
+    /// Rolling alive counter in the `mask` bits of `byte` (0x0F = low nibble),
+    /// advanced by one per send
+    Counter { byte: usize, mask: u8 },
+    /// XOR of bytes `from..to` written to `byte`
+    Xor { byte: usize, from: usize, to: usize },
+    /// CRC8 (SAE J1850: poly 0x1D, init and final XOR 0xFF) of bytes `from..to`
+    /// written to `byte`
+    Crc8 { byte: usize, from: usize, to: usize },
+    /// `mask` bits of `byte` set per vehicle state; states not listed keep the
+    /// frame's base bits
+    Signal {
+        byte: usize,
+        mask: u8,
+        values: BTreeMap<VehicleState, u8>,
+    },
+    /// Raw bits of a DBC signal set per vehicle state (see `BroadcastMsg::with_signal`)
+    Field {
+        field: BitField,
+        values: BTreeMap<VehicleState, u64>,
+    },
+}
+
+impl ByteRule {
+    fn apply(&self, data: &mut [u8; 8], sent: u32, state: VehicleState) {
+        match self {
+            ByteRule::Counter { byte, mask } => {
+                let shift = mask.trailing_zeros();
+                let span = (*mask as u32 >> shift) + 1;
+                let value = ((sent % span) << shift) as u8;
+                data[*byte] = (data[*byte] & !mask) | (value & mask);
+            }
+            ByteRule::Xor { byte, from, to } => {
+                data[*byte] = data[*from..*to].iter().fold(0, |acc, b| acc ^ b);
+            }
+            ByteRule::Crc8 { byte, from, to } => {
+                data[*byte] = crc8_j1850(&data[*from..*to]);
+            }
+            ByteRule::Signal { byte, mask, values } => {
+                if let Some(value) = values.get(&state) {
+                    data[*byte] = (data[*byte] & !mask) | (value & mask);
+                }
+            }
+            ByteRule::Field { field, values } => {
+                if let Some(raw) = values.get(&state) {
+                    // Checked against the 8-byte payload when the rule was built
+                    let _ = field.insert(data, *raw);
+                }
+            }
+        }
+    }
+}
+
+/// CRC8 with the SAE J1850 parameters
+pub fn crc8_j1850(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for &b in data {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x1D
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc ^ 0xFF
+}
+
+/// One periodic frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BroadcastMsg {
+    pub can_id: u32,
+    pub period_ms: u64,
+    /// Base payload the rules are applied to
+    pub data: [u8; 8],
+    #[serde(default)]
+    pub rules: Vec<ByteRule>,
+    /// States the frame is sent in (empty = every state)
+    #[serde(default)]
+    pub states: Vec<VehicleState>,
+}
+
+impl BroadcastMsg {
+    pub fn new(can_id: u32, period_ms: u64, data: [u8; 8]) -> Self {
+        Self {
+            can_id,
+            period_ms,
+            data,
+            rules: Vec::new(),
+            states: Vec::new(),
+        }
+    }
+
+    /// Frame for DBC `message` with its payload built from signal values (all
+    /// other bits clear) instead of a hex literal
+    pub fn from_signals(
+        message: &Message,
+        period_ms: u64,
+        values: &BTreeMap<String, SignalValue>,
+    ) -> Result<Self, String> {
+        if message.size > 8 {
+            return Err(format!(
+                "{} is a {}-byte frame; the broadcast sends classic CAN only",
+                message.name, message.size
+            ));
+        }
+        if message.extended {
+            return Err(format!(
+                "{} has a 29-bit ID; the broadcast sends 11-bit IDs only",
+                message.name
+            ));
+        }
+        let mut data = [0u8; 8];
+        message.encode_into(&mut data[..message.size], values)?;
+        Ok(Self::new(message.id, period_ms, data))
+    }
+
+    /// Set DBC `signal` of `message` per vehicle state (physical values or
+    /// value-table labels); states not listed keep the base payload's bits
+    pub fn with_signal(
+        self,
+        message: &Message,
+        signal: &str,
+        values: &[(VehicleState, SignalValue)],
+    ) -> Result<Self, String> {
+        let signal = message
+            .signal(signal)
+            .ok_or_else(|| format!("{} has no signal '{}'", message.name, signal))?;
+        signal.field.insert(&mut [0u8; 8], 0)?;
+        let values = values
+            .iter()
+            .map(|(state, value)| Ok((*state, signal.to_raw(value)?)))
+            .collect::<Result<_, String>>()?;
+        Ok(self.with_rule(ByteRule::Field {
+            field: signal.field,
+            values,
+        }))
+    }
+
+    pub fn with_rule(mut self, rule: ByteRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Only send the frame in `states`
+    pub fn in_states(mut self, states: &[VehicleState]) -> Self {
+        self.states = states.to_vec();
+        self
+    }
+
+    pub fn active_in(&self, state: VehicleState) -> bool {
+        self.states.is_empty() || self.states.contains(&state)
+    }
+
+    /// Payload of the `sent`-th transmission in `state`
+    pub fn frame(&self, sent: u32, state: VehicleState) -> [u8; 8] {
+        let mut data = self.data;
+        for rule in &self.rules {
+            rule.apply(&mut data, sent, state);
+        }
+        data
+    }
+}
+
+/// `values` for a `Signal` rule as (state, bits) pairs
+fn by_state(values: &[(VehicleState, u8)]) -> BTreeMap<VehicleState, u8> {
+    values.iter().copied().collect()
+}
+
+/// Default bench schedule: frames captured from a real car (SAJBL4BVXGCY16353)
+/// that are absent on a bench with only the IMC, plus the ignition status frame.
+///
+/// Only the payloads and the 0x230 ignition bit are taken from captures. None of
+/// the captures in the repo is dense enough to measure a period or follow a
+/// counter: `can_dump_ignition.txt` and `can_dump_ign_onoff.txt` hold 2-6 frames
+/// per ID over 30 s, `can_full_dump.txt` only per-ID counts over 5 s, and
+/// `can_capture.log` a once-a-second monitor screen. So, per ID:
+///
+/// - Periods (all assumed): 20 ms for 0x030 and 0x070, 50 ms for 0x0B0, 0x0D0,
+///   0x140, 0x154 and 0x1D0, 100 ms for 0x130, 0x200-0x2C0 and 0x230, 200 ms for
+///   0x300 and the NM frames 0x400/0x407/0x460. They are common classes, ordered
+///   after the `can_full_dump.txt` counts (powertrain/chassis busiest, NM least).
+/// - Counters (all assumed): the high nibble of 0x070 byte 1, the low nibble of
+///   0x1D0 byte 1 and of 0x0B0 byte 6. Those nibbles differ between the sparse
+///   samples while the rest of the frame stays put, which is all that backs them;
+///   the sequence and step are not known. Byte 0 of 0x070 and 0x1D0 changes with
+///   them too, but its algorithm is unknown, so it keeps the captured value.
+/// - 0x230 byte 0 bit 5 (measured): clear in `can_dump_ign_onoff.txt` (0x40),
+///   set in `can_dump_ignition.txt` (0x60).
+///
+/// Take the schedule from a DBC schedule file (`DbcSchedule`) for measured rates.
+pub fn default_schedule() -> Vec<BroadcastMsg> {
+    use VehicleState::*;
+    // Powertrain/chassis traffic stops with the ignition; NM and body keep the bus awake
+    let running: &[VehicleState] = &[On, Crank];
+    let awake: &[VehicleState] = &[Accessory, On, Crank];
+    vec![
+        // Counter (assumed) in the high nibble of byte 1
+        BroadcastMsg::new(0x070, 20, [0xFF, 0x87, 0xD0, 0xFE, 0xFE, 0x3F, 0xFF, 0x03])
+            .with_rule(ByteRule::Counter {
+                byte: 1,
+                mask: 0xF0,
+            })
+            .in_states(running),
+        BroadcastMsg::new(0x0B0, 50, [0x00, 0x04, 0x32, 0x03, 0xF8, 0x0D, 0x35, 0x00])
+            .with_rule(ByteRule::Counter {
+                byte: 6,
+                mask: 0x0F,
+            })
+            .in_states(running),
+        BroadcastMsg::new(0x0D0, 50, [0xEC, 0x00, 0x42, 0x50, 0xE2, 0x69, 0xA8, 0x84])
+            .in_states(running),
+        BroadcastMsg::new(0x154, 50, [0x27, 0xC7, 0x07, 0xED, 0x07, 0xD9, 0x07, 0xBD])
+            .in_states(running),
+        // Counter (assumed) in the low nibble of byte 1
+        BroadcastMsg::new(0x1D0, 50, [0x62, 0xFE, 0x00, 0x10, 0x80, 0x00, 0x80, 0x00])
+            .with_rule(ByteRule::Counter {
+                byte: 1,
+                mask: 0x0F,
+            })
+            .in_states(running),
+        BroadcastMsg::new(0x200, 100, [0x01, 0x00, 0x00, 0x00, 0x03, 0x5E, 0x0E, 0x00])
+            .in_states(awake),
+        BroadcastMsg::new(0x270, 100, [0x00, 0xE8, 0x50, 0x00, 0x83, 0xFE, 0x03, 0x00])
+            .in_states(awake),
+        BroadcastMsg::new(0x280, 100, [0x00, 0x00, 0x03, 0xFE, 0x01, 0xFE, 0x13, 0xFE])
+            .in_states(awake),
+        BroadcastMsg::new(0x2A0, 100, [0x80, 0x81, 0x40, 0x00, 0x5D, 0x44, 0x66, 0x0D])
+            .in_states(awake),
+        BroadcastMsg::new(0x2C0, 100, [0x30, 0x00, 0x7D, 0xD0, 0x01, 0x40, 0x9A, 0x80])
+            .in_states(awake),
+        BroadcastMsg::new(0x300, 200, [0x01, 0x6B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
+            .in_states(awake),
+        // Network Management heartbeats — IMC checks for these to know other ECUs are alive
+        BroadcastMsg::new(0x400, 200, [0x08, 0x01, 0x00, 0x00, 0x16, 0x04, 0x00, 0x01]), // BCM NM
+        BroadcastMsg::new(0x407, 200, [0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]), // GWM NM
+        BroadcastMsg::new(0x460, 200, [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]), // IPC NM
+        // Common IDs with car-specific data (bench has different values)
+        BroadcastMsg::new(0x030, 20, [0x04, 0x00, 0x00, 0x00, 0x00, 0x1F, 0xFE, 0x70])
+            .in_states(running),
+        BroadcastMsg::new(0x130, 100, [0x02, 0x00, 0x50, 0x04, 0x04, 0x00, 0x00, 0x00])
+            .in_states(awake),
+        // Ignition status: byte 0 bit 5 is clear in the ign on/off capture (0x40)
+        // and set with the ignition on (0x60)
+        BroadcastMsg::new(0x230, 100, [0x60, 0x00, 0x7F, 0xCA, 0x00, 0x40, 0x00, 0x04])
+            .with_rule(ByteRule::Signal {
+                byte: 0,
+                mask: 0x20,
+                values: by_state(&[(Accessory, 0x00), (On, 0x20), (Crank, 0x20)]),
+            })
+            .in_states(awake),
+        BroadcastMsg::new(0x140, 50, [0x00, 0x6D, 0x83, 0x00, 0x00, 0x7F, 0x80, 0x00])
+            .in_states(awake),
+    ]
+}
+
+// ─── DBC Schedules ───────────────────────────────────────────────────
+
+/// Bench schedule file: frames built from DBC signal values. They replace the
+/// default frames with the same IDs; the rest of the default schedule stays.
+///
+/// ```json
+/// {"dbc": "bench.dbc", "frames": [
+///   {"message": "IgnitionStatus", "period_ms": 100, "signals": {"Supply": 12.6},
+///    "by_state": {"IgnitionOn": {"accessory": "Off", "on": "On"}},
+///    "states": ["accessory", "on", "crank"]}]}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DbcSchedule {
+    /// DBC file, relative to the schedule file
+    pub dbc: String,
+    pub frames: Vec<DbcFrame>,
+}
+
+/// One frame of a `DbcSchedule`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DbcFrame {
+    /// Message name or ID
+    pub message: String,
+    pub period_ms: u64,
+    /// Base payload as signal values (all other bits clear)
+    #[serde(default)]
+    pub signals: BTreeMap<String, SignalValue>,
+    /// Signals set per vehicle state
+    #[serde(default)]
+    pub by_state: BTreeMap<String, BTreeMap<VehicleState, SignalValue>>,
+    /// States the frame is sent in (empty = every state)
+    #[serde(default)]
+    pub states: Vec<VehicleState>,
+}
+
+impl DbcSchedule {
+    /// The full bench schedule for the schedule file at `path`
+    pub fn load(path: &Path) -> Result<Vec<BroadcastMsg>, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let schedule: DbcSchedule = serde_json::from_str(&text)
+            .map_err(|e| format!("Bad broadcast schedule {}: {}", path.display(), e))?;
+        let dbc_path = path.parent().unwrap_or(Path::new(".")).join(&schedule.dbc);
+        let frames = schedule
+            .build(&Dbc::load(&dbc_path)?)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(schedule_with(frames))
+    }
+
+    /// The schedule's frames against `dbc`
+    pub fn build(&self, dbc: &Dbc) -> Result<Vec<BroadcastMsg>, String> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                let message = dbc
+                    .find(&frame.message)
+                    .ok_or_else(|| format!("No message '{}' in the DBC", frame.message))?;
+                let mut msg = BroadcastMsg::from_signals(message, frame.period_ms, &frame.signals)?
+                    .in_states(&frame.states);
+                for (signal, values) in &frame.by_state {
+                    let values: Vec<_> = values.iter().map(|(s, v)| (*s, v.clone())).collect();
+                    msg = msg.with_signal(message, signal, &values)?;
+                }
+                Ok(msg)
+            })
+            .collect()
+    }
+}
+
+/// The default schedule with `frames` in place of the frames with the same IDs
+pub fn schedule_with(frames: Vec<BroadcastMsg>) -> Vec<BroadcastMsg> {
+    let mut schedule = default_schedule();
+    schedule.retain(|m| !frames.iter().any(|f| f.can_id == m.can_id));
+    schedule.extend(frames);
+    schedule
+}
+
+// ─── Scheduler ───────────────────────────────────────────────────────
+
+struct Slot {
+    msg: BroadcastMsg,
+    next_due: Instant,
+    sent: u32,
+}
+
+/// Decides which frames are due and what they carry
+pub struct BroadcastScheduler {
+    slots: Vec<Slot>,
+}
+
+impl BroadcastScheduler {
+    /// Every message is first due at `start`
+    pub fn new(messages: Vec<BroadcastMsg>, start: Instant) -> Self {
+        Self {
+            slots: messages
+                .into_iter()
+                .map(|msg| Slot {
+                    msg,
+                    next_due: start,
+                    sent: 0,
+                })
+                .collect(),
+        }
+    }
+
+    /// Frames due at `now` in `state`, as (CAN ID, data). Messages silent in
+    /// `state` keep their schedule but not their counters.
+    pub fn due(&mut self, now: Instant, state: VehicleState) -> Vec<(u32, [u8; 8])> {
+        let mut out = Vec::new();
+        for slot in &mut self.slots {
+            if now < slot.next_due {
+                continue;
+            }
+            let period = Duration::from_millis(slot.msg.period_ms.max(1));
+            // Don't burst to catch up after a stall
+            slot.next_due = (slot.next_due + period).max(now);
+            if slot.msg.active_in(state) {
+                out.push((slot.msg.can_id, slot.msg.frame(slot.sent, state)));
+                slot.sent = slot.sent.wrapping_add(1);
+            }
+        }
+        out
+    }
+
+    /// When the next frame is due
+    pub fn next_due(&self) -> Option<Instant> {
+        self.slots.iter().map(|s| s.next_due).min()
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_rolls_within_mask() {
+        let msg = BroadcastMsg::new(0x070, 20, [0x00, 0x87, 0, 0, 0, 0, 0, 0]).with_rule(
+            ByteRule::Counter {
+                byte: 1,
+                mask: 0xF0,
+            },
+        );
+        let bytes: Vec<u8> = (0..17).map(|n| msg.frame(n, VehicleState::On)[1]).collect();
+        assert_eq!(bytes[0], 0x07);
+        assert_eq!(bytes[1], 0x17);
+        assert_eq!(bytes[15], 0xF7);
+        assert_eq!(bytes[16], 0x07);
+    }
+
+    #[test]
+    fn test_checksums_cover_updated_bytes() {
+        assert_eq!(crc8_j1850(b"123456789"), 0x4B);
+        let msg = BroadcastMsg::new(0x123, 50, [0, 0x10, 0x22, 0, 0, 0, 0, 0])
+            .with_rule(ByteRule::Counter {
+                byte: 1,
+                mask: 0x0F,
+            })
+            .with_rule(ByteRule::Xor {
+                byte: 7,
+                from: 1,
+                to: 7,
+            })
+            .with_rule(ByteRule::Crc8 {
+                byte: 0,
+                from: 1,
+                to: 8,
+            });
+        let f = msg.frame(3, VehicleState::On);
+        assert_eq!(f[1], 0x13);
+        assert_eq!(f[7], 0x13 ^ 0x22);
+        assert_eq!(f[0], crc8_j1850(&f[1..]));
+    }
+
+    #[test]
+    fn test_signal_follows_vehicle_state() {
+        let schedule = default_schedule();
+        let ign = schedule.iter().find(|m| m.can_id == 0x230).unwrap();
+        assert_eq!(ign.frame(0, VehicleState::Accessory)[0], 0x40);
+        assert_eq!(ign.frame(0, VehicleState::On)[0], 0x60);
+        assert_eq!(ign.frame(0, VehicleState::Crank)[0], 0x60);
+        assert!(!ign.active_in(VehicleState::Off));
+    }
+
+    #[test]
+    fn test_frames_from_dbc_signals() {
+        let dbc = crate::dbc::Dbc::parse(
+            "BO_ 560 IgnitionStatus: 8 BCM\n\
+             \x20SG_ IgnitionOn : 5|1@1+ (1,0) [0|1] \"\" IMC\n\
+             \x20SG_ Supply : 16|8@1+ (0.1,0) [0|25.5] \"V\" IMC\n\
+             VAL_ 560 IgnitionOn 0 \"Off\" 1 \"On\" ;\n",
+        )
+        .unwrap();
+        let message = dbc.find("IgnitionStatus").unwrap();
+        let base = BTreeMap::from([("Supply".to_string(), SignalValue::Number(12.6))]);
+        let msg = BroadcastMsg::from_signals(message, 100, &base)
+            .unwrap()
+            .with_signal(
+                message,
+                "IgnitionOn",
+                &[
+                    (VehicleState::Accessory, SignalValue::Label("Off".into())),
+                    (VehicleState::On, SignalValue::Number(1.0)),
+                ],
+            )
+            .unwrap();
+        assert_eq!(msg.can_id, 0x230);
+        assert_eq!(
+            msg.frame(0, VehicleState::On),
+            [0x20, 0, 126, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            msg.frame(0, VehicleState::Accessory),
+            [0x00, 0, 126, 0, 0, 0, 0, 0]
+        );
+
+        let bad = [(VehicleState::On, SignalValue::Label("Crank".into()))];
+        assert!(msg
+            .clone()
+            .with_signal(message, "IgnitionOn", &bad)
+            .is_err());
+        assert!(msg.with_signal(message, "Gear", &[]).is_err());
+    }
+
+    #[test]
+    fn test_dbc_schedule_file() {
+        let dir = std::env::temp_dir().join(format!("udsapp_schedule_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("bench.dbc"),
+            "BO_ 560 IgnitionStatus: 8 BCM\n\
+             \x20SG_ IgnitionOn : 5|1@1+ (1,0) [0|1] \"\" IMC\n\
+             \x20SG_ Supply : 16|8@1+ (0.1,0) [0|25.5] \"V\" IMC\n\
+             BO_ 2566844768 DiagMux: 8 GWM\n\
+             VAL_ 560 IgnitionOn 0 \"Off\" 1 \"On\" ;\n",
+        )
+        .unwrap();
+        let path = dir.join("schedule.json");
+        std::fs::write(
+            &path,
+            r#"{"dbc": "bench.dbc", "frames": [
+                {"message": "IgnitionStatus", "period_ms": 50, "signals": {"Supply": 12.6},
+                 "by_state": {"IgnitionOn": {"accessory": "Off", "on": "On"}},
+                 "states": ["accessory", "on"]}]}"#,
+        )
+        .unwrap();
+        let schedule = DbcSchedule::load(&path).unwrap();
+        assert_eq!(schedule.len(), default_schedule().len());
+        let ign: Vec<_> = schedule.iter().filter(|m| m.can_id == 0x230).collect();
+        assert_eq!(ign.len(), 1);
+        assert_eq!(ign[0].period_ms, 50);
+        assert_eq!(
+            ign[0].frame(0, VehicleState::On),
+            [0x20, 0, 126, 0, 0, 0, 0, 0]
+        );
+        assert!(!ign[0].active_in(VehicleState::Crank));
+
+        // 29-bit messages can't be broadcast
+        let frames = r#"[{"message": "DiagMux", "period_ms": 100}]"#;
+        std::fs::write(
+            &path,
+            format!(r#"{{"dbc": "bench.dbc", "frames": {}}}"#, frames),
+        )
+        .unwrap();
+        assert!(DbcSchedule::load(&path).unwrap_err().contains("29-bit"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_scheduler_periods_and_states() {
+        let start = Instant::now();
+        let mut s = BroadcastScheduler::new(
+            vec![
+                BroadcastMsg::new(0x030, 20, [0; 8]).in_states(&[VehicleState::On]),
+                BroadcastMsg::new(0x400, 100, [0; 8]),
+            ],
+            start,
+        );
+        let ms = |n| start + Duration::from_millis(n);
+        assert_eq!(s.due(ms(0), VehicleState::On).len(), 2);
+        assert!(s.due(ms(10), VehicleState::On).is_empty());
+        assert_eq!(s.next_due(), Some(ms(20)));
+        let ids: Vec<u32> = s
+            .due(ms(20), VehicleState::On)
+            .iter()
+            .map(|f| f.0)
+            .collect();
+        assert_eq!(ids, vec![0x030]);
+
+        // With the ignition off only the NM frame is sent
+        assert!(s.due(ms(40), VehicleState::Off).is_empty());
+        let ids: Vec<u32> = s
+            .due(ms(100), VehicleState::Off)
+            .iter()
+            .map(|f| f.0)
+            .collect();
+        assert_eq!(ids, vec![0x400]);
+    }
+
+    #[test]
+    fn test_vehicle_state_parse() {
+        assert_eq!(VehicleState::parse("ACC"), Ok(VehicleState::Accessory));
+        assert!(VehicleState::parse("run")
+            .unwrap_err()
+            .contains("off, accessory, on, crank"));
+        let ign = default_schedule()
+            .into_iter()
+            .find(|m| m.can_id == 0x230)
+            .unwrap();
+        let json = serde_json::to_string(&ign).unwrap();
+        assert!(
+            json.contains(r#""values":{"accessory":0,"on":32,"crank":32}"#),
+            "{}",
+            json
+        );
+        let back: BroadcastMsg = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, ign);
+    }
+}
@@ -0,0 +1,169 @@
+//! Vector ASC text logs (CANalyzer/CANoe).
+//!
+//! Reads classic CAN data frames with hex or decimal base and absolute or relative
+//! timestamps; remote, error and CAN FD events are skipped. Writes hex base with
+//! absolute timestamps.
+
+use super::{hex_bytes, local_time, unix_us, CanFrame, CanLog, CaptureFormat};
+
+/// Header date formats: 12-hour as CANalyzer writes it, and 24-hour
+const DATE_FORMATS: &[&str] = &[
+    "%a %b %d %I:%M:%S%.f %p %Y",
+    "%a %b %d %I:%M:%S %p %Y",
+    "%a %b %d %H:%M:%S%.f %Y",
+    "%a %b %d %H:%M:%S %Y",
+];
+
+pub(super) fn is_asc(text: &str) -> bool {
+    text.lines().take(10).any(|line| {
+        let line = line.trim_start().to_lowercase();
+        line.starts_with("date ") || line.starts_with("begin triggerblock")
+    })
+}
+
+fn parse_date(s: &str) -> Option<u64> {
+    let s = s.trim();
+    DATE_FORMATS
+        .iter()
+        .find_map(|f| chrono::NaiveDateTime::parse_from_str(s, f).ok())
+        .and_then(unix_us)
+}
+
+/// `   0.434971 1  90              Rx   d 8 00 00 3F D7 00 00 27 FF`
+fn parse_frame(line: &str, hex: bool) -> Option<CanFrame> {
+    let radix = if hex { 16 } else { 10 };
+    let mut tokens = line.split_whitespace().skip(1);
+    let _channel: u32 = tokens.next()?.parse().ok()?;
+    let id = tokens.next()?;
+    let (id, extended) = match id.strip_suffix(['x', 'X']) {
+        Some(id) => (id, true),
+        None => (id, false),
+    };
+    let can_id = u32::from_str_radix(id, radix).ok()?;
+    let tx = match tokens.next()? {
+        "Rx" => false,
+        "Tx" => true,
+        _ => return None,
+    };
+    if tokens.next()? != "d" {
+        return None;
+    }
+    let len = usize::from_str_radix(tokens.next()?, 16).ok()?.min(8);
+    let data = tokens
+        .take(len)
+        .map(|t| u8::from_str_radix(t, radix).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    if data.len() != len {
+        return None;
+    }
+    Some(
+        CanFrame::new(0, can_id, data)
+            .with_extended(extended)
+            .with_tx(tx),
+    )
+}
+
+pub(super) fn read(text: &str) -> Result<CanLog, String> {
+    let mut start = None;
+    let mut hex = true;
+    let mut relative = false;
+    let mut last_us = 0u64;
+    let mut frames = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let lower = trimmed.to_lowercase();
+        if let Some(date) = trimmed.strip_prefix("date ") {
+            start = start.or_else(|| parse_date(date));
+        } else if lower.starts_with("begin triggerblock") {
+            start = start.or_else(|| parse_date(&trimmed["begin triggerblock".len()..]));
+        } else if lower.starts_with("base ") {
+            hex = !lower.contains("base dec");
+            relative = lower.contains("timestamps relative");
+        } else if let Some(seconds) = trimmed
+            .split_whitespace()
+            .next()
+            .and_then(|t| t.parse::<f64>().ok())
+        {
+            // Relative times count from the previous event of any kind
+            let us = (seconds * 1e6).round() as u64;
+            last_us = if relative { last_us + us } else { us };
+            if let Some(mut frame) = parse_frame(trimmed, hex) {
+                frame.timestamp_us = last_us;
+                frames.push(frame);
+            }
+        }
+    }
+    Ok(CanLog::new(CaptureFormat::Asc, start, frames))
+}
+
+/// `Tue Feb 03 01:15:16.250 pm 2026`
+fn format_date(unix_us: u64) -> String {
+    let t = local_time(unix_us);
+    format!(
+        "{} {} {}",
+        t.format("%a %b %d %I:%M:%S%.3f"),
+        t.format("%p").to_string().to_lowercase(),
+        t.format("%Y")
+    )
+}
+
+pub(super) fn write(log: &CanLog) -> String {
+    let date = format_date(log.start_unix_us.unwrap_or(0));
+    let mut out = format!(
+        "date {date}\n\
+         base hex  timestamps absolute\n\
+         internal events logged\n\
+         // version 9.0.0\n\
+         Begin Triggerblock {date}\n\
+         \x20  0.000000 Start of measurement\n"
+    );
+    for f in &log.frames {
+        let time = format!(
+            "{}.{:06}",
+            f.timestamp_us / 1_000_000,
+            f.timestamp_us % 1_000_000
+        );
+        let id = if f.is_extended() {
+            format!("{:X}x", f.can_id)
+        } else {
+            format!("{:X}", f.can_id)
+        };
+        out.push_str(&format!(
+            "{:>11} 1  {:<15} {:<4} d {:X} {}\n",
+            time,
+            id,
+            if f.is_tx() { "Tx" } else { "Rx" },
+            f.data.len(),
+            hex_bytes(&f.data, " ")
+        ));
+    }
+    out.push_str("End TriggerBlock\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse, CaptureFormat};
+
+    #[test]
+    fn test_parse_asc_decimal_relative() {
+        let text = "date Tue Feb 03 13:15:16.000 2026\n\
+                    base dec  timestamps relative\n\
+                    Begin Triggerblock Tue Feb 03 13:15:16.000 2026\n\
+                    \x20  0.000000 Start of measurement\n\
+                    \x20  0.100000 1  144             Rx   d 2 1 255\n\
+                    \x20  0.050000 1  ErrorFrame\n\
+                    \x20  0.050000 2  1000x           Tx   d 1 16\n\
+                    \x20  0.010000 1  144             Rx   r\n\
+                    End TriggerBlock\n";
+        let log = parse(text).unwrap();
+        assert_eq!(log.format, CaptureFormat::Asc);
+        assert!(log.start_unix_us.is_some());
+        assert_eq!(log.frames.len(), 2);
+        assert_eq!(log.frames[0].can_id, 0x90);
+        assert_eq!(log.frames[0].data, vec![1, 255]);
+        assert_eq!(log.frames[1].timestamp_us, 200_000);
+        assert_eq!(log.frames[1].can_id, 1000);
+        assert!(log.frames[1].is_extended() && log.frames[1].is_tx());
+    }
+}
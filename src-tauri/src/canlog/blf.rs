@@ -0,0 +1,318 @@
+//! Vector BLF binary logs.
+//!
+//! A 144-byte `LOGG` file header followed by `LOBJ` objects. Frames normally sit
+//! inside zlib-compressed log containers whose payloads form one continuous object
+//! stream. Reads CAN_MESSAGE, CAN_MESSAGE2 and CAN_FD_MESSAGE; writes CAN_MESSAGE
+//! objects in compressed containers.
+
+use std::io::{Read, Write};
+
+use chrono::{Datelike, NaiveDate, Timelike};
+
+use super::{local_time, unix_us, CanFrame, CanLog, CaptureFormat};
+
+pub(super) const FILE_SIGNATURE: &[u8] = b"LOGG";
+const OBJ_SIGNATURE: &[u8] = b"LOBJ";
+const FILE_HEADER_SIZE: usize = 144;
+const OBJ_HEADER_BASE_SIZE: usize = 16;
+const OBJ_HEADER_V1_SIZE: usize = 32;
+const CONTAINER_HEADER_SIZE: usize = 32;
+
+const CAN_MESSAGE: u32 = 1;
+const LOG_CONTAINER: u32 = 10;
+const CAN_MESSAGE2: u32 = 86;
+const CAN_FD_MESSAGE: u32 = 100;
+
+const TIME_TEN_MICS: u32 = 1;
+const TIME_ONE_NANS: u32 = 2;
+const NO_COMPRESSION: u16 = 0;
+const ZLIB_DEFLATE: u16 = 2;
+const DIR_TX: u8 = 0x01;
+const REMOTE_FLAG: u8 = 0x80;
+const CAN_MSG_EXT: u32 = 0x8000_0000;
+
+/// Uncompressed bytes per log container (as CANalyzer and python-can write them)
+const MAX_CONTAINER_SIZE: usize = 128 * 1024;
+
+fn u16_at(b: &[u8], off: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(off..off + 2)?.try_into().ok()?))
+}
+
+fn u32_at(b: &[u8], off: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(off..off + 4)?.try_into().ok()?))
+}
+
+fn u64_at(b: &[u8], off: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(b.get(off..off + 8)?.try_into().ok()?))
+}
+
+/// Windows SYSTEMTIME (local time) at `off`
+fn systemtime_at(b: &[u8], off: usize) -> Option<u64> {
+    let field = |i: usize| u16_at(b, off + 2 * i).map(u32::from);
+    let date = NaiveDate::from_ymd_opt(field(0)? as i32, field(1)?, field(3)?)?;
+    let time = date.and_hms_milli_opt(field(4)?, field(5)?, field(6)?, field(7)?)?;
+    unix_us(time)
+}
+
+fn systemtime(unix_us: u64) -> [u8; 16] {
+    let t = local_time(unix_us);
+    let fields = [
+        t.year() as u16,
+        t.month() as u16,
+        t.weekday().num_days_from_sunday() as u16,
+        t.day() as u16,
+        t.hour() as u16,
+        t.minute() as u16,
+        t.second() as u16,
+        (t.nanosecond() / 1_000_000) as u16,
+    ];
+    let mut out = [0u8; 16];
+    for (i, f) in fields.iter().enumerate() {
+        out[2 * i..2 * i + 2].copy_from_slice(&f.to_le_bytes());
+    }
+    out
+}
+
+/// Object type, header size and the whole object's bytes
+type Object<'a> = (u32, usize, &'a [u8]);
+
+/// Split an object stream into objects, following the 4-byte alignment padding
+/// between them
+fn objects(stream: &[u8]) -> Result<Vec<Object<'_>>, String> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos + OBJ_HEADER_BASE_SIZE <= stream.len() {
+        if &stream[pos..pos + 4] != OBJ_SIGNATURE {
+            return Err(format!("BLF: no object signature at offset {}", pos));
+        }
+        let header_size = u16_at(stream, pos + 4).unwrap_or(0) as usize;
+        let size = u32_at(stream, pos + 8).unwrap_or(0) as usize;
+        let obj_type = u32_at(stream, pos + 12).unwrap_or(0);
+        if size < OBJ_HEADER_BASE_SIZE || header_size > size {
+            return Err(format!("BLF: bad object size {} at offset {}", size, pos));
+        }
+        // A truncated last object (capture still being written) ends the stream
+        let Some(obj) = stream.get(pos..pos + size) else {
+            break;
+        };
+        out.push((obj_type, header_size, obj));
+        pos += size + size % 4;
+    }
+    Ok(out)
+}
+
+fn container_payload(obj: &[u8]) -> Result<Vec<u8>, String> {
+    let method = u16_at(obj, 16).unwrap_or(0);
+    let data = obj.get(CONTAINER_HEADER_SIZE..).unwrap_or_default();
+    match method {
+        NO_COMPRESSION => Ok(data.to_vec()),
+        ZLIB_DEFLATE => {
+            // The header's uncompressed size isn't trusted for preallocation
+            let mut out = Vec::new();
+            flate2::read::ZlibDecoder::new(data)
+                .read_to_end(&mut out)
+                .map_err(|e| format!("BLF: container decompression failed: {}", e))?;
+            Ok(out)
+        }
+        other => Err(format!("BLF: unsupported compression method {}", other)),
+    }
+}
+
+/// A CAN frame object; None for other object types and remote frames
+fn frame(obj_type: u32, header_size: usize, obj: &[u8]) -> Option<CanFrame> {
+    let flags = u32_at(obj, 16)?;
+    let timestamp = u64_at(obj, 24)?;
+    let timestamp_us = match flags {
+        TIME_TEN_MICS => timestamp.saturating_mul(10),
+        TIME_ONE_NANS => timestamp / 1000,
+        _ => return None,
+    };
+    let body = obj.get(header_size..)?;
+    let msg_flags = *body.get(2)?;
+    let dlc = *body.get(3)? as usize;
+    let id = u32_at(body, 4)?;
+    let data = match obj_type {
+        CAN_MESSAGE | CAN_MESSAGE2 => body.get(8..8 + dlc.min(8))?,
+        CAN_FD_MESSAGE => {
+            let valid = (*body.get(15)? as usize).min(64);
+            body.get(20..20 + valid)?
+        }
+        _ => return None,
+    };
+    if msg_flags & REMOTE_FLAG != 0 {
+        return None;
+    }
+    Some(
+        CanFrame::new(timestamp_us, id & !CAN_MSG_EXT, data.to_vec())
+            .with_extended(id & CAN_MSG_EXT != 0)
+            .with_tx(msg_flags & DIR_TX != 0),
+    )
+}
+
+pub(super) fn read(bytes: &[u8]) -> Result<CanLog, String> {
+    let header_size = u32_at(bytes, 4).ok_or("BLF: truncated file header")? as usize;
+    let start = systemtime_at(bytes, 40);
+    let body = bytes
+        .get(header_size..)
+        .ok_or("BLF: truncated file header")?;
+
+    // Container payloads are one object stream: objects may span containers
+    let mut stream = Vec::new();
+    let mut frames = Vec::new();
+    for (obj_type, obj_header, obj) in objects(body)? {
+        if obj_type == LOG_CONTAINER {
+            stream.extend(container_payload(obj)?);
+        } else {
+            frames.extend(frame(obj_type, obj_header, obj));
+        }
+    }
+    for (obj_type, obj_header, obj) in objects(&stream)? {
+        frames.extend(frame(obj_type, obj_header, obj));
+    }
+    Ok(CanLog::new(CaptureFormat::Blf, start, frames))
+}
+
+fn can_message(f: &CanFrame) -> Result<[u8; 48], String> {
+    if f.data.len() > 8 {
+        return Err(format!(
+            "BLF export supports classic CAN only (0x{:X} has {} bytes)",
+            f.can_id,
+            f.data.len()
+        ));
+    }
+    let mut obj = [0u8; 48];
+    obj[0..4].copy_from_slice(OBJ_SIGNATURE);
+    obj[4..6].copy_from_slice(&(OBJ_HEADER_V1_SIZE as u16).to_le_bytes());
+    obj[6..8].copy_from_slice(&1u16.to_le_bytes());
+    obj[8..12].copy_from_slice(&48u32.to_le_bytes());
+    obj[12..16].copy_from_slice(&CAN_MESSAGE.to_le_bytes());
+    obj[16..20].copy_from_slice(&TIME_ONE_NANS.to_le_bytes());
+    let timestamp_ns = f.timestamp_us.checked_mul(1000).ok_or_else(|| {
+        format!(
+            "BLF export: timestamp {} µs is out of range",
+            f.timestamp_us
+        )
+    })?;
+    obj[24..32].copy_from_slice(&timestamp_ns.to_le_bytes());
+    obj[32..34].copy_from_slice(&1u16.to_le_bytes());
+    obj[34] = if f.is_tx() { DIR_TX } else { 0 };
+    obj[35] = f.data.len() as u8;
+    let id = if f.is_extended() {
+        f.can_id | CAN_MSG_EXT
+    } else {
+        f.can_id
+    };
+    obj[36..40].copy_from_slice(&id.to_le_bytes());
+    obj[40..40 + f.data.len()].copy_from_slice(&f.data);
+    Ok(obj)
+}
+
+pub(super) fn write(log: &CanLog) -> Result<Vec<u8>, String> {
+    let mut stream = Vec::with_capacity(log.frames.len() * 48);
+    for f in &log.frames {
+        stream.extend_from_slice(&can_message(f)?);
+    }
+
+    let mut body = Vec::new();
+    let mut uncompressed_size = FILE_HEADER_SIZE;
+    for chunk in stream.chunks(MAX_CONTAINER_SIZE) {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(chunk).map_err(|e| e.to_string())?;
+        let compressed = encoder.finish().map_err(|e| e.to_string())?;
+        let size = CONTAINER_HEADER_SIZE + compressed.len();
+        body.extend_from_slice(OBJ_SIGNATURE);
+        body.extend_from_slice(&(OBJ_HEADER_BASE_SIZE as u16).to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&(size as u32).to_le_bytes());
+        body.extend_from_slice(&LOG_CONTAINER.to_le_bytes());
+        body.extend_from_slice(&ZLIB_DEFLATE.to_le_bytes());
+        body.extend_from_slice(&[0; 6]);
+        body.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        body.extend_from_slice(&[0; 4]);
+        body.extend_from_slice(&compressed);
+        body.resize(body.len() + size % 4, 0);
+        uncompressed_size += CONTAINER_HEADER_SIZE + chunk.len();
+    }
+
+    let start = log.start_unix_us.unwrap_or(0);
+    let mut out = Vec::with_capacity(FILE_HEADER_SIZE + body.len());
+    out.extend_from_slice(FILE_SIGNATURE);
+    out.extend_from_slice(&(FILE_HEADER_SIZE as u32).to_le_bytes());
+    // Application ID, application version, BL version (2.6.8.1)
+    out.extend_from_slice(&[5, 0, 0, 0, 2, 6, 8, 1]);
+    out.extend_from_slice(&((FILE_HEADER_SIZE + body.len()) as u64).to_le_bytes());
+    out.extend_from_slice(&(uncompressed_size as u64).to_le_bytes());
+    out.extend_from_slice(&(log.frames.len() as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&systemtime(start));
+    out.extend_from_slice(&systemtime(start + log.duration_us()));
+    out.resize(FILE_HEADER_SIZE, 0);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blf_objects_span_containers() {
+        let frames: Vec<CanFrame> = (0..5000u64)
+            .map(|i| CanFrame::new(i * 100, 0x100 + (i % 50) as u32, vec![i as u8; 8]))
+            .collect();
+        let log = CanLog::new(CaptureFormat::Blf, Some(1_770_120_916_000_000), frames);
+        let bytes = write(&log).unwrap();
+        // 240 KB of objects: two containers, the second starting mid-object
+        assert_eq!(objects(&bytes[FILE_HEADER_SIZE..]).unwrap().len(), 2);
+        let back = read(&bytes).unwrap();
+        assert_eq!(back.frames, log.frames);
+        assert_eq!(back.start_unix_us, log.start_unix_us);
+    }
+
+    #[test]
+    fn test_blf_uncompressed_ten_micros() {
+        // One CAN_MESSAGE2 outside any container, 10 µs timestamps, then a remote frame
+        let mut msg = can_message(&CanFrame::new(0, 0x7E8, vec![0x02, 0x50, 0x03])).unwrap();
+        msg[12..16].copy_from_slice(&CAN_MESSAGE2.to_le_bytes());
+        msg[16..20].copy_from_slice(&TIME_TEN_MICS.to_le_bytes());
+        msg[24..32].copy_from_slice(&1234u64.to_le_bytes());
+        let mut remote = msg;
+        remote[34] = REMOTE_FLAG;
+        let mut bytes = vec![0u8; FILE_HEADER_SIZE];
+        bytes[..4].copy_from_slice(FILE_SIGNATURE);
+        bytes[4..8].copy_from_slice(&(FILE_HEADER_SIZE as u32).to_le_bytes());
+        bytes.extend_from_slice(&msg);
+        bytes.extend_from_slice(&remote);
+
+        let log = super::super::read(&bytes).unwrap();
+        assert_eq!(log.format, CaptureFormat::Blf);
+        assert_eq!(log.start_unix_us, None);
+        assert_eq!(log.frames.len(), 1);
+        assert_eq!(log.frames[0].timestamp_us, 12_340);
+        assert_eq!(log.frames[0].data, vec![0x02, 0x50, 0x03]);
+    }
+
+    #[test]
+    fn test_blf_hostile_sizes_and_timestamps() {
+        // A container claiming a 4 GB payload only decompresses what is there
+        let log = CanLog::new(
+            CaptureFormat::Blf,
+            None,
+            vec![CanFrame::new(7, 0x100, vec![1])],
+        );
+        let mut bytes = write(&log).unwrap();
+        let size_at = FILE_HEADER_SIZE + 24;
+        bytes[size_at..size_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(read(&bytes).unwrap().frames, log.frames);
+
+        let mut msg = can_message(&CanFrame::new(0, 0x7E8, vec![0x01])).unwrap();
+        msg[16..20].copy_from_slice(&TIME_TEN_MICS.to_le_bytes());
+        msg[24..32].copy_from_slice(&u64::MAX.to_le_bytes());
+        let frame = frame(CAN_MESSAGE, OBJ_HEADER_V1_SIZE, &msg).unwrap();
+        assert_eq!(frame.timestamp_us, u64::MAX);
+
+        let err = can_message(&CanFrame::new(u64::MAX, 0x7E8, vec![])).unwrap_err();
+        assert!(err.contains("out of range"), "{}", err);
+    }
+}
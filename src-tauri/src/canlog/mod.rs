@@ -0,0 +1,415 @@
+//! CAN capture files: a common frame type plus readers and writers for the standard
+//! log formats and the text formats our own tools have produced.
+//!
+//! | Format | Read | Write |
+//! |---|---|---|
+//! | SocketCAN `candump -l` | ✓ | ✓ |
+//! | Vector ASC | ✓ | ✓ |
+//! | Vector BLF | ✓ | ✓ |
+//! | PCAP / PCAPNG (`LINKTYPE_CAN_SOCKETCAN`) | ✓ | ✓ |
+//! | `can_dump_*.txt` (`<seconds> 0x<ID> [<len>] <bytes> RxStat=0x…`) | ✓ | ✓ |
+//! | sniff JSON (`CanSniffEntry` lists, `CanSniffResult`) | ✓ | ✓ |
+//! | `can_capture*.log` ("Live CAN Monitor" screens) | ✓ | |
+
+mod asc;
+mod blf;
+mod pcap;
+mod text;
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::j2534::types::{CAN_29BIT_ID, TX_MSG_TYPE};
+
+/// One CAN frame on the bus
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CanFrame {
+    /// Microseconds since the start of the capture
+    pub timestamp_us: u64,
+    /// Arbitration ID without flag bits
+    pub can_id: u32,
+    pub data: Vec<u8>,
+    /// J2534 RxStatus: `TX_MSG_TYPE` for our own transmissions, `CAN_29BIT_ID` for
+    /// extended IDs
+    pub rx_status: u32,
+}
+
+impl CanFrame {
+    /// A received frame; IDs above 0x7FF are marked extended
+    pub fn new(timestamp_us: u64, can_id: u32, data: Vec<u8>) -> Self {
+        let rx_status = if can_id > 0x7FF { CAN_29BIT_ID } else { 0 };
+        Self {
+            timestamp_us,
+            can_id,
+            data,
+            rx_status,
+        }
+    }
+
+    pub fn is_extended(&self) -> bool {
+        self.rx_status & CAN_29BIT_ID != 0 || self.can_id > 0x7FF
+    }
+
+    pub fn is_tx(&self) -> bool {
+        self.rx_status & TX_MSG_TYPE != 0
+    }
+
+    fn with_tx(mut self, tx: bool) -> Self {
+        if tx {
+            self.rx_status |= TX_MSG_TYPE;
+        }
+        self
+    }
+
+    fn with_extended(mut self, extended: bool) -> Self {
+        if extended {
+            self.rx_status |= CAN_29BIT_ID;
+        }
+        self
+    }
+}
+
+/// Capture file layouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureFormat {
+    /// Our `can_dump_*.txt` text
+    DumpTxt,
+    /// Live CAN Monitor screens (`can_capture*.log`), read only
+    Monitor,
+    SniffJson,
+    /// SocketCAN `candump -l`
+    Candump,
+    Asc,
+    Blf,
+    Pcap,
+    Pcapng,
+}
+
+impl CaptureFormat {
+    pub fn all() -> &'static [CaptureFormat] {
+        &[
+            CaptureFormat::DumpTxt,
+            CaptureFormat::Monitor,
+            CaptureFormat::SniffJson,
+            CaptureFormat::Candump,
+            CaptureFormat::Asc,
+            CaptureFormat::Blf,
+            CaptureFormat::Pcap,
+            CaptureFormat::Pcapng,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CaptureFormat::DumpTxt => "dump_txt",
+            CaptureFormat::Monitor => "monitor",
+            CaptureFormat::SniffJson => "sniff_json",
+            CaptureFormat::Candump => "candump",
+            CaptureFormat::Asc => "asc",
+            CaptureFormat::Blf => "blf",
+            CaptureFormat::Pcap => "pcap",
+            CaptureFormat::Pcapng => "pcapng",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::all().iter().copied().find(|f| f.name() == name)
+    }
+
+    /// Name lookup for formats that can be written
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::from_name(s)
+            .filter(|f| f.is_writable())
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::all()
+                    .iter()
+                    .filter(|f| f.is_writable())
+                    .map(|f| f.name())
+                    .collect();
+                format!(
+                    "Unknown CAN log format '{}' (expected {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// Format implied by a file extension (`.log` is taken as candump)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        Some(match ext.as_str() {
+            "txt" => CaptureFormat::DumpTxt,
+            "json" => CaptureFormat::SniffJson,
+            "log" => CaptureFormat::Candump,
+            "asc" => CaptureFormat::Asc,
+            "blf" => CaptureFormat::Blf,
+            "pcap" => CaptureFormat::Pcap,
+            "pcapng" => CaptureFormat::Pcapng,
+            _ => return None,
+        })
+    }
+
+    pub fn is_writable(self) -> bool {
+        self != CaptureFormat::Monitor
+    }
+}
+
+/// A capture: frames sorted by time, plus the wall-clock time of timestamp 0
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CanLog {
+    pub format: CaptureFormat,
+    /// Capture start in microseconds since the Unix epoch, when the file records it
+    pub start_unix_us: Option<u64>,
+    pub frames: Vec<CanFrame>,
+}
+
+impl CanLog {
+    pub fn new(format: CaptureFormat, start_unix_us: Option<u64>, frames: Vec<CanFrame>) -> Self {
+        Self {
+            format,
+            start_unix_us,
+            frames,
+        }
+    }
+
+    /// Frames stamped with absolute times: rebase them so the first one is at 0
+    pub(crate) fn from_absolute(format: CaptureFormat, mut frames: Vec<CanFrame>) -> Self {
+        frames.sort_by_key(|f| f.timestamp_us);
+        let start = frames.first().map(|f| f.timestamp_us);
+        if let Some(start) = start {
+            for f in &mut frames {
+                f.timestamp_us -= start;
+            }
+        }
+        Self::new(format, start, frames)
+    }
+
+    pub fn duration_us(&self) -> u64 {
+        self.frames.last().map_or(0, |f| f.timestamp_us)
+    }
+}
+
+/// Read a capture file, detecting its format from the content
+pub fn load(path: &Path) -> Result<CanLog, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    read(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Write a capture file in `format`, or the one its extension implies.
+/// Returns the format written.
+pub fn save(
+    path: &Path,
+    log: &CanLog,
+    format: Option<CaptureFormat>,
+) -> Result<CaptureFormat, String> {
+    let format = format
+        .or_else(|| CaptureFormat::from_path(path))
+        .ok_or_else(|| format!("Cannot tell the log format from {}", path.display()))?;
+    let bytes = write(log, format)?;
+    std::fs::write(path, bytes)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(format)
+}
+
+/// Parse a capture in any supported format. Frames come back sorted by time.
+pub fn read(bytes: &[u8]) -> Result<CanLog, String> {
+    let mut log = if bytes.starts_with(blf::FILE_SIGNATURE) {
+        blf::read(bytes)?
+    } else if pcap::is_pcap(bytes) {
+        pcap::read(bytes)?
+    } else if pcap::is_pcapng(bytes) {
+        pcap::read_ng(bytes)?
+    } else {
+        parse_text(&String::from_utf8_lossy(bytes))?
+    };
+    if log.frames.is_empty() {
+        return Err(format!(
+            "No CAN frames found ({} format)",
+            log.format.name()
+        ));
+    }
+    log.frames.sort_by_key(|f| f.timestamp_us);
+    Ok(log)
+}
+
+/// Parse a text capture, detecting its format
+pub fn parse(text: &str) -> Result<CanLog, String> {
+    read(text.as_bytes())
+}
+
+fn parse_text(text: &str) -> Result<CanLog, String> {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        return text::read_sniff_json(trimmed);
+    }
+    if text.contains("Live CAN Monitor") {
+        return Ok(text::read_monitor(text));
+    }
+    if asc::is_asc(trimmed) {
+        return asc::read(text);
+    }
+    if trimmed.starts_with('(') {
+        return text::read_candump(text);
+    }
+    Ok(text::read_dump_txt(text))
+}
+
+/// Serialize a capture in `format`
+pub fn write(log: &CanLog, format: CaptureFormat) -> Result<Vec<u8>, String> {
+    Ok(match format {
+        CaptureFormat::DumpTxt => text::write_dump_txt(log).into_bytes(),
+        CaptureFormat::SniffJson => text::write_sniff_json(log)?.into_bytes(),
+        CaptureFormat::Candump => text::write_candump(log).into_bytes(),
+        CaptureFormat::Asc => asc::write(log).into_bytes(),
+        CaptureFormat::Blf => blf::write(log)?,
+        CaptureFormat::Pcap => pcap::write(log),
+        CaptureFormat::Pcapng => pcap::write_ng(log),
+        CaptureFormat::Monitor => {
+            return Err("Live CAN Monitor logs can be read but not written".into())
+        }
+    })
+}
+
+/// `0x`-prefixed hex ID
+pub(crate) fn parse_id(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Hex byte tokens (`3F D7 …`)
+pub(crate) fn parse_bytes<'a>(tokens: impl Iterator<Item = &'a str>) -> Option<Vec<u8>> {
+    tokens.map(|t| u8::from_str_radix(t, 16).ok()).collect()
+}
+
+fn hex_bytes(data: &[u8], sep: &str) -> String {
+    data.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+/// Local wall-clock time of a Unix timestamp (ASC and BLF headers are local time)
+fn local_time(unix_us: u64) -> chrono::NaiveDateTime {
+    use chrono::TimeZone;
+    chrono::Local
+        .timestamp_micros(unix_us as i64)
+        .single()
+        .map(|t| t.naive_local())
+        .unwrap_or_default()
+}
+
+fn unix_us(local: chrono::NaiveDateTime) -> Option<u64> {
+    use chrono::TimeZone;
+    let t = chrono::Local.from_local_datetime(&local).earliest()?;
+    u64::try_from(t.timestamp_micros()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames covering standard, extended and transmitted IDs and short payloads
+    pub(super) fn sample_log() -> CanLog {
+        CanLog::new(
+            CaptureFormat::DumpTxt,
+            Some(1_770_120_916_250_000),
+            vec![
+                CanFrame::new(
+                    0,
+                    0x090,
+                    vec![0x00, 0x00, 0x3F, 0xD7, 0x00, 0x00, 0x27, 0xFF],
+                ),
+                CanFrame::new(120_500, 0x2E0, vec![0x00]),
+                CanFrame::new(250_000, 0x18DAF160, vec![0x02, 0x10, 0x03]),
+                CanFrame::new(1_000_001, 0x7E0, vec![0x02, 0x3E, 0x00]).with_tx(true),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_round_trip_all_writable_formats() {
+        let log = sample_log();
+        for &format in CaptureFormat::all() {
+            if !format.is_writable() {
+                continue;
+            }
+            let bytes = write(&log, format).unwrap();
+            let back = read(&bytes).unwrap_or_else(|e| panic!("{}: {}", format.name(), e));
+            assert_eq!(back.format, format);
+            let ids: Vec<u32> = back.frames.iter().map(|f| f.can_id).collect();
+            assert_eq!(
+                ids,
+                vec![0x090, 0x2E0, 0x18DAF160, 0x7E0],
+                "{}",
+                format.name()
+            );
+            for (a, b) in log.frames.iter().zip(&back.frames) {
+                assert_eq!(a.data, b.data, "{}", format.name());
+                assert_eq!(a.is_extended(), b.is_extended(), "{}", format.name());
+                // sniff JSON keeps milliseconds only
+                let tolerance = if format == CaptureFormat::SniffJson {
+                    1000
+                } else {
+                    1
+                };
+                assert!(
+                    a.timestamp_us.abs_diff(b.timestamp_us) < tolerance,
+                    "{}: {} vs {}",
+                    format.name(),
+                    a.timestamp_us,
+                    b.timestamp_us
+                );
+            }
+            // Direction survives where the format records it
+            if matches!(
+                format,
+                CaptureFormat::Asc
+                    | CaptureFormat::Blf
+                    | CaptureFormat::Pcapng
+                    | CaptureFormat::DumpTxt
+            ) {
+                assert!(back.frames[3].is_tx(), "{}", format.name());
+                assert!(!back.frames[0].is_tx(), "{}", format.name());
+            }
+            // Formats with absolute times keep the start to the microsecond, BLF to the ms
+            if let Some(start) = back.start_unix_us {
+                let tolerance = if format == CaptureFormat::Blf {
+                    1000
+                } else {
+                    1
+                };
+                assert!(
+                    start.abs_diff(log.start_unix_us.unwrap()) < tolerance,
+                    "{}",
+                    format.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_names_and_extensions() {
+        for &format in CaptureFormat::all() {
+            assert_eq!(CaptureFormat::from_name(format.name()), Some(format));
+        }
+        assert_eq!(
+            CaptureFormat::from_path(Path::new("a/b.PCAPNG")),
+            Some(CaptureFormat::Pcapng)
+        );
+        assert_eq!(
+            CaptureFormat::from_path(Path::new("trace.log")),
+            Some(CaptureFormat::Candump)
+        );
+        assert_eq!(CaptureFormat::from_path(Path::new("trace")), None);
+        assert!(write(&sample_log(), CaptureFormat::Monitor).is_err());
+        assert!(CaptureFormat::parse("monitor")
+            .unwrap_err()
+            .contains("pcapng"));
+        assert_eq!(CaptureFormat::parse(" BLF"), Ok(CaptureFormat::Blf));
+        assert!(read(b"").is_err());
+    }
+}
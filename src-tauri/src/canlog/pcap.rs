@@ -0,0 +1,378 @@
+//! PCAP and PCAPNG captures with `LINKTYPE_CAN_SOCKETCAN` (227), the layout
+//! Wireshark and SavvyCAN use for CAN.
+//!
+//! Each packet is a SocketCAN frame: big-endian ID with the EFF/RTR/ERR flag bits,
+//! length, FD flags, two reserved bytes, then the data (16 bytes for classic CAN,
+//! 72 for CAN FD). Reads both byte orders, microsecond and nanosecond PCAP, and
+//! PCAPNG `if_tsresol`; PCAPNG `epb_flags` carry the direction.
+
+use super::{CanFrame, CanLog, CaptureFormat};
+
+const LINKTYPE_CAN_SOCKETCAN: u32 = 227;
+
+const PCAP_MAGIC_US: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NS: u32 = 0xA1B2_3C4D;
+const PCAP_HEADER_SIZE: usize = 24;
+
+const BLOCK_SHB: u32 = 0x0A0D_0D0A;
+const BLOCK_IDB: u32 = 1;
+const BLOCK_EPB: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_EPB_FLAGS: u16 = 2;
+const EPB_INBOUND: u32 = 1;
+const EPB_OUTBOUND: u32 = 2;
+
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+const CAN_MTU: usize = 16;
+const CANFD_MTU: usize = 72;
+const CANFD_FDF: u8 = 0x04;
+
+/// Byte order of a capture (PCAP header or PCAPNG section)
+#[derive(Clone, Copy)]
+struct Endian {
+    big: bool,
+}
+
+impl Endian {
+    fn u16(self, b: &[u8], off: usize) -> Option<u16> {
+        let raw: [u8; 2] = b.get(off..off + 2)?.try_into().ok()?;
+        Some(if self.big {
+            u16::from_be_bytes(raw)
+        } else {
+            u16::from_le_bytes(raw)
+        })
+    }
+
+    fn u32(self, b: &[u8], off: usize) -> Option<u32> {
+        let raw: [u8; 4] = b.get(off..off + 4)?.try_into().ok()?;
+        Some(if self.big {
+            u32::from_be_bytes(raw)
+        } else {
+            u32::from_le_bytes(raw)
+        })
+    }
+}
+
+fn magic(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?))
+}
+
+pub(super) fn is_pcap(bytes: &[u8]) -> bool {
+    magic(bytes).is_some_and(|m| {
+        [PCAP_MAGIC_US, PCAP_MAGIC_NS]
+            .iter()
+            .any(|&x| m == x || m == x.swap_bytes())
+    })
+}
+
+pub(super) fn is_pcapng(bytes: &[u8]) -> bool {
+    magic(bytes) == Some(BLOCK_SHB)
+}
+
+/// A SocketCAN frame; None for RTR and error frames
+fn socketcan_frame(packet: &[u8], timestamp_us: u64) -> Option<CanFrame> {
+    let id = u32::from_be_bytes(packet.get(..4)?.try_into().ok()?);
+    if id & (CAN_RTR_FLAG | CAN_ERR_FLAG) != 0 {
+        return None;
+    }
+    let len = *packet.get(4)? as usize;
+    let data = packet.get(8..8 + len)?.to_vec();
+    let extended = id & CAN_EFF_FLAG != 0;
+    Some(CanFrame::new(timestamp_us, id & !CAN_EFF_FLAG, data).with_extended(extended))
+}
+
+fn socketcan_packet(f: &CanFrame) -> Vec<u8> {
+    let fd = f.data.len() > 8;
+    let mut packet = vec![0u8; if fd { CANFD_MTU } else { CAN_MTU }];
+    let id = if f.is_extended() {
+        f.can_id | CAN_EFF_FLAG
+    } else {
+        f.can_id
+    };
+    packet[..4].copy_from_slice(&id.to_be_bytes());
+    let len = f.data.len().min(64);
+    packet[4] = len as u8;
+    if fd {
+        packet[5] = CANFD_FDF;
+    }
+    packet[8..8 + len].copy_from_slice(&f.data[..len]);
+    packet
+}
+
+// ─── PCAP ───────────────────────────────────────────────────────────
+
+pub(super) fn read(bytes: &[u8]) -> Result<CanLog, String> {
+    let m = magic(bytes).ok_or("PCAP: truncated header")?;
+    let endian = Endian {
+        big: m == PCAP_MAGIC_US.swap_bytes() || m == PCAP_MAGIC_NS.swap_bytes(),
+    };
+    let nanos = m == PCAP_MAGIC_NS || m == PCAP_MAGIC_NS.swap_bytes();
+    let linktype = endian.u32(bytes, 20).ok_or("PCAP: truncated header")?;
+    if linktype != LINKTYPE_CAN_SOCKETCAN {
+        return Err(format!(
+            "PCAP: link type {} is not SocketCAN CAN (227)",
+            linktype
+        ));
+    }
+
+    let mut frames = Vec::new();
+    let mut pos = PCAP_HEADER_SIZE;
+    while let (Some(secs), Some(frac), Some(len)) = (
+        endian.u32(bytes, pos),
+        endian.u32(bytes, pos + 4),
+        endian.u32(bytes, pos + 8),
+    ) {
+        let Some(packet) = bytes.get(pos + 16..pos + 16 + len as usize) else {
+            break;
+        };
+        let frac_us = if nanos {
+            frac as u64 / 1000
+        } else {
+            frac as u64
+        };
+        frames.extend(socketcan_frame(packet, secs as u64 * 1_000_000 + frac_us));
+        pos += 16 + len as usize;
+    }
+    Ok(CanLog::from_absolute(CaptureFormat::Pcap, frames))
+}
+
+pub(super) fn write(log: &CanLog) -> Vec<u8> {
+    let start = log.start_unix_us.unwrap_or(0);
+    let mut out = Vec::with_capacity(PCAP_HEADER_SIZE + log.frames.len() * 32);
+    out.extend_from_slice(&PCAP_MAGIC_US.to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&4u16.to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+    out.extend_from_slice(&(CANFD_MTU as u32).to_le_bytes());
+    out.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+    for f in &log.frames {
+        let t = start + f.timestamp_us;
+        let packet = socketcan_packet(f);
+        out.extend_from_slice(&((t / 1_000_000) as u32).to_le_bytes());
+        out.extend_from_slice(&((t % 1_000_000) as u32).to_le_bytes());
+        out.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        out.extend_from_slice(&packet);
+    }
+    out
+}
+
+// ─── PCAPNG ─────────────────────────────────────────────────────────
+
+/// Options as (code, value) pairs, up to opt_endofopt
+fn options(endian: Endian, mut b: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut out = Vec::new();
+    while let (Some(code), Some(len)) = (endian.u16(b, 0), endian.u16(b, 2)) {
+        let len = len as usize;
+        let Some(value) = b.get(4..4 + len) else {
+            break;
+        };
+        if code == OPT_ENDOFOPT {
+            break;
+        }
+        out.push((code, value));
+        b = b.get(4 + len.next_multiple_of(4)..).unwrap_or_default();
+    }
+    out
+}
+
+/// Timestamp units per second from an `if_tsresol` value (default microseconds)
+fn units_per_second(tsresol: Option<u8>) -> u128 {
+    match tsresol {
+        Some(v) if v & 0x80 != 0 => 1u128 << (v & 0x7F).min(64),
+        Some(v) => 10u128.pow(v.min(19) as u32),
+        None => 1_000_000,
+    }
+}
+
+pub(super) fn read_ng(bytes: &[u8]) -> Result<CanLog, String> {
+    let mut endian = Endian { big: false };
+    // Per interface of the current section: (SocketCAN?, units per second)
+    let mut interfaces: Vec<(bool, u128)> = Vec::new();
+    let mut frames = Vec::new();
+    let mut pos = 0;
+    while pos + 12 <= bytes.len() {
+        let block_type = endian.u32(bytes, pos).unwrap_or(0);
+        if block_type == BLOCK_SHB {
+            let bom = u32::from_le_bytes(bytes[pos + 8..pos + 12].try_into().unwrap());
+            endian = Endian {
+                big: bom != BYTE_ORDER_MAGIC,
+            };
+            interfaces.clear();
+        }
+        let len = endian.u32(bytes, pos + 4).unwrap_or(0) as usize;
+        if len < 12 || !len.is_multiple_of(4) {
+            return Err(format!(
+                "PCAPNG: bad block length {} at offset {}",
+                len, pos
+            ));
+        }
+        let Some(body) = bytes.get(pos + 8..pos + len - 4) else {
+            break;
+        };
+        match block_type {
+            BLOCK_IDB => {
+                let linktype = endian.u16(body, 0).unwrap_or(0) as u32;
+                let tsresol = options(endian, body.get(8..).unwrap_or_default())
+                    .into_iter()
+                    .find(|(code, _)| *code == OPT_IF_TSRESOL)
+                    .and_then(|(_, v)| v.first().copied());
+                interfaces.push((
+                    linktype == LINKTYPE_CAN_SOCKETCAN,
+                    units_per_second(tsresol),
+                ));
+            }
+            BLOCK_EPB => {
+                let field = |i: usize| endian.u32(body, 4 * i).unwrap_or(0);
+                let (iface, caplen) = (field(0) as usize, field(3) as usize);
+                let Some(&(true, units)) = interfaces.get(iface) else {
+                    pos += len;
+                    continue;
+                };
+                let ts = ((field(1) as u128) << 32) | field(2) as u128;
+                let timestamp_us = (ts * 1_000_000 / units) as u64;
+                let packet = body.get(20..20 + caplen).unwrap_or_default();
+                let opts = body
+                    .get(20 + caplen.next_multiple_of(4)..)
+                    .unwrap_or_default();
+                let outbound = options(endian, opts)
+                    .into_iter()
+                    .find(|(code, _)| *code == OPT_EPB_FLAGS)
+                    .and_then(|(_, v)| endian.u32(v, 0))
+                    .is_some_and(|flags| flags & 0x3 == EPB_OUTBOUND);
+                frames.extend(socketcan_frame(packet, timestamp_us).map(|f| f.with_tx(outbound)));
+            }
+            _ => {}
+        }
+        pos += len;
+    }
+    if frames.is_empty() && !interfaces.iter().any(|(can, _)| *can) {
+        return Err("PCAPNG: no SocketCAN CAN interface (link type 227)".into());
+    }
+    Ok(CanLog::from_absolute(CaptureFormat::Pcapng, frames))
+}
+
+fn block(out: &mut Vec<u8>, block_type: u32, body: &[u8]) {
+    let len = (12 + body.len()) as u32;
+    out.extend_from_slice(&block_type.to_le_bytes());
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(body);
+    out.extend_from_slice(&len.to_le_bytes());
+}
+
+pub(super) fn write_ng(log: &CanLog) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut shb = Vec::new();
+    shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+    shb.extend_from_slice(&1u16.to_le_bytes());
+    shb.extend_from_slice(&0u16.to_le_bytes());
+    shb.extend_from_slice(&(-1i64).to_le_bytes());
+    block(&mut out, BLOCK_SHB, &shb);
+
+    // Default if_tsresol: microseconds
+    let mut idb = Vec::new();
+    idb.extend_from_slice(&(LINKTYPE_CAN_SOCKETCAN as u16).to_le_bytes());
+    idb.extend_from_slice(&0u16.to_le_bytes());
+    idb.extend_from_slice(&(CANFD_MTU as u32).to_le_bytes());
+    block(&mut out, BLOCK_IDB, &idb);
+
+    let start = log.start_unix_us.unwrap_or(0);
+    for f in &log.frames {
+        let t = start + f.timestamp_us;
+        let packet = socketcan_packet(f);
+        let mut epb = Vec::with_capacity(48 + packet.len());
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&((t >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(t as u32).to_le_bytes());
+        epb.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&packet);
+        let direction = if f.is_tx() { EPB_OUTBOUND } else { EPB_INBOUND };
+        epb.extend_from_slice(&OPT_EPB_FLAGS.to_le_bytes());
+        epb.extend_from_slice(&4u16.to_le_bytes());
+        epb.extend_from_slice(&direction.to_le_bytes());
+        epb.extend_from_slice(&[0; 4]);
+        block(&mut out, BLOCK_EPB, &epb);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcap_big_endian_nanoseconds() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&PCAP_MAGIC_NS.to_be_bytes());
+        bytes.extend_from_slice(&[0, 2, 0, 4]);
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_be_bytes());
+        let mut record = |secs: u32, nanos: u32, packet: Vec<u8>| {
+            bytes.extend_from_slice(&secs.to_be_bytes());
+            bytes.extend_from_slice(&nanos.to_be_bytes());
+            bytes.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&packet);
+        };
+        record(
+            100,
+            500_000_000,
+            socketcan_packet(&CanFrame::new(0, 0x123, vec![1, 2])),
+        );
+        let mut error = socketcan_packet(&CanFrame::new(0, 0x80, vec![0; 8]));
+        error[0] |= 0x20;
+        record(100, 600_000_000, error);
+        record(
+            101,
+            0,
+            socketcan_packet(&CanFrame::new(0, 0x18DAF160, vec![0xAA; 12])),
+        );
+
+        let log = super::super::read(&bytes).unwrap();
+        assert_eq!(log.format, CaptureFormat::Pcap);
+        assert_eq!(log.start_unix_us, Some(100_500_000));
+        assert_eq!(log.frames.len(), 2);
+        assert_eq!(log.frames[1].timestamp_us, 500_000);
+        assert!(log.frames[1].is_extended());
+        assert_eq!(log.frames[1].data.len(), 12);
+    }
+
+    #[test]
+    fn test_pcapng_tsresol_and_other_links() {
+        let log = CanLog::new(
+            CaptureFormat::Pcapng,
+            Some(5_000_000),
+            vec![CanFrame::new(1500, 0x7E0, vec![0x3E, 0x00]).with_tx(true)],
+        );
+        let mut bytes = write_ng(&log);
+        // Rewrite the IDB with if_tsresol = 2^-10 and add an Ethernet interface first
+        let idb_start = 28;
+        let mut idb = Vec::new();
+        idb.extend_from_slice(&(LINKTYPE_CAN_SOCKETCAN as u16).to_le_bytes());
+        idb.extend_from_slice(&[0; 6]);
+        idb.extend_from_slice(&OPT_IF_TSRESOL.to_le_bytes());
+        idb.extend_from_slice(&1u16.to_le_bytes());
+        idb.extend_from_slice(&[0x8A, 0, 0, 0]);
+        idb.extend_from_slice(&[0; 4]);
+        let mut ether = Vec::new();
+        block(&mut ether, BLOCK_IDB, &[1, 0, 0, 0, 0, 0, 0, 0]);
+        block(&mut ether, BLOCK_IDB, &idb);
+        bytes.splice(idb_start..idb_start + 20, ether);
+        // The EPB still names interface 0: point it at the CAN interface
+        let epb = idb_start + 20 + 32;
+        bytes[epb + 8..epb + 12].copy_from_slice(&1u32.to_le_bytes());
+        // 2048 units of 1/1024 s = 2 s
+        bytes[epb + 12..epb + 16].copy_from_slice(&0u32.to_le_bytes());
+        bytes[epb + 16..epb + 20].copy_from_slice(&2048u32.to_le_bytes());
+
+        let back = super::super::read(&bytes).unwrap();
+        assert_eq!(back.start_unix_us, Some(2_000_000));
+        assert_eq!(back.frames[0].can_id, 0x7E0);
+        assert!(back.frames[0].is_tx());
+    }
+}
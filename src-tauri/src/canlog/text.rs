@@ -0,0 +1,303 @@
+//! Line-based formats: our `can_dump_*.txt`, Live CAN Monitor screens, sniff JSON
+//! and SocketCAN `candump -l`.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::{hex_bytes, parse_bytes, parse_id, CanFrame, CanLog, CaptureFormat};
+
+/// Screen interval of the Live CAN Monitor
+const MONITOR_SCREEN_US: u64 = 1_000_000;
+
+/// Largest 29-bit ID; candump IDs above it carry the error/RTR flag bits
+const MAX_EXTENDED_ID: u32 = 0x1FFF_FFFF;
+
+// ─── can_dump_*.txt ─────────────────────────────────────────────────
+
+/// `0.434971 0x090 [8] 00 00 3F D7 00 00 27 FF RxStat=0x00000000`
+pub(super) fn read_dump_txt(text: &str) -> CanLog {
+    let frames = text
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            let seconds: f64 = tokens.next()?.parse().ok()?;
+            let can_id = parse_id(tokens.next()?)?;
+            let len: usize = tokens
+                .next()?
+                .strip_prefix('[')?
+                .strip_suffix(']')?
+                .parse()
+                .ok()?;
+            let data = parse_bytes(tokens.by_ref().take(len))?;
+            if data.len() != len {
+                return None;
+            }
+            let mut frame = CanFrame::new((seconds * 1e6).round() as u64, can_id, data);
+            if let Some(status) = tokens.next().and_then(|t| t.strip_prefix("RxStat=")) {
+                frame.rx_status |= parse_id(status)?;
+            }
+            Some(frame)
+        })
+        .collect();
+    CanLog::new(CaptureFormat::DumpTxt, None, frames)
+}
+
+pub(super) fn write_dump_txt(log: &CanLog) -> String {
+    let ids: BTreeSet<u32> = log.frames.iter().map(|f| f.can_id).collect();
+    let mut out = format!(
+        "# CAN Dump\n\
+         # Duration: {} seconds\n\
+         # Total messages: {}\n\
+         # Unique IDs: {}\n\
+         #\n\
+         # Format: timestamp CAN_ID [len] data RxStatus\n\
+         #\n",
+        log.duration_us().div_ceil(1_000_000),
+        log.frames.len(),
+        ids.len()
+    );
+    for f in &log.frames {
+        let id = if f.is_extended() {
+            format!("0x{:08X}", f.can_id)
+        } else {
+            format!("0x{:03X}", f.can_id)
+        };
+        out.push_str(&format!(
+            "{}.{:06} {} [{}] {} RxStat=0x{:08X}\n",
+            f.timestamp_us / 1_000_000,
+            f.timestamp_us % 1_000_000,
+            id,
+            f.data.len(),
+            hex_bytes(&f.data, " "),
+            f.rx_status
+        ));
+    }
+    out
+}
+
+// ─── Live CAN Monitor ───────────────────────────────────────────────
+
+/// Monitor screens carry no per-frame times: each screen's frames are spread
+/// evenly over its second, so a replay keeps the screen's order without bursting.
+pub(super) fn read_monitor(text: &str) -> CanLog {
+    let mut frames = Vec::new();
+    for (screen, block) in text.split("Live CAN Monitor -").skip(1).enumerate() {
+        let rows: Vec<(u32, Vec<u8>)> = block
+            .lines()
+            .filter_map(|line| {
+                let (id, data) = line.trim().split_once(':')?;
+                Some((parse_id(id.trim())?, parse_bytes(data.split_whitespace())?))
+            })
+            .collect();
+        let start = screen as u64 * MONITOR_SCREEN_US;
+        let step = MONITOR_SCREEN_US / rows.len().max(1) as u64;
+        for (i, (can_id, data)) in rows.into_iter().enumerate() {
+            frames.push(CanFrame::new(start + i as u64 * step, can_id, data));
+        }
+    }
+    CanLog::new(CaptureFormat::Monitor, None, frames)
+}
+
+// ─── Sniff JSON ─────────────────────────────────────────────────────
+
+/// Sniff entries as produced by `capture_raw_can` (`timestamp_ms`, `can_id`
+/// "0x…", `data_hex`)
+#[derive(Serialize, Deserialize)]
+struct SniffEntry {
+    timestamp_ms: u64,
+    can_id: String,
+    data_hex: String,
+    #[serde(default)]
+    data_len: usize,
+}
+
+fn sniff_frames(entries: Vec<SniffEntry>, offset_us: u64) -> Result<Vec<CanFrame>, String> {
+    entries
+        .into_iter()
+        .map(|e| {
+            let can_id = parse_id(&e.can_id).ok_or_else(|| format!("Bad CAN ID '{}'", e.can_id))?;
+            let data = parse_bytes(e.data_hex.split_whitespace())
+                .ok_or_else(|| format!("Bad data '{}'", e.data_hex))?;
+            Ok(CanFrame::new(
+                offset_us + e.timestamp_ms * 1000,
+                can_id,
+                data,
+            ))
+        })
+        .collect()
+}
+
+/// A list of entries, or a sniff result whose after window follows its baseline
+pub(super) fn read_sniff_json(text: &str) -> Result<CanLog, String> {
+    #[derive(Deserialize)]
+    struct SniffResult {
+        baseline_frames: Vec<SniffEntry>,
+        after_frames: Vec<SniffEntry>,
+    }
+    let frames = if text.starts_with('[') {
+        let entries: Vec<SniffEntry> = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        sniff_frames(entries, 0)?
+    } else {
+        let result: SniffResult = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let mut frames = sniff_frames(result.baseline_frames, 0)?;
+        let after_start = frames.last().map_or(0, |f| f.timestamp_us + 1000);
+        frames.extend(sniff_frames(result.after_frames, after_start)?);
+        frames
+    };
+    Ok(CanLog::new(CaptureFormat::SniffJson, None, frames))
+}
+
+pub(super) fn write_sniff_json(log: &CanLog) -> Result<String, String> {
+    let entries: Vec<SniffEntry> = log
+        .frames
+        .iter()
+        .map(|f| SniffEntry {
+            timestamp_ms: f.timestamp_us / 1000,
+            can_id: format!("0x{:03X}", f.can_id),
+            data_hex: hex_bytes(&f.data, " "),
+            data_len: f.data.len(),
+        })
+        .collect();
+    serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())
+}
+
+// ─── SocketCAN candump -l ───────────────────────────────────────────
+
+/// `(1436509052.249713) can0 18DAF160#021003`, optionally followed by a
+/// direction (`R`/`T`). RTR and error frames are skipped; CAN FD (`##`) keeps
+/// its data.
+pub(super) fn read_candump(text: &str) -> Result<CanLog, String> {
+    let frames = text
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            let (secs, frac) = tokens
+                .next()?
+                .strip_prefix('(')?
+                .strip_suffix(')')?
+                .split_once('.')?;
+            let micros: u64 = format!("{:0<6}", &frac[..frac.len().min(6)]).parse().ok()?;
+            let timestamp_us = secs.parse::<u64>().ok()? * 1_000_000 + micros;
+            let _interface = tokens.next()?;
+            let (id, payload) = tokens.next()?.split_once('#')?;
+            let can_id = u32::from_str_radix(id, 16).ok()?;
+            if can_id > MAX_EXTENDED_ID || payload.starts_with('R') {
+                return None;
+            }
+            // CAN FD: one flags nibble before the data
+            let payload = match payload.strip_prefix('#') {
+                Some(fd) => fd.get(1..)?,
+                None => payload,
+            };
+            let data = (0..payload.len() / 2)
+                .map(|i| u8::from_str_radix(&payload[2 * i..2 * i + 2], 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            let tx = tokens.next() == Some("T");
+            Some(
+                CanFrame::new(timestamp_us, can_id, data)
+                    .with_extended(id.len() > 3)
+                    .with_tx(tx),
+            )
+        })
+        .collect();
+    Ok(CanLog::from_absolute(CaptureFormat::Candump, frames))
+}
+
+pub(super) fn write_candump(log: &CanLog) -> String {
+    let start = log.start_unix_us.unwrap_or(0);
+    log.frames
+        .iter()
+        .map(|f| {
+            let t = start + f.timestamp_us;
+            let id = if f.is_extended() {
+                format!("{:08X}", f.can_id)
+            } else {
+                format!("{:03X}", f.can_id)
+            };
+            format!(
+                "({}.{:06}) can0 {}#{}\n",
+                t / 1_000_000,
+                t % 1_000_000,
+                id,
+                hex_bytes(&f.data, "")
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse, CaptureFormat};
+
+    #[test]
+    fn test_parse_dump_txt() {
+        let text = "# CAN Dump\n#\n\
+                    0.434971 0x090 [8] 00 00 3F D7 00 00 27 FF RxStat=0x00000000\n\
+                    0.120000 0x2E0 [1] 00 RxStat=0x00000001\n\
+                    garbage line\n";
+        let log = parse(text).unwrap();
+        assert_eq!(log.format, CaptureFormat::DumpTxt);
+        assert_eq!(log.start_unix_us, None);
+        assert_eq!(log.frames.len(), 2);
+        assert_eq!(log.frames[0].can_id, 0x2E0);
+        assert_eq!(log.frames[0].timestamp_us, 120_000);
+        assert!(log.frames[0].is_tx());
+        assert_eq!(
+            log.frames[1].data,
+            vec![0x00, 0x00, 0x3F, 0xD7, 0x00, 0x00, 0x27, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_parse_monitor_screens() {
+        let text = "Live CAN Monitor (Ctrl+C to stop)\nListening...\n\
+                    \x1b[2J\x1b[H=====\nLive CAN Monitor - 2 unique IDs (Ctrl+C to stop)\n=====\n\
+                    0x030: 38 00 00 00 00 1F FE 40\r\n0x040: 80 00\r\n\
+                    \x1b[2J\x1b[H=====\nLive CAN Monitor - 1 unique IDs (Ctrl+C to stop)\n=====\n\
+                    0x030: 29 00 00 00 00 1F FE 54\r\n";
+        let log = parse(text).unwrap();
+        assert_eq!(log.format, CaptureFormat::Monitor);
+        let times: Vec<u64> = log.frames.iter().map(|f| f.timestamp_us).collect();
+        assert_eq!(times, vec![0, 500_000, 1_000_000]);
+        assert_eq!(log.frames[1].data, vec![0x80, 0x00]);
+        assert_eq!(log.frames[2].data[0], 0x29);
+    }
+
+    #[test]
+    fn test_parse_sniff_json() {
+        let entry = |ms: u64, id: &str| serde_json::json!({ "timestamp_ms": ms, "can_id": id, "data_hex": "01 02" });
+        let list = serde_json::json!([entry(5, "0x400"), entry(0, "0x407")]).to_string();
+        assert_eq!(parse(&list).unwrap().frames[0].can_id, 0x407);
+
+        let result = serde_json::json!({
+            "routine_response": null,
+            "baseline_frames": [entry(0, "0x400"), entry(200, "0x400")],
+            "after_frames": [entry(0, "0x511")],
+            "new_can_ids": [],
+        })
+        .to_string();
+        let log = parse(&result).unwrap();
+        assert_eq!(log.format, CaptureFormat::SniffJson);
+        assert_eq!(log.frames[2].can_id, 0x511);
+        assert_eq!(log.frames[2].timestamp_us, 201_000);
+        assert!(parse("[]").is_err());
+    }
+
+    #[test]
+    fn test_parse_candump() {
+        let text = "(1436509052.249713) can0 090#00003FD7000027FF\n\
+                    (1436509052.250000) can0 18DAF160#021003 T\n\
+                    (1436509052.260000) can0 123#R\n\
+                    (1436509052.270000) can0 20000080#0000000000000000\n\
+                    (1436509052.280000) can0 7E0##1AABB\n";
+        let log = parse(text).unwrap();
+        assert_eq!(log.format, CaptureFormat::Candump);
+        assert_eq!(log.start_unix_us, Some(1_436_509_052_249_713));
+        assert_eq!(log.frames.len(), 3);
+        assert_eq!(log.frames[1].timestamp_us, 287);
+        assert!(log.frames[1].is_extended() && log.frames[1].is_tx());
+        assert_eq!(log.frames[2].data, vec![0xAA, 0xBB]);
+    }
+}
@@ -0,0 +1,182 @@
+//! DES and triple DES (EDE, three keys) on single 8-byte blocks, per FIPS 46-3.
+//! Only used to open SDD EXML files, so no modes or padding here.
+
+/// Initial permutation
+const IP: [u8; 64] = [
+    58, 50, 42, 34, 26, 18, 10, 2, 60, 52, 44, 36, 28, 20, 12, 4, 62, 54, 46, 38, 30, 22, 14, 6,
+    64, 56, 48, 40, 32, 24, 16, 8, 57, 49, 41, 33, 25, 17, 9, 1, 59, 51, 43, 35, 27, 19, 11, 3, 61,
+    53, 45, 37, 29, 21, 13, 5, 63, 55, 47, 39, 31, 23, 15, 7,
+];
+
+/// Final permutation (IP⁻¹)
+const FP: [u8; 64] = [
+    40, 8, 48, 16, 56, 24, 64, 32, 39, 7, 47, 15, 55, 23, 63, 31, 38, 6, 46, 14, 54, 22, 62, 30,
+    37, 5, 45, 13, 53, 21, 61, 29, 36, 4, 44, 12, 52, 20, 60, 28, 35, 3, 43, 11, 51, 19, 59, 27,
+    34, 2, 42, 10, 50, 18, 58, 26, 33, 1, 41, 9, 49, 17, 57, 25,
+];
+
+/// Expansion of the 32-bit half block to 48 bits
+const E: [u8; 48] = [
+    32, 1, 2, 3, 4, 5, 4, 5, 6, 7, 8, 9, 8, 9, 10, 11, 12, 13, 12, 13, 14, 15, 16, 17, 16, 17, 18,
+    19, 20, 21, 20, 21, 22, 23, 24, 25, 24, 25, 26, 27, 28, 29, 28, 29, 30, 31, 32, 1,
+];
+
+/// Permutation of the S-box outputs
+const P: [u8; 32] = [
+    16, 7, 20, 21, 29, 12, 28, 17, 1, 15, 23, 26, 5, 18, 31, 10, 2, 8, 24, 14, 32, 27, 3, 9, 19,
+    13, 30, 6, 22, 11, 4, 25,
+];
+
+/// Key permuted choice 1 (drops the parity bits)
+const PC1: [u8; 56] = [
+    57, 49, 41, 33, 25, 17, 9, 1, 58, 50, 42, 34, 26, 18, 10, 2, 59, 51, 43, 35, 27, 19, 11, 3, 60,
+    52, 44, 36, 63, 55, 47, 39, 31, 23, 15, 7, 62, 54, 46, 38, 30, 22, 14, 6, 61, 53, 45, 37, 29,
+    21, 13, 5, 28, 20, 12, 4,
+];
+
+/// Key permuted choice 2 (56 → 48 bits per round)
+const PC2: [u8; 48] = [
+    14, 17, 11, 24, 1, 5, 3, 28, 15, 6, 21, 10, 23, 19, 12, 4, 26, 8, 16, 7, 27, 20, 13, 2, 41, 52,
+    31, 37, 47, 55, 30, 40, 51, 45, 33, 48, 44, 49, 39, 56, 34, 53, 46, 42, 50, 36, 29, 32,
+];
+
+/// Left rotations of the key halves per round
+const SHIFTS: [u32; 16] = [1, 1, 2, 2, 2, 2, 2, 2, 1, 2, 2, 2, 2, 2, 2, 1];
+
+/// S-boxes, 4 rows of 16 each
+const SBOX: [[u8; 64]; 8] = [
+    [
+        14, 4, 13, 1, 2, 15, 11, 8, 3, 10, 6, 12, 5, 9, 0, 7, 0, 15, 7, 4, 14, 2, 13, 1, 10, 6, 12,
+        11, 9, 5, 3, 8, 4, 1, 14, 8, 13, 6, 2, 11, 15, 12, 9, 7, 3, 10, 5, 0, 15, 12, 8, 2, 4, 9,
+        1, 7, 5, 11, 3, 14, 10, 0, 6, 13,
+    ],
+    [
+        15, 1, 8, 14, 6, 11, 3, 4, 9, 7, 2, 13, 12, 0, 5, 10, 3, 13, 4, 7, 15, 2, 8, 14, 12, 0, 1,
+        10, 6, 9, 11, 5, 0, 14, 7, 11, 10, 4, 13, 1, 5, 8, 12, 6, 9, 3, 2, 15, 13, 8, 10, 1, 3, 15,
+        4, 2, 11, 6, 7, 12, 0, 5, 14, 9,
+    ],
+    [
+        10, 0, 9, 14, 6, 3, 15, 5, 1, 13, 12, 7, 11, 4, 2, 8, 13, 7, 0, 9, 3, 4, 6, 10, 2, 8, 5,
+        14, 12, 11, 15, 1, 13, 6, 4, 9, 8, 15, 3, 0, 11, 1, 2, 12, 5, 10, 14, 7, 1, 10, 13, 0, 6,
+        9, 8, 7, 4, 15, 14, 3, 11, 5, 2, 12,
+    ],
+    [
+        7, 13, 14, 3, 0, 6, 9, 10, 1, 2, 8, 5, 11, 12, 4, 15, 13, 8, 11, 5, 6, 15, 0, 3, 4, 7, 2,
+        12, 1, 10, 14, 9, 10, 6, 9, 0, 12, 11, 7, 13, 15, 1, 3, 14, 5, 2, 8, 4, 3, 15, 0, 6, 10, 1,
+        13, 8, 9, 4, 5, 11, 12, 7, 2, 14,
+    ],
+    [
+        2, 12, 4, 1, 7, 10, 11, 6, 8, 5, 3, 15, 13, 0, 14, 9, 14, 11, 2, 12, 4, 7, 13, 1, 5, 0, 15,
+        10, 3, 9, 8, 6, 4, 2, 1, 11, 10, 13, 7, 8, 15, 9, 12, 5, 6, 3, 0, 14, 11, 8, 12, 7, 1, 14,
+        2, 13, 6, 15, 0, 9, 10, 4, 5, 3,
+    ],
+    [
+        12, 1, 10, 15, 9, 2, 6, 8, 0, 13, 3, 4, 14, 7, 5, 11, 10, 15, 4, 2, 7, 12, 9, 5, 6, 1, 13,
+        14, 0, 11, 3, 8, 9, 14, 15, 5, 2, 8, 12, 3, 7, 0, 4, 10, 1, 13, 11, 6, 4, 3, 2, 12, 9, 5,
+        15, 10, 11, 14, 1, 7, 6, 0, 8, 13,
+    ],
+    [
+        4, 11, 2, 14, 15, 0, 8, 13, 3, 12, 9, 7, 5, 10, 6, 1, 13, 0, 11, 7, 4, 9, 1, 10, 14, 3, 5,
+        12, 2, 15, 8, 6, 1, 4, 11, 13, 12, 3, 7, 14, 10, 15, 6, 8, 0, 5, 9, 2, 6, 11, 13, 8, 1, 4,
+        10, 7, 9, 5, 0, 15, 14, 2, 3, 12,
+    ],
+    [
+        13, 2, 8, 4, 6, 15, 11, 1, 10, 9, 3, 14, 5, 0, 12, 7, 1, 15, 13, 8, 10, 3, 7, 4, 12, 5, 6,
+        11, 0, 14, 9, 2, 7, 11, 4, 1, 9, 12, 14, 2, 0, 6, 10, 13, 15, 3, 5, 8, 2, 1, 14, 7, 4, 10,
+        8, 13, 15, 12, 9, 0, 3, 5, 6, 11,
+    ],
+];
+
+/// Output bit i is input bit `table[i]` (1 = most significant of `in_bits`)
+fn permute(input: u64, in_bits: u32, table: &[u8]) -> u64 {
+    table.iter().fold(0, |out, &pos| {
+        (out << 1) | ((input >> (in_bits - pos as u32)) & 1)
+    })
+}
+
+/// Single DES with its 16 round keys expanded once
+pub struct Des {
+    subkeys: [u64; 16],
+}
+
+impl Des {
+    pub fn new(key: &[u8; 8]) -> Self {
+        let cd = permute(u64::from_be_bytes(*key), 64, &PC1);
+        let (mut c, mut d) = ((cd >> 28) as u32, (cd & 0x0FFF_FFFF) as u32);
+        let rotate = |half: u32, n: u32| ((half << n) | (half >> (28 - n))) & 0x0FFF_FFFF;
+        let mut subkeys = [0; 16];
+        for (subkey, &shift) in subkeys.iter_mut().zip(&SHIFTS) {
+            c = rotate(c, shift);
+            d = rotate(d, shift);
+            *subkey = permute(((c as u64) << 28) | d as u64, 56, &PC2);
+        }
+        Self { subkeys }
+    }
+
+    pub fn encrypt(&self, block: u64) -> u64 {
+        self.crypt(block, self.subkeys.iter())
+    }
+
+    pub fn decrypt(&self, block: u64) -> u64 {
+        self.crypt(block, self.subkeys.iter().rev())
+    }
+
+    fn crypt<'a>(&self, block: u64, subkeys: impl Iterator<Item = &'a u64>) -> u64 {
+        let block = permute(block, 64, &IP);
+        let (mut l, mut r) = ((block >> 32) as u32, block as u32);
+        for &k in subkeys {
+            (l, r) = (r, l ^ feistel(r, k));
+        }
+        permute(((r as u64) << 32) | l as u64, 64, &FP)
+    }
+}
+
+fn feistel(half: u32, subkey: u64) -> u32 {
+    let x = permute(half as u64, 32, &E) ^ subkey;
+    let s = SBOX.iter().enumerate().fold(0u64, |out, (i, sbox)| {
+        let six = (x >> (42 - 6 * i)) & 0x3F;
+        let row = ((six & 0x20) >> 4) | (six & 1);
+        let col = (six >> 1) & 0xF;
+        (out << 4) | sbox[(row * 16 + col) as usize] as u64
+    });
+    permute(s, 32, &P) as u32
+}
+
+/// Triple DES, encrypt-decrypt-encrypt with a 24-byte key K1‖K2‖K3
+pub struct TripleDes([Des; 3]);
+
+impl TripleDes {
+    pub fn new(key: &[u8; 24]) -> Self {
+        let part = |i: usize| Des::new(key[i * 8..i * 8 + 8].try_into().unwrap());
+        Self([part(0), part(1), part(2)])
+    }
+
+    pub fn encrypt(&self, block: u64) -> u64 {
+        self.0[2].encrypt(self.0[1].decrypt(self.0[0].encrypt(block)))
+    }
+
+    pub fn decrypt(&self, block: u64) -> u64 {
+        self.0[0].decrypt(self.0[1].encrypt(self.0[2].decrypt(block)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_des_known_answer() {
+        // The classic worked example (key 133457799BBCDFF1)
+        let des = Des::new(&0x1334_5779_9BBC_DFF1u64.to_be_bytes());
+        assert_eq!(des.encrypt(0x0123_4567_89AB_CDEF), 0x85E8_1354_0F0A_B405);
+        assert_eq!(des.decrypt(0x85E8_1354_0F0A_B405), 0x0123_4567_89AB_CDEF);
+    }
+
+    #[test]
+    fn test_triple_des_matches_single_des_with_equal_keys() {
+        let key = 0x1334_5779_9BBC_DFF1u64.to_be_bytes();
+        let tdes = TripleDes::new(&[key, key, key].concat().try_into().unwrap());
+        assert_eq!(tdes.encrypt(0x0123_4567_89AB_CDEF), 0x85E8_1354_0F0A_B405);
+        assert_eq!(tdes.decrypt(0x85E8_1354_0F0A_B405), 0x0123_4567_89AB_CDEF);
+    }
+}
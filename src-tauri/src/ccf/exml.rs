@@ -0,0 +1,537 @@
+//! SDD EXML files: XML encrypted with 3DES-ECB under one fixed key, PKCS7
+//! padded (what `exml_decrypt.py` undoes). `CCF_DATA_<line>_<year>.exml`
+//! describes the CCF options of one vehicle line and model year;
+//! `parse_ccf_data` reads it the way `parse_ccf.py` did.
+
+use std::collections::BTreeMap;
+
+use super::des::TripleDes;
+use super::{CcfField, CcfOptionDef, CcfTable, CcfTableInfo, OPTION_COUNT};
+
+/// The key SDD uses for every EXML file
+const KEY: &[u8; 24] = b"YmZwZlQrQ3V4dVltNTArWE9s";
+
+const BLOCK: usize = 8;
+
+pub fn decrypt(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.is_empty() || !data.len().is_multiple_of(BLOCK) {
+        return Err(format!(
+            "Not an EXML file ({} bytes is not a whole number of 3DES blocks)",
+            data.len()
+        ));
+    }
+    let cipher = TripleDes::new(KEY);
+    let mut plain: Vec<u8> = data
+        .chunks_exact(BLOCK)
+        .flat_map(|b| {
+            let block = u64::from_be_bytes(b.try_into().unwrap());
+            cipher.decrypt(block).to_be_bytes()
+        })
+        .collect();
+    let pad = plain[plain.len() - 1] as usize;
+    if (1..=BLOCK).contains(&pad)
+        && plain[plain.len() - pad..]
+            .iter()
+            .all(|&b| b as usize == pad)
+    {
+        plain.truncate(plain.len() - pad);
+    }
+    Ok(plain)
+}
+
+pub fn encrypt(plain: &[u8]) -> Vec<u8> {
+    let pad = BLOCK - plain.len() % BLOCK;
+    let padded: Vec<u8> = plain
+        .iter()
+        .copied()
+        .chain(std::iter::repeat_n(pad as u8, pad))
+        .collect();
+    let cipher = TripleDes::new(KEY);
+    padded
+        .chunks_exact(BLOCK)
+        .flat_map(|b| {
+            let block = u64::from_be_bytes(b.try_into().unwrap());
+            cipher.encrypt(block).to_be_bytes()
+        })
+        .collect()
+}
+
+/// Name, vehicle line and model year from an SDD file name such as
+/// `CCF_DATA_X260_201600.exml` (→ "X260_201600", X260, 2016)
+pub fn table_info(file_name: &str) -> CcfTableInfo {
+    let base = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+    let stem = base.split('.').next().unwrap_or(base);
+    let name = stem.strip_prefix("CCF_DATA_").unwrap_or(stem);
+    let (vehicle_line, model_year) = match name.split_once('_') {
+        Some((line, year))
+            if !line.is_empty() && year.len() == 6 && year.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (Some(line.to_string()), year[..4].parse().ok())
+        }
+        _ => (None, None),
+    };
+    CcfTableInfo {
+        name: name.to_string(),
+        vehicle_line,
+        model_year,
+        ..Default::default()
+    }
+}
+
+/// A decode table from a CCF_DATA file, encrypted or already decrypted
+pub fn import(file_name: &str, data: &[u8]) -> Result<CcfTable, String> {
+    let plain;
+    let xml = if looks_like_xml(data) {
+        data
+    } else {
+        plain = decrypt(data)?;
+        if !looks_like_xml(&plain) {
+            return Err(format!("{} does not decrypt to XML", file_name));
+        }
+        &plain
+    };
+    let defs = parse_ccf_data(&decode_text(xml))?;
+    if defs.is_empty() {
+        return Err(format!("{} has no CCF option groups", file_name));
+    }
+    CcfTable::new(table_info(file_name), defs)
+}
+
+/// Option definitions from CCF_DATA XML: every `<group start="ID">` with its
+/// title and the `<option value=…>` labels of its parameters. The layout comes
+/// from a CCFMDFID attribute on the group or a parameter when there is one,
+/// otherwise the option is its own whole byte. Labels that are unresolved
+/// string IDs (`@…`) fall back to the option's name.
+pub fn parse_ccf_data(xml: &str) -> Result<Vec<CcfOptionDef>, String> {
+    let root = parse_xml(xml)?;
+    let mut defs = BTreeMap::new();
+    for group in root.iter("group") {
+        let Some(id) = group
+            .attr("start")
+            .and_then(|s| s.trim().parse::<u16>().ok())
+        else {
+            continue;
+        };
+        if id == 0 || id as usize > OPTION_COUNT {
+            continue;
+        }
+        let group_name = group.attr("name").unwrap_or_default();
+        let name = group
+            .iter("title")
+            .into_iter()
+            .find_map(|title| title.child("tm"))
+            .map(XmlElement::tm_text)
+            .filter(|t| resolved(t))
+            .unwrap_or_else(|| clean_name(group_name));
+
+        let mdfid = std::iter::once(group)
+            .chain(group.iter("parameter"))
+            .find_map(|e| e.attr_ignore_case("ccfmdfid"))
+            .and_then(parse_int)
+            .and_then(|m| u16::try_from(m).ok());
+        let (field, mdfid) = match mdfid.and_then(|m| Some((CcfField::from_mdfid(m)?, m))) {
+            Some((field, m)) => (field, Some(format!("0x{:04X}", m))),
+            None => (CcfField::full_byte(id), None),
+        };
+
+        let mut values = BTreeMap::new();
+        for option in group
+            .iter("parameter")
+            .iter()
+            .flat_map(|p| p.iter("option"))
+        {
+            let Some(raw) = option.attr("value").filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            let Some(value) = parse_int(raw).and_then(|v| u8::try_from(v).ok()) else {
+                continue;
+            };
+            if value > field.max_value() {
+                continue;
+            }
+            let label = option
+                .child("tm")
+                .map(XmlElement::tm_text)
+                .filter(|t| resolved(t))
+                .unwrap_or_else(|| option.attr("name").unwrap_or(raw).to_string());
+            values.insert(value, label);
+        }
+
+        defs.insert(
+            id,
+            CcfOptionDef {
+                id,
+                name,
+                group: group_name.to_string(),
+                mdfid,
+                field,
+                values,
+            },
+        );
+    }
+    Ok(defs.into_values().collect())
+}
+
+fn resolved(text: &str) -> bool {
+    !text.is_empty() && !text.starts_with('@')
+}
+
+/// `GROUP_CCF_EUCD_DOORS` → "Doors"
+fn clean_name(group: &str) -> String {
+    let name = ["GROUP_CCF_EUCD_", "GROUP_EUCD_CCF_", "GROUP_CCF_"]
+        .iter()
+        .fold(group.to_string(), |n, prefix| n.replace(prefix, ""));
+    let mut prev_letter = false;
+    name.chars()
+        .map(|c| {
+            let c = if c == '_' { ' ' } else { c };
+            let out = if prev_letter {
+                c.to_ascii_lowercase()
+            } else {
+                c.to_ascii_uppercase()
+            };
+            prev_letter = c.is_alphabetic();
+            out
+        })
+        .collect()
+}
+
+/// `0x`-prefixed hex or decimal
+fn parse_int(s: &str) -> Option<u32> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn looks_like_xml(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    data.starts_with(b"\xFF\xFE<")
+        || data.starts_with(b"\xFE\xFF\0<")
+        || data.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<')
+}
+
+/// UTF-8, or UTF-16 when the file starts with its BOM
+fn decode_text(data: &[u8]) -> String {
+    let utf16 = |data: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = data.chunks_exact(2).map(|c| from([c[0], c[1]])).collect();
+        String::from_utf16_lossy(&units)
+    };
+    if let Some(rest) = data.strip_prefix(b"\xFF\xFE") {
+        utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = data.strip_prefix(b"\xFE\xFF") {
+        utf16(rest, u16::from_be_bytes)
+    } else {
+        let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+        String::from_utf8_lossy(data).into_owned()
+    }
+}
+
+// ─── XML ─────────────────────────────────────────────────────────────
+
+/// An element with its attributes, child elements and direct text; enough
+/// XML for SDD's data files (no namespaces or DTDs)
+#[derive(Debug, Default)]
+struct XmlElement {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<XmlElement>,
+    text: String,
+}
+
+impl XmlElement {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn attr_ignore_case(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// This element and its descendants called `name`, in document order
+    fn iter<'a>(&'a self, name: &str) -> Vec<&'a XmlElement> {
+        let mut found = Vec::new();
+        let mut pending = vec![self];
+        while let Some(e) = pending.pop() {
+            if e.name == name {
+                found.push(e);
+            }
+            pending.extend(e.children.iter().rev());
+        }
+        found
+    }
+
+    /// `<tm>` text, or its string `id` when it has none
+    fn tm_text(&self) -> String {
+        let text = self.text.trim();
+        if text.is_empty() {
+            self.attr("id").unwrap_or_default().trim().to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn parse_xml(text: &str) -> Result<XmlElement, String> {
+    // The document itself sits at the bottom of the stack
+    let mut stack = vec![XmlElement::default()];
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after
+                .split_once("-->")
+                .ok_or("XML: unterminated comment")?
+                .1;
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let (data, after) = after.split_once("]]>").ok_or("XML: unterminated CDATA")?;
+            stack.last_mut().unwrap().text.push_str(data);
+            rest = after;
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = rest
+                .split_once('>')
+                .ok_or("XML: unterminated declaration")?
+                .1;
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let (name, after) = after.split_once('>').ok_or("XML: unterminated end tag")?;
+            let name = name.trim();
+            if stack.len() < 2 {
+                return Err(format!("XML: unexpected </{}>", name));
+            }
+            let element = stack.pop().unwrap();
+            if element.name != name {
+                return Err(format!("XML: <{}> closed by </{}>", element.name, name));
+            }
+            stack.last_mut().unwrap().children.push(element);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let end = tag_end(after).ok_or("XML: unterminated tag")?;
+            let (tag, empty) = match after[..end].strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (&after[..end], false),
+            };
+            let element = parse_tag(tag)?;
+            if empty {
+                stack.last_mut().unwrap().children.push(element);
+            } else {
+                stack.push(element);
+            }
+            rest = &after[end + 1..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            stack
+                .last_mut()
+                .unwrap()
+                .text
+                .push_str(&unescape(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+    if stack.len() > 1 {
+        return Err(format!(
+            "XML: <{}> is never closed",
+            stack.last().unwrap().name
+        ));
+    }
+    let document = stack.pop().unwrap();
+    document
+        .children
+        .into_iter()
+        .next()
+        .ok_or_else(|| "XML: no root element".to_string())
+}
+
+/// Index of the `>` closing a tag, skipping quoted attribute values
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `name key="value" …` of a start tag
+fn parse_tag(tag: &str) -> Result<XmlElement, String> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut element = XmlElement {
+        name: tag[..name_end].to_string(),
+        ..Default::default()
+    };
+    if element.name.is_empty() {
+        return Err("XML: tag without a name".into());
+    }
+    let bad = |what: &str| format!("XML: {} in <{}>", what, element.name);
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let (key, after) = rest
+            .split_once('=')
+            .ok_or_else(|| bad("attribute without a value"))?;
+        let after = after.trim_start();
+        let quote = after
+            .chars()
+            .next()
+            .filter(|&c| c == '"' || c == '\'')
+            .ok_or_else(|| bad("unquoted attribute"))?;
+        let (value, after) = after[1..]
+            .split_once(quote)
+            .ok_or_else(|| bad("unterminated attribute"))?;
+        element
+            .attrs
+            .push((key.trim().to_string(), unescape(value)));
+        rest = after.trim_start();
+    }
+    Ok(element)
+}
+
+/// Predefined and numeric character references; anything else is left as is
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                entity => {
+                    let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => entity.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CCF_DATA: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- trimmed CCF_DATA layout -->
+<ccfdata>
+  <block name="CCF">
+    <group name="GROUP_CCF_EUCD_DOORS" start="1">
+      <title><tm id="@T_DOORS">Doors</tm></title>
+      <parameter name="DOORS">
+        <option value="0x02" name="5_DR"><tm>5 door</tm></option>
+        <option value="3" name="4_DR"><tm id="@T_4DR"/></option>
+        <option value="x" name="BAD"/>
+        <option value="300" name="TOO_BIG"/>
+      </parameter>
+    </group>
+    <group name="GROUP_CCF_FRONT_DISPLAY_VARIANT" start="467">
+      <title><tm id="@T_FDV"/></title>
+      <parameter name="FDV" CCFMDFID="0x4363">
+        <option value="5" name="10_INCH_DUAL_VIEW"><tm>10&quot; dual &amp; view</tm></option>
+        <option value="0x20" name="WIDER_THAN_THE_FIELD"/>
+      </parameter>
+    </group>
+    <group name="GROUP_CCF_BEYOND_THE_TABLE" start="900"/>
+    <group name="NO_START"/>
+  </block>
+</ccfdata>
+"#;
+
+    #[test]
+    fn test_decrypt_known_answer() {
+        // openssl enc -des-ede3 -K <KEY as hex> (PKCS7 padded)
+        let encrypted = [
+            0x13, 0xdf, 0x22, 0x66, 0xe9, 0xe2, 0x5f, 0x23, 0x67, 0x7d, 0xb0, 0xf7, 0xb4, 0x28,
+            0xba, 0xad, 0x7f, 0x0b, 0xee, 0xc0, 0x9f, 0x82, 0x80, 0x4d,
+        ];
+        assert_eq!(decrypt(&encrypted).unwrap(), b"<group start=\"1\"/>");
+        assert_eq!(encrypt(b"<group start=\"1\"/>"), encrypted);
+        assert!(decrypt(&encrypted[..20]).is_err());
+    }
+
+    #[test]
+    fn test_parse_ccf_data() {
+        let defs = parse_ccf_data(CCF_DATA).unwrap();
+        assert_eq!(defs.len(), 2);
+
+        let doors = &defs[0];
+        assert_eq!((doors.id, doors.name.as_str()), (1, "Doors"));
+        assert_eq!(doors.group, "GROUP_CCF_EUCD_DOORS");
+        assert_eq!(doors.field, CcfField::full_byte(1));
+        let labels: Vec<(u8, &str)> = doors.values.iter().map(|(&v, l)| (v, l.as_str())).collect();
+        assert_eq!(labels, [(2, "5 door"), (3, "4_DR")]);
+
+        let display = &defs[1];
+        assert_eq!(display.name, "Front Display Variant");
+        assert_eq!(display.mdfid.as_deref(), Some("0x4363"));
+        assert_eq!(display.field.mask(), 0x0F);
+        assert_eq!(display.label(5), Some("10\" dual & view"));
+        assert_eq!(display.values.len(), 1);
+    }
+
+    #[test]
+    fn test_import_encrypted_or_plain() {
+        let name = "C:\\SDD\\Xml\\CCF_DATA_X761_201800.exml";
+        let table = import(name, &encrypt(CCF_DATA.as_bytes())).unwrap();
+        let info = table.info();
+        assert_eq!(info.name, "X761_201800");
+        assert_eq!(info.vehicle_line.as_deref(), Some("X761"));
+        assert_eq!(info.model_year, Some(2018));
+        assert_eq!(info.option_count, 2);
+        assert_eq!(table.find("front display variant"), Ok(467));
+
+        let plain = import("CCF_DATA_X761_201800.exml-decrypted", CCF_DATA.as_bytes()).unwrap();
+        assert_eq!(plain, table);
+        assert_eq!(table_info("custom.exml").name, "custom");
+        assert_eq!(table_info("custom.exml").model_year, None);
+
+        assert!(import(name, &encrypt(b"not xml")).is_err());
+        assert!(import(name, &encrypt(b"<ccfdata/>")).is_err());
+    }
+
+    #[test]
+    fn test_xml_reader() {
+        let root = parse_xml("<a x='1 > 0'><b>t&#65;&#x42;</b><!-- c --><b/><![CDATA[<raw>]]></a>")
+            .unwrap();
+        assert_eq!(root.attr("x"), Some("1 > 0"));
+        assert_eq!(root.iter("b").len(), 2);
+        assert_eq!(root.child("b").unwrap().text, "tAB");
+        assert_eq!(root.text, "<raw>");
+        assert!(parse_xml("<a><b></a>").is_err());
+        assert!(parse_xml("<a>").is_err());
+        assert!(parse_xml("text only").is_err());
+        assert_eq!(clean_name("GROUP_CCF_3RD_ROW_SEAT"), "3Rd Row Seat");
+        assert_eq!(decode_text(b"\xFF\xFE<\0a\0/\0>\0"), "<a/>");
+    }
+}
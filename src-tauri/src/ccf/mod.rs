@@ -0,0 +1,532 @@
+//! Car configuration file (CCF) as returned by GWM DID 0xEE00 and BCM DID 0xDE00.
+//!
+//! The payload is a VDF image: an 8-byte header (CRC-16, total length, format
+//! and variant words) followed by a section table whose first entry describes
+//! the header itself, then 8-byte option blocks `[block_id][7 option bytes]`
+//! in whatever order the ECU stored them. Everything after the option blocks
+//! (the 0x12 section, part numbers, 0xFF fill) is kept verbatim, so
+//! `Ccf::parse(raw)?.to_bytes() == raw` for any payload that parses.
+//!
+//! What the options mean lives in the decode table, see [`CcfTable`]; tables
+//! for other vehicle lines are imported from SDD EXML (`exml`) into
+//! [`CcfTables`]. Which option values go together is checked by the
+//! declarative rules in `rules`.
+
+mod des;
+pub mod exml;
+mod registry;
+mod rules;
+mod table;
+
+pub use registry::{vin_model_year, CcfTables};
+pub use rules::{
+    CcfRule, CcfRuleFile, CcfRules, RuleCheck, RuleFinding, RuleInput, RuleReport, RuleValue,
+    Severity, SkippedRule,
+};
+pub use table::{CcfOptionChange, CcfOptionDef, CcfTable, CcfTableInfo};
+
+use serde::{Deserialize, Serialize};
+
+/// Number of CCF options (112 blocks × 7 bytes)
+pub const OPTION_COUNT: usize = 784;
+
+/// Option bytes carried by one block
+pub const BLOCK_DATA_LEN: usize = 7;
+
+/// Block ID byte + option bytes
+const BLOCK_LEN: usize = 1 + BLOCK_DATA_LEN;
+
+/// Checksum, length, format and variant words ahead of the section table
+const FIXED_HEADER_LEN: usize = 8;
+
+/// `[id][version][end: u16 BE]`
+const SECTION_ENTRY_LEN: usize = 4;
+
+/// Section table entry describing the header itself
+pub const SECTION_HEADER: u8 = 0x01;
+
+/// Section holding the option blocks
+pub const SECTION_OPTIONS: u8 = 0x11;
+
+/// The VIN is stored as ASCII in options 256..=272
+const VIN_OPTION: u16 = 256;
+const VIN_LEN: usize = 17;
+
+// ─── Layout ──────────────────────────────────────────────────────────
+
+/// One section table entry; `end` is the offset just past the section
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcfSection {
+    pub id: u8,
+    pub version: u8,
+    pub end: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcfHeader {
+    /// CRC-16/CCITT-FALSE over bytes `2..length`, as stored
+    pub checksum: u16,
+    /// Bytes covered by the image, header included (0xFF fill excluded)
+    pub length: u16,
+    pub format: u16,
+    pub variant: u16,
+    pub sections: Vec<CcfSection>,
+}
+
+impl CcfHeader {
+    /// Size of the encoded header, i.e. the end of the header section
+    pub fn size(&self) -> usize {
+        FIXED_HEADER_LEN + self.sections.len() * SECTION_ENTRY_LEN
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.checksum.to_be_bytes());
+        out.extend_from_slice(&self.length.to_be_bytes());
+        out.extend_from_slice(&self.format.to_be_bytes());
+        out.extend_from_slice(&self.variant.to_be_bytes());
+        for s in &self.sections {
+            out.push(s.id);
+            out.push(s.version);
+            out.extend_from_slice(&s.end.to_be_bytes());
+        }
+    }
+}
+
+/// Block `id` carries options `(id - 1) * 7 + 1 ..= id * 7`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcfBlock {
+    pub id: u8,
+    pub data: [u8; BLOCK_DATA_LEN],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum ChecksumStatus {
+    Valid,
+    Mismatch {
+        expected: u16,
+    },
+    /// The read stopped before `length`, so the CRC cannot be checked
+    Truncated,
+}
+
+// ─── Option fields ───────────────────────────────────────────────────
+
+/// Where an option's value sits: `width` bits from `bit` up in one byte of the
+/// flat option array. High-numbered options pack several values per byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcfField {
+    /// Index into `Ccf::options` (option ID - 1 for the option's own byte)
+    pub byte: u16,
+    pub bit: u8,
+    pub width: u8,
+}
+
+impl CcfField {
+    /// An unpacked option: its whole own byte
+    pub fn full_byte(option_id: u16) -> Self {
+        Self {
+            byte: option_id.saturating_sub(1),
+            bit: 0,
+            width: 8,
+        }
+    }
+
+    /// Decode an SDD CCFMDFID: block ID in the high byte, then nibbles for the
+    /// position in the block (data byte + 2) and the value's top bit; values
+    /// start at bit 0, e.g. 0x4363 = block 0x43, data byte 4, bits 3:0
+    pub fn from_mdfid(mdfid: u16) -> Option<Self> {
+        let block = (mdfid >> 8) as usize;
+        let pos = ((mdfid >> 4) & 0xF) as usize;
+        let top = (mdfid & 0xF) as u8;
+        if block == 0
+            || block > OPTION_COUNT / BLOCK_DATA_LEN
+            || !(2..2 + BLOCK_DATA_LEN).contains(&pos)
+            || top > 7
+        {
+            return None;
+        }
+        Some(Self {
+            byte: ((block - 1) * BLOCK_DATA_LEN + pos - 2) as u16,
+            bit: 0,
+            width: top + 1,
+        })
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (self.byte as usize) < OPTION_COUNT && self.width > 0 && self.bit + self.width <= 8
+    }
+
+    /// Bits of the raw byte holding the value
+    pub fn mask(&self) -> u8 {
+        (((1u16 << self.width) - 1) << self.bit) as u8
+    }
+
+    pub fn max_value(&self) -> u8 {
+        self.mask() >> self.bit
+    }
+
+    pub fn extract(&self, raw: u8) -> u8 {
+        (raw & self.mask()) >> self.bit
+    }
+
+    /// `raw` with the field set to `value`, other packed bits kept
+    pub fn insert(&self, raw: u8, value: u8) -> u8 {
+        (raw & !self.mask()) | ((value << self.bit) & self.mask())
+    }
+}
+
+// ─── CCF image ───────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ccf {
+    pub header: CcfHeader,
+    /// Option blocks in stored order
+    pub blocks: Vec<CcfBlock>,
+    /// Bytes after the option blocks up to `length` (or the end of a short read)
+    pub tail: Vec<u8>,
+    /// Fill after `length`
+    pub padding: Vec<u8>,
+}
+
+impl Ccf {
+    /// Parse a DID 0xEE00/0xDE00 payload (without the `62 EE 00` prefix)
+    pub fn parse(raw: &[u8]) -> Result<Self, String> {
+        if raw.len() < FIXED_HEADER_LEN + SECTION_ENTRY_LEN {
+            return Err(format!("CCF too short: {} bytes", raw.len()));
+        }
+        let word = |at: usize| u16::from_be_bytes([raw[at], raw[at + 1]]);
+        let entry = |at: usize| CcfSection {
+            id: raw[at],
+            version: raw[at + 1],
+            end: word(at + 2),
+        };
+
+        let first = entry(FIXED_HEADER_LEN);
+        let header_len = first.end as usize;
+        if first.id != SECTION_HEADER
+            || header_len < FIXED_HEADER_LEN + SECTION_ENTRY_LEN
+            || !(header_len - FIXED_HEADER_LEN).is_multiple_of(SECTION_ENTRY_LEN)
+            || header_len > raw.len()
+        {
+            return Err(format!(
+                "Not a VDF CCF: header section {:02X} ends at {}",
+                first.id, header_len
+            ));
+        }
+        let header = CcfHeader {
+            checksum: word(0),
+            length: word(2),
+            format: word(4),
+            variant: word(6),
+            sections: (FIXED_HEADER_LEN..header_len)
+                .step_by(SECTION_ENTRY_LEN)
+                .map(entry)
+                .collect(),
+        };
+
+        // Option blocks must directly follow the header
+        let options = header
+            .sections
+            .get(1)
+            .filter(|s| s.id == SECTION_OPTIONS)
+            .ok_or("VDF CCF has no option section after the header")?;
+        let blocks_end = options.end as usize;
+        if blocks_end < header_len || !(blocks_end - header_len).is_multiple_of(BLOCK_LEN) {
+            return Err(format!("Option section ends at odd offset {}", blocks_end));
+        }
+        if blocks_end > raw.len() {
+            return Err(format!(
+                "CCF truncated inside option blocks ({} of {} bytes)",
+                raw.len(),
+                blocks_end
+            ));
+        }
+        if (header.length as usize) < blocks_end {
+            return Err(format!(
+                "CCF length {} ends before the option blocks ({})",
+                header.length, blocks_end
+            ));
+        }
+
+        let blocks = raw[header_len..blocks_end]
+            .chunks_exact(BLOCK_LEN)
+            .map(|b| {
+                let mut data = [0u8; BLOCK_DATA_LEN];
+                data.copy_from_slice(&b[1..]);
+                CcfBlock { id: b[0], data }
+            })
+            .collect();
+        let payload_end = raw.len().min(header.length as usize);
+
+        Ok(Self {
+            header,
+            blocks,
+            tail: raw[blocks_end..payload_end].to_vec(),
+            padding: raw[payload_end..].to_vec(),
+        })
+    }
+
+    /// Re-encode; byte-identical to the parsed payload unless modified
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.encoded_len() + self.padding.len());
+        self.header.write(&mut out);
+        for b in &self.blocks {
+            out.push(b.id);
+            out.extend_from_slice(&b.data);
+        }
+        out.extend_from_slice(&self.tail);
+        out.extend_from_slice(&self.padding);
+        out
+    }
+
+    /// Bytes up to `length` that are actually present
+    fn encoded_len(&self) -> usize {
+        self.header.size() + self.blocks.len() * BLOCK_LEN + self.tail.len()
+    }
+
+    /// True when the payload stops before `length` (e.g. a capped DID read)
+    pub fn is_truncated(&self) -> bool {
+        self.encoded_len() < self.header.length as usize
+    }
+
+    /// Error for a truncated image: its CRC can't be recomputed, and writing it back
+    /// would cut the module's CCF short
+    pub fn check_complete(&self) -> Result<(), String> {
+        if self.is_truncated() {
+            return Err(format!(
+                "CCF image is truncated ({} of {} bytes)",
+                self.encoded_len(),
+                self.header.length
+            ));
+        }
+        Ok(())
+    }
+
+    /// CRC the header should carry, or None for a truncated payload
+    pub fn compute_checksum(&self) -> Option<u16> {
+        if self.is_truncated() {
+            return None;
+        }
+        let bytes = self.to_bytes();
+        Some(crc16_ccitt(0xFFFF, &bytes[2..self.header.length as usize]))
+    }
+
+    pub fn checksum_status(&self) -> ChecksumStatus {
+        match self.compute_checksum() {
+            None => ChecksumStatus::Truncated,
+            Some(c) if c == self.header.checksum => ChecksumStatus::Valid,
+            Some(expected) => ChecksumStatus::Mismatch { expected },
+        }
+    }
+
+    /// Recompute the header CRC; a truncated payload keeps its stored value
+    pub fn update_checksum(&mut self) {
+        if let Some(c) = self.compute_checksum() {
+            self.header.checksum = c;
+        }
+    }
+
+    /// (block index, byte) holding 1-based option `id`
+    fn locate(&self, id: u16) -> Option<(usize, usize)> {
+        if id == 0 || id as usize > OPTION_COUNT {
+            return None;
+        }
+        let idx = id as usize - 1;
+        let block_id = (idx / BLOCK_DATA_LEN + 1) as u8;
+        let pos = self.blocks.iter().position(|b| b.id == block_id)?;
+        Some((pos, idx % BLOCK_DATA_LEN))
+    }
+
+    /// Raw byte of 1-based option `id`
+    pub fn option(&self, id: u16) -> Option<u8> {
+        self.locate(id).map(|(b, i)| self.blocks[b].data[i])
+    }
+
+    /// Set the raw byte of option `id` and keep the checksum in step
+    pub fn set_option(&mut self, id: u16, value: u8) -> Result<(), String> {
+        self.check_complete()?;
+        let (b, i) = self
+            .locate(id)
+            .ok_or_else(|| format!("CCF option {} is not in this image", id))?;
+        self.blocks[b].data[i] = value;
+        self.update_checksum();
+        Ok(())
+    }
+
+    /// Flat option array, index = option id - 1; missing blocks read as 0
+    pub fn options(&self) -> Vec<u8> {
+        let mut options = vec![0u8; OPTION_COUNT];
+        for b in self.blocks.iter().filter(|b| b.id != 0) {
+            let start = (b.id as usize - 1) * BLOCK_DATA_LEN;
+            if start + BLOCK_DATA_LEN <= OPTION_COUNT {
+                options[start..start + BLOCK_DATA_LEN].copy_from_slice(&b.data);
+            }
+        }
+        options
+    }
+
+    /// Value of a (possibly packed) option field
+    pub fn field(&self, field: CcfField) -> Option<u8> {
+        self.option(field.byte + 1).map(|raw| field.extract(raw))
+    }
+
+    /// Set a field's value, keeping the other bits of its byte
+    pub fn set_field(&mut self, field: CcfField, value: u8) -> Result<(), String> {
+        if !field.is_valid() || value > field.max_value() {
+            return Err(format!(
+                "0x{:02X} does not fit CCF byte {} bits {}..{}",
+                value,
+                field.byte,
+                field.bit,
+                field.bit + field.width
+            ));
+        }
+        let raw = self
+            .option(field.byte + 1)
+            .ok_or_else(|| format!("CCF byte {} is not in this image", field.byte))?;
+        self.set_option(field.byte + 1, field.insert(raw, value))
+    }
+
+    /// VIN the CCF was built for, if the options hold a plausible one
+    pub fn vin(&self) -> Option<String> {
+        let vin = (VIN_OPTION..VIN_OPTION + VIN_LEN as u16)
+            .map(|id| self.option(id).map(char::from))
+            .collect::<Option<String>>()?;
+        vin.chars()
+            .all(|c| c.is_ascii_alphanumeric())
+            .then_some(vin)
+    }
+}
+
+/// CRC-16 with poly 0x1021; init 0xFFFF gives CCITT-FALSE
+fn crc16_ccitt(init: u16, data: &[u8]) -> u16 {
+    let mut crc = init;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static GWM: &[u8] = include_bytes!("../../assets/gwm_ccf.bin");
+    static BCM: &[u8] = include_bytes!("../../assets/bcm_ccf.bin");
+
+    #[test]
+    fn test_round_trip_bundled_payloads() {
+        let gwm = Ccf::parse(GWM).unwrap();
+        assert_eq!(gwm.to_bytes(), GWM);
+        assert_eq!(gwm.header.length, 0x464);
+        assert_eq!(gwm.header.variant, 0x0040);
+        assert_eq!(
+            gwm.header
+                .sections
+                .iter()
+                .map(|s| (s.id, s.end))
+                .collect::<Vec<_>>(),
+            vec![(0x01, 0x14), (0x11, 0x394), (0x12, 0x404)]
+        );
+        assert_eq!(gwm.blocks.len(), 112);
+        // Stored order is not block-ID order
+        let ids: Vec<u8> = gwm.blocks.iter().take(4).map(|b| b.id).collect();
+        assert_eq!(ids, vec![0x01, 0x02, 0x25, 0x44]);
+        assert_eq!(gwm.checksum_status(), ChecksumStatus::Valid);
+        assert_eq!(gwm.padding.len(), GWM.len() - 0x464);
+        assert!(gwm.padding.iter().all(|&b| b == 0xFF));
+
+        // The bench BCM read was capped at 1024 bytes, short of its 0x464 length
+        let bcm = Ccf::parse(BCM).unwrap();
+        assert_eq!(bcm.to_bytes(), BCM);
+        assert_eq!(bcm.header.variant, 0x0100);
+        assert!(bcm.is_truncated());
+        assert_eq!(bcm.checksum_status(), ChecksumStatus::Truncated);
+        assert_eq!(bcm.options(), gwm.options());
+        let err = bcm.clone().set_option(467, 0x15).unwrap_err();
+        assert!(err.contains("1024 of 1124"), "{}", err);
+    }
+
+    #[test]
+    fn test_option_access() {
+        let ccf = Ccf::parse(GWM).unwrap();
+        // Option 467 (front display variant) lives in block 0x43, byte 4
+        assert_eq!(ccf.option(467), Some(0x14));
+        assert_eq!(ccf.options()[466], 0x14);
+        assert_eq!(ccf.option(0), None);
+        assert_eq!(ccf.option(785), None);
+        assert_eq!(ccf.vin().as_deref(), Some("SAJBL4BVXGCY16353"));
+    }
+
+    #[test]
+    fn test_packed_fields() {
+        // The CCFMDFIDs read from SDD for the display options
+        let front_display = CcfField::from_mdfid(0x4363).unwrap();
+        assert_eq!(
+            front_display,
+            CcfField {
+                byte: 466,
+                bit: 0,
+                width: 4
+            }
+        );
+        assert_eq!(CcfField::from_mdfid(0x4122).unwrap().width, 3);
+        assert_eq!(CcfField::from_mdfid(0x5983).unwrap().byte, 622);
+        assert_eq!(CcfField::from_mdfid(0x0022), None);
+        assert_eq!(CcfField::from_mdfid(0x4393), None);
+
+        let mut ccf = Ccf::parse(GWM).unwrap();
+        assert_eq!(ccf.field(front_display), Some(0x04));
+        assert_eq!(ccf.field(CcfField::full_byte(467)), Some(0x14));
+        ccf.set_field(front_display, 0x05).unwrap();
+        assert_eq!(ccf.option(467), Some(0x15));
+        assert!(ccf.set_field(front_display, 0x10).is_err());
+
+        // A field in the high nibble leaves the low one alone
+        let high = CcfField {
+            byte: 466,
+            bit: 4,
+            width: 4,
+        };
+        assert_eq!(ccf.field(high), Some(0x01));
+        ccf.set_field(high, 0x0A).unwrap();
+        assert_eq!(ccf.option(467), Some(0xA5));
+        assert!(!CcfField {
+            byte: 0,
+            bit: 6,
+            width: 4
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn test_set_option_re_encodes() {
+        let mut ccf = Ccf::parse(GWM).unwrap();
+        ccf.set_option(467, 0x15).unwrap();
+        assert!(ccf.set_option(785, 0).is_err());
+
+        let bytes = ccf.to_bytes();
+        let changed: Vec<usize> = (0..bytes.len()).filter(|&i| bytes[i] != GWM[i]).collect();
+        // Both CRC bytes change, plus the option byte at block 0x43's offset
+        assert_eq!(changed, vec![0, 1, 0x1d4 + 1 + 4]);
+
+        let again = Ccf::parse(&bytes).unwrap();
+        assert_eq!(again.option(467), Some(0x15));
+        assert_eq!(again.checksum_status(), ChecksumStatus::Valid);
+        assert!(Ccf::parse(&GWM[..0x100]).is_err());
+
+        // A capped read has no CRC to recompute, so it can't be edited
+        let mut short = Ccf::parse(&GWM[..1024]).unwrap();
+        assert!(short.set_option(467, 0x15).is_err());
+        assert_eq!(short.to_bytes(), &GWM[..1024]);
+        assert!(Ccf::parse(&[0u8; 32]).is_err());
+    }
+}
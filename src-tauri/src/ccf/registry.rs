@@ -0,0 +1,268 @@
+//! Decode tables available to a session: the built-in one plus any imported
+//! from SDD EXML, each kept as `<name>.json` in one directory. The table for a
+//! vehicle is picked from the VIN its CCF carries.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::{CcfOptionDef, CcfTable, CcfTableInfo};
+
+/// An imported table as saved on disk
+#[derive(Serialize, Deserialize)]
+struct StoredTable {
+    info: CcfTableInfo,
+    options: BTreeMap<u16, CcfOptionDef>,
+}
+
+pub struct CcfTables {
+    dir: PathBuf,
+    /// Loaded from `dir` on first use
+    imported: Mutex<Option<Vec<Arc<CcfTable>>>>,
+}
+
+impl CcfTables {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            imported: Mutex::new(None),
+        }
+    }
+
+    fn with_imported<T>(&self, f: impl FnOnce(&mut Vec<Arc<CcfTable>>) -> T) -> T {
+        let mut imported = self.imported.lock().unwrap();
+        f(imported.get_or_insert_with(|| self.load()))
+    }
+
+    /// Tables saved by earlier imports; unreadable files are skipped
+    fn load(&self) -> Vec<Arc<CcfTable>> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut tables: Vec<Arc<CcfTable>> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|x| x == "json"))
+            .filter_map(|path| {
+                let loaded = std::fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+                    .and_then(|t: StoredTable| CcfTable::new(t.info, t.options.into_values()));
+                match loaded {
+                    Ok(table) => Some(Arc::new(table)),
+                    Err(e) => {
+                        log::warn!("Skipping CCF table {}: {}", path.display(), e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        tables.sort_by(|a, b| a.info().name.cmp(&b.info().name));
+        tables
+    }
+
+    /// The built-in table first, then imports by name
+    pub fn list(&self) -> Vec<CcfTableInfo> {
+        let mut infos = vec![CcfTable::builtin().info().clone()];
+        self.with_imported(|imported| {
+            infos.extend(imported.iter().map(|t| t.info().clone()));
+        });
+        infos
+    }
+
+    /// The built-in table and every import
+    pub fn tables(&self) -> Vec<Arc<CcfTable>> {
+        let mut tables = vec![CcfTable::builtin()];
+        self.with_imported(|imported| tables.extend(imported.iter().cloned()));
+        tables
+    }
+
+    /// By name; an import shadows the built-in table of the same name
+    pub fn get(&self, name: &str) -> Option<Arc<CcfTable>> {
+        let builtin = CcfTable::builtin();
+        self.with_imported(|imported| imported.iter().find(|t| t.info().name == name).cloned())
+            .or_else(|| (builtin.info().name == name).then_some(builtin))
+    }
+
+    /// Save `table` (replacing an import of the same name) and offer it from now on.
+    /// It must be tied to VIN prefixes: nothing maps a table's vehicle line to VINs.
+    pub fn import(&self, table: CcfTable) -> Result<CcfTableInfo, String> {
+        let info = table.info().clone();
+        if info.vin_prefixes.iter().all(|p| p.is_empty()) {
+            return Err(format!(
+                "Give the VIN prefixes of the vehicles {} is for",
+                info.name
+            ));
+        }
+        let file_name: String = info
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        if file_name.is_empty() {
+            return Err("CCF table has no name".into());
+        }
+        let stored = StoredTable {
+            info: info.clone(),
+            options: table.iter().map(|d| (d.id, d.clone())).collect(),
+        };
+        let json = serde_json::to_string_pretty(&stored).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Cannot create {}: {}", self.dir.display(), e))?;
+        let path = self.dir.join(format!("{}.json", file_name));
+        std::fs::write(&path, json)
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+
+        self.with_imported(|imported| {
+            imported.retain(|t| t.info().name != info.name);
+            imported.push(Arc::new(table));
+            imported.sort_by(|a, b| a.info().name.cmp(&b.info().name));
+        });
+        Ok(info)
+    }
+
+    /// The table for the vehicle with `vin`: an import only applies to VINs
+    /// starting with one of its prefixes (longest prefix first), then the model
+    /// year closest to the VIN's wins, preferring older tables over newer ones.
+    /// Without a VIN or a matching import, the built-in table.
+    pub fn for_vin(&self, vin: Option<&str>) -> Arc<CcfTable> {
+        let builtin = CcfTable::builtin();
+        let Some(vin) = vin else {
+            return builtin;
+        };
+        let year = vin_model_year(vin);
+        let score = |info: &CcfTableInfo| -> Option<(usize, i32)> {
+            let prefix = info
+                .vin_prefixes
+                .iter()
+                .filter(|p| vin.starts_with(p.as_str()))
+                .map(|p| p.len())
+                .max();
+            if prefix.is_none() && !info.builtin {
+                return None;
+            }
+            let distance = match (info.model_year, year) {
+                (Some(table), Some(car)) if table <= car => (car - table) as i32,
+                (Some(table), Some(car)) => 100 + (table - car) as i32,
+                _ => 1000,
+            };
+            Some((prefix.unwrap_or(0), -distance))
+        };
+        self.with_imported(|imported| {
+            // On a tie the later entry wins, so imports beat the built-in table
+            std::iter::once(&builtin)
+                .chain(imported.iter())
+                .filter_map(|t| Some((score(t.info())?, t)))
+                .max_by_key(|&(score, _)| score)
+                .map(|(_, t)| t.clone())
+        })
+        .unwrap_or(builtin)
+    }
+}
+
+/// Model year from VIN position 10 (ISO 3779), read as 2001–2030
+pub fn vin_model_year(vin: &str) -> Option<u16> {
+    const LETTERS: &str = "ABCDEFGHJKLMNPRSTVWXY";
+    let code = vin.chars().nth(9)?.to_ascii_uppercase();
+    match code {
+        '1'..='9' => Some(2000 + code.to_digit(10)? as u16),
+        _ => LETTERS.find(code).map(|i| 2010 + i as u16),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ccf::CcfField;
+
+    fn table(name: &str, year: u16, prefixes: &[&str]) -> CcfTable {
+        let info = CcfTableInfo {
+            name: name.into(),
+            vehicle_line: Some(name[..4].into()),
+            model_year: Some(year),
+            vin_prefixes: prefixes.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        };
+        let def = CcfOptionDef {
+            id: 1,
+            name: format!("Doors of {}", name),
+            group: String::new(),
+            mdfid: None,
+            field: CcfField::full_byte(1),
+            values: BTreeMap::new(),
+        };
+        CcfTable::new(info, [def]).unwrap()
+    }
+
+    #[test]
+    fn test_vin_model_year() {
+        assert_eq!(vin_model_year("SAJBL4BVXGCY16353"), Some(2016));
+        assert_eq!(vin_model_year("SADCA2BN0JA000000"), Some(2018));
+        assert_eq!(vin_model_year("SAJAA00009A000000"), Some(2009));
+        assert_eq!(vin_model_year("SHORT"), None);
+        assert_eq!(vin_model_year("SAJBL4BVXQCY16353"), None);
+    }
+
+    #[test]
+    fn test_import_and_select_tables() {
+        let dir = std::env::temp_dir().join(format!("ccf_tables_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let tables = CcfTables::new(dir.clone());
+        let xf = "SAJBL4BVXGCY16353";
+        assert!(tables.for_vin(Some(xf)).info().builtin);
+        assert!(tables.for_vin(None).info().builtin);
+
+        // The vehicle line alone doesn't say which VINs a table is for
+        assert!(tables.import(table("X260_201800", 2018, &[])).is_err());
+        tables
+            .import(table("X260_201800", 2018, &["SAJB"]))
+            .unwrap();
+        tables
+            .import(table("X260_202000", 2020, &["SAJB"]))
+            .unwrap();
+        tables
+            .import(table("X761_201700", 2017, &["SADC"]))
+            .unwrap();
+        // Tied tables beat the built-in one; an MY16 car takes the nearest newer
+        assert_eq!(tables.for_vin(Some(xf)).info().name, "X260_201800");
+        // A 2019 car takes the nearest older year of its own line
+        assert_eq!(
+            tables.for_vin(Some("SAJBL4BVXKCY16353")).info().name,
+            "X260_201800"
+        );
+        // Tables stay out of other lines' VINs, even with a closer model year
+        assert_eq!(
+            tables.for_vin(Some("SADCA2BN0JA000000")).info().name,
+            "X761_201700"
+        );
+        assert!(tables.for_vin(Some("SALGA2BN0KA000000")).info().builtin);
+        let names: Vec<String> = tables.list().into_iter().map(|i| i.name).collect();
+        assert_eq!(
+            names,
+            ["X260_201600", "X260_201800", "X260_202000", "X761_201700"]
+        );
+
+        // Imports persist and replace earlier ones of the same name
+        tables
+            .import(table("X260_201800", 2018, &["SAJBL"]))
+            .unwrap();
+        let reopened = CcfTables::new(dir.clone());
+        assert_eq!(reopened.list().len(), 4);
+        let x260 = reopened.get("X260_201800").unwrap();
+        assert_eq!(x260.info().vin_prefixes, ["SAJBL"]);
+        assert_eq!(x260.name(1), "Doors of X260_201800");
+        assert_eq!(reopened.for_vin(Some(xf)).info().name, "X260_201800");
+        assert!(reopened.get("X260_201600").unwrap().info().builtin);
+        assert!(reopened.get("nope").is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
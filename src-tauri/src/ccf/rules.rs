@@ -0,0 +1,767 @@
+//! CCF consistency rules: declarative constraints between CCF options, across
+//! the copies different ECUs hold and against DIDs, each with a severity and
+//! the reason it matters.
+//!
+//! The built-in rules are `assets/ccf_rules.json`; a rules file in the app
+//! directory replaces them (see [`CcfRuleFile`]). Option values are numbers or
+//! labels of the decode table picked for the vehicle.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Ccf, CcfTable, OPTION_COUNT};
+
+/// Rules shipped with the app
+static BUILTIN_JSON: &str = include_str!("../../assets/ccf_rules.json");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// An option value as a rule names it: raw, or a decode-table label
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleValue {
+    Raw(u8),
+    Label(String),
+}
+
+impl RuleValue {
+    /// `None` for a label the table doesn't have; such rules are skipped (see
+    /// `CcfRule::unresolved`)
+    fn resolve(&self, table: &CcfTable, option: u16) -> Option<u8> {
+        match self {
+            Self::Raw(v) => Some(*v),
+            Self::Label(label) => table.get(option)?.value_for_label(label),
+        }
+    }
+}
+
+/// One condition of a rule; without `ecu` it reads the ECU being checked
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum RuleCheck {
+    /// The option holds one of `values`
+    OptionIn {
+        option: u16,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ecu: Option<String>,
+        values: Vec<RuleValue>,
+    },
+    /// The option holds none of `values`
+    OptionNotIn {
+        option: u16,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ecu: Option<String>,
+        values: Vec<RuleValue>,
+    },
+    /// Each option holds the same value on all of `ecus`
+    SameOn {
+        options: Vec<u16>,
+        ecus: Vec<String>,
+    },
+    /// Byte `byte` of the DID (hex, e.g. "F18C") is one of `values`
+    DidIn {
+        did: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ecu: Option<String>,
+        #[serde(default)]
+        byte: usize,
+        values: Vec<u8>,
+    },
+    /// The DID (normally F190) reads the VIN stored in the CCF
+    VinMatches {
+        did: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ecu: Option<String>,
+    },
+}
+
+/// A failed check: what was found and the options involved
+struct Violation {
+    detail: String,
+    options: Vec<u16>,
+}
+
+impl RuleCheck {
+    fn validate(&self) -> Result<(), String> {
+        let option_ok = |id: u16| {
+            if id == 0 || id as usize > OPTION_COUNT {
+                Err(format!(
+                    "option {} is out of range (1-{})",
+                    id, OPTION_COUNT
+                ))
+            } else {
+                Ok(())
+            }
+        };
+        match self {
+            Self::OptionIn { option, values, .. } | Self::OptionNotIn { option, values, .. } => {
+                option_ok(*option)?;
+                if values.is_empty() {
+                    return Err(format!("option {} check lists no values", option));
+                }
+            }
+            Self::SameOn { options, ecus } => {
+                options.iter().try_for_each(|&id| option_ok(id))?;
+                if options.is_empty() || ecus.len() < 2 {
+                    return Err("same_on needs options and at least two ECUs".into());
+                }
+            }
+            Self::DidIn { did, values, .. } => {
+                parse_did(did)?;
+                if values.is_empty() {
+                    return Err(format!("DID {} check lists no values", did));
+                }
+            }
+            Self::VinMatches { did, .. } => {
+                parse_did(did)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `None` when the input lacks what the check reads
+    fn test(&self, input: &RuleInput) -> Option<Result<(), Violation>> {
+        match self {
+            Self::OptionIn {
+                option,
+                ecu,
+                values,
+            } => input.test_option(*option, ecu, values, true),
+            Self::OptionNotIn {
+                option,
+                ecu,
+                values,
+            } => input.test_option(*option, ecu, values, false),
+            Self::SameOn { options, ecus } => {
+                let ccfs = ecus
+                    .iter()
+                    .map(|ecu| Some((ecu, input.ccf(Some(ecu))?)))
+                    .collect::<Option<Vec<_>>>()?;
+                let mut differing = Vec::new();
+                for &id in options {
+                    let field = input.table.field(id);
+                    let values = ccfs
+                        .iter()
+                        .map(|(ecu, ccf)| Some((ecu, ccf.field(field)?)))
+                        .collect::<Option<Vec<_>>>()?;
+                    if values.iter().any(|&(_, v)| v != values[0].1) {
+                        let found: Vec<String> = values
+                            .iter()
+                            .map(|(ecu, v)| {
+                                format!("{} {}", ecu.to_uppercase(), input.table.decode(id, *v))
+                            })
+                            .collect();
+                        differing.push((id, found.join(" vs ")));
+                    }
+                }
+                if differing.is_empty() {
+                    return Some(Ok(()));
+                }
+                let detail = differing
+                    .iter()
+                    .map(|(id, found)| format!("{} {}: {}", id, input.table.name(*id), found))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                Some(Err(Violation {
+                    detail,
+                    options: differing.into_iter().map(|(id, _)| id).collect(),
+                }))
+            }
+            Self::DidIn {
+                did,
+                ecu,
+                byte,
+                values,
+            } => {
+                let did = parse_did(did).ok()?;
+                let value = *input.did(ecu, did)?.get(*byte)?;
+                if values.contains(&value) {
+                    return Some(Ok(()));
+                }
+                let expected: Vec<String> = values.iter().map(|v| format!("0x{:02X}", v)).collect();
+                Some(Err(Violation {
+                    detail: format!(
+                        "{} DID 0x{:04X} byte {} is 0x{:02X}, expected one of {}",
+                        input.ecu_name(ecu),
+                        did,
+                        byte,
+                        value,
+                        expected.join(", ")
+                    ),
+                    options: Vec::new(),
+                }))
+            }
+            Self::VinMatches { did, ecu } => {
+                let did = parse_did(did).ok()?;
+                let stored = input.ccf(ecu.as_deref())?.vin()?;
+                let data = input.did(ecu, did)?;
+                let read = String::from_utf8_lossy(data);
+                let read = read.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+                if read.eq_ignore_ascii_case(&stored) {
+                    return Some(Ok(()));
+                }
+                Some(Err(Violation {
+                    detail: format!(
+                        "{} CCF is for VIN {}, DID 0x{:04X} reads '{}'",
+                        input.ecu_name(ecu),
+                        stored,
+                        did,
+                        read
+                    ),
+                    options: Vec::new(),
+                }))
+            }
+        }
+    }
+}
+
+fn parse_did(did: &str) -> Result<u16, String> {
+    let hex = did.trim().trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(hex, 16).map_err(|_| format!("'{}' is not a hex DID", did))
+}
+
+/// One constraint: when every `when` check holds, every `require` check must
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcfRule {
+    pub id: String,
+    pub severity: Severity,
+    /// What is wrong when the rule fails, in a few words
+    pub description: String,
+    /// Why it matters
+    pub explanation: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub when: Vec<RuleCheck>,
+    pub require: Vec<RuleCheck>,
+}
+
+impl CcfRule {
+    /// Why the rule can't be checked with `table`: a label it names that isn't
+    /// a value of its option there
+    pub fn unresolved(&self, table: &CcfTable) -> Option<String> {
+        self.when
+            .iter()
+            .chain(&self.require)
+            .find_map(|check| match check {
+                RuleCheck::OptionIn { option, values, .. }
+                | RuleCheck::OptionNotIn { option, values, .. } => values
+                    .iter()
+                    .find(|v| v.resolve(table, *option).is_none())
+                    .map(|v| {
+                        format!(
+                            "{:?} is not a value of option {} in table {}",
+                            v,
+                            option,
+                            table.info().name
+                        )
+                    }),
+                _ => None,
+            })
+    }
+}
+
+/// A rule the checked data breaks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleFinding {
+    pub rule: String,
+    pub severity: Severity,
+    pub description: String,
+    /// The values that break it
+    pub detail: String,
+    pub explanation: String,
+    /// Options involved, e.g. to highlight them
+    pub options: Vec<u16>,
+}
+
+/// A rule that couldn't be checked, and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedRule {
+    pub rule: String,
+    pub severity: Severity,
+    pub reason: String,
+}
+
+/// Outcome of checking a set of rules
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleReport {
+    /// Most severe first, in rule order within a severity
+    pub findings: Vec<RuleFinding>,
+    /// Rules that hold or don't apply
+    pub passed: usize,
+    /// Rules needing a CCF or DID that wasn't read, or naming a label the
+    /// decode table doesn't have
+    pub skipped: Vec<SkippedRule>,
+}
+
+/// What rules are checked against: CCFs and DIDs by ECU (gwm, bcm, …) and
+/// the decode table for the vehicle
+pub struct RuleInput<'a> {
+    table: &'a CcfTable,
+    ecu: String,
+    ccfs: BTreeMap<String, &'a Ccf>,
+    dids: BTreeMap<(String, u16), &'a [u8]>,
+}
+
+impl<'a> RuleInput<'a> {
+    /// Checks without an ECU of their own read `ecu`
+    pub fn new(table: &'a CcfTable, ecu: &str) -> Self {
+        Self {
+            table,
+            ecu: ecu.to_lowercase(),
+            ccfs: BTreeMap::new(),
+            dids: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_ccf(mut self, ecu: &str, ccf: &'a Ccf) -> Self {
+        self.ccfs.insert(ecu.to_lowercase(), ccf);
+        self
+    }
+
+    /// `data` is the DID value without the 0x62 response header
+    pub fn with_did(mut self, ecu: &str, did: u16, data: &'a [u8]) -> Self {
+        self.dids.insert((ecu.to_lowercase(), did), data);
+        self
+    }
+
+    fn ecu_key(&self, ecu: Option<&str>) -> String {
+        ecu.map_or_else(|| self.ecu.clone(), str::to_lowercase)
+    }
+
+    fn ecu_name(&self, ecu: &Option<String>) -> String {
+        self.ecu_key(ecu.as_deref()).to_uppercase()
+    }
+
+    fn ccf(&self, ecu: Option<&str>) -> Option<&'a Ccf> {
+        self.ccfs.get(&self.ecu_key(ecu)).copied()
+    }
+
+    fn did(&self, ecu: &Option<String>, did: u16) -> Option<&'a [u8]> {
+        self.dids.get(&(self.ecu_key(ecu.as_deref()), did)).copied()
+    }
+
+    fn test_option(
+        &self,
+        option: u16,
+        ecu: &Option<String>,
+        values: &[RuleValue],
+        wanted: bool,
+    ) -> Option<Result<(), Violation>> {
+        let value = self.ccf(ecu.as_deref())?.field(self.table.field(option))?;
+        let matches = values
+            .iter()
+            .any(|v| v.resolve(self.table, option) == Some(value));
+        if matches == wanted {
+            return Some(Ok(()));
+        }
+        let listed: Vec<String> = values
+            .iter()
+            .map(|v| match v {
+                RuleValue::Raw(raw) => self.table.decode(option, *raw),
+                RuleValue::Label(label) => label.clone(),
+            })
+            .collect();
+        Some(Err(Violation {
+            detail: format!(
+                "{} option {} {} is {}, expected {} {}",
+                self.ecu_name(ecu),
+                option,
+                self.table.name(option),
+                self.table.decode(option, value),
+                if wanted { "one of" } else { "none of" },
+                listed.join(", ")
+            ),
+            options: vec![option],
+        }))
+    }
+}
+
+/// A validated set of rules
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CcfRules {
+    /// "built-in", or the file the rules were read from
+    pub source: String,
+    pub rules: Vec<CcfRule>,
+}
+
+impl CcfRules {
+    /// The rules shipped with the app, parsed on first use
+    pub fn builtin() -> Arc<CcfRules> {
+        static RULES: OnceLock<Arc<CcfRules>> = OnceLock::new();
+        let rules = RULES.get_or_init(|| {
+            let rules = Self::from_json("built-in", BUILTIN_JSON);
+            Arc::new(rules.expect("built-in CCF rules are valid"))
+        });
+        rules.clone()
+    }
+
+    /// A JSON array of rules; ids must be unique and every check well formed
+    pub fn from_json(source: &str, json: &str) -> Result<Self, String> {
+        let rules: Vec<CcfRule> =
+            serde_json::from_str(json).map_err(|e| format!("{}: {}", source, e))?;
+        let mut ids = HashSet::new();
+        for rule in &rules {
+            if rule.id.trim().is_empty() || !ids.insert(rule.id.as_str()) {
+                return Err(format!(
+                    "{}: rule id '{}' is empty or repeated",
+                    source, rule.id
+                ));
+            }
+            if rule.require.is_empty() {
+                return Err(format!("{}: rule {} requires nothing", source, rule.id));
+            }
+            for check in rule.when.iter().chain(&rule.require) {
+                check
+                    .validate()
+                    .map_err(|e| format!("{}: rule {}: {}", source, rule.id, e))?;
+            }
+        }
+        Ok(Self {
+            source: source.to_string(),
+            rules,
+        })
+    }
+
+    /// Every label the rules name must be a value of its option in `table`
+    pub fn check_labels(&self, table: &CcfTable) -> Result<(), String> {
+        match self
+            .rules
+            .iter()
+            .find_map(|rule| Some((rule, rule.unresolved(table)?)))
+        {
+            Some((rule, reason)) => Err(format!("{}: rule {}: {}", self.source, rule.id, reason)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn check(&self, input: &RuleInput) -> RuleReport {
+        let mut report = RuleReport::default();
+        let skip = |rule: &CcfRule, reason: String| SkippedRule {
+            rule: rule.id.clone(),
+            severity: rule.severity,
+            reason,
+        };
+        'rules: for rule in &self.rules {
+            if let Some(reason) = rule.unresolved(input.table) {
+                report.skipped.push(skip(rule, reason));
+                continue;
+            }
+            for check in &rule.when {
+                match check.test(input) {
+                    None => {
+                        report
+                            .skipped
+                            .push(skip(rule, "needs a CCF or DID that wasn't read".into()));
+                        continue 'rules;
+                    }
+                    Some(Err(_)) => {
+                        report.passed += 1;
+                        continue 'rules;
+                    }
+                    Some(Ok(())) => {}
+                }
+            }
+            let mut violations = Vec::new();
+            let mut unknown = false;
+            for check in &rule.require {
+                match check.test(input) {
+                    None => unknown = true,
+                    Some(Err(v)) => violations.push(v),
+                    Some(Ok(())) => {}
+                }
+            }
+            if violations.is_empty() {
+                if unknown {
+                    report
+                        .skipped
+                        .push(skip(rule, "needs a CCF or DID that wasn't read".into()));
+                } else {
+                    report.passed += 1;
+                }
+                continue;
+            }
+            let options: BTreeSet<u16> =
+                violations.iter().flat_map(|v| v.options.clone()).collect();
+            report.findings.push(RuleFinding {
+                rule: rule.id.clone(),
+                severity: rule.severity,
+                description: rule.description.clone(),
+                detail: violations
+                    .into_iter()
+                    .map(|v| v.detail)
+                    .collect::<Vec<_>>()
+                    .join("; "),
+                explanation: rule.explanation.clone(),
+                options: options.into_iter().collect(),
+            });
+        }
+        // Stable, so rule order survives within a severity
+        report
+            .findings
+            .sort_by_key(|f| std::cmp::Reverse(f.severity));
+        report
+    }
+}
+
+/// The rules in effect: those in `path` when it exists, else the built-in
+/// ones. Edits to the file apply from the next check on.
+pub struct CcfRuleFile {
+    path: PathBuf,
+    /// Rules with the modification time of the file they came from
+    loaded: Mutex<Option<(Option<SystemTime>, Arc<CcfRules>)>>,
+}
+
+impl CcfRuleFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            loaded: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A file that doesn't load is logged and the built-in rules used
+    pub fn current(&self) -> Arc<CcfRules> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some((stamp, rules)) = loaded.as_ref() {
+            if *stamp == modified {
+                return rules.clone();
+            }
+        }
+        let rules = match modified {
+            None => CcfRules::builtin(),
+            Some(_) => {
+                let source = self.path.display().to_string();
+                let parsed = std::fs::read_to_string(&self.path)
+                    .map_err(|e| format!("{}: {}", source, e))
+                    .and_then(|json| CcfRules::from_json(&source, &json));
+                match parsed {
+                    Ok(rules) => Arc::new(rules),
+                    Err(e) => {
+                        log::warn!("Using the built-in CCF rules: {}", e);
+                        CcfRules::builtin()
+                    }
+                }
+            }
+        };
+        *loaded = Some((modified, rules.clone()));
+        rules
+    }
+
+    /// Check the rules in `json` (read from `source`) and make them the ones
+    /// in effect. Each label must be a value of its option in one of `tables`.
+    pub fn import(
+        &self,
+        source: &str,
+        json: &str,
+        tables: &[Arc<CcfTable>],
+    ) -> Result<Arc<CcfRules>, String> {
+        let rules = CcfRules::from_json(source, json)?;
+        for rule in &rules.rules {
+            let reasons: Vec<String> = tables.iter().filter_map(|t| rule.unresolved(t)).collect();
+            if reasons.len() == tables.len() {
+                if let Some(reason) = reasons.first() {
+                    return Err(format!("{}: rule {}: {}", source, rule.id, reason));
+                }
+            }
+        }
+        std::fs::write(&self.path, json)
+            .map_err(|e| format!("Cannot write {}: {}", self.path.display(), e))?;
+        *self.loaded.lock().unwrap() = None;
+        Ok(self.current())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static VIN: &[u8] = b"SAJBL4BVXGCY16353";
+
+    fn car() -> (Ccf, Ccf) {
+        let gwm = Ccf::parse(include_bytes!("../../assets/gwm_ccf.bin")).unwrap();
+        let bcm = Ccf::parse(include_bytes!("../../assets/bcm_ccf.bin")).unwrap();
+        (gwm, bcm)
+    }
+
+    fn rule_ids(findings: &[RuleFinding]) -> Vec<&str> {
+        findings.iter().map(|f| f.rule.as_str()).collect()
+    }
+
+    #[test]
+    fn test_builtin_rules_resolve_against_builtin_table() {
+        let rules = CcfRules::builtin();
+        assert!(rules.rules.len() >= 5);
+        rules.check_labels(&CcfTable::builtin()).unwrap();
+    }
+
+    #[test]
+    fn test_builtin_rules_on_the_bench_car() {
+        let table = CcfTable::builtin();
+        let (gwm, bcm) = car();
+        let input = RuleInput::new(&table, "gwm")
+            .with_ccf("gwm", &gwm)
+            .with_ccf("bcm", &bcm)
+            .with_did("gwm", 0xF190, VIN);
+        let report = CcfRules::builtin().check(&input);
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+        assert!(report.skipped.is_empty());
+        assert_eq!(report.passed, CcfRules::builtin().rules.len());
+    }
+
+    #[test]
+    fn test_broken_configuration_is_reported() {
+        let table = CcfTable::builtin();
+        let (mut gwm, bcm) = car();
+        gwm.set_field(table.field(467), 0x02).unwrap();
+        gwm.set_field(table.field(468), 0x01).unwrap();
+        let input = RuleInput::new(&table, "gwm")
+            .with_ccf("gwm", &gwm)
+            .with_ccf("bcm", &bcm)
+            .with_did("gwm", 0xF190, b"SAJBL4BVXGCY00000");
+        let report = CcfRules::builtin().check(&input);
+        assert_eq!(
+            rule_ids(&report.findings),
+            [
+                "display-needs-avio-panel",
+                "gwm-bcm-infotainment-match",
+                "ccf-vin-matches-car",
+                "display-10-inch",
+            ]
+        );
+        let avio = &report.findings[0];
+        assert_eq!(avio.severity, Severity::Error);
+        assert!(avio.detail.contains("NOT_FITTED (0x01)"), "{}", avio.detail);
+        assert!(avio.detail.contains("expected none of UNDEF, NOT_FITTED"));
+        assert_eq!(report.findings[1].options, [467, 468]);
+        assert!(report.findings[1]
+            .detail
+            .contains("GWM 8_INCH_SINGLE_VIEW (0x02) vs BCM"));
+        assert!(report.findings[2].detail.contains("SAJBL4BVXGCY00000"));
+
+        // Without the BCM copy or the VIN DID those rules can't be checked
+        let report =
+            CcfRules::builtin().check(&RuleInput::new(&table, "gwm").with_ccf("gwm", &gwm));
+        let skipped: Vec<&str> = report.skipped.iter().map(|s| s.rule.as_str()).collect();
+        assert_eq!(
+            skipped,
+            ["gwm-bcm-infotainment-match", "ccf-vin-matches-car"]
+        );
+        assert_eq!(report.findings[0].rule, "display-needs-avio-panel");
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let json = r#"[
+            {"id": "ipc-variant", "severity": "warning", "description": "d", "explanation": "e",
+             "when": [{"check": "option_in", "option": 116, "ecu": "bcm", "values": [0]}],
+             "require": [{"check": "did_in", "did": "0xF18C", "ecu": "ipc", "byte": 1,
+                          "values": [7, 8]}]}
+        ]"#;
+        let rules = CcfRules::from_json("test", json).unwrap();
+        let table = CcfTable::builtin();
+        let (gwm, bcm) = car();
+        let input = RuleInput::new(&table, "gwm")
+            .with_ccf("gwm", &gwm)
+            .with_ccf("bcm", &bcm)
+            .with_did("ipc", 0xF18C, &[0x00, 0x09]);
+        let report = rules.check(&input);
+        assert_eq!(
+            report.findings[0].detail,
+            "IPC DID 0xF18C byte 1 is 0x09, expected one of 0x07, 0x08"
+        );
+        let input = RuleInput::new(&table, "gwm")
+            .with_ccf("bcm", &bcm)
+            .with_did("ipc", 0xF18C, &[0x00, 0x08]);
+        assert_eq!(rules.check(&input).passed, 1);
+
+        for bad in [
+            r#"[{"id": "a", "severity": "fatal", "description": "", "explanation": "",
+                 "require": [{"check": "vin_matches", "did": "F190"}]}]"#,
+            r#"[{"id": "a", "severity": "info", "description": "", "explanation": "",
+                 "require": [{"check": "vin_matches", "did": "VIN"}]}]"#,
+            r#"[{"id": "a", "severity": "info", "description": "", "explanation": "",
+                 "require": [{"check": "same_on", "options": [467], "ecus": ["gwm"]}]}]"#,
+            r#"[{"id": "a", "severity": "info", "description": "", "explanation": "",
+                 "require": [{"check": "option_in", "option": 900, "values": [1]}]}]"#,
+            r#"[{"id": "a", "severity": "info", "description": "", "explanation": "",
+                 "require": []}]"#,
+        ] {
+            assert!(CcfRules::from_json("test", bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_unknown_labels_skip_the_rule_and_fail_import() {
+        let json = r#"[{"id": "typo", "severity": "error", "description": "d", "explanation": "e",
+                        "require": [{"check": "option_not_in", "option": 467,
+                                     "values": ["UNDEF", "NOT_FITED"]}]}]"#;
+        let rules = CcfRules::from_json("test", json).unwrap();
+        let table = CcfTable::builtin();
+        let (gwm, _) = car();
+        let report = rules.check(&RuleInput::new(&table, "gwm").with_ccf("gwm", &gwm));
+        assert_eq!(report.passed, 0);
+        assert_eq!(report.skipped[0].rule, "typo");
+        assert_eq!(report.skipped[0].severity, Severity::Error);
+        assert!(
+            report.skipped[0].reason.contains("NOT_FITED"),
+            "{:?}",
+            report.skipped
+        );
+        assert!(rules.check_labels(&table).is_err());
+
+        let path = std::env::temp_dir().join(format!("ccf_rules_typo_{}.json", std::process::id()));
+        let file = CcfRuleFile::new(path.clone());
+        let err = file.import("test", json, &[table]).unwrap_err();
+        assert!(err.contains("rule typo"), "{}", err);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_rule_file_replaces_builtin_rules() {
+        let path = std::env::temp_dir().join(format!("ccf_rules_test_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let file = CcfRuleFile::new(path.clone());
+        assert_eq!(file.current().source, "built-in");
+
+        let json = r#"[{"id": "mph", "severity": "info", "description": "d", "explanation": "e",
+                        "require": [{"check": "option_in", "option": 116, "values": ["MPH"]}]}]"#;
+        let tables = [CcfTable::builtin()];
+        let rules = file.import("test", json, &tables).unwrap();
+        assert_eq!(rules.source, path.display().to_string());
+        assert_eq!(rules.rules[0].id, "mph");
+        assert!(file.import("test", "[{}]", &tables).is_err());
+        assert_eq!(file.current().rules[0].id, "mph");
+
+        // A file broken by hand falls back to the built-in rules
+        std::fs::write(&path, "not json").unwrap();
+        let reopened = CcfRuleFile::new(path.clone());
+        assert_eq!(reopened.current().source, "built-in");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,474 @@
+//! CCF decode table: each option's name, group, bit layout and value labels.
+//!
+//! The built-in table (`assets/ccf_decode.json`, from SDD's X260 MY16 CCF_DATA)
+//! is parsed once on first use; tables imported from other EXML files are
+//! checked the same way by `CcfTable::new`.
+//!
+//! The built-in table only covers 88 of the 784 options, and only 449, 467,
+//! 468, 623 and 641 carry an SDD CCFMDFID. Every other option, listed or not,
+//! is treated as a whole byte; import CCF_DATA_X260_201600 with
+//! `exml::import` for the real layouts.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Ccf, CcfField, OPTION_COUNT};
+
+/// Option ID → definition, as shipped with the app
+static BUILTIN_JSON: &str = include_str!("../../assets/ccf_decode.json");
+
+/// One option of the decode table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcfOptionDef {
+    pub id: u16,
+    pub name: String,
+    #[serde(default)]
+    pub group: String,
+    /// SDD CCFMDFID the layout was derived from, e.g. "0x4363"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mdfid: Option<String>,
+    #[serde(flatten)]
+    pub field: CcfField,
+    /// Raw value → label
+    #[serde(default)]
+    pub values: BTreeMap<u8, String>,
+}
+
+impl CcfOptionDef {
+    pub fn label(&self, value: u8) -> Option<&str> {
+        self.values.get(&value).map(String::as_str)
+    }
+
+    /// Value whose label matches `label` (case, spaces and `_` ignored)
+    pub fn value_for_label(&self, label: &str) -> Option<u8> {
+        let wanted = match_key(label);
+        self.values
+            .iter()
+            .find(|(_, l)| match_key(l) == wanted)
+            .map(|(&v, _)| v)
+    }
+
+    /// `value` must be one of the labelled values when there are any, and
+    /// must fit the option's field
+    pub fn validate(&self, value: u8) -> Result<(), String> {
+        if !self.values.is_empty() && !self.values.contains_key(&value) {
+            let allowed: Vec<String> = self
+                .values
+                .iter()
+                .map(|(v, l)| format!("{}={}", v, l))
+                .collect();
+            return Err(format!(
+                "0x{:02X} is not defined for CCF option {} (allowed: {})",
+                value,
+                self.id,
+                allowed.join(", ")
+            ));
+        }
+        if value > self.field.max_value() {
+            return Err(format!(
+                "0x{:02X} does not fit CCF option {} ({} bits)",
+                value, self.id, self.field.width
+            ));
+        }
+        Ok(())
+    }
+
+    /// A number (decimal or 0x hex) or a label, validated
+    pub fn parse_value(&self, value: &str) -> Result<u8, String> {
+        let v = match parse_number(value) {
+            Some(v) => v,
+            None => self.value_for_label(value).ok_or_else(|| {
+                format!(
+                    "'{}' is not a value of CCF option {}",
+                    value.trim(),
+                    self.id
+                )
+            })?,
+        };
+        self.validate(v)?;
+        Ok(v)
+    }
+}
+
+/// An option whose raw byte differs between two CCF images
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcfOptionChange {
+    pub option_id: u16,
+    pub name: String,
+    pub raw_before: u8,
+    pub raw_after: u8,
+    pub before: String,
+    pub after: String,
+}
+
+/// Where a decode table came from and which vehicles it is for
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcfTableInfo {
+    /// e.g. "X260_201600" for CCF_DATA_X260_201600.exml
+    pub name: String,
+    pub vehicle_line: Option<String>,
+    pub model_year: Option<u16>,
+    /// VINs starting with one of these use this table; see `CcfTables::for_vin`
+    #[serde(default)]
+    pub vin_prefixes: Vec<String>,
+    #[serde(default)]
+    pub option_count: usize,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+/// Option definitions by ID
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CcfTable {
+    info: CcfTableInfo,
+    options: BTreeMap<u16, CcfOptionDef>,
+}
+
+impl CcfTable {
+    /// The table shipped with the app, parsed on first use (88 options)
+    pub fn builtin() -> Arc<CcfTable> {
+        static TABLE: OnceLock<Arc<CcfTable>> = OnceLock::new();
+        let table = TABLE.get_or_init(|| {
+            let info = CcfTableInfo {
+                name: "X260_201600".into(),
+                vehicle_line: Some("X260".into()),
+                model_year: Some(2016),
+                builtin: true,
+                ..Default::default()
+            };
+            let table = Self::from_json(info, BUILTIN_JSON);
+            Arc::new(table.expect("built-in CCF decode table is valid"))
+        });
+        table.clone()
+    }
+
+    /// Rejects out-of-range or duplicate IDs, bad layouts and labelled
+    /// values that don't fit their field
+    pub fn new(
+        mut info: CcfTableInfo,
+        defs: impl IntoIterator<Item = CcfOptionDef>,
+    ) -> Result<Self, String> {
+        let mut options = BTreeMap::new();
+        for def in defs {
+            if def.id == 0 || def.id as usize > OPTION_COUNT {
+                return Err(format!(
+                    "CCF option {} is out of range (1-{})",
+                    def.id, OPTION_COUNT
+                ));
+            }
+            if !def.field.is_valid() {
+                return Err(format!(
+                    "CCF option {} has an invalid layout {:?}",
+                    def.id, def.field
+                ));
+            }
+            if let Some(v) = def.values.keys().find(|&&v| v > def.field.max_value()) {
+                return Err(format!(
+                    "CCF option {} value {} does not fit {} bits",
+                    def.id, v, def.field.width
+                ));
+            }
+            let id = def.id;
+            if options.insert(id, def).is_some() {
+                return Err(format!("CCF option {} is defined twice", id));
+            }
+        }
+        info.option_count = options.len();
+        Ok(Self { info, options })
+    }
+
+    /// `{"<id>": {id, name, group, byte, bit, width, values}, ...}`
+    pub fn from_json(info: CcfTableInfo, json: &str) -> Result<Self, String> {
+        let options: BTreeMap<u16, CcfOptionDef> =
+            serde_json::from_str(json).map_err(|e| format!("Bad CCF decode table: {}", e))?;
+        Self::new(info, options.into_values())
+    }
+
+    pub fn info(&self) -> &CcfTableInfo {
+        &self.info
+    }
+
+    /// Same definitions, described by `info` (its option count is kept)
+    pub fn with_info(mut self, info: CcfTableInfo) -> Self {
+        self.info = CcfTableInfo {
+            option_count: self.options.len(),
+            ..info
+        };
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.options.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    pub fn get(&self, id: u16) -> Option<&CcfOptionDef> {
+        self.options.get(&id)
+    }
+
+    /// Definitions in option order
+    pub fn iter(&self) -> impl Iterator<Item = &CcfOptionDef> {
+        self.options.values()
+    }
+
+    /// Where option `id`'s value sits; options without a layout are a whole byte
+    pub fn field(&self, id: u16) -> CcfField {
+        self.get(id)
+            .map(|def| def.field)
+            .unwrap_or_else(|| CcfField::full_byte(id))
+    }
+
+    pub fn name(&self, id: u16) -> String {
+        match self.get(id) {
+            Some(def) => def.name.clone(),
+            None => format!("Option {}", id),
+        }
+    }
+
+    /// Human-readable value of option `id`
+    pub fn decode(&self, id: u16, value: u8) -> String {
+        match self.get(id) {
+            Some(def) => match def.label(value) {
+                Some(label) => format!("{} (0x{:02X})", label, value),
+                None => format!("{}: 0x{:02X} (unknown)", def.name, value),
+            },
+            None => format!("0x{:02X}", value),
+        }
+    }
+
+    /// Options whose value differs between two images, decoded
+    pub fn diff(&self, before: &Ccf, after: &Ccf) -> Vec<CcfOptionChange> {
+        (1..=OPTION_COUNT as u16)
+            .filter_map(|id| {
+                let field = self.field(id);
+                let (a, b) = (before.field(field)?, after.field(field)?);
+                (a != b).then(|| CcfOptionChange {
+                    option_id: id,
+                    name: self.name(id),
+                    raw_before: before.option(field.byte + 1).unwrap_or_default(),
+                    raw_after: after.option(field.byte + 1).unwrap_or_default(),
+                    before: self.decode(id, a),
+                    after: self.decode(id, b),
+                })
+            })
+            .collect()
+    }
+
+    /// The option numbered `query`, or those whose name or group contains it
+    /// (case, spaces and `_` ignored); an empty query lists everything
+    pub fn search(&self, query: &str) -> Vec<&CcfOptionDef> {
+        if let Ok(id) = query.trim().parse::<u16>() {
+            return self.get(id).into_iter().collect();
+        }
+        let wanted = match_key(query);
+        self.iter()
+            .filter(|def| {
+                match_key(&def.name).contains(&wanted) || match_key(&def.group).contains(&wanted)
+            })
+            .collect()
+    }
+
+    /// Option ID from a number or an exact name (case, spaces and `_` ignored)
+    pub fn find(&self, option: &str) -> Result<u16, String> {
+        if let Ok(id) = option.trim().parse::<u16>() {
+            if id == 0 || id as usize > OPTION_COUNT {
+                return Err(format!(
+                    "CCF option {} is out of range (1-{})",
+                    id, OPTION_COUNT
+                ));
+            }
+            return Ok(id);
+        }
+        let wanted = match_key(option);
+        let ids: Vec<u16> = self
+            .iter()
+            .filter(|def| match_key(&def.name) == wanted)
+            .map(|def| def.id)
+            .collect();
+        match ids[..] {
+            [id] => Ok(id),
+            [] => Err(format!("Unknown CCF option '{}'", option)),
+            _ => Err(format!(
+                "CCF option name '{}' is ambiguous: {:?}",
+                option, ids
+            )),
+        }
+    }
+
+    /// `CcfOptionDef::parse_value`; options the table doesn't describe take
+    /// any byte
+    pub fn parse_value(&self, id: u16, value: &str) -> Result<u8, String> {
+        match self.get(id) {
+            Some(def) => def.parse_value(value),
+            None => parse_number(value)
+                .ok_or_else(|| format!("'{}' is not a value of CCF option {}", value.trim(), id)),
+        }
+    }
+}
+
+fn parse_number(value: &str) -> Option<u8> {
+    let value = value.trim();
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn match_key(s: &str) -> String {
+    s.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(id: u16, field: CcfField, values: &[(u8, &str)]) -> CcfOptionDef {
+        CcfOptionDef {
+            id,
+            name: format!("Test {}", id),
+            group: "GROUP_TEST".into(),
+            mdfid: None,
+            field,
+            values: values.iter().map(|&(v, l)| (v, l.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_builtin_table_layouts() {
+        let table = CcfTable::builtin();
+        assert!(!table.is_empty());
+        for def in table.iter() {
+            if let Some(mdfid) = &def.mdfid {
+                let mdfid = u16::from_str_radix(mdfid.trim_start_matches("0x"), 16).unwrap();
+                assert_eq!(
+                    CcfField::from_mdfid(mdfid),
+                    Some(def.field),
+                    "option {}",
+                    def.id
+                );
+            }
+        }
+        assert_eq!(table.len(), 88);
+        let with_mdfid: Vec<u16> = table
+            .iter()
+            .filter(|d| d.mdfid.is_some())
+            .map(|d| d.id)
+            .collect();
+        assert_eq!(with_mdfid, [449, 467, 468, 623, 641]);
+        // Without an SDD layout an option is a whole byte, listed or not
+        for def in table.iter().filter(|d| d.mdfid.is_none()) {
+            assert_eq!(def.field, CcfField::full_byte(def.id), "option {}", def.id);
+        }
+        assert_eq!(table.field(700), CcfField::full_byte(700));
+        assert_eq!(table.field(467).mask(), 0x0F);
+        assert_eq!(table.name(700), "Option 700");
+        assert_eq!(table.decode(1, 3), "4_DR (0x03)");
+        assert_eq!(table.decode(1, 9), "Doors: 0x09 (unknown)");
+        assert_eq!(table.decode(700, 9), "0x09");
+    }
+
+    #[test]
+    fn test_builtin_options_without_layout_keep_the_whole_byte() {
+        use crate::ccf::Ccf;
+        let table = CcfTable::builtin();
+        let mut ccf = Ccf::parse(include_bytes!("../../assets/gwm_ccf.bin")).unwrap();
+        assert_eq!(ccf.option(101), Some(0xB1));
+        let raw = ccf.field(table.field(101)).unwrap();
+        assert_eq!(table.decode(101, raw), "Oil Level Sensor: 0xB1 (unknown)");
+        // Writing a listed option replaces the whole byte, not guessed low bits
+        ccf.set_field(table.field(101), 0x01).unwrap();
+        assert_eq!(ccf.option(101), Some(0x01));
+    }
+
+    #[test]
+    fn test_table_queries() {
+        let table = CcfTable::builtin();
+        let hits: Vec<u16> = table.search("front display").iter().map(|d| d.id).collect();
+        assert!(hits.contains(&467), "{:?}", hits);
+        let by_group: Vec<u16> = table.search("eucd_doors").iter().map(|d| d.id).collect();
+        assert_eq!(by_group, [1]);
+        assert_eq!(table.search("").len(), table.len());
+        assert_eq!(table.search("7").len(), 1);
+        assert!(table.search("700").is_empty());
+
+        assert_eq!(table.find("frontdisplayvariant"), Ok(467));
+        assert_eq!(table.find("700"), Ok(700));
+        assert!(table.find("0").is_err());
+        assert!(table.find("Warp Drive").is_err());
+
+        let doors = table.get(1).unwrap();
+        assert_eq!(doors.value_for_label("4 dr"), Some(3));
+        assert_eq!(table.parse_value(1, "4_DR"), Ok(3));
+        assert_eq!(table.parse_value(467, "0x04"), Ok(4));
+        assert!(table.parse_value(467, "9").unwrap_err().contains("allowed"));
+        assert_eq!(table.parse_value(700, "0xFF"), Ok(0xFF));
+        assert!(table.parse_value(700, "lots").is_err());
+
+        let narrow = def(
+            2,
+            CcfField {
+                byte: 1,
+                bit: 4,
+                width: 2,
+            },
+            &[],
+        );
+        assert_eq!(narrow.validate(3), Ok(()));
+        assert!(narrow.validate(4).unwrap_err().contains("2 bits"));
+    }
+
+    #[test]
+    fn test_table_rejects_bad_definitions() {
+        let nibble = CcfField {
+            byte: 0,
+            bit: 0,
+            width: 4,
+        };
+        assert!(CcfTable::new(CcfTableInfo::default(), [def(0, nibble, &[])]).is_err());
+        assert!(CcfTable::new(
+            CcfTableInfo::default(),
+            [def(
+                1,
+                CcfField {
+                    byte: 0,
+                    bit: 6,
+                    width: 4
+                },
+                &[]
+            )]
+        )
+        .is_err());
+        assert!(CcfTable::new(
+            CcfTableInfo::default(),
+            [def(1, nibble, &[(16, "TOO_BIG")])]
+        )
+        .is_err());
+        assert!(CcfTable::new(
+            CcfTableInfo::default(),
+            [def(1, nibble, &[]), def(1, nibble, &[])]
+        )
+        .is_err());
+
+        let info = CcfTableInfo {
+            name: "test".into(),
+            ..Default::default()
+        };
+        let table = CcfTable::new(info.clone(), [def(1, nibble, &[(2, "TWO")])]).unwrap();
+        assert_eq!(table.info().option_count, 1);
+        let json =
+            serde_json::to_string(&table.iter().map(|d| (d.id, d)).collect::<BTreeMap<_, _>>())
+                .unwrap();
+        assert_eq!(CcfTable::from_json(info, &json), Ok(table));
+    }
+}
@@ -1,72 +1,40 @@
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter, State};
-
-use crate::ecu_emulator::{EcuEmulatorManager, EcuId};
+use tauri::{AppHandle, Manager, State};
+
+use crate::api::{ApiInfo, ApiServer};
+use crate::archive::{
+    self, ArchivedVehicle, ScannedDid, Snapshot, SnapshotData, SnapshotDiff, SnapshotInfo,
+    VehicleArchive,
+};
+use crate::broadcast::{default_schedule, BroadcastScheduler, DbcSchedule, VehicleState};
+use crate::canlog::{self, CanFrame, CanLog, CaptureFormat};
+use crate::ccf::{
+    Ccf, CcfOptionChange, CcfOptionDef, CcfRules, CcfTable, CcfTableInfo, CcfTables,
+    ChecksumStatus, RuleFinding, RuleInput, RuleReport, Severity,
+};
+use crate::dbc::{Dbc, DecodedFrame};
+use crate::ecu_emulator::{
+    load_dump_handlers, stateful, EcuEmulatorManager, EcuId, EmulatedBus, FaultScenario,
+};
+use crate::j2534::device::{J2534Channel, J2534Device};
 use crate::j2534::dll;
+use crate::j2534::remote::{self, BridgeServer, RemoteChannel};
+use crate::j2534::router::{ChannelRouter, EcuPort};
+use crate::j2534::Channel;
 use crate::j2534::types::*;
-use crate::state::{AppState, Connection};
-use crate::uds::client::{LogDirection, LogEntry};
-use crate::uds::services::{did, ecu_addr, routine};
-
-/// CCF decode table — maps option_id → {name, values: {value_byte → label}}
-static CCF_DECODE_JSON: &str = include_str!("../assets/ccf_decode.json");
-
-/// Decode a CCF option value to human-readable string
-fn decode_ccf_value(option_id: u16, raw_value: u8) -> String {
-    if let Ok(table) = serde_json::from_str::<serde_json::Value>(CCF_DECODE_JSON) {
-        let key = option_id.to_string();
-        if let Some(entry) = table.get(&key) {
-            if let Some(values) = entry.get("values") {
-                let val_key = raw_value.to_string();
-                if let Some(label) = values.get(&val_key) {
-                    if let Some(s) = label.as_str() {
-                        return format!("{} (0x{:02X})", s, raw_value);
-                    }
-                }
-            }
-            if let Some(name) = entry.get("name").and_then(|v| v.as_str()) {
-                return format!("{}: 0x{:02X} (unknown)", name, raw_value);
-            }
-        }
-    }
-    format!("0x{:02X}", raw_value)
-}
-
-/// Get CCF option name from decode table
-fn ccf_option_name(option_id: u16) -> String {
-    if let Ok(table) = serde_json::from_str::<serde_json::Value>(CCF_DECODE_JSON) {
-        let key = option_id.to_string();
-        if let Some(entry) = table.get(&key) {
-            if let Some(name) = entry.get("name").and_then(|v| v.as_str()) {
-                return name.to_string();
-            }
-        }
-    }
-    format!("Option {}", option_id)
-}
-
-/// Extract the correct sub-field from a CCF byte for bit-packed options.
-/// High-numbered CCF options (449+) pack multiple sub-fields into a single byte.
-/// CCFMDFID low nibble encodes which bits: sub_nibble 3 → bits 3:0, sub_nibble 7 → bits 7:4, etc.
-fn extract_ccf_subfield(option_id: u16, raw_byte: u8) -> u8 {
-    match option_id {
-        // Option 449: Camera HMI Strategy = bits 2:0 (CCFMDFID 0x4122, sub_nibble 2)
-        449 => raw_byte & 0x07,
-        // Option 467: Front Display Variant = bits 3:0 (CCFMDFID 0x4363, sub_nibble 3)
-        // CRITICAL: raw byte 0x14 → extracted 0x04 = 10" display
-        467 => raw_byte & 0x0F,
-        // Option 468: Front AVIO Panel = bits 2:0 (CCFMDFID 0x4372, sub_nibble 2)
-        468 => raw_byte & 0x07,
-        // Option 623: Display Cable Length = bits 3:0 (CCFMDFID 0x5983, sub_nibble 3)
-        623 => raw_byte & 0x0F,
-        // Option 641: Front Lower Display = bits 3:0 (CCFMDFID 0x5C53, sub_nibble 3)
-        641 => raw_byte & 0x0F,
-        // All other options: full byte (standard encoding, sub_nibble 7)
-        _ => raw_byte,
-    }
-}
+use crate::jobs::{JobContext, JobInfo, JobKind};
+use crate::monitor::{BusMonitor, MonitorOptions, MonitorSnapshot};
+use crate::replay::{self, ReplayOptions, ReplayStats};
+use crate::reverse::{self, Analysis, AnalysisOptions, CanEvent};
+use crate::sink::LogSink;
+use crate::sniff::{SniffAction, SniffDiff, SniffOptions, SniffWindows};
+use crate::state::{AppState, BridgeSession, Connection};
+use crate::uds::client::{LogCallback, LogDirection, LogEntry};
+use crate::uds::services::{
+    did, ecu_addr, parse_dtc_report, routine, DTC_BY_STATUS_MASK, DTC_STATUS_ALL,
+};
 
 /// IMC CCF option IDs — all options relevant to IMC variant config (vc_config.json).
 /// Options 467/468 are CRITICAL for display size. 0x6038 reads these from GWM CCF.
@@ -89,6 +57,8 @@ pub struct CcfCompareEntry {
     pub bcm: Option<String>,
     pub imc: Option<String>,
     pub mismatch: bool,
+    /// CCF rules broken that involve this option
+    pub findings: Vec<RuleFinding>,
 }
 
 /// Log an error and return it — ensures all command errors are visible in the log file
@@ -97,6 +67,9 @@ fn log_err(context: &str, msg: String) -> String {
     msg
 }
 
+/// `dll_path` reported for connections made by `connect_emulated_inner`
+pub const EMULATED_DLL_PATH: &str = "<emulated>";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeviceInfo {
     pub firmware_version: String,
@@ -105,7 +78,7 @@ pub struct DeviceInfo {
     pub dll_path: String,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EcuInfoEntry {
     pub label: String,
     pub did_hex: String,
@@ -137,7 +110,7 @@ pub struct J2534DeviceEntry {
     pub dll_path: String,
 }
 
-fn emit_log<R: tauri::Runtime>(app: &tauri::AppHandle<R>, entry: LogEntry) {
+fn emit_log(sink: &dyn LogSink, entry: LogEntry) {
     log::info!(
         "[UDS] {} [{}] {}{}",
         entry.timestamp,
@@ -149,17 +122,17 @@ fn emit_log<R: tauri::Runtime>(app: &tauri::AppHandle<R>, entry: LogEntry) {
             format!(" {}", entry.description)
         }
     );
-    let _ = app.emit("uds-log", entry);
+    sink.log(entry);
 }
 
-fn emit_log_simple<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn emit_log_simple(
+    sink: &dyn LogSink,
     direction: LogDirection,
     data: &[u8],
     description: &str,
 ) {
     emit_log(
-        app,
+        sink,
         LogEntry {
             direction,
             data_hex: data
@@ -173,6 +146,12 @@ fn emit_log_simple<R: tauri::Runtime>(
     );
 }
 
+/// Log callback for background workers, reporting through the state's background sink
+fn background_log(state: &AppState) -> LogCallback {
+    let sink = state.background_sink();
+    Box::new(move |entry| emit_log(&*sink, entry))
+}
+
 /// Discover available J2534 devices from the Windows registry
 #[tauri::command]
 pub fn discover_devices() -> Vec<J2534DeviceEntry> {
@@ -192,23 +171,26 @@ pub fn connect(
     state: State<'_, AppState>,
     dll_path: Option<String>,
 ) -> Result<DeviceInfo, String> {
-    let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
-
-    if conn.is_some() {
-        return Err("Already connected. Disconnect first.".into());
-    }
+    connect_inner(&app, &state, dll_path).map_err(|e| log_err("connect", e))
+}
 
-    let (lib, device, path) = if let Some(path) = dll_path {
+/// Load a J2534 DLL (explicit path, or auto-detect from the registry with the
+/// Mongoose default as fallback) and open its device
+pub fn open_device(
+    sink: &dyn LogSink,
+    dll_path: Option<String>,
+) -> Result<(Arc<dll::J2534Lib>, J2534Device, String), String> {
+    if let Some(path) = dll_path {
         // Explicit path provided
         emit_log_simple(
-            &app,
+            sink,
             LogDirection::Tx,
             &[],
             &format!("Loading J2534 DLL: {}", path),
         );
         let lib = Arc::new(dll::J2534Lib::load(&path)?);
-        let device = crate::j2534::device::J2534Device::open(lib.clone())?;
-        (lib, device, path)
+        let device = J2534Device::open(lib.clone())?;
+        Ok((lib, device, path))
     } else {
         // Auto-detect: try each discovered device until one opens successfully
         let devices = dll::discover_j2534_dlls();
@@ -221,7 +203,7 @@ pub fn connect(
             let p_str = p.to_string_lossy().to_string();
             log::info!("Auto-detect: trying {} at {}", name, p.display());
             emit_log_simple(
-                &app,
+                sink,
                 LogDirection::Tx,
                 &[],
                 &format!("Trying: {} ({})", name, p_str),
@@ -230,7 +212,7 @@ pub fn connect(
             match dll::J2534Lib::load(&p_str) {
                 Ok(lib) => {
                     let lib = Arc::new(lib);
-                    let device = crate::j2534::device::J2534Device::open(lib.clone())?;
+                    let device = J2534Device::open(lib.clone())?;
                     found = Some((lib, device, p_str));
                     break;
                 }
@@ -252,19 +234,32 @@ pub fn connect(
             );
             if let Ok(lib) = dll::J2534Lib::load(&default_path) {
                 let lib = Arc::new(lib);
-                if let Ok(device) = crate::j2534::device::J2534Device::open(lib.clone()) {
+                if let Ok(device) = J2534Device::open(lib.clone()) {
                     found = Some((lib, device, default_path));
                 }
             }
         }
 
-        found.ok_or_else(|| format!("No J2534 device responded. Last error: {}", last_err))?
-    };
+        found.ok_or_else(|| format!("No J2534 device responded. Last error: {}", last_err))
+    }
+}
+
+pub fn connect_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    dll_path: Option<String>,
+) -> Result<DeviceInfo, String> {
+    let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
+
+    if conn.is_some() {
+        return Err("Already connected. Disconnect first.".into());
+    }
 
+    let (lib, device, path) = open_device(sink, dll_path)?;
     let version = device.read_version()?;
 
     emit_log_simple(
-        &app,
+        sink,
         LogDirection::Rx,
         &[],
         &format!(
@@ -280,7 +275,7 @@ pub fn connect(
     // BS=0 (send all frames without waiting), STMIN=0 (no delay between frames), WFT_MAX=0 (no wait frame limit)
     if let Err(e) = channel.set_iso15765_config(0, 0, 0) {
         emit_log_simple(
-            &app,
+            sink,
             LogDirection::Rx,
             &[],
             &format!("Warning: SET_CONFIG ISO15765 failed: {}", e),
@@ -298,7 +293,7 @@ pub fn connect(
     channel.setup_iso15765_filter(ecu_addr::IPC_TX, ecu_addr::IPC_RX)?;
 
     emit_log_simple(
-        &app,
+        sink,
         LogDirection::Rx,
         &[],
         "ISO15765 channel connected, IMC + BCM + GWM + IPC filters set",
@@ -312,9 +307,9 @@ pub fn connect(
     };
 
     *conn = Some(Connection {
-        lib,
-        device,
-        channel: Some(channel),
+        lib: Some(lib),
+        device: Some(device),
+        channel: Some(Arc::new(ChannelRouter::new(Box::new(channel)))),
         can_channel: None,
         dll_path: path,
         emulator_manager: None,
@@ -323,9 +318,180 @@ pub fn connect(
     Ok(info)
 }
 
+/// Connect to a simulated vehicle: every ECU (IMC included) is emulated, no DLL
+/// or adapter needed. `ecus` narrows the set (default: all); ECUs with a
+/// `<ecu>_dump.json` in `dump_dir` answer DID reads from that scan.
+#[tauri::command]
+pub fn connect_simulated(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ecus: Option<Vec<String>>,
+    dump_dir: Option<String>,
+) -> Result<DeviceInfo, String> {
+    let ecu_ids = match ecus {
+        Some(names) => names
+            .iter()
+            .map(|n| EcuId::from_str(n).ok_or_else(|| format!("Unknown ECU: {}", n)))
+            .collect::<Result<Vec<_>, _>>(),
+        None => Ok(EcuId::all().to_vec()),
+    };
+    ecu_ids
+        .and_then(|ids| {
+            let dir = dump_dir.as_deref().map(std::path::Path::new);
+            connect_emulated_inner(&app, &state, &ids, dir)
+        })
+        .map_err(|e| log_err("connect_simulated", e))
+}
+
+/// Connect to an emulated bus instead of a J2534 adapter: the given ECUs
+/// answer from their emulator handlers, or from scan dumps found in `dump_dir`.
+pub fn connect_emulated_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecus: &[EcuId],
+    dump_dir: Option<&std::path::Path>,
+) -> Result<DeviceInfo, String> {
+    if ecus.is_empty() {
+        return Err("No ECUs to emulate".into());
+    }
+
+    let mut bus = EmulatedBus::new(ecus);
+    if let Some(dir) = dump_dir {
+        for (ecu, handler) in load_dump_handlers(dir, ecus)? {
+            emit_log_simple(
+                sink,
+                LogDirection::Rx,
+                &[],
+                &format!(
+                    "{}: replaying {} DIDs from {}_dump.json",
+                    ecu.name(),
+                    handler.readable_dids(),
+                    ecu.name().to_lowercase()
+                ),
+            );
+            bus = bus.with_handler(ecu, stateful(ecu, Box::new(handler)));
+        }
+    }
+    connect_bus_inner(sink, state, bus)
+}
+
+/// Connect to an already-built emulated bus (custom handlers, timing or faults)
+pub fn connect_bus_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    bus: EmulatedBus,
+) -> Result<DeviceInfo, String> {
+    let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
+
+    if conn.is_some() {
+        return Err("Already connected. Disconnect first.".into());
+    }
+
+    let names: Vec<&str> = bus.ecus().iter().map(|e| e.name()).collect();
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!("Connected to emulated bus: {}", names.join(", ")),
+    );
+
+    *conn = Some(Connection {
+        lib: None,
+        device: None,
+        channel: Some(Arc::new(ChannelRouter::new(Box::new(bus)))),
+        can_channel: None,
+        dll_path: EMULATED_DLL_PATH.to_string(),
+        emulator_manager: None,
+    });
+
+    Ok(DeviceInfo {
+        firmware_version: "emulated".into(),
+        dll_version: env!("CARGO_PKG_VERSION").into(),
+        api_version: "-".into(),
+        dll_path: EMULATED_DLL_PATH.into(),
+    })
+}
+
+/// Connect to a J2534 adapter served by a remote bridge (`udsapp-cli bridge` or
+/// "Start bridge" on the machine plugged into the car)
+#[tauri::command]
+pub fn connect_remote(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    addr: String,
+    token: String,
+) -> Result<DeviceInfo, String> {
+    connect_remote_inner(&app, &state, &addr, &token).map_err(|e| log_err("connect_remote", e))
+}
+
+pub fn connect_remote_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    addr: &str,
+    token: &str,
+) -> Result<DeviceInfo, String> {
+    let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
+
+    if conn.is_some() {
+        return Err("Already connected. Disconnect first.".into());
+    }
+
+    emit_log_simple(
+        sink,
+        LogDirection::Tx,
+        &[],
+        &format!("Connecting to remote bridge {}", addr),
+    );
+    let channel = RemoteChannel::connect(addr, token)?;
+    if let Err(e) = channel.set_iso15765_config(0, 0, 0) {
+        emit_log_simple(
+            sink,
+            LogDirection::Rx,
+            &[],
+            &format!("Warning: remote SET_CONFIG ISO15765 failed: {}", e),
+        );
+    }
+    channel.setup_iso15765_filter(ecu_addr::IMC_TX, ecu_addr::IMC_RX)?;
+    channel.setup_iso15765_filter(ecu_addr::BCM_TX, ecu_addr::BCM_RX)?;
+    channel.setup_iso15765_filter(ecu_addr::GWM_TX, ecu_addr::GWM_RX)?;
+    channel.setup_iso15765_filter(ecu_addr::IPC_TX, ecu_addr::IPC_RX)?;
+
+    let latency = channel.latency();
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "Remote bridge connected ({} ms round trip), IMC + BCM + GWM + IPC filters set",
+            latency.as_millis()
+        ),
+    );
+
+    let dll_path = format!("remote://{}", addr);
+    *conn = Some(Connection {
+        lib: None,
+        device: None,
+        channel: Some(Arc::new(ChannelRouter::new(Box::new(channel)))),
+        can_channel: None,
+        dll_path: dll_path.clone(),
+        emulator_manager: None,
+    });
+
+    Ok(DeviceInfo {
+        firmware_version: "remote".into(),
+        dll_version: format!("bridge protocol v{}", remote::PROTOCOL_VERSION),
+        api_version: format!("{} ms RTT", latency.as_millis()),
+        dll_path,
+    })
+}
+
 /// Disconnect from J2534 device
 #[tauri::command]
 pub fn disconnect(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    disconnect_inner(&app, &state)
+}
+
+pub fn disconnect_inner(sink: &dyn LogSink, state: &AppState) -> Result<(), String> {
     let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
 
     if conn.is_none() {
@@ -335,9 +501,11 @@ pub fn disconnect(app: AppHandle, state: State<'_, AppState>) -> Result<(), Stri
 
     // Drop connection (RAII will close channel and device)
     *conn = None;
+    // The next connection may be to another car
+    state.archive.forget_vin();
 
     emit_log_simple(
-        &app,
+        sink,
         LogDirection::Tx,
         &[],
         "Disconnected from J2534 device",
@@ -350,22 +518,55 @@ pub fn disconnect(app: AppHandle, state: State<'_, AppState>) -> Result<(), Stri
 pub struct BenchModeStatus {
     pub enabled: bool,
     pub emulated_ecus: Vec<String>,
+    /// Fault scenario the emulated ECUs run under, if any
+    pub faults: Option<String>,
+    /// Simulated ignition position ("off", "accessory", "on", "crank")
+    pub vehicle_state: Option<String>,
 }
 
-/// Toggle bench mode (multi-ECU emulation)
+/// Toggle bench mode (multi-ECU emulation).
+/// `faults` is a fault scenario preset name or JSON scenario for the emulated ECUs,
+/// `schedule` a DBC broadcast schedule file to send instead of the default frames.
 #[tauri::command]
 pub fn toggle_bench_mode(
     app: AppHandle,
     state: State<'_, AppState>,
     enabled: bool,
     ecus: Option<Vec<String>>,
+    faults: Option<String>,
+    schedule: Option<String>,
+) -> Result<(), String> {
+    toggle_bench_mode_inner(&app, &state, enabled, ecus, faults.as_deref(), schedule.as_deref())
+        .map_err(|e| log_err("toggle_bench_mode", e))
+}
+
+pub fn toggle_bench_mode_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    enabled: bool,
+    ecus: Option<Vec<String>>,
+    faults: Option<&str>,
+    schedule: Option<&str>,
 ) -> Result<(), String> {
+    // Parse before tearing anything down so a typo leaves bench mode as it was
+    let faults = faults
+        .filter(|f| !f.trim().is_empty())
+        .map(FaultScenario::parse)
+        .transpose()?;
+    let schedule_path = schedule.map(str::trim).filter(|s| !s.is_empty());
+    let schedule = match schedule_path {
+        Some(path) => DbcSchedule::load(std::path::Path::new(path))?,
+        None => default_schedule(),
+    };
+
     let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
     let conn = conn.as_mut().ok_or("Not connected")?;
 
     // Always clean up any existing emulator/CAN channel first
-    if let Some(mut mgr) = conn.emulator_manager.take() {
-        mgr.stop();
+    // (dropping the last manager reference stops its broadcast thread; the bus
+    // responder stops now, even if a request in flight still holds the manager)
+    if let Some(mgr) = conn.emulator_manager.take() {
+        mgr.detach();
     }
     conn.can_channel = None;
 
@@ -386,17 +587,24 @@ pub fn toggle_bench_mode(
         // Try to open a raw CAN channel for broadcast (separate from ISO15765)
         // Some J2534 devices (e.g. MongoosePro) only support one channel at a time,
         // so broadcast must happen before ISO15765
-        let manager = match conn.device.connect_can(500000) {
-            Ok(can_channel) => {
+        // (emulated connections have no adapter and always use software routing)
+        let can_channel = match (conn.lib.clone(), conn.device()) {
+            (Some(lib), Ok(device)) => device.connect_can(500000).map(|ch| (lib, ch)),
+            (_, Err(e)) => Err(e),
+            (None, Ok(_)) => Err("J2534 library not loaded".to_string()),
+        };
+        let manager = match can_channel {
+            Ok((lib, can_channel)) => {
                 let can_channel_id = can_channel.channel_id();
                 let mgr = crate::ecu_emulator::EcuEmulatorManager::new_with_broadcast(
-                    &conn.lib,
+                    &lib,
                     can_channel_id,
                     ecu_ids,
+                    schedule,
                 );
-                conn.can_channel = Some(can_channel);
+                conn.can_channel = Some(Arc::new(can_channel));
                 emit_log_simple(
-                    &app,
+                    sink,
                     LogDirection::Rx,
                     &[],
                     &format!(
@@ -412,7 +620,7 @@ pub fn toggle_bench_mode(
                     e
                 );
                 emit_log_simple(
-                    &app,
+                    sink,
                     LogDirection::Rx,
                     &[],
                     &format!(
@@ -420,21 +628,33 @@ pub fn toggle_bench_mode(
                         ecu_names.join(", ")
                     ),
                 );
-                crate::ecu_emulator::EcuEmulatorManager::new(ecu_ids)
+                crate::ecu_emulator::EcuEmulatorManager::new(ecu_ids).with_schedule(schedule)
+            }
+        };
+        if let Some(path) = schedule_path {
+            emit_log_simple(sink, LogDirection::Rx, &[], &format!("Broadcast schedule: {}", path));
+        }
+        let manager = match faults {
+            Some(scenario) => {
+                emit_log_simple(
+                    sink,
+                    LogDirection::Rx,
+                    &[],
+                    &format!("Fault injection: '{}' on emulated ECUs", scenario.name),
+                );
+                manager.with_faults(&scenario)?
             }
+            None => manager,
         };
-        // Set up CAN bus emulation filters (reversed ISO15765 filters)
-        // so we can respond to requests from IMC to emulated ECUs.
-        // E.g., when IMC sends ReadDID 0xEE00 to GWM (0x716) during 0x0E00,
-        // our adapter catches it and we respond as GWM (0x71E).
-        if let Some(channel) = conn.channel.as_ref() {
-            for ecu in manager.emulated_ecus() {
-                // Reversed filter: pattern=TX (receive requests TO this ECU),
-                // flow_control=RX (respond AS this ECU)
-                match channel.setup_iso15765_filter(ecu.rx_id(), ecu.tx_id()) {
+        // Answer the IMC's own requests to emulated ECUs (e.g. ReadDID 0xEE00 to
+        // the GWM during 0x0E00) in the background, logged as bus-emulation traffic
+        let manager = manager.with_log_callback(background_log(state));
+        if let Some(channel) = conn.channel.as_deref() {
+            for (ecu, filter) in manager.attach(channel) {
+                match filter {
                     Ok(_) => {
                         emit_log_simple(
-                            &app,
+                            sink,
                             LogDirection::Rx,
                             &[],
                             &format!(
@@ -452,11 +672,11 @@ pub fn toggle_bench_mode(
             }
         }
 
-        conn.emulator_manager = Some(manager);
+        conn.emulator_manager = Some(Arc::new(manager));
     } else {
         // Cleanup already done above
         emit_log_simple(
-            &app,
+            sink,
             LogDirection::Rx,
             &[],
             "Bench mode OFF — emulation stopped",
@@ -469,6 +689,10 @@ pub fn toggle_bench_mode(
 /// Get bench mode status
 #[tauri::command]
 pub fn get_bench_mode_status(state: State<'_, AppState>) -> Result<BenchModeStatus, String> {
+    get_bench_mode_status_inner(&state)
+}
+
+pub fn get_bench_mode_status_inner(state: &AppState) -> Result<BenchModeStatus, String> {
     let conn = state.connection.lock().map_err(|e| e.to_string())?;
     let conn = conn.as_ref().ok_or("Not connected")?;
 
@@ -480,14 +704,60 @@ pub fn get_bench_mode_status(state: State<'_, AppState>) -> Result<BenchModeStat
                 .iter()
                 .map(|e| e.name().to_lowercase())
                 .collect(),
+            faults: mgr.faults().map(str::to_string),
+            vehicle_state: Some(mgr.vehicle_state().name().to_string()),
         }),
         None => Ok(BenchModeStatus {
             enabled: false,
             emulated_ecus: vec![],
+            faults: None,
+            vehicle_state: None,
         }),
     }
 }
 
+/// Switch the vehicle state (ignition off/accessory/on/crank) the bench
+/// broadcast simulates, without restarting bench mode
+#[tauri::command]
+pub fn set_vehicle_state(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    vehicle_state: String,
+) -> Result<(), String> {
+    set_vehicle_state_inner(&app, &state, &vehicle_state)
+        .map_err(|e| log_err("set_vehicle_state", e))
+}
+
+pub fn set_vehicle_state_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    vehicle_state: &str,
+) -> Result<(), String> {
+    let vehicle_state = VehicleState::parse(vehicle_state)?;
+    let conn = state.connection.lock().map_err(|e| e.to_string())?;
+    let conn = conn.as_ref().ok_or("Not connected")?;
+    let mgr = conn
+        .emulator_manager
+        .as_deref()
+        .ok_or("Bench mode is off")?;
+    mgr.set_vehicle_state(vehicle_state);
+    emit_log_simple(
+        sink,
+        LogDirection::Tx,
+        &[],
+        &format!(
+            "Vehicle state: {}{}",
+            vehicle_state.name(),
+            if mgr.is_broadcasting() {
+                ""
+            } else {
+                " (no CAN broadcast on this connection)"
+            }
+        ),
+    );
+    Ok(())
+}
+
 /// Read ECU info — returns a list of DID entries for the given ECU
 #[tauri::command]
 pub fn read_ecu_info(
@@ -498,31 +768,44 @@ pub fn read_ecu_info(
     read_ecu_info_inner(&app, &state, &ecu).map_err(|e| log_err("read_ecu_info", e))
 }
 
-fn read_ecu_info_inner(
-    app: &AppHandle,
-    state: &State<'_, AppState>,
+pub fn read_ecu_info_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
     ecu: &str,
 ) -> Result<Vec<EcuInfoEntry>, String> {
-    let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_mut().ok_or("Not connected")?;
-
     // In bench mode, do CAN pre-broadcast before IMC reads to wake IMC
     // MongoosePro only supports one channel, so broadcast must happen before ISO15765
-    if ecu == "imc" && conn.emulator_manager.is_some() {
-        can_pre_broadcast(app, conn)?;
+    {
+        let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
+        let conn = conn.as_mut().ok_or("Not connected")?;
+        if ecu == "imc" && conn.emulator_manager.is_some() {
+            can_pre_broadcast(sink, conn)?;
+        }
     }
 
-    let channel: &dyn crate::j2534::Channel =
-        conn.channel.as_ref().ok_or("No channel available")?;
-    let emulator = conn.emulator_manager.as_ref();
+    // Only this ECU's queue is held, so other ECUs can be read meanwhile
+    let bus = bus_handles(state)?;
+    let emulator = bus.emulator.as_deref();
     let entries = match ecu {
-        "imc" => read_imc_info(app, channel, emulator),
-        "bcm" => read_bcm_info(app, channel, emulator),
-        "gwm" => read_gwm_info(app, channel, emulator),
-        "ipc" => read_ipc_info(app, channel, emulator),
+        "imc" => read_imc_info(sink, &bus.port(ecu_addr::IMC_TX, ecu_addr::IMC_RX)?, emulator),
+        "bcm" => read_bcm_info(sink, &bus.port(ecu_addr::BCM_TX, ecu_addr::BCM_RX)?, emulator),
+        "gwm" => read_gwm_info(sink, &bus.port(ecu_addr::GWM_TX, ecu_addr::GWM_RX)?, emulator),
+        "ipc" => read_ipc_info(sink, &bus.port(ecu_addr::IPC_TX, ecu_addr::IPC_RX)?, emulator),
         _ => return Err(format!("Unknown ECU: {}", ecu)),
     };
 
+    // Live values (battery, status) change on every read, so only archive identification
+    let identification: Vec<EcuInfoEntry> = entries
+        .iter()
+        .filter(|e| matches!(e.category.as_str(), "vehicle" | "software" | "hardware"))
+        .filter(|e| e.value.is_some())
+        .cloned()
+        .collect();
+    if !identification.is_empty() {
+        let data = SnapshotData::Identification { entries: identification };
+        archive_snapshot(sink, &state.archive, ecu, data);
+    }
+
     Ok(entries)
 }
 
@@ -530,32 +813,47 @@ fn read_ecu_info_inner(
 /// broadcast NM messages to wake the IMC, then restore ISO15765.
 /// MongoosePro only supports one J2534 channel at a time, so we must
 /// close ISO15765 first, broadcast on CAN, then reopen ISO15765.
-fn can_pre_broadcast(app: &AppHandle, conn: &mut Connection) -> Result<(), String> {
+fn can_pre_broadcast(sink: &dyn LogSink, conn: &mut Connection) -> Result<(), String> {
     emit_log_simple(
-        app,
+        sink,
         LogDirection::Tx,
         &[],
         "Bench: CAN pre-broadcast to wake IMC...",
     );
 
+    if conn.device.is_none() {
+        emit_log_simple(sink, LogDirection::Rx, &[], "No J2534 adapter — pre-broadcast skipped");
+        return Ok(());
+    }
+
     // Close ISO15765 channel to free the single J2534 channel slot
-    conn.channel.take(); // Drop triggers PassThruDisconnect
+    close_iso15765_channel(conn);
 
     // Open raw CAN channel and broadcast NM messages for 5 seconds
-    match conn.device.connect_can(500000) {
+    match conn.device()?.connect_can(500000) {
         Ok(can_ch) => {
-            for cycle in 0..50 {
-                // 50 × 100ms = 5 seconds
-                for &(can_id, ref data) in crate::ecu_emulator::BROADCAST_MSGS {
-                    let _ = can_ch.send_raw_can(can_id, data);
+            // The bench schedule in the current vehicle state (ignition on by default)
+            let vehicle_state = conn
+                .emulator_manager
+                .as_deref()
+                .map_or(VehicleState::On, |mgr| mgr.vehicle_state());
+            let schedule = conn
+                .emulator_manager
+                .as_deref()
+                .map_or_else(default_schedule, |mgr| mgr.schedule().to_vec());
+            let mut scheduler = BroadcastScheduler::new(schedule, std::time::Instant::now());
+            for tick in 0..500 {
+                // 500 × 10ms = 5 seconds
+                for (can_id, data) in scheduler.due(std::time::Instant::now(), vehicle_state) {
+                    let _ = can_ch.send_raw_can(can_id, &data, false);
                 }
-                std::thread::sleep(std::time::Duration::from_millis(100));
-                if cycle % 10 == 9 {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                if tick % 100 == 99 {
                     emit_log_simple(
-                        app,
+                        sink,
                         LogDirection::Tx,
                         &[],
-                        &format!("CAN broadcast: {}s / 5s", (cycle + 1) / 10),
+                        &format!("CAN broadcast: {}s / 5s", (tick + 1) / 100),
                     );
                 }
             }
@@ -563,7 +861,7 @@ fn can_pre_broadcast(app: &AppHandle, conn: &mut Connection) -> Result<(), Strin
         }
         Err(e) => {
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Rx,
                 &[],
                 &format!("Warning: CAN broadcast failed: {}", e),
@@ -573,7 +871,7 @@ fn can_pre_broadcast(app: &AppHandle, conn: &mut Connection) -> Result<(), Strin
 
     // Re-open ISO15765 channel with all ECU filters
     let channel = conn
-        .device
+        .device()?
         .connect_iso15765(500000)
         .map_err(|e| format!("Failed to reopen ISO15765 after broadcast: {}", e))?;
     let _ = channel.set_iso15765_config(0, 0, 0);
@@ -581,10 +879,14 @@ fn can_pre_broadcast(app: &AppHandle, conn: &mut Connection) -> Result<(), Strin
     channel.setup_iso15765_filter(ecu_addr::BCM_TX, ecu_addr::BCM_RX)?;
     channel.setup_iso15765_filter(ecu_addr::GWM_TX, ecu_addr::GWM_RX)?;
     channel.setup_iso15765_filter(ecu_addr::IPC_TX, ecu_addr::IPC_RX)?;
-    conn.channel = Some(channel);
+    let router = ChannelRouter::new(Box::new(channel));
+    if let Some(mgr) = conn.emulator_manager.as_deref() {
+        mgr.attach(&router);
+    }
+    conn.channel = Some(Arc::new(router));
 
     emit_log_simple(
-        app,
+        sink,
         LogDirection::Rx,
         &[],
         "CAN broadcast done, ISO15765 restored",
@@ -592,8 +894,8 @@ fn can_pre_broadcast(app: &AppHandle, conn: &mut Connection) -> Result<(), Strin
     Ok(())
 }
 
-fn read_did_entry<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn read_did_entry(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     tx_id: u32,
     did_id: u16,
@@ -604,11 +906,11 @@ fn read_did_entry<R: tauri::Runtime>(
 ) -> EcuInfoEntry {
     let did_hex = format!("{:04X}", did_id);
 
-    match send_read_did(app, channel, tx_id, did_id, emulator) {
+    match send_read_did(sink, channel, tx_id, did_id, emulator) {
         Ok(data) => {
             let value = format_fn(&data);
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Rx,
                 &[],
                 &format!("{} = {}", label, value),
@@ -722,8 +1024,8 @@ fn format_odometer(data: &[u8]) -> String {
     }
 }
 
-fn read_imc_info<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn read_imc_info(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     emulator: Option<&EcuEmulatorManager>,
 ) -> Vec<EcuInfoEntry> {
@@ -735,7 +1037,7 @@ fn read_imc_info<R: tauri::Runtime>(
     // In bench mode, poll until IMC responds (CAN pre-broadcast should have woken it)
     if bench_mode {
         emit_log_simple(
-            app,
+            sink,
             LogDirection::Tx,
             &[],
             "Bench mode: waiting for IMC to boot...",
@@ -743,15 +1045,15 @@ fn read_imc_info<R: tauri::Runtime>(
         let mut imc_ready = false;
         for attempt in 1..=15 {
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Tx,
                 &[0x3E, 0x00],
                 &format!("TesterPresent poll {}/15", attempt),
             );
-            match send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator) {
+            match send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator) {
                 Ok(_) => {
                     emit_log_simple(
-                        app,
+                        sink,
                         LogDirection::Rx,
                         &[],
                         &format!("IMC responded on attempt {}", attempt),
@@ -768,7 +1070,7 @@ fn read_imc_info<R: tauri::Runtime>(
         }
         if !imc_ready {
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Rx,
                 &[],
                 "IMC not responding after 15 attempts",
@@ -782,13 +1084,13 @@ fn read_imc_info<R: tauri::Runtime>(
             }];
         }
     } else {
-        emit_log_simple(app, LogDirection::Tx, &[0x3E, 0x00], "TesterPresent (IMC)");
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+        emit_log_simple(sink, LogDirection::Tx, &[0x3E, 0x00], "TesterPresent (IMC)");
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
     }
 
     // Step 2: Read D100 (works in Default + Extended Session per EXML)
     entries.push(read_did_entry(
-        app,
+        sink,
         channel,
         tx,
         did::ACTIVE_DIAG_SESSION,
@@ -810,24 +1112,24 @@ fn read_imc_info<R: tauri::Runtime>(
     ];
 
     for (did_id, label, formatter, category) in &dids {
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
         entries.push(read_did_entry(
-            app, channel, tx, *did_id, label, *formatter, category, emulator,
+            sink, channel, tx, *did_id, label, *formatter, category, emulator,
         ));
     }
 
     // Step 4: Extended Session for DID 0x0202 (requires Extended per EXML)
     emit_log_simple(
-        app,
+        sink,
         LogDirection::Tx,
         &[0x10, 0x03],
         "ExtendedSession (IMC)",
     );
-    if send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator).is_ok() {
-        emit_log_simple(app, LogDirection::Rx, &[], "Extended Session OK");
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+    if send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator).is_ok() {
+        emit_log_simple(sink, LogDirection::Rx, &[], "Extended Session OK");
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
         entries.push(read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::IMC_STATUS,
@@ -849,15 +1151,15 @@ fn read_imc_info<R: tauri::Runtime>(
     entries
 }
 
-fn read_bcm_info<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn read_bcm_info(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     emulator: Option<&EcuEmulatorManager>,
 ) -> Vec<EcuInfoEntry> {
     let tx = ecu_addr::BCM_TX;
     vec![
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::VIN,
@@ -867,7 +1169,7 @@ fn read_bcm_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::MASTER_RPM_PART,
@@ -877,7 +1179,7 @@ fn read_bcm_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::ECU_SERIAL,
@@ -887,7 +1189,7 @@ fn read_bcm_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::ECU_SERIAL2,
@@ -899,15 +1201,15 @@ fn read_bcm_info<R: tauri::Runtime>(
     ]
 }
 
-fn read_gwm_info<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn read_gwm_info(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     emulator: Option<&EcuEmulatorManager>,
 ) -> Vec<EcuInfoEntry> {
     let tx = ecu_addr::GWM_TX;
     vec![
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::VIN,
@@ -917,7 +1219,7 @@ fn read_gwm_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::MASTER_RPM_PART,
@@ -927,7 +1229,7 @@ fn read_gwm_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::BATTERY_VOLTAGE,
@@ -937,7 +1239,7 @@ fn read_gwm_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::BATTERY_SOC,
@@ -947,7 +1249,7 @@ fn read_gwm_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::BATTERY_TEMP,
@@ -959,15 +1261,15 @@ fn read_gwm_info<R: tauri::Runtime>(
     ]
 }
 
-fn read_ipc_info<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn read_ipc_info(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     emulator: Option<&EcuEmulatorManager>,
 ) -> Vec<EcuInfoEntry> {
     let tx = ecu_addr::IPC_TX;
     vec![
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::VIN,
@@ -977,7 +1279,7 @@ fn read_ipc_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             did::MASTER_RPM_PART,
@@ -987,7 +1289,7 @@ fn read_ipc_info<R: tauri::Runtime>(
             emulator,
         ),
         read_did_entry(
-            app,
+            sink,
             channel,
             tx,
             0x61BB,
@@ -1000,7 +1302,7 @@ fn read_ipc_info<R: tauri::Runtime>(
 }
 
 /// Returns path for saving dump files — uses exe parent dir so it's always findable
-fn dump_path(filename: &str) -> std::path::PathBuf {
+pub(crate) fn dump_path(filename: &str) -> std::path::PathBuf {
     if let Ok(exe) = std::env::current_exe() {
         if let Some(parent) = exe.parent() {
             return parent.join(filename);
@@ -1013,14 +1315,19 @@ fn dump_path(filename: &str) -> std::path::PathBuf {
 /// saves raw response bytes to bcm_dump.json for offline emulator tuning.
 #[tauri::command]
 pub fn scan_bcm_full(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
-    scan_bcm_full_inner(&app, &state).map_err(|e| log_err("scan_bcm_full", e))
+    scan_bcm_full_inner(&app, &state, &JobContext::detached())
+        .map_err(|e| log_err("scan_bcm_full", e))
 }
 
-fn scan_bcm_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<String, String> {
-    let conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_ref().ok_or("Not connected")?;
-    let channel: &dyn crate::j2534::Channel = conn.channel.as_ref().ok_or("No channel")?;
-    let emulator = conn.emulator_manager.as_ref();
+pub fn scan_bcm_full_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    job: &JobContext,
+) -> Result<String, String> {
+    let bus = bus_handles(state)?;
+    let port = bus.port(ecu_addr::BCM_TX, ecu_addr::BCM_RX)?;
+    let channel: &dyn crate::j2534::Channel = &port;
+    let emulator = bus.emulator.as_deref();
     let tx = ecu_addr::BCM_TX;
 
     // All DIDs to scan: standard ISO 14229 DIDs
@@ -1035,19 +1342,26 @@ fn scan_bcm_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<S
         0xDE01, 0xDE02, 0xDE03, 0xDE04, 0xDE06, 0xE103, 0xEE03, 0xEEB0, 0xEEB1, 0xEEB3, 0xEEBB,
     ];
 
-    emit_log_simple(app, LogDirection::Tx, &[], "=== BCM FULL SCAN START ===");
+    emit_log_simple(sink, LogDirection::Tx, &[], "=== BCM FULL SCAN START ===");
 
     // Wake BCM with TesterPresent
-    let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+    let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
 
     let mut did_results: Vec<serde_json::Value> = Vec::new();
     let mut failed_in_default: Vec<u16> = Vec::new();
 
     // Pass 1: Default session
-    emit_log_simple(app, LogDirection::Tx, &[], "Pass 1: Default session");
-    for &did in all_dids {
+    emit_log_simple(sink, LogDirection::Tx, &[], "Pass 1: Default session");
+    for (i, &did) in all_dids.iter().enumerate() {
+        job.check()?;
+        job.progress(
+            sink,
+            "Default session",
+            (i * 80 / all_dids.len()) as u8,
+            &format!("DID 0x{:04X}", did),
+        );
         let req = [0x22, (did >> 8) as u8, (did & 0xFF) as u8];
-        match send_uds_request(app, channel, tx, &req, false, emulator) {
+        match send_uds_request(sink, channel, tx, &req, false, emulator) {
             Ok(resp) => {
                 let hex: String = resp
                     .iter()
@@ -1083,22 +1397,29 @@ fn scan_bcm_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<S
             }
         }
         // Brief TesterPresent to keep session alive
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
     }
 
     // Pass 2: Extended session — retry failed DIDs
     if !failed_in_default.is_empty() {
         emit_log_simple(
-            app,
+            sink,
             LogDirection::Tx,
             &[0x10, 0x03],
             "Pass 2: Extended session",
         );
-        let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
-
-        for &did in &failed_in_default {
+        let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
+
+        for (i, &did) in failed_in_default.iter().enumerate() {
+            job.check()?;
+            job.progress(
+                sink,
+                "Extended session",
+                (80 + i * 15 / failed_in_default.len()) as u8,
+                &format!("DID 0x{:04X}", did),
+            );
             let req = [0x22, (did >> 8) as u8, (did & 0xFF) as u8];
-            match send_uds_request(app, channel, tx, &req, false, emulator) {
+            match send_uds_request(sink, channel, tx, &req, false, emulator) {
                 Ok(resp) => {
                     let hex: String = resp
                         .iter()
@@ -1132,17 +1453,19 @@ fn scan_bcm_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<S
                 }
                 Err(_) => {} // Still failed — keep original error entry
             }
-            let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+            let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
         }
     }
 
     // Try CCF routines on BCM
-    emit_log_simple(app, LogDirection::Tx, &[], "Trying BCM CCF routines...");
-    let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
+    job.check()?;
+    job.progress(sink, "CCF routines", 95, "0x0E02 / 0x0E01");
+    emit_log_simple(sink, LogDirection::Tx, &[], "Trying BCM CCF routines...");
+    let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
     let ccf_list_resp =
-        send_uds_request(app, channel, tx, &[0x31, 0x01, 0x0E, 0x02], true, emulator);
+        send_uds_request(sink, channel, tx, &[0x31, 0x01, 0x0E, 0x02], true, emulator);
     let ccf_retrieve_resp =
-        send_uds_request(app, channel, tx, &[0x31, 0x01, 0x0E, 0x01], true, emulator);
+        send_uds_request(sink, channel, tx, &[0x31, 0x01, 0x0E, 0x01], true, emulator);
 
     let ccf = serde_json::json!({
         "list_0E02": ccf_list_resp.as_ref().ok().map(|r| r.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")),
@@ -1169,6 +1492,8 @@ fn scan_bcm_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<S
     let json_str = serde_json::to_string_pretty(&dump).map_err(|e| e.to_string())?;
     let path = dump_path("bcm_dump.json");
     std::fs::write(&path, &json_str).map_err(|e| format!("Write failed: {}", e))?;
+    let dids = SnapshotData::DidScan { dids: scanned_dids(&did_results) };
+    archive_snapshot(sink, &state.archive, "BCM", dids);
 
     let msg = format!(
         "BCM scan done: {}/{} DIDs OK → {}",
@@ -1176,21 +1501,26 @@ fn scan_bcm_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<S
         all_dids.len(),
         path.display()
     );
-    emit_log_simple(app, LogDirection::Rx, &[], &msg);
+    emit_log_simple(sink, LogDirection::Rx, &[], &msg);
     Ok(msg)
 }
 
 /// Full GWM DID scan — reads all GWM DIDs (MDX_GWM X260 EXML), saves to gwm_dump.json
 #[tauri::command]
 pub fn scan_gwm_full(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
-    scan_gwm_full_inner(&app, &state).map_err(|e| log_err("scan_gwm_full", e))
+    scan_gwm_full_inner(&app, &state, &JobContext::detached())
+        .map_err(|e| log_err("scan_gwm_full", e))
 }
 
-fn scan_gwm_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<String, String> {
-    let conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_ref().ok_or("Not connected")?;
-    let channel: &dyn crate::j2534::Channel = conn.channel.as_ref().ok_or("No channel")?;
-    let emulator = conn.emulator_manager.as_ref();
+pub fn scan_gwm_full_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    job: &JobContext,
+) -> Result<String, String> {
+    let bus = bus_handles(state)?;
+    let port = bus.port(ecu_addr::GWM_TX, ecu_addr::GWM_RX)?;
+    let channel: &dyn crate::j2534::Channel = &port;
+    let emulator = bus.emulator.as_deref();
     let tx = ecu_addr::GWM_TX;
 
     // Standard ISO + GWM-specific DIDs from MDX_GWM X260
@@ -1203,30 +1533,36 @@ fn scan_gwm_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<S
         0xD100,
     ];
 
-    emit_log_simple(app, LogDirection::Tx, &[], "=== GWM FULL SCAN START ===");
+    emit_log_simple(sink, LogDirection::Tx, &[], "=== GWM FULL SCAN START ===");
     scan_ecu_dids(
-        app,
+        sink,
         channel,
         tx,
         "GWM",
         ecu_addr::GWM_RX,
         all_dids,
         emulator,
-        "gwm_dump.json",
+        &state.archive,
+        job,
     )
 }
 
 /// Full IPC DID scan — reads all IPC DIDs (MDX_IPC X260 EXML), saves to ipc_dump.json
 #[tauri::command]
 pub fn scan_ipc_full(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
-    scan_ipc_full_inner(&app, &state).map_err(|e| log_err("scan_ipc_full", e))
+    scan_ipc_full_inner(&app, &state, &JobContext::detached())
+        .map_err(|e| log_err("scan_ipc_full", e))
 }
 
-fn scan_ipc_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<String, String> {
-    let conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_ref().ok_or("Not connected")?;
-    let channel: &dyn crate::j2534::Channel = conn.channel.as_ref().ok_or("No channel")?;
-    let emulator = conn.emulator_manager.as_ref();
+pub fn scan_ipc_full_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    job: &JobContext,
+) -> Result<String, String> {
+    let bus = bus_handles(state)?;
+    let port = bus.port(ecu_addr::IPC_TX, ecu_addr::IPC_RX)?;
+    let channel: &dyn crate::j2534::Channel = &port;
+    let emulator = bus.emulator.as_deref();
     let tx = ecu_addr::IPC_TX;
 
     // IPC DIDs from MDX_IPC X260 + standard ISO
@@ -1236,40 +1572,50 @@ fn scan_ipc_full_inner(app: &AppHandle, state: &State<'_, AppState>) -> Result<S
         0xD100,
     ];
 
-    emit_log_simple(app, LogDirection::Tx, &[], "=== IPC FULL SCAN START ===");
+    emit_log_simple(sink, LogDirection::Tx, &[], "=== IPC FULL SCAN START ===");
     scan_ecu_dids(
-        app,
+        sink,
         channel,
         tx,
         "IPC",
         ecu_addr::IPC_RX,
         all_dids,
         emulator,
-        "ipc_dump.json",
+        &state.archive,
+        job,
     )
 }
 
-/// Generic ECU DID scan: default session, then extended for failed DIDs. Saves to JSON.
+/// Generic ECU DID scan: default session, then extended for failed DIDs. Saves to
+/// `<ecu>_dump.json` and the vehicle archive.
 fn scan_ecu_dids(
-    app: &AppHandle,
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     tx: u32,
     ecu_name: &str,
     rx_id: u32,
     all_dids: &[u16],
     emulator: Option<&EcuEmulatorManager>,
-    filename: &str,
+    archive: &VehicleArchive,
+    job: &JobContext,
 ) -> Result<String, String> {
     // Wake ECU
-    let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+    let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
 
     let mut did_results: Vec<serde_json::Value> = Vec::new();
     let mut failed_in_default: Vec<u16> = Vec::new();
 
-    emit_log_simple(app, LogDirection::Tx, &[], "Pass 1: Default session");
-    for &did in all_dids {
+    emit_log_simple(sink, LogDirection::Tx, &[], "Pass 1: Default session");
+    for (i, &did) in all_dids.iter().enumerate() {
+        job.check()?;
+        job.progress(
+            sink,
+            "Default session",
+            (i * 80 / all_dids.len()) as u8,
+            &format!("DID 0x{:04X}", did),
+        );
         let req = [0x22, (did >> 8) as u8, (did & 0xFF) as u8];
-        match send_uds_request(app, channel, tx, &req, false, emulator) {
+        match send_uds_request(sink, channel, tx, &req, false, emulator) {
             Ok(resp) => {
                 let hex: String = resp
                     .iter()
@@ -1304,20 +1650,27 @@ fn scan_ecu_dids(
                 }));
             }
         }
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
     }
 
     if !failed_in_default.is_empty() {
         emit_log_simple(
-            app,
+            sink,
             LogDirection::Tx,
             &[0x10, 0x03],
             "Pass 2: Extended session",
         );
-        let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
-        for &did in &failed_in_default {
+        let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
+        for (i, &did) in failed_in_default.iter().enumerate() {
+            job.check()?;
+            job.progress(
+                sink,
+                "Extended session",
+                (80 + i * 20 / failed_in_default.len()) as u8,
+                &format!("DID 0x{:04X}", did),
+            );
             let req = [0x22, (did >> 8) as u8, (did & 0xFF) as u8];
-            match send_uds_request(app, channel, tx, &req, false, emulator) {
+            match send_uds_request(sink, channel, tx, &req, false, emulator) {
                 Ok(resp) => {
                     let hex: String = resp
                         .iter()
@@ -1351,7 +1704,7 @@ fn scan_ecu_dids(
                 }
                 Err(_) => {}
             }
-            let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+            let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
         }
     }
 
@@ -1370,8 +1723,10 @@ fn scan_ecu_dids(
     });
 
     let json_str = serde_json::to_string_pretty(&dump).map_err(|e| e.to_string())?;
-    let path = dump_path(filename);
+    let path = dump_path(&format!("{}_dump.json", ecu_name.to_lowercase()));
     std::fs::write(&path, &json_str).map_err(|e| format!("Write failed: {}", e))?;
+    let dids = SnapshotData::DidScan { dids: scanned_dids(&did_results) };
+    archive_snapshot(sink, archive, ecu_name, dids);
 
     let msg = format!(
         "{} scan done: {}/{} DIDs OK → {}",
@@ -1380,22 +1735,22 @@ fn scan_ecu_dids(
         all_dids.len(),
         path.display()
     );
-    emit_log_simple(app, LogDirection::Rx, &[], &msg);
+    emit_log_simple(sink, LogDirection::Rx, &[], &msg);
     Ok(msg)
 }
 
 /// SDD prerequisite flow: TesterPresent → Extended Session → Security Access (if needed)
 /// This is the standard JLR SDD sequence required before executing secured routines.
-fn sdd_prerequisite_flow<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn sdd_prerequisite_flow(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     needs_security: bool,
     emulator: Option<&EcuEmulatorManager>,
 ) -> Result<(), String> {
     // Step 1: TesterPresent
-    emit_log_simple(app, LogDirection::Tx, &[0x3E, 0x00], "TesterPresent");
+    emit_log_simple(sink, LogDirection::Tx, &[0x3E, 0x00], "TesterPresent");
     send_uds_request(
-        app,
+        sink,
         channel,
         ecu_addr::IMC_TX,
         &[0x3E, 0x00],
@@ -1406,13 +1761,13 @@ fn sdd_prerequisite_flow<R: tauri::Runtime>(
 
     // Step 2: Extended session
     emit_log_simple(
-        app,
+        sink,
         LogDirection::Tx,
         &[0x10, 0x03],
         "DiagnosticSessionControl Extended",
     );
     send_uds_request(
-        app,
+        sink,
         channel,
         ecu_addr::IMC_TX,
         &[0x10, 0x03],
@@ -1424,13 +1779,13 @@ fn sdd_prerequisite_flow<R: tauri::Runtime>(
     // Step 3: Security Access (if required)
     if needs_security {
         emit_log_simple(
-            app,
+            sink,
             LogDirection::Tx,
             &[0x27, 0x11],
             "SecurityAccess RequestSeed",
         );
         let seed_resp = send_uds_request(
-            app,
+            sink,
             channel,
             ecu_addr::IMC_TX,
             &[0x27, 0x11],
@@ -1455,13 +1810,13 @@ fn sdd_prerequisite_flow<R: tauri::Runtime>(
                 let mut key_request = vec![0x27, 0x12];
                 key_request.extend_from_slice(&key_bytes);
                 emit_log_simple(
-                    app,
+                    sink,
                     LogDirection::Tx,
                     &key_request,
                     "SecurityAccess SendKey",
                 );
                 send_uds_request(
-                    app,
+                    sink,
                     channel,
                     ecu_addr::IMC_TX,
                     &key_request,
@@ -1494,26 +1849,25 @@ pub fn run_routine(
     run_routine_inner(&app, &state, routine_id, &data).map_err(|e| log_err("run_routine", e))
 }
 
-fn run_routine_inner(
-    app: &AppHandle,
-    state: &State<'_, AppState>,
+pub fn run_routine_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
     routine_id: u16,
     data: &[u8],
 ) -> Result<RoutineResponse, String> {
-    let conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_ref().ok_or("Not connected")?;
-    let channel: &dyn crate::j2534::Channel =
-        conn.channel.as_ref().ok_or("No channel available")?;
+    let bus = bus_handles(state)?;
+    let port = bus.port(ecu_addr::IMC_TX, ecu_addr::IMC_RX)?;
+    let channel: &dyn crate::j2534::Channel = &port;
 
     // Look up routine metadata for SDD flow requirements
     let meta = find_routine_meta(routine_id);
     let needs_security = meta.as_ref().map_or(false, |m| m.needs_security);
     let needs_pending = meta.as_ref().map_or(false, |m| m.needs_pending);
 
-    let emulator = conn.emulator_manager.as_ref();
+    let emulator = bus.emulator.as_deref();
 
     // Run SDD prerequisite flow (TesterPresent + Extended Session + optional Security)
-    sdd_prerequisite_flow(app, channel, needs_security, emulator)?;
+    sdd_prerequisite_flow(sink, channel, needs_security, emulator)?;
 
     // Send RoutineControl Start
     let mut request = vec![
@@ -1525,7 +1879,7 @@ fn run_routine_inner(
     request.extend_from_slice(data);
 
     let resp = send_uds_request(
-        app,
+        sink,
         channel,
         ecu_addr::IMC_TX,
         &request,
@@ -1563,7 +1917,7 @@ fn run_routine_inner(
 }
 
 /// CAN sniff entry — one captured CAN frame
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CanSniffEntry {
     pub timestamp_ms: u64,
     pub can_id: String,
@@ -1571,6 +1925,38 @@ pub struct CanSniffEntry {
     pub data_len: usize,
 }
 
+impl From<&CanFrame> for CanSniffEntry {
+    fn from(f: &CanFrame) -> Self {
+        Self {
+            timestamp_ms: f.timestamp_us / 1000,
+            can_id: format!("0x{:03X}", f.can_id),
+            data_hex: f
+                .data
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(" "),
+            data_len: f.data.len(),
+        }
+    }
+}
+
+impl TryFrom<&CanSniffEntry> for CanFrame {
+    type Error = String;
+
+    fn try_from(e: &CanSniffEntry) -> Result<Self, String> {
+        let can_id =
+            canlog::parse_id(&e.can_id).ok_or_else(|| format!("Bad CAN ID '{}'", e.can_id))?;
+        let data = canlog::parse_bytes(e.data_hex.split_whitespace())
+            .ok_or_else(|| format!("Bad CAN data '{}'", e.data_hex))?;
+        Ok(CanFrame::new(e.timestamp_ms * 1000, can_id, data))
+    }
+}
+
+fn sniff_entries(frames: &[CanFrame]) -> Vec<CanSniffEntry> {
+    frames.iter().map(CanSniffEntry::from).collect()
+}
+
 /// CAN sniff result
 #[derive(Debug, Serialize)]
 pub struct CanSniffResult {
@@ -1609,6 +1995,8 @@ pub struct PreFlightInfo {
     pub option_467_extracted: Option<u8>,
     pub option_467_desc: String,
     pub warnings: Vec<String>,
+    /// CCF rules the GWM copy breaks; warnings and errors also land in `warnings`
+    pub findings: Vec<RuleFinding>,
 }
 
 /// Mid-flight: IMC CCF verification after 0x0E06 transfer, before 0x6038
@@ -1638,194 +2026,369 @@ pub fn can_sniff_routine(
     app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<CanSniffResult, String> {
-    can_sniff_routine_inner(&app, &state).map_err(|e| log_err("can_sniff_routine", e))
+    can_sniff_routine_inner(&app, &state, &JobContext::detached())
+        .map_err(|e| log_err("can_sniff_routine", e))
 }
 
-fn can_sniff_routine_inner(
-    app: &AppHandle,
-    state: &State<'_, AppState>,
+pub fn can_sniff_routine_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    job: &JobContext,
 ) -> Result<CanSniffResult, String> {
-    let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_mut().ok_or("Not connected")?;
+    let action = SniffAction::Routine {
+        routine_id: 0x6038,
+        data: Vec::new(),
+    };
+    let options = SniffOptions {
+        baseline_s: 5,
+        during_s: 30,
+        after_s: 0,
+        ..Default::default()
+    };
+    let (windows, exchange, _) =
+        sniff_around(sink, state, ecu_addr::IMC_TX, &action, &options, job)?;
+    let diff = windows.diff(options.period_tolerance_pct);
+    let new_can_ids: Vec<String> = diff.new_ids.iter().map(|id| format!("0x{:03X}", id)).collect();
 
-    // === Phase 1: Baseline CAN capture (5 seconds) ===
-    emit_log_simple(
-        app,
-        LogDirection::Tx,
-        &[],
-        "CAN Sniff: Phase 1 — baseline capture (5s)...",
+    let summary = format!(
+        "Baseline: {} frames ({} IDs) | After 0x6038: {} frames ({} IDs) | New IDs: {}",
+        diff.baseline.frames,
+        diff.baseline.ids,
+        diff.during.frames,
+        diff.during.ids,
+        if new_can_ids.is_empty() {
+            "NONE — IMC sends no new CAN traffic during 0x6038".to_string()
+        } else {
+            new_can_ids.join(", ")
+        }
     );
+    emit_log_simple(sink, LogDirection::Rx, &[], &summary);
 
-    // Close ISO15765 channel to free J2534 slot
-    conn.channel.take();
-
-    let baseline_frames = capture_raw_can(app, conn, 5)?;
-    let baseline_ids: std::collections::HashSet<String> =
-        baseline_frames.iter().map(|f| f.can_id.clone()).collect();
+    Ok(CanSniffResult {
+        routine_response: exchange.response,
+        baseline_frames: sniff_entries(&windows.baseline),
+        after_frames: sniff_entries(&windows.during),
+        new_can_ids,
+        summary,
+    })
+}
 
-    emit_log_simple(
-        app,
-        LogDirection::Rx,
-        &[],
-        &format!(
-            "Baseline: {} frames, {} unique CAN IDs",
-            baseline_frames.len(),
-            baseline_ids.len()
-        ),
-    );
+// ─── Differential capture ───────────────────────────────────────────
 
-    // === Phase 2: Send 0x6038 on ISO15765 ===
-    emit_log_simple(
-        app,
-        LogDirection::Tx,
-        &[],
-        "CAN Sniff: Phase 2 — sending 0x6038...",
-    );
+/// The UDS exchange in the middle of a differential capture
+#[derive(Debug, Clone, Serialize)]
+pub struct SniffExchange {
+    pub request: String,
+    /// Final response (hex)
+    pub response: Option<String>,
+    /// Negative response or timeout
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
 
-    // Reopen ISO15765 channel
-    let channel = conn
-        .device
-        .connect_iso15765(500000)
-        .map_err(|e| format!("Failed to open ISO15765: {}", e))?;
-    let _ = channel.set_iso15765_config(0, 0, 0);
-    channel.setup_iso15765_filter(ecu_addr::IMC_TX, ecu_addr::IMC_RX)?;
+/// Differential capture report: the exchange and how the bus changed around it
+#[derive(Debug, Serialize)]
+pub struct SniffActionResult {
+    pub ecu_tx: u32,
+    pub action: SniffAction,
+    pub exchange: SniffExchange,
+    #[serde(flatten)]
+    pub diff: SniffDiff,
+    pub summary: String,
+    pub saved: Option<CanLogInfo>,
+}
 
-    // TesterPresent + Extended Session
-    let _ = channel.send(&PassThruMsg::new_iso15765(ecu_addr::IMC_TX, &[0x3E, 0x00]), 2000);
-    std::thread::sleep(std::time::Duration::from_millis(200));
-    let _ = channel.read(500); // drain
-
-    let _ = channel.send(&PassThruMsg::new_iso15765(ecu_addr::IMC_TX, &[0x10, 0x03]), 2000);
-    std::thread::sleep(std::time::Duration::from_millis(200));
-    let _ = channel.read(500); // drain
-
-    // Send 0x6038 routine start
-    let routine_req = [0x31, 0x01, 0x60, 0x38]; // 0x6038 no sub-function data
-    channel.send(&PassThruMsg::new_iso15765(ecu_addr::IMC_TX, &routine_req), 2000)?;
-
-    // Read immediate response (might be pending 0x78 or immediate result)
-    std::thread::sleep(std::time::Duration::from_millis(500));
-    let mut routine_response = None;
-    if let Ok(msgs) = channel.read(2000) {
-        for msg in &msgs {
-            if msg.can_id() == ecu_addr::IMC_RX && msg.data_size > 4 {
-                let payload = msg.payload();
-                let hex: String = payload
-                    .iter()
-                    .map(|b| format!("{:02X}", b))
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                emit_log_simple(
-                    app,
-                    LogDirection::Rx,
-                    payload,
-                    &format!("0x6038 immediate response: {}", hex),
-                );
-                routine_response = Some(hex);
-            }
+/// Record the bus before, right after and later after one UDS request (routine,
+/// DID write, reset, session change or raw) and diff the windows per CAN ID.
+/// `ecu` is an ECU name or a `0x` request ID.
+#[tauri::command]
+pub fn sniff_action(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ecu: String,
+    action: SniffAction,
+    options: Option<SniffOptions>,
+) -> Result<SniffActionResult, String> {
+    let options = options.unwrap_or_default();
+    sniff_action_inner(&app, &state, &ecu, &action, &options, &JobContext::detached())
+        .map_err(|e| log_err("sniff_action", e))
+}
+
+pub fn sniff_action_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecu: &str,
+    action: &SniffAction,
+    options: &SniffOptions,
+    job: &JobContext,
+) -> Result<SniffActionResult, String> {
+    let ecu_tx = ecu_tx_by_name_or_id(ecu).ok_or_else(|| format!("Unknown ECU: {}", ecu))?;
+    let (windows, exchange, post_start_us) =
+        sniff_around(sink, state, ecu_tx, action, options, job)?;
+    let diff = windows.diff(options.period_tolerance_pct);
+    let summary = diff.summary(&action.describe());
+    emit_log_simple(sink, LogDirection::Rx, &[], &summary);
+
+    let saved = match &options.save {
+        Some(path) => {
+            let log = windows.to_log(post_start_us);
+            let written = canlog::save(std::path::Path::new(path), &log, None)?;
+            emit_log_simple(
+                sink,
+                LogDirection::Rx,
+                &[],
+                &format!("CAN Sniff: {} frames saved to {}", log.frames.len(), path),
+            );
+            Some(CanLogInfo::new(path, written, &log))
         }
-    }
+        None => None,
+    };
+
+    Ok(SniffActionResult {
+        ecu_tx,
+        action: action.clone(),
+        exchange,
+        diff,
+        summary,
+        saved,
+    })
+}
 
-    // Close ISO15765 — routine continues executing internally on IMC
-    drop(channel);
+/// Baseline capture, the request, then one capture covering the during and after
+/// windows. The ISO15765 channel gives up its slot for the captures and is restored
+/// at the end, also on failure. Returns the windows, the exchange and when the
+/// post-request capture started (µs after the baseline started).
+fn sniff_around(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecu_tx: u32,
+    action: &SniffAction,
+    options: &SniffOptions,
+    job: &JobContext,
+) -> Result<(SniffWindows, SniffExchange, u64), String> {
+    action.validate()?;
+    options.validate()?;
+    // Raw CAN capture needs the adapter; check before giving up the ISO15765 channel
+    with_connection(state, |conn| conn.device().map(|_| ()))?;
+
+    let result = sniff_windows(sink, state, ecu_tx, action, options, job);
+    restore_iso15765_channel(state).map_err(|e| format!("Failed to restore ISO15765: {}", e))?;
+    result
+}
 
-    // === Phase 3: CAN capture after 0x6038 (30 seconds) ===
+fn sniff_windows(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecu_tx: u32,
+    action: &SniffAction,
+    options: &SniffOptions,
+    job: &JobContext,
+) -> Result<(SniffWindows, SniffExchange, u64), String> {
+    let label = action.describe();
     emit_log_simple(
-        app,
+        sink,
         LogDirection::Tx,
         &[],
-        "CAN Sniff: Phase 3 — capturing CAN after 0x6038 (30s)...",
+        &format!("CAN Sniff: baseline capture ({}s)...", options.baseline_s),
+    );
+    let can_ch = open_raw_can(state)?;
+    let start = std::time::Instant::now();
+    let baseline =
+        capture_raw_can(sink, &can_ch, options.baseline_s, job, "Baseline capture", (0, 15))?;
+    drop(can_ch);
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!("Baseline: {} frames", baseline.len()),
     );
 
-    let after_frames = capture_raw_can(app, conn, 30)?;
-    let after_ids: std::collections::HashSet<String> =
-        after_frames.iter().map(|f| f.can_id.clone()).collect();
-
-    // Find CAN IDs that appeared ONLY after 0x6038
-    let mut new_can_ids: Vec<String> = after_ids.difference(&baseline_ids).cloned().collect();
-    new_can_ids.sort();
+    job.check()?;
+    job.progress(sink, &label, 15, "TesterPresent + request");
+    emit_log_simple(sink, LogDirection::Tx, &[], &format!("CAN Sniff: sending {}...", label));
+    restore_iso15765_channel(state).map_err(|e| format!("Failed to open ISO15765: {}", e))?;
+    let exchange = sniff_exchange(sink, state, ecu_tx, action)?;
+    // Free the slot at once: whatever the ECU does next belongs to the during window
+    let can_ch = open_raw_can(state)?;
 
-    let summary = format!(
-        "Baseline: {} frames ({} IDs) | After 0x6038: {} frames ({} IDs) | New IDs: {}",
-        baseline_frames.len(),
-        baseline_ids.len(),
-        after_frames.len(),
-        after_ids.len(),
-        if new_can_ids.is_empty() {
-            "NONE — IMC sends no new CAN traffic during 0x6038".to_string()
-        } else {
-            new_can_ids.join(", ")
-        }
+    let post_start_us = start.elapsed().as_micros() as u64;
+    let seconds = options.during_s + options.after_s;
+    emit_log_simple(
+        sink,
+        LogDirection::Tx,
+        &[],
+        &format!("CAN Sniff: capturing CAN after {} ({}s)...", label, seconds),
     );
+    let post = capture_raw_can(sink, &can_ch, seconds, job, "Post-request capture", (20, 100))?;
+    Ok((SniffWindows::new(baseline, post, options), exchange, post_start_us))
+}
+
+/// TesterPresent, the action's prerequisite session, then the request itself. Only a
+/// failed prerequisite is an error; the request's own NRC or timeout is part of the report.
+fn sniff_exchange(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecu_tx: u32,
+    action: &SniffAction,
+) -> Result<SniffExchange, String> {
+    let bus = bus_handles(state)?;
+    // JLR response IDs are request ID + 8
+    let port = bus.port(ecu_tx, ecu_tx + 8)?;
+    let emulator = bus.emulator.as_deref();
+
+    emit_log_simple(sink, LogDirection::Tx, &[0x3E, 0x00], "TesterPresent");
+    let _ = send_uds_request(sink, &port, ecu_tx, &[0x3E, 0x00], false, emulator);
+    if let Some(session) = action.prerequisite_session() {
+        let req = [0x10, session];
+        emit_log_simple(sink, LogDirection::Tx, &req, "DiagnosticSessionControl");
+        send_uds_request(sink, &port, ecu_tx, &req, false, emulator)
+            .map_err(|e| format!("Session 0x{:02X} failed: {}", session, e))?;
+    }
 
-    emit_log_simple(app, LogDirection::Rx, &[], &summary);
+    let request = action.request();
+    emit_log_simple(sink, LogDirection::Tx, &request, &action.describe());
+    let start = std::time::Instant::now();
+    // Don't sit out 0x78 pending answers: a long routine is what the during window is for
+    let result = send_uds_request(sink, &port, ecu_tx, &request, false, emulator);
+    let hex = |b: &[u8]| b.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+    let (response, error) = match result {
+        Ok(resp) => (Some(hex(&resp)), None),
+        Err(e) => (None, Some(e)),
+    };
+    Ok(SniffExchange {
+        request: hex(&request),
+        response,
+        error,
+        duration_ms: start.elapsed().as_millis() as u64,
+    })
+}
 
-    // === Restore ISO15765 channel ===
-    let channel = conn
-        .device
-        .connect_iso15765(500000)
-        .map_err(|e| format!("Failed to restore ISO15765: {}", e))?;
+/// Reopen the ISO15765 channel with all ECU filters (after a raw CAN capture took its slot)
+fn reopen_iso15765_channel(conn: &mut Connection) -> Result<(), String> {
+    let channel = conn.device()?.connect_iso15765(500000)?;
     let _ = channel.set_iso15765_config(0, 0, 0);
     let _ = channel.setup_iso15765_filter(ecu_addr::IMC_TX, ecu_addr::IMC_RX);
     let _ = channel.setup_iso15765_filter(ecu_addr::BCM_TX, ecu_addr::BCM_RX);
     let _ = channel.setup_iso15765_filter(ecu_addr::GWM_TX, ecu_addr::GWM_RX);
     let _ = channel.setup_iso15765_filter(ecu_addr::IPC_TX, ecu_addr::IPC_RX);
-    conn.channel = Some(channel);
+    let router = ChannelRouter::new(Box::new(channel));
+    if let Some(mgr) = conn.emulator_manager.as_deref() {
+        mgr.attach(&router);
+    }
+    conn.channel = Some(Arc::new(router));
+    Ok(())
+}
 
-    Ok(CanSniffResult {
-        routine_response,
-        baseline_frames,
-        after_frames,
-        new_can_ids,
-        summary,
+/// Close the ISO15765 channel immediately, even if other threads still hold
+/// the router (their in-flight requests fail with "Channel closed").
+fn close_iso15765_channel(conn: &mut Connection) {
+    if let Some(router) = conn.channel.take() {
+        router.close();
+    }
+}
+
+/// Run `f` on the connection, holding its lock only for that long
+fn with_connection<T>(
+    state: &AppState,
+    f: impl FnOnce(&mut Connection) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut conn = state.connection.lock().map_err(|e| e.to_string())?;
+    f(conn.as_mut().ok_or("Not connected")?)
+}
+
+/// Give the ISO15765 slot to a raw CAN channel that passes every frame. The
+/// connection is only locked for the swap, so other commands fail fast with
+/// "No channel available" instead of queueing behind a capture.
+fn open_raw_can(state: &AppState) -> Result<J2534Channel, String> {
+    with_connection(state, |conn| {
+        conn.device()?;
+        close_iso15765_channel(conn);
+        let can_ch = conn
+            .device()?
+            .connect_can(500000)
+            .map_err(|e| format!("CAN connect failed: {}", e))?;
+        can_ch
+            .setup_can_pass_filter()
+            .map_err(|e| format!("CAN pass filter failed: {}", e))?;
+        Ok(can_ch)
+    })
+}
+
+/// Reopen the ISO15765 channel if a raw CAN channel took its slot
+fn restore_iso15765_channel(state: &AppState) -> Result<(), String> {
+    with_connection(state, |conn| match conn.channel {
+        Some(_) => Ok(()),
+        None => reopen_iso15765_channel(conn),
     })
 }
 
-/// Capture raw CAN traffic for the given number of seconds.
-/// Opens a raw CAN channel with PASS filter, reads all messages, closes channel.
+/// Read whatever frames a raw CAN channel has within `timeout_ms`, stamped in µs
+/// since `start`. The first frame ever read anchors the adapter clock: `anchor` holds
+/// its (adapter timestamp, µs since start).
+fn read_raw_frames(
+    can_ch: &J2534Channel,
+    timeout_ms: u32,
+    start: std::time::Instant,
+    anchor: &mut Option<(u32, u64)>,
+) -> Vec<CanFrame> {
+    let Ok(msgs) = can_ch.read(timeout_ms) else {
+        return Vec::new();
+    };
+    msgs.iter()
+        .filter(|msg| msg.data_size >= 4)
+        .map(|msg| {
+            let elapsed_us = start.elapsed().as_micros() as u64;
+            let (hw_start, host_start) = *anchor.get_or_insert((msg.timestamp, elapsed_us));
+            // Adapter timestamps are µs in a wrapping u32 (~71 min)
+            CanFrame {
+                timestamp_us: host_start + msg.timestamp.wrapping_sub(hw_start) as u64,
+                can_id: msg.can_id(),
+                data: msg.data[4..msg.data_size as usize].to_vec(),
+                rx_status: msg.rx_status,
+            }
+        })
+        .collect()
+}
+
+/// Capture raw CAN traffic on `can_ch` (see `open_raw_can`) for the given number
+/// of seconds. Frames keep the adapter's timestamps and RxStatus; the first frame
+/// anchors the adapter clock to the capture start.
+/// Progress is reported to `job` scaled into the `percent` range (start, end).
 fn capture_raw_can(
-    app: &AppHandle,
-    conn: &mut Connection,
+    sink: &dyn LogSink,
+    can_ch: &J2534Channel,
     seconds: u32,
-) -> Result<Vec<CanSniffEntry>, String> {
-    let can_ch = conn
-        .device
-        .connect_can(500000)
-        .map_err(|e| format!("CAN connect failed: {}", e))?;
-    can_ch
-        .setup_can_pass_filter()
-        .map_err(|e| format!("CAN pass filter failed: {}", e))?;
-
+    job: &JobContext,
+    phase: &str,
+    percent: (u8, u8),
+) -> Result<Vec<CanFrame>, String> {
     let mut frames = Vec::new();
     let start = std::time::Instant::now();
     let duration = std::time::Duration::from_secs(seconds as u64);
+    // (adapter timestamp, µs since start) of the first frame
+    let mut anchor: Option<(u32, u64)> = None;
     let mut last_log = 0u64;
+    let mut last_progress = 0u64;
 
     while start.elapsed() < duration {
-        if let Ok(msgs) = can_ch.read(200) {
-            for msg in &msgs {
-                if msg.data_size >= 4 {
-                    let can_id = msg.can_id();
-                    let data = &msg.data[4..msg.data_size as usize];
-                    frames.push(CanSniffEntry {
-                        timestamp_ms: start.elapsed().as_millis() as u64,
-                        can_id: format!("0x{:03X}", can_id),
-                        data_hex: data
-                            .iter()
-                            .map(|b| format!("{:02X}", b))
-                            .collect::<Vec<_>>()
-                            .join(" "),
-                        data_len: data.len(),
-                    });
-                }
-            }
-        }
+        job.check()?;
+        frames.extend(read_raw_frames(can_ch, 200, start, &mut anchor));
 
         let elapsed_secs = start.elapsed().as_secs();
+        if elapsed_secs > last_progress {
+            last_progress = elapsed_secs;
+            let span = (percent.1 - percent.0) as u64;
+            job.progress(
+                sink,
+                phase,
+                percent.0 + (elapsed_secs * span / seconds.max(1) as u64) as u8,
+                &format!("{}s / {}s ({} frames)", elapsed_secs, seconds, frames.len()),
+            );
+        }
         if elapsed_secs > last_log && elapsed_secs % 5 == 0 {
             last_log = elapsed_secs;
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Tx,
                 &[],
                 &format!("CAN capture: {}s / {}s ({} frames)", elapsed_secs, seconds, frames.len()),
@@ -1833,15 +2396,553 @@ fn capture_raw_can(
         }
     }
 
-    drop(can_ch);
     Ok(frames)
 }
 
+// ─── CAN trace replay ───────────────────────────────────────────────
+
+/// Replay report: the trace that was played and how it went
+#[derive(Debug, Serialize)]
+pub struct ReplayReport {
+    pub source: String,
+    pub format: CaptureFormat,
+    #[serde(flatten)]
+    pub stats: ReplayStats,
+}
+
+/// Replay a CAN capture (can_dump text, Live CAN Monitor log or sniff JSON) onto the
+/// bus with its original timing. Uses the bench broadcast channel when one is open;
+/// otherwise the ISO15765 channel gives its slot to a raw CAN channel for the replay.
+/// The connection is not locked while frames go out; a cancel ends the replay with
+/// the stats so far.
+pub fn replay_can_log_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    path: &str,
+    options: &ReplayOptions,
+    job: &JobContext,
+) -> Result<ReplayReport, String> {
+    options.validate()?;
+    let CanLog { format, frames, .. } = canlog::load(std::path::Path::new(path))?;
+    emit_log_simple(
+        sink,
+        LogDirection::Tx,
+        &[],
+        &format!(
+            "CAN replay: {} ({}, {} frames, speed {}x, loops {})",
+            path,
+            format.name(),
+            frames.len(),
+            options.speed,
+            if options.loops == 0 {
+                "until cancelled".to_string()
+            } else {
+                options.loops.to_string()
+            }
+        ),
+    );
+
+    let send = |can_ch: &J2534Channel, frame: &CanFrame| {
+        can_ch.send_raw_can(frame.can_id, &frame.data, frame.is_extended())
+    };
+    let bench = with_connection(state, |conn| Ok(conn.can_channel.clone()))?;
+    let stats = if let Some(can_ch) = bench {
+        replay::replay(&frames, options, sink, job, |frame| send(&can_ch, frame))?
+    } else {
+        let can_ch = open_raw_can(state)?;
+        let outcome = replay::replay(&frames, options, sink, job, |frame| send(&can_ch, frame));
+        drop(can_ch);
+        // Give the slot back even after a failed replay
+        let reopened = restore_iso15765_channel(state);
+        let stats = outcome?;
+        reopened.map_err(|e| format!("Failed to restore ISO15765: {}", e))?;
+        stats
+    };
+
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "CAN replay {}: {} frames sent ({} errors) in {} ms, \
+             jitter mean {} µs / p95 {} µs / max {} µs",
+            if stats.cancelled { "cancelled" } else { "done" },
+            stats.frames_sent,
+            stats.send_errors,
+            stats.duration_ms,
+            stats.jitter.mean_us,
+            stats.jitter.p95_us,
+            stats.jitter.max_us
+        ),
+    );
+    Ok(ReplayReport {
+        source: path.to_string(),
+        format,
+        stats,
+    })
+}
+
+/// Summary of a CAN log written by `convert_can_log` or `save_can_monitor`
+#[derive(Debug, Serialize)]
+pub struct CanLogInfo {
+    pub path: String,
+    pub format: CaptureFormat,
+    pub source_format: CaptureFormat,
+    pub frames: usize,
+    pub unique_ids: usize,
+    pub duration_ms: u64,
+}
+
+impl CanLogInfo {
+    fn new(path: &str, format: CaptureFormat, log: &CanLog) -> Self {
+        let unique_ids: std::collections::HashSet<u32> =
+            log.frames.iter().map(|f| f.can_id).collect();
+        Self {
+            path: path.to_string(),
+            format,
+            source_format: log.format,
+            frames: log.frames.len(),
+            unique_ids: unique_ids.len(),
+            duration_ms: log.duration_us() / 1000,
+        }
+    }
+}
+
+/// Convert a CAN capture between formats (candump, Vector ASC/BLF, PCAP/PCAPNG and our
+/// own dumps). Without `format` the output file's extension decides.
+#[tauri::command]
+pub fn convert_can_log(
+    app: AppHandle,
+    input: String,
+    output: String,
+    format: Option<String>,
+) -> Result<CanLogInfo, String> {
+    convert_can_log_inner(&app, &input, &output, format.as_deref())
+        .map_err(|e| log_err("convert_can_log", e))
+}
+
+pub fn convert_can_log_inner(
+    sink: &dyn LogSink,
+    input: &str,
+    output: &str,
+    format: Option<&str>,
+) -> Result<CanLogInfo, String> {
+    let format = format.map(CaptureFormat::parse).transpose()?;
+    let log = canlog::load(std::path::Path::new(input))?;
+    let written = canlog::save(std::path::Path::new(output), &log, format)?;
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "CAN log: {} ({}) → {} ({}), {} frames",
+            input,
+            log.format.name(),
+            output,
+            written.name(),
+            log.frames.len()
+        ),
+    );
+    Ok(CanLogInfo::new(output, written, &log))
+}
+
+/// Frames decoded against a DBC by `decode_can_log` / `decode_can_frames`
+#[derive(Debug, Serialize)]
+pub struct CanDecodeReport {
+    pub dbc: String,
+    pub frames: usize,
+    pub decoded: usize,
+    /// IDs the database doesn't describe
+    pub unknown_ids: Vec<String>,
+    pub messages: Vec<DecodedFrame>,
+}
+
+fn decode_frames(
+    sink: &dyn LogSink,
+    dbc_path: &str,
+    frames: &[CanFrame],
+    source: &str,
+) -> Result<CanDecodeReport, String> {
+    let dbc = Dbc::load(std::path::Path::new(dbc_path))?;
+    let mut messages = Vec::new();
+    let mut unknown = std::collections::BTreeSet::new();
+    for frame in frames {
+        match dbc.decode(frame) {
+            Some(decoded) => messages.push(decoded),
+            None => {
+                unknown.insert(frame.can_id);
+            }
+        }
+    }
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "DBC decode: {} — {} of {} frames decoded, {} unknown IDs",
+            source,
+            messages.len(),
+            frames.len(),
+            unknown.len()
+        ),
+    );
+    Ok(CanDecodeReport {
+        dbc: dbc_path.to_string(),
+        frames: frames.len(),
+        decoded: messages.len(),
+        unknown_ids: unknown.iter().map(|id| format!("0x{:03X}", id)).collect(),
+        messages,
+    })
+}
+
+/// Decode a CAN capture file (any `convert_can_log` input format) into signals
+#[tauri::command]
+pub fn decode_can_log(app: AppHandle, dbc: String, log: String) -> Result<CanDecodeReport, String> {
+    decode_can_log_inner(&app, &dbc, &log).map_err(|e| log_err("decode_can_log", e))
+}
+
+pub fn decode_can_log_inner(
+    sink: &dyn LogSink,
+    dbc: &str,
+    log: &str,
+) -> Result<CanDecodeReport, String> {
+    let capture = canlog::load(std::path::Path::new(log))?;
+    decode_frames(sink, dbc, &capture.frames, log)
+}
+
+/// Decode frames already in the GUI (from `capture_raw_can` / sniff results)
+#[tauri::command]
+pub fn decode_can_frames(
+    app: AppHandle,
+    dbc: String,
+    frames: Vec<CanSniffEntry>,
+) -> Result<CanDecodeReport, String> {
+    decode_can_frames_inner(&app, &dbc, &frames).map_err(|e| log_err("decode_can_frames", e))
+}
+
+pub fn decode_can_frames_inner(
+    sink: &dyn LogSink,
+    dbc: &str,
+    frames: &[CanSniffEntry],
+) -> Result<CanDecodeReport, String> {
+    let frames = frames
+        .iter()
+        .map(CanFrame::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    decode_frames(sink, dbc, &frames, "captured frames")
+}
+
+// ─── Signal reverse-engineering ─────────────────────────────────────
+
+/// Per-ID findings for a capture, with the draft DBC built from them
+#[derive(Debug, Serialize)]
+pub struct CanAnalysisReport {
+    #[serde(flatten)]
+    pub analysis: Analysis,
+    /// Draft DBC text
+    pub dbc: String,
+    /// Where the draft was written, if asked to
+    pub dbc_path: Option<String>,
+}
+
+fn analyze_frames(
+    sink: &dyn LogSink,
+    frames: &[CanFrame],
+    events: &[CanEvent],
+    options: &AnalysisOptions,
+    dbc_out: Option<&str>,
+    source: &str,
+) -> Result<CanAnalysisReport, String> {
+    let analysis = reverse::analyze(frames, events, options);
+    let draft = analysis.draft_dbc();
+    if let Some(path) = dbc_out {
+        draft.save(std::path::Path::new(path))?;
+    }
+    let counters: usize = analysis.ids.iter().map(|id| id.counters.len()).sum();
+    let checksums = analysis.ids.iter().filter(|id| !id.checksums.is_empty()).count();
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "CAN analysis: {} — {} IDs, {} counters, {} IDs with a checksum candidate{}",
+            source,
+            analysis.ids.len(),
+            counters,
+            checksums,
+            dbc_out.map(|p| format!(", draft DBC written to {}", p)).unwrap_or_default()
+        ),
+    );
+    Ok(CanAnalysisReport {
+        analysis,
+        dbc: draft.write(),
+        dbc_path: dbc_out.map(str::to_string),
+    })
+}
+
+/// Reverse-engineer the IDs in a CAN capture file: constant bytes, counters, checksum
+/// candidates and changing fields, correlated with `events`. With `dbc_out` the draft
+/// DBC is also written there.
+#[tauri::command]
+pub fn analyze_can_log(
+    app: AppHandle,
+    log: String,
+    events: Option<Vec<CanEvent>>,
+    options: Option<AnalysisOptions>,
+    dbc_out: Option<String>,
+) -> Result<CanAnalysisReport, String> {
+    analyze_can_log_inner(
+        &app,
+        &log,
+        &events.unwrap_or_default(),
+        &options.unwrap_or_default(),
+        dbc_out.as_deref(),
+    )
+    .map_err(|e| log_err("analyze_can_log", e))
+}
+
+pub fn analyze_can_log_inner(
+    sink: &dyn LogSink,
+    log: &str,
+    events: &[CanEvent],
+    options: &AnalysisOptions,
+    dbc_out: Option<&str>,
+) -> Result<CanAnalysisReport, String> {
+    let capture = canlog::load(std::path::Path::new(log))?;
+    analyze_frames(sink, &capture.frames, events, options, dbc_out, log)
+}
+
+/// Reverse-engineer frames already in the GUI (sniff results)
+#[tauri::command]
+pub fn analyze_can_frames(
+    app: AppHandle,
+    frames: Vec<CanSniffEntry>,
+    events: Option<Vec<CanEvent>>,
+    options: Option<AnalysisOptions>,
+    dbc_out: Option<String>,
+) -> Result<CanAnalysisReport, String> {
+    analyze_can_frames_inner(
+        &app,
+        &frames,
+        &events.unwrap_or_default(),
+        &options.unwrap_or_default(),
+        dbc_out.as_deref(),
+    )
+    .map_err(|e| log_err("analyze_can_frames", e))
+}
+
+pub fn analyze_can_frames_inner(
+    sink: &dyn LogSink,
+    frames: &[CanSniffEntry],
+    events: &[CanEvent],
+    options: &AnalysisOptions,
+    dbc_out: Option<&str>,
+) -> Result<CanAnalysisReport, String> {
+    let frames = frames
+        .iter()
+        .map(CanFrame::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    analyze_frames(sink, &frames, events, options, dbc_out, "captured frames")
+}
+
+// ─── Live CAN monitor ───────────────────────────────────────────────
+
+/// Watch the bus until the job is cancelled (or `options.seconds` pass), feeding
+/// `state.can_monitor` and streaming an update every `options.update_ms`.
+/// Returns the final snapshot; the frame buffer stays in `state` for saving.
+pub fn monitor_can_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    options: &MonitorOptions,
+    job: &JobContext,
+) -> Result<MonitorSnapshot, String> {
+    options.validate()?;
+    {
+        let mut monitor = state.can_monitor.lock().unwrap();
+        if monitor.as_ref().is_some_and(BusMonitor::is_running) {
+            return Err("A CAN monitor is already running".into());
+        }
+        *monitor = Some(BusMonitor::new(options.clone(), Some(job.id())));
+    }
+    emit_log_simple(sink, LogDirection::Tx, &[], "CAN monitor started");
+
+    // The connection is only locked to pick the channel, not while watching
+    let outcome = match with_connection(state, |conn| Ok(conn.can_channel.clone())) {
+        Err(e) => Err(e),
+        Ok(Some(can_ch)) => can_ch
+            .setup_can_pass_filter()
+            .map_err(|e| format!("CAN pass filter failed: {}", e))
+            .and_then(|_| watch_bus(sink, state, &can_ch, options, job)),
+        Ok(None) => {
+            let outcome = open_raw_can(state)
+                .and_then(|can_ch| watch_bus(sink, state, &can_ch, options, job));
+            let reopened = restore_iso15765_channel(state);
+            outcome.and(reopened.map_err(|e| format!("Failed to restore ISO15765: {}", e)))
+        }
+    };
+
+    let mut monitor = state.can_monitor.lock().unwrap();
+    let monitor = monitor.as_mut().ok_or("CAN monitor state was lost")?;
+    monitor.stop();
+    let snapshot = monitor.snapshot(monitor.elapsed_us());
+    outcome?;
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "CAN monitor stopped: {} frames, {} IDs in {} ms ({} buffered)",
+            snapshot.frames,
+            snapshot.ids.len(),
+            snapshot.elapsed_ms,
+            snapshot.buffered
+        ),
+    );
+    Ok(snapshot)
+}
+
+/// The monitor's read loop on an open raw CAN channel with a pass filter
+fn watch_bus(
+    sink: &dyn LogSink,
+    state: &AppState,
+    can_ch: &J2534Channel,
+    options: &MonitorOptions,
+    job: &JobContext,
+) -> Result<(), String> {
+    let start = std::time::Instant::now();
+    let duration = std::time::Duration::from_secs(options.seconds as u64);
+    let interval = std::time::Duration::from_millis(options.update_ms);
+    let mut next_update = start + interval;
+    let mut anchor = None;
+    let mut last_progress = 0u64;
+
+    while !job.is_cancelled() && (options.seconds == 0 || start.elapsed() < duration) {
+        let frames = read_raw_frames(can_ch, 50, start, &mut anchor);
+        let mut guard = state.can_monitor.lock().unwrap();
+        let monitor = guard.as_mut().ok_or("CAN monitor state was lost")?;
+        for frame in frames {
+            monitor.record(frame);
+        }
+        let now = std::time::Instant::now();
+        if now < next_update {
+            continue;
+        }
+        next_update = (next_update + interval).max(now);
+        let update = monitor.update(monitor.elapsed_us());
+        drop(guard);
+        sink.can_monitor(&update);
+
+        let elapsed_secs = start.elapsed().as_secs();
+        if elapsed_secs > last_progress {
+            last_progress = elapsed_secs;
+            let percent = (elapsed_secs * 100)
+                .checked_div(options.seconds as u64)
+                .map_or(0, |p| p.min(99) as u8);
+            job.progress(
+                sink,
+                "Monitoring CAN",
+                percent,
+                &format!(
+                    "{} frames, {} IDs, bus load {:.1}%",
+                    update.snapshot.frames,
+                    update.snapshot.ids.len(),
+                    update.snapshot.bus_load_pct
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Start the live CAN monitor as a background job; returns the job ID
+#[tauri::command]
+pub fn start_can_monitor(app: AppHandle, options: Option<MonitorOptions>) -> Result<u64, String> {
+    let kind = JobKind::MonitorCan {
+        options: options.unwrap_or_default(),
+    };
+    start_job_inner(&app, kind).map_err(|e| log_err("start_can_monitor", e))
+}
+
+/// Stop the running monitor (its statistics and buffer stay available)
+#[tauri::command]
+pub fn stop_can_monitor(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    stop_can_monitor_inner(&app, &state).map_err(|e| log_err("stop_can_monitor", e))
+}
+
+pub fn stop_can_monitor_inner(sink: &dyn LogSink, state: &AppState) -> Result<(), String> {
+    let job_id = state
+        .can_monitor
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(BusMonitor::job_id)
+        .ok_or("No CAN monitor is running")?;
+    cancel_job_inner(sink, state, job_id)
+}
+
+/// Current monitor state, or None if no monitor has run
+#[tauri::command]
+pub fn get_can_monitor(state: State<'_, AppState>) -> Option<MonitorSnapshot> {
+    get_can_monitor_inner(&state)
+}
+
+pub fn get_can_monitor_inner(state: &AppState) -> Option<MonitorSnapshot> {
+    let mut monitor = state.can_monitor.lock().unwrap();
+    let monitor = monitor.as_mut()?;
+    Some(monitor.snapshot(monitor.elapsed_us()))
+}
+
+/// Save the monitor's frame buffer (while running or after a stop). Without `format`
+/// the file extension decides, as in `convert_can_log`.
+#[tauri::command]
+pub fn save_can_monitor(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    format: Option<String>,
+) -> Result<CanLogInfo, String> {
+    save_can_monitor_inner(&app, &state, &path, format.as_deref())
+        .map_err(|e| log_err("save_can_monitor", e))
+}
+
+pub fn save_can_monitor_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    path: &str,
+    format: Option<&str>,
+) -> Result<CanLogInfo, String> {
+    let format = format.map(CaptureFormat::parse).transpose()?;
+    // Copy the buffer out so the monitor isn't held up by the file write
+    let log = state
+        .can_monitor
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(BusMonitor::to_log)
+        .ok_or("No CAN monitor has run")?;
+    if log.frames.is_empty() {
+        return Err("The CAN monitor buffer is empty".into());
+    }
+    let written = canlog::save(std::path::Path::new(path), &log, format)?;
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "CAN monitor: {} frames saved to {} ({})",
+            log.frames.len(),
+            path,
+            written.name()
+        ),
+    );
+    Ok(CanLogInfo::new(path, written, &log))
+}
+
 /// Read CCF (Central Configuration File) from IMC
 /// Read the full CCF block from one ECU via a DID read.
 /// GWM uses 0xEE00, BCM uses 0xDE00.
-fn read_ccf_block_did<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn read_ccf_block_did(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     tx: u32,
     ecu_name: &str,
@@ -1850,16 +2951,16 @@ fn read_ccf_block_did<R: tauri::Runtime>(
 ) -> Option<Vec<u8>> {
     let req = [0x22, (did >> 8) as u8, (did & 0xFF) as u8];
     emit_log_simple(
-        app,
+        sink,
         LogDirection::Tx,
         &req,
         &format!("ReadDID 0x{:04X} ({} CCF block)", did, ecu_name),
     );
-    match send_uds_request(app, channel, tx, &req, true, emulator) {
+    match send_uds_request(sink, channel, tx, &req, true, emulator) {
         Ok(resp) if resp.len() > 3 => {
             let data = resp[3..].to_vec();
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Rx,
                 &[],
                 &format!("{} CCF block: {} bytes", ecu_name, data.len()),
@@ -1868,7 +2969,7 @@ fn read_ccf_block_did<R: tauri::Runtime>(
         }
         Ok(_) => {
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Rx,
                 &[],
                 &format!("{} CCF block: empty response", ecu_name),
@@ -1877,7 +2978,7 @@ fn read_ccf_block_did<R: tauri::Runtime>(
         }
         Err(e) => {
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Rx,
                 &[],
                 &format!("{} CCF block failed: {}", ecu_name, e),
@@ -1895,48 +2996,14 @@ fn read_ccf_block_did<R: tauri::Runtime>(
 /// CCF enters IMC via SDD config sequence (0x0E08→0x0E06→0x6038) and is
 /// written to the Linux filesystem — no UDS read-back interface exists.
 #[allow(dead_code)]
-fn read_ccf_report_imc<R: tauri::Runtime>(
-    _app: &tauri::AppHandle<R>,
+fn read_ccf_report_imc(
+    _sink: &dyn LogSink,
     _channel: &dyn crate::j2534::Channel,
     _emulator: Option<&EcuEmulatorManager>,
 ) -> Option<Vec<u8>> {
     None
 }
 
-// ── CCF VDF parsing (shared by compare_ccf and restore_ccf) ──
-const VDF_HDR: usize = 20;
-const VDF_BLOCK_SIZE: usize = 8;
-const CCF_OPTIONS_COUNT: usize = 784;
-
-/// Parse raw DID 0xEE00/0xDE00 response (VDF format) into a flat 784-byte CCF option array.
-/// VDF header = 20 bytes, then 8-byte blocks: [block_id (1)] [data (7)].
-/// Block ID B maps to CCF options (B-1)*7 through (B-1)*7+6.
-fn parse_ccf_vdf(raw: &[u8]) -> Option<Vec<u8>> {
-    if raw.len() < VDF_HDR + VDF_BLOCK_SIZE {
-        return None;
-    }
-    let mut options = vec![0u8; CCF_OPTIONS_COUNT];
-    let mut offset = VDF_HDR;
-    while offset + VDF_BLOCK_SIZE <= raw.len() {
-        let blk_id = raw[offset] as usize;
-        if blk_id == 0 || blk_id == 0xFF {
-            break;
-        }
-        let opt_start = (blk_id - 1) * 7;
-        for j in 0..7 {
-            let pos = opt_start + j;
-            if pos < CCF_OPTIONS_COUNT {
-                options[pos] = raw[offset + 1 + j];
-            }
-        }
-        offset += VDF_BLOCK_SIZE;
-        if offset >= VDF_HDR + 112 * VDF_BLOCK_SIZE {
-            break;
-        }
-    }
-    Some(options)
-}
-
 /// Compare CCF across GWM, BCM, and IMC.
 /// Reads the full CCF block from each ECU and decodes option values.
 #[tauri::command]
@@ -1947,56 +3014,12 @@ pub fn compare_ccf(
     compare_ccf_inner(&app, &state).map_err(|e| log_err("compare_ccf", e))
 }
 
-fn compare_ccf_inner(
-    app: &AppHandle,
-    state: &State<'_, AppState>,
+pub fn compare_ccf_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
 ) -> Result<Vec<CcfCompareEntry>, String> {
-    let conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_ref().ok_or("Not connected")?;
-    let channel: &dyn crate::j2534::Channel =
-        conn.channel.as_ref().ok_or("No channel available")?;
-    let emulator = conn.emulator_manager.as_ref();
-
-    // Enter Extended Session (needed for DID reads on some ECUs)
-    sdd_prerequisite_flow(app, channel, false, emulator)?;
-
-    // --- GWM CCF ---
-    let _ = send_uds_request(
-        app,
-        channel,
-        ecu_addr::GWM_TX,
-        &[0x3E, 0x00],
-        false,
-        emulator,
-    );
-    let _ = send_uds_request(
-        app,
-        channel,
-        ecu_addr::GWM_TX,
-        &[0x10, 0x03],
-        false,
-        emulator,
-    );
-    let gwm_block = read_ccf_block_did(app, channel, ecu_addr::GWM_TX, "GWM", 0xEE00, emulator);
-
-    // --- BCM CCF ---
-    let _ = send_uds_request(
-        app,
-        channel,
-        ecu_addr::BCM_TX,
-        &[0x3E, 0x00],
-        false,
-        emulator,
-    );
-    let _ = send_uds_request(
-        app,
-        channel,
-        ecu_addr::BCM_TX,
-        &[0x10, 0x03],
-        false,
-        emulator,
-    );
-    let bcm_block = read_ccf_block_did(app, channel, ecu_addr::BCM_TX, "BCM", 0xDE00, emulator);
+    let VehicleCcf { gwm: gwm_block, bcm: bcm_block, vin: vin_did } =
+        read_vehicle_ccf(sink, state)?;
 
     // --- IMC CCF ---
     // IMC does NOT expose CCF via any DID or routine:
@@ -2008,34 +3031,35 @@ fn compare_ccf_inner(
     // Use GWM CCF as IMC's CCF (same data by SDD design).
     let imc_block = gwm_block.clone();
     emit_log_simple(
-        app,
+        sink,
         LogDirection::Rx,
         &[],
         "IMC CCF: using GWM data (IMC receives CCF from GWM via SDD)",
     );
 
     // --- Decode and compare ---
-    let gwm_opts = gwm_block.as_deref().and_then(parse_ccf_vdf);
-    let bcm_opts = bcm_block.as_deref().and_then(parse_ccf_vdf);
+    let gwm_ccf = gwm_block.as_deref().and_then(|raw| Ccf::parse(raw).ok());
+    let bcm_ccf = bcm_block.as_deref().and_then(|raw| Ccf::parse(raw).ok());
     // IMC gets CCF from GWM via SDD (0x0E08→0x0E06→0x6038).
     // IMC CCF == GWM CCF by design, so use GWM data as IMC's CCF.
-    let imc_opts = gwm_opts.clone();
+    let imc_ccf = gwm_ccf.clone();
 
     let mut entries: Vec<CcfCompareEntry> = Vec::new();
 
+    let vin = gwm_ccf.as_ref().or(bcm_ccf.as_ref()).and_then(Ccf::vin);
+    let table = state.ccf_tables.for_vin(vin.as_deref());
+    let report =
+        check_vehicle_rules(state, &table, gwm_ccf.as_ref(), bcm_ccf.as_ref(), vin_did.as_deref());
+    log_rule_findings(sink, &report.findings);
     for &opt_id in IMC_CCF_OPTION_IDS {
-        let idx = (opt_id - 1) as usize;
+        let field = table.field(opt_id);
+        let gwm_val = gwm_ccf.as_ref().and_then(|c| c.field(field));
+        let bcm_val = bcm_ccf.as_ref().and_then(|c| c.field(field));
+        let imc_val = imc_ccf.as_ref().and_then(|c| c.field(field));
 
-        let gwm_val = gwm_opts.as_ref().and_then(|o| o.get(idx)).copied()
-            .map(|v| extract_ccf_subfield(opt_id, v));
-        let bcm_val = bcm_opts.as_ref().and_then(|o| o.get(idx)).copied()
-            .map(|v| extract_ccf_subfield(opt_id, v));
-        let imc_val = imc_opts.as_ref().and_then(|o| o.get(idx)).copied()
-            .map(|v| extract_ccf_subfield(opt_id, v));
-
-        let gwm_str = gwm_val.map(|v| decode_ccf_value(opt_id, v));
-        let bcm_str = bcm_val.map(|v| decode_ccf_value(opt_id, v));
-        let imc_str = imc_val.map(|v| decode_ccf_value(opt_id, v));
+        let gwm_str = gwm_val.map(|v| table.decode(opt_id, v));
+        let bcm_str = bcm_val.map(|v| table.decode(opt_id, v));
+        let imc_str = imc_val.map(|v| table.decode(opt_id, v));
 
         // Mismatch if GWM and BCM differ (IMC == GWM by SDD design)
         let mismatch = match (&gwm_str, &bcm_str) {
@@ -2045,11 +3069,17 @@ fn compare_ccf_inner(
 
         entries.push(CcfCompareEntry {
             option_id: opt_id,
-            name: ccf_option_name(opt_id),
+            name: table.name(opt_id),
             gwm: gwm_str,
             bcm: bcm_str,
             imc: imc_str,
             mismatch,
+            findings: report
+                .findings
+                .iter()
+                .filter(|f| f.options.contains(&opt_id))
+                .cloned()
+                .collect(),
         });
     }
 
@@ -2077,7 +3107,7 @@ fn compare_ccf_inner(
                 let path = dump_path(filename);
                 let _ = std::fs::write(&path, &json_str);
                 emit_log_simple(
-                    app,
+                    sink,
                     LogDirection::Rx,
                     &[],
                     &format!("{} CCF raw saved → {}", name, path.display()),
@@ -2086,6 +3116,13 @@ fn compare_ccf_inner(
         }
     }
 
+    for (name, did, block) in [("GWM", 0xEE00, &gwm_block), ("BCM", 0xDE00, &bcm_block)] {
+        if let Some(block) = block {
+            let source = format!("DID 0x{:04X}", did);
+            archive_ccf(sink, &state.archive, name, &source, block);
+        }
+    }
+
     // Save comparison to file
     let mismatches: Vec<_> = entries.iter().filter(|e| e.mismatch).collect();
     let dump = serde_json::json!({
@@ -2094,6 +3131,7 @@ fn compare_ccf_inner(
         "bcm_block_bytes": bcm_block.as_ref().map(|b| b.len()),
         "imc_block_bytes": imc_block.as_ref().map(|b| b.len()),
         "mismatches": mismatches.len(),
+        "findings": report.findings,
         "options": entries.iter().map(|e| serde_json::json!({
             "id": e.option_id,
             "name": e.name,
@@ -2107,7 +3145,7 @@ fn compare_ccf_inner(
         let path = dump_path("ccf_compare.json");
         let _ = std::fs::write(&path, &json_str);
         emit_log_simple(
-            app,
+            sink,
             LogDirection::Rx,
             &[],
             &format!(
@@ -2121,12 +3159,78 @@ fn compare_ccf_inner(
     Ok(entries)
 }
 
+/// Raw GWM and BCM CCF blocks and the GWM's VIN (DID 0xF190); `None` where a
+/// read failed
+struct VehicleCcf {
+    gwm: Option<Vec<u8>>,
+    bcm: Option<Vec<u8>>,
+    vin: Option<Vec<u8>>,
+}
+
+fn read_vehicle_ccf(sink: &dyn LogSink, state: &AppState) -> Result<VehicleCcf, String> {
+    let bus = bus_handles(state)?;
+    let emulator = bus.emulator.as_deref();
+
+    // Enter Extended Session (needed for DID reads on some ECUs)
+    let imc = bus.port(ecu_addr::IMC_TX, ecu_addr::IMC_RX)?;
+    sdd_prerequisite_flow(sink, &imc, false, emulator)?;
+    drop(imc);
+
+    // --- GWM CCF ---
+    let gwm_port = bus.port(ecu_addr::GWM_TX, ecu_addr::GWM_RX)?;
+    let channel: &dyn crate::j2534::Channel = &gwm_port;
+    let _ = send_uds_request(
+        sink,
+        channel,
+        ecu_addr::GWM_TX,
+        &[0x3E, 0x00],
+        false,
+        emulator,
+    );
+    let _ = send_uds_request(
+        sink,
+        channel,
+        ecu_addr::GWM_TX,
+        &[0x10, 0x03],
+        false,
+        emulator,
+    );
+    let gwm = read_ccf_block_did(sink, channel, ecu_addr::GWM_TX, "GWM", 0xEE00, emulator);
+    let vin = send_read_did(sink, channel, ecu_addr::GWM_TX, did::VIN, emulator).ok();
+
+    drop(gwm_port);
+
+    // --- BCM CCF ---
+    let bcm_port = bus.port(ecu_addr::BCM_TX, ecu_addr::BCM_RX)?;
+    let channel: &dyn crate::j2534::Channel = &bcm_port;
+    let _ = send_uds_request(
+        sink,
+        channel,
+        ecu_addr::BCM_TX,
+        &[0x3E, 0x00],
+        false,
+        emulator,
+    );
+    let _ = send_uds_request(
+        sink,
+        channel,
+        ecu_addr::BCM_TX,
+        &[0x10, 0x03],
+        false,
+        emulator,
+    );
+    let bcm = read_ccf_block_did(sink, channel, ecu_addr::BCM_TX, "BCM", 0xDE00, emulator);
+
+    Ok(VehicleCcf { gwm, bcm, vin })
+}
+
 /// Restore IMC CCF — full SDD configuration sequence with diagnostics:
-/// Pre-flight: Read GWM CCF, verify option 467
+/// Pre-flight: Read GWM CCF, check it against the CCF rules
 /// J_40: 0x0E08 Start (trigger CCF fetch from GWM via CAN)
 /// J_45: 0x0E06 Start + Request Results polling (wait for transfer)
 ///       Optional CAN sniff during 0x0E06 to capture GWM→IMC traffic
-/// J_60: 0x6038 Start (Configure Linux to Hardware — apply CCF)
+/// J_60: 0x6038 Start (Configure Linux to Hardware — apply CCF); rehearsals against
+///       the emulated IMC also check its Request Results before the reset
 /// J_80: ECU Reset (0x1101 hard reset)
 /// J_85: 90-second delay for IMC to reboot
 /// Post-flight: Read IMC DIDs to confirm responsiveness
@@ -2136,21 +3240,65 @@ pub fn restore_ccf(
     state: State<'_, AppState>,
     sniff: bool,
 ) -> Result<RestoreCcfResult, String> {
-    restore_ccf_inner(&app, &state, sniff).map_err(|e| log_err("restore_ccf", e))
+    restore_ccf_inner(&app, &state, sniff, &JobContext::detached())
+        .map_err(|e| log_err("restore_ccf", e))
 }
 
-fn restore_ccf_inner(
-    app: &AppHandle,
-    state: &State<'_, AppState>,
+/// Waits in the restore sequence. `Default` follows the SDD; rehearsals against
+/// an emulated IMC with short routine timings can shorten them.
+#[derive(Debug, Clone, Copy)]
+pub struct RestoreCcfTiming {
+    /// After 0x0E08 (SDD: ~10 timer ticks)
+    pub prepare_wait: std::time::Duration,
+    /// Between 0x0E06 Request Results polls
+    pub poll_interval: std::time::Duration,
+    /// After 0x6038 (SDD: ~100 timer operations)
+    pub configure_wait: std::time::Duration,
+    /// After the ECU reset, for the IMC to reboot (J_85)
+    pub reboot_wait: std::time::Duration,
+}
+
+impl Default for RestoreCcfTiming {
+    fn default() -> Self {
+        Self {
+            prepare_wait: std::time::Duration::from_secs(5),
+            poll_interval: std::time::Duration::from_secs(1),
+            configure_wait: std::time::Duration::from_secs(30),
+            reboot_wait: std::time::Duration::from_secs(90),
+        }
+    }
+}
+
+pub fn restore_ccf_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
     sniff: bool,
+    job: &JobContext,
 ) -> Result<RestoreCcfResult, String> {
-    let mut conn_guard = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn_guard.as_mut().ok_or("Not connected")?;
+    restore_ccf_timed(sink, state, sniff, job, &RestoreCcfTiming::default())
+}
 
-    if conn.emulator_manager.is_some() {
-        return Err("Restore CCF requires real car (IMC fetches CCF from GWM via CAN)".into());
-    }
+/// Option 467 of a CCF the IMC reported, decoded with the table for its VIN
+fn option_467_summary(state: &AppState, ccf: &Ccf) -> Option<String> {
+    let table = state.ccf_tables.for_vin(ccf.vin().as_deref());
+    let field = table.field(467);
+    let raw = ccf.option(field.byte + 1)?;
+    let extracted = field.extract(raw);
+    Some(format!(
+        "raw=0x{:02X}, extracted=0x{:02X} ({})",
+        raw,
+        extracted,
+        table.decode(467, extracted)
+    ))
+}
 
+pub fn restore_ccf_timed(
+    sink: &dyn LogSink,
+    state: &AppState,
+    sniff: bool,
+    job: &JobContext,
+    timing: &RestoreCcfTiming,
+) -> Result<RestoreCcfResult, String> {
     let mut result = RestoreCcfResult {
         success: false,
         steps: Vec::new(),
@@ -2161,17 +3309,19 @@ fn restore_ccf_inner(
     };
 
     // ══════════════════════════════════════════════════════
-    // PRE-FLIGHT: Read GWM CCF and verify option 467
+    // PRE-FLIGHT: Read GWM CCF and check the CCF rules
     // ══════════════════════════════════════════════════════
-    emit_log_simple(app, LogDirection::Tx, &[], "═══ PRE-FLIGHT: Reading GWM CCF ═══");
+    job.progress(sink, "Pre-flight", 0, "Reading GWM CCF (0xEE00)");
+    emit_log_simple(sink, LogDirection::Tx, &[], "═══ PRE-FLIGHT: Reading GWM CCF ═══");
     {
-        let channel: &dyn crate::j2534::Channel =
-            conn.channel.as_ref().ok_or("No channel available")?;
-        let emulator = conn.emulator_manager.as_ref();
+        let bus = bus_handles(state)?;
+        let port = bus.port(ecu_addr::GWM_TX, ecu_addr::GWM_RX)?;
+        let channel: &dyn crate::j2534::Channel = &port;
+        let emulator = bus.emulator.as_deref();
 
-        let _ = send_uds_request(app, channel, ecu_addr::GWM_TX, &[0x3E, 0x00], false, emulator);
-        let _ = send_uds_request(app, channel, ecu_addr::GWM_TX, &[0x10, 0x03], false, emulator);
-        let gwm_block = read_ccf_block_did(app, channel, ecu_addr::GWM_TX, "GWM", 0xEE00, emulator);
+        let _ = send_uds_request(sink, channel, ecu_addr::GWM_TX, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, ecu_addr::GWM_TX, &[0x10, 0x03], false, emulator);
+        let gwm_block = read_ccf_block_did(sink, channel, ecu_addr::GWM_TX, "GWM", 0xEE00, emulator);
 
         let mut pre = PreFlightInfo {
             gwm_ccf_hex: String::new(),
@@ -2179,30 +3329,35 @@ fn restore_ccf_inner(
             option_467_extracted: None,
             option_467_desc: "Not read".into(),
             warnings: Vec::new(),
+            findings: Vec::new(),
         };
 
         if let Some(ref raw) = gwm_block {
             pre.gwm_ccf_hex = raw.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
-            if let Some(opts) = parse_ccf_vdf(raw) {
-                let idx = 466; // option 467 is 1-indexed, array is 0-indexed
-                if let Some(&raw_byte) = opts.get(idx) {
+            if let Ok(ccf) = Ccf::parse(raw) {
+                let table = state.ccf_tables.for_vin(ccf.vin().as_deref());
+                let field = table.field(467);
+                if let Some(raw_byte) = ccf.option(field.byte + 1) {
                     pre.option_467_raw = Some(raw_byte);
-                    let extracted = extract_ccf_subfield(467, raw_byte);
+                    let extracted = field.extract(raw_byte);
                     pre.option_467_extracted = Some(extracted);
-                    pre.option_467_desc = decode_ccf_value(467, extracted);
-                    emit_log_simple(app, LogDirection::Rx, &[], &format!(
+                    pre.option_467_desc = table.decode(467, extracted);
+                    emit_log_simple(sink, LogDirection::Rx, &[], &format!(
                         "GWM CCF option 467: raw=0x{:02X}, extracted=0x{:02X} → {}",
                         raw_byte, extracted, pre.option_467_desc
                     ));
-                    if extracted != 0x04 && extracted != 0x05 {
-                        let warn = format!(
-                            "Option 467 = 0x{:02X} — NOT 10\" display (expected 0x04 or 0x05)!",
-                            extracted
-                        );
-                        emit_log_simple(app, LogDirection::Rx, &[], &format!("⚠ WARNING: {}", warn));
-                        pre.warnings.push(warn);
-                    }
                 }
+                let vin = send_read_did(sink, channel, ecu_addr::GWM_TX, did::VIN, emulator).ok();
+                let report = check_vehicle_rules(state, &table, Some(&ccf), None, vin.as_deref());
+                log_rule_findings(sink, &report.findings);
+                pre.warnings.extend(
+                    report
+                        .findings
+                        .iter()
+                        .filter(|f| f.severity >= Severity::Warning)
+                        .map(|f| format!("{}: {}", f.description, f.detail)),
+                );
+                pre.findings = report.findings;
             } else {
                 pre.warnings.push("Failed to parse GWM CCF VDF format".into());
             }
@@ -2217,20 +3372,23 @@ fn restore_ccf_inner(
     // STEP 1: 0x0E08 — Prepare/trigger CCF fetch from GWM
     // ══════════════════════════════════════════════════════
     {
-        let channel: &dyn crate::j2534::Channel =
-            conn.channel.as_ref().ok_or("No channel available")?;
-        let emulator = conn.emulator_manager.as_ref();
+        let bus = bus_handles(state)?;
+        let port = bus.port(ecu_addr::IMC_TX, ecu_addr::IMC_RX)?;
+        let channel: &dyn crate::j2534::Channel = &port;
+        let emulator = bus.emulator.as_deref();
         let tx = ecu_addr::IMC_TX;
 
-        sdd_prerequisite_flow(app, channel, false, emulator)?;
+        job.check()?;
+        job.progress(sink, "Prepare", 5, "0x0E08");
+        sdd_prerequisite_flow(sink, channel, false, emulator)?;
 
-        emit_log_simple(app, LogDirection::Tx, &[], "═══ RESTORE CCF: Step 1/4 — Prepare (0x0E08) ═══");
+        emit_log_simple(sink, LogDirection::Tx, &[], "═══ RESTORE CCF: Step 1/4 — Prepare (0x0E08) ═══");
         let prepare_req = [0x31, 0x01, 0x0E, 0x08];
         let start = std::time::Instant::now();
-        match send_uds_request(app, channel, tx, &prepare_req, true, emulator) {
+        match send_uds_request(sink, channel, tx, &prepare_req, true, emulator) {
             Ok(resp) => {
                 let hex = resp.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
-                emit_log_simple(app, LogDirection::Rx, &resp, "0x0E08 OK");
+                emit_log_simple(sink, LogDirection::Rx, &resp, "0x0E08 OK");
                 result.steps.push(RestoreCcfStep {
                     name: "0x0E08 Prepare".into(),
                     success: true,
@@ -2250,25 +3408,30 @@ fn restore_ccf_inner(
         }
 
         // SDD waits ~10 timer ticks after 0x0E08
-        emit_log_simple(app, LogDirection::Tx, &[], "Waiting 5s for 0x0E08 to complete...");
+        let total = timing.prepare_wait.as_secs();
+        let tick = timing.prepare_wait / 5;
+        emit_log_simple(sink, LogDirection::Tx, &[], &format!("Waiting {}s for 0x0E08 to complete...", total));
         for i in 1..=5 {
-            std::thread::sleep(std::time::Duration::from_secs(1));
-            let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+            job.sleep(tick)?;
+            let waited = (tick * i as u32).as_secs();
+            job.progress(sink, "Prepare", 5 + i * 2, &format!("Waiting {}s / {}s", waited, total));
+            let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
             if i % 2 == 0 {
-                emit_log_simple(app, LogDirection::Tx, &[], &format!("{}s / 5s", i));
+                emit_log_simple(sink, LogDirection::Tx, &[], &format!("{}s / {}s", waited, total));
             }
         }
 
         // ── STEP 2: 0x0E06 — Transfer CCF ──
-        emit_log_simple(app, LogDirection::Tx, &[], "═══ RESTORE CCF: Step 2/4 — Transfer (0x0E06) ═══");
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-        let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
+        job.progress(sink, "Transfer", 15, "0x0E06 Start");
+        emit_log_simple(sink, LogDirection::Tx, &[], "═══ RESTORE CCF: Step 2/4 — Transfer (0x0E06) ═══");
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
 
         let transfer_req = [0x31, 0x01, 0x0E, 0x06];
-        emit_log_simple(app, LogDirection::Tx, &transfer_req, "RoutineControl Start 0x0E06");
-        match send_uds_request(app, channel, tx, &transfer_req, true, emulator) {
+        emit_log_simple(sink, LogDirection::Tx, &transfer_req, "RoutineControl Start 0x0E06");
+        match send_uds_request(sink, channel, tx, &transfer_req, true, emulator) {
             Ok(resp) => {
-                emit_log_simple(app, LogDirection::Rx, &resp, "0x0E06 Start OK");
+                emit_log_simple(sink, LogDirection::Rx, &resp, "0x0E06 Start OK");
             }
             Err(e) => {
                 result.steps.push(RestoreCcfStep {
@@ -2285,46 +3448,44 @@ fn restore_ccf_inner(
     // ══════════════════════════════════════════════════════
     // OPTIONAL CAN SNIFF during 0x0E06 transfer
     // ══════════════════════════════════════════════════════
-    if sniff {
-        emit_log_simple(app, LogDirection::Tx, &[], "═══ CAN SNIFF: Capturing GWM→IMC traffic during 0x0E06 (10s) ═══");
+    if sniff && with_connection(state, |conn| Ok(conn.device.is_none()))? {
+        emit_log_simple(sink, LogDirection::Rx, &[], "CAN sniff skipped: no J2534 adapter on this connection");
+    } else if sniff {
+        emit_log_simple(sink, LogDirection::Tx, &[], "═══ CAN SNIFF: Capturing GWM→IMC traffic during 0x0E06 (10s) ═══");
         // Close ISO15765 to free J2534 slot for raw CAN
-        conn.channel.take();
-
-        match capture_raw_can(app, conn, 10) {
+        let capture = open_raw_can(state)
+            .and_then(|can_ch| capture_raw_can(sink, &can_ch, 10, job, "CAN sniff", (15, 20)));
+        match capture {
             Ok(frames) => {
-                emit_log_simple(app, LogDirection::Rx, &[], &format!(
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!(
                     "CAN sniff captured {} frames", frames.len()
                 ));
-                result.sniff_frames = frames;
+                result.sniff_frames = sniff_entries(&frames);
             }
             Err(e) => {
-                emit_log_simple(app, LogDirection::Rx, &[], &format!("CAN sniff error: {}", e));
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!("CAN sniff error: {}", e));
             }
         }
 
         // Restore ISO15765 channel with all ECU filters
-        let channel = conn.device.connect_iso15765(500000)
+        restore_iso15765_channel(state)
             .map_err(|e| format!("Failed to restore ISO15765 after sniff: {}", e))?;
-        let _ = channel.set_iso15765_config(0, 0, 0);
-        let _ = channel.setup_iso15765_filter(ecu_addr::IMC_TX, ecu_addr::IMC_RX);
-        let _ = channel.setup_iso15765_filter(ecu_addr::BCM_TX, ecu_addr::BCM_RX);
-        let _ = channel.setup_iso15765_filter(ecu_addr::GWM_TX, ecu_addr::GWM_RX);
-        let _ = channel.setup_iso15765_filter(ecu_addr::IPC_TX, ecu_addr::IPC_RX);
-        conn.channel = Some(channel);
+        job.check()?;
     }
 
     // ══════════════════════════════════════════════════════
     // POLL 0x0E06 results + STEP 3 (0x6038) + STEP 4 (Reset) + POST-FLIGHT
     // ══════════════════════════════════════════════════════
     {
-        let channel: &dyn crate::j2534::Channel =
-            conn.channel.as_ref().ok_or("No channel available")?;
-        let emulator = conn.emulator_manager.as_ref();
+        let bus = bus_handles(state)?;
+        let port = bus.port(ecu_addr::IMC_TX, ecu_addr::IMC_RX)?;
+        let channel: &dyn crate::j2534::Channel = &port;
+        let emulator = bus.emulator.as_deref();
         let tx = ecu_addr::IMC_TX;
 
         // Re-establish session (may have expired during CAN sniff)
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-        let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
 
         // Poll 0x0E06 Request Results
         let transfer_start = std::time::Instant::now();
@@ -2332,19 +3493,20 @@ fn restore_ccf_inner(
         let mut transfer_detail = String::new();
 
         for poll in 1..=20 {
-            std::thread::sleep(std::time::Duration::from_secs(1));
-            let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+            job.sleep(timing.poll_interval)?;
+            job.progress(sink, "Transfer", 20 + poll / 2, &format!("0x0E06 Results ({}/20)", poll));
+            let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
             let results_req = [0x31, 0x03, 0x0E, 0x06];
-            emit_log_simple(app, LogDirection::Tx, &results_req, &format!("0x0E06 Results ({}/20)", poll));
+            emit_log_simple(sink, LogDirection::Tx, &results_req, &format!("0x0E06 Results ({}/20)", poll));
 
-            match send_uds_request(app, channel, tx, &results_req, true, emulator) {
+            match send_uds_request(sink, channel, tx, &results_req, true, emulator) {
                 Ok(resp) => {
                     let hex = resp.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
-                    emit_log_simple(app, LogDirection::Rx, &resp, "0x0E06 Results OK");
+                    emit_log_simple(sink, LogDirection::Rx, &resp, "0x0E06 Results OK");
                     if resp.len() >= 6 {
                         let status = resp[4];
                         let additional = resp[5];
-                        emit_log_simple(app, LogDirection::Rx, &[], &format!(
+                        emit_log_simple(sink, LogDirection::Rx, &[], &format!(
                             "COMPLETION_STATUS=0x{:02X}, ADDITIONAL_DATA=0x{:02X}", status, additional
                         ));
                         if additional != 0x00 {
@@ -2363,7 +3525,7 @@ fn restore_ccf_inner(
                     break;
                 }
                 Err(e) if e.contains("0x21") => {
-                    emit_log_simple(app, LogDirection::Rx, &[], &format!("0x0E06 busy ({}/20)", poll));
+                    emit_log_simple(sink, LogDirection::Rx, &[], &format!("0x0E06 busy ({}/20)", poll));
                     continue;
                 }
                 Err(e) => {
@@ -2398,9 +3560,11 @@ fn restore_ccf_inner(
         // ══════════════════════════════════════════════════════
         // MID-FLIGHT: Read IMC's cached CCF after 0x0E06 transfer
         // ══════════════════════════════════════════════════════
-        emit_log_simple(app, LogDirection::Tx, &[], "═══ MID-FLIGHT: Reading IMC CCF after transfer (0x0E02/0x0E01) ═══");
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-        let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
+        job.check()?;
+        job.progress(sink, "Mid-flight", 30, "Reading IMC CCF (0x0E02/0x0E01)");
+        emit_log_simple(sink, LogDirection::Tx, &[], "═══ MID-FLIGHT: Reading IMC CCF after transfer (0x0E02/0x0E01) ═══");
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
 
         let mut mid = MidFlightInfo {
             imc_ccf_0e02_hex: None,
@@ -2412,12 +3576,12 @@ fn restore_ccf_inner(
 
         // Try 0x0E02 List CCF
         let list_req = [0x31, 0x01, 0x0E, 0x02];
-        emit_log_simple(app, LogDirection::Tx, &list_req, "0x0E02 List CCF");
-        match send_uds_request(app, channel, tx, &list_req, true, emulator) {
+        emit_log_simple(sink, LogDirection::Tx, &list_req, "0x0E02 List CCF");
+        match send_uds_request(sink, channel, tx, &list_req, true, emulator) {
             Ok(resp) if resp.len() > 4 => {
                 let ccf_data = &resp[4..];
                 let hex = ccf_data.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
-                emit_log_simple(app, LogDirection::Rx, &[], &format!(
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!(
                     "0x0E02 List CCF: {} bytes of CCF data", ccf_data.len()
                 ));
                 mid.imc_ccf_0e02_len = ccf_data.len();
@@ -2435,7 +3599,7 @@ fn restore_ccf_inner(
                     }
                     // Log all pairs for debugging
                     for &(opt, val) in &pairs {
-                        emit_log_simple(app, LogDirection::Rx, &[], &format!(
+                        emit_log_simple(sink, LogDirection::Rx, &[], &format!(
                             "  CCF option {} = 0x{:02X} ({})", opt, val, val
                         ));
                     }
@@ -2445,91 +3609,73 @@ fn restore_ccf_inner(
                     mid.option_pairs = pairs.clone();
 
                     // Also try parsing as VDF format (in case 0x0E02 returns raw VDF)
-                    if let Some(vdf_opts) = parse_ccf_vdf(ccf_data) {
-                        if let Some(&val) = vdf_opts.get(467) {
-                            let extracted = val & 0x0F;
-                            let desc = match extracted {
-                                0x02 | 0x03 => "8_INCH",
-                                0x04 => "10_INCH_SINGLE_VIEW",
-                                0x05 => "10_INCH_DUAL_VIEW",
-                                _ => "UNKNOWN",
-                            };
-                            emit_log_simple(app, LogDirection::Rx, &[], &format!(
-                                "*** IMC CCF option 467 (VDF parse): raw=0x{:02X}, extracted=0x{:02X} = {} ***",
-                                val, extracted, desc
-                            ));
-                            mid.option_467_value = Some(format!(
-                                "raw=0x{:02X}, extracted=0x{:02X} ({})", val, extracted, desc
+                    if let Ok(ccf) = Ccf::parse(ccf_data) {
+                        if let Some(value) = option_467_summary(state, &ccf) {
+                            emit_log_simple(sink, LogDirection::Rx, &[], &format!(
+                                "*** IMC CCF option 467 (VDF parse): {} ***", value
                             ));
+                            mid.option_467_value = Some(value);
                         }
                     }
                 }
             }
             Ok(resp) => {
                 let hex = resp.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
-                emit_log_simple(app, LogDirection::Rx, &[], &format!("0x0E02 short response: {}", hex));
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!("0x0E02 short response: {}", hex));
             }
             Err(e) => {
-                emit_log_simple(app, LogDirection::Rx, &[], &format!("0x0E02 failed: {}", e));
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!("0x0E02 failed: {}", e));
             }
         }
 
         // Try 0x0E01 Report CCF
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
         let report_req = [0x31, 0x01, 0x0E, 0x01];
-        emit_log_simple(app, LogDirection::Tx, &report_req, "0x0E01 Report CCF");
-        match send_uds_request(app, channel, tx, &report_req, true, emulator) {
+        emit_log_simple(sink, LogDirection::Tx, &report_req, "0x0E01 Report CCF");
+        match send_uds_request(sink, channel, tx, &report_req, true, emulator) {
             Ok(resp) if resp.len() > 4 => {
                 let ccf_data = &resp[4..];
                 let hex = ccf_data.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
-                emit_log_simple(app, LogDirection::Rx, &[], &format!(
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!(
                     "0x0E01 Report CCF: {} bytes", ccf_data.len()
                 ));
                 mid.imc_ccf_0e01_hex = Some(hex);
 
                 // Try VDF parse on 0x0E01 response too
                 if mid.option_467_value.is_none() {
-                    if let Some(vdf_opts) = parse_ccf_vdf(ccf_data) {
-                        if let Some(&val) = vdf_opts.get(467) {
-                            let extracted = val & 0x0F;
-                            let desc = match extracted {
-                                0x02 | 0x03 => "8_INCH",
-                                0x04 => "10_INCH_SINGLE_VIEW",
-                                0x05 => "10_INCH_DUAL_VIEW",
-                                _ => "UNKNOWN",
-                            };
-                            emit_log_simple(app, LogDirection::Rx, &[], &format!(
-                                "*** IMC CCF option 467 (0x0E01): raw=0x{:02X}, extracted=0x{:02X} = {} ***",
-                                val, extracted, desc
-                            ));
-                            mid.option_467_value = Some(format!(
-                                "raw=0x{:02X}, extracted=0x{:02X} ({})", val, extracted, desc
+                    if let Ok(ccf) = Ccf::parse(ccf_data) {
+                        if let Some(value) = option_467_summary(state, &ccf) {
+                            emit_log_simple(sink, LogDirection::Rx, &[], &format!(
+                                "*** IMC CCF option 467 (0x0E01): {} ***", value
                             ));
+                            mid.option_467_value = Some(value);
                         }
                     }
                 }
             }
             Ok(resp) => {
                 let hex = resp.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
-                emit_log_simple(app, LogDirection::Rx, &[], &format!("0x0E01 short response: {}", hex));
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!("0x0E01 short response: {}", hex));
             }
             Err(e) => {
-                emit_log_simple(app, LogDirection::Rx, &[], &format!("0x0E01 failed: {}", e));
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!("0x0E01 failed: {}", e));
             }
         }
 
         result.mid_flight = Some(mid);
 
         // ── STEP 3: 0x6038 — Configure Linux to Hardware ──
-        emit_log_simple(app, LogDirection::Tx, &[], "═══ RESTORE CCF: Step 3/4 — Apply Config (0x6038) ═══");
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-        let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
+        job.check()?;
+        job.progress(sink, "Apply config", 35, "0x6038");
+        emit_log_simple(sink, LogDirection::Tx, &[], "═══ RESTORE CCF: Step 3/4 — Apply Config (0x6038) ═══");
+        // 0x6038 is a secured routine
+        sdd_prerequisite_flow(sink, channel, true, emulator)?;
 
         let config_req = [0x31, 0x01, 0x60, 0x38];
         let config_start = std::time::Instant::now();
-        match send_uds_request(app, channel, tx, &config_req, true, emulator) {
+        match send_uds_request(sink, channel, tx, &config_req, true, emulator) {
             Ok(resp) => {
-                emit_log_simple(app, LogDirection::Rx, &resp, "0x6038 response");
+                emit_log_simple(sink, LogDirection::Rx, &resp, "0x6038 response");
                 let mut detail = resp.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
                 if resp.len() >= 7 {
                     let status = resp[4];
@@ -2538,7 +3684,7 @@ fn restore_ccf_inner(
                     let desc = crate::uds::services::describe_routine_result(
                         0x6038, Some(status), Some(result_byte), Some(error),
                     );
-                    emit_log_simple(app, LogDirection::Rx, &[], &format!(
+                    emit_log_simple(sink, LogDirection::Rx, &[], &format!(
                         "0x6038: STATUS=0x{:02X} RESULT=0x{:02X} ERROR=0x{:02X} — {}",
                         status, result_byte, error, desc
                     ));
@@ -2563,26 +3709,70 @@ fn restore_ccf_inner(
         }
 
         // SDD waits ~100 timer operations after 0x6038
-        emit_log_simple(app, LogDirection::Tx, &[], "Waiting 30s for 0x6038 to apply...");
+        let total = timing.configure_wait.as_secs();
+        let tick = timing.configure_wait / 30;
+        emit_log_simple(sink, LogDirection::Tx, &[], &format!("Waiting {}s for 0x6038 to apply...", total));
         for i in 1..=30 {
-            std::thread::sleep(std::time::Duration::from_secs(1));
+            job.sleep(tick)?;
+            let waited = (tick * i as u32).as_secs();
+            job.progress(sink, "Apply config", 40 + i / 2, &format!("Waiting {}s / {}s", waited, total));
             if i % 10 == 0 {
-                emit_log_simple(app, LogDirection::Tx, &[], &format!("{}s / 30s", i));
+                emit_log_simple(sink, LogDirection::Tx, &[], &format!("{}s / {}s", waited, total));
+            }
+        }
+
+        // 0x6038 Request Results, only when rehearsing against the emulated IMC: SDD goes
+        // straight to the reset, and a car that doesn't answer it must still be reset
+        let rehearsal = with_connection(state, |conn| Ok(conn.dll_path == EMULATED_DLL_PATH))?;
+        if rehearsal {
+            sdd_prerequisite_flow(sink, channel, true, emulator)?;
+            let results_req = [0x31, 0x03, 0x60, 0x38];
+            emit_log_simple(sink, LogDirection::Tx, &results_req, "0x6038 Request Results");
+            match send_uds_request(sink, channel, tx, &results_req, true, emulator) {
+                Ok(resp) if resp.len() >= 7 => {
+                    let (status, result_byte, error) = (resp[4], resp[5], resp[6]);
+                    let desc = crate::uds::services::describe_routine_result(
+                        0x6038, Some(status), Some(result_byte), Some(error),
+                    );
+                    let msg = format!("0x6038 Results: {}", desc);
+                    emit_log_simple(sink, LogDirection::Rx, &resp, &msg);
+                    let failed = status == 0x21 || result_byte != 0x01 || error != 0x00;
+                    result.steps.push(RestoreCcfStep {
+                        name: "0x6038 Results".into(),
+                        success: !failed,
+                        detail: desc.clone(),
+                        duration_ms: config_start.elapsed().as_millis() as u64,
+                    });
+                    if failed {
+                        return Err(format!("0x6038 did not complete: {}", desc));
+                    }
+                }
+                Ok(resp) => {
+                    let hex: Vec<String> = resp.iter().map(|b| format!("{:02X}", b)).collect();
+                    let msg = format!("0x6038 Results short response: {}", hex.join(" "));
+                    emit_log_simple(sink, LogDirection::Rx, &[], &msg);
+                }
+                Err(e) => {
+                    let msg = format!("0x6038 Results unavailable: {}", e);
+                    emit_log_simple(sink, LogDirection::Rx, &[], &msg);
+                }
             }
         }
 
         // ── STEP 4: ECU Reset ──
-        emit_log_simple(app, LogDirection::Tx, &[], "═══ RESTORE CCF: Step 4/4 — ECU Reset ═══");
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-        let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
+        job.check()?;
+        job.progress(sink, "ECU reset", 55, "ECUReset Hard (0x1101)");
+        emit_log_simple(sink, LogDirection::Tx, &[], "═══ RESTORE CCF: Step 4/4 — ECU Reset ═══");
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
 
         let reset_start = std::time::Instant::now();
         let reset_req = [0x11, 0x01];
-        emit_log_simple(app, LogDirection::Tx, &reset_req, "ECUReset Hard (0x1101)");
-        match send_uds_request(app, channel, tx, &reset_req, false, emulator) {
+        emit_log_simple(sink, LogDirection::Tx, &reset_req, "ECUReset Hard (0x1101)");
+        match send_uds_request(sink, channel, tx, &reset_req, false, emulator) {
             Ok(resp) => {
                 let hex = resp.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
-                emit_log_simple(app, LogDirection::Rx, &resp, "ECUReset OK");
+                emit_log_simple(sink, LogDirection::Rx, &resp, "ECUReset OK");
                 result.steps.push(RestoreCcfStep {
                     name: "ECU Reset".into(),
                     success: true,
@@ -2591,7 +3781,7 @@ fn restore_ccf_inner(
                 });
             }
             Err(e) => {
-                emit_log_simple(app, LogDirection::Rx, &[], &format!("ECUReset: {} (expected — ECU rebooting)", e));
+                emit_log_simple(sink, LogDirection::Rx, &[], &format!("ECUReset: {} (expected — ECU rebooting)", e));
                 result.steps.push(RestoreCcfStep {
                     name: "ECU Reset".into(),
                     success: true, // Expected to error — ECU is rebooting
@@ -2602,20 +3792,25 @@ fn restore_ccf_inner(
         }
 
         // J_85: 90-second delay for IMC reboot
-        emit_log_simple(app, LogDirection::Tx, &[], "IMC rebooting — waiting 90s...");
+        let total = timing.reboot_wait.as_secs();
+        let tick = timing.reboot_wait / 90;
+        emit_log_simple(sink, LogDirection::Tx, &[], &format!("IMC rebooting — waiting {}s...", total));
         for i in 1..=90 {
-            std::thread::sleep(std::time::Duration::from_secs(1));
+            job.sleep(tick)?;
+            let waited = (tick * i).as_secs();
+            job.progress(sink, "IMC reboot", 55 + (i * 40 / 90) as u8, &format!("Waiting {}s / {}s", waited, total));
             if i % 15 == 0 {
-                emit_log_simple(app, LogDirection::Tx, &[], &format!("{}s / 90s", i));
+                emit_log_simple(sink, LogDirection::Tx, &[], &format!("{}s / {}s", waited, total));
             }
         }
 
         // ══════════════════════════════════════════════════════
         // POST-FLIGHT: Read IMC DIDs after reboot
         // ══════════════════════════════════════════════════════
-        emit_log_simple(app, LogDirection::Tx, &[], "═══ POST-FLIGHT: Reading IMC DIDs after reboot ═══");
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-        let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
+        job.progress(sink, "Post-flight", 95, "Reading IMC DIDs");
+        emit_log_simple(sink, LogDirection::Tx, &[], "═══ POST-FLIGHT: Reading IMC DIDs after reboot ═══");
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
 
         let mut post = PostFlightInfo {
             dids_read: Vec::new(),
@@ -2631,7 +3826,7 @@ fn restore_ccf_inner(
 
         for &(did_id, label) in post_dids {
             let req = [0x22, (did_id >> 8) as u8, (did_id & 0xFF) as u8];
-            match send_uds_request(app, channel, tx, &req, true, emulator) {
+            match send_uds_request(sink, channel, tx, &req, true, emulator) {
                 Ok(resp) if resp.len() > 3 => {
                     let value_hex = resp[3..].iter()
                         .map(|b| format!("{:02X}", b))
@@ -2644,7 +3839,7 @@ fn restore_ccf_inner(
                     } else {
                         value_hex
                     };
-                    emit_log_simple(app, LogDirection::Rx, &[], &format!("DID 0x{:04X}: {}", did_id, display));
+                    emit_log_simple(sink, LogDirection::Rx, &[], &format!("DID 0x{:04X}: {}", did_id, display));
                     post.dids_read.push(EcuInfoEntry {
                         label: label.to_string(),
                         did_hex: format!("0x{:04X}", did_id),
@@ -2664,7 +3859,7 @@ fn restore_ccf_inner(
                     });
                 }
                 Err(e) => {
-                    emit_log_simple(app, LogDirection::Rx, &[], &format!("DID 0x{:04X}: {}", did_id, e));
+                    emit_log_simple(sink, LogDirection::Rx, &[], &format!("DID 0x{:04X}: {}", did_id, e));
                     post.dids_read.push(EcuInfoEntry {
                         label: label.to_string(),
                         did_hex: format!("0x{:04X}", did_id),
@@ -2679,7 +3874,7 @@ fn restore_ccf_inner(
         result.post_flight = Some(post);
     }
 
-    emit_log_simple(app, LogDirection::Rx, &[], "═══ RESTORE CCF COMPLETE ═══");
+    emit_log_simple(sink, LogDirection::Rx, &[], "═══ RESTORE CCF COMPLETE ═══");
     result.success = result.steps.iter().all(|s| s.success);
 
     // Save full result to JSON for analysis
@@ -2731,7 +3926,7 @@ fn restore_ccf_inner(
     if let Ok(json_str) = serde_json::to_string_pretty(&dump) {
         let path = dump_path(&filename);
         let _ = std::fs::write(&path, &json_str);
-        emit_log_simple(app, LogDirection::Rx, &[], &format!("Result saved → {}", path.display()));
+        emit_log_simple(sink, LogDirection::Rx, &[], &format!("Result saved → {}", path.display()));
     }
 
     Ok(result)
@@ -2744,21 +3939,20 @@ pub fn read_ccf(app: AppHandle, state: State<'_, AppState>) -> Result<Vec<EcuInf
     read_ccf_inner(&app, &state).map_err(|e| log_err("read_ccf", e))
 }
 
-fn read_ccf_inner(
-    app: &AppHandle,
-    state: &State<'_, AppState>,
+pub fn read_ccf_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
 ) -> Result<Vec<EcuInfoEntry>, String> {
-    let conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_ref().ok_or("Not connected")?;
-    let channel: &dyn crate::j2534::Channel =
-        conn.channel.as_ref().ok_or("No channel available")?;
-    let emulator = conn.emulator_manager.as_ref();
+    let bus = bus_handles(state)?;
+    let port = bus.port(ecu_addr::IMC_TX, ecu_addr::IMC_RX)?;
+    let channel: &dyn crate::j2534::Channel = &port;
+    let emulator = bus.emulator.as_deref();
 
     let bench_mode = emulator.is_some();
     let tx = ecu_addr::IMC_TX;
 
     // SDD prerequisite flow (no security needed for CCF)
-    sdd_prerequisite_flow(app, channel, false, emulator)?;
+    sdd_prerequisite_flow(sink, channel, false, emulator)?;
 
     // On real car: use SDD CCF transfer sequence (0x0E08 → 0x0E06)
     // On bench: skip (no GWM on CAN bus)
@@ -2766,15 +3960,15 @@ fn read_ccf_inner(
         // Step 1: 0x0E08 — Prepare/trigger CCF fetch from GWM (SDD J_40)
         let prepare_req = vec![0x31, 0x01, 0x0E, 0x08];
         emit_log_simple(
-            app,
+            sink,
             LogDirection::Tx,
             &prepare_req,
             "CCF Prepare (0x0E08) — trigger fetch from GWM",
         );
-        match send_uds_request(app, channel, tx, &prepare_req, true, emulator) {
+        match send_uds_request(sink, channel, tx, &prepare_req, true, emulator) {
             Ok(resp) => {
                 emit_log_simple(
-                    app,
+                    sink,
                     LogDirection::Rx,
                     &resp,
                     "0x0E08 OK",
@@ -2782,7 +3976,7 @@ fn read_ccf_inner(
             }
             Err(e) => {
                 emit_log_simple(
-                    app,
+                    sink,
                     LogDirection::Rx,
                     &[],
                     &format!("0x0E08 failed: {}", e),
@@ -2791,35 +3985,35 @@ fn read_ccf_inner(
         }
 
         // Step 2: 0x0E06 Start — begin CCF transfer (SDD J_45)
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
         let transfer_req = vec![0x31, 0x01, 0x0E, 0x06];
         emit_log_simple(
-            app,
+            sink,
             LogDirection::Tx,
             &transfer_req,
             "CCF Transfer (0x0E06) Start",
         );
-        match send_uds_request(app, channel, tx, &transfer_req, true, emulator) {
+        match send_uds_request(sink, channel, tx, &transfer_req, true, emulator) {
             Ok(resp) => {
-                emit_log_simple(app, LogDirection::Rx, &resp, "0x0E06 Start OK");
+                emit_log_simple(sink, LogDirection::Rx, &resp, "0x0E06 Start OK");
 
                 // Step 3: Poll Request Results — SDD gets 0x21 busy 2-3x then success
                 let mut transfer_ok = false;
                 for poll in 1..=10 {
                     std::thread::sleep(std::time::Duration::from_secs(1));
-                    let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
+                    let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
                     let results_req = vec![0x31, 0x03, 0x0E, 0x06];
                     emit_log_simple(
-                        app,
+                        sink,
                         LogDirection::Tx,
                         &results_req,
                         &format!("0x0E06 Request Results ({}/10)", poll),
                     );
-                    match send_uds_request(app, channel, tx, &results_req, true, emulator) {
+                    match send_uds_request(sink, channel, tx, &results_req, true, emulator) {
                         Ok(resp) => {
                             let extra = resp.len().saturating_sub(4);
                             emit_log_simple(
-                                app,
+                                sink,
                                 LogDirection::Rx,
                                 &resp,
                                 &format!("0x0E06 Results OK: {} extra bytes", extra),
@@ -2827,15 +4021,16 @@ fn read_ccf_inner(
                             // If response has CCF data beyond the 2-byte status, return it
                             if extra > 2 {
                                 let ccf_data = &resp[4..];
-                                save_ccf_dump(app, "IMC", "0x0E06 Results", ccf_data);
-                                return Ok(parse_ccf_entries(ccf_data));
+                                let source = "0x0E06 Results";
+                                archive_ccf(sink, &state.archive, "IMC", source, ccf_data);
+                                return Ok(parse_ccf_entries(ccf_data, &state.ccf_tables));
                             }
                             transfer_ok = true;
                             break;
                         }
                         Err(e) if e.contains("0x21") => {
                             emit_log_simple(
-                                app,
+                                sink,
                                 LogDirection::Rx,
                                 &[],
                                 &format!("0x0E06 busy (0x21), retrying... ({}/10)", poll),
@@ -2844,7 +4039,7 @@ fn read_ccf_inner(
                         }
                         Err(e) => {
                             emit_log_simple(
-                                app,
+                                sink,
                                 LogDirection::Rx,
                                 &[],
                                 &format!("0x0E06 Results failed: {}", e),
@@ -2856,7 +4051,7 @@ fn read_ccf_inner(
 
                 if transfer_ok {
                     emit_log_simple(
-                        app,
+                        sink,
                         LogDirection::Tx,
                         &[],
                         "CCF transfer complete, trying DID read...",
@@ -2865,7 +4060,7 @@ fn read_ccf_inner(
             }
             Err(e) => {
                 emit_log_simple(
-                    app,
+                    sink,
                     LogDirection::Rx,
                     &[],
                     &format!("0x0E06 Start failed: {}", e),
@@ -2874,7 +4069,7 @@ fn read_ccf_inner(
         }
     } else {
         emit_log_simple(
-            app,
+            sink,
             LogDirection::Tx,
             &[],
             "Bench: skipping 0x0E08/0x0E06 (no GWM on CAN)",
@@ -2882,15 +4077,16 @@ fn read_ccf_inner(
     }
 
     // Re-establish session before DID read
-    let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-    let _ = send_uds_request(app, channel, tx, &[0x10, 0x03], false, emulator);
+    let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
+    let _ = send_uds_request(sink, channel, tx, &[0x10, 0x03], false, emulator);
 
     // Try reading CCF via DID (after transfer, data may be in IMC's readable storage)
     for did in [0xEE00u16, 0xDE00] {
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-        if let Some(data) = read_ccf_block_did(app, channel, tx, "IMC", did, emulator) {
-            save_ccf_dump(app, "IMC", &format!("DID 0x{:04X}", did), &data);
-            return Ok(parse_ccf_entries(&data));
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
+        if let Some(data) = read_ccf_block_did(sink, channel, tx, "IMC", did, emulator) {
+            let source = format!("DID 0x{:04X}", did);
+            archive_ccf(sink, &state.archive, "IMC", &source, &data);
+            return Ok(parse_ccf_entries(&data, &state.ccf_tables));
         }
     }
 
@@ -2899,23 +4095,23 @@ fn read_ccf_inner(
         (vec![0x31, 0x01, 0x0E, 0x02], "0x0E02 List CCF"),
         (vec![0x31, 0x01, 0x0E, 0x01], "0x0E01 Report CCF"),
     ] {
-        let _ = send_uds_request(app, channel, tx, &[0x3E, 0x00], false, emulator);
-        emit_log_simple(app, LogDirection::Tx, &req, label);
-        match send_uds_request(app, channel, tx, &req, true, emulator) {
+        let _ = send_uds_request(sink, channel, tx, &[0x3E, 0x00], false, emulator);
+        emit_log_simple(sink, LogDirection::Tx, &req, label);
+        match send_uds_request(sink, channel, tx, &req, true, emulator) {
             Ok(resp) if resp.len() > 6 => {
                 let ccf_data = &resp[4..];
                 emit_log_simple(
-                    app,
+                    sink,
                     LogDirection::Rx,
                     &[],
                     &format!("{}: {} bytes", label, ccf_data.len()),
                 );
-                save_ccf_dump(app, "IMC", label, ccf_data);
-                return Ok(parse_ccf_entries(ccf_data));
+                archive_ccf(sink, &state.archive, "IMC", label, ccf_data);
+                return Ok(parse_ccf_entries(ccf_data, &state.ccf_tables));
             }
             Ok(resp) => {
                 emit_log_simple(
-                    app,
+                    sink,
                     LogDirection::Rx,
                     &resp,
                     &format!("{}: short response", label),
@@ -2923,7 +4119,7 @@ fn read_ccf_inner(
             }
             Err(e) => {
                 emit_log_simple(
-                    app,
+                    sink,
                     LogDirection::Rx,
                     &[],
                     &format!("{} failed: {}", label, e),
@@ -2942,35 +4138,17 @@ fn read_ccf_inner(
 }
 
 
-/// Save raw CCF bytes to a JSON dump file for analysis
-fn save_ccf_dump(app: &AppHandle, ecu: &str, source: &str, data: &[u8]) {
-    let hex: String = data
-        .iter()
-        .map(|b| format!("{:02X}", b))
-        .collect::<Vec<_>>()
-        .join(" ");
-    let dump = serde_json::json!({
-        "ecu": ecu,
-        "source": source,
-        "timestamp": chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-        "bytes": data.len(),
-        "raw_hex": hex,
-        "raw_bytes": data,
-    });
-    let path = dump_path("imc_ccf_dump.json");
-    if let Ok(json_str) = serde_json::to_string_pretty(&dump) {
-        let _ = std::fs::write(&path, &json_str);
-        emit_log_simple(
-            app,
-            LogDirection::Rx,
-            &[],
-            &format!("CCF saved → {}", path.display()),
-        );
-    }
+/// Keep raw CCF bytes in the vehicle archive for later diffs and rollback
+fn archive_ccf(sink: &dyn LogSink, archive: &VehicleArchive, ecu: &str, source: &str, data: &[u8]) {
+    let data = SnapshotData::Ccf {
+        source: source.to_string(),
+        raw_bytes: data.to_vec(),
+    };
+    archive_snapshot(sink, archive, ecu, data);
 }
 
 /// Parse CCF response data into structured entries
-fn parse_ccf_entries(data: &[u8]) -> Vec<EcuInfoEntry> {
+fn parse_ccf_entries(data: &[u8], tables: &CcfTables) -> Vec<EcuInfoEntry> {
     let mut entries = Vec::new();
 
     if data.is_empty() {
@@ -3006,7 +4184,13 @@ fn parse_ccf_entries(data: &[u8]) -> Vec<EcuInfoEntry> {
         category: "config".to_string(),
     });
 
-    // Parse known CCF fields if enough data
+    if let Ok(ccf) = Ccf::parse(data) {
+        let table = tables.for_vin(ccf.vin().as_deref());
+        entries.extend(parse_ccf_vdf_entries(&ccf, &table));
+        return entries;
+    }
+
+    // Not a VDF image: fall back to the legacy byte layout
     if !data.is_empty() {
         entries.push(EcuInfoEntry {
             label: "CCF Status Byte".to_string(),
@@ -3107,149 +4291,1316 @@ fn parse_ccf_entries(data: &[u8]) -> Vec<EcuInfoEntry> {
     entries
 }
 
-/// Read a single DID
+/// Header summary plus the decoded IMC-relevant options of a VDF CCF
+fn parse_ccf_vdf_entries(ccf: &Ccf, table: &CcfTable) -> Vec<EcuInfoEntry> {
+    let entry = |label: String, did_hex: String, value: String| EcuInfoEntry {
+        label,
+        did_hex,
+        value: Some(value),
+        error: None,
+        category: "config".to_string(),
+    };
+    let checksum = match ccf.checksum_status() {
+        ChecksumStatus::Valid => format!("0x{:04X} (valid)", ccf.header.checksum),
+        ChecksumStatus::Mismatch { expected } => format!(
+            "0x{:04X} (MISMATCH, expected 0x{:04X})",
+            ccf.header.checksum, expected
+        ),
+        ChecksumStatus::Truncated => format!(
+            "0x{:04X} (unverified: read stopped before {} bytes)",
+            ccf.header.checksum, ccf.header.length
+        ),
+    };
+    let mut entries = vec![
+        entry("CCF Checksum".into(), "CCF".into(), checksum),
+        entry(
+            "CCF Variant".into(),
+            "CCF".into(),
+            format!("0x{:04X} ({} blocks)", ccf.header.variant, ccf.blocks.len()),
+        ),
+    ];
+    if let Some(vin) = ccf.vin() {
+        entries.push(entry("CCF VIN".into(), "CCF".into(), vin));
+    }
+    entries.push(entry("CCF Decode Table".into(), "CCF".into(), table.info().name.clone()));
+    for &opt_id in IMC_CCF_OPTION_IDS {
+        let field = table.field(opt_id);
+        if let Some(value) = ccf.field(field) {
+            entries.push(entry(
+                table.name(opt_id),
+                format!("CCF {}", opt_id),
+                table.decode(opt_id, value),
+            ));
+        }
+    }
+    entries
+}
+
+// ─── CCF editor ─────────────────────────────────────────────────────
+
+/// Largest request a single ISO-TP message carries (12-bit length field)
+const ISOTP_MAX_LEN: usize = 4095;
+
+/// One requested change: option by number or decode-table name, value by
+/// number or decode-table label
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CcfEdit {
+    pub option: String,
+    pub value: String,
+}
+
+/// Outcome of a CCF edit preview, write or undo
+#[derive(Debug, Clone, Serialize)]
+pub struct CcfEditResult {
+    pub ecu: String,
+    pub did: u16,
+    pub vin: Option<String>,
+    /// Decode table the edits were resolved with
+    pub table: String,
+    pub changes: Vec<CcfOptionChange>,
+    pub checksum_before: u16,
+    pub checksum_after: u16,
+    /// False for a preview, or when the image already matched
+    pub written: bool,
+    /// The image as read before writing, for `undo_ccf_edit`
+    pub backup: Option<String>,
+    /// CCF rules the resulting image breaks
+    pub findings: Vec<RuleFinding>,
+}
+
+/// CCF image saved before it is overwritten
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CcfBackup {
+    pub ecu: String,
+    pub did: u16,
+    pub vin: Option<String>,
+    pub timestamp: String,
+    pub raw_hex: String,
+    pub raw_bytes: Vec<u8>,
+}
+
+/// An ECU whose CCF can be written, and the DID holding it
+struct CcfTarget {
+    tx: u32,
+    name: &'static str,
+    did: u16,
+    /// KeyGenMkI constants for security level 0x01; `None` until confirmed on a car
+    security: Option<[u8; 5]>,
+}
+
+impl CcfTarget {
+    /// How its image is read, as archived
+    fn source(&self) -> String {
+        format!("DID 0x{:04X}", self.did)
+    }
+}
+
+fn ccf_target(ecu: &str) -> Result<CcfTarget, String> {
+    match ecu.to_lowercase().as_str() {
+        "gwm" => Ok(CcfTarget { tx: ecu_addr::GWM_TX, name: "GWM", did: 0xEE00, security: None }),
+        "bcm" => Ok(CcfTarget { tx: ecu_addr::BCM_TX, name: "BCM", did: 0xDE00, security: None }),
+        other => Err(format!("No writable CCF on '{}' (use gwm or bcm)", other)),
+    }
+}
+
+/// Decode tables available: built in, then imported
 #[tauri::command]
-pub fn read_did(
+pub fn list_ccf_tables(state: State<'_, AppState>) -> Vec<CcfTableInfo> {
+    state.ccf_tables.list()
+}
+
+/// Import an SDD `CCF_DATA_*.exml` (encrypted or already decrypted) as a decode
+/// table for vehicles whose VIN starts with one of `vin_prefixes` (at least one)
+#[tauri::command]
+pub fn import_ccf_exml(
+    state: State<'_, AppState>,
+    path: String,
+    vin_prefixes: Vec<String>,
+) -> Result<CcfTableInfo, String> {
+    import_ccf_exml_inner(&state, &path, vin_prefixes)
+        .map_err(|e| log_err("import_ccf_exml", e))
+}
+
+pub fn import_ccf_exml_inner(
+    state: &AppState,
+    path: &str,
+    vin_prefixes: Vec<String>,
+) -> Result<CcfTableInfo, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let table = crate::ccf::exml::import(path, &data)?;
+    let info = CcfTableInfo {
+        vin_prefixes: vin_prefixes
+            .iter()
+            .map(|p| p.trim().to_uppercase())
+            .filter(|p| !p.is_empty())
+            .collect(),
+        ..table.info().clone()
+    };
+    state.ccf_tables.import(table.with_info(info))
+}
+
+/// Options of decode table `table` (default: the built-in one) whose ID, name or
+/// group matches `query` (all without one)
+#[tauri::command]
+pub fn search_ccf_options(
+    state: State<'_, AppState>,
+    query: Option<String>,
+    table: Option<String>,
+) -> Result<Vec<CcfOptionDef>, String> {
+    search_ccf_options_inner(&state, query.as_deref(), table.as_deref())
+        .map_err(|e| log_err("search_ccf_options", e))
+}
+
+pub fn search_ccf_options_inner(
+    state: &AppState,
+    query: Option<&str>,
+    table: Option<&str>,
+) -> Result<Vec<CcfOptionDef>, String> {
+    let table = match table {
+        Some(name) => state
+            .ccf_tables
+            .get(name)
+            .ok_or_else(|| format!("No CCF decode table '{}'", name))?,
+        None => CcfTable::builtin(),
+    };
+    Ok(table.search(query.unwrap_or_default()).into_iter().cloned().collect())
+}
+
+/// Read the CCF of `ecu` (gwm/bcm), apply `edits` and show what would change
+#[tauri::command]
+pub fn preview_ccf_edit(
     app: AppHandle,
     state: State<'_, AppState>,
-    ecu_tx: u32,
-    did_id: u16,
-) -> Result<Vec<u8>, String> {
-    let conn = state.connection.lock().map_err(|e| e.to_string())?;
-    let conn = conn.as_ref().ok_or("Not connected")?;
-    let channel: &dyn crate::j2534::Channel =
-        conn.channel.as_ref().ok_or("No channel available")?;
+    ecu: String,
+    edits: Vec<CcfEdit>,
+) -> Result<CcfEditResult, String> {
+    preview_ccf_edit_inner(&app, &state, &ecu, &edits).map_err(|e| log_err("preview_ccf_edit", e))
+}
+
+pub fn preview_ccf_edit_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecu: &str,
+    edits: &[CcfEdit],
+) -> Result<CcfEditResult, String> {
+    let target = ccf_target(ecu)?;
+    let bus = bus_handles(state)?;
+    let port = bus.port(target.tx, target.tx + 8)?;
+    let before = read_ccf_image(sink, &port, &target, bus.emulator.as_deref(), &state.archive)?;
+    let table = state.ccf_tables.for_vin(before.vin().as_deref());
+    let after = apply_ccf_edits(&table, &before, edits)?;
+    let mut result = ccf_edit_result(&table, &target, &before, &after);
+    result.findings = ccf_image_findings(sink, state, &table, &target, &after);
+    Ok(result)
+}
+
+/// Apply `edits` as in `preview_ccf_edit`, back up the current CCF, write the
+/// edited image with WriteDataByIdentifier and verify it by reading it back.
+/// An image breaking an error-severity CCF rule is only written with `force`.
+#[tauri::command]
+pub fn write_ccf_edit(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ecu: String,
+    edits: Vec<CcfEdit>,
+    force: Option<bool>,
+) -> Result<CcfEditResult, String> {
+    write_ccf_edit_inner(&app, &state, &ecu, &edits, force.unwrap_or(false))
+        .map_err(|e| log_err("write_ccf_edit", e))
+}
+
+pub fn write_ccf_edit_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecu: &str,
+    edits: &[CcfEdit],
+    force: bool,
+) -> Result<CcfEditResult, String> {
+    let target = ccf_target(ecu)?;
+    let bus = bus_handles(state)?;
+    let port = bus.port(target.tx, target.tx + 8)?;
+    let emulator = bus.emulator.as_deref();
+    let before = read_ccf_image(sink, &port, &target, emulator, &state.archive)?;
+    let table = state.ccf_tables.for_vin(before.vin().as_deref());
+    let after = apply_ccf_edits(&table, &before, edits)?;
+    let findings = check_ccf_before_write(sink, state, &table, &target, &before, &after, force)?;
+    let mut result = commit_ccf(sink, &port, &target, &table, &before, &after, emulator)?;
+    result.findings = findings;
+    if result.written {
+        archive_ccf(sink, &state.archive, target.name, &target.source(), &after.to_bytes());
+    }
+    Ok(result)
+}
+
+/// Write a backup made by `write_ccf_edit` back to its ECU (itself backed up first)
+#[tauri::command]
+pub fn undo_ccf_edit(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    backup: String,
+    force: Option<bool>,
+) -> Result<CcfEditResult, String> {
+    undo_ccf_edit_inner(&app, &state, &backup, force.unwrap_or(false))
+        .map_err(|e| log_err("undo_ccf_edit", e))
+}
+
+pub fn undo_ccf_edit_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    backup: &str,
+    force: bool,
+) -> Result<CcfEditResult, String> {
+    let text =
+        std::fs::read_to_string(backup).map_err(|e| format!("Cannot read {}: {}", backup, e))?;
+    let saved: CcfBackup = serde_json::from_str(&text)
+        .map_err(|e| format!("{} is not a CCF backup: {}", backup, e))?;
+    let target = ccf_target(&saved.ecu)?;
+    if saved.did != target.did {
+        return Err(format!(
+            "Backup holds DID 0x{:04X}, but the {} CCF is DID 0x{:04X}",
+            saved.did, target.name, target.did
+        ));
+    }
+    let image = Ccf::parse(&saved.raw_bytes).map_err(|e| format!("{}: {}", backup, e))?;
+    write_back_ccf(sink, state, &target, &image, "Backup", force)
+}
+
+/// Write a saved CCF image back to `target`, refusing one from another car or,
+/// unless `force`d, one breaking an error-severity CCF rule
+fn write_back_ccf(
+    sink: &dyn LogSink,
+    state: &AppState,
+    target: &CcfTarget,
+    after: &Ccf,
+    origin: &str,
+    force: bool,
+) -> Result<CcfEditResult, String> {
+    let bus = bus_handles(state)?;
+    let port = bus.port(target.tx, target.tx + 8)?;
+    let emulator = bus.emulator.as_deref();
+    let before = read_ccf_image(sink, &port, target, emulator, &state.archive)?;
+    if let (Some(car), Some(saved)) = (before.vin(), after.vin()) {
+        if car != saved {
+            return Err(format!(
+                "{} is for VIN {}, but the {} CCF belongs to {}",
+                origin, saved, target.name, car
+            ));
+        }
+    }
+    let table = state.ccf_tables.for_vin(before.vin().as_deref());
+    let findings = check_ccf_before_write(sink, state, &table, target, &before, after, force)?;
+    let mut result = commit_ccf(sink, &port, target, &table, &before, after, emulator)?;
+    result.findings = findings;
+    if result.written {
+        archive_ccf(sink, &state.archive, target.name, &target.source(), &after.to_bytes());
+    }
+    Ok(result)
+}
+
+/// `ccf` with `edits` applied to each option's sub-field
+fn apply_ccf_edits(table: &CcfTable, ccf: &Ccf, edits: &[CcfEdit]) -> Result<Ccf, String> {
+    if edits.is_empty() {
+        return Err("No CCF edits given".into());
+    }
+    let mut edited = ccf.clone();
+    for edit in edits {
+        let id = table.find(&edit.option)?;
+        let value = table.parse_value(id, &edit.value)?;
+        edited.set_field(table.field(id), value)?;
+    }
+    Ok(edited)
+}
+
+fn ccf_edit_result(
+    table: &CcfTable,
+    target: &CcfTarget,
+    before: &Ccf,
+    after: &Ccf,
+) -> CcfEditResult {
+    CcfEditResult {
+        ecu: target.name.to_string(),
+        did: target.did,
+        vin: before.vin(),
+        table: table.info().name.clone(),
+        changes: table.diff(before, after),
+        checksum_before: before.header.checksum,
+        checksum_after: after.header.checksum,
+        written: false,
+        backup: None,
+        findings: Vec::new(),
+    }
+}
+
+/// Read and parse the CCF image in the extended session, and archive it
+fn read_ccf_image(
+    sink: &dyn LogSink,
+    channel: &dyn crate::j2534::Channel,
+    target: &CcfTarget,
+    emulator: Option<&EcuEmulatorManager>,
+    archive: &VehicleArchive,
+) -> Result<Ccf, String> {
+    let _ = send_uds_request(sink, channel, target.tx, &[0x3E, 0x00], false, emulator);
+    send_uds_request(sink, channel, target.tx, &[0x10, 0x03], false, emulator)
+        .map_err(|e| format!("{} extended session failed: {}", target.name, e))?;
+    let raw = read_ccf_block_did(sink, channel, target.tx, target.name, target.did, emulator)
+        .ok_or_else(|| format!("{} CCF read (DID 0x{:04X}) failed", target.name, target.did))?;
+    let ccf = Ccf::parse(&raw).map_err(|e| format!("{} CCF: {}", target.name, e))?;
+    archive_ccf(sink, archive, target.name, &target.source(), &raw);
+    Ok(ccf)
+}
+
+/// Back up `before`, then write `after` and verify it; nothing is sent when the
+/// images already match
+fn commit_ccf(
+    sink: &dyn LogSink,
+    channel: &dyn crate::j2534::Channel,
+    target: &CcfTarget,
+    table: &CcfTable,
+    before: &Ccf,
+    after: &Ccf,
+    emulator: Option<&EcuEmulatorManager>,
+) -> Result<CcfEditResult, String> {
+    let mut result = ccf_edit_result(table, target, before, after);
+    let bytes = after.to_bytes();
+    if bytes == before.to_bytes() {
+        emit_log_simple(
+            sink,
+            LogDirection::Rx,
+            &[],
+            &format!("{} CCF already matches, nothing written", target.name),
+        );
+        return Ok(result);
+    }
+    after.check_complete().map_err(|e| format!("{} {}, not writing it", target.name, e))?;
+    if bytes.len() + 3 > ISOTP_MAX_LEN {
+        return Err(format!(
+            "{} CCF of {} bytes does not fit one WriteDataByIdentifier request",
+            target.name,
+            bytes.len()
+        ));
+    }
+    result.backup = Some(backup_ccf(sink, target, before)?);
+
+    unlock_ecu(sink, channel, target, emulator)?;
+    let mut req = vec![0x2E, (target.did >> 8) as u8, target.did as u8];
+    req.extend_from_slice(&bytes);
+    emit_log_simple(
+        sink,
+        LogDirection::Tx,
+        &req[..3],
+        &format!("WriteDID 0x{:04X} ({} CCF, {} bytes)", target.did, target.name, bytes.len()),
+    );
+    send_uds_request(sink, channel, target.tx, &req, true, emulator)
+        .map_err(|e| format!("{} CCF write failed: {}", target.name, e))?;
+    result.written = true;
+
+    let readback = read_ccf_block_did(sink, channel, target.tx, target.name, target.did, emulator)
+        .ok_or_else(|| format!("{} CCF readback failed after writing", target.name))?;
+    if readback != bytes {
+        return Err(format!(
+            "{} CCF readback differs from the written image — restore with the backup {}",
+            target.name,
+            result.backup.as_deref().unwrap_or_default()
+        ));
+    }
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "{} CCF written and verified ({} options changed)",
+            target.name,
+            result.changes.len()
+        ),
+    );
+    Ok(result)
+}
+
+/// Level 0x01 security access; a zero seed means the ECU is already unlocked. A key
+/// is only sent with the target's confirmed constants: a wrong one counts towards the
+/// attempt limit and locks the module out (NRC 0x36/0x37).
+fn unlock_ecu(
+    sink: &dyn LogSink,
+    channel: &dyn crate::j2534::Channel,
+    target: &CcfTarget,
+    emulator: Option<&EcuEmulatorManager>,
+) -> Result<(), String> {
+    let seed_resp = match send_uds_request(sink, channel, target.tx, &[0x27, 0x01], false, emulator)
+    {
+        Ok(resp) => resp,
+        // No level 0x01 on this module: the write itself tells whether it needs one
+        Err(e) if matches!(crate::ecu_emulator::nrc_from_error(&e), Some(0x11 | 0x12)) => {
+            emit_log_simple(
+                sink,
+                LogDirection::Rx,
+                &[],
+                &format!("{} has no security level 0x01, writing without unlocking", target.name),
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(format!("{} security seed request failed: {}", target.name, e)),
+    };
+    let seed = match seed_resp[..] {
+        [0x67, 0x01, a, b, c, ..] => u32::from_be_bytes([0, a, b, c]),
+        _ => return Err(format!("{} sent a malformed seed", target.name)),
+    };
+    if seed == 0 {
+        return Ok(());
+    }
+    let Some(constants) = target.security else {
+        return Err(format!(
+            "{} wants a security key, but its constants are not confirmed; \
+             not sending a guessed key",
+            target.name
+        ));
+    };
+    let key = crate::uds::keygen::keygen_mki(seed, &constants);
+    let [_, k0, k1, k2] = key.to_be_bytes();
+    send_uds_request(sink, channel, target.tx, &[0x27, 0x02, k0, k1, k2], false, emulator)
+        .map_err(|e| format!("{} security key rejected: {}", target.name, e))?;
+    Ok(())
+}
+
+/// Save `ccf` as a JSON backup next to the executable; returns its path
+fn backup_ccf(sink: &dyn LogSink, target: &CcfTarget, ccf: &Ccf) -> Result<String, String> {
+    let now = chrono::Local::now();
+    let raw = ccf.to_bytes();
+    let backup = CcfBackup {
+        ecu: target.name.to_string(),
+        did: target.did,
+        vin: ccf.vin(),
+        timestamp: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+        raw_hex: raw.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
+        raw_bytes: raw,
+    };
+    let path = dump_path(&format!(
+        "ccf_backup_{}_{}.json",
+        target.name.to_lowercase(),
+        now.format("%Y-%m-%d_%H-%M-%S%.3f")
+    ));
+    let json = serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("CCF backup failed: {}", e))?;
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!("{} CCF backup → {}", target.name, path.display()),
+    );
+    Ok(path.display().to_string())
+}
+
+// ─── CCF rules ──────────────────────────────────────────────────────
+
+/// CCF rules checked against what the car holds
+#[derive(Debug, Clone, Serialize)]
+pub struct CcfCheckReport {
+    pub vin: Option<String>,
+    /// Decode table the rules' labels were resolved with
+    pub table: String,
+    /// Where the rules came from: "built-in" or the rules file
+    pub rules: String,
+    #[serde(flatten)]
+    pub report: RuleReport,
+}
+
+/// Check the rules in effect against the GWM/BCM copies and the GWM's VIN;
+/// rules naming no ECU read the GWM copy, or the BCM one without it
+fn check_vehicle_rules(
+    state: &AppState,
+    table: &CcfTable,
+    gwm: Option<&Ccf>,
+    bcm: Option<&Ccf>,
+    vin: Option<&[u8]>,
+) -> RuleReport {
+    let mut input = RuleInput::new(table, if gwm.is_some() { "gwm" } else { "bcm" });
+    if let Some(ccf) = gwm {
+        input = input.with_ccf("gwm", ccf);
+    }
+    if let Some(ccf) = bcm {
+        input = input.with_ccf("bcm", ccf);
+    }
+    if let Some(vin) = vin {
+        input = input.with_did("gwm", did::VIN, vin);
+    }
+    state.ccf_rules.current().check(&input)
+}
+
+/// Rules `ccf` breaks on its own, as `target` would hold it; rules needing
+/// the other ECU's copy or a DID are skipped
+fn ccf_image_findings(
+    sink: &dyn LogSink,
+    state: &AppState,
+    table: &CcfTable,
+    target: &CcfTarget,
+    ccf: &Ccf,
+) -> Vec<RuleFinding> {
+    let ecu = target.name.to_lowercase();
+    let input = RuleInput::new(table, &ecu).with_ccf(&ecu, ccf);
+    let findings = state.ccf_rules.current().check(&input).findings;
+    log_rule_findings(sink, &findings);
+    findings
+}
+
+/// Check `after` against the CCF rules before it replaces `before`: an image
+/// breaking an error-severity rule, or one such rule naming a label `table`
+/// doesn't have, is refused unless `force`d
+fn check_ccf_before_write(
+    sink: &dyn LogSink,
+    state: &AppState,
+    table: &CcfTable,
+    target: &CcfTarget,
+    before: &Ccf,
+    after: &Ccf,
+    force: bool,
+) -> Result<Vec<RuleFinding>, String> {
+    let findings = ccf_image_findings(sink, state, table, target, after);
+    let unchecked: Vec<String> = state
+        .ccf_rules
+        .current()
+        .rules
+        .iter()
+        .filter(|r| r.severity == Severity::Error)
+        .filter_map(|r| Some(format!("{} ({})", r.id, r.unresolved(table)?)))
+        .collect();
+    if !unchecked.is_empty() && !force {
+        return Err(format!(
+            "CCF rule {} can't be checked; not writing {} CCF unless forced",
+            unchecked.join(", "),
+            target.name
+        ));
+    }
+    let broken: Vec<&str> = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .map(|f| f.rule.as_str())
+        .collect();
+    if !broken.is_empty() && !force && after.to_bytes() != before.to_bytes() {
+        return Err(format!(
+            "{} CCF breaks CCF rule {}; not writing it unless forced",
+            target.name,
+            broken.join(", ")
+        ));
+    }
+    Ok(findings)
+}
+
+fn log_rule_findings(sink: &dyn LogSink, findings: &[RuleFinding]) {
+    for f in findings {
+        let marker = if f.severity >= Severity::Warning { "⚠ " } else { "" };
+        emit_log_simple(
+            sink,
+            LogDirection::Rx,
+            &[],
+            &format!("{}CCF rule {} ({}): {}", marker, f.rule, f.severity, f.detail),
+        );
+    }
+}
+
+/// The CCF rules in effect: `ccf_rules.json` next to the executable, or the
+/// built-in ones
+#[tauri::command]
+pub fn list_ccf_rules(state: State<'_, AppState>) -> CcfRules {
+    (*state.ccf_rules.current()).clone()
+}
+
+/// Validate the rules in the JSON file at `path` and put them in effect
+#[tauri::command]
+pub fn import_ccf_rules(state: State<'_, AppState>, path: String) -> Result<CcfRules, String> {
+    import_ccf_rules_inner(&state, &path).map_err(|e| log_err("import_ccf_rules", e))
+}
+
+pub fn import_ccf_rules_inner(state: &AppState, path: &str) -> Result<CcfRules, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    let tables = state.ccf_tables.tables();
+    Ok((*state.ccf_rules.import(path, &json, &tables)?).clone())
+}
+
+/// Read the GWM and BCM CCFs and the VIN, and check the CCF rules against them
+#[tauri::command]
+pub fn check_ccf(app: AppHandle, state: State<'_, AppState>) -> Result<CcfCheckReport, String> {
+    check_ccf_inner(&app, &state).map_err(|e| log_err("check_ccf", e))
+}
+
+pub fn check_ccf_inner(sink: &dyn LogSink, state: &AppState) -> Result<CcfCheckReport, String> {
+    let read = read_vehicle_ccf(sink, state)?;
+    let gwm = read.gwm.as_deref().and_then(|raw| Ccf::parse(raw).ok());
+    let bcm = read.bcm.as_deref().and_then(|raw| Ccf::parse(raw).ok());
+    if gwm.is_none() && bcm.is_none() {
+        return Err("Neither the GWM nor the BCM CCF could be read".into());
+    }
+    let vin = gwm.as_ref().or(bcm.as_ref()).and_then(Ccf::vin);
+    let table = state.ccf_tables.for_vin(vin.as_deref());
+    let vin_did = read.vin.as_deref();
+    let report = check_vehicle_rules(state, &table, gwm.as_ref(), bcm.as_ref(), vin_did);
+    log_rule_findings(sink, &report.findings);
+    for skipped in &report.skipped {
+        emit_log_simple(
+            sink,
+            LogDirection::Rx,
+            &[],
+            &format!("CCF rule {} skipped: {}", skipped.rule, skipped.reason),
+        );
+    }
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!(
+            "CCF rules: {} broken, {} passed, {} skipped",
+            report.findings.len(),
+            report.passed,
+            report.skipped.len()
+        ),
+    );
+    Ok(CcfCheckReport {
+        vin,
+        table: table.info().name.clone(),
+        rules: state.ccf_rules.current().source.clone(),
+        report,
+    })
+}
+
+// ─── Vehicle archive ────────────────────────────────────────────────
+
+/// Keep `data` read from `ecu` in the vehicle archive; a failure is only logged
+fn archive_snapshot(sink: &dyn LogSink, archive: &VehicleArchive, ecu: &str, data: SnapshotData) {
+    let kind = data.kind();
+    let msg = match archive.save(ecu, data) {
+        Ok(info) => format!("{} {} archived → {}/{}", ecu.to_uppercase(), kind, info.vin, info.id),
+        Err(e) => format!("{} {} not archived: {}", ecu.to_uppercase(), kind, e),
+    };
+    emit_log_simple(sink, LogDirection::Rx, &[], &msg);
+}
+
+/// DIDs of a full scan from its JSON results (`bytes` is the whole 0x62 response)
+fn scanned_dids(results: &[serde_json::Value]) -> Vec<ScannedDid> {
+    results
+        .iter()
+        .filter_map(|r| {
+            let did = u16::from_str_radix(r["did"].as_str()?, 16).ok()?;
+            let resp: Option<Vec<u8>> = serde_json::from_value(r["bytes"].clone()).ok();
+            Some(ScannedDid {
+                did,
+                session: r["session"].as_str().unwrap_or_default().to_string(),
+                data: resp.map(|resp| resp.get(3..).unwrap_or_default().to_vec()),
+                error: r["error"].as_str().map(String::from),
+            })
+        })
+        .collect()
+}
+
+/// Vehicles with archived snapshots
+#[tauri::command]
+pub fn list_archived_vehicles(state: State<'_, AppState>) -> Vec<ArchivedVehicle> {
+    state.archive.vehicles()
+}
+
+/// Snapshots archived for `vin`, oldest first
+#[tauri::command]
+pub fn get_vehicle_history(state: State<'_, AppState>, vin: String) -> Vec<SnapshotInfo> {
+    state.archive.history(&vin)
+}
+
+/// One archived snapshot with its data
+#[tauri::command]
+pub fn get_snapshot(state: State<'_, AppState>, id: String) -> Result<Snapshot, String> {
+    state.archive.load(&id).map_err(|e| log_err("get_snapshot", e))
+}
+
+/// What changed between two archived snapshots of the same kind
+#[tauri::command]
+pub fn diff_snapshots(
+    state: State<'_, AppState>,
+    from: String,
+    to: String,
+) -> Result<SnapshotDiff, String> {
+    diff_snapshots_inner(&state, &from, &to).map_err(|e| log_err("diff_snapshots", e))
+}
+
+pub fn diff_snapshots_inner(
+    state: &AppState,
+    from: &str,
+    to: &str,
+) -> Result<SnapshotDiff, String> {
+    let from = state.archive.load(from)?;
+    let to = state.archive.load(to)?;
+    archive::diff(&state.ccf_tables, &from, &to)
+}
+
+/// Write an archived GWM/BCM CCF snapshot back to its ECU, backing up and
+/// verifying as `write_ccf_edit` does
+#[tauri::command]
+pub fn rollback_ccf(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    force: Option<bool>,
+) -> Result<CcfEditResult, String> {
+    rollback_ccf_inner(&app, &state, &id, force.unwrap_or(false))
+        .map_err(|e| log_err("rollback_ccf", e))
+}
+
+pub fn rollback_ccf_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    id: &str,
+    force: bool,
+) -> Result<CcfEditResult, String> {
+    let snapshot = state.archive.load(id)?;
+    let SnapshotData::Ccf { raw_bytes, .. } = &snapshot.data else {
+        return Err(format!("Snapshot {} is a {}, not a CCF", id, snapshot.data.kind()));
+    };
+    let target = ccf_target(&snapshot.ecu)?;
+    let image = Ccf::parse(raw_bytes).map_err(|e| format!("{}: {}", id, e))?;
+    // A capped read can't be written back whole
+    image.check_complete().map_err(|e| format!("Snapshot {}: {}", id, e))?;
+    write_back_ccf(sink, state, &target, &image, "Snapshot", force)
+}
+
+/// Read a single DID
+#[tauri::command]
+pub fn read_did(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ecu_tx: u32,
+    did_id: u16,
+) -> Result<Vec<u8>, String> {
+    read_did_inner(&app, &state, ecu_tx, did_id).map_err(|e| log_err("read_did", e))
+}
+
+pub fn read_did_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecu_tx: u32,
+    did_id: u16,
+) -> Result<Vec<u8>, String> {
+    let bus = bus_handles(state)?;
+    // JLR response IDs are request ID + 8
+    let port = bus.port(ecu_tx, ecu_tx + 8)?;
+
+    send_read_did(sink, &port, ecu_tx, did_id, bus.emulator.as_deref())
+}
+
+/// DTC as returned to the frontend / CLI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DtcEntry {
+    pub code: String,
+    pub raw_hex: String,
+    pub status: u8,
+    pub flags: Vec<String>,
+}
+
+/// Read DTCs from an ECU (0x19 0x02, reportDTCByStatusMask)
+#[tauri::command]
+pub fn read_dtc(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    ecu: String,
+    status_mask: Option<u8>,
+) -> Result<Vec<DtcEntry>, String> {
+    read_dtc_inner(&app, &state, &ecu, status_mask.unwrap_or(DTC_STATUS_ALL))
+        .map_err(|e| log_err("read_dtc", e))
+}
+
+pub fn read_dtc_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    ecu: &str,
+    status_mask: u8,
+) -> Result<Vec<DtcEntry>, String> {
+    let (tx_id, rx_id) = ecu_ids_by_name(ecu).ok_or_else(|| format!("Unknown ECU: {}", ecu))?;
+    let bus = bus_handles(state)?;
+    let port = bus.port(tx_id, rx_id)?;
+
+    let request = [0x19, DTC_BY_STATUS_MASK, status_mask];
+    emit_log_simple(
+        sink,
+        LogDirection::Tx,
+        &request,
+        &format!("ReadDTC {} (mask 0x{:02X})", ecu.to_uppercase(), status_mask),
+    );
+    let resp = send_uds_request(sink, &port, tx_id, &request, false, bus.emulator.as_deref())?;
+    let dtcs = parse_dtc_report(&resp)?;
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!("{} DTC(s) stored", dtcs.len()),
+    );
+
+    let entries: Vec<DtcEntry> = dtcs
+        .iter()
+        .map(|d| DtcEntry {
+            code: d.code(),
+            raw_hex: format!("{:06X}", d.raw),
+            status: d.status,
+            flags: d.status_flags().into_iter().map(String::from).collect(),
+        })
+        .collect();
+    // Only a full report says which DTCs are gone since the last one
+    if status_mask == DTC_STATUS_ALL {
+        if state.archive.current_vin().is_none() {
+            // Nothing read this session carried the VIN yet; ask the ECU
+            let emulator = bus.emulator.as_deref();
+            if let Ok(vin) = send_read_did(sink, &port, tx_id, did::VIN, emulator) {
+                state.archive.note_vin(&String::from_utf8_lossy(&vin));
+            }
+        }
+        let data = SnapshotData::Dtcs { dtcs: entries.clone() };
+        archive_snapshot(sink, &state.archive, ecu, data);
+    }
+    Ok(entries)
+}
+
+/// List available routines
+#[tauri::command]
+pub fn list_routines() -> Vec<RoutineInfo> {
+    vec![
+        // Diagnostics
+        RoutineInfo {
+            routine_id: routine::SSH_ENABLE,
+            name: "SSH Enable".into(),
+            description: "Enable SSH access on IMC (0x603E)".into(),
+            category: "Diagnostics".into(),
+            needs_security: true,
+            needs_pending: true,
+        },
+        RoutineInfo {
+            routine_id: routine::ENG_SCREEN_LVL2,
+            name: "Engineering Screen Level 2".into(),
+            description: "Enable engineering screen level 2 (0x603D)".into(),
+            category: "Diagnostics".into(),
+            needs_security: true,
+            needs_pending: false,
+        },
+        RoutineInfo {
+            routine_id: routine::POWER_OVERRIDE,
+            name: "IMC Power Override".into(),
+            description: "Override IMC power state (0x6043)".into(),
+            category: "Diagnostics".into(),
+            needs_security: true,
+            needs_pending: false,
+        },
+        // Configuration
+        RoutineInfo {
+            routine_id: routine::CONFIGURE_LINUX,
+            name: "Configure Linux to Hardware".into(),
+            description: "Reconfigure IMC Linux environment (0x6038)".into(),
+            category: "Configuration".into(),
+            needs_security: true,
+            needs_pending: true,
+        },
+        RoutineInfo {
+            routine_id: routine::VIN_LEARN,
+            name: "VIN Learn".into(),
+            description: "Learn VIN to ECU (0x0404)".into(),
+            category: "Configuration".into(),
+            needs_security: false,
+            needs_pending: false,
+        },
+        RoutineInfo {
+            routine_id: routine::RETRIEVE_CCF,
+            name: "Retrieve CCF".into(),
+            description: "Retrieve Central Configuration (0x0E00)".into(),
+            category: "Configuration".into(),
+            needs_security: false,
+            needs_pending: true,
+        },
+        RoutineInfo {
+            routine_id: routine::REPORT_CCF,
+            name: "Report CCF".into(),
+            description: "Report Central Configuration (0x0E01)".into(),
+            category: "Configuration".into(),
+            needs_security: false,
+            needs_pending: true,
+        },
+        RoutineInfo {
+            routine_id: routine::LIST_CCF,
+            name: "List CCF".into(),
+            description: "List Central Configuration (0x0E02)".into(),
+            category: "Configuration".into(),
+            needs_security: false,
+            needs_pending: true,
+        },
+        // Recovery
+        RoutineInfo {
+            routine_id: routine::DVD_RECOVER,
+            name: "Recover Locked DVD Region".into(),
+            description: "Recover locked DVD region (0x603F)".into(),
+            category: "Recovery".into(),
+            needs_security: true,
+            needs_pending: false,
+        },
+        RoutineInfo {
+            routine_id: routine::RESET_PIN,
+            name: "Reset Customer Pin".into(),
+            description: "Reset customer PIN code (0x6042)".into(),
+            category: "Recovery".into(),
+            needs_security: true,
+            needs_pending: false,
+        },
+        // Advanced
+        RoutineInfo {
+            routine_id: routine::FAN_CONTROL,
+            name: "Control Auxiliary Fan".into(),
+            description: "Control auxiliary fan (0x6041)".into(),
+            category: "Advanced".into(),
+            needs_security: true,
+            needs_pending: false,
+        },
+        RoutineInfo {
+            routine_id: routine::GEN_KEY,
+            name: "Generate Key".into(),
+            description: "Generate cryptographic key (0x6045)".into(),
+            category: "Advanced".into(),
+            needs_security: true,
+            needs_pending: false,
+        },
+        RoutineInfo {
+            routine_id: routine::SHARED_SECRET,
+            name: "Shared Secret".into(),
+            description: "Compute shared secret (0x6046)".into(),
+            category: "Advanced".into(),
+            needs_security: true,
+            needs_pending: false,
+        },
+    ]
+}
+
+// ─── Background jobs ────────────────────────────────────────────────
+
+/// Start a long-running operation on a worker thread. Returns the job ID immediately;
+/// progress arrives as "job-progress" events and the result via `list_jobs`.
+#[tauri::command]
+pub fn start_job(app: AppHandle, kind: JobKind) -> Result<u64, String> {
+    start_job_inner(&app, kind).map_err(|e| log_err("start_job", e))
+}
+
+/// Spawn the job worker; the app handle is both the log sink and the source of `AppState`
+pub fn start_job_inner<R: tauri::Runtime>(app: &AppHandle<R>, kind: JobKind) -> Result<u64, String> {
+    let state = app.state::<AppState>();
+    if !state.is_connected() {
+        return Err("Not connected".into());
+    }
+    let job = state.jobs.create(&kind);
+    let id = job.id();
+    let app = app.clone();
+    emit_log_simple(
+        &app,
+        LogDirection::Tx,
+        &[],
+        &format!("Job {} started: {}", id, kind.name()),
+    );
+
+    std::thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let outcome = run_job(&app, &state, &kind, &job);
+        if job.is_cancelled() {
+            cleanup_cancelled_job(&app, &state, &kind);
+        }
+        match &outcome {
+            Ok(_) => emit_log_simple(&app, LogDirection::Rx, &[], &format!("Job {} finished", id)),
+            Err(e) => {
+                let _ = log_err(kind.name(), e.clone());
+            }
+        }
+        job.finish(&app, outcome);
+    });
+
+    Ok(id)
+}
+
+/// Request cancellation of a running job. The worker stops at its next check point.
+#[tauri::command]
+pub fn cancel_job(app: AppHandle, state: State<'_, AppState>, job_id: u64) -> Result<(), String> {
+    cancel_job_inner(&app, &state, job_id).map_err(|e| log_err("cancel_job", e))
+}
+
+pub fn cancel_job_inner(sink: &dyn LogSink, state: &AppState, job_id: u64) -> Result<(), String> {
+    if !state.jobs.cancel(job_id) {
+        return Err(format!("Job {} is not running", job_id));
+    }
+    emit_log_simple(
+        sink,
+        LogDirection::Tx,
+        &[],
+        &format!("Job {}: cancel requested", job_id),
+    );
+    Ok(())
+}
+
+/// List running and finished jobs (oldest first)
+#[tauri::command]
+pub fn list_jobs(state: State<'_, AppState>) -> Vec<JobInfo> {
+    state.jobs.list()
+}
+
+/// Run a job's operation on the calling thread and serialize its result
+/// (the worker body of `start_job`; the CLI calls it directly).
+pub fn run_job(
+    sink: &dyn LogSink,
+    state: &AppState,
+    kind: &JobKind,
+    job: &JobContext,
+) -> Result<serde_json::Value, String> {
+    let value = match kind {
+        JobKind::RestoreCcf { sniff } => {
+            serde_json::to_value(restore_ccf_inner(sink, state, *sniff, job)?)
+        }
+        JobKind::CanSniffRoutine => serde_json::to_value(can_sniff_routine_inner(sink, state, job)?),
+        JobKind::SniffAction {
+            ecu,
+            action,
+            options,
+        } => serde_json::to_value(sniff_action_inner(sink, state, ecu, action, options, job)?),
+        JobKind::ScanBcm => serde_json::to_value(scan_bcm_full_inner(sink, state, job)?),
+        JobKind::ScanGwm => serde_json::to_value(scan_gwm_full_inner(sink, state, job)?),
+        JobKind::ScanIpc => serde_json::to_value(scan_ipc_full_inner(sink, state, job)?),
+        JobKind::ReplayCan { path, options } => {
+            serde_json::to_value(replay_can_log_inner(sink, state, path, options, job)?)
+        }
+        JobKind::MonitorCan { options } => {
+            serde_json::to_value(monitor_can_inner(sink, state, options, job)?)
+        }
+    };
+    value.map_err(|e| e.to_string())
+}
+
+/// After a cancel: make sure the ISO15765 channel is back (a raw CAN capture or replay may
+/// have taken its slot) and return the target ECU to the default session.
+fn cleanup_cancelled_job(sink: &dyn LogSink, state: &AppState, kind: &JobKind) {
+    let Ok(mut guard) = state.connection.lock() else {
+        return;
+    };
+    let Some(conn) = guard.as_mut() else {
+        return;
+    };
+    if conn.channel.is_none() {
+        if let Err(e) = reopen_iso15765_channel(conn) {
+            emit_log_simple(
+                sink,
+                LogDirection::Error,
+                &[],
+                &format!("Cancel cleanup: failed to reopen ISO15765: {}", e),
+            );
+            return;
+        }
+    }
+    let tx = match kind {
+        JobKind::RestoreCcf { .. } | JobKind::CanSniffRoutine => ecu_addr::IMC_TX,
+        JobKind::ScanBcm => ecu_addr::BCM_TX,
+        JobKind::ScanGwm => ecu_addr::GWM_TX,
+        JobKind::ScanIpc => ecu_addr::IPC_TX,
+        JobKind::SniffAction { ecu, .. } => {
+            match ecu_tx_by_name_or_id(ecu) {
+                Some(tx) => tx,
+                None => return,
+            }
+        }
+        // A replay or the monitor talks to no ECU
+        JobKind::ReplayCan { .. } | JobKind::MonitorCan { .. } => return,
+    };
+    if let Some(channel) = conn.channel.as_deref() {
+        let emulator = conn.emulator_manager.as_deref();
+        emit_log_simple(sink, LogDirection::Tx, &[0x10, 0x01], "Cancel cleanup: Default Session");
+        let _ = send_uds_request(sink, channel, tx, &[0x10, 0x01], false, emulator);
+    }
+}
+
+// ─── Automation API ─────────────────────────────────────────────────
+
+/// Start the local HTTP automation API (127.0.0.1 only). Returns its URL and token;
+/// calling it again while running returns the existing server's info.
+#[tauri::command]
+pub fn start_api_server(app: AppHandle, port: Option<u16>) -> Result<ApiInfo, String> {
+    start_api_server_inner(&app, port, None).map_err(|e| log_err("start_api_server", e))
+}
+
+pub fn start_api_server_inner<R: tauri::Runtime>(
+    app: &AppHandle<R>,
+    port: Option<u16>,
+    token: Option<String>,
+) -> Result<ApiInfo, String> {
+    let state = app.state::<AppState>();
+    let mut api = state.api.lock().map_err(|e| e.to_string())?;
+    if let Some(server) = api.as_ref() {
+        return Ok(server.info());
+    }
+    let server = ApiServer::start(
+        app.clone(),
+        port.unwrap_or(crate::api::DEFAULT_PORT),
+        token.unwrap_or_else(crate::api::generate_token),
+    )?;
+    let info = server.info();
+    emit_log_simple(
+        app,
+        LogDirection::Rx,
+        &[],
+        &format!("Automation API listening on {}", info.url),
+    );
+    *api = Some(server);
+    Ok(info)
+}
+
+/// Stop the automation API (no-op if it isn't running)
+#[tauri::command]
+pub fn stop_api_server(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let server = state.api.lock().map_err(|e| e.to_string())?.take();
+    if let Some(mut server) = server {
+        server.stop();
+        emit_log_simple(&app, LogDirection::Rx, &[], "Automation API stopped");
+    }
+    Ok(())
+}
+
+/// URL and token of the running automation API, if any
+#[tauri::command]
+pub fn get_api_status(state: State<'_, AppState>) -> Option<ApiInfo> {
+    state.api.lock().ok()?.as_ref().map(|s| s.info())
+}
+
+// ─── Remote adapter bridge ──────────────────────────────────────────
+
+/// Bridge status returned to frontend
+#[derive(Debug, Serialize)]
+pub struct BridgeStatus {
+    pub addr: String,
+    pub token: String,
+    pub dll_path: String,
+}
+
+/// Serve this machine's J2534 adapter to a remote helper. The bridge needs the
+/// adapter to itself, so the app must not be connected locally. It listens on
+/// loopback unless `bind` names another address (e.g. "0.0.0.0" for every interface).
+#[tauri::command]
+pub fn start_bridge(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    dll_path: Option<String>,
+    bind: Option<String>,
+    port: Option<u16>,
+    token: Option<String>,
+) -> Result<BridgeStatus, String> {
+    start_bridge_inner(&app, &state, dll_path, bind, port, token)
+        .map_err(|e| log_err("start_bridge", e))
+}
+
+pub fn start_bridge_inner(
+    sink: &dyn LogSink,
+    state: &AppState,
+    dll_path: Option<String>,
+    bind: Option<String>,
+    port: Option<u16>,
+    token: Option<String>,
+) -> Result<BridgeStatus, String> {
+    if state.is_connected() {
+        return Err("Disconnect first — the bridge needs exclusive use of the adapter".into());
+    }
+    let mut bridge = state.bridge.lock().map_err(|e| e.to_string())?;
+    if bridge.is_some() {
+        return Err("Bridge already running".into());
+    }
+
+    let (_lib, device, path) = open_device(sink, dll_path)?;
+    let channel = device.connect_iso15765(500000)?;
+    let _ = channel.set_iso15765_config(0, 0, 0);
+    let token = token.unwrap_or_else(crate::api::generate_token);
+    let bind = format!(
+        "{}:{}",
+        bind.as_deref().unwrap_or(remote::DEFAULT_BIND),
+        port.unwrap_or(remote::DEFAULT_PORT)
+    );
+    let server = BridgeServer::start(Box::new(channel), &bind, token.clone())?;
+
+    let status = BridgeStatus {
+        addr: server.addr().to_string(),
+        token,
+        dll_path: path.clone(),
+    };
+    emit_log_simple(
+        sink,
+        LogDirection::Rx,
+        &[],
+        &format!("Bridge serving {} on {}", path, status.addr),
+    );
+    *bridge = Some(BridgeSession {
+        server,
+        device,
+        token: status.token.clone(),
+        dll_path: path,
+    });
+    Ok(status)
+}
+
+/// Stop serving the adapter (no-op if the bridge isn't running)
+#[tauri::command]
+pub fn stop_bridge(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let session = state.bridge.lock().map_err(|e| e.to_string())?.take();
+    if let Some(session) = session {
+        // Server (and its channel) drop before the device
+        drop(session);
+        emit_log_simple(&app, LogDirection::Rx, &[], "Bridge stopped");
+    }
+    Ok(())
+}
+
+/// Address/token of the running bridge, if any
+#[tauri::command]
+pub fn get_bridge_status(state: State<'_, AppState>) -> Option<BridgeStatus> {
+    let bridge = state.bridge.lock().ok()?;
+    bridge.as_ref().map(|b| BridgeStatus {
+        addr: b.server.addr().to_string(),
+        token: b.token.clone(),
+        dll_path: b.dll_path.clone(),
+    })
+}
+
+// ─── Internal helpers ───────────────────────────────────────────────
+
+/// Router + emulator cloned out of the connection, so a UDS exchange doesn't
+/// hold `state.connection` for its whole duration.
+struct Bus {
+    router: Arc<ChannelRouter>,
+    emulator: Option<Arc<EcuEmulatorManager>>,
+}
+
+impl Bus {
+    /// Exclusive, FIFO-ordered queue for one ECU; errors if earlier requests
+    /// to it still hold it after `router::PORT_WAIT`
+    fn port(&self, tx_id: u32, rx_id: u32) -> Result<EcuPort, String> {
+        self.router.port(tx_id, rx_id)
+    }
+}
 
-    let data = send_read_did(
-        &app,
-        channel,
-        ecu_tx,
-        did_id,
-        conn.emulator_manager.as_ref(),
-    )
-    .map_err(|e| log_err("read_did", e))?;
-    Ok(data)
+/// (request, response) CAN IDs for an ECU name ("imc", "bcm", "gwm", "ipc")
+pub fn ecu_ids_by_name(ecu: &str) -> Option<(u32, u32)> {
+    match ecu.to_lowercase().as_str() {
+        "imc" => Some((ecu_addr::IMC_TX, ecu_addr::IMC_RX)),
+        "bcm" => Some((ecu_addr::BCM_TX, ecu_addr::BCM_RX)),
+        "gwm" => Some((ecu_addr::GWM_TX, ecu_addr::GWM_RX)),
+        "ipc" => Some((ecu_addr::IPC_TX, ecu_addr::IPC_RX)),
+        _ => None,
+    }
 }
 
-/// List available routines
-#[tauri::command]
-pub fn list_routines() -> Vec<RoutineInfo> {
-    vec![
-        // Diagnostics
-        RoutineInfo {
-            routine_id: routine::SSH_ENABLE,
-            name: "SSH Enable".into(),
-            description: "Enable SSH access on IMC (0x603E)".into(),
-            category: "Diagnostics".into(),
-            needs_security: true,
-            needs_pending: true,
-        },
-        RoutineInfo {
-            routine_id: routine::ENG_SCREEN_LVL2,
-            name: "Engineering Screen Level 2".into(),
-            description: "Enable engineering screen level 2 (0x603D)".into(),
-            category: "Diagnostics".into(),
-            needs_security: true,
-            needs_pending: false,
-        },
-        RoutineInfo {
-            routine_id: routine::POWER_OVERRIDE,
-            name: "IMC Power Override".into(),
-            description: "Override IMC power state (0x6043)".into(),
-            category: "Diagnostics".into(),
-            needs_security: true,
-            needs_pending: false,
-        },
-        // Configuration
-        RoutineInfo {
-            routine_id: routine::CONFIGURE_LINUX,
-            name: "Configure Linux to Hardware".into(),
-            description: "Reconfigure IMC Linux environment (0x6038)".into(),
-            category: "Configuration".into(),
-            needs_security: true,
-            needs_pending: true,
-        },
-        RoutineInfo {
-            routine_id: routine::VIN_LEARN,
-            name: "VIN Learn".into(),
-            description: "Learn VIN to ECU (0x0404)".into(),
-            category: "Configuration".into(),
-            needs_security: false,
-            needs_pending: false,
-        },
-        RoutineInfo {
-            routine_id: routine::RETRIEVE_CCF,
-            name: "Retrieve CCF".into(),
-            description: "Retrieve Central Configuration (0x0E00)".into(),
-            category: "Configuration".into(),
-            needs_security: false,
-            needs_pending: true,
-        },
-        RoutineInfo {
-            routine_id: routine::REPORT_CCF,
-            name: "Report CCF".into(),
-            description: "Report Central Configuration (0x0E01)".into(),
-            category: "Configuration".into(),
-            needs_security: false,
-            needs_pending: true,
-        },
-        RoutineInfo {
-            routine_id: routine::LIST_CCF,
-            name: "List CCF".into(),
-            description: "List Central Configuration (0x0E02)".into(),
-            category: "Configuration".into(),
-            needs_security: false,
-            needs_pending: true,
-        },
-        // Recovery
-        RoutineInfo {
-            routine_id: routine::DVD_RECOVER,
-            name: "Recover Locked DVD Region".into(),
-            description: "Recover locked DVD region (0x603F)".into(),
-            category: "Recovery".into(),
-            needs_security: true,
-            needs_pending: false,
-        },
-        RoutineInfo {
-            routine_id: routine::RESET_PIN,
-            name: "Reset Customer Pin".into(),
-            description: "Reset customer PIN code (0x6042)".into(),
-            category: "Recovery".into(),
-            needs_security: true,
-            needs_pending: false,
-        },
-        // Advanced
-        RoutineInfo {
-            routine_id: routine::FAN_CONTROL,
-            name: "Control Auxiliary Fan".into(),
-            description: "Control auxiliary fan (0x6041)".into(),
-            category: "Advanced".into(),
-            needs_security: true,
-            needs_pending: false,
-        },
-        RoutineInfo {
-            routine_id: routine::GEN_KEY,
-            name: "Generate Key".into(),
-            description: "Generate cryptographic key (0x6045)".into(),
-            category: "Advanced".into(),
-            needs_security: true,
-            needs_pending: false,
-        },
-        RoutineInfo {
-            routine_id: routine::SHARED_SECRET,
-            name: "Shared Secret".into(),
-            description: "Compute shared secret (0x6046)".into(),
-            category: "Advanced".into(),
-            needs_security: true,
-            needs_pending: false,
-        },
-    ]
+/// Request CAN ID of an ECU name or a `0x`-prefixed ID
+fn ecu_tx_by_name_or_id(ecu: &str) -> Option<u32> {
+    ecu_ids_by_name(ecu)
+        .map(|(tx, _)| tx)
+        .or_else(|| canlog::parse_id(ecu))
 }
 
-// ─── Internal helpers ───────────────────────────────────────────────
+fn bus_handles(state: &AppState) -> Result<Bus, String> {
+    let conn = state.connection.lock().map_err(|e| e.to_string())?;
+    let conn = conn.as_ref().ok_or("Not connected")?;
+    Ok(Bus {
+        router: conn.channel.clone().ok_or("No channel available")?,
+        emulator: conn.emulator_manager.clone(),
+    })
+}
 
 /// Human-readable DID name lookup
-fn did_name(did_id: u16) -> &'static str {
+pub(crate) fn did_name(did_id: u16) -> &'static str {
     match did_id {
         0xF190 => "VIN",
         0xF188 => "Master RPM Part",
@@ -3274,45 +5625,34 @@ fn did_name(did_id: u16) -> &'static str {
 }
 
 /// Send raw UDS request on a channel and get response.
-/// If an emulator is provided and handles the tx_id, bypass J2534 entirely.
+/// If an emulator is provided and handles the tx_id, bypass J2534 entirely
+/// (its bus still applies response timing and any fault scenario).
 /// Handles NRC 0x21 (busyRepeatRequest) with retries per SDD EXML:
 ///   MAX_BUSY_ATTEMPTS=6, MAX_RETRY_PERIOD=6000ms
 /// Handles NRC 0x78 (responsePending) by continuing to wait.
-/// When CAN bus emulation filters are active, also responds to requests from
-/// other ECUs (e.g. IMC→GWM during 0x0E00 Retrieve CCF).
-fn send_uds_request<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+/// Requests other ECUs make of emulated ones (e.g. IMC→GWM during 0x0E00
+/// Retrieve CCF) are answered by the emulator's bus responder, not here.
+fn send_uds_request(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     tx_id: u32,
     request: &[u8],
     wait_pending: bool,
     emulator: Option<&EcuEmulatorManager>,
 ) -> Result<Vec<u8>, String> {
-    // Software routing: if the target ECU is emulated, handle locally
-    if let Some(emu) = emulator {
-        if let Some(response) = emu.try_handle(tx_id, request) {
-            emit_log_simple(app, LogDirection::Rx, &response, "EMU");
-            if response[0] == 0x7F && response.len() >= 3 {
-                let nrc = crate::uds::error::NegativeResponseCode::from_byte(response[2]);
-                return Err(format!("NRC: {}", nrc));
-            }
-            return Ok(response);
-        }
-    }
-
     let max_busy_retries: u32 = 6;
 
     for busy_attempt in 0..=max_busy_retries {
         if busy_attempt > 0 {
             emit_log_simple(
-                app,
+                sink,
                 LogDirection::Tx,
                 &[],
                 &format!("Busy retry {}/{}", busy_attempt, max_busy_retries),
             );
         }
 
-        match send_uds_request_once(app, channel, tx_id, request, wait_pending, emulator) {
+        match send_uds_request_once(sink, channel, tx_id, request, wait_pending, emulator) {
             Ok(resp) => return Ok(resp),
             Err(e) if e.contains("0x21") && busy_attempt < max_busy_retries => {
                 std::thread::sleep(std::time::Duration::from_secs(1));
@@ -3326,23 +5666,23 @@ fn send_uds_request<R: tauri::Runtime>(
 }
 
 /// Single attempt to send a UDS request and wait for response.
-/// Also handles CAN bus emulation: if a request to an emulated ECU arrives
-/// while waiting (e.g. IMC asks GWM for CCF during 0x0E00), we respond
-/// immediately and continue waiting for the original response.
-fn send_uds_request_once<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn send_uds_request_once(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     tx_id: u32,
     request: &[u8],
     wait_pending: bool,
     emulator: Option<&EcuEmulatorManager>,
 ) -> Result<Vec<u8>, String> {
+    // Software routing: if the target ECU is emulated, talk to its bus instead,
+    // so injected faults go through the same handling as a real module's answers
+    let (channel, label): (&dyn crate::j2534::Channel, _) = match emulator {
+        Some(emu) if emu.emulates(tx_id) => (emu.bus(), "EMU"),
+        _ => (channel, ""),
+    };
     let msg = PassThruMsg::new_iso15765(tx_id, request);
     channel.send(&msg, 2000)?;
 
-    // Expected response CAN ID (ECU response address)
-    let expected_rx_id = tx_id + 8; // Standard: TX + 8 = RX (e.g. 0x7B3→0x7BB)
-
     let timeout = if wait_pending {
         std::time::Duration::from_secs(60)
     } else {
@@ -3364,37 +5704,8 @@ fn send_uds_request_once<R: tauri::Runtime>(
             if payload.is_empty() {
                 continue;
             }
-            let msg_can_id = m.can_id();
-
-            // CAN bus emulation: respond to requests from other ECUs (e.g. IMC→GWM)
-            if let Some(emu) = emulator {
-                if msg_can_id != expected_rx_id {
-                    if let Some((resp_can_id, resp_payload)) =
-                        emu.try_handle_bus_request(msg_can_id, payload)
-                    {
-                        emit_log_simple(
-                            app,
-                            LogDirection::Rx,
-                            payload,
-                            &format!("BUS→EMU 0x{:03X}", msg_can_id),
-                        );
-                        let resp_msg = PassThruMsg::new_iso15765(resp_can_id, &resp_payload);
-                        if let Err(e) = channel.send(&resp_msg, 2000) {
-                            log::warn!("EMU response send failed: {}", e);
-                        } else {
-                            emit_log_simple(
-                                app,
-                                LogDirection::Tx,
-                                &resp_payload,
-                                &format!("EMU→BUS 0x{:03X}", resp_can_id),
-                            );
-                        }
-                        continue;
-                    }
-                }
-            }
 
-            emit_log_simple(app, LogDirection::Rx, payload, "");
+            emit_log_simple(sink, LogDirection::Rx, payload, label);
 
             // Negative response
             if payload[0] == 0x7F && payload.len() >= 3 {
@@ -3402,7 +5713,7 @@ fn send_uds_request_once<R: tauri::Runtime>(
                 // requests (e.g. 7F 10 12 from a session change) must be ignored
                 if payload[1] != request[0] {
                     emit_log_simple(
-                        app,
+                        sink,
                         LogDirection::Rx,
                         &[],
                         &format!(
@@ -3413,7 +5724,7 @@ fn send_uds_request_once<R: tauri::Runtime>(
                     continue;
                 }
                 if payload[2] == 0x78 {
-                    emit_log_simple(app, LogDirection::Pending, payload, "Response pending...");
+                    emit_log_simple(sink, LogDirection::Pending, payload, "Response pending...");
                     continue;
                 }
                 let nrc = crate::uds::error::NegativeResponseCode::from_byte(payload[2]);
@@ -3432,8 +5743,8 @@ fn send_uds_request_once<R: tauri::Runtime>(
 }
 
 /// Read a DID on a channel, with optional emulator bypass
-fn send_read_did<R: tauri::Runtime>(
-    app: &tauri::AppHandle<R>,
+fn send_read_did(
+    sink: &dyn LogSink,
     channel: &dyn crate::j2534::Channel,
     tx_id: u32,
     did_id: u16,
@@ -3446,8 +5757,8 @@ fn send_read_did<R: tauri::Runtime>(
     } else {
         format!("ReadDID {} ({:04X})", name, did_id)
     };
-    emit_log_simple(app, LogDirection::Tx, &request, &label);
-    let resp = send_uds_request(app, channel, tx_id, &request, false, emulator)?;
+    emit_log_simple(sink, LogDirection::Tx, &request, &label);
+    let resp = send_uds_request(sink, channel, tx_id, &request, false, emulator)?;
     // Return data after service ID + DID
     if resp.len() > 3 {
         Ok(resp[3..].to_vec())
@@ -3503,6 +5814,575 @@ mod tests {
         mock
     }
 
+    // ─── Simulated vehicle tests ────────────────────────────────────
+
+    fn simulated_state() -> AppState {
+        let state = AppState::new();
+        connect_emulated_inner(&crate::sink::NullSink, &state, EcuId::all(), None).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_simulated_imc_info() {
+        let state = simulated_state();
+        let entries = read_ecu_info_inner(&crate::sink::NullSink, &state, "imc").unwrap();
+        let value = |label: &str| {
+            entries
+                .iter()
+                .find(|e| e.label == label)
+                .and_then(|e| e.value.clone())
+                .unwrap_or_default()
+        };
+        assert!(value("VIN").starts_with("SAJBL4BVXG"));
+        assert_eq!(value("IMC Status"), "Normal (0x00)");
+        assert!(value("Diag Session").starts_with("Default"));
+    }
+
+    #[test]
+    fn test_simulated_secured_routine() {
+        let state = simulated_state();
+        let resp = run_routine_inner(&crate::sink::NullSink, &state, routine::SSH_ENABLE, &[0x01])
+            .unwrap();
+        assert!(resp.success);
+        assert_eq!(resp.raw_data, vec![0x22]);
+
+        let resp = run_routine_inner(&crate::sink::NullSink, &state, routine::CONFIGURE_LINUX, &[])
+            .unwrap();
+        assert_eq!(resp.raw_data, vec![0x20, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_simulated_read_ccf_after_transfer() {
+        let state = simulated_state();
+        let entries = read_ccf_inner(&crate::sink::NullSink, &state).unwrap();
+        assert!(entries.len() > 1, "expected decoded CCF, got {:?}", entries);
+        assert!(entries.iter().all(|e| e.error.is_none()));
+    }
+
+    // ─── Restore CCF rehearsal tests ────────────────────────────────
+
+    const FAST_RESTORE: RestoreCcfTiming = RestoreCcfTiming {
+        prepare_wait: std::time::Duration::from_millis(50),
+        poll_interval: std::time::Duration::from_millis(50),
+        configure_wait: std::time::Duration::from_millis(150),
+        reboot_wait: std::time::Duration::from_millis(150),
+    };
+
+    /// Simulated vehicle whose IMC runs its CCF routines in milliseconds
+    fn rehearsal_state(outcome: crate::ecu_emulator::ImcOutcome) -> AppState {
+        rehearsal_state_with(outcome, EcuId::all())
+    }
+
+    /// Connection to a bus carrying only `ecus`, with a fast IMC among them
+    fn rehearsal_state_with(outcome: crate::ecu_emulator::ImcOutcome, ecus: &[EcuId]) -> AppState {
+        use crate::ecu_emulator::{ImcHandler, ImcTiming};
+        let timing = ImcTiming {
+            prepare: std::time::Duration::from_millis(30),
+            transfer: std::time::Duration::from_millis(60),
+            configure: std::time::Duration::from_millis(60),
+            reboot: std::time::Duration::from_millis(80),
+        };
+        let imc = ImcHandler::new().with_timing(timing).with_outcome(outcome);
+        let bus = EmulatedBus::new(ecus)
+            .with_handler(EcuId::Imc, stateful(EcuId::Imc, Box::new(imc)));
+        let state = AppState::new();
+        connect_bus_inner(&crate::sink::NullSink, &state, bus).unwrap();
+        state
+    }
+
+    fn rehearse(state: &AppState) -> Result<RestoreCcfResult, String> {
+        let job = JobContext::detached();
+        restore_ccf_timed(&crate::sink::NullSink, state, false, &job, &FAST_RESTORE)
+    }
+
+    #[test]
+    fn test_restore_ccf_rehearsal() {
+        let state = rehearsal_state(crate::ecu_emulator::ImcOutcome::Success);
+        let result = rehearse(&state).unwrap();
+        assert!(result.success, "{:?}", result.steps);
+        let names: Vec<&str> = result.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "0x0E08 Prepare",
+                "0x0E06 Transfer",
+                "0x6038 Configure Linux",
+                "0x6038 Results",
+                "ECU Reset"
+            ]
+        );
+        assert!(result.steps[3].detail.contains("Completed"));
+        // The bench car passes the pre-flight rules
+        let pre = result.pre_flight.unwrap();
+        assert!(pre.warnings.is_empty(), "{:?}", pre.warnings);
+        assert!(pre.findings.is_empty(), "{:?}", pre.findings);
+        let mid = result.mid_flight.unwrap();
+        // As on the car, the IMC has no CCF read-back to check mid-flight
+        assert!(mid.imc_ccf_0e01_hex.is_none());
+        assert!(result.post_flight.unwrap().imc_responsive);
+    }
+
+    #[test]
+    fn test_restore_ccf_rehearsal_on_bench_imc() {
+        // Bench IMC alone on the bus: its GWM CCF fetch is answered by bench mode
+        let state = rehearsal_state_with(crate::ecu_emulator::ImcOutcome::Success, &[EcuId::Imc]);
+        let traffic = Arc::new(crate::sink::MemorySink::new());
+        state.set_background_sink(traffic.clone());
+        let ecus = ["gwm", "bcm", "ipc"].map(String::from).to_vec();
+        toggle_bench_mode_inner(&crate::sink::NullSink, &state, true, Some(ecus), None, None)
+            .unwrap();
+
+        let result = rehearse(&state).unwrap();
+        assert!(result.success, "{:?}", result.steps);
+        let logged: Vec<String> = traffic.entries().into_iter().map(|e| e.description).collect();
+        assert!(logged.contains(&"BUS→EMU 0x716".to_string()), "{:?}", logged);
+        assert!(logged.contains(&"EMU→BUS 0x71E".to_string()), "{:?}", logged);
+
+        // Without the responder the IMC never gets the GWM's CCF
+        bus_handles(&state).unwrap().emulator.unwrap().detach();
+        let err = rehearse(&state).unwrap_err();
+        assert!(err.contains("additional=0x01"), "{}", err);
+    }
+
+    #[test]
+    fn test_restore_ccf_rehearsal_failures() {
+        use crate::ecu_emulator::ImcOutcome;
+        let err = rehearse(&rehearsal_state(ImcOutcome::TransferFails(0x05))).unwrap_err();
+        assert!(err.contains("additional=0x05"), "{}", err);
+
+        let err = rehearse(&rehearsal_state(ImcOutcome::ConfigureFails(0x04))).unwrap_err();
+        assert!(err.contains("0x6038 did not complete"), "{}", err);
+        assert!(err.contains("Start-up configuration XML"), "{}", err);
+
+        // On a car the SDD sequence never asks for the results, so the reset still runs
+        let state = rehearsal_state(ImcOutcome::ConfigureFails(0x04));
+        state.connection.lock().unwrap().as_mut().unwrap().dll_path = "car.dll".into();
+        let result = rehearse(&state).unwrap();
+        let names: Vec<&str> = result.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["0x0E08 Prepare", "0x0E06 Transfer", "0x6038 Configure Linux", "ECU Reset"]
+        );
+    }
+
+    #[test]
+    fn test_option_467_summary_uses_decode_table() {
+        let state = AppState::new();
+        let mut ccf = Ccf::parse(include_bytes!("../assets/gwm_ccf.bin")).unwrap();
+        let summary = option_467_summary(&state, &ccf).unwrap();
+        assert_eq!(summary, "raw=0x14, extracted=0x04 (10_INCH_SINGLE_VIEW (0x04))");
+        ccf.set_field(CcfTable::builtin().field(467), 0x03).unwrap();
+        let summary = option_467_summary(&state, &ccf).unwrap();
+        assert!(summary.ends_with("(8_INCH_DUAL_VIEW (0x03))"), "{}", summary);
+    }
+
+    // ─── CCF editor tests ───────────────────────────────────────────
+
+    fn ccf_edit(option: &str, value: &str) -> CcfEdit {
+        CcfEdit { option: option.into(), value: value.into() }
+    }
+
+    #[test]
+    fn test_ccf_edit_resolution() {
+        let gwm = Ccf::parse(include_bytes!("../assets/gwm_ccf.bin")).unwrap();
+        let table = CcfTable::builtin();
+        let edits = [ccf_edit("Front Display Variant", "10_inch_dual_view")];
+        let edited = apply_ccf_edits(&table, &gwm, &edits).unwrap();
+        // Only the low nibble of 0x14 belongs to option 467
+        assert_eq!(edited.option(467), Some(0x15));
+        let changes = table.diff(&gwm, &edited);
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].option_id, changes[0].raw_before), (467, 0x14));
+        assert_eq!(changes[0].after, "10_INCH_DUAL_VIEW (0x05)");
+
+        let err = apply_ccf_edits(&table, &gwm, &[ccf_edit("467", "9")]).unwrap_err();
+        assert!(err.contains("4=10_INCH_SINGLE_VIEW"), "{}", err);
+        assert!(apply_ccf_edits(&table, &gwm, &[ccf_edit("Warp Drive", "1")]).is_err());
+        assert!(apply_ccf_edits(&table, &gwm, &[]).is_err());
+    }
+
+    #[test]
+    fn test_simulated_ccf_write_and_undo() {
+        let state = simulated_state();
+        let sink = &crate::sink::NullSink;
+        let edits = [ccf_edit("467", "5")];
+
+        let preview = preview_ccf_edit_inner(sink, &state, "gwm", &edits).unwrap();
+        assert_eq!(preview.changes.len(), 1);
+        assert!(!preview.written && preview.backup.is_none());
+        assert_eq!(preview.vin.as_deref(), Some("SAJBL4BVXGCY16353"));
+
+        let written = write_ccf_edit_inner(sink, &state, "gwm", &edits, false).unwrap();
+        assert!(written.written);
+        let backup = written.backup.unwrap();
+        assert_ne!(written.checksum_after, written.checksum_before);
+        // The emulated GWM now holds the edit, so there is nothing left to write
+        let again = write_ccf_edit_inner(sink, &state, "gwm", &edits, false).unwrap();
+        assert!(again.changes.is_empty() && !again.written);
+
+        let undo = undo_ccf_edit_inner(sink, &state, &backup, false).unwrap();
+        assert!(undo.written);
+        assert_eq!((undo.changes[0].raw_before, undo.changes[0].raw_after), (0x15, 0x14));
+        let restored = preview_ccf_edit_inner(sink, &state, "gwm", &edits).unwrap();
+        assert_eq!(restored.checksum_before, written.checksum_before);
+
+        // The BCM's 0xDE00 read is capped short of the image: no CRC to recompute
+        let err = write_ccf_edit_inner(sink, &state, "bcm", &edits, false).unwrap_err();
+        assert!(err.contains("truncated"), "{}", err);
+        assert!(preview_ccf_edit_inner(sink, &state, "ipc", &edits).is_err());
+
+        for path in [Some(backup), undo.backup].into_iter().flatten() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_ccf_write_never_guesses_a_security_key() {
+        use crate::ecu_emulator::{create_raw_handler, EcuPolicy, SecurityLevel, StatefulEcu};
+        // A GWM that does want a level 0x01 key, with constants nobody has confirmed
+        let policy = EcuPolicy::new(vec![SecurityLevel {
+            seed_sub: 0x01,
+            sessions: &[0x03],
+            constants: [0x12, 0x34, 0x56, 0x78, 0x9A],
+        }]);
+        let gwm = StatefulEcu::new(create_raw_handler(EcuId::Gwm), policy);
+        let bus = EmulatedBus::new(&[EcuId::Gwm]).with_handler(EcuId::Gwm, Box::new(gwm));
+        let state = AppState::new();
+        connect_bus_inner(&crate::sink::NullSink, &state, bus).unwrap();
+
+        let edits = [ccf_edit("467", "5")];
+        let sink = &crate::sink::NullSink;
+        let err = write_ccf_edit_inner(sink, &state, "gwm", &edits, false).unwrap_err();
+        assert!(err.contains("not sending a guessed key"), "{}", err);
+    }
+
+    #[test]
+    fn test_simulated_ccf_rules() {
+        let mut state = simulated_state();
+        let dir = std::env::temp_dir().join(format!("udsapp_rules_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        state.archive = VehicleArchive::new(dir.join("archive"));
+        state.ccf_rules = crate::ccf::CcfRuleFile::new(dir.join("ccf_rules.json"));
+        let sink = &crate::sink::NullSink;
+        let rule_ids = |findings: &[RuleFinding]| -> Vec<String> {
+            findings.iter().map(|f| f.rule.clone()).collect()
+        };
+
+        let check = check_ccf_inner(sink, &state).unwrap();
+        assert_eq!(check.rules, "built-in");
+        assert_eq!(check.vin.as_deref(), Some("SAJBL4BVXGCY16353"));
+        assert!(check.report.findings.is_empty(), "{:?}", check.report.findings);
+        assert!(check.report.skipped.is_empty());
+
+        // Taking out the AVIO panel breaks an error rule: refused unless forced
+        let no_avio = [ccf_edit("468", "1")];
+        let err = write_ccf_edit_inner(sink, &state, "gwm", &no_avio, false).unwrap_err();
+        assert!(err.contains("display-needs-avio-panel"), "{}", err);
+        let forced = write_ccf_edit_inner(sink, &state, "gwm", &no_avio, true).unwrap();
+        assert!(forced.written);
+        assert_eq!(rule_ids(&forced.findings), ["display-needs-avio-panel"]);
+        let backup = forced.backup.unwrap();
+        // Undoing it is fine, the backup breaks no rule
+        let undo = undo_ccf_edit_inner(sink, &state, &backup, false).unwrap();
+        assert!(undo.written && undo.findings.is_empty());
+
+        // An 8" display on the GWM only warns in the editor…
+        let edits = [ccf_edit("467", "2")];
+        let written = write_ccf_edit_inner(sink, &state, "gwm", &edits, false).unwrap();
+        assert!(written.written);
+        assert_eq!(rule_ids(&written.findings), ["display-10-inch"]);
+        // …and, with the BCM still on 10", the GWM/BCM match in the comparison
+        let entries = compare_ccf_inner(sink, &state).unwrap();
+        let display = entries.iter().find(|e| e.option_id == 467).unwrap();
+        assert!(display.mismatch);
+        assert_eq!(
+            rule_ids(&display.findings),
+            ["gwm-bcm-infotainment-match", "display-10-inch"]
+        );
+        assert!(entries.iter().find(|e| e.option_id == 468).unwrap().findings.is_empty());
+
+        // A rules file edited by hand with a mistyped label: the rule is skipped,
+        // not passed, and being an error rule it blocks writes
+        let typo = r#"[{"id": "typo", "severity": "error", "description": "d", "explanation": "e",
+                        "require": [{"check": "option_not_in", "option": 468,
+                                     "values": ["NOT_FITED"]}]}]"#;
+        std::fs::write(dir.join("ccf_rules.json"), typo).unwrap();
+        let check = check_ccf_inner(sink, &state).unwrap();
+        assert_eq!((check.report.passed, check.report.skipped[0].rule.as_str()), (0, "typo"));
+        let err = write_ccf_edit_inner(sink, &state, "gwm", &[ccf_edit("467", "5")], false)
+            .unwrap_err();
+        assert!(err.contains("typo") && err.contains("can't be checked"), "{}", err);
+
+        for path in [Some(backup), undo.backup, written.backup].into_iter().flatten() {
+            let _ = std::fs::remove_file(path);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_simulated_archive_diff_and_rollback() {
+        let mut state = simulated_state();
+        let dir = std::env::temp_dir().join(format!("udsapp_archive_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        state.archive = VehicleArchive::new(dir.clone());
+        let sink = &crate::sink::NullSink;
+        let edits = [ccf_edit("467", "5")];
+
+        // Identification carries the VIN, so the DTCs read after it land on the same car
+        read_ecu_info_inner(sink, &state, "bcm").unwrap();
+        read_dtc_inner(sink, &state, "bcm", DTC_STATUS_ALL).unwrap();
+        preview_ccf_edit_inner(sink, &state, "gwm", &edits).unwrap();
+        let written = write_ccf_edit_inner(sink, &state, "gwm", &edits, false).unwrap();
+
+        let history = state.archive.history("SAJBL4BVXGCY16353");
+        let kinds: Vec<&str> = history.iter().map(|s| s.kind.as_str()).collect();
+        assert_eq!(kinds, ["identification", "dtcs", "ccf", "ccf"]);
+        let (original, edited) = (&history[2].id, &history[3].id);
+        let diff = diff_snapshots_inner(&state, original, edited).unwrap();
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].key, "467");
+        assert!(diff_snapshots_inner(&state, &history[0].id, edited).is_err());
+
+        let rollback = rollback_ccf_inner(sink, &state, original, false).unwrap();
+        assert!(rollback.written);
+        assert_eq!(rollback.changes[0].option_id, 467);
+        let restored = preview_ccf_edit_inner(sink, &state, "gwm", &edits).unwrap();
+        assert_eq!(restored.checksum_before, written.checksum_before);
+        assert!(rollback_ccf_inner(sink, &state, &history[1].id, false).is_err());
+        // The BCM's capped read is archived, but can't be rolled back to
+        assert!(preview_ccf_edit_inner(sink, &state, "bcm", &edits).is_err());
+        let bcm = state.archive.history("SAJBL4BVXGCY16353").into_iter().last().unwrap();
+        assert_eq!((bcm.ecu.as_str(), bcm.kind.as_str()), ("bcm", "ccf"));
+        let err = rollback_ccf_inner(sink, &state, &bcm.id, false).unwrap_err();
+        assert!(err.contains("truncated"), "{}", err);
+
+        for path in [written.backup, rollback.backup].into_iter().flatten() {
+            let _ = std::fs::remove_file(path);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // ─── Fault injection tests ──────────────────────────────────────
+
+    fn faulty_bcm(rules: Vec<crate::ecu_emulator::FaultRule>) -> EcuEmulatorManager {
+        EcuEmulatorManager::new(vec![EcuId::Bcm])
+            .with_faults(&FaultScenario::new("test", rules))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_faults_pending_and_stale_nrc_are_waited_out() {
+        use crate::ecu_emulator::{Fault, FaultRule};
+        let mock = setup_mock_channel();
+        let emu = faulty_bcm(vec![
+            FaultRule::new(Fault::StaleNrc { service: 0x10, nrc: 0x12 }),
+            FaultRule::new(Fault::Pending { count: 3, interval_ms: 50 }),
+        ]);
+        let sink = crate::sink::NullSink;
+        let vin = send_read_did(&sink, &mock, ecu_addr::BCM_TX, 0xF190, Some(&emu)).unwrap();
+        assert!(vin.starts_with(b"SAJ"));
+    }
+
+    #[test]
+    fn test_faults_busy_is_retried() {
+        use crate::ecu_emulator::{Fault, FaultRule};
+        let mock = setup_mock_channel();
+        let emu = faulty_bcm(vec![FaultRule::new(Fault::Busy).every(2)]);
+        let request = [0x22, 0xF1, 0x90];
+        for _ in 0..2 {
+            let sink = crate::sink::NullSink;
+            let resp = send_uds_request(&sink, &mock, ecu_addr::BCM_TX, &request, false, Some(&emu));
+            assert_eq!(resp.unwrap()[0], 0x62);
+        }
+    }
+
+    #[test]
+    fn test_faults_dropped_response_times_out() {
+        use crate::ecu_emulator::{Fault, FaultRule};
+        let mock = setup_mock_channel();
+        let emu = faulty_bcm(vec![FaultRule::new(Fault::Drop)]);
+        let sink = crate::sink::NullSink;
+        let resp = send_uds_request(&sink, &mock, ecu_addr::BCM_TX, &[0x3E, 0x00], false, Some(&emu));
+        assert_eq!(resp.unwrap_err(), "Timeout waiting for response");
+    }
+
+    #[test]
+    fn test_bench_mode_faults_selection() {
+        let state = simulated_state();
+        let sink = crate::sink::NullSink;
+        let ecus = Some(vec!["bcm".to_string()]);
+        let bad = toggle_bench_mode_inner(&sink, &state, true, ecus.clone(), Some("nope"), None);
+        assert!(bad.is_err());
+        toggle_bench_mode_inner(&sink, &state, true, ecus, Some("stale-nrc"), None).unwrap();
+        let status = get_bench_mode_status_inner(&state).unwrap();
+        assert_eq!(status.faults.as_deref(), Some("stale-nrc"));
+
+        let entries = read_ecu_info_inner(&sink, &state, "bcm").unwrap();
+        assert!(entries[0].value.as_deref().unwrap_or_default().starts_with("SAJ"));
+    }
+
+    #[test]
+    fn test_set_vehicle_state() {
+        let state = simulated_state();
+        let sink = crate::sink::NullSink;
+        assert_eq!(set_vehicle_state_inner(&sink, &state, "on").unwrap_err(), "Bench mode is off");
+
+        toggle_bench_mode_inner(&sink, &state, true, None, None, None).unwrap();
+        let status = get_bench_mode_status_inner(&state).unwrap();
+        assert_eq!(status.vehicle_state.as_deref(), Some("on"));
+        set_vehicle_state_inner(&sink, &state, "accessory").unwrap();
+        assert!(set_vehicle_state_inner(&sink, &state, "run").is_err());
+        let status = get_bench_mode_status_inner(&state).unwrap();
+        assert_eq!(status.vehicle_state.as_deref(), Some("accessory"));
+    }
+
+    #[test]
+    fn test_replay_can_log_needs_adapter() {
+        let state = simulated_state();
+        let sink = crate::sink::NullSink;
+        let job = JobContext::detached();
+        let path = std::env::temp_dir().join(format!("replay_test_{}.txt", std::process::id()));
+        std::fs::write(&path, "0.000100 0x400 [2] 01 02 RxStat=0x00000000\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let bad_speed = ReplayOptions {
+            speed: -1.0,
+            ..Default::default()
+        };
+        assert!(replay_can_log_inner(&sink, &state, &path, &bad_speed, &job).is_err());
+        let err =
+            replay_can_log_inner(&sink, &state, &path, &ReplayOptions::default(), &job).unwrap_err();
+        assert!(err.contains("No local J2534 adapter"), "{}", err);
+        // The ISO15765 channel is untouched
+        assert!(read_did_inner(&sink, &state, ecu_addr::BCM_TX, 0xF190).is_ok());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_can_monitor_state_and_save() {
+        let state = simulated_state();
+        let sink = crate::sink::NullSink;
+        let job = JobContext::detached();
+        assert!(get_can_monitor_inner(&state).is_none());
+        assert!(stop_can_monitor_inner(&sink, &state).is_err());
+
+        let err = monitor_can_inner(&sink, &state, &MonitorOptions::default(), &job).unwrap_err();
+        assert!(err.contains("No local J2534 adapter"), "{}", err);
+        // The monitor is marked stopped and the ISO15765 channel is untouched
+        assert_eq!(get_can_monitor_inner(&state).unwrap().job_id, None);
+        assert!(read_did_inner(&sink, &state, ecu_addr::BCM_TX, 0xF190).is_ok());
+        let path = std::env::temp_dir().join(format!("monitor_test_{}.asc", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        assert!(save_can_monitor_inner(&sink, &state, &path, None).unwrap_err().contains("empty"));
+
+        // A running monitor refuses a second one; its buffer saves at any time
+        let mut monitor = BusMonitor::new(MonitorOptions::default(), Some(7));
+        monitor.record(CanFrame::new(5_000, 0x400, vec![0x08, 0x01]));
+        monitor.record(CanFrame::new(25_000, 0x230, vec![0x60]));
+        *state.can_monitor.lock().unwrap() = Some(monitor);
+        assert!(monitor_can_inner(&sink, &state, &MonitorOptions::default(), &job).is_err());
+        let info = save_can_monitor_inner(&sink, &state, &path, None).unwrap();
+        assert_eq!(info.format, CaptureFormat::Asc);
+        assert_eq!((info.frames, info.unique_ids, info.duration_ms), (2, 2, 20));
+        let back = canlog::load(std::path::Path::new(&path)).unwrap();
+        assert_eq!(back.frames[1].can_id, 0x230);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_sniff_action_needs_adapter() {
+        let state = simulated_state();
+        let sink = crate::sink::NullSink;
+        let job = JobContext::detached();
+        let reset = SniffAction::Reset { reset_type: 0x01 };
+        let options = SniffOptions::default();
+        let err = sniff_action_inner(&sink, &state, "abs", &reset, &options, &job).unwrap_err();
+        assert!(err.contains("Unknown ECU"), "{}", err);
+        let err = sniff_action_inner(&sink, &state, "0x726", &reset, &options, &job).unwrap_err();
+        assert!(err.contains("No local J2534 adapter"), "{}", err);
+        // Checked before the ISO15765 channel is given up
+        assert!(read_did_inner(&sink, &state, ecu_addr::BCM_TX, 0xF190).is_ok());
+    }
+
+    #[test]
+    fn test_convert_can_log() {
+        let sink = crate::sink::NullSink;
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("convert_test_{}.txt", std::process::id()));
+        let output = dir.join(format!("convert_test_{}.blf", std::process::id()));
+        std::fs::write(
+            &input,
+            "# CAN Dump\n0.100000 0x400 [2] 01 02 RxStat=0x00000000\n\
+             0.350000 0x407 [1] 03 RxStat=0x00000000\n",
+        )
+        .unwrap();
+        let (input, output) = (input.to_string_lossy(), output.to_string_lossy());
+
+        let info = convert_can_log_inner(&sink, &input, &output, None).unwrap();
+        assert_eq!(info.format, CaptureFormat::Blf);
+        assert_eq!(info.source_format, CaptureFormat::DumpTxt);
+        assert_eq!((info.frames, info.unique_ids, info.duration_ms), (2, 2, 350));
+        let back = canlog::load(std::path::Path::new(output.as_ref())).unwrap();
+        assert_eq!(back.frames[1].timestamp_us, 350_000);
+
+        assert!(convert_can_log_inner(&sink, &input, &output, Some("monitor")).is_err());
+        assert!(convert_can_log_inner(&sink, &input, "out.unknown", None).is_err());
+        let _ = std::fs::remove_file(input.as_ref());
+        let _ = std::fs::remove_file(output.as_ref());
+    }
+
+    #[test]
+    fn test_decode_can_frames_with_dbc() {
+        let sink = crate::sink::NullSink;
+        let dbc = std::env::temp_dir().join(format!("decode_test_{}.dbc", std::process::id()));
+        std::fs::write(
+            &dbc,
+            "BO_ 1024 BCM_NM: 8 BCM\n SG_ Awake : 0|4@1+ (1,0) [0|15] \"\" IMC\n",
+        )
+        .unwrap();
+        let dbc = dbc.to_string_lossy();
+        let frames = sniff_entries(&[
+            CanFrame::new(0, 0x400, vec![0x08, 0x01]),
+            CanFrame::new(20_000, 0x407, vec![0x0A]),
+        ]);
+        let report = decode_can_frames_inner(&sink, &dbc, &frames).unwrap();
+        assert_eq!((report.frames, report.decoded), (2, 1));
+        assert_eq!(report.unknown_ids, vec!["0x407"]);
+        assert_eq!(report.messages[0].message, "BCM_NM");
+        assert_eq!(report.messages[0].signals[0].raw, 8);
+
+        let mut bad = frames.clone();
+        bad[0].data_hex = "ZZ".into();
+        assert!(decode_can_frames_inner(&sink, &dbc, &bad).is_err());
+        assert!(decode_can_log_inner(&sink, &dbc, "/nonexistent.log").is_err());
+        let _ = std::fs::remove_file(dbc.as_ref());
+    }
+
+    #[test]
+    fn test_analyze_can_frames_writes_draft_dbc() {
+        let sink = crate::sink::NullSink;
+        let out = std::env::temp_dir().join(format!("draft_test_{}.dbc", std::process::id()));
+        let out = out.to_string_lossy().to_string();
+        // 0x0B0: byte 0 counts 0..15, byte 1 flips at 200 ms
+        let frames: Vec<CanFrame> = (0..40u64)
+            .map(|i| CanFrame::new(i * 10_000, 0x0B0, vec![(i % 16) as u8, (i >= 20) as u8]))
+            .collect();
+        let events = [CanEvent {
+            time_ms: 200,
+            label: "door open".into(),
+        }];
+        let options = AnalysisOptions::default();
+        let report =
+            analyze_can_frames_inner(&sink, &sniff_entries(&frames), &events, &options, Some(&out))
+                .unwrap();
+        assert_eq!(report.analysis.ids[0].counters.len(), 1);
+        assert_eq!(report.analysis.ids[0].events[0].event, "door open");
+        let draft = Dbc::load(std::path::Path::new(&out)).unwrap();
+        assert_eq!(draft.write(), report.dbc);
+        assert_eq!(draft.find("0x0B0").unwrap().signals.len(), 2);
+        let _ = std::fs::remove_file(&out);
+    }
+
     // ─── BCM bench mode tests ───────────────────────────────────────
 
     #[test]
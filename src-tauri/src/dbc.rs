@@ -0,0 +1,900 @@
+//! Vector DBC databases: messages, signals, value tables and multiplexing.
+//!
+//! Decodes frames (from `capture_raw_can` or a loaded `canlog`) into named
+//! physical values, and encodes signal values back into payloads for the bench
+//! broadcast. Only plain multiplexing (one `M` switch per message) is
+//! supported; float signals (`SIG_VALTYPE_`) and attributes are ignored.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::canlog::CanFrame;
+
+/// Bit 31 of a `BO_` ID marks an extended (29-bit) frame
+const DBC_EXTENDED: u32 = 0x8000_0000;
+
+/// Placeholder message CANdb++ stores unassigned signals in
+const INDEPENDENT_SIGNALS: &str = "VECTOR__INDEPENDENT_SIG_MSG";
+
+// ─── Signal layout ───────────────────────────────────────────────────
+
+/// `@1` (Intel) or `@0` (Motorola)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// Where a signal's raw bits sit in the payload, numbered as in the DBC: the
+/// start bit is the LSB for little-endian signals and the MSB for big-endian ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BitField {
+    pub start_bit: u16,
+    pub length: u16,
+    pub byte_order: ByteOrder,
+}
+
+impl BitField {
+    /// Payload bit positions (byte * 8 + bit), most significant first
+    fn positions(&self) -> Vec<usize> {
+        let len = self.length as usize;
+        let start = self.start_bit as usize;
+        match self.byte_order {
+            ByteOrder::LittleEndian => (start..start + len).rev().collect(),
+            ByteOrder::BigEndian => {
+                let mut out = Vec::with_capacity(len);
+                let mut pos = start;
+                for _ in 0..len {
+                    out.push(pos);
+                    // Motorola bits run down within a byte, then on to the next byte's MSB
+                    pos = if pos.is_multiple_of(8) {
+                        pos + 15
+                    } else {
+                        pos - 1
+                    };
+                }
+                out
+            }
+        }
+    }
+
+    /// Unsigned raw bits; None if the payload is too short
+    pub fn extract(&self, data: &[u8]) -> Option<u64> {
+        self.positions().into_iter().try_fold(0u64, |raw, pos| {
+            let bit = (data.get(pos / 8)? >> (pos % 8)) & 1;
+            Some(raw << 1 | bit as u64)
+        })
+    }
+
+    /// Write the low `length` bits of `raw`
+    pub fn insert(&self, data: &mut [u8], raw: u64) -> Result<(), String> {
+        let positions = self.positions();
+        let len = positions.len();
+        let size = data.len();
+        for (i, pos) in positions.into_iter().enumerate() {
+            let byte = data
+                .get_mut(pos / 8)
+                .ok_or_else(|| format!("Bit {} is outside a {}-byte payload", pos, size))?;
+            let mask = 1u8 << (pos % 8);
+            if (raw >> (len - 1 - i)) & 1 != 0 {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+        Ok(())
+    }
+}
+
+// ─── Messages and signals ────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signal {
+    pub name: String,
+    #[serde(flatten)]
+    pub field: BitField,
+    pub signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+    pub unit: String,
+    pub receivers: Vec<String>,
+    /// This signal is the message's multiplexer switch (`M`)
+    pub multiplexor: bool,
+    /// Only present when the switch reads this value (`m<n>`)
+    pub mux_value: Option<u64>,
+    /// Value table (`VAL_`): raw value → label
+    pub values: BTreeMap<i64, String>,
+    pub comment: Option<String>,
+}
+
+/// A value to encode: physical, or a label from the signal's value table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SignalValue {
+    Number(f64),
+    Label(String),
+}
+
+impl Signal {
+    /// Raw value, sign-extended for signed signals
+    pub fn raw(&self, data: &[u8]) -> Option<i64> {
+        let bits = self.field.extract(data)?;
+        let len = self.field.length as u32;
+        if self.signed && len < 64 && bits >> (len - 1) & 1 != 0 {
+            Some((bits | (u64::MAX << len)) as i64)
+        } else {
+            Some(bits as i64)
+        }
+    }
+
+    pub fn physical(&self, raw: i64) -> f64 {
+        raw as f64 * self.factor + self.offset
+    }
+
+    /// Raw bits for `value`, checked against the signal's width
+    pub fn to_raw(&self, value: &SignalValue) -> Result<u64, String> {
+        let raw = match value {
+            SignalValue::Number(v) => ((v - self.offset) / self.factor).round(),
+            SignalValue::Label(label) => self
+                .values
+                .iter()
+                .find(|(_, l)| l.eq_ignore_ascii_case(label))
+                .map(|(raw, _)| *raw as f64)
+                .ok_or_else(|| format!("{}: no value labelled '{}'", self.name, label))?,
+        };
+        let len = self.field.length as i32;
+        let (lo, hi) = if self.signed {
+            (-(2f64.powi(len - 1)), 2f64.powi(len - 1) - 1.0)
+        } else {
+            (0.0, 2f64.powi(len) - 1.0)
+        };
+        if !(lo..=hi).contains(&raw) {
+            return Err(format!(
+                "{}: {:?} is out of range ({} to {})",
+                self.name,
+                value,
+                self.physical(lo as i64),
+                self.physical(hi as i64)
+            ));
+        }
+        let mask = if len >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << len) - 1
+        };
+        Ok(raw as i64 as u64 & mask)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    /// Arbitration ID without the DBC extended flag
+    pub id: u32,
+    pub extended: bool,
+    pub name: String,
+    /// DLC in bytes
+    pub size: usize,
+    pub sender: String,
+    pub signals: Vec<Signal>,
+    pub comment: Option<String>,
+}
+
+/// One decoded signal
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedSignal {
+    pub name: String,
+    pub raw: i64,
+    pub value: f64,
+    pub unit: String,
+    /// Value-table label for the raw value
+    pub label: Option<String>,
+}
+
+impl Message {
+    pub fn signal(&self, name: &str) -> Option<&Signal> {
+        self.signals.iter().find(|s| s.name == name)
+    }
+
+    fn switch(&self) -> Option<&Signal> {
+        self.signals
+            .iter()
+            .find(|s| s.multiplexor && s.mux_value.is_none())
+    }
+
+    /// Signals present in `data`: everything not multiplexed, plus the ones
+    /// selected by the switch value
+    pub fn decode(&self, data: &[u8]) -> Vec<DecodedSignal> {
+        let mux = self
+            .switch()
+            .and_then(|s| s.raw(data))
+            .map(|raw| raw as u64);
+        self.signals
+            .iter()
+            .filter(|s| s.mux_value.is_none() || s.mux_value == mux)
+            .filter_map(|s| {
+                let raw = s.raw(data)?;
+                Some(DecodedSignal {
+                    name: s.name.clone(),
+                    raw,
+                    value: s.physical(raw),
+                    unit: s.unit.clone(),
+                    label: s.values.get(&raw).cloned(),
+                })
+            })
+            .collect()
+    }
+
+    /// Write signal values into `data`; other bits are left alone
+    pub fn encode_into(
+        &self,
+        data: &mut [u8],
+        values: &BTreeMap<String, SignalValue>,
+    ) -> Result<(), String> {
+        for (name, value) in values {
+            let signal = self
+                .signal(name)
+                .ok_or_else(|| format!("{} has no signal '{}'", self.name, name))?;
+            signal.field.insert(data, signal.to_raw(value)?)?;
+        }
+        Ok(())
+    }
+
+    /// A `size`-byte payload with `values` set and every other bit clear
+    pub fn encode(&self, values: &BTreeMap<String, SignalValue>) -> Result<Vec<u8>, String> {
+        let mut data = vec![0u8; self.size];
+        self.encode_into(&mut data, values)?;
+        Ok(data)
+    }
+}
+
+// ─── Database ────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Dbc {
+    pub version: String,
+    pub nodes: Vec<String>,
+    pub messages: Vec<Message>,
+}
+
+/// A frame decoded against a database
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedFrame {
+    pub timestamp_us: u64,
+    pub can_id: u32,
+    pub message: String,
+    pub signals: Vec<DecodedSignal>,
+}
+
+impl Dbc {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        // DBC files are usually Windows-1252; non-ASCII only shows up in comments
+        Self::parse(&String::from_utf8_lossy(&bytes))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut dbc = Dbc::default();
+        for (line_no, statement) in statements(text) {
+            parse_statement(&mut dbc, &statement)
+                .map_err(|e| format!("line {}: {}", line_no, e))?;
+        }
+        Ok(dbc)
+    }
+
+    /// The message for a frame with `can_id`, 29-bit when `extended`
+    pub fn message(&self, can_id: u32, extended: bool) -> Option<&Message> {
+        self.messages
+            .iter()
+            .find(|m| m.id == can_id && m.extended == extended)
+    }
+
+    /// By name, or by ID (`0x230`, `560`; IDs above 0x7FF prefer 29-bit messages)
+    pub fn find(&self, name_or_id: &str) -> Option<&Message> {
+        self.messages
+            .iter()
+            .find(|m| m.name == name_or_id)
+            .or_else(|| {
+                let s = name_or_id.trim();
+                let id = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => s.parse().ok(),
+                };
+                id.and_then(|id| {
+                    self.message(id, id > 0x7FF)
+                        .or_else(|| self.messages.iter().find(|m| m.id == id))
+                })
+            })
+    }
+
+    /// None for IDs the database doesn't describe
+    pub fn decode(&self, frame: &CanFrame) -> Option<DecodedFrame> {
+        let message = self.message(frame.can_id, frame.is_extended())?;
+        Some(DecodedFrame {
+            timestamp_us: frame.timestamp_us,
+            can_id: frame.can_id,
+            message: message.name.clone(),
+            signals: message.decode(&frame.data),
+        })
+    }
+}
+
+// ─── Parser ──────────────────────────────────────────────────────────
+
+/// Whether `s` ends inside a string (`\"` doesn't close one)
+fn in_string(s: &str) -> bool {
+    let mut open = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => open = !open,
+            '\\' if open => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    open
+}
+
+/// Logical statements with their first line number: a line continues while it
+/// has an unterminated string (multi-line `CM_` comments)
+fn statements(text: &str) -> Vec<(usize, String)> {
+    let mut out = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (i, line) in text.lines().enumerate() {
+        match current.as_mut() {
+            Some((_, s)) => {
+                s.push('\n');
+                s.push_str(line);
+            }
+            None => current = Some((i + 1, line.to_string())),
+        }
+        if !current.as_ref().is_some_and(|(_, s)| in_string(s)) {
+            out.extend(current.take());
+        }
+    }
+    out.extend(current);
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(s: &str) -> Vec<Token> {
+    let mut out = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => text.extend(chars.next()),
+                    c => text.push(c),
+                }
+            }
+            out.push(Token::Str(text));
+        } else if ":|@()[],;".contains(c) {
+            chars.next();
+            out.push(Token::Punct(c));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' || ":|@()[],;".contains(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            out.push(Token::Word(word));
+        }
+    }
+    out
+}
+
+struct Cursor {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Cursor {
+    fn next(&mut self) -> Option<&Token> {
+        let t = self.tokens.get(self.pos);
+        self.pos += 1;
+        t
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(w)) => Ok(w.clone()),
+            other => Err(format!("expected a name or number, found {:?}", other)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(s.clone()),
+            other => Err(format!("expected a string, found {:?}", other)),
+        }
+    }
+
+    fn punct(&mut self, c: char) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Punct(p)) if *p == c => Ok(()),
+            other => Err(format!("expected '{}', found {:?}", c, other)),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        let w = self.word()?;
+        w.parse().map_err(|_| format!("bad number '{}'", w))
+    }
+
+    /// Remaining words up to an optional `;`
+    fn words(&mut self) -> Vec<String> {
+        let mut out = Vec::new();
+        while let Some(Token::Word(w)) = self.peek() {
+            out.push(w.clone());
+            self.pos += 1;
+            if self.peek() == Some(&Token::Punct(',')) {
+                self.pos += 1;
+            }
+        }
+        out
+    }
+}
+
+fn parse_statement(dbc: &mut Dbc, statement: &str) -> Result<(), String> {
+    let mut c = Cursor {
+        tokens: tokenize(statement),
+        pos: 0,
+    };
+    let keyword = match c.next() {
+        Some(Token::Word(w)) => w.clone(),
+        _ => return Ok(()),
+    };
+    match keyword.as_str() {
+        "VERSION" => dbc.version = c.string().unwrap_or_default(),
+        "BU_" => {
+            c.punct(':')?;
+            dbc.nodes = c.words();
+        }
+        "BO_" => {
+            let raw_id: u32 = c.number()?;
+            let name = c.word()?;
+            c.punct(':')?;
+            let size = c.number()?;
+            let sender = c.word().unwrap_or_default();
+            dbc.messages.push(Message {
+                id: raw_id & !DBC_EXTENDED,
+                extended: raw_id & DBC_EXTENDED != 0,
+                name,
+                size,
+                sender,
+                signals: Vec::new(),
+                comment: None,
+            });
+        }
+        "SG_" => {
+            let signal = parse_signal(&mut c)?;
+            dbc.messages
+                .last_mut()
+                .ok_or("SG_ before any BO_")?
+                .signals
+                .push(signal);
+        }
+        "CM_" => parse_comment(dbc, &mut c),
+        "VAL_" => parse_values(dbc, &mut c)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// `SG_ Name [M|m<n>] : start|len@order± (factor,offset) [min|max] "unit" receivers`
+fn parse_signal(c: &mut Cursor) -> Result<Signal, String> {
+    let name = c.word()?;
+    let mut multiplexor = false;
+    let mut mux_value = None;
+    if let Some(Token::Word(mux)) = c.peek().cloned() {
+        c.pos += 1;
+        if mux == "M" {
+            multiplexor = true;
+        } else {
+            let digits = mux
+                .strip_prefix('m')
+                .ok_or_else(|| format!("bad multiplex indicator '{}'", mux))?;
+            let digits = match digits.strip_suffix('M') {
+                Some(d) => {
+                    multiplexor = true;
+                    d
+                }
+                None => digits,
+            };
+            mux_value = Some(
+                digits
+                    .parse()
+                    .map_err(|_| format!("bad multiplex indicator '{}'", mux))?,
+            );
+        }
+    }
+    c.punct(':')?;
+    let start_bit = c.number()?;
+    c.punct('|')?;
+    let length: u16 = c.number()?;
+    c.punct('@')?;
+    let order = c.word()?;
+    let (byte_order, signed) = match order.as_str() {
+        "1+" => (ByteOrder::LittleEndian, false),
+        "1-" => (ByteOrder::LittleEndian, true),
+        "0+" => (ByteOrder::BigEndian, false),
+        "0-" => (ByteOrder::BigEndian, true),
+        _ => return Err(format!("{}: bad byte order/sign '@{}'", name, order)),
+    };
+    if !(1..=64).contains(&length) {
+        return Err(format!("{}: bad length {}", name, length));
+    }
+    c.punct('(')?;
+    let factor = c.number()?;
+    c.punct(',')?;
+    let offset = c.number()?;
+    c.punct(')')?;
+    c.punct('[')?;
+    let min = c.number()?;
+    c.punct('|')?;
+    let max = c.number()?;
+    c.punct(']')?;
+    let unit = c.string()?;
+    let receivers = c.words();
+    Ok(Signal {
+        name,
+        field: BitField {
+            start_bit,
+            length,
+            byte_order,
+        },
+        signed,
+        factor,
+        offset,
+        min,
+        max,
+        unit,
+        receivers,
+        multiplexor,
+        mux_value,
+        values: BTreeMap::new(),
+        comment: None,
+    })
+}
+
+fn find_message(dbc: &mut Dbc, raw_id: u32) -> Option<&mut Message> {
+    let id = raw_id & !DBC_EXTENDED;
+    dbc.messages
+        .iter_mut()
+        .find(|m| m.id == id && m.name != INDEPENDENT_SIGNALS)
+}
+
+/// `CM_ BO_ <id> "…";` and `CM_ SG_ <id> <signal> "…";` (others are ignored)
+fn parse_comment(dbc: &mut Dbc, c: &mut Cursor) {
+    let target = match c.next() {
+        Some(Token::Word(w)) => w.clone(),
+        _ => return,
+    };
+    let Ok(id) = c.number::<u32>() else {
+        return;
+    };
+    let Some(message) = find_message(dbc, id) else {
+        return;
+    };
+    match target.as_str() {
+        "BO_" => message.comment = c.string().ok(),
+        "SG_" => {
+            let (Ok(name), Ok(text)) = (c.word(), c.string()) else {
+                return;
+            };
+            if let Some(s) = message.signals.iter_mut().find(|s| s.name == name) {
+                s.comment = Some(text);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `VAL_ <id> <signal> 0 "Off" 1 "On" ;`
+fn parse_values(dbc: &mut Dbc, c: &mut Cursor) -> Result<(), String> {
+    // Named tables (`VAL_TABLE_`) and environment variables have no numeric ID
+    let Ok(id) = c.number::<u32>() else {
+        return Ok(());
+    };
+    let name = c.word()?;
+    let mut values = BTreeMap::new();
+    while let Some(Token::Word(_)) = c.peek() {
+        let raw: i64 = c.number()?;
+        values.insert(raw, c.string()?);
+    }
+    if let Some(signal) =
+        find_message(dbc, id).and_then(|m| m.signals.iter_mut().find(|s| s.name == name))
+    {
+        signal.values = values;
+    }
+    Ok(())
+}
+
+// ─── Writer ──────────────────────────────────────────────────────────
+
+/// Node name CANdb++ uses for "no sender/receiver"
+const NO_NODE: &str = "Vector__XXX";
+
+/// A quoted DBC string, with `\` and `"` escaped
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl Dbc {
+    /// The database as DBC text (what `parse` reads back)
+    pub fn write(&self) -> String {
+        let mut out = format!("VERSION {}\n\nNS_ :\n\nBS_:\n\n", quoted(&self.version));
+        out.push_str(&format!("BU_: {}\n", self.nodes.join(" ")));
+        let raw_id = |m: &Message| m.id | if m.extended { DBC_EXTENDED } else { 0 };
+        let node = |s: &str| {
+            if s.is_empty() {
+                NO_NODE.to_string()
+            } else {
+                s.to_string()
+            }
+        };
+
+        for m in &self.messages {
+            out.push_str(&format!(
+                "\nBO_ {} {}: {} {}\n",
+                raw_id(m),
+                m.name,
+                m.size,
+                node(&m.sender)
+            ));
+            for sig in &m.signals {
+                let mux = match (sig.multiplexor, sig.mux_value) {
+                    (true, None) => " M".to_string(),
+                    (true, Some(v)) => format!(" m{}M", v),
+                    (false, Some(v)) => format!(" m{}", v),
+                    (false, None) => String::new(),
+                };
+                let order = match sig.field.byte_order {
+                    ByteOrder::LittleEndian => '1',
+                    ByteOrder::BigEndian => '0',
+                };
+                out.push_str(&format!(
+                    " SG_ {}{} : {}|{}@{}{} ({},{}) [{}|{}] {} {}\n",
+                    sig.name,
+                    mux,
+                    sig.field.start_bit,
+                    sig.field.length,
+                    order,
+                    if sig.signed { '-' } else { '+' },
+                    sig.factor,
+                    sig.offset,
+                    sig.min,
+                    sig.max,
+                    quoted(&sig.unit),
+                    node(&sig.receivers.join(","))
+                ));
+            }
+        }
+
+        out.push('\n');
+        for m in &self.messages {
+            if let Some(comment) = &m.comment {
+                out.push_str(&format!("CM_ BO_ {} {};\n", raw_id(m), quoted(comment)));
+            }
+            for sig in &m.signals {
+                if let Some(comment) = &sig.comment {
+                    out.push_str(&format!(
+                        "CM_ SG_ {} {} {};\n",
+                        raw_id(m),
+                        sig.name,
+                        quoted(comment)
+                    ));
+                }
+            }
+        }
+        for m in &self.messages {
+            for sig in m.signals.iter().filter(|s| !s.values.is_empty()) {
+                let values: Vec<String> = sig
+                    .values
+                    .iter()
+                    .map(|(raw, label)| format!("{} {}", raw, quoted(label)))
+                    .collect();
+                out.push_str(&format!(
+                    "VAL_ {} {} {} ;\n",
+                    raw_id(m),
+                    sig.name,
+                    values.join(" ")
+                ));
+            }
+        }
+        out
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.write())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"VERSION "bench"
+
+NS_ :
+	CM_
+	VAL_TABLE_
+
+BS_:
+
+BU_: BCM IMC GWM
+
+BO_ 560 IgnitionStatus: 8 BCM
+ SG_ IgnitionOn : 5|1@1+ (1,0) [0|1] "" IMC
+ SG_ Voltage : 16|8@1+ (0.1,0) [0|25.5] "V" IMC,GWM
+ SG_ Temperature : 31|12@0- (0.5,-10) [-1034|1013.5] "degC" IMC
+
+BO_ 2566844768 DiagMux: 8 GWM
+ SG_ Page M : 0|8@1+ (1,0) [0|255] "" IMC
+ SG_ Speed m1 : 8|16@1+ (0.01,0) [0|655.35] "km/h" IMC
+ SG_ Gear m2 : 8|4@1+ (1,0) [0|15] "" IMC
+
+CM_ BO_ 560 "Ignition and supply";
+CM_ SG_ 560 IgnitionOn "Set with the ignition on;
+also in crank";
+VAL_ 560 IgnitionOn 0 "Off" 1 "On" ;
+VAL_ 2566844768 Gear 0 "P" 1 "R" 2 "N" 3 "D" ;
+"#;
+
+    #[test]
+    fn test_parse_messages_and_signals() {
+        let dbc = Dbc::parse(SAMPLE).unwrap();
+        assert_eq!(dbc.version, "bench");
+        assert_eq!(dbc.nodes, vec!["BCM", "IMC", "GWM"]);
+        assert_eq!(dbc.messages.len(), 2);
+
+        let ign = dbc.find("0x230").unwrap();
+        assert_eq!(ign.comment.as_deref(), Some("Ignition and supply"));
+        let on = ign.signal("IgnitionOn").unwrap();
+        assert_eq!(on.values.get(&1).map(String::as_str), Some("On"));
+        assert_eq!(
+            on.comment.as_deref(),
+            Some("Set with the ignition on;\nalso in crank")
+        );
+        let temp = ign.signal("Temperature").unwrap();
+        assert_eq!(temp.field.byte_order, ByteOrder::BigEndian);
+        assert!(temp.signed);
+        assert_eq!(ign.signal("Voltage").unwrap().receivers, vec!["IMC", "GWM"]);
+
+        let mux = dbc.find("DiagMux").unwrap();
+        assert!(mux.extended);
+        assert_eq!(mux.id, 0x18FEF160);
+        assert!(mux.signal("Page").unwrap().multiplexor);
+        assert_eq!(mux.signal("Gear").unwrap().mux_value, Some(2));
+
+        assert!(
+            Dbc::parse("BO_ 1 X: 8 A\n SG_ S : 0|8@2+ (1,0) [0|1] \"\" B\n")
+                .unwrap_err()
+                .starts_with("line 2:")
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let dbc = Dbc::parse(SAMPLE).unwrap();
+        let text = dbc.write();
+        assert!(text.contains(" SG_ Temperature : 31|12@0- (0.5,-10) [-1034|1013.5] \"degC\" IMC"));
+        assert!(text.contains("VAL_ 2566844768 Gear 0 \"P\" 1 \"R\" 2 \"N\" 3 \"D\" ;"));
+        assert_eq!(Dbc::parse(&text).unwrap(), dbc);
+    }
+
+    #[test]
+    fn test_escaped_quotes_and_extended_ids() {
+        let text = "BO_ 256 Std: 8 BCM\n SG_ A : 0|8@1+ (1,0) [0|255] \"\" IMC\n\
+                    BO_ 2147483904 Ext: 8 GWM\n SG_ B : 0|8@1+ (1,0) [0|255] \"\" IMC\n\
+                    CM_ BO_ 256 \"Says \\\"hi\\\";\nthen C:\\\\temp\";\n\
+                    CM_ SG_ 256 A \"after\";\n";
+        let mut dbc = Dbc::parse(text).unwrap();
+        let plain = dbc.message(0x100, false).unwrap();
+        assert_eq!(
+            plain.comment.as_deref(),
+            Some("Says \"hi\";\nthen C:\\temp")
+        );
+        assert_eq!(plain.signals[0].comment.as_deref(), Some("after"));
+        assert_eq!(dbc.message(0x100, true).unwrap().name, "Ext");
+        let frame = CanFrame::new(0, 0x100, vec![0; 8]);
+        assert_eq!(dbc.decode(&frame).unwrap().message, "Std");
+        let frame = CanFrame {
+            rx_status: crate::j2534::types::CAN_29BIT_ID,
+            ..frame
+        };
+        assert_eq!(dbc.decode(&frame).unwrap().message, "Ext");
+
+        dbc.version = "v\"2\"".into();
+        assert_eq!(Dbc::parse(&dbc.write()).unwrap(), dbc);
+    }
+
+    #[test]
+    fn test_decode_byte_orders_and_values() {
+        let dbc = Dbc::parse(SAMPLE).unwrap();
+        // IgnitionOn set, 12.6 V, big-endian 12-bit temperature spanning bytes 3-4
+        let frame = CanFrame::new(0, 0x230, vec![0x60, 0x00, 126, 0xFF, 0xE0, 0, 0, 0]);
+        let decoded = dbc.decode(&frame).unwrap();
+        assert_eq!(decoded.message, "IgnitionStatus");
+        let by_name = |n: &str| decoded.signals.iter().find(|s| s.name == n).unwrap();
+        assert_eq!(by_name("IgnitionOn").label.as_deref(), Some("On"));
+        assert!((by_name("Voltage").value - 12.6).abs() < 1e-9);
+        assert_eq!(by_name("Voltage").unit, "V");
+        // 0xFFE = -2 → -2 * 0.5 - 10
+        assert_eq!(by_name("Temperature").raw, -2);
+        assert_eq!(by_name("Temperature").value, -11.0);
+        assert!(dbc.decode(&CanFrame::new(0, 0x7E0, vec![0; 8])).is_none());
+    }
+
+    #[test]
+    fn test_decode_multiplexed() {
+        let dbc = Dbc::parse(SAMPLE).unwrap();
+        let mux = dbc.find("DiagMux").unwrap();
+        let names =
+            |data: &[u8]| -> Vec<String> { mux.decode(data).into_iter().map(|s| s.name).collect() };
+        assert_eq!(
+            names(&[1, 0x10, 0x27, 0, 0, 0, 0, 0]),
+            vec!["Page", "Speed"]
+        );
+        let gear = mux.decode(&[2, 3, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(gear[1].label.as_deref(), Some("D"));
+        assert_eq!(names(&[7, 0, 0, 0, 0, 0, 0, 0]), vec!["Page"]);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let dbc = Dbc::parse(SAMPLE).unwrap();
+        let ign = dbc.find("IgnitionStatus").unwrap();
+        let values: BTreeMap<String, SignalValue> = [
+            ("IgnitionOn", SignalValue::Label("on".into())),
+            ("Voltage", SignalValue::Number(13.8)),
+            ("Temperature", SignalValue::Number(-11.0)),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        let data = ign.encode(&values).unwrap();
+        assert_eq!(data, vec![0x20, 0x00, 138, 0xFF, 0xE0, 0, 0, 0]);
+        let back = ign.decode(&data);
+        assert!((back[1].value - 13.8).abs() < 1e-9);
+        assert_eq!(back[2].value, -11.0);
+
+        let too_big = BTreeMap::from([("Voltage".to_string(), SignalValue::Number(30.0))]);
+        assert!(ign.encode(&too_big).unwrap_err().contains("out of range"));
+        let unknown = BTreeMap::from([("Rpm".to_string(), SignalValue::Number(0.0))]);
+        assert!(ign
+            .encode(&unknown)
+            .unwrap_err()
+            .contains("no signal 'Rpm'"));
+    }
+}
@@ -1,15 +1,25 @@
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
+use crate::broadcast::{default_schedule, BroadcastMsg, BroadcastScheduler, VehicleState};
+use crate::j2534::router::{BusListener, ChannelRouter};
 use crate::j2534::types::*;
+use crate::j2534::Channel;
+use crate::uds::client::{LogCallback, LogDirection, LogEntry};
+use crate::uds::keygen::{keygen_mki, DC0314_CONSTANTS};
 use crate::uds::services::ecu_addr;
 
 // ─── CCF raw data from real car (SAJBL4BVXGCY16353, X260 MY16 Jaguar XF) ──
 static GWM_CCF_RAW: &[u8] = include_bytes!("../assets/gwm_ccf.bin");
 static BCM_CCF_RAW: &[u8] = include_bytes!("../assets/bcm_ccf.bin");
+/// IMC 0x0E02 List CCF response payload (option IDs the IMC consumes)
+static IMC_CCF_LIST: &[u8] = include_bytes!("../assets/imc_ccf_list.bin");
 
 // ─── ECU Identification ──────────────────────────────────────────────
 
@@ -17,6 +27,7 @@ static BCM_CCF_RAW: &[u8] = include_bytes!("../assets/bcm_ccf.bin");
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum EcuId {
+    Imc,
     Bcm,
     Gwm,
     Ipc,
@@ -26,6 +37,7 @@ impl EcuId {
     /// CAN ID used to send requests TO this ECU (from tester perspective)
     pub fn tx_id(self) -> u32 {
         match self {
+            EcuId::Imc => ecu_addr::IMC_TX,
             EcuId::Bcm => ecu_addr::BCM_TX,
             EcuId::Gwm => ecu_addr::GWM_TX,
             EcuId::Ipc => ecu_addr::IPC_TX,
@@ -35,6 +47,7 @@ impl EcuId {
     /// CAN ID used for responses FROM this ECU
     pub fn rx_id(self) -> u32 {
         match self {
+            EcuId::Imc => ecu_addr::IMC_RX,
             EcuId::Bcm => ecu_addr::BCM_RX,
             EcuId::Gwm => ecu_addr::GWM_RX,
             EcuId::Ipc => ecu_addr::IPC_RX,
@@ -44,6 +57,7 @@ impl EcuId {
     /// Human-readable name
     pub fn name(self) -> &'static str {
         match self {
+            EcuId::Imc => "IMC",
             EcuId::Bcm => "BCM",
             EcuId::Gwm => "GWM",
             EcuId::Ipc => "IPC",
@@ -53,6 +67,7 @@ impl EcuId {
     /// Parse from string (case-insensitive)
     pub fn from_str(s: &str) -> Option<EcuId> {
         match s.to_lowercase().as_str() {
+            "imc" => Some(EcuId::Imc),
             "bcm" => Some(EcuId::Bcm),
             "gwm" => Some(EcuId::Gwm),
             "ipc" => Some(EcuId::Ipc),
@@ -60,27 +75,87 @@ impl EcuId {
         }
     }
 
-    /// All known ECU IDs
+    /// All known ECU IDs (a complete simulated vehicle)
     pub fn all() -> &'static [EcuId] {
-        &[EcuId::Bcm, EcuId::Gwm, EcuId::Ipc]
+        &[EcuId::Imc, EcuId::Bcm, EcuId::Gwm, EcuId::Ipc]
     }
 }
 
 // ─── ECU Handler Trait ───────────────────────────────────────────────
 
 /// Trait for ECU-specific response logic
-pub trait EcuHandler: Send {
+pub trait EcuHandler: Send + Sync {
     /// Build a UDS response for a given request payload.
     /// Returns None if the request should be ignored (not for this ECU).
     fn build_response(&self, request: &[u8]) -> Option<Vec<u8>>;
 
     /// ECU name for logging
     fn name(&self) -> &str;
+
+    /// Called by `StatefulEcu` whenever the active session changes (session
+    /// control, reset or S3 timeout), for handlers whose data depends on it
+    fn session_changed(&self, _session: u8) {}
+
+    /// False while the ECU answers nothing at all (e.g. rebooting after a reset)
+    fn online(&self) -> bool {
+        true
+    }
+
+    /// Requests this ECU puts on the bus for other modules, as (target TX ID,
+    /// payload) — e.g. the IMC asking the GWM for its CCF. Drained by the bus.
+    fn bus_requests(&self) -> Vec<(u32, Vec<u8>)> {
+        Vec::new()
+    }
+
+    /// Answer from the module at `rx_id` to one of `bus_requests`
+    fn bus_response(&self, _rx_id: u32, _payload: &[u8]) {}
+}
+
+// ─── Written DIDs ────────────────────────────────────────────────────
+
+/// Values stored by WriteDataByIdentifier, read back in place of the built-in
+/// data so a write → readback cycle (e.g. a CCF edit) behaves like the car
+#[derive(Default)]
+struct WrittenDids(Mutex<HashMap<u16, Vec<u8>>>);
+
+impl WrittenDids {
+    /// Positive 0x22 response for a DID written earlier
+    fn read(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let [0x22, hi, lo, ..] = *request else {
+            return None;
+        };
+        let data = self.0.lock().unwrap().get(&u16::from_be_bytes([hi, lo]))?.clone();
+        let mut resp = vec![0x62, hi, lo];
+        resp.extend_from_slice(&data);
+        Some(resp)
+    }
+
+    /// Answer to a 0x2E request, storing its data
+    fn write(&self, request: &[u8]) -> Option<Vec<u8>> {
+        match *request {
+            [0x2E, _, _] => Some(vec![0x7F, 0x2E, 0x13]),
+            [0x2E, hi, lo, ..] => {
+                let data = request[3..].to_vec();
+                self.0.lock().unwrap().insert(u16::from_be_bytes([hi, lo]), data);
+                Some(vec![0x6E, hi, lo])
+            }
+            _ => None,
+        }
+    }
 }
 
 // ─── BCM Handler ─────────────────────────────────────────────────────
 
-pub struct BcmHandler;
+#[derive(Default)]
+pub struct BcmHandler {
+    written: WrittenDids,
+}
+
+impl BcmHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 
 impl EcuHandler for BcmHandler {
     fn name(&self) -> &str {
@@ -88,6 +163,9 @@ impl EcuHandler for BcmHandler {
     }
 
     fn build_response(&self, request: &[u8]) -> Option<Vec<u8>> {
+        if let Some(resp) = self.written.read(request) {
+            return Some(resp);
+        }
         match request {
             // TesterPresent (3E 00)
             [0x3E, 0x00, ..] => Some(vec![0x7E, 0x00]),
@@ -159,14 +237,17 @@ impl EcuHandler for BcmHandler {
                 }
             }
 
+            // ReadDTCInformation by status mask (19 02 MM) → no stored DTCs, all status bits supported
+            [0x19, 0x02, ..] => Some(vec![0x59, 0x02, 0xFF]),
+
             // SecurityAccess (27 XX) → zero seed (already unlocked)
             [0x27, level, ..] => Some(vec![0x67, *level, 0x00, 0x00, 0x00]),
 
             // CommunicationControl (28 XX XX)
             [0x28, sub_function, ..] => Some(vec![0x68, *sub_function]),
 
-            // WriteDataByIdentifier (2E XX XX ...)
-            [0x2E, did_hi, did_lo, ..] => Some(vec![0x6E, *did_hi, *did_lo]),
+            // WriteDataByIdentifier (2E XX XX ...) — stored for readback
+            [0x2E, ..] => self.written.write(request),
 
             // RoutineControl (31 XX XX XX)
             [0x31, sub_fn, rid_hi, rid_lo, ..] => Some(vec![0x71, *sub_fn, *rid_hi, *rid_lo]),
@@ -181,7 +262,16 @@ impl EcuHandler for BcmHandler {
 
 // ─── GWM Handler ─────────────────────────────────────────────────────
 
-pub struct GwmHandler;
+#[derive(Default)]
+pub struct GwmHandler {
+    written: WrittenDids,
+}
+
+impl GwmHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 
 impl EcuHandler for GwmHandler {
     fn name(&self) -> &str {
@@ -189,6 +279,9 @@ impl EcuHandler for GwmHandler {
     }
 
     fn build_response(&self, request: &[u8]) -> Option<Vec<u8>> {
+        if let Some(resp) = self.written.read(request) {
+            return Some(resp);
+        }
         match request {
             // TesterPresent
             [0x3E, 0x00, ..] => Some(vec![0x7E, 0x00]),
@@ -246,14 +339,17 @@ impl EcuHandler for GwmHandler {
                 }
             }
 
+            // ReadDTCInformation by status mask → no stored DTCs
+            [0x19, 0x02, ..] => Some(vec![0x59, 0x02, 0xFF]),
+
             // SecurityAccess → zero seed
             [0x27, level, ..] => Some(vec![0x67, *level, 0x00, 0x00, 0x00]),
 
             // CommunicationControl (28 XX XX)
             [0x28, sub_function, ..] => Some(vec![0x68, *sub_function]),
 
-            // WriteDataByIdentifier (2E XX XX ...)
-            [0x2E, did_hi, did_lo, ..] => Some(vec![0x6E, *did_hi, *did_lo]),
+            // WriteDataByIdentifier (2E XX XX ...) — stored for readback
+            [0x2E, ..] => self.written.write(request),
 
             // RoutineControl (31 XX XX XX)
             [0x31, sub_fn, rid_hi, rid_lo, ..] => Some(vec![0x71, *sub_fn, *rid_hi, *rid_lo]),
@@ -283,176 +379,1565 @@ impl EcuHandler for IpcHandler {
             // DiagnosticSessionControl
             [0x10, session, ..] => Some(vec![0x50, *session, 0x00, 0x19, 0x01, 0xF4]),
 
+            // ReadDTCInformation by status mask → no stored DTCs
+            [0x19, 0x02, ..] => Some(vec![0x59, 0x02, 0xFF]),
+
             // SecurityAccess → zero seed
             [0x27, level, ..] => Some(vec![0x67, *level, 0x00, 0x00, 0x00]),
 
             // Unknown → NRC serviceNotSupported
             [sid, ..] => Some(vec![0x7F, *sid, 0x11]),
 
-            _ => None,
+            _ => None,
+        }
+    }
+}
+
+// ─── IMC Handler ─────────────────────────────────────────────────────
+
+/// How long the virtual IMC's CCF routines and reboot take
+#[derive(Debug, Clone, Copy)]
+pub struct ImcTiming {
+    /// 0x0E08: fetching the CCF from the GWM
+    pub prepare: Duration,
+    /// 0x0E06: copying the fetched CCF in (results answer 0x21 until done)
+    pub transfer: Duration,
+    /// 0x6038: configuring Linux from the CCF
+    pub configure: Duration,
+    /// ECU reset: silent while rebooting
+    pub reboot: Duration,
+}
+
+impl Default for ImcTiming {
+    fn default() -> Self {
+        Self {
+            prepare: Duration::from_secs(1),
+            transfer: Duration::from_secs(2),
+            configure: Duration::from_secs(8),
+            reboot: Duration::from_secs(20),
+        }
+    }
+}
+
+/// How the virtual IMC's CCF routines end
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ImcOutcome {
+    #[default]
+    Success,
+    /// 0x0E06 results report COMPLETION_STATUS 0x20 with this ADDITIONAL_DATA
+    TransferFails(u8),
+    /// 0x6038 completes with RESULT 0x03 (failed to configure) and these ERROR bits
+    ConfigureFails(u8),
+    /// 0x6038 ends with STATUS 0x21 (routine aborted)
+    ConfigureAborted,
+}
+
+/// 0x0E06 ADDITIONAL_DATA when the GWM never answered the 0x0E08 fetch
+const TRANSFER_NO_GWM_CCF: u8 = 0x01;
+
+/// Session / CCF-routine state of the virtual IMC
+#[derive(Debug)]
+struct ImcState {
+    session: u8,
+    /// 0x0E08 started — GWM fetch finished at this instant
+    prepared_at: Option<Instant>,
+    /// CCF the GWM answered the 0x0E08 fetch with
+    gwm_ccf: Option<Vec<u8>>,
+    /// 0x0E06 started — transfer finished at this instant
+    transfer_done_at: Option<Instant>,
+    /// 0x6038 started — configuration finished at this instant
+    configure_done_at: Option<Instant>,
+    /// ECU reset — silent until this instant
+    rebooting_until: Option<Instant>,
+    /// Requests for other modules, drained by the bus
+    bus_requests: Vec<(u32, Vec<u8>)>,
+}
+
+/// Virtual IMC for pure-simulation connections. Session and security are enforced
+/// by the `StatefulEcu` wrapper from `create_handler`; this handler models the
+/// IMC-specific parts: 0x0202 only in the extended session, and the CCF routines
+/// progressing over time like on the car — 0x0E08 fetches the CCF from the GWM
+/// over the bus, 0x0E06 copies it in, 0x6038 configures from it, and a reset
+/// leaves the IMC silent while it reboots. Like the car, it has no way to read
+/// the CCF back: DID 0xEE00 and routine 0x0E01 answer NRC 0x31.
+pub struct ImcHandler {
+    state: Mutex<ImcState>,
+    timing: ImcTiming,
+    outcome: ImcOutcome,
+}
+
+impl ImcHandler {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(ImcState {
+                session: 0x01,
+                prepared_at: None,
+                gwm_ccf: None,
+                transfer_done_at: None,
+                configure_done_at: None,
+                rebooting_until: None,
+                bus_requests: Vec::new(),
+            }),
+            timing: ImcTiming::default(),
+            outcome: ImcOutcome::Success,
+        }
+    }
+
+    pub fn with_timing(mut self, timing: ImcTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Make the CCF routines end with `outcome` (failure injection)
+    pub fn with_outcome(mut self, outcome: ImcOutcome) -> Self {
+        self.outcome = outcome;
+        self
+    }
+
+    /// 0x6038 STATUS / RESULT / ERROR bytes (see `describe_6038`)
+    fn configure_status(&self, done_at: Instant, now: Instant) -> [u8; 3] {
+        if now < done_at {
+            return [0x22, 0x02, 0x00];
+        }
+        match self.outcome {
+            ImcOutcome::ConfigureFails(errors) => [0x20, 0x03, errors],
+            ImcOutcome::ConfigureAborted => [0x21, 0x03, 0x00],
+            _ => [0x20, 0x01, 0x00],
+        }
+    }
+
+    /// 0x0E06 Request Results: busy until done, then COMPLETION_STATUS / ADDITIONAL_DATA
+    fn transfer_results(
+        &self,
+        st: &ImcState,
+        done_at: Instant,
+        now: Instant,
+    ) -> Result<[u8; 2], u8> {
+        if now < done_at {
+            return Err(0x21);
+        }
+        let additional = match (self.outcome, &st.gwm_ccf) {
+            (ImcOutcome::TransferFails(additional), _) => additional,
+            (_, None) => TRANSFER_NO_GWM_CCF,
+            (_, Some(_)) => 0x00,
+        };
+        Ok([0x20, additional])
+    }
+}
+
+impl Default for ImcHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn ascii_did(did: u16, text: &[u8], pad_to: usize) -> Vec<u8> {
+    let mut resp = vec![0x62, (did >> 8) as u8, did as u8];
+    resp.extend_from_slice(text);
+    resp.resize(3 + pad_to.max(text.len()), 0x00);
+    resp
+}
+
+impl EcuHandler for ImcHandler {
+    fn name(&self) -> &str {
+        "IMC"
+    }
+
+    fn session_changed(&self, session: u8) {
+        self.state.lock().unwrap().session = session;
+    }
+
+    fn online(&self) -> bool {
+        let st = self.state.lock().unwrap();
+        st.rebooting_until.is_none_or(|t| Instant::now() >= t)
+    }
+
+    fn bus_requests(&self) -> Vec<(u32, Vec<u8>)> {
+        std::mem::take(&mut self.state.lock().unwrap().bus_requests)
+    }
+
+    fn bus_response(&self, rx_id: u32, payload: &[u8]) {
+        if rx_id == ecu_addr::GWM_RX {
+            if let [0x62, 0xEE, 0x00, ccf @ ..] = payload {
+                self.state.lock().unwrap().gwm_ccf = Some(ccf.to_vec());
+            }
+        }
+    }
+
+    fn build_response(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let now = Instant::now();
+        let mut st = self.state.lock().unwrap();
+        match st.rebooting_until {
+            Some(t) if now < t => return None,
+            Some(_) => st.rebooting_until = None,
+            None => {}
+        }
+        match request {
+            [0x3E, 0x00, ..] => Some(vec![0x7E, 0x00]),
+
+            [0x10, session, ..] => Some(vec![0x50, *session, 0x00, 0x32, 0x01, 0xF4]),
+
+            // Reboot: routines in flight are lost, the received CCF is kept
+            [0x11, reset_type, ..] => {
+                st.session = 0x01;
+                st.prepared_at = None;
+                st.transfer_done_at = None;
+                st.configure_done_at = None;
+                st.rebooting_until = Some(now + self.timing.reboot);
+                Some(vec![0x51, *reset_type])
+            }
+
+            [0x22, did_hi, did_lo, ..] => {
+                let did = ((*did_hi as u16) << 8) | (*did_lo as u16);
+                match did {
+                    0xD100 => Some(vec![0x62, 0xD1, 0x00, st.session]),
+                    0xF190 => Some(ascii_did(did, b"SAJBL4BVXGCY16353", 0)),
+                    0xF188 => Some(ascii_did(did, b"FW93-19C204-AJ", 24)),
+                    0xF120 => Some(ascii_did(did, b"FW93-19C206-AC", 24)),
+                    0xF1A5 => Some(ascii_did(did, b"GX73-14F657-AB", 24)),
+                    0xF180 => Some(ascii_did(did, b"GX73-14C337-AA", 24)),
+                    0xF18C => Some(ascii_did(did, b"2205310457", 0)),
+                    0xF113 => Some(ascii_did(did, b"GX73-19C299-AE", 24)),
+                    // IMC status: extended session only (per EXML)
+                    0x0202 if st.session == 0x03 => Some(vec![0x62, 0x02, 0x02, 0x00]),
+                    0x0202 => Some(vec![0x7F, 0x22, 0x31]),
+                    _ => Some(vec![0x7F, 0x22, 0x31]),
+                }
+            }
+
+            [0x19, 0x02, ..] => Some(vec![0x59, 0x02, 0xFF]),
+
+            [0x28, sub_function, ..] => Some(vec![0x68, *sub_function]),
+
+            [0x31, sub_fn, rid_hi, rid_lo, data @ ..] => {
+                let rid = ((*rid_hi as u16) << 8) | (*rid_lo as u16);
+                let positive = |extra: &[u8]| {
+                    let mut resp = vec![0x71, *sub_fn, *rid_hi, *rid_lo];
+                    resp.extend_from_slice(extra);
+                    resp
+                };
+                let nrc = |code: u8| Some(vec![0x7F, 0x31, code]);
+                match (*sub_fn, rid) {
+                    // SSH enable needs its parameter byte
+                    (0x01, 0x603E) if data.is_empty() => nrc(0x13),
+                    (0x01, 0x603E) => Some(positive(&[0x22])),
+
+                    // Prepare: fetch the CCF from the GWM over the bus
+                    (0x01, 0x0E08) => {
+                        st.prepared_at = Some(now + self.timing.prepare);
+                        st.gwm_ccf = None;
+                        st.transfer_done_at = None;
+                        st.bus_requests.push((ecu_addr::GWM_TX, vec![0x22, 0xEE, 0x00]));
+                        Some(positive(&[]))
+                    }
+                    // Transfer: starts once the fetch is done
+                    (0x01, 0x0E06) => match st.prepared_at {
+                        Some(prepared) => {
+                            st.transfer_done_at = Some(prepared.max(now) + self.timing.transfer);
+                            Some(positive(&[]))
+                        }
+                        None => nrc(0x24),
+                    },
+                    (0x03, 0x0E06) => match st.transfer_done_at {
+                        Some(done) => match self.transfer_results(&st, done, now) {
+                            Ok(status) => Some(positive(&status)),
+                            Err(code) => nrc(code),
+                        },
+                        None => nrc(0x24),
+                    },
+
+                    (0x01, 0x0E02) => Some(positive(IMC_CCF_LIST)),
+                    // No CCF read-back on the car (see `read_ccf_report_imc`)
+                    (0x01, 0x0E01) | (0x01, 0x0E00) => nrc(0x31),
+
+                    // Configure Linux: completed, no errors; Request Results follows the
+                    // configuration as it runs (active until done, then completed / failed)
+                    (0x01, 0x6038) => {
+                        st.configure_done_at = Some(now + self.timing.configure);
+                        Some(positive(&[0x20, 0x01, 0x00]))
+                    }
+                    (0x03, 0x6038) => match st.configure_done_at {
+                        Some(done) => Some(positive(&self.configure_status(done, now))),
+                        None => nrc(0x24),
+                    },
+
+                    _ => nrc(0x31),
+                }
+            }
+
+            [sid, ..] => Some(vec![0x7F, *sid, 0x11]),
+
+            _ => None,
+        }
+    }
+}
+
+// ─── Session & Security State Machine ────────────────────────────────
+
+/// NRC 0x7F serviceNotSupportedInActiveSession
+const NRC_NOT_IN_SESSION: u8 = 0x7F;
+/// NRC 0x7E subFunctionNotSupportedInActiveSession
+const NRC_SUB_NOT_IN_SESSION: u8 = 0x7E;
+
+/// One SecurityAccess level: seed sub-function (key = seed + 1), the sessions it
+/// is available in, and the KeyGenMkI constants the tester must use
+#[derive(Debug, Clone)]
+pub struct SecurityLevel {
+    pub seed_sub: u8,
+    pub sessions: &'static [u8],
+    pub constants: [u8; 5],
+}
+
+/// Session/security rules of an emulated ECU
+#[derive(Debug, Clone)]
+pub struct EcuPolicy {
+    pub levels: Vec<SecurityLevel>,
+    /// Routines answered with 0x33 until a security level is unlocked
+    pub secured_routines: &'static [u16],
+    /// Non-default session falls back to default after this long without a request
+    pub s3_timeout: Duration,
+    /// Invalid keys allowed before 0x36 and the lockout delay
+    pub max_attempts: u8,
+    pub lockout: Duration,
+}
+
+impl EcuPolicy {
+    /// JLR defaults: 5 s S3, 3 key attempts, 10 s lockout
+    pub fn new(levels: Vec<SecurityLevel>) -> Self {
+        Self {
+            levels,
+            secured_routines: &[],
+            s3_timeout: Duration::from_secs(5),
+            max_attempts: 3,
+            lockout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn with_secured_routines(mut self, routines: &'static [u16]) -> Self {
+        self.secured_routines = routines;
+        self
+    }
+
+    /// Shorter timers, for tests
+    pub fn with_timing(mut self, s3_timeout: Duration, lockout: Duration) -> Self {
+        self.s3_timeout = s3_timeout;
+        self.lockout = lockout;
+        self
+    }
+
+    /// Policy used for `ecu` by `create_handler`. Level 0x11 with the DC0314 constants
+    /// is what the IMC uses on the car. The other modules' constants are unknown, so
+    /// they get no levels: 0x27 answers 0x12 and writes are not gated.
+    pub fn for_ecu(ecu: EcuId) -> Self {
+        match ecu {
+            EcuId::Imc => Self::new(vec![
+                SecurityLevel {
+                    seed_sub: 0x11,
+                    sessions: &[0x03],
+                    constants: DC0314_CONSTANTS,
+                },
+                SecurityLevel {
+                    seed_sub: 0x01,
+                    sessions: &[0x02],
+                    constants: DC0314_CONSTANTS,
+                },
+            ])
+            .with_secured_routines(&[
+                0x6038, 0x603D, 0x603E, 0x603F, 0x6041, 0x6042, 0x6043, 0x6045, 0x6046,
+            ]),
+            EcuId::Bcm | EcuId::Gwm | EcuId::Ipc => Self::new(Vec::new()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DiagState {
+    session: u8,
+    last_request: Instant,
+    /// (seed sub-function, seed) waiting for its key
+    pending_seed: Option<(u8, u32)>,
+    /// Seed sub-function of the unlocked level
+    unlocked: Option<u8>,
+    failed_attempts: u8,
+    locked_until: Option<Instant>,
+}
+
+/// Random non-zero 24-bit seed (std's per-instance random SipHash keys)
+fn random_seed() -> u32 {
+    use std::hash::{BuildHasher, Hasher};
+    let seed = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish() as u32
+        & 0xFF_FFFF;
+    seed.max(1)
+}
+
+/// Wraps an ECU handler with real session and SecurityAccess behaviour: session
+/// transitions, S3 timeout, per-session service checks (0x7F/0x7E), random seeds
+/// checked with KeyGenMkI, and attempt counting with a lockout delay (0x35/0x36/0x37).
+pub struct StatefulEcu {
+    inner: Box<dyn EcuHandler>,
+    policy: EcuPolicy,
+    state: Mutex<DiagState>,
+}
+
+impl StatefulEcu {
+    pub fn new(inner: Box<dyn EcuHandler>, policy: EcuPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            state: Mutex::new(DiagState {
+                session: 0x01,
+                last_request: Instant::now(),
+                pending_seed: None,
+                unlocked: None,
+                failed_attempts: 0,
+                locked_until: None,
+            }),
+        }
+    }
+
+    /// Currently active session (0x01 default, 0x02 programming, 0x03 extended)
+    pub fn session(&self) -> u8 {
+        self.state.lock().unwrap().session
+    }
+
+    fn set_session(&self, st: &mut DiagState, session: u8) {
+        if st.session != session {
+            st.unlocked = None;
+            st.pending_seed = None;
+            st.session = session;
+            self.inner.session_changed(session);
+        }
+    }
+
+    fn security_access(&self, st: &mut DiagState, sub: u8, key: &[u8], now: Instant) -> Vec<u8> {
+        let nrc = |code: u8| vec![0x7F, 0x27, code];
+        if st.session == 0x01 {
+            return nrc(NRC_NOT_IN_SESSION);
+        }
+        let seed_sub = if sub % 2 == 1 { sub } else { sub.wrapping_sub(1) };
+        let Some(level) = self.policy.levels.iter().find(|l| l.seed_sub == seed_sub) else {
+            return nrc(0x12);
+        };
+        if !level.sessions.contains(&st.session) {
+            return nrc(NRC_SUB_NOT_IN_SESSION);
+        }
+        if st.locked_until.is_some_and(|t| now < t) {
+            return nrc(0x37);
+        }
+        st.locked_until = None;
+
+        if sub == seed_sub {
+            // requestSeed
+            if !key.is_empty() {
+                return nrc(0x13);
+            }
+            if st.unlocked == Some(seed_sub) {
+                return vec![0x67, sub, 0x00, 0x00, 0x00];
+            }
+            let seed = random_seed();
+            st.pending_seed = Some((seed_sub, seed));
+            return vec![0x67, sub, (seed >> 16) as u8, (seed >> 8) as u8, seed as u8];
+        }
+
+        // sendKey
+        let seed = match st.pending_seed.take() {
+            Some((pending_sub, seed)) if pending_sub == seed_sub => seed,
+            _ => return nrc(0x24),
+        };
+        if key.len() != 3 {
+            return nrc(0x13);
+        }
+        let key = ((key[0] as u32) << 16) | ((key[1] as u32) << 8) | key[2] as u32;
+        if key == keygen_mki(seed, &level.constants) {
+            st.unlocked = Some(seed_sub);
+            st.failed_attempts = 0;
+            return vec![0x67, sub];
+        }
+        st.failed_attempts += 1;
+        if st.failed_attempts >= self.policy.max_attempts {
+            st.failed_attempts = 0;
+            st.locked_until = Some(now + self.policy.lockout);
+            nrc(0x36)
+        } else {
+            nrc(0x35)
+        }
+    }
+}
+
+impl EcuHandler for StatefulEcu {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn session_changed(&self, session: u8) {
+        self.inner.session_changed(session);
+    }
+
+    fn online(&self) -> bool {
+        self.inner.online()
+    }
+
+    fn bus_requests(&self) -> Vec<(u32, Vec<u8>)> {
+        self.inner.bus_requests()
+    }
+
+    fn bus_response(&self, rx_id: u32, payload: &[u8]) {
+        self.inner.bus_response(rx_id, payload);
+    }
+
+    fn build_response(&self, request: &[u8]) -> Option<Vec<u8>> {
+        if !self.inner.online() {
+            return None;
+        }
+        let now = Instant::now();
+        let mut st = self.state.lock().unwrap();
+
+        // S3: a non-default session without tester activity falls back to default
+        if st.session != 0x01 && now.duration_since(st.last_request) > self.policy.s3_timeout {
+            self.set_session(&mut st, 0x01);
+        }
+        st.last_request = now;
+
+        let sid = *request.first()?;
+        let nrc = |code: u8| Some(vec![0x7F, sid, code]);
+        match request {
+            // Suppress-positive-response bit
+            [0x3E, 0x80] => None,
+            [0x3E, 0x00] => Some(vec![0x7E, 0x00]),
+
+            [0x10, session, ..] => {
+                let allowed = match session {
+                    0x01 | 0x03 => true,
+                    // Programming only from extended (or programming)
+                    0x02 => st.session != 0x01,
+                    _ => return nrc(0x12),
+                };
+                if !allowed {
+                    return nrc(NRC_SUB_NOT_IN_SESSION);
+                }
+                self.set_session(&mut st, *session);
+                match self.inner.build_response(request) {
+                    Some(resp) if resp.first() == Some(&0x50) => Some(resp),
+                    _ => Some(vec![0x50, *session, 0x00, 0x19, 0x01, 0xF4]),
+                }
+            }
+
+            [0x11, reset_type, ..] => {
+                self.set_session(&mut st, 0x01);
+                st.pending_seed = None;
+                match self.inner.build_response(request) {
+                    Some(resp) if resp.first() == Some(&0x51) => Some(resp),
+                    _ => Some(vec![0x51, *reset_type]),
+                }
+            }
+
+            [0x27, sub, key @ ..] => Some(self.security_access(&mut st, *sub, key, now)),
+
+            // Services that need a non-default session
+            [0x28 | 0x2E | 0x2F | 0x31, ..] if st.session == 0x01 => nrc(NRC_NOT_IN_SESSION),
+            [0x2E, ..] if st.unlocked.is_none() && !self.policy.levels.is_empty() => nrc(0x33),
+            [0x31, _, rid_hi, rid_lo, ..]
+                if st.unlocked.is_none()
+                    && self
+                        .policy
+                        .secured_routines
+                        .contains(&(((*rid_hi as u16) << 8) | *rid_lo as u16)) =>
+            {
+                nrc(0x33)
+            }
+
+            _ => {
+                drop(st);
+                self.inner.build_response(request)
+            }
+        }
+    }
+}
+
+// ─── Dump Handler ────────────────────────────────────────────────────
+
+/// One DID as recorded by a full scan
+#[derive(Debug, Clone)]
+enum DumpedDid {
+    /// Full positive response (62 DID ...) and whether it needed the extended session
+    Positive { response: Vec<u8>, extended: bool },
+    /// The ECU answered with this NRC
+    Negative(u8),
+    /// No usable answer was recorded (timeout, transport error) — stay silent
+    Silent,
+}
+
+/// Emulates an ECU from a `*_dump.json` file written by the full DID scans
+/// (`scan_bcm_full` etc.). 0x22 reads answer with the recorded bytes — DIDs that
+/// were only readable in the extended session get NRC 0x31 in the default session,
+/// and recorded NRCs are reproduced. Other services go to the fallback handler.
+pub struct DumpHandler {
+    name: String,
+    tx_id: Option<u32>,
+    dids: HashMap<u16, DumpedDid>,
+    /// Recorded 0x0E02 / 0x0E01 CCF routine results (older dumps only)
+    ccf: HashMap<u16, DumpedDid>,
+    session: Mutex<u8>,
+    fallback: Option<Box<dyn EcuHandler>>,
+}
+
+/// "NRC: Request out of range (0x31)" → 0x31
+pub(crate) fn nrc_from_error(error: &str) -> Option<u8> {
+    if !error.contains("NRC") {
+        return None;
+    }
+    let start = error.rfind("(0x")? + 3;
+    let hex = error.get(start..start + 2)?;
+    u8::from_str_radix(hex, 16).ok()
+}
+
+fn parse_hex_u32(s: &str) -> Option<u32> {
+    u32::from_str_radix(s.trim().trim_start_matches("0x").trim_start_matches("0X"), 16).ok()
+}
+
+impl DumpHandler {
+    /// Parse a scan dump (JSON text)
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let dump: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| format!("Invalid dump JSON: {}", e))?;
+        let name = dump["ecu"].as_str().unwrap_or("DUMP").to_string();
+        let tx_id = dump["tx_id"].as_str().and_then(parse_hex_u32);
+        let entries = dump["dids"]
+            .as_array()
+            .ok_or("Dump has no \"dids\" array")?;
+
+        let mut dids = HashMap::new();
+        for entry in entries {
+            let did = entry["did"]
+                .as_str()
+                .and_then(parse_hex_u32)
+                .ok_or_else(|| format!("Bad DID entry in dump: {}", entry))? as u16;
+            let recorded = match (entry["bytes"].as_array(), entry["error"].as_str()) {
+                (Some(bytes), _) => DumpedDid::Positive {
+                    response: bytes
+                        .iter()
+                        .map(|b| b.as_u64().map(|b| b as u8))
+                        .collect::<Option<Vec<u8>>>()
+                        .ok_or_else(|| format!("Bad bytes for DID {:04X}", did))?,
+                    extended: entry["session"].as_str() == Some("extended"),
+                },
+                (None, Some(error)) => match nrc_from_error(error) {
+                    Some(nrc) => DumpedDid::Negative(nrc),
+                    None => DumpedDid::Silent,
+                },
+                (None, None) => DumpedDid::Silent,
+            };
+            dids.insert(did, recorded);
+        }
+
+        let mut ccf = HashMap::new();
+        for (rid, data_key, error_key) in [
+            (0x0E02u16, "list_0E02", "list_error"),
+            (0x0E01, "retrieve_0E01", "retrieve_error"),
+        ] {
+            let ccf_entry = &dump["ccf"];
+            if let Some(bytes) = ccf_entry[data_key].as_array() {
+                let mut response = vec![0x71, 0x01, (rid >> 8) as u8, rid as u8];
+                response.extend(bytes.iter().filter_map(|b| b.as_u64()).map(|b| b as u8));
+                ccf.insert(rid, DumpedDid::Positive { response, extended: false });
+            } else if let Some(nrc) = ccf_entry[error_key].as_str().and_then(nrc_from_error) {
+                ccf.insert(rid, DumpedDid::Negative(nrc));
+            }
+        }
+
+        Ok(Self {
+            name,
+            tx_id,
+            dids,
+            ccf,
+            session: Mutex::new(0x01),
+            fallback: None,
+        })
+    }
+
+    /// Load a dump file from disk
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        Self::from_json(&json).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Answer services the dump doesn't cover (security, routines, ...) with `handler`
+    pub fn with_fallback(mut self, handler: Box<dyn EcuHandler>) -> Self {
+        self.fallback = Some(handler);
+        self
+    }
+
+    /// The ECU this dump was taken from, by name or request CAN ID
+    pub fn ecu(&self) -> Option<EcuId> {
+        EcuId::from_str(&self.name).or_else(|| {
+            let tx = self.tx_id?;
+            EcuId::all().iter().copied().find(|e| e.tx_id() == tx)
+        })
+    }
+
+    /// Number of DIDs with a recorded positive response
+    pub fn readable_dids(&self) -> usize {
+        self.dids
+            .values()
+            .filter(|d| matches!(d, DumpedDid::Positive { .. }))
+            .count()
+    }
+
+    fn replay(recorded: &DumpedDid, sid: u8, session: u8) -> Option<Vec<u8>> {
+        match recorded {
+            DumpedDid::Positive { extended: true, .. } if session != 0x03 => {
+                Some(vec![0x7F, sid, 0x31])
+            }
+            DumpedDid::Positive { response, .. } => Some(response.clone()),
+            DumpedDid::Negative(nrc) => Some(vec![0x7F, sid, *nrc]),
+            DumpedDid::Silent => None,
+        }
+    }
+}
+
+impl EcuHandler for DumpHandler {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn session_changed(&self, session: u8) {
+        *self.session.lock().unwrap() = session;
+        if let Some(fallback) = &self.fallback {
+            fallback.session_changed(session);
+        }
+    }
+
+    fn online(&self) -> bool {
+        self.fallback.as_ref().is_none_or(|f| f.online())
+    }
+
+    fn bus_requests(&self) -> Vec<(u32, Vec<u8>)> {
+        self.fallback.as_ref().map(|f| f.bus_requests()).unwrap_or_default()
+    }
+
+    fn bus_response(&self, rx_id: u32, payload: &[u8]) {
+        if let Some(fallback) = &self.fallback {
+            fallback.bus_response(rx_id, payload);
+        }
+    }
+
+    fn build_response(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let mut session = self.session.lock().unwrap();
+        match request {
+            [0x10, s, ..] => *session = *s,
+            [0x11, ..] => *session = 0x01,
+            [0x22, did_hi, did_lo] => {
+                let did = ((*did_hi as u16) << 8) | (*did_lo as u16);
+                return match self.dids.get(&did) {
+                    Some(recorded) => Self::replay(recorded, 0x22, *session),
+                    None => Some(vec![0x7F, 0x22, 0x31]),
+                };
+            }
+            [0x31, 0x01, rid_hi, rid_lo, ..] => {
+                let rid = ((*rid_hi as u16) << 8) | (*rid_lo as u16);
+                if let Some(recorded) = self.ccf.get(&rid) {
+                    return Self::replay(recorded, 0x31, *session);
+                }
+            }
+            _ => {}
+        }
+        drop(session);
+
+        if let Some(fallback) = &self.fallback {
+            return fallback.build_response(request);
+        }
+        match request {
+            [0x3E, 0x00, ..] => Some(vec![0x7E, 0x00]),
+            [0x10, s, ..] => Some(vec![0x50, *s, 0x00, 0x19, 0x01, 0xF4]),
+            [0x19, 0x02, ..] => Some(vec![0x59, 0x02, 0xFF]),
+            [sid, ..] => Some(vec![0x7F, *sid, 0x11]),
+            _ => None,
+        }
+    }
+}
+
+/// Build handlers from `<ecu>_dump.json` files in `dir` for the ECUs that have one,
+/// with the built-in handler as fallback for everything but DID reads. Wrap them
+/// with `stateful` for session/security enforcement.
+pub fn load_dump_handlers(dir: &Path, ecus: &[EcuId]) -> Result<Vec<(EcuId, DumpHandler)>, String> {
+    let mut handlers = Vec::new();
+    for &ecu in ecus {
+        let path = dir.join(format!("{}_dump.json", ecu.name().to_lowercase()));
+        if !path.exists() {
+            continue;
+        }
+        let handler = DumpHandler::load(&path)?.with_fallback(create_raw_handler(ecu));
+        if handler.ecu().is_some_and(|id| id != ecu) {
+            return Err(format!(
+                "{} is a dump of {}, not {}",
+                path.display(),
+                handler.name,
+                ecu.name()
+            ));
+        }
+        handlers.push((ecu, handler));
+    }
+    Ok(handlers)
+}
+
+// ─── ECU Emulator Manager ────────────────────────────────────────────
+
+/// Raw write-only function pointer for the broadcast thread.
+struct RawWriteFn {
+    write_msgs: unsafe extern "system" fn(u32, *const PassThruMsg, *mut u32, u32) -> u32,
+}
+
+unsafe impl Send for RawWriteFn {}
+
+/// Background thread answering requests other modules send to emulated ECUs
+struct BusResponder {
+    running: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+/// Multi-ECU emulator manager.
+/// - Software routing: try_handle() serves emulated ECU responses locally
+/// - CAN broadcast: write-only thread sends the periodic frames of the current
+///   vehicle state (see `broadcast`) to simulate ECU presence
+/// - Bus responder: once attached to the ISO15765 router, answers the IMC's own
+///   requests to emulated ECUs (e.g. GWM CCF fetch) whenever they arrive
+pub struct EcuEmulatorManager {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    emulated_ecus: Vec<EcuId>,
+    /// Long-lived handlers (session/security state persists across requests),
+    /// plus any fault scenario, behind a bus software-routed requests go through
+    bus: Arc<EmulatedBus>,
+    /// Receives the responder's bus-emulation traffic
+    log_callback: Option<Arc<LogCallback>>,
+    responder: Mutex<Option<BusResponder>>,
+    /// Ignition position the broadcast simulates, switchable while it runs
+    vehicle_state: Arc<Mutex<VehicleState>>,
+    /// Frames the broadcast (and the pre-broadcast) sends
+    schedule: Vec<BroadcastMsg>,
+}
+
+impl EcuEmulatorManager {
+    /// Create emulator with software routing + CAN broadcast thread.
+    /// `can_channel_id` is a raw CAN channel (not ISO15765) for broadcast,
+    /// `schedule` the frames it sends (`default_schedule` or a `DbcSchedule`).
+    pub fn new_with_broadcast(
+        lib: &Arc<crate::j2534::dll::J2534Lib>,
+        can_channel_id: u32,
+        ecus: Vec<EcuId>,
+        schedule: Vec<BroadcastMsg>,
+    ) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let running_clone = running.clone();
+        let vehicle_state = Arc::new(Mutex::new(VehicleState::On));
+        let state_clone = vehicle_state.clone();
+
+        let write_fn = RawWriteFn {
+            write_msgs: lib.pass_thru_write_msgs,
+        };
+
+        let frames = schedule.clone();
+        let handle = thread::spawn(move || {
+            Self::broadcast_loop(write_fn, can_channel_id, frames, &running_clone, &state_clone);
+        });
+
+        Self {
+            running,
+            handle: Some(handle),
+            bus: Arc::new(EmulatedBus::new(&ecus)),
+            emulated_ecus: ecus,
+            log_callback: None,
+            responder: Mutex::new(None),
+            vehicle_state,
+            schedule,
+        }
+    }
+
+    /// Create software-routing-only emulator (no CAN broadcast).
+    pub fn new(ecus: Vec<EcuId>) -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            handle: None,
+            bus: Arc::new(EmulatedBus::new(&ecus)),
+            emulated_ecus: ecus,
+            log_callback: None,
+            responder: Mutex::new(None),
+            vehicle_state: Arc::new(Mutex::new(VehicleState::On)),
+            schedule: default_schedule(),
+        }
+    }
+
+    /// Frames for the pre-broadcast of a manager without a broadcast thread
+    /// (`new_with_broadcast` takes its schedule up front)
+    pub fn with_schedule(mut self, schedule: Vec<BroadcastMsg>) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Run every emulated ECU's responses through (its own copy of) `scenario`.
+    /// Fails while a bus responder is attached, since it shares the bus.
+    pub fn with_faults(mut self, scenario: &FaultScenario) -> Result<Self, String> {
+        let bus = Arc::get_mut(&mut self.bus)
+            .ok_or("Faults can't change while the bus responder is attached")?;
+        for entry in &mut bus.ecus {
+            entry.faults = Some(scenario.clone());
+        }
+        Ok(self)
+    }
+
+    /// Report the bus responder's traffic through `callback`
+    pub fn with_log_callback(mut self, callback: LogCallback) -> Self {
+        self.log_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Name of the active fault scenario, if any
+    pub fn faults(&self) -> Option<&str> {
+        self.emulated_ecus.first().and_then(|&ecu| self.bus.faults(ecu))
+    }
+
+    /// Whether requests to `tx_id` are answered by an emulated ECU
+    pub fn emulates(&self, tx_id: u32) -> bool {
+        self.emulated_ecus.iter().any(|e| e.tx_id() == tx_id)
+    }
+
+    /// The emulated ECUs as a `Channel`, with response timing and faults applied
+    pub fn bus(&self) -> &EmulatedBus {
+        &self.bus
+    }
+
+    /// Stop the emulator manager thread.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+        self.detach();
+    }
+
+    /// Start answering requests to emulated ECUs that arrive on `router`,
+    /// replacing any earlier responder. Sets up the reversed ISO15765 filters
+    /// (pattern=ECU TX, flow control=ECU RX) so the adapter catches e.g. the
+    /// IMC's ReadDID 0xEE00 to the GWM, and claims those CAN IDs so tester
+    /// ports never see them. Returns each ECU's filter result.
+    pub fn attach(&self, router: &ChannelRouter) -> Vec<(EcuId, Result<u32, String>)> {
+        self.detach();
+        let filters: Vec<_> = self
+            .emulated_ecus
+            .iter()
+            .map(|&ecu| (ecu, router.setup_iso15765_filter(ecu.rx_id(), ecu.tx_id())))
+            .collect();
+        let filter_ids: Vec<u32> = filters.iter().filter_map(|(_, r)| r.clone().ok()).collect();
+        let listener = router.listen(self.emulated_ecus.iter().map(|e| e.tx_id()).collect());
+        let running = Arc::new(AtomicBool::new(true));
+        let (bus, log, flag) = (self.bus.clone(), self.log_callback.clone(), running.clone());
+        let handle = thread::spawn(move || {
+            Self::respond_loop(&listener, &bus, log.as_deref(), &flag);
+            for id in filter_ids {
+                if let Err(e) = listener.stop_filter(id) {
+                    log::warn!("Removing bus responder filter {} failed: {}", id, e);
+                }
+            }
+        });
+        *self.responder.lock().unwrap() = Some(BusResponder { running, handle });
+        filters
+    }
+
+    /// Stop the bus responder and remove the filters `attach` set up
+    /// (its CAN IDs go back to the router)
+    pub fn detach(&self) {
+        let responder = self.responder.lock().unwrap().take();
+        if let Some(r) = responder {
+            r.running.store(false, Ordering::Relaxed);
+            let _ = r.handle.join();
+        }
+    }
+
+    /// Whether a bus responder is running
+    pub fn is_attached(&self) -> bool {
+        self.responder
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|r| !r.handle.is_finished())
+    }
+
+    /// Whether the CAN broadcast thread is running
+    pub fn is_broadcasting(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// Frames the broadcast sends
+    pub fn schedule(&self) -> &[BroadcastMsg] {
+        &self.schedule
+    }
+
+    pub fn vehicle_state(&self) -> VehicleState {
+        *self.vehicle_state.lock().unwrap()
+    }
+
+    /// Switch the simulated ignition position; the broadcast follows on its next frame
+    pub fn set_vehicle_state(&self, state: VehicleState) {
+        *self.vehicle_state.lock().unwrap() = state;
+    }
+
+    /// Get the list of emulated ECUs.
+    pub fn emulated_ecus(&self) -> &[EcuId] {
+        &self.emulated_ecus
+    }
+
+    /// Try to handle a UDS request locally if it targets an emulated ECU.
+    /// Returns Some(response) if handled, None if the ECU isn't emulated.
+    pub fn try_handle(&self, tx_id: u32, request: &[u8]) -> Option<Vec<u8>> {
+        self.bus.respond(tx_id, request).map(|(_, response)| response)
+    }
+
+    /// Try to handle a UDS request addressed to an emulated ECU on the CAN bus.
+    /// Uses RX address matching (for when IMC sends requests to GWM/BCM).
+    /// tx_on_bus is the CAN ID the request arrived on (e.g. 0x716 for GWM).
+    /// Returns Some((response_can_id, response_payload)) if handled.
+    pub fn try_handle_bus_request(&self, request_can_id: u32, request: &[u8]) -> Option<(u32, Vec<u8>)> {
+        self.bus.respond(request_can_id, request)
+    }
+
+    /// Responder loop: answers each claimed request as the addressed ECU.
+    /// Ends when stopped or when the router closes under it.
+    fn respond_loop(
+        listener: &BusListener,
+        bus: &EmulatedBus,
+        on_log: Option<&LogCallback>,
+        running: &AtomicBool,
+    ) {
+        let emit = |direction: LogDirection, data: &[u8], description: String| {
+            if let Some(cb) = on_log {
+                cb(LogEntry {
+                    direction,
+                    data_hex: data
+                        .iter()
+                        .map(|b| format!("{:02X}", b))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    timestamp: chrono::Local::now().format("%H:%M:%S%.3f").to_string(),
+                    description,
+                });
+            }
+        };
+        while running.load(Ordering::Relaxed) {
+            let msgs = match listener.read(100) {
+                Ok(msgs) => msgs,
+                Err(_) => break,
+            };
+            for m in msgs {
+                let request = m.payload();
+                if request.is_empty() {
+                    continue;
+                }
+                let Some((rx_id, frames)) = bus.respond_scheduled(m.can_id(), request) else {
+                    continue;
+                };
+                emit(LogDirection::Rx, request, format!("BUS→EMU 0x{:03X}", m.can_id()));
+                let received = Instant::now();
+                for (offset, response) in frames {
+                    thread::sleep((received + offset).saturating_duration_since(Instant::now()));
+                    match listener.send(&PassThruMsg::new_iso15765(rx_id, &response), 2000) {
+                        Ok(()) => {
+                            emit(LogDirection::Tx, &response, format!("EMU→BUS 0x{:03X}", rx_id))
+                        }
+                        Err(e) => log::warn!("EMU response send failed: {}", e),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write-only broadcast loop: sends CAN messages to simulate ECU presence.
+    /// Runs on a separate raw CAN channel — never reads, so it can't steal
+    /// ISO15765 responses from the client thread.
+    fn broadcast_loop(
+        fns: RawWriteFn,
+        can_channel_id: u32,
+        schedule: Vec<BroadcastMsg>,
+        running: &AtomicBool,
+        vehicle_state: &Mutex<VehicleState>,
+    ) {
+        // Small delay to let the channel settle after connect
+        thread::sleep(std::time::Duration::from_millis(100));
+
+        let scheduler = BroadcastScheduler::new(schedule, Instant::now());
+        run_broadcast(scheduler, running, vehicle_state, |can_id, data| {
+            let mut msg = PassThruMsg::default();
+            msg.protocol_id = 5; // PROTOCOL_CAN
+            msg.data[0..4].copy_from_slice(&can_id.to_be_bytes());
+            msg.data[4..12].copy_from_slice(data);
+            msg.data_size = 12; // 4 bytes CAN ID + 8 bytes data
+
+            let mut num_msgs: u32 = 1;
+            let _ = unsafe { (fns.write_msgs)(can_channel_id, &msg, &mut num_msgs, 50) };
+        });
+    }
+}
+
+/// Drive `scheduler` until `running` clears, writing each due frame for the
+/// current vehicle state
+pub(crate) fn run_broadcast(
+    mut scheduler: BroadcastScheduler,
+    running: &AtomicBool,
+    vehicle_state: &Mutex<VehicleState>,
+    mut write: impl FnMut(u32, &[u8; 8]),
+) {
+    while running.load(Ordering::Relaxed) {
+        let state = *vehicle_state.lock().unwrap();
+        for (can_id, data) in scheduler.due(Instant::now(), state) {
+            write(can_id, &data);
+        }
+        // Wake for the next frame, but often enough to notice a stop
+        let now = Instant::now();
+        let wait = scheduler
+            .next_due()
+            .map_or(Duration::from_millis(10), |t| t.saturating_duration_since(now));
+        thread::sleep(wait.min(Duration::from_millis(10)));
+    }
+}
+
+impl Drop for EcuEmulatorManager {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Create the default handler for an ECU ID
+pub fn create_handler(ecu: EcuId) -> Box<dyn EcuHandler> {
+    stateful(ecu, create_raw_handler(ecu))
+}
+
+/// Put `handler` behind the session/security state machine with `ecu`'s policy
+pub fn stateful(ecu: EcuId, handler: Box<dyn EcuHandler>) -> Box<dyn EcuHandler> {
+    Box::new(StatefulEcu::new(handler, EcuPolicy::for_ecu(ecu)))
+}
+
+/// The ECU's response logic alone, without session/security enforcement
+pub fn create_raw_handler(ecu: EcuId) -> Box<dyn EcuHandler> {
+    match ecu {
+        EcuId::Imc => Box::new(ImcHandler::new()),
+        EcuId::Bcm => Box::new(BcmHandler::new()),
+        EcuId::Gwm => Box::new(GwmHandler::new()),
+        EcuId::Ipc => Box::new(IpcHandler),
+    }
+}
+
+// ─── Fault Injection ─────────────────────────────────────────────────
+
+/// Frames an ECU puts on the bus for one request, as (offset from now, payload)
+pub type Frames = Vec<(Duration, Vec<u8>)>;
+
+/// One misbehaviour of an emulated ECU, applied to the response of a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fault {
+    /// Hold the answer back for `ms`
+    Delay { ms: u64 },
+    /// Send `count` × `7F sid 78` (responsePending), `interval_ms` apart, before the answer
+    Pending { count: u32, interval_ms: u64 },
+    /// Answer `7F sid 21` (busyRepeatRequest) without acting on the request
+    Busy,
+    /// Act on the request but never answer
+    Drop,
+    /// Send the answer a second time, `after_ms` later
+    Duplicate { after_ms: u64 },
+    /// Cut multi-frame answers (more than 7 bytes) down to `len` bytes
+    Truncate { len: usize },
+    /// Send an unrelated `7F service nrc` ahead of the answer
+    StaleNrc { service: u8, nrc: u8 },
+}
+
+/// A fault and the requests it applies to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaultRule {
+    /// Only requests for this service ID (all services when absent)
+    #[serde(default)]
+    pub service: Option<u8>,
+    /// Fire on every Nth matching request (1 = always)
+    #[serde(default = "FaultRule::always")]
+    pub every: u32,
+    #[serde(flatten)]
+    pub fault: Fault,
+}
+
+impl FaultRule {
+    fn always() -> u32 {
+        1
+    }
+
+    pub fn new(fault: Fault) -> Self {
+        Self { service: None, every: 1, fault }
+    }
+
+    pub fn on_service(mut self, service: u8) -> Self {
+        self.service = Some(service);
+        self
+    }
+
+    pub fn every(mut self, n: u32) -> Self {
+        self.every = n.max(1);
+        self
+    }
+}
+
+/// A named set of fault rules attached to an emulated ECU.
+/// Counts matching requests so `every` rules fire intermittently.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FaultScenario {
+    pub name: String,
+    pub rules: Vec<FaultRule>,
+    #[serde(skip)]
+    hits: Mutex<Vec<u32>>,
+}
+
+impl Clone for FaultScenario {
+    /// Same rules, fresh hit counters
+    fn clone(&self) -> Self {
+        Self::new(&self.name, self.rules.clone())
+    }
+}
+
+/// Built-in scenarios, by name
+pub const FAULT_PRESETS: &[&str] = &[
+    "slow",
+    "pending",
+    "busy",
+    "drop",
+    "duplicate",
+    "truncate",
+    "stale-nrc",
+    "flaky-imc",
+];
+
+impl FaultScenario {
+    pub fn new(name: &str, rules: Vec<FaultRule>) -> Self {
+        Self { name: name.to_string(), rules, hits: Mutex::new(Vec::new()) }
+    }
+
+    /// One of `FAULT_PRESETS`
+    pub fn preset(name: &str) -> Option<Self> {
+        let rules = match name {
+            "slow" => vec![FaultRule::new(Fault::Delay { ms: 1500 })],
+            "pending" => vec![FaultRule::new(Fault::Pending { count: 3, interval_ms: 200 })],
+            "busy" => vec![FaultRule::new(Fault::Busy).every(2)],
+            "drop" => vec![FaultRule::new(Fault::Drop).every(3)],
+            "duplicate" => vec![FaultRule::new(Fault::Duplicate { after_ms: 20 })],
+            "truncate" => vec![FaultRule::new(Fault::Truncate { len: 7 })],
+            "stale-nrc" => vec![FaultRule::new(Fault::StaleNrc { service: 0x10, nrc: 0x12 })],
+            // What we've seen from real IMCs: long routines answered after a
+            // string of 0x78s, reads refused as busy now and then, and a
+            // leftover 7F 10 12 from the last session change
+            "flaky-imc" => vec![
+                FaultRule::new(Fault::Pending { count: 5, interval_ms: 300 }).on_service(0x31),
+                FaultRule::new(Fault::Busy).on_service(0x22).every(4),
+                FaultRule::new(Fault::StaleNrc { service: 0x10, nrc: 0x12 }).on_service(0x27),
+            ],
+            _ => return None,
+        };
+        Some(Self::new(name, rules))
+    }
+
+    /// A preset name, or a JSON scenario (`{"name": .., "rules": [{"kind": "busy", ..}]}`)
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        if spec.starts_with('{') {
+            return serde_json::from_str(spec).map_err(|e| format!("Bad fault scenario: {}", e));
+        }
+        Self::preset(spec).ok_or_else(|| {
+            format!("Unknown fault scenario '{}' (expected {})", spec, FAULT_PRESETS.join(", "))
+        })
+    }
+
+    /// Run `respond` for `request` under this scenario. Returns the frames to put on
+    /// the bus as (offset from now, payload), in order.
+    pub fn apply(
+        &self,
+        request: &[u8],
+        respond: impl FnOnce() -> Option<Vec<u8>>,
+    ) -> Frames {
+        let Some(&sid) = request.first() else {
+            return Vec::new();
+        };
+        let active: Vec<&Fault> = {
+            let mut hits = self.hits.lock().unwrap();
+            hits.resize(self.rules.len(), 0);
+            self.rules
+                .iter()
+                .zip(hits.iter_mut())
+                .filter(|(rule, _)| rule.service.is_none_or(|s| s == sid))
+                .filter_map(|(rule, hit)| {
+                    *hit += 1;
+                    (*hit % rule.every.max(1) == 0).then_some(&rule.fault)
+                })
+                .collect()
+        };
+
+        let response = if active.contains(&&Fault::Busy) {
+            Some(vec![0x7F, sid, 0x21])
+        } else {
+            respond()
+        };
+        let Some(mut response) = response else {
+            return Vec::new();
+        };
+
+        let mut at = Duration::ZERO;
+        let mut frames = Vec::new();
+        let mut duplicate = None;
+        let mut dropped = false;
+        for fault in active {
+            match *fault {
+                Fault::Delay { ms } => at += Duration::from_millis(ms),
+                Fault::Pending { count, interval_ms } => {
+                    for _ in 0..count {
+                        frames.push((at, vec![0x7F, sid, 0x78]));
+                        at += Duration::from_millis(interval_ms);
+                    }
+                }
+                Fault::Busy => {}
+                Fault::Drop => dropped = true,
+                Fault::Duplicate { after_ms } => duplicate = Some(Duration::from_millis(after_ms)),
+                Fault::Truncate { len } => {
+                    if response.len() > 7 {
+                        response.truncate(len.max(1));
+                    }
+                }
+                Fault::StaleNrc { service, nrc } => frames.push((at, vec![0x7F, service, nrc])),
+            }
         }
+        if dropped {
+            return frames;
+        }
+        if let Some(after) = duplicate {
+            frames.push((at + after, response.clone()));
+        }
+        frames.push((at, response));
+        frames.sort_by_key(|(offset, _)| *offset);
+        frames
     }
 }
 
-// ─── ECU Emulator Manager ────────────────────────────────────────────
-
-/// CAN broadcast messages captured from a real car that are NOT present on a bench
-/// with only the IMC. These simulate BCM/GWM/IPC presence on the CAN bus.
-/// Format: (CAN ID, 8-byte data)
-pub(crate) const BROADCAST_MSGS: &[(u32, [u8; 8])] = &[
-    // From car CAN dump — IDs absent in bench-only dump
-    (0x070, [0xFF, 0x87, 0xD0, 0xFE, 0xFE, 0x3F, 0xFF, 0x03]),
-    (0x0B0, [0x00, 0x04, 0x32, 0x03, 0xF8, 0x0D, 0x35, 0x00]),
-    (0x0D0, [0xEC, 0x00, 0x42, 0x50, 0xE2, 0x69, 0xA8, 0x84]),
-    (0x154, [0x27, 0xC7, 0x07, 0xED, 0x07, 0xD9, 0x07, 0xBD]),
-    (0x1D0, [0x62, 0xFE, 0x00, 0x10, 0x80, 0x00, 0x80, 0x00]),
-    (0x200, [0x01, 0x00, 0x00, 0x00, 0x03, 0x5E, 0x0E, 0x00]),
-    (0x270, [0x00, 0xE8, 0x50, 0x00, 0x83, 0xFE, 0x03, 0x00]),
-    (0x280, [0x00, 0x00, 0x03, 0xFE, 0x01, 0xFE, 0x13, 0xFE]),
-    (0x2A0, [0x80, 0x81, 0x40, 0x00, 0x5D, 0x44, 0x66, 0x0D]),
-    (0x2C0, [0x30, 0x00, 0x7D, 0xD0, 0x01, 0x40, 0x9A, 0x80]),
-    (0x300, [0x01, 0x6B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
-    // Network Management heartbeats — IMC checks for these to know other ECUs are alive
-    (0x400, [0x08, 0x01, 0x00, 0x00, 0x16, 0x04, 0x00, 0x01]), // BCM NM
-    (0x407, [0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]), // GWM NM
-    (0x460, [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]), // IPC NM
-    // Common IDs with car-specific data (bench has different values)
-    (0x030, [0x04, 0x00, 0x00, 0x00, 0x00, 0x1F, 0xFE, 0x70]),
-    (0x130, [0x02, 0x00, 0x50, 0x04, 0x04, 0x00, 0x00, 0x00]),
-    (0x140, [0x00, 0x6D, 0x83, 0x00, 0x00, 0x7F, 0x80, 0x00]),
-];
+// ─── Emulated Bus ────────────────────────────────────────────────────
 
-/// Raw write-only function pointer for the broadcast thread.
-struct RawWriteFn {
-    write_msgs: unsafe extern "system" fn(u32, *const PassThruMsg, *mut u32, u32) -> u32,
+/// An ECU on the emulated bus: its handler, a fixed response delay and
+/// optional fault scenario
+struct BusEcu {
+    id: EcuId,
+    handler: Box<dyn EcuHandler>,
+    delay: Duration,
+    faults: Option<FaultScenario>,
 }
 
-unsafe impl Send for RawWriteFn {}
-
-/// Multi-ECU emulator manager.
-/// - Software routing: try_handle() serves emulated ECU responses locally
-/// - CAN broadcast: write-only thread sends periodic messages to simulate ECU presence
-pub struct EcuEmulatorManager {
-    running: Arc<AtomicBool>,
-    handle: Option<JoinHandle<()>>,
-    emulated_ecus: Vec<EcuId>,
+impl BusEcu {
+    /// Frames this ECU puts on the bus for `request`, as (offset from now, payload),
+    /// with its response delay and fault scenario applied
+    fn answer(&self, request: &[u8]) -> Frames {
+        let respond = || self.handler.build_response(request);
+        let frames = match &self.faults {
+            Some(scenario) => scenario.apply(request, respond),
+            None => respond().map(|r| (Duration::ZERO, r)).into_iter().collect(),
+        };
+        frames.into_iter().map(|(offset, payload)| (self.delay + offset, payload)).collect()
+    }
 }
 
-impl EcuEmulatorManager {
-    /// Create emulator with software routing + CAN broadcast thread.
-    /// `can_channel_id` is a raw CAN channel (not ISO15765) for broadcast.
-    pub fn new_with_broadcast(
-        lib: &Arc<crate::j2534::dll::J2534Lib>,
-        can_channel_id: u32,
-        ecus: Vec<EcuId>,
-    ) -> Self {
-        let running = Arc::new(AtomicBool::new(true));
-        let running_clone = running.clone();
-
-        let write_fn = RawWriteFn {
-            write_msgs: lib.pass_thru_write_msgs,
-        };
+/// A `Channel` with no adapter behind it: requests sent to an emulated ECU are
+/// answered by its handler after an optional per-ECU delay, through its fault
+/// scenario if one is attached. Backs `--emulate` connections (no J2534 DLL),
+/// bench-mode software routing and routing tests.
+pub struct EmulatedBus {
+    ecus: Vec<BusEcu>,
+    pending: Mutex<Vec<(Instant, PassThruMsg)>>,
+    /// Answers to ECUs' bus requests still in flight
+    bus_answers: Mutex<Vec<BusAnswer>>,
+}
 
-        let handle = thread::spawn(move || {
-            Self::broadcast_loop(write_fn, can_channel_id, &running_clone);
-        });
+/// Answer to an ECU's bus request, handed over once due
+struct BusAnswer {
+    due: Instant,
+    to: EcuId,
+    rx_id: u32,
+    payload: Vec<u8>,
+}
 
+impl EmulatedBus {
+    pub fn new(ecus: &[EcuId]) -> Self {
         Self {
-            running,
-            handle: Some(handle),
-            emulated_ecus: ecus,
+            ecus: ecus
+                .iter()
+                .map(|&id| BusEcu {
+                    id,
+                    handler: create_handler(id),
+                    delay: Duration::ZERO,
+                    faults: None,
+                })
+                .collect(),
+            pending: Mutex::new(Vec::new()),
+            bus_answers: Mutex::new(Vec::new()),
         }
     }
 
-    /// Create software-routing-only emulator (no CAN broadcast).
-    pub fn new(ecus: Vec<EcuId>) -> Self {
-        Self {
-            running: Arc::new(AtomicBool::new(false)),
-            handle: None,
-            emulated_ecus: ecus,
+    /// Delay every response from `ecu` (e.g. a module busy with a long routine)
+    pub fn with_response_delay(mut self, ecu: EcuId, delay: Duration) -> Self {
+        for entry in self.ecus.iter_mut().filter(|e| e.id == ecu) {
+            entry.delay = delay;
         }
-    }
-
-    /// Stop the emulator manager thread.
-    pub fn stop(&mut self) {
-        self.running.store(false, Ordering::Relaxed);
-        if let Some(h) = self.handle.take() {
-            let _ = h.join();
+        self
+    }
+
+    /// Answer for `ecu` with `handler` instead of its built-in one
+    pub fn with_handler(mut self, ecu: EcuId, handler: Box<dyn EcuHandler>) -> Self {
+        match self.ecus.iter_mut().find(|e| e.id == ecu) {
+            Some(entry) => entry.handler = handler,
+            None => self.ecus.push(BusEcu {
+                id: ecu,
+                handler,
+                delay: Duration::ZERO,
+                faults: None,
+            }),
         }
+        self
     }
 
-    /// Get the list of emulated ECUs.
-    pub fn emulated_ecus(&self) -> &[EcuId] {
-        &self.emulated_ecus
-    }
-
-    /// Try to handle a UDS request locally if it targets an emulated ECU.
-    /// Returns Some(response) if handled, None if the ECU isn't emulated.
-    pub fn try_handle(&self, tx_id: u32, request: &[u8]) -> Option<Vec<u8>> {
-        for ecu in &self.emulated_ecus {
-            if ecu.tx_id() == tx_id {
-                return create_handler(*ecu).build_response(request);
-            }
+    /// Run `ecu`'s responses through `scenario`
+    pub fn with_faults(mut self, ecu: EcuId, scenario: FaultScenario) -> Self {
+        if let Some(entry) = self.ecus.iter_mut().find(|e| e.id == ecu) {
+            entry.faults = Some(scenario);
         }
-        None
-    }
-
-    /// Try to handle a UDS request addressed to an emulated ECU on the CAN bus.
-    /// Uses RX address matching (for when IMC sends requests to GWM/BCM).
-    /// tx_on_bus is the CAN ID the request arrived on (e.g. 0x716 for GWM).
-    /// Returns Some((response_can_id, response_payload)) if handled.
-    pub fn try_handle_bus_request(&self, request_can_id: u32, request: &[u8]) -> Option<(u32, Vec<u8>)> {
-        for ecu in &self.emulated_ecus {
-            if ecu.tx_id() == request_can_id {
-                if let Some(response) = create_handler(*ecu).build_response(request) {
-                    return Some((ecu.rx_id(), response));
+        self
+    }
+
+    pub fn ecus(&self) -> Vec<EcuId> {
+        self.ecus.iter().map(|e| e.id).collect()
+    }
+
+    /// Name of the fault scenario attached to `ecu`, if any
+    pub fn faults(&self, ecu: EcuId) -> Option<&str> {
+        self.ecus
+            .iter()
+            .find(|e| e.id == ecu)
+            .and_then(|e| e.faults.as_ref())
+            .map(|f| f.name.as_str())
+    }
+
+    /// Pass requests ECUs make of each other (IMC → GWM CCF fetch) to their
+    /// targets, whose answers arrive after the target's delay and faults.
+    /// Requests to modules not on the bus go out as frames, for a bench-mode
+    /// responder on the other side to answer.
+    fn deliver_bus_requests(&self) {
+        let now = Instant::now();
+        for entry in &self.ecus {
+            for (tx_id, request) in entry.handler.bus_requests() {
+                match self.respond_scheduled(tx_id, &request) {
+                    Some((rx_id, frames)) => {
+                        let mut answers = self.bus_answers.lock().unwrap();
+                        for (offset, payload) in frames {
+                            let due = now + offset;
+                            answers.push(BusAnswer { due, to: entry.id, rx_id, payload });
+                        }
+                    }
+                    None => self
+                        .pending
+                        .lock()
+                        .unwrap()
+                        .push((now, PassThruMsg::new_iso15765(tx_id, &request))),
                 }
             }
         }
-        None
+        self.deliver_bus_answers();
+    }
+
+    /// Hand the bus-request answers that are due to the ECUs that asked
+    fn deliver_bus_answers(&self) {
+        let now = Instant::now();
+        let due: Vec<_> = {
+            let mut answers = self.bus_answers.lock().unwrap();
+            let (mut due, later): (Vec<_>, Vec<_>) =
+                answers.drain(..).partition(|a| a.due <= now);
+            *answers = later;
+            due.sort_by_key(|a| a.due);
+            due
+        };
+        for answer in due {
+            for entry in self.ecus.iter().filter(|e| e.id == answer.to) {
+                entry.handler.bus_response(answer.rx_id, &answer.payload);
+            }
+        }
     }
 
-    /// Write-only broadcast loop: sends CAN messages to simulate ECU presence.
-    /// Runs on a separate raw CAN channel — never reads, so it can't steal
-    /// ISO15765 responses from the client thread.
-    fn broadcast_loop(fns: RawWriteFn, can_channel_id: u32, running: &AtomicBool) {
-        // Small delay to let the channel settle after connect
-        thread::sleep(std::time::Duration::from_millis(100));
-
-        while running.load(Ordering::Relaxed) {
-            for &(can_id, ref data) in BROADCAST_MSGS {
-                if !running.load(Ordering::Relaxed) {
-                    break;
-                }
+    /// Answer a request to `tx_id` immediately, skipping delays and faults.
+    /// Returns (response CAN ID, payload).
+    pub fn respond(&self, tx_id: u32, request: &[u8]) -> Option<(u32, Vec<u8>)> {
+        let entry = self.ecus.iter().find(|e| e.id.tx_id() == tx_id)?;
+        entry.handler.build_response(request).map(|r| (entry.id.rx_id(), r))
+    }
 
-                let mut msg = PassThruMsg::default();
-                msg.protocol_id = 5; // PROTOCOL_CAN
-                msg.data[0..4].copy_from_slice(&can_id.to_be_bytes());
-                msg.data[4..12].copy_from_slice(data);
-                msg.data_size = 12; // 4 bytes CAN ID + 8 bytes data
+    /// Answer a request to `tx_id` as it would come over the bus, with the ECU's
+    /// delay and faults applied. Returns (response CAN ID, frames as offset from now
+    /// and payload); no frames when the answer is dropped.
+    pub fn respond_scheduled(
+        &self,
+        tx_id: u32,
+        request: &[u8],
+    ) -> Option<(u32, Frames)> {
+        let entry = self.ecus.iter().find(|e| e.id.tx_id() == tx_id)?;
+        Some((entry.id.rx_id(), entry.answer(request)))
+    }
+}
 
-                let mut num_msgs: u32 = 1;
-                let _ = unsafe { (fns.write_msgs)(can_channel_id, &msg, &mut num_msgs, 50) };
+impl Channel for EmulatedBus {
+    fn send(&self, msg: &PassThruMsg, _timeout_ms: u32) -> Result<(), String> {
+        self.deliver_bus_answers();
+        let tx = msg.can_id();
+        let request = msg.payload();
+        if !self.ecus.iter().any(|e| e.id.tx_id() == tx) {
+            // Not a request to us: an answer to one of our ECUs' bus requests
+            for entry in &self.ecus {
+                entry.handler.bus_response(tx, request);
             }
-
-            // ~100ms cycle matches typical CAN bus timing
-            thread::sleep(std::time::Duration::from_millis(100));
+        }
+        for entry in self.ecus.iter().filter(|e| e.id.tx_id() == tx) {
+            let frames = entry.answer(request);
+            let now = Instant::now();
+            let mut pending = self.pending.lock().unwrap();
+            for (offset, payload) in frames {
+                pending.push((now + offset, PassThruMsg::new_iso15765(entry.id.rx_id(), &payload)));
+            }
+        }
+        self.deliver_bus_requests();
+        Ok(())
+    }
+
+    fn read(&self, timeout_ms: u32) -> Result<Vec<PassThruMsg>, String> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+        loop {
+            self.deliver_bus_answers();
+            let now = Instant::now();
+            let mut pending = self.pending.lock().unwrap();
+            let (mut due, later): (Vec<_>, Vec<_>) =
+                pending.drain(..).partition(|(t, _)| *t <= now);
+            *pending = later;
+            if !due.is_empty() || now >= deadline {
+                due.sort_by_key(|(t, _)| *t);
+                return Ok(due.into_iter().map(|(_, m)| m).collect());
+            }
+            drop(pending);
+            thread::sleep((deadline - now).min(Duration::from_millis(2)));
         }
     }
-}
 
-impl Drop for EcuEmulatorManager {
-    fn drop(&mut self) {
-        self.stop();
+    fn setup_iso15765_filter(&self, _tx_id: u32, _rx_id: u32) -> Result<u32, String> {
+        Ok(1)
     }
-}
 
-/// Create the default handler for an ECU ID
-pub fn create_handler(ecu: EcuId) -> Box<dyn EcuHandler> {
-    match ecu {
-        EcuId::Bcm => Box::new(BcmHandler),
-        EcuId::Gwm => Box::new(GwmHandler),
-        EcuId::Ipc => Box::new(IpcHandler),
+    fn stop_filter(&self, _filter_id: u32) -> Result<(), String> {
+        Ok(())
     }
 }
 
@@ -466,6 +1951,8 @@ mod tests {
 
     #[test]
     fn test_ecu_id_addresses() {
+        assert_eq!(EcuId::Imc.tx_id(), 0x7B3);
+        assert_eq!(EcuId::Imc.rx_id(), 0x7BB);
         assert_eq!(EcuId::Bcm.tx_id(), 0x726);
         assert_eq!(EcuId::Bcm.rx_id(), 0x72E);
         assert_eq!(EcuId::Gwm.tx_id(), 0x716);
@@ -487,13 +1974,15 @@ mod tests {
         assert_eq!(EcuId::from_str("BCM"), Some(EcuId::Bcm));
         assert_eq!(EcuId::from_str("gwm"), Some(EcuId::Gwm));
         assert_eq!(EcuId::from_str("ipc"), Some(EcuId::Ipc));
+        assert_eq!(EcuId::from_str("IMC"), Some(EcuId::Imc));
         assert_eq!(EcuId::from_str("unknown"), None);
     }
 
     #[test]
     fn test_ecu_id_all() {
         let all = EcuId::all();
-        assert_eq!(all.len(), 3);
+        assert_eq!(all.len(), 4);
+        assert!(all.contains(&EcuId::Imc));
         assert!(all.contains(&EcuId::Bcm));
         assert!(all.contains(&EcuId::Gwm));
         assert!(all.contains(&EcuId::Ipc));
@@ -503,28 +1992,28 @@ mod tests {
 
     #[test]
     fn test_bcm_handler_tester_present() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x3E, 0x00]).unwrap();
         assert_eq!(resp, vec![0x7E, 0x00]);
     }
 
     #[test]
     fn test_bcm_handler_diag_session() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x10, 0x01]).unwrap();
         assert_eq!(resp, vec![0x50, 0x01, 0x00, 0x19, 0x01, 0xF4]);
     }
 
     #[test]
     fn test_bcm_handler_diag_session_extended() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x10, 0x03]).unwrap();
         assert_eq!(resp, vec![0x50, 0x03, 0x00, 0x19, 0x01, 0xF4]);
     }
 
     #[test]
     fn test_bcm_handler_security_access() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x27, 0x11]).unwrap();
         assert_eq!(resp, vec![0x67, 0x11, 0x00, 0x00, 0x00]);
     }
@@ -532,42 +2021,42 @@ mod tests {
     #[test]
     fn test_bcm_handler_voltage_returns_nrc() {
         // Real BCM returns 0x31 for 402A — battery data is on GWM
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0x40, 0x2A]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x22, 0x31]);
     }
 
     #[test]
     fn test_bcm_handler_soc_returns_nrc() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0x40, 0x28]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x22, 0x31]);
     }
 
     #[test]
     fn test_bcm_handler_temp_returns_nrc() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0x40, 0x29]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x22, 0x31]);
     }
 
     #[test]
     fn test_bcm_handler_door_status_returns_nrc() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0x40, 0x30]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x22, 0x31]);
     }
 
     #[test]
     fn test_bcm_handler_fuel_level_returns_nrc() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0x40, 0x32]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x22, 0x31]);
     }
 
     #[test]
     fn test_bcm_handler_ccf_block() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0xDE, 0x00]).unwrap();
         assert_eq!(resp[0], 0x62);
         assert_eq!(resp[1], 0xDE);
@@ -577,7 +2066,7 @@ mod tests {
 
     #[test]
     fn test_bcm_handler_vin() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0xF1, 0x90]).unwrap();
         assert_eq!(resp[0], 0x62);
         let vin = String::from_utf8_lossy(&resp[3..]);
@@ -586,7 +2075,7 @@ mod tests {
 
     #[test]
     fn test_bcm_handler_sw_part() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0xF1, 0x88]).unwrap();
         assert_eq!(resp[0], 0x62);
         let part = String::from_utf8_lossy(&resp[3..])
@@ -597,7 +2086,7 @@ mod tests {
 
     #[test]
     fn test_bcm_handler_ecu_serial() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0xF1, 0x8C]).unwrap();
         assert_eq!(resp[0], 0x62);
         let serial = String::from_utf8_lossy(&resp[3..]);
@@ -606,7 +2095,7 @@ mod tests {
 
     #[test]
     fn test_bcm_handler_hw_part() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0xF1, 0x13]).unwrap();
         assert_eq!(resp[0], 0x62);
         let part = String::from_utf8_lossy(&resp[3..])
@@ -617,42 +2106,42 @@ mod tests {
 
     #[test]
     fn test_bcm_handler_ecu_reset() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x11, 0x01]).unwrap();
         assert_eq!(resp, vec![0x51, 0x01]);
     }
 
     #[test]
     fn test_bcm_handler_comm_control() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x28, 0x01, 0x01]).unwrap();
         assert_eq!(resp, vec![0x68, 0x01]);
     }
 
     #[test]
     fn test_bcm_handler_write_did() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x2E, 0x40, 0x30, 0x01]).unwrap();
         assert_eq!(resp, vec![0x6E, 0x40, 0x30]);
     }
 
     #[test]
     fn test_bcm_handler_routine_control() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x31, 0x01, 0x60, 0x3E]).unwrap();
         assert_eq!(resp, vec![0x71, 0x01, 0x60, 0x3E]);
     }
 
     #[test]
     fn test_bcm_handler_unknown_did() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x22, 0xFF, 0xFF]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x22, 0x31]);
     }
 
     #[test]
     fn test_bcm_handler_unknown_service() {
-        let handler = BcmHandler;
+        let handler = BcmHandler::new();
         let resp = handler.build_response(&[0x99]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x99, 0x11]);
     }
@@ -661,21 +2150,21 @@ mod tests {
 
     #[test]
     fn test_gwm_handler_tester_present() {
-        let handler = GwmHandler;
+        let handler = GwmHandler::new();
         let resp = handler.build_response(&[0x3E, 0x00]).unwrap();
         assert_eq!(resp, vec![0x7E, 0x00]);
     }
 
     #[test]
     fn test_gwm_handler_diag_session() {
-        let handler = GwmHandler;
+        let handler = GwmHandler::new();
         let resp = handler.build_response(&[0x10, 0x03]).unwrap();
         assert_eq!(resp, vec![0x50, 0x03, 0x00, 0x19, 0x01, 0xF4]);
     }
 
     #[test]
     fn test_gwm_handler_vin() {
-        let handler = GwmHandler;
+        let handler = GwmHandler::new();
         let resp = handler.build_response(&[0x22, 0xF1, 0x90]).unwrap();
         assert_eq!(resp[0], 0x62);
         let vin = String::from_utf8_lossy(&resp[3..]);
@@ -684,7 +2173,7 @@ mod tests {
 
     #[test]
     fn test_gwm_handler_ccf_block() {
-        let handler = GwmHandler;
+        let handler = GwmHandler::new();
         let resp = handler.build_response(&[0x22, 0xEE, 0x00]).unwrap();
         assert_eq!(resp[0], 0x62);
         assert_eq!(resp[1], 0xEE);
@@ -692,9 +2181,22 @@ mod tests {
         assert_eq!(resp.len(), 3 + 2000); // 2000 bytes CCF data
     }
 
+    #[test]
+    fn test_gwm_handler_stores_writes() {
+        let handler = GwmHandler::new();
+        assert_eq!(handler.build_response(&[0x2E, 0xEE, 0x00]), Some(vec![0x7F, 0x2E, 0x13]));
+        let mut write = vec![0x2E, 0xEE, 0x00];
+        write.extend_from_slice(&GWM_CCF_RAW[..100]);
+        assert_eq!(handler.build_response(&write), Some(vec![0x6E, 0xEE, 0x00]));
+        let resp = handler.build_response(&[0x22, 0xEE, 0x00]).unwrap();
+        assert_eq!(&resp[3..], &GWM_CCF_RAW[..100]);
+        // Other DIDs keep their built-in data
+        assert_eq!(handler.build_response(&[0x22, 0x40, 0x28]), Some(vec![0x62, 0x40, 0x28, 0x55]));
+    }
+
     #[test]
     fn test_gwm_handler_battery_voltage() {
-        let handler = GwmHandler;
+        let handler = GwmHandler::new();
         let resp = handler.build_response(&[0x22, 0x40, 0x2A]).unwrap();
         assert_eq!(resp[0], 0x62);
         assert_eq!(resp[1], 0x40);
@@ -703,14 +2205,14 @@ mod tests {
 
     #[test]
     fn test_gwm_handler_unknown_did() {
-        let handler = GwmHandler;
+        let handler = GwmHandler::new();
         let resp = handler.build_response(&[0x22, 0xFF, 0xFF]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x22, 0x31]); // requestOutOfRange
     }
 
     #[test]
     fn test_gwm_handler_unknown_service() {
-        let handler = GwmHandler;
+        let handler = GwmHandler::new();
         let resp = handler.build_response(&[0x99]).unwrap();
         assert_eq!(resp, vec![0x7F, 0x99, 0x11]);
     }
@@ -738,6 +2240,408 @@ mod tests {
         assert_eq!(resp, vec![0x7F, 0x31, 0x11]);
     }
 
+    // ─── IMC Handler tests ─────────────────────────────────────
+
+    /// Extended session + level 0x11 unlock with the real KeyGenMkI key
+    fn imc_unlock(h: &dyn EcuHandler) {
+        assert_eq!(h.build_response(&[0x10, 0x03]).unwrap()[0], 0x50);
+        let seed_resp = h.build_response(&[0x27, 0x11]).unwrap();
+        assert_eq!(&seed_resp[..2], &[0x67, 0x11]);
+        let seed = u32::from_be_bytes([0, seed_resp[2], seed_resp[3], seed_resp[4]]);
+        assert_ne!(seed, 0);
+        let key = keygen_mki(seed, &DC0314_CONSTANTS).to_be_bytes();
+        assert_eq!(
+            h.build_response(&[0x27, 0x12, key[1], key[2], key[3]]),
+            Some(vec![0x67, 0x12])
+        );
+    }
+
+    #[test]
+    fn test_imc_handler_status_needs_extended_session() {
+        let h = create_handler(EcuId::Imc);
+        assert_eq!(h.build_response(&[0x22, 0x02, 0x02]), Some(vec![0x7F, 0x22, 0x31]));
+        h.build_response(&[0x10, 0x03]);
+        assert_eq!(h.build_response(&[0x22, 0x02, 0x02]), Some(vec![0x62, 0x02, 0x02, 0x00]));
+        assert_eq!(h.build_response(&[0x22, 0xD1, 0x00]), Some(vec![0x62, 0xD1, 0x00, 0x03]));
+    }
+
+    #[test]
+    fn test_imc_handler_routines() {
+        let h = create_handler(EcuId::Imc);
+        h.build_response(&[0x10, 0x03]);
+        // Secured routine refused while locked
+        assert_eq!(h.build_response(&[0x31, 0x01, 0x60, 0x3E, 0x01]), Some(vec![0x7F, 0x31, 0x33]));
+
+        imc_unlock(h.as_ref());
+        assert_eq!(h.build_response(&[0x31, 0x01, 0x60, 0x3E]), Some(vec![0x7F, 0x31, 0x13]));
+        assert_eq!(
+            h.build_response(&[0x31, 0x01, 0x60, 0x3E, 0x01]),
+            Some(vec![0x71, 0x01, 0x60, 0x3E, 0x22])
+        );
+        assert_eq!(
+            h.build_response(&[0x31, 0x01, 0x60, 0x38]),
+            Some(vec![0x71, 0x01, 0x60, 0x38, 0x20, 0x01, 0x00])
+        );
+    }
+
+    fn fast_imc_timing() -> ImcTiming {
+        ImcTiming {
+            prepare: Duration::from_millis(30),
+            transfer: Duration::from_millis(60),
+            configure: Duration::from_millis(60),
+            reboot: Duration::from_millis(80),
+        }
+    }
+
+    /// IMC with fast routines (plus the GWM it fetches the CCF from, if `with_gwm`),
+    /// already in the extended session and unlocked
+    fn imc_bus(outcome: ImcOutcome, with_gwm: bool) -> EmulatedBus {
+        let ecus: &[EcuId] = if with_gwm { &[EcuId::Imc, EcuId::Gwm] } else { &[EcuId::Imc] };
+        let imc = ImcHandler::new().with_timing(fast_imc_timing()).with_outcome(outcome);
+        let bus = EmulatedBus::new(ecus)
+            .with_handler(EcuId::Imc, stateful(EcuId::Imc, Box::new(imc)));
+        assert_eq!(imc_request(&bus, &[0x10, 0x03]).unwrap()[0], 0x50);
+        let seed = imc_request(&bus, &[0x27, 0x11]).unwrap();
+        let key = keygen_mki(u32::from_be_bytes([0, seed[2], seed[3], seed[4]]), &DC0314_CONSTANTS);
+        let key = key.to_be_bytes();
+        let unlock = imc_request(&bus, &[0x27, 0x12, key[1], key[2], key[3]]);
+        assert_eq!(unlock, Some(vec![0x67, 0x12]));
+        bus
+    }
+
+    /// Send to the IMC over `bus` and take its immediate answer, if any
+    fn imc_request(bus: &EmulatedBus, request: &[u8]) -> Option<Vec<u8>> {
+        bus.send(&PassThruMsg::new_iso15765(ecu_addr::IMC_TX, request), 100).unwrap();
+        bus.read(5).unwrap().first().map(|m| m.payload().to_vec())
+    }
+
+    #[test]
+    fn test_imc_handler_ccf_transfer() {
+        let bus = imc_bus(ImcOutcome::Success, true);
+        // 0x0E06 out of sequence before 0x0E08
+        assert_eq!(imc_request(&bus, &[0x31, 0x01, 0x0E, 0x06]), Some(vec![0x7F, 0x31, 0x24]));
+
+        for rid_lo in [0x08, 0x06] {
+            let start = imc_request(&bus, &[0x31, 0x01, 0x0E, rid_lo]);
+            assert_eq!(start, Some(vec![0x71, 0x01, 0x0E, rid_lo]));
+        }
+        // Busy until prepare + transfer have run
+        assert_eq!(imc_request(&bus, &[0x31, 0x03, 0x0E, 0x06]), Some(vec![0x7F, 0x31, 0x21]));
+        thread::sleep(Duration::from_millis(120));
+        assert_eq!(
+            imc_request(&bus, &[0x31, 0x03, 0x0E, 0x06]),
+            Some(vec![0x71, 0x03, 0x0E, 0x06, 0x20, 0x00])
+        );
+        // Like the car, the transferred CCF can't be read back
+        assert_eq!(imc_request(&bus, &[0x22, 0xEE, 0x00]), Some(vec![0x7F, 0x22, 0x31]));
+        assert_eq!(imc_request(&bus, &[0x31, 0x01, 0x0E, 0x01]), Some(vec![0x7F, 0x31, 0x31]));
+        // Unknown routines and stop requests aren't accepted either
+        assert_eq!(imc_request(&bus, &[0x31, 0x01, 0x12, 0x34]), Some(vec![0x7F, 0x31, 0x31]));
+        assert_eq!(imc_request(&bus, &[0x31, 0x02, 0x0E, 0x06]), Some(vec![0x7F, 0x31, 0x31]));
+
+        let list = imc_request(&bus, &[0x31, 0x01, 0x0E, 0x02]).unwrap();
+        assert_eq!(list.len(), 4 + 147);
+    }
+
+    #[test]
+    fn test_imc_transfer_failures() {
+        // No GWM on the bus: the fetch goes unanswered
+        let bus = imc_bus(ImcOutcome::Success, false);
+        imc_request(&bus, &[0x31, 0x01, 0x0E, 0x08]);
+        imc_request(&bus, &[0x31, 0x01, 0x0E, 0x06]);
+        thread::sleep(Duration::from_millis(120));
+        assert_eq!(imc_request(&bus, &[0x31, 0x03, 0x0E, 0x06]).unwrap()[4..], [0x20, 0x01]);
+        assert_eq!(imc_request(&bus, &[0x22, 0xEE, 0x00]), Some(vec![0x7F, 0x22, 0x31]));
+
+        let bus = imc_bus(ImcOutcome::TransferFails(0x05), true);
+        imc_request(&bus, &[0x31, 0x01, 0x0E, 0x08]);
+        imc_request(&bus, &[0x31, 0x01, 0x0E, 0x06]);
+        thread::sleep(Duration::from_millis(120));
+        assert_eq!(imc_request(&bus, &[0x31, 0x03, 0x0E, 0x06]).unwrap()[4..], [0x20, 0x05]);
+    }
+
+    #[test]
+    fn test_imc_configure_outcomes_decode() {
+        use crate::uds::services::describe_routine_result;
+        let cases = [
+            (ImcOutcome::Success, [0x20, 0x01, 0x00], "Completed"),
+            (ImcOutcome::ConfigureFails(0x24), [0x20, 0x03, 0x24], "Polar switch"),
+            (ImcOutcome::ConfigureAborted, [0x21, 0x03, 0x00], "Routine aborted"),
+        ];
+        for (outcome, expected, text) in cases {
+            let bus = imc_bus(outcome, true);
+            assert_eq!(imc_request(&bus, &[0x31, 0x03, 0x60, 0x38]), Some(vec![0x7F, 0x31, 0x24]));
+            imc_request(&bus, &[0x31, 0x01, 0x60, 0x38]);
+            let active = imc_request(&bus, &[0x31, 0x03, 0x60, 0x38]).unwrap();
+            assert_eq!(active[4..], [0x22, 0x02, 0x00]);
+            thread::sleep(Duration::from_millis(80));
+            let resp = imc_request(&bus, &[0x31, 0x03, 0x60, 0x38]).unwrap();
+            assert_eq!(resp[4..], expected);
+            let desc = describe_routine_result(0x6038, Some(resp[4]), Some(resp[5]), Some(resp[6]));
+            assert!(desc.contains(text), "{}", desc);
+        }
+    }
+
+    #[test]
+    fn test_imc_silent_while_rebooting() {
+        let bus = imc_bus(ImcOutcome::Success, true);
+        assert_eq!(imc_request(&bus, &[0x11, 0x01]), Some(vec![0x51, 0x01]));
+        assert_eq!(imc_request(&bus, &[0x3E, 0x00]), None);
+        assert_eq!(imc_request(&bus, &[0x10, 0x03]), None);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(imc_request(&bus, &[0x22, 0xD1, 0x00]), Some(vec![0x62, 0xD1, 0x00, 0x01]));
+    }
+
+    // ─── Session & security state machine tests ────────────────
+
+    const TEST_CONSTANTS: [u8; 5] = [0x12, 0x34, 0x56, 0x78, 0x9A];
+
+    fn stateful_bcm(s3_ms: u64, lockout_ms: u64) -> StatefulEcu {
+        // Made-up constants: the real BCM level is unknown
+        let policy = EcuPolicy::new(vec![SecurityLevel {
+            seed_sub: 0x01,
+            sessions: &[0x02, 0x03],
+            constants: TEST_CONSTANTS,
+        }])
+        .with_timing(
+            Duration::from_millis(s3_ms),
+            Duration::from_millis(lockout_ms),
+        );
+        StatefulEcu::new(Box::new(BcmHandler::new()), policy)
+    }
+
+    #[test]
+    fn test_stateful_session_rules() {
+        let h = stateful_bcm(5000, 10000);
+        // Security, routines and writes need a non-default session
+        assert_eq!(h.build_response(&[0x27, 0x01]), Some(vec![0x7F, 0x27, 0x7F]));
+        assert_eq!(h.build_response(&[0x31, 0x01, 0x12, 0x34]), Some(vec![0x7F, 0x31, 0x7F]));
+        assert_eq!(h.build_response(&[0x2E, 0xDE, 0x00, 0x01]), Some(vec![0x7F, 0x2E, 0x7F]));
+        // Programming session only from extended
+        assert_eq!(h.build_response(&[0x10, 0x02]), Some(vec![0x7F, 0x10, 0x7E]));
+        assert_eq!(h.build_response(&[0x10, 0x03]).unwrap()[..2], [0x50, 0x03]);
+        assert_eq!(h.session(), 0x03);
+        assert_eq!(h.build_response(&[0x10, 0x02]).unwrap()[..2], [0x50, 0x02]);
+        // Writes also need security
+        h.build_response(&[0x10, 0x03]);
+        assert_eq!(h.build_response(&[0x2E, 0xDE, 0x00, 0x01]), Some(vec![0x7F, 0x2E, 0x33]));
+        // Level 0x11 doesn't exist on the BCM
+        assert_eq!(h.build_response(&[0x27, 0x11]), Some(vec![0x7F, 0x27, 0x12]));
+        // Suppressed TesterPresent gets no answer
+        assert_eq!(h.build_response(&[0x3E, 0x80]), None);
+    }
+
+    #[test]
+    fn test_stateful_random_seed_and_key() {
+        let h = stateful_bcm(5000, 10000);
+        h.build_response(&[0x10, 0x03]);
+        // Key without a seed
+        assert_eq!(h.build_response(&[0x27, 0x02, 1, 2, 3]), Some(vec![0x7F, 0x27, 0x24]));
+
+        let first = h.build_response(&[0x27, 0x01]).unwrap();
+        let second = h.build_response(&[0x27, 0x01]).unwrap();
+        assert_ne!(first, second, "seeds should be random");
+        assert_ne!(&second[2..], &[0, 0, 0]);
+
+        let seed = u32::from_be_bytes([0, second[2], second[3], second[4]]);
+        let key = keygen_mki(seed, &TEST_CONSTANTS).to_be_bytes();
+        assert_eq!(h.build_response(&[0x27, 0x02, key[1], key[2], key[3]]), Some(vec![0x67, 0x02]));
+        assert_eq!(h.build_response(&[0x27, 0x01]), Some(vec![0x67, 0x01, 0, 0, 0]));
+        assert_eq!(h.build_response(&[0x2E, 0xDE, 0x00, 0x01]), Some(vec![0x6E, 0xDE, 0x00]));
+        // Session change re-locks
+        h.build_response(&[0x10, 0x01]);
+        h.build_response(&[0x10, 0x03]);
+        assert_ne!(h.build_response(&[0x27, 0x01]), Some(vec![0x67, 0x01, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_stateful_attempt_counter_and_delay() {
+        let h = stateful_bcm(5000, 150);
+        h.build_response(&[0x10, 0x03]);
+        for expected in [0x35, 0x35, 0x36] {
+            h.build_response(&[0x27, 0x01]);
+            assert_eq!(
+                h.build_response(&[0x27, 0x02, 0, 0, 0]),
+                Some(vec![0x7F, 0x27, expected])
+            );
+        }
+        // Locked out until the delay expires
+        assert_eq!(h.build_response(&[0x27, 0x01]), Some(vec![0x7F, 0x27, 0x37]));
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(h.build_response(&[0x27, 0x01]).unwrap()[..2], [0x67, 0x01]);
+    }
+
+    #[test]
+    fn test_stateful_s3_timeout() {
+        let h = stateful_bcm(100, 10000);
+        h.build_response(&[0x10, 0x03]);
+        thread::sleep(Duration::from_millis(60));
+        // TesterPresent keeps the session alive
+        h.build_response(&[0x3E, 0x00]);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(h.build_response(&[0x27, 0x01]).unwrap()[..2], [0x67, 0x01]);
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(h.build_response(&[0x27, 0x01]), Some(vec![0x7F, 0x27, 0x7F]));
+        assert_eq!(h.session(), 0x01);
+    }
+
+    #[test]
+    fn test_manager_keeps_state_between_requests() {
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Imc]);
+        mgr.try_handle(ecu_addr::IMC_TX, &[0x10, 0x03]);
+        let seed = mgr.try_handle(ecu_addr::IMC_TX, &[0x27, 0x11]).unwrap();
+        assert_eq!(seed[..2], [0x67, 0x11]);
+        assert_ne!(seed[2..], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_unknown_security_is_not_emulated() {
+        let h = create_handler(EcuId::Gwm);
+        h.build_response(&[0x10, 0x03]);
+        assert_eq!(h.build_response(&[0x27, 0x01]), Some(vec![0x7F, 0x27, 0x12]));
+        assert_eq!(h.build_response(&[0x2E, 0xEE, 0x00]), Some(vec![0x7F, 0x2E, 0x13]));
+    }
+
+    // ─── Fault injection tests ─────────────────────────────────
+
+    fn payloads(frames: &[(Duration, Vec<u8>)]) -> Vec<Vec<u8>> {
+        frames.iter().map(|(_, p)| p.clone()).collect()
+    }
+
+    #[test]
+    fn test_fault_scenario_parse() {
+        for name in FAULT_PRESETS {
+            assert_eq!(FaultScenario::parse(name).unwrap().name, *name);
+        }
+        let json = r#"{"name": "custom", "rules": [
+            {"kind": "busy", "service": 34, "every": 3},
+            {"kind": "pending", "count": 2, "interval_ms": 50}
+        ]}"#;
+        let scenario = FaultScenario::parse(json).unwrap();
+        assert_eq!(scenario.rules[0], FaultRule::new(Fault::Busy).on_service(0x22).every(3));
+        assert_eq!(scenario.rules[1].fault, Fault::Pending { count: 2, interval_ms: 50 });
+        assert!(FaultScenario::parse("sometimes").unwrap_err().contains("flaky-imc"));
+        assert!(FaultScenario::parse(r#"{"name": "x", "rules": [{"kind": "nope"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_fault_pending_and_stale_nrc_timeline() {
+        let scenario = FaultScenario::new(
+            "t",
+            vec![
+                FaultRule::new(Fault::StaleNrc { service: 0x10, nrc: 0x12 }),
+                FaultRule::new(Fault::Pending { count: 2, interval_ms: 100 }),
+            ],
+        );
+        let frames = scenario.apply(&[0x31, 0x01, 0x60, 0x38], || Some(vec![0x71, 0x01]));
+        assert_eq!(
+            payloads(&frames),
+            vec![
+                vec![0x7F, 0x10, 0x12],
+                vec![0x7F, 0x31, 0x78],
+                vec![0x7F, 0x31, 0x78],
+                vec![0x71, 0x01],
+            ]
+        );
+        assert_eq!(frames.last().unwrap().0, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_fault_busy_is_intermittent_and_skips_handler() {
+        let scenario =
+            FaultScenario::new("t", vec![FaultRule::new(Fault::Busy).on_service(0x22).every(2)]);
+        let calls = std::cell::Cell::new(0);
+        let answer = |req: &[u8]| {
+            payloads(&scenario.apply(req, || {
+                calls.set(calls.get() + 1);
+                Some(vec![req[0] + 0x40])
+            }))
+        };
+        assert_eq!(answer(&[0x22, 0xF1, 0x90]), vec![vec![0x62]]);
+        assert_eq!(answer(&[0x3E, 0x00]), vec![vec![0x7E]]);
+        assert_eq!(answer(&[0x22, 0xF1, 0x90]), vec![vec![0x7F, 0x22, 0x21]]);
+        assert_eq!(answer(&[0x22, 0xF1, 0x90]), vec![vec![0x62]]);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_fault_drop_duplicate_truncate() {
+        let drop = FaultScenario::preset("drop").unwrap();
+        let sizes: Vec<usize> =
+            (0..3).map(|_| drop.apply(&[0x3E, 0x00], || Some(vec![0x7E, 0x00])).len()).collect();
+        assert_eq!(sizes, vec![1, 1, 0]);
+
+        let dup = FaultScenario::preset("duplicate").unwrap();
+        assert_eq!(payloads(&dup.apply(&[0x3E, 0x00], || Some(vec![0x7E, 0x00]))).len(), 2);
+
+        let truncate = FaultScenario::preset("truncate").unwrap();
+        let short = truncate.apply(&[0x22, 0xD1, 0x00], || Some(vec![0x62, 0xD1, 0x00, 0x01]));
+        assert_eq!(payloads(&short), vec![vec![0x62, 0xD1, 0x00, 0x01]]);
+        let long = truncate.apply(&[0x22, 0xF1, 0x90], || Some(vec![0x62; 20]));
+        assert_eq!(payloads(&long), vec![vec![0x62; 7]]);
+    }
+
+    #[test]
+    fn test_emulated_bus_fault_timing() {
+        let bus = EmulatedBus::new(&[EcuId::Bcm]).with_faults(
+            EcuId::Bcm,
+            FaultScenario::new(
+                "t",
+                vec![FaultRule::new(Fault::Pending { count: 1, interval_ms: 80 })],
+            ),
+        );
+        bus.send(&PassThruMsg::new_iso15765(ecu_addr::BCM_TX, &[0x3E, 0x00]), 100).unwrap();
+        let first = bus.read(20).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].payload(), &[0x7F, 0x3E, 0x78]);
+        assert!(bus.read(20).unwrap().is_empty());
+        let last = bus.read(200).unwrap();
+        assert_eq!(last[0].payload(), &[0x7E, 0x00]);
+        assert_eq!(bus.faults(EcuId::Bcm), Some("t"));
+    }
+
+    fn gwm_read_faults(fault: Fault) -> FaultScenario {
+        FaultScenario::new("gwm", vec![FaultRule::new(fault).on_service(0x22)])
+    }
+
+    /// Start the IMC's CCF transfer and return its 0x0E06 result after `wait`
+    fn transfer_result(request: impl Fn(&[u8]) -> Option<Vec<u8>>, wait: u64) -> Vec<u8> {
+        for rid_lo in [0x08, 0x06] {
+            request(&[0x31, 0x01, 0x0E, rid_lo]);
+        }
+        thread::sleep(Duration::from_millis(wait));
+        request(&[0x31, 0x03, 0x0E, 0x06]).unwrap()[4..].to_vec()
+    }
+
+    #[test]
+    fn test_faults_apply_to_imc_bus_requests() {
+        // The GWM never answers the IMC's ReadDID 0xEE00
+        let bus = imc_bus(ImcOutcome::Success, true)
+            .with_faults(EcuId::Gwm, gwm_read_faults(Fault::Drop));
+        let result = transfer_result(|r| imc_request(&bus, r), 120);
+        assert_eq!(result, [0x20, TRANSFER_NO_GWM_CCF]);
+
+        // A late answer only counts once it has arrived
+        let bus = imc_bus(ImcOutcome::Success, true)
+            .with_faults(EcuId::Gwm, gwm_read_faults(Fault::Delay { ms: 300 }));
+        let result = transfer_result(|r| imc_request(&bus, r), 120);
+        assert_eq!(result, [0x20, TRANSFER_NO_GWM_CCF]);
+        thread::sleep(Duration::from_millis(250));
+        assert_eq!(imc_request(&bus, &[0x31, 0x03, 0x0E, 0x06]).unwrap()[4..], [0x20, 0x00]);
+
+        // Same through the bench responder answering for the GWM
+        use crate::uds::client::UdsClient;
+        let router = ChannelRouter::new(Box::new(imc_bus(ImcOutcome::Success, false)));
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Gwm])
+            .with_faults(&gwm_read_faults(Fault::Drop))
+            .unwrap();
+        mgr.attach(&router);
+        let port = router.port(ecu_addr::IMC_TX, ecu_addr::IMC_RX).unwrap();
+        let imc = UdsClient::new(port, ecu_addr::IMC_TX, ecu_addr::IMC_RX);
+        let result = transfer_result(|r| imc.send_recv(r, 500, false).ok(), 150);
+        assert_eq!(result, [0x20, TRANSFER_NO_GWM_CCF]);
+    }
+
     // ─── create_handler ─────────────────────────────────────────
 
     #[test]
@@ -764,11 +2668,7 @@ mod tests {
 
     #[test]
     fn test_try_handle_bcm_vin() {
-        let mgr = EcuEmulatorManager {
-            running: Arc::new(AtomicBool::new(false)),
-            handle: None,
-            emulated_ecus: vec![EcuId::Bcm],
-        };
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Bcm]);
         let resp = mgr
             .try_handle(ecu_addr::BCM_TX, &[0x22, 0xF1, 0x90])
             .unwrap();
@@ -780,11 +2680,7 @@ mod tests {
     #[test]
     fn test_try_handle_bcm_voltage_returns_nrc() {
         // Battery voltage is on GWM, not BCM — emulator returns 0x31
-        let mgr = EcuEmulatorManager {
-            running: Arc::new(AtomicBool::new(false)),
-            handle: None,
-            emulated_ecus: vec![EcuId::Bcm],
-        };
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Bcm]);
         let resp = mgr
             .try_handle(ecu_addr::BCM_TX, &[0x22, 0x40, 0x2A])
             .unwrap();
@@ -793,11 +2689,7 @@ mod tests {
 
     #[test]
     fn test_try_handle_unknown_txid() {
-        let mgr = EcuEmulatorManager {
-            running: Arc::new(AtomicBool::new(false)),
-            handle: None,
-            emulated_ecus: vec![EcuId::Bcm],
-        };
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Bcm]);
         // IMC TX is not emulated
         let resp = mgr.try_handle(ecu_addr::IMC_TX, &[0x22, 0xF1, 0x90]);
         assert!(resp.is_none());
@@ -805,26 +2697,155 @@ mod tests {
 
     #[test]
     fn test_try_handle_unknown_did() {
-        let mgr = EcuEmulatorManager {
-            running: Arc::new(AtomicBool::new(false)),
-            handle: None,
-            emulated_ecus: vec![EcuId::Bcm],
-        };
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Bcm]);
         let resp = mgr
             .try_handle(ecu_addr::BCM_TX, &[0x22, 0xFF, 0xFF])
             .unwrap();
         assert_eq!(resp, vec![0x7F, 0x22, 0x31]); // NRC requestOutOfRange
     }
 
+    // ─── Bus responder tests ─────────────────────────────────────
+
+    #[test]
+    fn test_responder_answers_imc_bus_requests() {
+        use crate::uds::client::UdsClient;
+        // Bench IMC alone on the wire; the manager plays the GWM it asks for its CCF
+        let router = ChannelRouter::new(Box::new(imc_bus(ImcOutcome::Success, false)));
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let log = entries.clone();
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Gwm])
+            .with_log_callback(Box::new(move |e| log.lock().unwrap().push(e.description)));
+        let filters = mgr.attach(&router);
+        assert_eq!(filters.len(), 1);
+        assert!(filters[0].1.is_ok());
+        assert!(mgr.is_attached());
+
+        // Tester responses still reach the tester while the responder is listening
+        let port = router.port(ecu_addr::IMC_TX, ecu_addr::IMC_RX).unwrap();
+        let imc = UdsClient::new(port, ecu_addr::IMC_TX, ecu_addr::IMC_RX);
+        for rid_lo in [0x08, 0x06] {
+            let start = imc.send_recv(&[0x31, 0x01, 0x0E, rid_lo], 500, false).unwrap();
+            assert_eq!(start, vec![0x71, 0x01, 0x0E, rid_lo]);
+        }
+        thread::sleep(Duration::from_millis(150));
+        let results = imc.send_recv(&[0x31, 0x03, 0x0E, 0x06], 500, false).unwrap();
+        assert_eq!(results, vec![0x71, 0x03, 0x0E, 0x06, 0x20, 0x00]);
+
+        let logged = entries.lock().unwrap().clone();
+        assert_eq!(logged, vec!["BUS→EMU 0x716", "EMU→BUS 0x71E"]);
+        mgr.detach();
+        assert!(!mgr.is_attached());
+    }
+
+    /// Filters currently set, by id
+    type LiveFilters = Mutex<Vec<(u32, (u32, u32))>>;
+
+    /// Emulated bus that keeps the filters currently set on it
+    struct FilterBus {
+        bus: EmulatedBus,
+        filters: Arc<LiveFilters>,
+    }
+
+    impl Channel for FilterBus {
+        fn send(&self, msg: &PassThruMsg, timeout_ms: u32) -> Result<(), String> {
+            self.bus.send(msg, timeout_ms)
+        }
+
+        fn read(&self, timeout_ms: u32) -> Result<Vec<PassThruMsg>, String> {
+            self.bus.read(timeout_ms)
+        }
+
+        fn setup_iso15765_filter(&self, tx_id: u32, rx_id: u32) -> Result<u32, String> {
+            let mut filters = self.filters.lock().unwrap();
+            let id = filters.last().map_or(1, |(id, _)| id + 1);
+            filters.push((id, (tx_id, rx_id)));
+            Ok(id)
+        }
+
+        fn stop_filter(&self, filter_id: u32) -> Result<(), String> {
+            self.filters.lock().unwrap().retain(|(id, _)| *id != filter_id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_detach_removes_filters_and_faults_refused_while_attached() {
+        let filters = Arc::new(Mutex::new(Vec::new()));
+        let bus = FilterBus {
+            bus: imc_bus(ImcOutcome::Success, false),
+            filters: filters.clone(),
+        };
+        let router = ChannelRouter::new(Box::new(bus));
+        router.setup_iso15765_filter(ecu_addr::IMC_TX, ecu_addr::IMC_RX).unwrap();
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Gwm, EcuId::Bcm]);
+        mgr.attach(&router);
+        let reversed = [(0x71E, 0x716), (0x72E, 0x726)];
+        let live = |f: &LiveFilters| {
+            f.lock().unwrap().iter().map(|(_, pair)| *pair).collect::<Vec<_>>()
+        };
+        assert_eq!(live(&filters)[1..], reversed[..]);
+
+        mgr.detach();
+        // Only the tester's own filter is left
+        assert_eq!(live(&filters), vec![(ecu_addr::IMC_TX, ecu_addr::IMC_RX)]);
+
+        mgr.attach(&router);
+        assert_eq!(live(&filters)[1..], reversed[..]);
+        let scenario = FaultScenario::new("test", Vec::new());
+        let err = mgr.with_faults(&scenario).err().unwrap();
+        assert!(err.contains("attached"), "{}", err);
+        // The refused manager is dropped, which detaches it too
+        assert_eq!(live(&filters), vec![(ecu_addr::IMC_TX, ecu_addr::IMC_RX)]);
+
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Gwm, EcuId::Bcm]);
+        mgr.attach(&router);
+        mgr.detach();
+        assert!(mgr.with_faults(&scenario).is_ok());
+    }
+
+    // ─── Broadcast tests ─────────────────────────────────────────
+
+    #[test]
+    fn test_broadcast_follows_vehicle_state() {
+        let running = AtomicBool::new(true);
+        let vehicle_state = Mutex::new(VehicleState::On);
+        let frames = Mutex::new(Vec::new());
+        let switched = thread::scope(|s| {
+            s.spawn(|| {
+                let scheduler = BroadcastScheduler::new(default_schedule(), Instant::now());
+                run_broadcast(scheduler, &running, &vehicle_state, |id, data| {
+                    frames.lock().unwrap().push((id, *data))
+                });
+            });
+            thread::sleep(Duration::from_millis(150));
+            *vehicle_state.lock().unwrap() = VehicleState::Off;
+            thread::sleep(Duration::from_millis(30));
+            let switched = frames.lock().unwrap().len();
+            thread::sleep(Duration::from_millis(250));
+            running.store(false, Ordering::Relaxed);
+            switched
+        });
+        let frames = frames.into_inner().unwrap();
+
+        // 20ms frame sent repeatedly while on, its alive counter rolling
+        let fast: Vec<u8> = frames[..switched]
+            .iter()
+            .filter(|(id, _)| *id == 0x070)
+            .map(|(_, data)| data[1] >> 4)
+            .collect();
+        assert!(fast.len() >= 4, "{:?}", fast);
+        assert_eq!(&fast[..4], &[0, 1, 2, 3]);
+        // With the ignition off only the NM heartbeats remain
+        let after: Vec<u32> = frames[switched..].iter().map(|(id, _)| *id).collect();
+        assert!(!after.is_empty());
+        assert!(after.iter().all(|id| [0x400, 0x407, 0x460].contains(id)), "{:?}", after);
+    }
+
     // ─── try_handle_bus_request tests ────────────────────────────
 
     #[test]
     fn test_bus_request_gwm_ccf() {
-        let mgr = EcuEmulatorManager {
-            running: Arc::new(AtomicBool::new(false)),
-            handle: None,
-            emulated_ecus: vec![EcuId::Gwm, EcuId::Bcm],
-        };
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Gwm, EcuId::Bcm]);
         // IMC sends ReadDID 0xEE00 to GWM (0x716)
         let result = mgr.try_handle_bus_request(ecu_addr::GWM_TX, &[0x22, 0xEE, 0x00]);
         assert!(result.is_some());
@@ -836,11 +2857,7 @@ mod tests {
 
     #[test]
     fn test_bus_request_bcm_ccf() {
-        let mgr = EcuEmulatorManager {
-            running: Arc::new(AtomicBool::new(false)),
-            handle: None,
-            emulated_ecus: vec![EcuId::Gwm, EcuId::Bcm],
-        };
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Gwm, EcuId::Bcm]);
         // ReadDID 0xDE00 to BCM (0x726)
         let result = mgr.try_handle_bus_request(ecu_addr::BCM_TX, &[0x22, 0xDE, 0x00]);
         assert!(result.is_some());
@@ -852,13 +2869,80 @@ mod tests {
 
     #[test]
     fn test_bus_request_not_emulated() {
-        let mgr = EcuEmulatorManager {
-            running: Arc::new(AtomicBool::new(false)),
-            handle: None,
-            emulated_ecus: vec![EcuId::Gwm],
-        };
+        let mgr = EcuEmulatorManager::new(vec![EcuId::Gwm]);
         // Request to IMC (not emulated) → None
         let result = mgr.try_handle_bus_request(ecu_addr::IMC_TX, &[0x22, 0xF1, 0x90]);
         assert!(result.is_none());
     }
+
+    // ─── Dump handler tests ─────────────────────────────────────
+
+    const DUMP: &str = r#"{
+        "ecu": "BCM", "tx_id": "0x726", "rx_id": "0x72E",
+        "dids": [
+            {"did": "F190", "session": "default", "bytes": [98, 241, 144, 65, 66]},
+            {"did": "DD01", "session": "extended", "bytes": [98, 221, 1, 3]},
+            {"did": "F120", "session": "default", "error": "NRC: Request out of range (0x31)"},
+            {"did": "A000", "session": "default", "error": "NRC: Security access denied (0x33)"},
+            {"did": "B000", "session": "default", "error": "Timeout waiting for response"}
+        ],
+        "ccf": {"list_0E02": [16, 0, 1, 0], "retrieve_error": "NRC: Request out of range (0x31)"}
+    }"#;
+
+    #[test]
+    fn test_dump_handler_replays_dids() {
+        let h = DumpHandler::from_json(DUMP).unwrap();
+        assert_eq!(h.ecu(), Some(EcuId::Bcm));
+        assert_eq!(h.readable_dids(), 2);
+        assert_eq!(h.build_response(&[0x22, 0xF1, 0x90]), Some(vec![0x62, 0xF1, 0x90, 0x41, 0x42]));
+        assert_eq!(h.build_response(&[0x22, 0xF1, 0x20]), Some(vec![0x7F, 0x22, 0x31]));
+        assert_eq!(h.build_response(&[0x22, 0xA0, 0x00]), Some(vec![0x7F, 0x22, 0x33]));
+        assert_eq!(h.build_response(&[0x22, 0xB0, 0x00]), None);
+        // Not in the dump at all
+        assert_eq!(h.build_response(&[0x22, 0x12, 0x34]), Some(vec![0x7F, 0x22, 0x31]));
+    }
+
+    #[test]
+    fn test_dump_handler_respects_session() {
+        let h = DumpHandler::from_json(DUMP).unwrap();
+        assert_eq!(h.build_response(&[0x22, 0xDD, 0x01]), Some(vec![0x7F, 0x22, 0x31]));
+        assert_eq!(h.build_response(&[0x10, 0x03]).unwrap()[0], 0x50);
+        assert_eq!(h.build_response(&[0x22, 0xDD, 0x01]), Some(vec![0x62, 0xDD, 0x01, 0x03]));
+        h.build_response(&[0x11, 0x01]);
+        assert_eq!(h.build_response(&[0x22, 0xDD, 0x01]), Some(vec![0x7F, 0x22, 0x31]));
+    }
+
+    #[test]
+    fn test_dump_handler_ccf_and_fallback() {
+        let h = DumpHandler::from_json(DUMP).unwrap().with_fallback(create_raw_handler(EcuId::Bcm));
+        assert_eq!(
+            h.build_response(&[0x31, 0x01, 0x0E, 0x02]),
+            Some(vec![0x71, 0x01, 0x0E, 0x02, 0x10, 0x00, 0x01, 0x00])
+        );
+        assert_eq!(h.build_response(&[0x31, 0x01, 0x0E, 0x01]), Some(vec![0x7F, 0x31, 0x31]));
+        // Not covered by the dump → built-in BCM handler
+        assert_eq!(h.build_response(&[0x27, 0x01]), Some(vec![0x67, 0x01, 0x00, 0x00, 0x00]));
+    }
+
+    #[test]
+    fn test_dump_handler_rejects_bad_json() {
+        assert!(DumpHandler::from_json("{}").is_err());
+        assert!(DumpHandler::from_json(r#"{"dids": [{"did": "XYZ"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_load_dump_handlers_from_scan_files() {
+        // The scans committed at the repo root
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let handlers = load_dump_handlers(&dir, EcuId::all()).unwrap();
+        let ids: Vec<EcuId> = handlers.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![EcuId::Bcm, EcuId::Gwm, EcuId::Ipc]);
+
+        let bus = handlers.into_iter().fold(EmulatedBus::new(&[EcuId::Bcm]), |bus, (id, h)| {
+            bus.with_handler(id, Box::new(h))
+        });
+        bus.send(&PassThruMsg::new_iso15765(ecu_addr::BCM_TX, &[0x22, 0xF1, 0x90]), 0).unwrap();
+        let resp = bus.read(100).unwrap();
+        assert_eq!(&resp[0].payload()[3..20], b"SAJBL4BVXGCY16353");
+    }
 }
@@ -208,10 +208,26 @@ impl J2534Channel {
         Ok(filter_id)
     }
 
-    /// Send a raw CAN frame (8 bytes max, for broadcast on a CAN channel)
-    pub fn send_raw_can(&self, can_id: u32, data: &[u8]) -> Result<(), String> {
+    /// Remove a message filter (PassThruStopMsgFilter)
+    pub fn stop_msg_filter(&self, filter_id: u32) -> Result<(), String> {
+        let ret = unsafe { (self.lib.pass_thru_stop_msg_filter)(self.channel_id, filter_id) };
+        if ret != 0 {
+            return Err(format!(
+                "PassThruStopMsgFilter failed: {}",
+                J2534Error::from_code(ret)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Send a raw CAN frame (8 bytes max, for broadcast on a CAN channel);
+    /// `extended` sends `can_id` as a 29-bit ID
+    pub fn send_raw_can(&self, can_id: u32, data: &[u8], extended: bool) -> Result<(), String> {
         let mut msg = PassThruMsg::default();
         msg.protocol_id = PROTOCOL_CAN;
+        if extended {
+            msg.tx_flags = CAN_29BIT_ID;
+        }
         msg.data[0..4].copy_from_slice(&can_id.to_be_bytes());
         let len = data.len().min(8);
         msg.data[4..4 + len].copy_from_slice(&data[..len]);
@@ -23,8 +23,8 @@ pub struct MockChannel {
     pending_responses: RefCell<VecDeque<(u32, Vec<u8>)>>,
     /// Track sent messages for assertions
     sent_messages: RefCell<Vec<(u32, Vec<u8>)>>,
-    /// Registered filters (tx_id, rx_id)
-    filters: RefCell<Vec<(u32, u32)>>,
+    /// Registered filters (tx_id, rx_id), indexed by filter id; `None` once stopped
+    filters: RefCell<Vec<Option<(u32, u32)>>>,
     /// If true, read() returns empty when no pending (simulates timeout)
     timeout_mode: RefCell<bool>,
 }
@@ -71,7 +71,7 @@ impl MockChannel {
 
     /// Get registered filters
     pub fn filters(&self) -> Vec<(u32, u32)> {
-        self.filters.borrow().clone()
+        self.filters.borrow().iter().flatten().copied().collect()
     }
 
     /// Verify all expectations were consumed
@@ -86,7 +86,7 @@ impl MockChannel {
 
     /// Find the rx_id for a given tx_id from registered filters
     fn rx_id_for_tx(&self, tx_id: u32) -> u32 {
-        for (filter_tx, filter_rx) in self.filters.borrow().iter() {
+        for (filter_tx, filter_rx) in self.filters.borrow().iter().flatten() {
             if *filter_tx == tx_id {
                 return *filter_rx;
             }
@@ -144,9 +144,19 @@ impl Channel for MockChannel {
     fn setup_iso15765_filter(&self, tx_id: u32, rx_id: u32) -> Result<u32, String> {
         let mut filters = self.filters.borrow_mut();
         let filter_id = filters.len() as u32;
-        filters.push((tx_id, rx_id));
+        filters.push(Some((tx_id, rx_id)));
         Ok(filter_id)
     }
+
+    fn stop_filter(&self, filter_id: u32) -> Result<(), String> {
+        match self.filters.borrow_mut().get_mut(filter_id as usize) {
+            Some(slot @ Some(_)) => {
+                *slot = None;
+                Ok(())
+            }
+            _ => Err(format!("No filter {}", filter_id)),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -2,6 +2,8 @@ pub mod device;
 pub mod dll;
 #[cfg(test)]
 pub mod mock;
+pub mod remote;
+pub mod router;
 pub mod types;
 
 use types::PassThruMsg;
@@ -11,6 +13,14 @@ pub trait Channel: Send {
     fn send(&self, msg: &PassThruMsg, timeout_ms: u32) -> Result<(), String>;
     fn read(&self, timeout_ms: u32) -> Result<Vec<PassThruMsg>, String>;
     fn setup_iso15765_filter(&self, tx_id: u32, rx_id: u32) -> Result<u32, String>;
+    /// Remove a filter set up by `setup_iso15765_filter`
+    fn stop_filter(&self, filter_id: u32) -> Result<(), String>;
+
+    /// Timeout for the router's background reads. Zero polls, which is cheap on a
+    /// local adapter; channels where every read costs a round trip block instead.
+    fn reader_timeout_ms(&self) -> u32 {
+        0
+    }
 }
 
 /// Implement Channel for the real J2534Channel
@@ -26,4 +36,8 @@ impl Channel for device::J2534Channel {
     fn setup_iso15765_filter(&self, tx_id: u32, rx_id: u32) -> Result<u32, String> {
         self.setup_iso15765_filter(tx_id, rx_id)
     }
+
+    fn stop_filter(&self, filter_id: u32) -> Result<(), String> {
+        self.stop_msg_filter(filter_id)
+    }
 }
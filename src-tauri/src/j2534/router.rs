@@ -0,0 +1,525 @@
+//! Per-ECU request routing over a single ISO15765 channel.
+//!
+//! One reader thread owns all reads from the underlying channel and sorts incoming
+//! frames into queues keyed by CAN ID. Each ECU is accessed through an `EcuPort`,
+//! which only sees frames from its own response ID, so requests to different ECUs
+//! can be in flight at the same time. Ports for the same ECU are handed out in FIFO
+//! order, keeping request/response pairs for one ECU strictly ordered. A
+//! `BusListener` claims CAN IDs outright (bench mode: requests the IMC sends to
+//! emulated modules), so neither ports nor whole-bus reads ever see them.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::types::PassThruMsg;
+use super::Channel;
+
+/// Reader thread sleep between empty polls (outside the channel lock)
+const READER_IDLE_MS: u64 = 2;
+
+/// Frames kept per CAN ID before the oldest are dropped
+const MAX_QUEUED_PER_ID: usize = 256;
+
+/// How long `ChannelRouter::port` waits for earlier ports of the same ECU
+pub const PORT_WAIT: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct Queues {
+    /// Received frames by CAN ID
+    frames: HashMap<u32, VecDeque<PassThruMsg>>,
+    /// Per-ECU FIFO tickets: rx_id → (next ticket, ticket being served)
+    tickets: HashMap<u32, (u64, u64)>,
+    /// (rx_id, ticket) of waiters that gave up; skipped when their turn comes
+    abandoned: HashSet<(u32, u64)>,
+    /// rx_ids currently checked out by a port
+    busy: Vec<u32>,
+    /// CAN IDs claimed by a `BusListener`; nobody else reads them
+    listened: Vec<u32>,
+}
+
+struct Shared {
+    channel: Mutex<Option<Box<dyn Channel>>>,
+    queues: Mutex<Queues>,
+    /// Signalled on new frames and on port release
+    changed: Condvar,
+    running: AtomicBool,
+}
+
+impl Shared {
+    fn with_channel<T>(
+        &self,
+        f: impl FnOnce(&dyn Channel) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let guard = self.channel.lock().map_err(|e| e.to_string())?;
+        match guard.as_deref() {
+            Some(ch) => f(ch),
+            None => Err("Channel closed".into()),
+        }
+    }
+
+    /// Wait up to `timeout_ms` for frames accepted by `select`, then drain them.
+    fn take_frames(
+        &self,
+        timeout_ms: u32,
+        select: impl Fn(&Queues, u32) -> bool,
+    ) -> Vec<PassThruMsg> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+        let mut q = self.queues.lock().unwrap();
+        loop {
+            let ids: Vec<u32> = q
+                .frames
+                .iter()
+                .filter(|(id, f)| !f.is_empty() && select(&q, **id))
+                .map(|(id, _)| *id)
+                .collect();
+            if !ids.is_empty() {
+                let mut out = Vec::new();
+                for id in ids {
+                    if let Some(f) = q.frames.get_mut(&id) {
+                        out.extend(f.drain(..));
+                    }
+                }
+                return out;
+            }
+            let now = Instant::now();
+            if now >= deadline || !self.running.load(Ordering::Relaxed) {
+                return Vec::new();
+            }
+            q = self.changed.wait_timeout(q, deadline - now).unwrap().0;
+        }
+    }
+}
+
+/// Owns a channel plus the reader thread that demultiplexes it by CAN ID.
+///
+/// `ChannelRouter` itself implements `Channel` as a whole-bus view: `read` returns
+/// frames for every CAN ID not currently checked out by an `EcuPort`.
+pub struct ChannelRouter {
+    shared: Arc<Shared>,
+    reader: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ChannelRouter {
+    pub fn new(channel: Box<dyn Channel>) -> Self {
+        let shared = Arc::new(Shared {
+            channel: Mutex::new(Some(channel)),
+            queues: Mutex::new(Queues::default()),
+            changed: Condvar::new(),
+            running: AtomicBool::new(true),
+        });
+        let reader_shared = shared.clone();
+        let reader = thread::spawn(move || Self::reader_loop(&reader_shared));
+        Self {
+            shared,
+            reader: Mutex::new(Some(reader)),
+        }
+    }
+
+    /// Check out the request queue for one ECU. Blocks until earlier ports for
+    /// the same `rx_id` are released (FIFO), up to `PORT_WAIT`; other ECUs are
+    /// unaffected.
+    pub fn port(&self, tx_id: u32, rx_id: u32) -> Result<EcuPort, String> {
+        self.port_within(tx_id, rx_id, PORT_WAIT)
+    }
+
+    /// `port`, giving up after `wait`
+    pub fn port_within(&self, tx_id: u32, rx_id: u32, wait: Duration) -> Result<EcuPort, String> {
+        let deadline = Instant::now() + wait;
+        let mut q = self.shared.queues.lock().unwrap();
+        let entry = q.tickets.entry(rx_id).or_insert((0, 0));
+        let ticket = entry.0;
+        entry.0 += 1;
+        while q.tickets.get(&rx_id).map(|t| t.1) != Some(ticket) {
+            let now = Instant::now();
+            if now >= deadline {
+                q.abandoned.insert((rx_id, ticket));
+                return Err(format!(
+                    "ECU 0x{:03X} is still busy with an earlier request after {:?}",
+                    rx_id, wait
+                ));
+            }
+            q = self.shared.changed.wait_timeout(q, deadline - now).unwrap().0;
+        }
+        q.busy.push(rx_id);
+        // Anything queued before this request belongs to an earlier exchange
+        q.frames.remove(&rx_id);
+        Ok(EcuPort {
+            shared: self.shared.clone(),
+            tx_id,
+            rx_id,
+        })
+    }
+
+    /// Claim `ids` for one reader until the listener is dropped. Used in bench
+    /// mode so IMC requests to emulated ECUs go to the bus responder alone.
+    pub fn listen(&self, ids: Vec<u32>) -> BusListener {
+        self.shared.queues.lock().unwrap().listened.extend(&ids);
+        BusListener {
+            shared: self.shared.clone(),
+            ids,
+        }
+    }
+
+    /// Stop the reader thread and close the underlying channel (drop triggers
+    /// PassThruDisconnect). Outstanding ports get "Channel closed" errors.
+    pub fn close(&self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        if let Some(h) = self.reader.lock().unwrap().take() {
+            let _ = h.join();
+        }
+        self.shared.channel.lock().unwrap().take();
+        self.shared.changed.notify_all();
+    }
+
+    fn reader_loop(shared: &Shared) {
+        while shared.running.load(Ordering::Relaxed) {
+            let read = shared.with_channel(|ch| {
+                let timeout_ms = ch.reader_timeout_ms();
+                ch.read(timeout_ms).map(|msgs| (msgs, timeout_ms))
+            });
+            let (msgs, timeout_ms) = match read {
+                Ok(read) => read,
+                Err(e) => {
+                    log::warn!("Channel router read error: {}", e);
+                    thread::sleep(Duration::from_millis(50));
+                    continue;
+                }
+            };
+            if msgs.is_empty() {
+                if timeout_ms == 0 {
+                    thread::sleep(Duration::from_millis(READER_IDLE_MS));
+                }
+                continue;
+            }
+            let mut q = shared.queues.lock().unwrap();
+            for msg in msgs {
+                let queue = q.frames.entry(msg.can_id()).or_default();
+                if queue.len() >= MAX_QUEUED_PER_ID {
+                    queue.pop_front();
+                }
+                queue.push_back(msg);
+            }
+            drop(q);
+            shared.changed.notify_all();
+        }
+    }
+}
+
+impl Drop for ChannelRouter {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl Channel for ChannelRouter {
+    fn send(&self, msg: &PassThruMsg, timeout_ms: u32) -> Result<(), String> {
+        self.shared.with_channel(|ch| ch.send(msg, timeout_ms))
+    }
+
+    fn read(&self, timeout_ms: u32) -> Result<Vec<PassThruMsg>, String> {
+        Ok(self.shared.take_frames(timeout_ms, |q, id| {
+            !q.busy.contains(&id) && !q.listened.contains(&id)
+        }))
+    }
+
+    fn setup_iso15765_filter(&self, tx_id: u32, rx_id: u32) -> Result<u32, String> {
+        self.shared
+            .with_channel(|ch| ch.setup_iso15765_filter(tx_id, rx_id))
+    }
+
+    fn stop_filter(&self, filter_id: u32) -> Result<(), String> {
+        self.shared.with_channel(|ch| ch.stop_filter(filter_id))
+    }
+}
+
+/// Exclusive request queue for one ECU, obtained from `ChannelRouter::port`.
+/// Reads only return frames from `rx_id`. Released on drop.
+pub struct EcuPort {
+    shared: Arc<Shared>,
+    tx_id: u32,
+    rx_id: u32,
+}
+
+impl EcuPort {
+    pub fn tx_id(&self) -> u32 {
+        self.tx_id
+    }
+
+    pub fn rx_id(&self) -> u32 {
+        self.rx_id
+    }
+}
+
+impl Drop for EcuPort {
+    fn drop(&mut self) {
+        let mut q = self.shared.queues.lock().unwrap();
+        q.busy.retain(|&id| id != self.rx_id);
+        let Queues {
+            tickets, abandoned, ..
+        } = &mut *q;
+        if let Some(t) = tickets.get_mut(&self.rx_id) {
+            t.1 += 1;
+            while abandoned.remove(&(self.rx_id, t.1)) {
+                t.1 += 1;
+            }
+        }
+        drop(q);
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Channel for EcuPort {
+    fn send(&self, msg: &PassThruMsg, timeout_ms: u32) -> Result<(), String> {
+        self.shared.with_channel(|ch| ch.send(msg, timeout_ms))
+    }
+
+    fn read(&self, timeout_ms: u32) -> Result<Vec<PassThruMsg>, String> {
+        let rx_id = self.rx_id;
+        Ok(self.shared.take_frames(timeout_ms, |_, id| id == rx_id))
+    }
+
+    fn setup_iso15765_filter(&self, tx_id: u32, rx_id: u32) -> Result<u32, String> {
+        self.shared
+            .with_channel(|ch| ch.setup_iso15765_filter(tx_id, rx_id))
+    }
+
+    fn stop_filter(&self, filter_id: u32) -> Result<(), String> {
+        self.shared.with_channel(|ch| ch.stop_filter(filter_id))
+    }
+}
+
+/// Exclusive reader for a set of CAN IDs, obtained from `ChannelRouter::listen`.
+/// Unlike ports it never blocks other readers; reads fail once the router closes.
+pub struct BusListener {
+    shared: Arc<Shared>,
+    ids: Vec<u32>,
+}
+
+impl BusListener {
+    pub fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+}
+
+impl Drop for BusListener {
+    fn drop(&mut self) {
+        let mut q = self.shared.queues.lock().unwrap();
+        for id in &self.ids {
+            if let Some(pos) = q.listened.iter().position(|l| l == id) {
+                q.listened.remove(pos);
+            }
+        }
+    }
+}
+
+impl Channel for BusListener {
+    fn send(&self, msg: &PassThruMsg, timeout_ms: u32) -> Result<(), String> {
+        self.shared.with_channel(|ch| ch.send(msg, timeout_ms))
+    }
+
+    fn read(&self, timeout_ms: u32) -> Result<Vec<PassThruMsg>, String> {
+        if !self.shared.running.load(Ordering::Relaxed) {
+            return Err("Channel closed".into());
+        }
+        Ok(self
+            .shared
+            .take_frames(timeout_ms, |_, id| self.ids.contains(&id)))
+    }
+
+    fn setup_iso15765_filter(&self, tx_id: u32, rx_id: u32) -> Result<u32, String> {
+        self.shared
+            .with_channel(|ch| ch.setup_iso15765_filter(tx_id, rx_id))
+    }
+
+    fn stop_filter(&self, filter_id: u32) -> Result<(), String> {
+        self.shared.with_channel(|ch| ch.stop_filter(filter_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecu_emulator::{EcuId, EmulatedBus};
+    use crate::uds::client::UdsClient;
+
+    /// Bus where emulated ECUs answer after a per-ECU delay, like a real
+    /// IMC sitting on a long routine while other modules answer promptly.
+    fn router(ecus: &[(EcuId, u64)]) -> Arc<ChannelRouter> {
+        let ids: Vec<EcuId> = ecus.iter().map(|&(id, _)| id).collect();
+        let bus = ecus.iter().fold(EmulatedBus::new(&ids), |bus, &(id, delay)| {
+            bus.with_response_delay(id, Duration::from_millis(delay))
+        });
+        Arc::new(ChannelRouter::new(Box::new(bus)))
+    }
+
+    fn request(router: &ChannelRouter, ecu: EcuId, req: &[u8]) -> Vec<u8> {
+        let port = router.port(ecu.tx_id(), ecu.rx_id()).unwrap();
+        let client = UdsClient::new(port, ecu.tx_id(), ecu.rx_id());
+        client.send_recv(req, 2000, false).unwrap()
+    }
+
+    fn tester_present(router: &ChannelRouter, ecu: EcuId) -> Vec<u8> {
+        request(router, ecu, &[0x3E, 0x00])
+    }
+
+    #[test]
+    fn test_port_reads_only_its_ecu() {
+        let r = router(&[(EcuId::Bcm, 0), (EcuId::Gwm, 0)]);
+        let resp = request(&r, EcuId::Bcm, &[0x22, 0xF1, 0x90]);
+        assert_eq!(&resp[..3], &[0x62, 0xF1, 0x90]);
+        assert_eq!(&resp[3..], b"SAJBL4BVXGCY16353");
+    }
+
+    #[test]
+    fn test_slow_ecu_does_not_block_other_ecu() {
+        let r = router(&[(EcuId::Bcm, 800), (EcuId::Ipc, 10)]);
+
+        let slow_router = r.clone();
+        let slow = thread::spawn(move || {
+            let start = Instant::now();
+            let resp = tester_present(&slow_router, EcuId::Bcm);
+            (resp, start.elapsed())
+        });
+
+        // Let the slow request get in flight first
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        let fast = tester_present(&r, EcuId::Ipc);
+        let fast_elapsed = start.elapsed();
+
+        assert_eq!(fast, vec![0x7E, 0x00]);
+        assert!(
+            fast_elapsed < Duration::from_millis(400),
+            "IPC waited {:?} behind BCM",
+            fast_elapsed
+        );
+
+        let (slow_resp, slow_elapsed) = slow.join().unwrap();
+        assert_eq!(slow_resp, vec![0x7E, 0x00]);
+        assert!(slow_elapsed >= Duration::from_millis(800));
+    }
+
+    #[test]
+    fn test_three_ecus_in_parallel() {
+        let r = router(&[(EcuId::Bcm, 300), (EcuId::Gwm, 300), (EcuId::Ipc, 300)]);
+        let start = Instant::now();
+        let handles: Vec<_> = [EcuId::Bcm, EcuId::Gwm, EcuId::Ipc]
+            .into_iter()
+            .map(|ecu| {
+                let r = r.clone();
+                thread::spawn(move || tester_present(&r, ecu))
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), vec![0x7E, 0x00]);
+        }
+        // Serialised would take ≥900ms
+        assert!(start.elapsed() < Duration::from_millis(800));
+    }
+
+    #[test]
+    fn test_same_ecu_requests_are_fifo() {
+        let r = router(&[(EcuId::Gwm, 100)]);
+        let first = r.port(EcuId::Gwm.tx_id(), EcuId::Gwm.rx_id()).unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let r2 = r.clone();
+        let order2 = order.clone();
+        let waiter = thread::spawn(move || {
+            let _port = r2.port(EcuId::Gwm.tx_id(), EcuId::Gwm.rx_id()).unwrap();
+            order2.lock().unwrap().push("second");
+        });
+
+        thread::sleep(Duration::from_millis(100));
+        // Second port can't be acquired while the first is held
+        assert!(order.lock().unwrap().is_empty());
+        order.lock().unwrap().push("first");
+        drop(first);
+        waiter.join().unwrap();
+        assert_eq!(*order.lock().unwrap(), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_port_wait_times_out_behind_hung_holder() {
+        let r = router(&[(EcuId::Gwm, 0)]);
+        let (tx, rx) = (EcuId::Gwm.tx_id(), EcuId::Gwm.rx_id());
+        let hung = r.port(tx, rx).unwrap();
+
+        let start = Instant::now();
+        let err = r.port_within(tx, rx, Duration::from_millis(100)).err().unwrap();
+        assert!(err.contains("busy"), "{}", err);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // The abandoned ticket is skipped once the holder lets go
+        drop(hung);
+        let port = r.port_within(tx, rx, Duration::from_millis(100)).unwrap();
+        drop(port);
+        assert_eq!(tester_present(&r, EcuId::Gwm), vec![0x7E, 0x00]);
+    }
+
+    #[test]
+    fn test_whole_bus_read_skips_checked_out_ecu() {
+        let r = router(&[(EcuId::Bcm, 0), (EcuId::Gwm, 0)]);
+        let port = r.port(EcuId::Bcm.tx_id(), EcuId::Bcm.rx_id()).unwrap();
+        r.send(
+            &PassThruMsg::new_iso15765(EcuId::Bcm.tx_id(), &[0x3E, 0x00]),
+            100,
+        )
+        .unwrap();
+        r.send(
+            &PassThruMsg::new_iso15765(EcuId::Gwm.tx_id(), &[0x3E, 0x00]),
+            100,
+        )
+        .unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        let bus = r.read(200).unwrap();
+        assert!(bus.iter().all(|m| m.can_id() == EcuId::Gwm.rx_id()));
+        let own = port.read(200).unwrap();
+        assert_eq!(own.len(), 1);
+        assert_eq!(own[0].can_id(), EcuId::Bcm.rx_id());
+    }
+
+    #[test]
+    fn test_listener_claims_its_ids() {
+        let r = router(&[(EcuId::Bcm, 0), (EcuId::Gwm, 0)]);
+        let listener = r.listen(vec![EcuId::Bcm.rx_id()]);
+        for ecu in [EcuId::Bcm, EcuId::Gwm] {
+            r.send(&PassThruMsg::new_iso15765(ecu.tx_id(), &[0x3E, 0x00]), 100)
+                .unwrap();
+        }
+        thread::sleep(Duration::from_millis(50));
+
+        let bus = r.read(200).unwrap();
+        assert_eq!(bus.len(), 1);
+        assert_eq!(bus[0].can_id(), EcuId::Gwm.rx_id());
+        let heard = listener.read(200).unwrap();
+        assert_eq!(heard.len(), 1);
+        assert_eq!(heard[0].can_id(), EcuId::Bcm.rx_id());
+
+        // Released on drop
+        drop(listener);
+        assert_eq!(tester_present(&r, EcuId::Bcm), vec![0x7E, 0x00]);
+        let listener = r.listen(vec![EcuId::Bcm.rx_id()]);
+        r.close();
+        assert!(listener.read(10).is_err());
+    }
+
+    #[test]
+    fn test_close_fails_outstanding_ports() {
+        let r = router(&[(EcuId::Bcm, 0)]);
+        let port = r.port(EcuId::Bcm.tx_id(), EcuId::Bcm.rx_id()).unwrap();
+        r.close();
+        let err = port
+            .send(
+                &PassThruMsg::new_iso15765(EcuId::Bcm.tx_id(), &[0x3E, 0x00]),
+                100,
+            )
+            .unwrap_err();
+        assert!(err.contains("closed"));
+        assert!(port.read(10).unwrap().is_empty());
+    }
+}
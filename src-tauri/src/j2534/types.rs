@@ -12,6 +12,9 @@ pub const FILTER_FLOW_CONTROL: u32 = 3;
 // J2534 Connect Flags
 pub const CAN_29BIT_ID: u32 = 0x0100;
 
+// J2534 RxStatus
+pub const TX_MSG_TYPE: u32 = 0x0001;
+
 // J2534 TxFlags
 pub const ISO15765_FRAME_PAD: u32 = 0x0040;
 
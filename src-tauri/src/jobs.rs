@@ -0,0 +1,438 @@
+//! Background job registry for long-running operations (restore CCF, CAN sniff, full scans,
+//! CAN trace replay, the live CAN monitor).
+//!
+//! Each job runs on its own worker thread and is identified by a numeric ID. Workers report
+//! structured progress through a `JobContext` and poll its cancel flag between steps.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::monitor::MonitorOptions;
+use crate::replay::ReplayOptions;
+use crate::sink::LogSink;
+use crate::sniff::{SniffAction, SniffOptions};
+
+/// Maximum number of finished jobs kept for `list_jobs`
+const MAX_FINISHED_JOBS: usize = 50;
+
+/// Error string returned by cancellation checks
+pub const CANCELLED: &str = "Cancelled";
+
+/// Long-running operations that can be started as a job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobKind {
+    RestoreCcf { sniff: bool },
+    CanSniffRoutine,
+    /// Differential capture around one request; `ecu` is a name or `0x` request ID
+    SniffAction {
+        ecu: String,
+        action: SniffAction,
+        #[serde(default)]
+        options: SniffOptions,
+    },
+    ScanBcm,
+    ScanGwm,
+    ScanIpc,
+    ReplayCan {
+        path: String,
+        #[serde(default)]
+        options: ReplayOptions,
+    },
+    /// Runs until cancelled (or `options.seconds`)
+    MonitorCan {
+        #[serde(default)]
+        options: MonitorOptions,
+    },
+}
+
+impl JobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::RestoreCcf { .. } => "restore_ccf",
+            JobKind::CanSniffRoutine => "can_sniff_routine",
+            JobKind::SniffAction { .. } => "sniff_action",
+            JobKind::ScanBcm => "scan_bcm_full",
+            JobKind::ScanGwm => "scan_gwm_full",
+            JobKind::ScanIpc => "scan_ipc_full",
+            JobKind::ReplayCan { .. } => "replay_can_log",
+            JobKind::MonitorCan { .. } => "monitor_can",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        self != JobStatus::Running
+    }
+}
+
+/// Snapshot of a job, returned by `list_jobs`
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: String,
+    pub status: JobStatus,
+    pub phase: String,
+    pub percent: u8,
+    pub step: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+/// Progress event emitted to the frontend as "job-progress"
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgress {
+    pub job_id: u64,
+    pub status: JobStatus,
+    pub phase: String,
+    pub percent: u8,
+    pub step: String,
+}
+
+struct JobEntry {
+    info: JobInfo,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Registry of running and finished jobs, shared through `AppState`
+pub struct JobRegistry {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, JobEntry>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register a new running job and return its context
+    pub fn create(self: &Arc<Self>, kind: &JobKind) -> JobContext {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let info = JobInfo {
+            id,
+            kind: kind.name().to_string(),
+            status: JobStatus::Running,
+            phase: "Starting".into(),
+            percent: 0,
+            step: String::new(),
+            started_at: chrono::Local::now().format("%H:%M:%S%.3f").to_string(),
+            finished_at: None,
+            result: None,
+            error: None,
+        };
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.insert(
+            id,
+            JobEntry {
+                info,
+                cancel: cancel.clone(),
+            },
+        );
+        Self::prune(&mut jobs);
+        JobContext {
+            id,
+            cancel,
+            registry: Some(self.clone()),
+        }
+    }
+
+    /// Request cancellation. Returns false if the job is unknown or already finished.
+    pub fn cancel(&self, id: u64) -> bool {
+        let jobs = self.jobs.lock().unwrap();
+        match jobs.get(&id) {
+            Some(entry) if !entry.info.status.is_finished() => {
+                entry.cancel.store(true, Ordering::SeqCst);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<JobInfo> {
+        self.jobs.lock().unwrap().get(&id).map(|e| e.info.clone())
+    }
+
+    /// All known jobs, oldest first
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut list: Vec<JobInfo> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|e| e.info.clone())
+            .collect();
+        list.sort_by_key(|j| j.id);
+        list
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut JobInfo)) -> Option<JobInfo> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let entry = jobs.get_mut(&id)?;
+        f(&mut entry.info);
+        Some(entry.info.clone())
+    }
+
+    /// Drop the oldest finished jobs beyond `MAX_FINISHED_JOBS`
+    fn prune(jobs: &mut HashMap<u64, JobEntry>) {
+        let mut finished: Vec<u64> = jobs
+            .values()
+            .filter(|e| e.info.status.is_finished())
+            .map(|e| e.info.id)
+            .collect();
+        if finished.len() <= MAX_FINISHED_JOBS {
+            return;
+        }
+        finished.sort_unstable();
+        for id in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+            jobs.remove(id);
+        }
+    }
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle given to a job worker: progress reporting + cancellation checks.
+/// A detached context (synchronous command path) never cancels and only logs progress.
+#[derive(Clone)]
+pub struct JobContext {
+    id: u64,
+    cancel: Arc<AtomicBool>,
+    registry: Option<Arc<JobRegistry>>,
+}
+
+impl JobContext {
+    pub fn detached() -> Self {
+        Self {
+            id: 0,
+            cancel: Arc::new(AtomicBool::new(false)),
+            registry: None,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Return `Err(CANCELLED)` if cancellation was requested
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(CANCELLED.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sleep in short slices so a cancel request is honoured within ~100ms
+    pub fn sleep(&self, duration: Duration) -> Result<(), String> {
+        let deadline = std::time::Instant::now() + duration;
+        loop {
+            self.check()?;
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            std::thread::sleep((deadline - now).min(Duration::from_millis(100)));
+        }
+    }
+
+    /// Record progress and report it to the sink ("job-progress" event in the GUI)
+    pub fn progress(&self, sink: &dyn LogSink, phase: &str, percent: u8, step: &str) {
+        log::debug!("[job {}] {} {}% {}", self.id, phase, percent, step);
+        if let Some(info) = self.set_progress(phase, percent, step) {
+            sink.progress(&Self::event(&info));
+        }
+    }
+
+    /// Mark the job finished and report the final progress event
+    pub fn finish(&self, sink: &dyn LogSink, outcome: Result<serde_json::Value, String>) {
+        if let Some(info) = self.set_finished(outcome) {
+            log::info!("[job {}] {} finished: {:?}", info.id, info.kind, info.status);
+            sink.progress(&Self::event(&info));
+        }
+    }
+
+    fn set_progress(&self, phase: &str, percent: u8, step: &str) -> Option<JobInfo> {
+        self.registry.as_ref()?.update(self.id, |info| {
+            info.phase = phase.to_string();
+            info.percent = percent.min(100);
+            info.step = step.to_string();
+        })
+    }
+
+    fn set_finished(&self, outcome: Result<serde_json::Value, String>) -> Option<JobInfo> {
+        let cancelled = self.is_cancelled();
+        let registry = self.registry.as_ref()?;
+        let info = registry.update(self.id, |info| {
+            info.finished_at = Some(chrono::Local::now().format("%H:%M:%S%.3f").to_string());
+            match outcome {
+                Ok(value) => {
+                    info.status = JobStatus::Completed;
+                    info.percent = 100;
+                    info.result = Some(value);
+                }
+                Err(_) if cancelled => {
+                    info.status = JobStatus::Cancelled;
+                    info.error = Some(CANCELLED.into());
+                }
+                Err(e) => {
+                    info.status = JobStatus::Failed;
+                    info.error = Some(e);
+                }
+            }
+        });
+        JobRegistry::prune(&mut registry.jobs.lock().unwrap());
+        info
+    }
+
+    fn event(info: &JobInfo) -> JobProgress {
+        JobProgress {
+            job_id: info.id,
+            status: info.status,
+            phase: info.phase.clone(),
+            percent: info.percent,
+            step: info.step.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_assigns_sequential_ids() {
+        let reg = Arc::new(JobRegistry::new());
+        let a = reg.create(&JobKind::ScanBcm);
+        let b = reg.create(&JobKind::CanSniffRoutine);
+        assert_eq!(a.id(), 1);
+        assert_eq!(b.id(), 2);
+        let list = reg.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].kind, "scan_bcm_full");
+        assert_eq!(list[1].kind, "can_sniff_routine");
+        assert!(list.iter().all(|j| j.status == JobStatus::Running));
+    }
+
+    #[test]
+    fn test_progress_updates_registry() {
+        let reg = Arc::new(JobRegistry::new());
+        let ctx = reg.create(&JobKind::RestoreCcf { sniff: false });
+        ctx.set_progress("Transfer", 140, "0x0E06 Results (3/20)");
+        let info = reg.get(ctx.id()).unwrap();
+        assert_eq!(info.phase, "Transfer");
+        assert_eq!(info.percent, 100); // clamped
+        assert_eq!(info.step, "0x0E06 Results (3/20)");
+    }
+
+    #[test]
+    fn test_cancel_sets_flag_and_finish_marks_cancelled() {
+        let reg = Arc::new(JobRegistry::new());
+        let ctx = reg.create(&JobKind::ScanGwm);
+        assert!(ctx.check().is_ok());
+        assert!(reg.cancel(ctx.id()));
+        assert_eq!(ctx.check(), Err(CANCELLED.to_string()));
+
+        ctx.set_finished(Err(CANCELLED.into()));
+        let info = reg.get(ctx.id()).unwrap();
+        assert_eq!(info.status, JobStatus::Cancelled);
+        assert!(info.finished_at.is_some());
+
+        // Finished jobs can't be cancelled again
+        assert!(!reg.cancel(ctx.id()));
+        assert!(!reg.cancel(999));
+    }
+
+    #[test]
+    fn test_finish_completed_and_failed() {
+        let reg = Arc::new(JobRegistry::new());
+        let ok = reg.create(&JobKind::ScanIpc);
+        let bad = reg.create(&JobKind::ScanIpc);
+        ok.set_finished(Ok(serde_json::json!("done")));
+        bad.set_finished(Err("No channel".into()));
+
+        let ok_info = reg.get(ok.id()).unwrap();
+        assert_eq!(ok_info.status, JobStatus::Completed);
+        assert_eq!(ok_info.percent, 100);
+        assert_eq!(ok_info.result, Some(serde_json::json!("done")));
+
+        let bad_info = reg.get(bad.id()).unwrap();
+        assert_eq!(bad_info.status, JobStatus::Failed);
+        assert_eq!(bad_info.error.as_deref(), Some("No channel"));
+    }
+
+    #[test]
+    fn test_sleep_returns_early_on_cancel() {
+        let reg = Arc::new(JobRegistry::new());
+        let ctx = reg.create(&JobKind::CanSniffRoutine);
+        let id = ctx.id();
+        let reg2 = reg.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(150));
+            reg2.cancel(id);
+        });
+        let start = std::time::Instant::now();
+        let res = ctx.sleep(Duration::from_secs(10));
+        canceller.join().unwrap();
+        assert_eq!(res, Err(CANCELLED.to_string()));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_detached_context_never_cancels() {
+        let ctx = JobContext::detached();
+        assert!(ctx.check().is_ok());
+        assert!(ctx.sleep(Duration::from_millis(1)).is_ok());
+        assert!(ctx.set_progress("x", 1, "y").is_none());
+    }
+
+    #[test]
+    fn test_finished_history_is_capped() {
+        let reg = Arc::new(JobRegistry::new());
+        for _ in 0..(MAX_FINISHED_JOBS + 5) {
+            let ctx = reg.create(&JobKind::ScanBcm);
+            ctx.set_finished(Ok(serde_json::Value::Null));
+        }
+        let running = reg.create(&JobKind::ScanGwm);
+        let list = reg.list();
+        assert_eq!(list.len(), MAX_FINISHED_JOBS + 1);
+        // Oldest finished jobs dropped, running job kept
+        assert_eq!(list[0].id, 6);
+        assert_eq!(list.last().unwrap().id, running.id());
+    }
+
+    #[test]
+    fn test_job_kind_deserialize() {
+        let k: JobKind = serde_json::from_str(r#"{"type":"restore_ccf","sniff":true}"#).unwrap();
+        assert_eq!(k, JobKind::RestoreCcf { sniff: true });
+        let k: JobKind = serde_json::from_str(r#"{"type":"scan_gwm"}"#).unwrap();
+        assert_eq!(k, JobKind::ScanGwm);
+    }
+}
@@ -1,6 +1,18 @@
+pub mod api;
+pub mod archive;
+pub mod broadcast;
+pub mod canlog;
+pub mod ccf;
 pub mod commands;
+pub mod dbc;
 pub mod ecu_emulator;
 pub mod j2534;
+pub mod jobs;
+pub mod monitor;
+pub mod replay;
+pub mod reverse;
+pub mod sink;
+pub mod sniff;
 pub mod state;
 pub mod uds;
 
@@ -62,25 +74,70 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             commands::discover_devices,
             commands::connect,
+            commands::connect_remote,
+            commands::connect_simulated,
             commands::disconnect,
             commands::toggle_bench_mode,
             commands::get_bench_mode_status,
+            commands::set_vehicle_state,
             commands::read_ecu_info,
             commands::run_routine,
             commands::read_ccf,
             commands::read_did,
+            commands::read_dtc,
             commands::list_routines,
             commands::export_logs,
             commands::scan_bcm_full,
             commands::scan_gwm_full,
             commands::scan_ipc_full,
             commands::compare_ccf,
+            commands::check_ccf,
+            commands::list_ccf_rules,
+            commands::import_ccf_rules,
+            commands::preview_ccf_edit,
+            commands::write_ccf_edit,
+            commands::undo_ccf_edit,
+            commands::search_ccf_options,
+            commands::list_ccf_tables,
+            commands::import_ccf_exml,
+            commands::list_archived_vehicles,
+            commands::get_vehicle_history,
+            commands::get_snapshot,
+            commands::diff_snapshots,
+            commands::rollback_ccf,
             commands::can_sniff_routine,
+            commands::sniff_action,
             commands::restore_ccf,
+            commands::convert_can_log,
+            commands::decode_can_log,
+            commands::decode_can_frames,
+            commands::analyze_can_log,
+            commands::analyze_can_frames,
+            commands::start_can_monitor,
+            commands::stop_can_monitor,
+            commands::get_can_monitor,
+            commands::save_can_monitor,
+            commands::start_job,
+            commands::cancel_job,
+            commands::list_jobs,
+            commands::start_api_server,
+            commands::stop_api_server,
+            commands::get_api_status,
+            commands::start_bridge,
+            commands::stop_bridge,
+            commands::get_bridge_status,
         ])
         .setup(|_app| {
             log::info!("Tauri setup hook running");
 
+            // Background traffic (bench-mode bus responder) goes to the frontend log
+            {
+                use tauri::Manager;
+                let handle = _app.handle().clone();
+                _app.state::<AppState>()
+                    .set_background_sink(std::sync::Arc::new(handle));
+            }
+
             // Always open devtools for debugging
             {
                 use tauri::Manager;
@@ -93,6 +150,17 @@ pub fn run() {
                 }
             }
 
+            // Opt-in automation API for scripts/test rigs: UDSAPP_API_PORT=<port>
+            // (token from UDSAPP_API_TOKEN, or generated and logged)
+            if let Ok(port) = std::env::var("UDSAPP_API_PORT") {
+                let port = port.parse().ok();
+                let token = std::env::var("UDSAPP_API_TOKEN").ok();
+                match commands::start_api_server_inner(_app.handle(), port, token) {
+                    Ok(info) => log::info!("Automation API: {} (token {})", info.url, info.token),
+                    Err(e) => log::error!("Automation API failed to start: {}", e),
+                }
+            }
+
             log::info!("Setup complete, window should be visible");
             Ok(())
         })
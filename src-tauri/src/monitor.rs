@@ -0,0 +1,415 @@
+//! Live CAN bus monitor: per-ID statistics, bus load and a bounded frame buffer.
+//!
+//! `BusMonitor` only does the bookkeeping; the `monitor_can` job reads the bus,
+//! feeds it frames and streams `MonitorUpdate`s to the sink ("can-monitor" events
+//! in the GUI). The buffer outlives the job so it can be saved after a stop.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::canlog::{CanFrame, CanLog, CaptureFormat};
+
+/// Bytes changed within this long are flagged in `IdStats::changed`
+const HIGHLIGHT_US: u64 = 1_000_000;
+
+/// Bus load and frame rate are measured over this window
+const LOAD_WINDOW_US: u64 = 1_000_000;
+
+/// Weight of a new interval in the period and jitter averages
+const EMA_WEIGHT: f64 = 1.0 / 16.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorOptions {
+    /// Frames kept for saving; the oldest are dropped beyond this
+    pub buffer_frames: usize,
+    /// Interval between update events
+    pub update_ms: u64,
+    /// Nominal bus bitrate for the load estimate
+    pub bitrate: u32,
+    /// Most raw frames sent with one update (0 = statistics only)
+    pub stream_frames: usize,
+    /// Stop after this long (0 = until stopped)
+    pub seconds: u32,
+}
+
+impl Default for MonitorOptions {
+    fn default() -> Self {
+        Self {
+            buffer_frames: 100_000,
+            update_ms: 250,
+            bitrate: 500_000,
+            stream_frames: 500,
+            seconds: 0,
+        }
+    }
+}
+
+impl MonitorOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.buffer_frames == 0 {
+            return Err("Monitor buffer must hold at least one frame".into());
+        }
+        if self.update_ms < 50 {
+            return Err(format!(
+                "Update interval {} ms is below 50 ms",
+                self.update_ms
+            ));
+        }
+        if self.bitrate == 0 {
+            return Err("Bitrate must be positive".into());
+        }
+        Ok(())
+    }
+}
+
+/// Per-ID state as shown in the monitor table
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IdStats {
+    pub can_id: u32,
+    pub extended: bool,
+    pub count: u64,
+    /// Latest payload
+    pub data: Vec<u8>,
+    /// Bytes of `data` that changed within the last second
+    pub changed: Vec<bool>,
+    /// Time of the latest frame (µs since the monitor started)
+    pub last_us: u64,
+    /// Moving average of the interval between frames
+    pub period_us: Option<u64>,
+    /// Moving average of each interval's deviation from the period
+    pub jitter_us: Option<u64>,
+}
+
+struct IdState {
+    extended: bool,
+    count: u64,
+    data: Vec<u8>,
+    /// When each byte last changed
+    byte_changed_us: Vec<Option<u64>>,
+    last_us: u64,
+    period_us: Option<f64>,
+    jitter_us: Option<f64>,
+}
+
+impl IdState {
+    fn new(frame: &CanFrame) -> Self {
+        Self {
+            extended: frame.is_extended(),
+            count: 1,
+            data: frame.data.clone(),
+            byte_changed_us: vec![None; frame.data.len()],
+            last_us: frame.timestamp_us,
+            period_us: None,
+            jitter_us: None,
+        }
+    }
+
+    fn update(&mut self, frame: &CanFrame) {
+        let interval = frame.timestamp_us.saturating_sub(self.last_us) as f64;
+        let period = match self.period_us {
+            Some(p) => p + (interval - p) * EMA_WEIGHT,
+            None => interval,
+        };
+        let deviation = (interval - period).abs();
+        self.jitter_us = Some(match self.jitter_us {
+            Some(j) => j + (deviation - j) * EMA_WEIGHT,
+            None => deviation,
+        });
+        self.period_us = Some(period);
+
+        self.byte_changed_us.resize(frame.data.len(), None);
+        for (i, b) in frame.data.iter().enumerate() {
+            if self.data.get(i) != Some(b) {
+                self.byte_changed_us[i] = Some(frame.timestamp_us);
+            }
+        }
+        self.data = frame.data.clone();
+        self.last_us = frame.timestamp_us;
+        self.count += 1;
+    }
+
+    fn stats(&self, can_id: u32, now_us: u64) -> IdStats {
+        IdStats {
+            can_id,
+            extended: self.extended,
+            count: self.count,
+            data: self.data.clone(),
+            changed: self
+                .byte_changed_us
+                .iter()
+                .map(|t| t.is_some_and(|t| now_us.saturating_sub(t) < HIGHLIGHT_US))
+                .collect(),
+            last_us: self.last_us,
+            period_us: self.period_us.map(|p| p.round() as u64),
+            jitter_us: self.jitter_us.map(|j| j.round() as u64),
+        }
+    }
+}
+
+/// Monitor state at one moment
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MonitorSnapshot {
+    /// The `monitor_can` job, if still running
+    pub job_id: Option<u64>,
+    pub elapsed_ms: u64,
+    pub frames: u64,
+    /// Frames in the last second
+    pub frames_per_sec: u64,
+    /// Share of the bitrate used in the last second (nominal frame bits, no stuffing)
+    pub bus_load_pct: f64,
+    pub buffered: usize,
+    /// Frames dropped from the buffer to stay within `buffer_frames`
+    pub overwritten: u64,
+    pub ids: Vec<IdStats>,
+}
+
+/// Streamed to the frontend as "can-monitor"
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorUpdate {
+    #[serde(flatten)]
+    pub snapshot: MonitorSnapshot,
+    /// Frames received since the previous update (at most `stream_frames`)
+    pub new_frames: Vec<CanFrame>,
+    /// Frames received since the previous update but not included above
+    pub skipped_frames: usize,
+}
+
+/// Bits on the wire for a data frame, without stuff bits: SOF, arbitration,
+/// control, data, CRC, ACK, EOF and the interframe space
+pub fn frame_bits(extended: bool, len: usize) -> u64 {
+    let overhead = if extended { 67 } else { 47 };
+    overhead + 8 * len as u64
+}
+
+pub struct BusMonitor {
+    options: MonitorOptions,
+    job_id: Option<u64>,
+    started: Instant,
+    start_unix_us: u64,
+    /// Elapsed time frozen by `stop`
+    stopped_us: Option<u64>,
+    ids: BTreeMap<u32, IdState>,
+    buffer: VecDeque<CanFrame>,
+    overwritten: u64,
+    total: u64,
+    /// (time, bits) of the frames inside `LOAD_WINDOW_US`
+    window: VecDeque<(u64, u64)>,
+    window_bits: u64,
+    /// Frames not yet streamed
+    pending: Vec<CanFrame>,
+    skipped: usize,
+}
+
+impl BusMonitor {
+    pub fn new(options: MonitorOptions, job_id: Option<u64>) -> Self {
+        Self {
+            options,
+            job_id,
+            started: Instant::now(),
+            start_unix_us: chrono::Utc::now().timestamp_micros().max(0) as u64,
+            stopped_us: None,
+            ids: BTreeMap::new(),
+            buffer: VecDeque::new(),
+            overwritten: 0,
+            total: 0,
+            window: VecDeque::new(),
+            window_bits: 0,
+            pending: Vec::new(),
+            skipped: 0,
+        }
+    }
+
+    pub fn options(&self) -> &MonitorOptions {
+        &self.options
+    }
+
+    /// The `monitor_can` job feeding this monitor, while it runs
+    pub fn job_id(&self) -> Option<u64> {
+        self.job_id
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.job_id.is_some()
+    }
+
+    /// Microseconds since the monitor started (frame timestamps use the same clock)
+    pub fn elapsed_us(&self) -> u64 {
+        self.stopped_us
+            .unwrap_or_else(|| self.started.elapsed().as_micros() as u64)
+    }
+
+    /// The job has ended; the statistics and buffer stay available
+    pub fn stop(&mut self) {
+        self.stopped_us = Some(self.elapsed_us());
+        self.job_id = None;
+    }
+
+    /// Take one frame (timestamps in µs since the monitor started, in order)
+    pub fn record(&mut self, frame: CanFrame) {
+        self.total += 1;
+        match self.ids.get_mut(&frame.can_id) {
+            Some(state) => state.update(&frame),
+            None => {
+                self.ids.insert(frame.can_id, IdState::new(&frame));
+            }
+        }
+
+        let bits = frame_bits(frame.is_extended(), frame.data.len());
+        self.window.push_back((frame.timestamp_us, bits));
+        self.window_bits += bits;
+        self.trim_window(frame.timestamp_us);
+
+        if self.pending.len() < self.options.stream_frames {
+            self.pending.push(frame.clone());
+        } else {
+            self.skipped += 1;
+        }
+        if self.buffer.len() == self.options.buffer_frames {
+            self.buffer.pop_front();
+            self.overwritten += 1;
+        }
+        self.buffer.push_back(frame);
+    }
+
+    fn trim_window(&mut self, now_us: u64) {
+        while let Some(&(t, bits)) = self.window.front() {
+            if now_us.saturating_sub(t) < LOAD_WINDOW_US {
+                break;
+            }
+            self.window.pop_front();
+            self.window_bits -= bits;
+        }
+    }
+
+    pub fn snapshot(&mut self, now_us: u64) -> MonitorSnapshot {
+        self.trim_window(now_us);
+        // Until a full window has passed, rate and load cover the time so far
+        let window_us = now_us.clamp(1, LOAD_WINDOW_US);
+        MonitorSnapshot {
+            job_id: self.job_id,
+            elapsed_ms: now_us / 1000,
+            frames: self.total,
+            frames_per_sec: self.window.len() as u64 * 1_000_000 / window_us,
+            bus_load_pct: self.window_bits as f64 * 100.0 * 1_000_000.0
+                / (self.options.bitrate as f64 * window_us as f64),
+            buffered: self.buffer.len(),
+            overwritten: self.overwritten,
+            ids: self
+                .ids
+                .iter()
+                .map(|(id, state)| state.stats(*id, now_us))
+                .collect(),
+        }
+    }
+
+    /// Snapshot plus the frames received since the previous update
+    pub fn update(&mut self, now_us: u64) -> MonitorUpdate {
+        MonitorUpdate {
+            snapshot: self.snapshot(now_us),
+            new_frames: std::mem::take(&mut self.pending),
+            skipped_frames: std::mem::replace(&mut self.skipped, 0),
+        }
+    }
+
+    /// The buffered frames as a capture, ready for `canlog::save`
+    pub fn to_log(&self) -> CanLog {
+        let frames = self
+            .buffer
+            .iter()
+            .map(|f| CanFrame {
+                timestamp_us: self.start_unix_us + f.timestamp_us,
+                ..f.clone()
+            })
+            .collect();
+        CanLog::from_absolute(CaptureFormat::DumpTxt, frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(ms: u64, can_id: u32, data: &[u8]) -> CanFrame {
+        CanFrame::new(ms * 1000, can_id, data.to_vec())
+    }
+
+    #[test]
+    fn test_period_jitter_and_changed_bytes() {
+        let mut m = BusMonitor::new(MonitorOptions::default(), Some(1));
+        for (i, ms) in [0, 20, 40, 60, 80].into_iter().enumerate() {
+            m.record(frame(ms, 0x070, &[0xAA, i as u8 & 0x0F, 0x00]));
+        }
+        m.record(frame(100, 0x400, &[0x08]));
+        let snap = m.snapshot(100_000);
+        assert_eq!(snap.frames, 6);
+        let s = &snap.ids[0];
+        assert_eq!((s.can_id, s.count), (0x070, 5));
+        assert_eq!(s.period_us, Some(20_000));
+        assert_eq!(s.jitter_us, Some(0));
+        assert_eq!(s.data, vec![0xAA, 4, 0]);
+        assert_eq!(s.changed, vec![false, true, false]);
+
+        // A second later the counter byte is no longer highlighted
+        assert_eq!(m.snapshot(1_100_000).ids[0].changed, vec![false; 3]);
+        assert_eq!(m.snapshot(1_100_000).ids[1].period_us, None);
+    }
+
+    #[test]
+    fn test_bus_load_and_rate() {
+        assert_eq!(frame_bits(false, 8), 111);
+        assert_eq!(frame_bits(true, 0), 67);
+        let mut m = BusMonitor::new(MonitorOptions::default(), Some(1));
+        // 500 standard 8-byte frames in one second: 55 500 bits of 500 000
+        for i in 0..500 {
+            m.record(frame(i * 2, 0x100, &[0; 8]));
+        }
+        let snap = m.snapshot(1_000_000);
+        assert_eq!(snap.frames_per_sec, 499);
+        assert!(
+            (snap.bus_load_pct - 11.078).abs() < 0.01,
+            "{}",
+            snap.bus_load_pct
+        );
+        // Nothing since: the window empties
+        assert_eq!(m.snapshot(3_000_000).bus_load_pct, 0.0);
+    }
+
+    #[test]
+    fn test_ring_buffer_and_streaming() {
+        let options = MonitorOptions {
+            buffer_frames: 3,
+            stream_frames: 2,
+            ..MonitorOptions::default()
+        };
+        let mut m = BusMonitor::new(options, Some(1));
+        for i in 0..5 {
+            m.record(frame(100 + i * 10, 0x200 + i as u32, &[i as u8]));
+        }
+        let update = m.update(200_000);
+        assert_eq!(update.new_frames.len(), 2);
+        assert_eq!(update.skipped_frames, 3);
+        assert_eq!(
+            (update.snapshot.buffered, update.snapshot.overwritten),
+            (3, 2)
+        );
+        assert!(m.update(200_000).new_frames.is_empty());
+
+        let log = m.to_log();
+        let ids: Vec<u32> = log.frames.iter().map(|f| f.can_id).collect();
+        assert_eq!(ids, vec![0x202, 0x203, 0x204]);
+        assert_eq!(log.frames[0].timestamp_us, 0);
+        assert_eq!(log.frames[2].timestamp_us, 20_000);
+
+        m.stop();
+        assert!(!m.is_running());
+        assert!(MonitorOptions {
+            update_ms: 10,
+            ..MonitorOptions::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,356 @@
+//! Replay of captured CAN traffic onto a raw CAN channel with the capture's
+//! original inter-frame timing.
+//!
+//! The engine is transport-agnostic: frames go to a `send` callback, so the same
+//! loop drives a J2534 channel or a test recorder. Each frame waits for its
+//! target time (sleep, then a short spin for the last millisecond) and its
+//! lateness is recorded as jitter.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::canlog::CanFrame;
+use crate::jobs::JobContext;
+use crate::sink::LogSink;
+
+/// Lateness samples kept for the percentile (mean and max cover every frame)
+const MAX_JITTER_SAMPLES: usize = 100_000;
+
+/// Sleep until this close to a frame's target time, then spin
+const SPIN_WINDOW: Duration = Duration::from_millis(1);
+
+/// How often progress is reported while replaying
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayOptions {
+    /// Time scale: 2.0 plays twice as fast, 0.5 at half speed
+    pub speed: f64,
+    /// Passes over the trace; 0 repeats until the job is cancelled
+    pub loops: u32,
+    /// Only replay these CAN IDs (empty = all)
+    pub include: Vec<u32>,
+    /// Never replay these CAN IDs
+    pub exclude: Vec<u32>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            loops: 1,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl ReplayOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.speed.is_finite() || self.speed <= 0.0 {
+            return Err(format!("Replay speed must be positive, got {}", self.speed));
+        }
+        Ok(())
+    }
+
+    fn selects(&self, can_id: u32) -> bool {
+        (self.include.is_empty() || self.include.contains(&can_id))
+            && !self.exclude.contains(&can_id)
+    }
+}
+
+/// Lateness of frames against their scheduled send time, in microseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct JitterStats {
+    pub mean_us: u64,
+    pub p95_us: u64,
+    pub max_us: u64,
+}
+
+/// Outcome of a replay
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReplayStats {
+    pub frames_in_trace: usize,
+    /// Frames per pass after the include/exclude filters
+    pub frames_selected: usize,
+    pub frames_sent: u64,
+    pub send_errors: u64,
+    pub loops_completed: u32,
+    /// The job was cancelled before the last pass finished
+    pub cancelled: bool,
+    pub duration_ms: u64,
+    pub jitter: JitterStats,
+}
+
+/// Filtered frames with their send offset from the start of a pass (speed applied)
+pub fn plan<'a>(frames: &'a [CanFrame], opts: &ReplayOptions) -> Vec<(Duration, &'a CanFrame)> {
+    let selected: Vec<&CanFrame> = frames.iter().filter(|f| opts.selects(f.can_id)).collect();
+    let Some(first) = selected.first().map(|f| f.timestamp_us) else {
+        return Vec::new();
+    };
+    selected
+        .into_iter()
+        .map(|f| {
+            let offset = f.timestamp_us.saturating_sub(first) as f64 / opts.speed;
+            (Duration::from_micros(offset as u64), f)
+        })
+        .collect()
+}
+
+/// Time between the start of one pass and the next: the trace length plus one
+/// average frame gap, so the loop seam keeps the trace's rhythm
+fn loop_period(plan: &[(Duration, &CanFrame)]) -> Duration {
+    let last = plan.last().map_or(Duration::ZERO, |(t, _)| *t);
+    let gap = match plan.len() {
+        0 | 1 => Duration::ZERO,
+        n => last / (n as u32 - 1),
+    };
+    (last + gap).max(SPIN_WINDOW)
+}
+
+/// Wait until `target`, honouring cancellation while sleeping
+fn wait_until(target: Instant, job: &JobContext) -> Result<(), String> {
+    job.check()?;
+    let now = Instant::now();
+    if target > now + SPIN_WINDOW {
+        job.sleep(target - now - SPIN_WINDOW)?;
+    }
+    while Instant::now() < target {
+        std::hint::spin_loop();
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct JitterRecorder {
+    samples: Vec<u64>,
+    sum: u64,
+    count: u64,
+    max: u64,
+}
+
+impl JitterRecorder {
+    fn record(&mut self, late_us: u64) {
+        if self.samples.len() < MAX_JITTER_SAMPLES {
+            self.samples.push(late_us);
+        }
+        self.sum += late_us;
+        self.count += 1;
+        self.max = self.max.max(late_us);
+    }
+
+    fn stats(mut self) -> JitterStats {
+        if self.count == 0 {
+            return JitterStats::default();
+        }
+        self.samples.sort_unstable();
+        let p95 = (self.samples.len() * 95).div_ceil(100).max(1) - 1;
+        JitterStats {
+            mean_us: self.sum / self.count,
+            p95_us: self.samples[p95],
+            max_us: self.max,
+        }
+    }
+}
+
+/// Replay `frames` through `send`. A send error before anything went out aborts
+/// (wrong channel); later errors are counted and the replay continues. A cancel
+/// stops it with the stats so far.
+pub fn replay(
+    frames: &[CanFrame],
+    opts: &ReplayOptions,
+    sink: &dyn LogSink,
+    job: &JobContext,
+    mut send: impl FnMut(&CanFrame) -> Result<(), String>,
+) -> Result<ReplayStats, String> {
+    opts.validate()?;
+    let plan = plan(frames, opts);
+    if plan.is_empty() {
+        return Err("No frames left to replay after the ID filters".into());
+    }
+    let period = loop_period(&plan);
+    let mut stats = ReplayStats {
+        frames_in_trace: frames.len(),
+        frames_selected: plan.len(),
+        ..Default::default()
+    };
+    let mut jitter = JitterRecorder::default();
+    let loops = if opts.loops == 0 {
+        "∞".to_string()
+    } else {
+        opts.loops.to_string()
+    };
+    let total = plan.len() as u64 * opts.loops as u64;
+    let start = Instant::now();
+    let mut last_progress = start;
+
+    'passes: while opts.loops == 0 || stats.loops_completed < opts.loops {
+        let pass_start = start + period * stats.loops_completed;
+        for (offset, frame) in &plan {
+            let target = pass_start + *offset;
+            if wait_until(target, job).is_err() {
+                stats.cancelled = true;
+                break 'passes;
+            }
+            jitter.record(target.elapsed().as_micros() as u64);
+            match send(frame) {
+                Ok(()) => stats.frames_sent += 1,
+                Err(e) if stats.frames_sent == 0 => {
+                    return Err(format!("Send 0x{:03X} failed: {}", frame.can_id, e))
+                }
+                Err(e) => {
+                    stats.send_errors += 1;
+                    log::debug!("Replay: send 0x{:03X} failed: {}", frame.can_id, e);
+                }
+            }
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                let done = stats.frames_sent + stats.send_errors;
+                let percent = (done * 100)
+                    .checked_div(total)
+                    .map_or(0, |p| p.min(99) as u8);
+                job.progress(
+                    sink,
+                    "Replaying",
+                    percent,
+                    &format!(
+                        "loop {}/{}, {} frames sent",
+                        stats.loops_completed + 1,
+                        loops,
+                        stats.frames_sent
+                    ),
+                );
+            }
+        }
+        stats.loops_completed += 1;
+    }
+
+    stats.duration_ms = start.elapsed().as_millis() as u64;
+    stats.jitter = jitter.stats();
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::NullSink;
+
+    fn frame(ms: u64, can_id: u32) -> CanFrame {
+        CanFrame::new(ms * 1000, can_id, vec![can_id as u8])
+    }
+
+    #[test]
+    fn test_plan_filters_and_scales() {
+        let frames = [
+            frame(100, 0x400),
+            frame(120, 0x407),
+            frame(140, 0x400),
+            frame(200, 0x460),
+        ];
+        let opts = ReplayOptions {
+            speed: 2.0,
+            exclude: vec![0x407],
+            ..Default::default()
+        };
+        let plan = plan(&frames, &opts);
+        let offsets: Vec<u64> = plan.iter().map(|(t, _)| t.as_millis() as u64).collect();
+        assert_eq!(offsets, vec![0, 20, 50]);
+
+        let only = ReplayOptions {
+            include: vec![0x460],
+            ..Default::default()
+        };
+        assert_eq!(super::plan(&frames, &only).len(), 1);
+        assert!(ReplayOptions {
+            speed: 0.0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_replay_keeps_timing_and_loops() {
+        let frames = [frame(0, 0x100), frame(30, 0x200), frame(60, 0x300)];
+        let opts = ReplayOptions {
+            loops: 2,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let mut sent = Vec::new();
+        let stats = replay(
+            &frames,
+            &opts,
+            &NullSink,
+            &JobContext::detached(),
+            |frame| {
+                sent.push((frame.can_id, start.elapsed()));
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stats.frames_sent, 6);
+        assert_eq!(stats.loops_completed, 2);
+        let ids: Vec<u32> = sent.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![0x100, 0x200, 0x300, 0x100, 0x200, 0x300]);
+        // Second pass starts one average gap (30 ms) after the last frame
+        for (i, (_, at)) in sent.iter().enumerate() {
+            let expected = Duration::from_millis(30 * i as u64);
+            assert!(*at >= expected, "frame {} sent early: {:?}", i, at);
+            assert!(
+                *at < expected + Duration::from_millis(20),
+                "frame {} late: {:?}",
+                i,
+                at
+            );
+        }
+        assert!(stats.jitter.max_us >= stats.jitter.p95_us);
+        assert!(stats.jitter.max_us >= stats.jitter.mean_us);
+    }
+
+    #[test]
+    fn test_replay_errors_and_cancel() {
+        let frames = [frame(0, 0x100), frame(1, 0x200)];
+        let job = JobContext::detached();
+        let err = replay(&frames, &ReplayOptions::default(), &NullSink, &job, |_| {
+            Err("no channel".into())
+        })
+        .unwrap_err();
+        assert!(err.contains("no channel"));
+
+        let mut calls = 0;
+        let stats = replay(&frames, &ReplayOptions::default(), &NullSink, &job, |_| {
+            calls += 1;
+            if calls == 1 {
+                Ok(())
+            } else {
+                Err("bus off".into())
+            }
+        })
+        .unwrap();
+        assert_eq!((stats.frames_sent, stats.send_errors), (1, 1));
+
+        // Endless loop stops at the first cancel check, with what it sent so far
+        let registry = std::sync::Arc::new(crate::jobs::JobRegistry::new());
+        let job = registry.create(&crate::jobs::JobKind::CanSniffRoutine);
+        let opts = ReplayOptions {
+            loops: 0,
+            ..Default::default()
+        };
+        let mut count = 0;
+        let stats = replay(&frames, &opts, &NullSink, &job, |_| {
+            count += 1;
+            if count == 50 {
+                registry.cancel(job.id());
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert!(stats.cancelled);
+        assert_eq!(stats.frames_sent, 50);
+        assert_eq!(stats.loops_completed, 25);
+    }
+}
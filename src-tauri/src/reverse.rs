@@ -0,0 +1,727 @@
+//! Signal reverse-engineering for unlabeled CAN IDs.
+//!
+//! `analyze` goes through a capture ID by ID and reports constant bytes, alive
+//! counters, checksum candidates and the bit fields that change, and how those
+//! fields step around annotated events ("ignition on" at 12.5 s). The findings
+//! become a draft DBC (`Analysis::draft_dbc`) for the team to refine.
+//!
+//! Counters and fields are searched within single bytes, and checksums over a
+//! contiguous byte range of the payload (no data ID or CAN ID folded in), so a
+//! multi-byte value shows up as one field per byte.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::canlog::CanFrame;
+use crate::dbc::{BitField, ByteOrder, Dbc, Message, Signal};
+
+/// Share of consecutive frames a counter must explain
+const COUNTER_MATCH: f64 = 0.95;
+
+/// Share of frames a checksum must explain
+const CHECKSUM_MATCH: f64 = 0.98;
+
+/// A labelled moment in the capture ("ignition on", "0x6038 started")
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanEvent {
+    /// Milliseconds since the start of the capture
+    pub time_ms: u64,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisOptions {
+    /// Only these IDs (empty = all)
+    pub ids: Vec<u32>,
+    /// IDs with fewer frames are listed but not analysed
+    pub min_frames: usize,
+    /// Frames this long before and after an event are compared
+    pub event_window_ms: u64,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            ids: Vec::new(),
+            min_frames: 10,
+            event_window_ms: 1000,
+        }
+    }
+}
+
+// ─── Checksums ───────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumKind {
+    Xor,
+    /// Sum of the bytes, mod 256
+    Sum,
+    /// Poly 0x1D, init and final XOR 0xFF (as `broadcast::crc8_j1850`)
+    Crc8J1850,
+    /// Poly 0x1D, init and final XOR 0x00
+    Crc8J1850Zero,
+    /// Poly 0x2F, init and final XOR 0xFF (AUTOSAR CRC8H2F)
+    Crc8Autosar,
+    /// Poly 0x07, init and final XOR 0x00
+    Crc8Smbus,
+}
+
+impl ChecksumKind {
+    pub fn all() -> &'static [ChecksumKind] {
+        &[
+            ChecksumKind::Xor,
+            ChecksumKind::Sum,
+            ChecksumKind::Crc8J1850,
+            ChecksumKind::Crc8J1850Zero,
+            ChecksumKind::Crc8Autosar,
+            ChecksumKind::Crc8Smbus,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumKind::Xor => "XOR",
+            ChecksumKind::Sum => "SUM8",
+            ChecksumKind::Crc8J1850 => "CRC8 SAE J1850",
+            ChecksumKind::Crc8J1850Zero => "CRC8 J1850 (init 0)",
+            ChecksumKind::Crc8Autosar => "CRC8 AUTOSAR",
+            ChecksumKind::Crc8Smbus => "CRC8 SMBus",
+        }
+    }
+
+    pub fn compute(self, data: &[u8]) -> u8 {
+        match self {
+            ChecksumKind::Xor => data.iter().fold(0, |acc, b| acc ^ b),
+            ChecksumKind::Sum => data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)),
+            ChecksumKind::Crc8J1850 => crc8(0x1D, 0xFF, 0xFF, data),
+            ChecksumKind::Crc8J1850Zero => crc8(0x1D, 0x00, 0x00, data),
+            ChecksumKind::Crc8Autosar => crc8(0x2F, 0xFF, 0xFF, data),
+            ChecksumKind::Crc8Smbus => crc8(0x07, 0x00, 0x00, data),
+        }
+    }
+}
+
+fn crc8(poly: u8, init: u8, xor_out: u8, data: &[u8]) -> u8 {
+    let mut crc = init;
+    for &b in data {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ poly
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc ^ xor_out
+}
+
+// ─── Findings ────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConstantByte {
+    pub index: usize,
+    pub value: u8,
+}
+
+/// Field that goes up by one per frame and wraps from `max` to `min`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Counter {
+    pub byte: usize,
+    /// Lowest bit within the byte
+    pub bit: u8,
+    pub length: u8,
+    pub min: u8,
+    pub max: u8,
+    pub match_pct: f64,
+}
+
+/// `byte` equals `kind` computed over bytes `from..to`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Checksum {
+    pub byte: usize,
+    pub kind: ChecksumKind,
+    pub from: usize,
+    pub to: usize,
+    pub match_pct: f64,
+}
+
+/// Run of bits within one byte that change (DBC little-endian numbering)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldCandidate {
+    pub start_bit: u16,
+    pub length: u16,
+    /// Distinct raw values seen
+    pub distinct: usize,
+    /// Frames whose value differs from the previous frame's
+    pub changes: usize,
+}
+
+/// A field whose values before and after an event don't overlap
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldStep {
+    pub start_bit: u16,
+    pub length: u16,
+    pub before: Vec<u64>,
+    pub after: Vec<u64>,
+}
+
+/// What an ID did around one event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventCorrelation {
+    pub event: String,
+    pub time_ms: u64,
+    /// Silent before the event, sending after it
+    pub started: bool,
+    /// Sending before the event, silent after it
+    pub stopped: bool,
+    pub fields: Vec<FieldStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IdAnalysis {
+    pub can_id: u32,
+    pub extended: bool,
+    pub frames: usize,
+    /// Most common payload length; only frames of this length are analysed
+    pub dlc: usize,
+    /// Median interval between frames
+    pub period_us: Option<u64>,
+    pub constant: Vec<ConstantByte>,
+    pub counters: Vec<Counter>,
+    pub checksums: Vec<Checksum>,
+    pub fields: Vec<FieldCandidate>,
+    pub events: Vec<EventCorrelation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Analysis {
+    /// Frames in the capture, including IDs left out by `AnalysisOptions::ids`
+    pub frames: usize,
+    pub ids: Vec<IdAnalysis>,
+}
+
+// ─── Analysis ────────────────────────────────────────────────────────
+
+pub fn analyze(frames: &[CanFrame], events: &[CanEvent], options: &AnalysisOptions) -> Analysis {
+    let mut by_id: BTreeMap<u32, Vec<&CanFrame>> = BTreeMap::new();
+    for f in frames {
+        if options.ids.is_empty() || options.ids.contains(&f.can_id) {
+            by_id.entry(f.can_id).or_default().push(f);
+        }
+    }
+    let ids = by_id
+        .into_iter()
+        .map(|(can_id, mut frames)| {
+            frames.sort_by_key(|f| f.timestamp_us);
+            analyze_id(can_id, &frames, events, options)
+        })
+        .collect();
+    Analysis {
+        frames: frames.len(),
+        ids,
+    }
+}
+
+fn analyze_id(
+    can_id: u32,
+    all: &[&CanFrame],
+    events: &[CanEvent],
+    options: &AnalysisOptions,
+) -> IdAnalysis {
+    let mut lengths: BTreeMap<usize, usize> = BTreeMap::new();
+    for f in all {
+        *lengths.entry(f.data.len()).or_default() += 1;
+    }
+    let dlc = lengths
+        .iter()
+        .max_by_key(|(len, count)| (**count, **len))
+        .map_or(0, |(len, _)| *len);
+    let mut gaps: Vec<u64> = all
+        .windows(2)
+        .map(|w| w[1].timestamp_us - w[0].timestamp_us)
+        .collect();
+    gaps.sort_unstable();
+
+    let mut id = IdAnalysis {
+        can_id,
+        extended: all.iter().any(|f| f.is_extended()),
+        frames: all.len(),
+        dlc,
+        period_us: gaps.get(gaps.len() / 2).copied(),
+        constant: Vec::new(),
+        counters: Vec::new(),
+        checksums: Vec::new(),
+        fields: Vec::new(),
+        events: Vec::new(),
+    };
+    let frames: Vec<&CanFrame> = all
+        .iter()
+        .copied()
+        .filter(|f| f.data.len() == dlc)
+        .collect();
+    if frames.len() < options.min_frames.max(2) {
+        // Still worth knowing when it starts or stops
+        id.events = events
+            .iter()
+            .filter_map(|e| correlate(&frames, &[], e, options.event_window_ms))
+            .collect();
+        return id;
+    }
+
+    // Bits already explained by a constant byte, counter or checksum
+    let mut used = vec![0u8; dlc];
+    for (i, used) in used.iter_mut().enumerate() {
+        let first = frames[0].data[i];
+        if frames.iter().all(|f| f.data[i] == first) {
+            id.constant.push(ConstantByte {
+                index: i,
+                value: first,
+            });
+            *used = 0xFF;
+            continue;
+        }
+        for counter in find_counters(&frames, i, *used) {
+            *used |= mask(counter.bit, counter.length);
+            id.counters.push(counter);
+        }
+        if *used == 0 {
+            let found = find_checksums(&frames, i);
+            if !found.is_empty() {
+                *used = 0xFF;
+                id.checksums.extend(found);
+            }
+        }
+    }
+    id.fields = find_fields(&frames, &used);
+    id.events = events
+        .iter()
+        .filter_map(|e| correlate(&frames, &id.fields, e, options.event_window_ms))
+        .collect();
+    id
+}
+
+fn mask(bit: u8, length: u8) -> u8 {
+    (((1u16 << length) - 1) << bit) as u8
+}
+
+/// Counters in the free bits of byte `i`, widest first
+fn find_counters(frames: &[&CanFrame], i: usize, used: u8) -> Vec<Counter> {
+    let mut taken = used;
+    let mut out = Vec::new();
+    for length in (2..=8u8).rev() {
+        for bit in 0..=(8 - length) {
+            let m = mask(bit, length);
+            if taken & m != 0 {
+                continue;
+            }
+            let values: Vec<u8> = frames.iter().map(|f| (f.data[i] & m) >> bit).collect();
+            let (min, max) = (
+                *values.iter().min().unwrap_or(&0),
+                *values.iter().max().unwrap_or(&0),
+            );
+            // A 2-bit counter still has to show all four values
+            if max - min < 3 {
+                continue;
+            }
+            let steps = values
+                .windows(2)
+                .filter(|w| w[1] == w[0].wrapping_add(1) || (w[0] == max && w[1] == min))
+                .count();
+            let share = steps as f64 / (values.len() - 1) as f64;
+            if share < COUNTER_MATCH {
+                continue;
+            }
+            // 0x50..0x5F counts as a byte, but only the low nibble is the counter
+            let changing = values.iter().fold(0, |acc, v| acc | (v ^ values[0]));
+            let length = 8 - changing.leading_zeros() as u8;
+            let field = ((1u16 << length) - 1) as u8;
+            taken |= mask(bit, length);
+            out.push(Counter {
+                byte: i,
+                bit,
+                length,
+                min: min & field,
+                max: max & field,
+                match_pct: pct(share),
+            });
+        }
+    }
+    out
+}
+
+/// Checksum algorithms over the bytes after `i`, or before it, that explain byte `i`
+fn find_checksums(frames: &[&CanFrame], i: usize) -> Vec<Checksum> {
+    let distinct: BTreeSet<u8> = frames.iter().map(|f| f.data[i]).collect();
+    // A byte with few values matches some algorithm by chance
+    if distinct.len() < 4 {
+        return Vec::new();
+    }
+    let dlc = frames[0].data.len();
+    let ranges = [(i + 1, dlc), (0, i)];
+    let mut out = Vec::new();
+    for (from, to) in ranges.into_iter().filter(|(from, to)| to > from) {
+        for &kind in ChecksumKind::all() {
+            let hits = frames
+                .iter()
+                .filter(|f| kind.compute(&f.data[from..to]) == f.data[i])
+                .count();
+            let share = hits as f64 / frames.len() as f64;
+            if share >= CHECKSUM_MATCH {
+                out.push(Checksum {
+                    byte: i,
+                    kind,
+                    from,
+                    to,
+                    match_pct: pct(share),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Runs of changing bits in each byte, outside `used`
+fn find_fields(frames: &[&CanFrame], used: &[u8]) -> Vec<FieldCandidate> {
+    let mut out = Vec::new();
+    for (i, used) in used.iter().enumerate() {
+        let changing: u8 = frames
+            .windows(2)
+            .fold(0, |acc, w| acc | (w[0].data[i] ^ w[1].data[i]))
+            & !used;
+        let mut bit = 0u8;
+        while bit < 8 {
+            if changing & (1 << bit) == 0 {
+                bit += 1;
+                continue;
+            }
+            let start = bit;
+            while bit < 8 && changing & (1 << bit) != 0 {
+                bit += 1;
+            }
+            let field = BitField {
+                start_bit: (i * 8) as u16 + start as u16,
+                length: (bit - start) as u16,
+                byte_order: ByteOrder::LittleEndian,
+            };
+            let values: Vec<u64> = frames
+                .iter()
+                .filter_map(|f| field.extract(&f.data))
+                .collect();
+            out.push(FieldCandidate {
+                start_bit: field.start_bit,
+                length: field.length,
+                distinct: values.iter().collect::<BTreeSet<_>>().len(),
+                changes: values.windows(2).filter(|w| w[0] != w[1]).count(),
+            });
+        }
+    }
+    out
+}
+
+fn correlate(
+    frames: &[&CanFrame],
+    fields: &[FieldCandidate],
+    event: &CanEvent,
+    window_ms: u64,
+) -> Option<EventCorrelation> {
+    let at = event.time_ms * 1000;
+    let window = window_ms * 1000;
+    let before: Vec<&CanFrame> = frames
+        .iter()
+        .copied()
+        .filter(|f| f.timestamp_us < at && f.timestamp_us + window >= at)
+        .collect();
+    let after: Vec<&CanFrame> = frames
+        .iter()
+        .copied()
+        .filter(|f| f.timestamp_us >= at && f.timestamp_us < at + window)
+        .collect();
+    let steps = if before.is_empty() || after.is_empty() {
+        Vec::new()
+    } else {
+        fields
+            .iter()
+            .filter_map(|c| {
+                let field = BitField {
+                    start_bit: c.start_bit,
+                    length: c.length,
+                    byte_order: ByteOrder::LittleEndian,
+                };
+                let values = |frames: &[&CanFrame]| -> BTreeSet<u64> {
+                    frames
+                        .iter()
+                        .filter_map(|f| field.extract(&f.data))
+                        .collect()
+                };
+                let (b, a) = (values(&before), values(&after));
+                b.is_disjoint(&a).then(|| FieldStep {
+                    start_bit: c.start_bit,
+                    length: c.length,
+                    before: b.into_iter().collect(),
+                    after: a.into_iter().collect(),
+                })
+            })
+            .collect()
+    };
+    let correlation = EventCorrelation {
+        event: event.label.clone(),
+        time_ms: event.time_ms,
+        started: before.is_empty() && !after.is_empty(),
+        stopped: !before.is_empty() && after.is_empty(),
+        fields: steps,
+    };
+    (correlation.started || correlation.stopped || !correlation.fields.is_empty())
+        .then_some(correlation)
+}
+
+fn pct(share: f64) -> f64 {
+    (share * 1000.0).round() / 10.0
+}
+
+// ─── Draft DBC ───────────────────────────────────────────────────────
+
+fn values_text(values: &[u64]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn draft_signal(name: String, start_bit: u16, length: u16, comment: String) -> Signal {
+    Signal {
+        name,
+        field: BitField {
+            start_bit,
+            length,
+            byte_order: ByteOrder::LittleEndian,
+        },
+        signed: false,
+        factor: 1.0,
+        offset: 0.0,
+        min: 0.0,
+        max: ((1u64 << length) - 1) as f64,
+        unit: String::new(),
+        receivers: Vec::new(),
+        multiplexor: false,
+        mux_value: None,
+        values: BTreeMap::new(),
+        comment: Some(comment),
+    }
+}
+
+impl IdAnalysis {
+    /// Draft message: counters, checksums and changing fields as signals, the rest
+    /// in comments
+    pub fn draft_message(&self) -> Message {
+        let mut signals = Vec::new();
+        for c in &self.counters {
+            signals.push(draft_signal(
+                format!("Counter_B{}", c.byte),
+                (c.byte * 8) as u16 + c.bit as u16,
+                c.length as u16,
+                format!(
+                    "Alive counter {}..{} ({}% of frames)",
+                    c.min, c.max, c.match_pct
+                ),
+            ));
+        }
+        let mut checksum_bytes: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for c in &self.checksums {
+            checksum_bytes.entry(c.byte).or_default().push(format!(
+                "{} of bytes {}..{} ({}%)",
+                c.kind.name(),
+                c.from,
+                c.to,
+                c.match_pct
+            ));
+        }
+        for (byte, candidates) in checksum_bytes {
+            signals.push(draft_signal(
+                format!("Checksum_B{}", byte),
+                (byte * 8) as u16,
+                8,
+                format!("Checksum candidates: {}", candidates.join(", ")),
+            ));
+        }
+        for f in &self.fields {
+            let (byte, bit) = (f.start_bit / 8, f.start_bit % 8);
+            let name = if f.length == 8 {
+                format!("Sig_B{}", byte)
+            } else {
+                format!("Sig_B{}_{}", byte, bit)
+            };
+            let mut comment = format!("{} values, {} changes", f.distinct, f.changes);
+            for e in &self.events {
+                for step in e.fields.iter().filter(|s| s.start_bit == f.start_bit) {
+                    comment.push_str(&format!(
+                        "; steps at '{}' ({} -> {})",
+                        e.event,
+                        values_text(&step.before),
+                        values_text(&step.after)
+                    ));
+                }
+            }
+            signals.push(draft_signal(name, f.start_bit, f.length, comment));
+        }
+        signals.sort_by_key(|s| s.field.start_bit);
+
+        let mut notes = vec![format!("Draft from {} frames", self.frames)];
+        if let Some(period) = self.period_us {
+            notes.push(format!("period {:.1} ms", period as f64 / 1000.0));
+        }
+        if !self.constant.is_empty() {
+            let constant: Vec<String> = self
+                .constant
+                .iter()
+                .map(|c| format!("B{}=0x{:02X}", c.index, c.value))
+                .collect();
+            notes.push(format!("constant {}", constant.join(" ")));
+        }
+        for e in &self.events {
+            if e.started {
+                notes.push(format!("starts at '{}'", e.event));
+            }
+            if e.stopped {
+                notes.push(format!("stops at '{}'", e.event));
+            }
+        }
+
+        Message {
+            id: self.can_id,
+            extended: self.extended,
+            name: if self.extended {
+                format!("ID_{:08X}", self.can_id)
+            } else {
+                format!("ID_{:03X}", self.can_id)
+            },
+            size: self.dlc,
+            sender: String::new(),
+            signals,
+            comment: Some(notes.join(", ")),
+        }
+    }
+}
+
+impl Analysis {
+    pub fn draft_dbc(&self) -> Dbc {
+        Dbc {
+            version: "draft".into(),
+            nodes: Vec::new(),
+            messages: self.ids.iter().map(IdAnalysis::draft_message).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 0x1D0 every 10 ms: B0 = CRC8 J1850 of B1..8, B1 low nibble = counter,
+    /// B1 high nibble = 0x5, B2 = 0x00 then 0x01 from 1 s ("ignition on"),
+    /// B3..8 constant; 0x2A0 only starts at 1 s
+    fn capture() -> Vec<CanFrame> {
+        let mut frames = Vec::new();
+        for i in 0..200u64 {
+            let mut data = vec![0, 0x50 | (i % 16) as u8, (i >= 100) as u8, 0x40, 0, 0, 0, 0];
+            data[0] = crate::broadcast::crc8_j1850(&data[1..8]);
+            frames.push(CanFrame::new(i * 10_000, 0x1D0, data));
+            if i >= 100 && i % 10 == 0 {
+                frames.push(CanFrame::new(i * 10_000 + 5, 0x2A0, vec![0x11]));
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn test_checksum_kinds() {
+        // CRC-8/SMBUS and CRC-8/AUTOSAR check values of "123456789"
+        assert_eq!(ChecksumKind::Crc8Smbus.compute(b"123456789"), 0xF4);
+        assert_eq!(ChecksumKind::Crc8Autosar.compute(b"123456789"), 0xDF);
+        assert_eq!(
+            ChecksumKind::Crc8J1850.compute(b"123456789"),
+            crate::broadcast::crc8_j1850(b"123456789")
+        );
+        assert_eq!(ChecksumKind::Sum.compute(&[0xF0, 0x20]), 0x10);
+    }
+
+    #[test]
+    fn test_analyze_counter_checksum_and_event() {
+        let events = [CanEvent {
+            time_ms: 1000,
+            label: "ignition on".into(),
+        }];
+        let analysis = analyze(&capture(), &events, &AnalysisOptions::default());
+        assert_eq!(analysis.frames, 210);
+        assert_eq!(analysis.ids.len(), 2);
+
+        let id = &analysis.ids[0];
+        assert_eq!((id.can_id, id.dlc, id.period_us), (0x1D0, 8, Some(10_000)));
+        let constant: Vec<usize> = id.constant.iter().map(|c| c.index).collect();
+        assert_eq!(constant, vec![3, 4, 5, 6, 7]);
+        assert_eq!(id.counters.len(), 1);
+        let counter = &id.counters[0];
+        assert_eq!((counter.byte, counter.bit, counter.length), (1, 0, 4));
+        assert_eq!((counter.min, counter.max), (0, 15));
+        assert!(id
+            .checksums
+            .iter()
+            .any(|c| c.kind == ChecksumKind::Crc8J1850 && (c.byte, c.from, c.to) == (0, 1, 8)));
+        // B1's high nibble is constant, only B2 bit 0 is left over
+        assert_eq!(id.fields.len(), 1);
+        assert_eq!((id.fields[0].start_bit, id.fields[0].length), (16, 1));
+        assert_eq!(id.events.len(), 1);
+        let step = &id.events[0].fields[0];
+        assert_eq!(
+            (step.before.clone(), step.after.clone()),
+            (vec![0], vec![1])
+        );
+
+        // Too few frames to analyse, but it starts at the event
+        let options = AnalysisOptions {
+            min_frames: 20,
+            ..Default::default()
+        };
+        let late = &analyze(&capture(), &events, &options).ids[1];
+        assert_eq!((late.can_id, late.frames), (0x2A0, 10));
+        assert!(late.constant.is_empty());
+        assert!(late.events[0].started);
+    }
+
+    #[test]
+    fn test_draft_dbc() {
+        let events = [CanEvent {
+            time_ms: 1000,
+            label: "ignition on".into(),
+        }];
+        let options = AnalysisOptions {
+            ids: vec![0x1D0],
+            ..Default::default()
+        };
+        let dbc = analyze(&capture(), &events, &options).draft_dbc();
+        assert_eq!(dbc.messages.len(), 1);
+        let msg = &dbc.messages[0];
+        assert_eq!(msg.name, "ID_1D0");
+        let names: Vec<&str> = msg.signals.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Checksum_B0", "Counter_B1", "Sig_B2_0"]);
+        assert!(msg.signals[2]
+            .comment
+            .as_deref()
+            .unwrap()
+            .contains("steps at 'ignition on' (0 -> 1)"));
+
+        // The draft parses back and decodes the capture
+        let back = Dbc::parse(&dbc.write()).unwrap();
+        let frame = capture()
+            .into_iter()
+            .find(|f| f.can_id == 0x1D0 && f.timestamp_us == 1_500_000)
+            .unwrap();
+        let decoded = back.decode(&frame).unwrap();
+        assert_eq!(decoded.signals[1].raw, (150 % 16) as i64);
+        assert_eq!(decoded.signals[2].raw, 1);
+    }
+}
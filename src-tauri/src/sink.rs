@@ -0,0 +1,74 @@
+//! Output sinks for UDS log entries, job progress and live CAN monitor updates.
+//!
+//! The diagnostic core (`commands::*_inner`) reports through `&dyn LogSink` instead of
+//! a Tauri `AppHandle`, so the same code can drive the GUI, the CLI and other front ends.
+
+use std::sync::Mutex;
+
+use tauri::Emitter;
+
+use crate::jobs::JobProgress;
+use crate::monitor::MonitorUpdate;
+use crate::uds::client::LogEntry;
+
+/// Receives UDS traffic logs and structured job progress
+pub trait LogSink: Send + Sync {
+    fn log(&self, entry: LogEntry);
+
+    fn progress(&self, _progress: &JobProgress) {}
+
+    fn can_monitor(&self, _update: &MonitorUpdate) {}
+}
+
+/// GUI sink: forwards to the frontend as "uds-log" / "job-progress" / "can-monitor" events
+impl<R: tauri::Runtime> LogSink for tauri::AppHandle<R> {
+    fn log(&self, entry: LogEntry) {
+        let _ = self.emit("uds-log", entry);
+    }
+
+    fn progress(&self, progress: &JobProgress) {
+        let _ = self.emit("job-progress", progress);
+    }
+
+    fn can_monitor(&self, update: &MonitorUpdate) {
+        let _ = self.emit("can-monitor", update);
+    }
+}
+
+/// Discards everything (entries still reach the `log` crate via `emit_log`)
+pub struct NullSink;
+
+impl LogSink for NullSink {
+    fn log(&self, _entry: LogEntry) {}
+}
+
+/// Collects entries in memory — used by tests and by callers that want the trace afterwards
+#[derive(Default)]
+pub struct MemorySink {
+    entries: Mutex<Vec<LogEntry>>,
+    progress: Mutex<Vec<JobProgress>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn progress_events(&self) -> Vec<JobProgress> {
+        self.progress.lock().unwrap().clone()
+    }
+}
+
+impl LogSink for MemorySink {
+    fn log(&self, entry: LogEntry) {
+        self.entries.lock().unwrap().push(entry);
+    }
+
+    fn progress(&self, progress: &JobProgress) {
+        self.progress.lock().unwrap().push(progress.clone());
+    }
+}
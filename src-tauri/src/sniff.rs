@@ -0,0 +1,535 @@
+//! Differential CAN capture around one UDS action.
+//!
+//! The `sniff_action` job records the bus in three windows: a baseline before the
+//! request, "during" right after it (while a routine runs, a module reboots, ...)
+//! and "after" once things should have settled. `SniffWindows::diff` compares them
+//! per CAN ID: IDs that appeared or vanished, periods that changed and byte values
+//! never seen in the baseline.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::canlog::{CanFrame, CanLog, CaptureFormat};
+
+/// The UDS request to sniff around
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SniffAction {
+    /// RoutineControl start (0x31 01)
+    Routine {
+        routine_id: u16,
+        #[serde(default)]
+        data: Vec<u8>,
+    },
+    /// WriteDataByIdentifier (0x2E)
+    WriteDid { did: u16, data: Vec<u8> },
+    /// ECUReset (0x11), hard reset by default
+    Reset {
+        #[serde(default = "hard_reset")]
+        reset_type: u8,
+    },
+    /// DiagnosticSessionControl (0x10)
+    Session { session: u8 },
+    /// Any other request, sent as is
+    Raw { request: Vec<u8> },
+}
+
+fn hard_reset() -> u8 {
+    0x01
+}
+
+impl SniffAction {
+    pub fn request(&self) -> Vec<u8> {
+        match self {
+            SniffAction::Routine { routine_id, data } => {
+                let mut req = vec![0x31, 0x01];
+                req.extend_from_slice(&routine_id.to_be_bytes());
+                req.extend_from_slice(data);
+                req
+            }
+            SniffAction::WriteDid { did, data } => {
+                let mut req = vec![0x2E];
+                req.extend_from_slice(&did.to_be_bytes());
+                req.extend_from_slice(data);
+                req
+            }
+            SniffAction::Reset { reset_type } => vec![0x11, *reset_type],
+            SniffAction::Session { session } => vec![0x10, *session],
+            SniffAction::Raw { request } => request.clone(),
+        }
+    }
+
+    /// Session to enter before the request (routines and DID writes need extended)
+    pub fn prerequisite_session(&self) -> Option<u8> {
+        match self {
+            SniffAction::Routine { .. } | SniffAction::WriteDid { .. } => Some(0x03),
+            _ => None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            SniffAction::Routine { routine_id, .. } => format!("Routine 0x{:04X}", routine_id),
+            SniffAction::WriteDid { did, .. } => format!("WriteDID 0x{:04X}", did),
+            SniffAction::Reset { reset_type } => format!("ECUReset 0x{:02X}", reset_type),
+            SniffAction::Session { session } => format!("Session 0x{:02X}", session),
+            SniffAction::Raw { request } => format!("Request {}", hex(request)),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            SniffAction::WriteDid { data, .. } if data.is_empty() => {
+                Err("WriteDID needs data".into())
+            }
+            SniffAction::Raw { request } if request.is_empty() => {
+                Err("Raw request is empty".into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SniffOptions {
+    /// Capture before the request
+    pub baseline_s: u32,
+    /// Window starting right after the request
+    pub during_s: u32,
+    /// Window following `during_s`
+    pub after_s: u32,
+    /// A period differing from the baseline by more than this is reported
+    pub period_tolerance_pct: u32,
+    /// Write all three windows to this capture file (format from the extension)
+    pub save: Option<String>,
+}
+
+impl Default for SniffOptions {
+    fn default() -> Self {
+        Self {
+            baseline_s: 5,
+            during_s: 5,
+            after_s: 25,
+            period_tolerance_pct: 20,
+            save: None,
+        }
+    }
+}
+
+impl SniffOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.baseline_s == 0 {
+            return Err("The baseline window must be at least 1 s".into());
+        }
+        match self.during_s.checked_add(self.after_s) {
+            Some(0) => return Err("Nothing to capture after the request".into()),
+            None => return Err("The during and after windows are too long".into()),
+            Some(_) => {}
+        }
+        if let Some(path) = &self.save {
+            CaptureFormat::from_path(std::path::Path::new(path))
+                .filter(|f| f.is_writable())
+                .ok_or_else(|| format!("Cannot save a capture as {}", path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Frame count and ID count of one window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WindowSummary {
+    pub frames: usize,
+    pub ids: usize,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdChange {
+    /// Absent from the baseline
+    New,
+    /// In the baseline, gone during and after
+    Vanished,
+    /// On the bus throughout, with a different period or payload
+    Changed,
+}
+
+/// Distinct values of one payload byte per window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ByteChange {
+    pub index: usize,
+    pub baseline: Vec<u8>,
+    pub during: Vec<u8>,
+    pub after: Vec<u8>,
+    /// Seen during or after but never in the baseline
+    pub new_values: Vec<u8>,
+}
+
+/// How one CAN ID differs between the windows
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IdDiff {
+    pub can_id: u32,
+    pub extended: bool,
+    pub change: IdChange,
+    pub baseline_count: usize,
+    pub during_count: usize,
+    pub after_count: usize,
+    /// Median interval between frames (None with fewer than two frames)
+    pub baseline_period_us: Option<u64>,
+    pub during_period_us: Option<u64>,
+    pub after_period_us: Option<u64>,
+    pub period_changed: bool,
+    pub bytes: Vec<ByteChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SniffDiff {
+    pub baseline: WindowSummary,
+    pub during: WindowSummary,
+    pub after: WindowSummary,
+    pub new_ids: Vec<u32>,
+    pub vanished_ids: Vec<u32>,
+    /// Every ID that differs in any way, by ID
+    pub ids: Vec<IdDiff>,
+}
+
+impl SniffDiff {
+    pub fn summary(&self, action: &str) -> String {
+        let period = self.ids.iter().filter(|d| d.period_changed).count();
+        let payload = self.ids.iter().filter(|d| !d.bytes.is_empty()).count();
+        format!(
+            "Baseline: {} frames ({} IDs) | During {}: {} frames ({} IDs) | After: {} frames \
+             ({} IDs) | New: {} | Vanished: {} | Period changed: {} | Payload changed: {}",
+            self.baseline.frames,
+            self.baseline.ids,
+            action,
+            self.during.frames,
+            self.during.ids,
+            self.after.frames,
+            self.after.ids,
+            id_list(&self.new_ids),
+            id_list(&self.vanished_ids),
+            period,
+            payload
+        )
+    }
+}
+
+fn id_list(ids: &[u32]) -> String {
+    if ids.is_empty() {
+        return "none".into();
+    }
+    ids.iter()
+        .map(|id| format!("0x{:03X}", id))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The three captures; timestamps are µs since the start of each capture
+/// (during and after share one, `after` starts `during_us` into it)
+#[derive(Debug, Clone, Default)]
+pub struct SniffWindows {
+    pub baseline: Vec<CanFrame>,
+    pub during: Vec<CanFrame>,
+    pub after: Vec<CanFrame>,
+    pub baseline_us: u64,
+    pub during_us: u64,
+    pub after_us: u64,
+}
+
+impl SniffWindows {
+    /// Split one capture taken after the request at `during_us`
+    pub fn new(baseline: Vec<CanFrame>, post: Vec<CanFrame>, options: &SniffOptions) -> Self {
+        let during_us = options.during_s as u64 * 1_000_000;
+        let (during, after) = post.into_iter().partition(|f| f.timestamp_us < during_us);
+        Self {
+            baseline,
+            during,
+            after,
+            baseline_us: options.baseline_s as u64 * 1_000_000,
+            during_us,
+            after_us: options.after_s as u64 * 1_000_000,
+        }
+    }
+
+    pub fn diff(&self, period_tolerance_pct: u32) -> SniffDiff {
+        let baseline = per_id(&self.baseline);
+        let during = per_id(&self.during);
+        let after = per_id(&self.after);
+        let all: BTreeSet<u32> = baseline
+            .keys()
+            .chain(during.keys())
+            .chain(after.keys())
+            .copied()
+            .collect();
+
+        let empty = Vec::new();
+        let mut ids = Vec::new();
+        for id in all {
+            let b = baseline.get(&id).unwrap_or(&empty);
+            let d = during.get(&id).unwrap_or(&empty);
+            let a = after.get(&id).unwrap_or(&empty);
+            let (bp, dp, ap) = (median_period(b), median_period(d), median_period(a));
+            let period_changed = [dp, ap]
+                .into_iter()
+                .any(|p| period_differs(bp, p, period_tolerance_pct));
+            let change = if b.is_empty() {
+                IdChange::New
+            } else if d.is_empty() && a.is_empty() {
+                IdChange::Vanished
+            } else {
+                IdChange::Changed
+            };
+            let bytes = if change == IdChange::Changed {
+                byte_changes(b, d, a)
+            } else {
+                Vec::new()
+            };
+            if change == IdChange::Changed && !period_changed && bytes.is_empty() {
+                continue;
+            }
+            let first = b.first().or(d.first()).or(a.first());
+            ids.push(IdDiff {
+                can_id: id,
+                extended: first.is_some_and(|f| f.is_extended()),
+                change,
+                baseline_count: b.len(),
+                during_count: d.len(),
+                after_count: a.len(),
+                baseline_period_us: bp,
+                during_period_us: dp,
+                after_period_us: ap,
+                period_changed,
+                bytes,
+            });
+        }
+
+        let with = |change| {
+            ids.iter()
+                .filter(|d: &&IdDiff| d.change == change)
+                .map(|d| d.can_id)
+                .collect()
+        };
+        SniffDiff {
+            baseline: summarize(&baseline, self.baseline.len(), self.baseline_us),
+            during: summarize(&during, self.during.len(), self.during_us),
+            after: summarize(&after, self.after.len(), self.after_us),
+            new_ids: with(IdChange::New),
+            vanished_ids: with(IdChange::Vanished),
+            ids,
+        }
+    }
+
+    /// All windows on one timeline; the post-request capture started `post_start_us`
+    /// after the baseline
+    pub fn to_log(&self, post_start_us: u64) -> CanLog {
+        let mut frames = self.baseline.clone();
+        frames.extend(self.during.iter().chain(&self.after).map(|f| CanFrame {
+            timestamp_us: f.timestamp_us + post_start_us,
+            ..f.clone()
+        }));
+        CanLog::new(CaptureFormat::Candump, None, frames)
+    }
+}
+
+fn per_id(frames: &[CanFrame]) -> BTreeMap<u32, Vec<&CanFrame>> {
+    let mut map: BTreeMap<u32, Vec<&CanFrame>> = BTreeMap::new();
+    for f in frames {
+        map.entry(f.can_id).or_default().push(f);
+    }
+    map
+}
+
+fn summarize(ids: &BTreeMap<u32, Vec<&CanFrame>>, frames: usize, us: u64) -> WindowSummary {
+    WindowSummary {
+        frames,
+        ids: ids.len(),
+        duration_ms: us / 1000,
+    }
+}
+
+fn median_period(frames: &[&CanFrame]) -> Option<u64> {
+    let mut gaps: Vec<u64> = frames
+        .windows(2)
+        .map(|w| w[1].timestamp_us.saturating_sub(w[0].timestamp_us))
+        .collect();
+    if gaps.is_empty() {
+        return None;
+    }
+    gaps.sort_unstable();
+    Some(gaps[gaps.len() / 2])
+}
+
+/// Only compares windows where both periods are known; an ID dropping out shows in
+/// its counts instead
+fn period_differs(baseline: Option<u64>, other: Option<u64>, tolerance_pct: u32) -> bool {
+    match (baseline, other) {
+        (Some(b), Some(o)) => b.abs_diff(o) * 100 > b * tolerance_pct as u64,
+        _ => false,
+    }
+}
+
+/// Bytes that took values never seen in the baseline, or stopped changing
+fn byte_changes(b: &[&CanFrame], d: &[&CanFrame], a: &[&CanFrame]) -> Vec<ByteChange> {
+    let len = b
+        .iter()
+        .chain(d)
+        .chain(a)
+        .map(|f| f.data.len())
+        .max()
+        .unwrap_or(0);
+    let values = |frames: &[&CanFrame], i: usize| -> BTreeSet<u8> {
+        frames
+            .iter()
+            .filter_map(|f| f.data.get(i).copied())
+            .collect()
+    };
+    (0..len)
+        .filter_map(|i| {
+            let (bv, dv, av) = (values(b, i), values(d, i), values(a, i));
+            let new_values: Vec<u8> = dv.union(&av).filter(|v| !bv.contains(v)).copied().collect();
+            let frozen = bv.len() > 1
+                && [&dv, &av].iter().all(|v| v.len() <= 1)
+                && !(dv.is_empty() && av.is_empty());
+            (!new_values.is_empty() || frozen).then(|| ByteChange {
+                index: i,
+                baseline: bv.into_iter().collect(),
+                during: dv.into_iter().collect(),
+                after: av.into_iter().collect(),
+                new_values,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` frames of `id` every `period_ms`, payload from `data(i)`
+    fn periodic(
+        id: u32,
+        period_ms: u64,
+        count: u64,
+        data: impl Fn(u64) -> Vec<u8>,
+    ) -> Vec<CanFrame> {
+        (0..count)
+            .map(|i| CanFrame::new(i * period_ms * 1000, id, data(i)))
+            .collect()
+    }
+
+    fn sorted(mut frames: Vec<CanFrame>) -> Vec<CanFrame> {
+        frames.sort_by_key(|f| f.timestamp_us);
+        frames
+    }
+
+    #[test]
+    fn test_action_requests() {
+        let routine = SniffAction::Routine {
+            routine_id: 0x6038,
+            data: vec![0x01],
+        };
+        assert_eq!(routine.request(), vec![0x31, 0x01, 0x60, 0x38, 0x01]);
+        assert_eq!(routine.prerequisite_session(), Some(0x03));
+        let reset: SniffAction = serde_json::from_str(r#"{"type":"reset"}"#).unwrap();
+        assert_eq!(reset.request(), vec![0x11, 0x01]);
+        assert_eq!(reset.prerequisite_session(), None);
+        let write: SniffAction =
+            serde_json::from_str(r#"{"type":"write_did","did":61840,"data":[1,2]}"#).unwrap();
+        assert_eq!(write.request(), vec![0x2E, 0xF1, 0x90, 0x01, 0x02]);
+        assert!(SniffAction::Raw { request: vec![] }.validate().is_err());
+    }
+
+    #[test]
+    fn test_diff_windows() {
+        let options = SniffOptions {
+            baseline_s: 1,
+            during_s: 1,
+            after_s: 1,
+            ..Default::default()
+        };
+        // 0x100: steady, unchanged; 0x200: counter in byte 0 until the action, then
+        // frozen, and byte 1 switches 0x00 → 0x01; 0x300: 100 ms → 20 ms;
+        // 0x400: gone after the action; 0x500: new after the action
+        let baseline = sorted(
+            [
+                periodic(0x100, 100, 10, |_| vec![0xAA]),
+                periodic(0x200, 100, 10, |i| vec![i as u8 & 0x0F, 0x00]),
+                periodic(0x300, 100, 10, |_| vec![0x01]),
+                periodic(0x400, 100, 10, |_| vec![0x02]),
+            ]
+            .concat(),
+        );
+        let post = sorted(
+            [
+                periodic(0x100, 100, 20, |_| vec![0xAA]),
+                periodic(0x200, 100, 20, |_| vec![0x05, 0x01]),
+                periodic(0x300, 20, 100, |_| vec![0x01]),
+                periodic(0x500, 1000, 2, |_| vec![0x03]),
+            ]
+            .concat(),
+        );
+        let windows = SniffWindows::new(baseline, post, &options);
+        assert_eq!(windows.during.len() + windows.after.len(), 142);
+        let diff = windows.diff(options.period_tolerance_pct);
+
+        assert_eq!(diff.new_ids, vec![0x500]);
+        assert_eq!(diff.vanished_ids, vec![0x400]);
+        assert_eq!(diff.baseline.ids, 4);
+        let ids: Vec<u32> = diff.ids.iter().map(|d| d.can_id).collect();
+        assert_eq!(ids, vec![0x200, 0x300, 0x400, 0x500]);
+
+        let counter = &diff.ids[0];
+        assert_eq!(counter.change, IdChange::Changed);
+        assert!(!counter.period_changed);
+        assert_eq!(counter.bytes.len(), 2);
+        // Frozen counter: no new value, but only one left
+        assert_eq!(counter.bytes[0].baseline.len(), 10);
+        assert_eq!(counter.bytes[0].during, vec![0x05]);
+        assert!(counter.bytes[0].new_values.is_empty());
+        assert_eq!(counter.bytes[1].new_values, vec![0x01]);
+
+        let faster = &diff.ids[1];
+        assert!(faster.period_changed);
+        assert_eq!(faster.baseline_period_us, Some(100_000));
+        assert_eq!(faster.during_period_us, Some(20_000));
+        assert!(faster.bytes.is_empty());
+
+        assert!(diff.summary("Routine 0x6038").contains("New: 0x500"));
+        let log = windows.to_log(1_500_000);
+        assert_eq!(log.frames.len(), 182);
+        assert_eq!(log.frames[40].timestamp_us, 1_500_000);
+    }
+
+    #[test]
+    fn test_options_validate() {
+        assert!(SniffOptions::default().validate().is_ok());
+        let no_post = SniffOptions {
+            during_s: 0,
+            after_s: 0,
+            ..Default::default()
+        };
+        assert!(no_post.validate().is_err());
+        let overflow = SniffOptions {
+            during_s: u32::MAX,
+            after_s: 1,
+            ..Default::default()
+        };
+        assert!(overflow.validate().is_err());
+        let bad_save = SniffOptions {
+            save: Some("capture.unknown".into()),
+            ..Default::default()
+        };
+        assert!(bad_save.validate().is_err());
+    }
+}
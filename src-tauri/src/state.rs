@@ -1,32 +1,101 @@
 use std::sync::{Arc, Mutex};
 
+use crate::api::ApiServer;
+use crate::archive::VehicleArchive;
+use crate::ccf::{CcfRuleFile, CcfTables};
+use crate::commands::dump_path;
 use crate::ecu_emulator::EcuEmulatorManager;
 use crate::j2534::device::{J2534Channel, J2534Device};
+use crate::j2534::remote::BridgeServer;
+use crate::j2534::router::ChannelRouter;
 use crate::j2534::dll::J2534Lib;
+use crate::jobs::JobRegistry;
+use crate::monitor::BusMonitor;
+use crate::sink::{LogSink, NullSink};
 
 /// Active connection to a J2534 device with an ECU channel
 pub struct Connection {
-    pub lib: Arc<J2534Lib>,
+    /// J2534 DLL and device; `None` for emulated connections (no adapter)
+    pub lib: Option<Arc<J2534Lib>>,
+    pub device: Option<J2534Device>,
+    /// ISO15765 channel behind the per-ECU router. Shared so UDS exchanges can
+    /// run without holding the connection lock (see `commands::bus_handles`).
+    pub channel: Option<Arc<ChannelRouter>>,
+    /// Raw CAN channel for broadcast emulation (separate from ISO15765 channel).
+    /// Shared so a replay or monitor can use it without holding the connection lock.
+    pub can_channel: Option<Arc<J2534Channel>>,
+    pub dll_path: String,
+    pub emulator_manager: Option<Arc<EcuEmulatorManager>>,
+}
+
+impl Connection {
+    /// The J2534 device, or an error on emulated connections
+    pub fn device(&self) -> Result<&J2534Device, String> {
+        self.device
+            .as_ref()
+            .ok_or_else(|| "No local J2534 adapter on this connection (emulated or remote)".to_string())
+    }
+}
+
+/// This machine's adapter served to a remote client (see `j2534::remote`).
+/// Field order matters: the server (and the channel it owns) drops before the device.
+pub struct BridgeSession {
+    pub server: BridgeServer,
     pub device: J2534Device,
-    pub channel: Option<J2534Channel>,
-    /// Raw CAN channel for broadcast emulation (separate from ISO15765 channel)
-    pub can_channel: Option<J2534Channel>,
+    pub token: String,
     pub dll_path: String,
-    pub emulator_manager: Option<EcuEmulatorManager>,
 }
 
 /// Global app state managed by Tauri
 pub struct AppState {
     pub connection: Mutex<Option<Connection>>,
+    /// Background jobs (long operations started via `start_job`)
+    pub jobs: Arc<JobRegistry>,
+    /// Local automation API, when started (see `api`)
+    pub api: Mutex<Option<ApiServer>>,
+    /// Adapter bridge, when serving a remote helper
+    pub bridge: Mutex<Option<BridgeSession>>,
+    /// Live CAN monitor, running or last stopped (its buffer can still be saved)
+    pub can_monitor: Mutex<Option<BusMonitor>>,
+    /// CCF decode tables: built in plus imported from SDD EXML
+    pub ccf_tables: CcfTables,
+    /// CCF consistency rules: `ccf_rules.json` if present, else built in
+    pub ccf_rules: CcfRuleFile,
+    /// Snapshots of what was read from each vehicle, by VIN
+    pub archive: VehicleArchive,
+    /// Where background workers (the bench-mode bus responder) log traffic;
+    /// the GUI sets its app handle, the CLI its stderr sink
+    background_sink: Mutex<Option<Arc<dyn LogSink>>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             connection: Mutex::new(None),
+            jobs: Arc::new(JobRegistry::new()),
+            api: Mutex::new(None),
+            bridge: Mutex::new(None),
+            can_monitor: Mutex::new(None),
+            ccf_tables: CcfTables::new(dump_path("ccf_tables")),
+            ccf_rules: CcfRuleFile::new(dump_path("ccf_rules.json")),
+            archive: VehicleArchive::new(dump_path("archive")),
+            background_sink: Mutex::new(None),
         }
     }
 
+    pub fn set_background_sink(&self, sink: Arc<dyn LogSink>) {
+        *self.background_sink.lock().unwrap() = Some(sink);
+    }
+
+    /// Sink for traffic not tied to a command call (discarded if none was set)
+    pub fn background_sink(&self) -> Arc<dyn LogSink> {
+        self.background_sink
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| Arc::new(NullSink))
+    }
+
     pub fn is_connected(&self) -> bool {
         self.connection.lock().unwrap().is_some()
     }
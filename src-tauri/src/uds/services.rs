@@ -429,6 +429,85 @@ pub fn execute_routine_sdd<C: Channel>(
     routine_start(client, routine_id, data, needs_pending)
 }
 
+// ─── ReadDTCInformation (0x19) ───────────────────────────────────────
+
+/// reportDTCByStatusMask sub-function
+pub const DTC_BY_STATUS_MASK: u8 = 0x02;
+
+/// Status mask matching every DTC the ECU has recorded
+pub const DTC_STATUS_ALL: u8 = 0xFF;
+
+/// One DTC record from a 0x19 0x02 response
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Dtc {
+    /// 3-byte DTC (2 bytes SAE code + failure type byte)
+    pub raw: u32,
+    pub status: u8,
+}
+
+impl Dtc {
+    /// SAE J2012 display form, e.g. 0xC10087 → "U0100-87"
+    pub fn code(&self) -> String {
+        let hi = (self.raw >> 16) as u8;
+        let letter = ['P', 'C', 'B', 'U'][(hi >> 6) as usize];
+        format!(
+            "{}{}{:03X}-{:02X}",
+            letter,
+            (hi >> 4) & 0x03,
+            (self.raw >> 8) & 0xFFF,
+            self.raw & 0xFF
+        )
+    }
+
+    /// Names of the ISO 14229 status bits that are set
+    pub fn status_flags(&self) -> Vec<&'static str> {
+        const BITS: [&str; 8] = [
+            "testFailed",
+            "testFailedThisOperationCycle",
+            "pending",
+            "confirmed",
+            "testNotCompletedSinceLastClear",
+            "testFailedSinceLastClear",
+            "testNotCompletedThisOperationCycle",
+            "warningIndicatorRequested",
+        ];
+        (0..8)
+            .filter(|bit| self.status & (1 << bit) != 0)
+            .map(|bit| BITS[bit])
+            .collect()
+    }
+}
+
+/// Parse a positive 0x19 0x02 response: `59 02 <availability mask> (<DTC hi mid lo> <status>)*`
+pub fn parse_dtc_report(response: &[u8]) -> Result<Vec<Dtc>, String> {
+    if response.len() < 3 || response[0] != 0x59 || response[1] != DTC_BY_STATUS_MASK {
+        return Err(format!("Unexpected DTC response: {:02X?}", response));
+    }
+    let records = &response[3..];
+    if !records.len().is_multiple_of(4) {
+        return Err(format!(
+            "Truncated DTC record ({} trailing bytes)",
+            records.len() % 4
+        ));
+    }
+    Ok(records
+        .chunks(4)
+        .map(|r| Dtc {
+            raw: u32::from_be_bytes([0, r[0], r[1], r[2]]),
+            status: r[3],
+        })
+        .collect())
+}
+
+/// Read DTCs matching `status_mask`
+pub fn read_dtc_by_status<C: Channel>(
+    client: &UdsClient<C>,
+    status_mask: u8,
+) -> Result<Vec<Dtc>, UdsError> {
+    let response = client.send_recv(&[0x19, DTC_BY_STATUS_MASK, status_mask], 2000, false)?;
+    parse_dtc_report(&response).map_err(UdsError::InvalidResponse)
+}
+
 // ─── SSH Enable flow ────────────────────────────────────────────────
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -1454,4 +1533,40 @@ mod tests {
         assert_eq!(routine::SSH_ENABLE, 0x603E);
         assert_eq!(routine::VIN_LEARN, 0x0404);
     }
+
+    #[test]
+    fn test_dtc_code_formatting() {
+        assert_eq!(Dtc { raw: 0xC10087, status: 0x08 }.code(), "U0100-87");
+        assert_eq!(Dtc { raw: 0x030000, status: 0x00 }.code(), "P0300-00");
+        assert_eq!(Dtc { raw: 0x9A1E13, status: 0x00 }.code(), "B1A1E-13");
+        assert_eq!(Dtc { raw: 0x412300, status: 0x00 }.code(), "C0123-00");
+    }
+
+    #[test]
+    fn test_parse_dtc_report() {
+        let resp = [0x59, 0x02, 0xFF, 0xC1, 0x00, 0x87, 0x09, 0x9A, 0x1E, 0x13, 0x28];
+        let dtcs = parse_dtc_report(&resp).unwrap();
+        assert_eq!(dtcs.len(), 2);
+        assert_eq!(dtcs[0].code(), "U0100-87");
+        assert_eq!(dtcs[0].status_flags(), vec!["testFailed", "confirmed"]);
+        assert_eq!(dtcs[1].raw, 0x9A1E13);
+
+        assert!(parse_dtc_report(&[0x59, 0x02, 0xFF]).unwrap().is_empty());
+        assert!(parse_dtc_report(&[0x59, 0x02, 0xFF, 0xC1, 0x00]).is_err());
+        assert!(parse_dtc_report(&[0x62, 0xF1, 0x90]).is_err());
+    }
+
+    #[test]
+    fn test_read_dtc_by_status() {
+        let mock = MockChannel::new();
+        mock.expect_request(
+            ecu_addr::BCM_TX,
+            vec![0x19, 0x02, 0xFF],
+            vec![0x59, 0x02, 0xFF, 0xC1, 0x00, 0x87, 0x09],
+        );
+        let client = make_bcm_client(mock);
+        let dtcs = read_dtc_by_status(&client, DTC_STATUS_ALL).unwrap();
+        assert_eq!(dtcs.len(), 1);
+        assert_eq!(dtcs[0].status, 0x09);
+    }
 }
@@ -15,8 +15,30 @@ const ECU_OPTIONS = [
   { id: "ipc", label: "IPC", address: "0x720" },
 ] as const;
 
+// Fault scenario presets (ecu_emulator::FAULT_PRESETS)
+const FAULT_OPTIONS = [
+  { id: "", label: "None — answer instantly" },
+  { id: "slow", label: "Slow (1.5 s per answer)" },
+  { id: "pending", label: "3× response pending (0x78)" },
+  { id: "busy", label: "Busy (0x21) every 2nd request" },
+  { id: "drop", label: "Drop every 3rd answer" },
+  { id: "duplicate", label: "Duplicate answers" },
+  { id: "truncate", label: "Truncate multi-frame answers" },
+  { id: "stale-nrc", label: "Stale NRC (7F 10 12) before answers" },
+  { id: "flaky-imc", label: "Flaky IMC (pending routines, busy reads)" },
+] as const;
+
+// Ignition positions the bench CAN broadcast can simulate (broadcast::VehicleState)
+const VEHICLE_STATES = [
+  { id: "off", label: "Off (NM only)" },
+  { id: "accessory", label: "Accessory" },
+  { id: "on", label: "On" },
+  { id: "crank", label: "Crank" },
+] as const;
+
 const AUTO_DETECT = "__auto__";
 const MANUAL_PATH = "__manual__";
+const SIMULATED = "__simulated__";
 
 export default function ConnectPanel({
   connected,
@@ -27,9 +49,13 @@ export default function ConnectPanel({
   const [devices, setDevices] = useState<J2534DeviceEntry[]>([]);
   const [selectedDevice, setSelectedDevice] = useState(AUTO_DETECT);
   const [manualPath, setManualPath] = useState("");
+  const [dumpDir, setDumpDir] = useState("");
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const [benchMode, setBenchMode] = useState(false);
+  const [faults, setFaults] = useState("");
+  const [schedule, setSchedule] = useState("");
+  const [vehicleState, setVehicleState] = useState("on");
   const [selectedEcus, setSelectedEcus] = useState<Set<string>>(
     new Set(["bcm"])
   );
@@ -49,6 +75,8 @@ export default function ConnectPanel({
     if (connected) {
       api.getBenchModeStatus().then((status) => {
         setBenchMode(status.enabled);
+        setFaults(status.faults ?? "");
+        setVehicleState(status.vehicle_state ?? "on");
         if (status.emulated_ecus.length > 0) {
           setSelectedEcus(new Set(status.emulated_ecus));
         }
@@ -66,7 +94,10 @@ export default function ConnectPanel({
     setLoading(true);
     setError(null);
     try {
-      const info = await api.connect(getDllPath());
+      const info =
+        selectedDevice === SIMULATED
+          ? await api.connectSimulated(undefined, dumpDir.trim() || undefined)
+          : await api.connect(getDllPath());
       onConnected(info);
     } catch (e) {
       setError(String(e));
@@ -93,8 +124,24 @@ export default function ConnectPanel({
     const newValue = !benchMode;
     try {
       const ecus = Array.from(selectedEcus);
-      await api.toggleBenchMode(newValue, ecus);
+      await api.toggleBenchMode(
+        newValue,
+        ecus,
+        faults || undefined,
+        schedule.trim() || undefined
+      );
       setBenchMode(newValue);
+      setVehicleState("on");
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  // Switched live — the broadcast follows without restarting bench mode
+  const handleVehicleState = async (next: string) => {
+    try {
+      await api.setVehicleState(next);
+      setVehicleState(next);
     } catch (e) {
       setError(String(e));
     }
@@ -142,6 +189,7 @@ export default function ConnectPanel({
             </option>
           ))}
           <option value={MANUAL_PATH}>Custom DLL path...</option>
+          <option value={SIMULATED}>Simulated vehicle (no adapter)</option>
         </select>
 
         {selectedDevice === MANUAL_PATH && !connected && (
@@ -155,6 +203,22 @@ export default function ConnectPanel({
           />
         )}
 
+        {selectedDevice === SIMULATED && !connected && (
+          <>
+            <p className="text-xs text-gray-500">
+              IMC, BCM, GWM and IPC are emulated in software — no DLL or hardware needed
+            </p>
+            <input
+              type="text"
+              value={dumpDir}
+              onChange={(e) => setDumpDir(e.target.value)}
+              className="w-full bg-bg-primary border border-gray-600 rounded px-3 py-2 text-sm font-mono
+                         focus:border-accent focus:outline-none"
+              placeholder="Folder with *_dump.json scans (optional)"
+            />
+          </>
+        )}
+
         <div className="flex gap-2">
           {!connected ? (
             <button
@@ -197,8 +261,8 @@ export default function ConnectPanel({
         </div>
       )}
 
-      {/* Bench Mode */}
-      {connected && (
+      {/* Bench Mode (not applicable when everything is simulated) */}
+      {connected && deviceInfo?.firmware_version !== "emulated" && (
         <div className="card space-y-3">
           <div className="flex items-center justify-between">
             <div>
@@ -242,6 +306,47 @@ export default function ConnectPanel({
               </label>
             ))}
           </div>
+
+          {/* Fault injection */}
+          <select
+            value={faults}
+            onChange={(e) => setFaults(e.target.value)}
+            disabled={benchMode}
+            className="w-full bg-bg-primary border border-gray-600 rounded px-3 py-1.5 text-xs
+                       focus:border-accent focus:outline-none disabled:opacity-50"
+          >
+            {FAULT_OPTIONS.map((f) => (
+              <option key={f.id} value={f.id}>
+                Faults: {f.label}
+              </option>
+            ))}
+          </select>
+
+          {/* DBC broadcast schedule */}
+          <input
+            type="text"
+            value={schedule}
+            onChange={(e) => setSchedule(e.target.value)}
+            disabled={benchMode}
+            className="w-full bg-bg-primary border border-gray-600 rounded px-3 py-1.5 text-xs font-mono
+                       focus:border-accent focus:outline-none disabled:opacity-50"
+            placeholder="Broadcast schedule JSON (optional, default frames)"
+          />
+
+          {/* Vehicle state for the CAN broadcast */}
+          <select
+            value={vehicleState}
+            onChange={(e) => handleVehicleState(e.target.value)}
+            disabled={!benchMode}
+            className="w-full bg-bg-primary border border-gray-600 rounded px-3 py-1.5 text-xs
+                       focus:border-accent focus:outline-none disabled:opacity-50"
+          >
+            {VEHICLE_STATES.map((v) => (
+              <option key={v.id} value={v.id}>
+                Ignition: {v.label}
+              </option>
+            ))}
+          </select>
         </div>
       )}
 
@@ -253,7 +358,11 @@ export default function ConnectPanel({
           />
           <span className="text-sm">
             {connected ? (
-              <span className="text-ok">Connected to Mongoose Pro</span>
+              <span className="text-ok">
+                {deviceInfo?.firmware_version === "emulated"
+                  ? "Connected to simulated vehicle"
+                  : "Connected to Mongoose Pro"}
+              </span>
             ) : (
               <span className="text-gray-400">
                 Not connected — select device and click Connect
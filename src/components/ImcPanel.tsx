@@ -197,7 +197,18 @@ export default function ImcPanel({ connected }: Props) {
                     className={`border-b border-[#333] last:border-0 ${entry.mismatch ? "bg-[#3a1a1a]" : ""}`}
                   >
                     <td className="py-1.5 pr-3 text-[#555] font-mono">{entry.option_id}</td>
-                    <td className="py-1.5 pr-3 text-[#aaaaaa]">{entry.name}</td>
+                    <td className="py-1.5 pr-3 text-[#aaaaaa]">
+                      {entry.name}
+                      {entry.findings.map((f) => (
+                        <div
+                          key={f.rule}
+                          title={`${f.detail}\n${f.explanation}`}
+                          className={`text-[11px] ${f.severity === "error" ? "text-err" : f.severity === "warning" ? "text-warn" : "text-[#858585]"}`}
+                        >
+                          {f.description}
+                        </div>
+                      ))}
+                    </td>
                     <td className="py-1.5 pr-3 font-mono text-[#cccccc]">
                       {entry.gwm ?? <span className="text-[#555]">—</span>}
                     </td>
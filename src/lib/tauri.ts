@@ -7,8 +7,35 @@ import type {
   J2534DeviceEntry,
   BenchModeStatus,
   CcfCompareEntry,
+  CcfEdit,
+  CcfEditResult,
+  CcfOptionDef,
+  CcfTableInfo,
+  CcfRules,
+  CcfCheckReport,
   CanSniffResult,
+  SniffAction,
+  SniffActionResult,
+  SniffOptions,
+  CanLogInfo,
+  CaptureFormat,
+  CanDecodeReport,
+  CanAnalysisReport,
+  CanEvent,
+  AnalysisOptions,
+  CanSniffEntry,
+  MonitorOptions,
+  MonitorSnapshot,
   RestoreCcfResult,
+  JobKind,
+  DtcEntry,
+  ArchivedVehicle,
+  Snapshot,
+  SnapshotDiff,
+  SnapshotInfo,
+  ApiInfo,
+  BridgeStatus,
+  JobInfo,
 } from "../types";
 
 export async function discoverDevices(): Promise<J2534DeviceEntry[]> {
@@ -19,15 +46,33 @@ export async function connect(dllPath?: string): Promise<DeviceInfo> {
   return invoke<DeviceInfo>("connect", { dllPath });
 }
 
+export async function connectSimulated(
+  ecus?: string[],
+  dumpDir?: string
+): Promise<DeviceInfo> {
+  return invoke<DeviceInfo>("connect_simulated", { ecus, dumpDir });
+}
+
+export async function connectRemote(addr: string, token: string): Promise<DeviceInfo> {
+  return invoke<DeviceInfo>("connect_remote", { addr, token });
+}
+
 export async function disconnect(): Promise<void> {
   return invoke<void>("disconnect");
 }
 
+/** `schedule` is a DBC broadcast schedule file sent instead of the default frames */
 export async function toggleBenchMode(
   enabled: boolean,
-  ecus?: string[]
+  ecus?: string[],
+  faults?: string,
+  schedule?: string
 ): Promise<void> {
-  return invoke<void>("toggle_bench_mode", { enabled, ecus });
+  return invoke<void>("toggle_bench_mode", { enabled, ecus, faults, schedule });
+}
+
+export async function setVehicleState(vehicleState: string): Promise<void> {
+  return invoke<void>("set_vehicle_state", { vehicleState });
 }
 
 export async function getBenchModeStatus(): Promise<BenchModeStatus> {
@@ -56,6 +101,13 @@ export async function readDid(
   return invoke<number[]>("read_did", { ecuTx, didId });
 }
 
+export async function readDtc(
+  ecu: string,
+  statusMask?: number
+): Promise<DtcEntry[]> {
+  return invoke<DtcEntry[]>("read_dtc", { ecu, statusMask });
+}
+
 export async function listRoutines(): Promise<RoutineInfo[]> {
   return invoke<RoutineInfo[]>("list_routines");
 }
@@ -68,10 +120,183 @@ export async function compareCcf(): Promise<CcfCompareEntry[]> {
   return invoke<CcfCompareEntry[]>("compare_ccf");
 }
 
+/** Options of decode table `table` (default built-in) matching `query` by ID, name or group */
+export async function searchCcfOptions(query?: string, table?: string): Promise<CcfOptionDef[]> {
+  return invoke<CcfOptionDef[]>("search_ccf_options", { query, table });
+}
+
+export async function listCcfTables(): Promise<CcfTableInfo[]> {
+  return invoke<CcfTableInfo[]>("list_ccf_tables");
+}
+
+/** Import an SDD CCF_DATA_*.exml for VINs starting with one of `vinPrefixes` */
+export async function importCcfExml(path: string, vinPrefixes: string[]): Promise<CcfTableInfo> {
+  return invoke<CcfTableInfo>("import_ccf_exml", { path, vinPrefixes });
+}
+
+/** CCF consistency rules in effect: the rules file, or the built-in ones */
+export async function listCcfRules(): Promise<CcfRules> {
+  return invoke<CcfRules>("list_ccf_rules");
+}
+
+/** Validate the rules in the JSON file at `path` and put them in effect */
+export async function importCcfRules(path: string): Promise<CcfRules> {
+  return invoke<CcfRules>("import_ccf_rules", { path });
+}
+
+/** Read the GWM/BCM CCFs and the VIN and check the CCF rules against them */
+export async function checkCcf(): Promise<CcfCheckReport> {
+  return invoke<CcfCheckReport>("check_ccf");
+}
+
+/** Read the GWM/BCM CCF and show what `edits` would change */
+export async function previewCcfEdit(ecu: string, edits: CcfEdit[]): Promise<CcfEditResult> {
+  return invoke<CcfEditResult>("preview_ccf_edit", { ecu, edits });
+}
+
+/** Back up, write and verify the GWM/BCM CCF with `edits`; rule errors need `force` */
+export async function writeCcfEdit(
+  ecu: string,
+  edits: CcfEdit[],
+  force = false
+): Promise<CcfEditResult> {
+  return invoke<CcfEditResult>("write_ccf_edit", { ecu, edits, force });
+}
+
+/** Write a backup made by writeCcfEdit back to its ECU */
+export async function undoCcfEdit(backup: string, force = false): Promise<CcfEditResult> {
+  return invoke<CcfEditResult>("undo_ccf_edit", { backup, force });
+}
+
+export async function listArchivedVehicles(): Promise<ArchivedVehicle[]> {
+  return invoke<ArchivedVehicle[]>("list_archived_vehicles");
+}
+
+/** Snapshots archived for `vin`, oldest first */
+export async function getVehicleHistory(vin: string): Promise<SnapshotInfo[]> {
+  return invoke<SnapshotInfo[]>("get_vehicle_history", { vin });
+}
+
+export async function getSnapshot(id: string): Promise<Snapshot> {
+  return invoke<Snapshot>("get_snapshot", { id });
+}
+
+/** What changed between two archived snapshots of the same kind */
+export async function diffSnapshots(from: string, to: string): Promise<SnapshotDiff> {
+  return invoke<SnapshotDiff>("diff_snapshots", { from, to });
+}
+
+/** Write an archived GWM/BCM CCF snapshot back (backed up and verified) */
+export async function rollbackCcf(id: string, force = false): Promise<CcfEditResult> {
+  return invoke<CcfEditResult>("rollback_ccf", { id, force });
+}
+
 export async function canSniffRoutine(): Promise<CanSniffResult> {
   return invoke<CanSniffResult>("can_sniff_routine");
 }
 
+export async function sniffAction(
+  ecu: string,
+  action: SniffAction,
+  options?: Partial<SniffOptions>,
+): Promise<SniffActionResult> {
+  return invoke<SniffActionResult>("sniff_action", { ecu, action, options });
+}
+
+export async function convertCanLog(
+  input: string,
+  output: string,
+  format?: CaptureFormat,
+): Promise<CanLogInfo> {
+  return invoke<CanLogInfo>("convert_can_log", { input, output, format });
+}
+
+export async function decodeCanLog(dbc: string, log: string): Promise<CanDecodeReport> {
+  return invoke<CanDecodeReport>("decode_can_log", { dbc, log });
+}
+
+export async function decodeCanFrames(
+  dbc: string,
+  frames: CanSniffEntry[],
+): Promise<CanDecodeReport> {
+  return invoke<CanDecodeReport>("decode_can_frames", { dbc, frames });
+}
+
+export async function analyzeCanLog(
+  log: string,
+  events?: CanEvent[],
+  options?: Partial<AnalysisOptions>,
+  dbcOut?: string,
+): Promise<CanAnalysisReport> {
+  return invoke<CanAnalysisReport>("analyze_can_log", { log, events, options, dbcOut });
+}
+
+export async function analyzeCanFrames(
+  frames: CanSniffEntry[],
+  events?: CanEvent[],
+  options?: Partial<AnalysisOptions>,
+  dbcOut?: string,
+): Promise<CanAnalysisReport> {
+  return invoke<CanAnalysisReport>("analyze_can_frames", { frames, events, options, dbcOut });
+}
+
 export async function restoreCcf(sniff: boolean = false): Promise<RestoreCcfResult> {
   return invoke<RestoreCcfResult>("restore_ccf", { sniff });
 }
+
+export async function startJob(kind: JobKind): Promise<number> {
+  return invoke<number>("start_job", { kind });
+}
+
+export async function cancelJob(jobId: number): Promise<void> {
+  return invoke<void>("cancel_job", { jobId });
+}
+
+export async function listJobs(): Promise<JobInfo[]> {
+  return invoke<JobInfo[]>("list_jobs");
+}
+
+export async function startCanMonitor(options?: Partial<MonitorOptions>): Promise<number> {
+  return invoke<number>("start_can_monitor", { options });
+}
+
+export async function stopCanMonitor(): Promise<void> {
+  return invoke<void>("stop_can_monitor");
+}
+
+export async function getCanMonitor(): Promise<MonitorSnapshot | null> {
+  return invoke<MonitorSnapshot | null>("get_can_monitor");
+}
+
+export async function saveCanMonitor(path: string, format?: CaptureFormat): Promise<CanLogInfo> {
+  return invoke<CanLogInfo>("save_can_monitor", { path, format });
+}
+
+export async function startApiServer(port?: number): Promise<ApiInfo> {
+  return invoke<ApiInfo>("start_api_server", { port });
+}
+
+export async function stopApiServer(): Promise<void> {
+  return invoke<void>("stop_api_server");
+}
+
+export async function getApiStatus(): Promise<ApiInfo | null> {
+  return invoke<ApiInfo | null>("get_api_status");
+}
+
+export async function startBridge(
+  dllPath?: string,
+  bind?: string,
+  port?: number,
+  token?: string
+): Promise<BridgeStatus> {
+  return invoke<BridgeStatus>("start_bridge", { dllPath, bind, port, token });
+}
+
+export async function stopBridge(): Promise<void> {
+  return invoke<void>("stop_bridge");
+}
+
+export async function getBridgeStatus(): Promise<BridgeStatus | null> {
+  return invoke<BridgeStatus | null>("get_bridge_status");
+}
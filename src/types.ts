@@ -43,6 +43,10 @@ export interface LogEntry {
 export interface BenchModeStatus {
   enabled: boolean;
   emulated_ecus: string[];
+  /** Fault scenario the emulated ECUs run under, if any */
+  faults: string | null;
+  /** Simulated ignition position the CAN broadcast follows */
+  vehicle_state: string | null;
 }
 
 export interface CanSniffEntry {
@@ -52,6 +56,50 @@ export interface CanSniffEntry {
   data_len: number;
 }
 
+export type CaptureFormat =
+  | "dump_txt"
+  | "monitor"
+  | "sniff_json"
+  | "candump"
+  | "asc"
+  | "blf"
+  | "pcap"
+  | "pcapng";
+
+export interface CanLogInfo {
+  path: string;
+  format: CaptureFormat;
+  source_format: CaptureFormat;
+  frames: number;
+  unique_ids: number;
+  duration_ms: number;
+}
+
+export interface DecodedSignal {
+  name: string;
+  raw: number;
+  value: number;
+  unit: string;
+  /** Value-table label for the raw value */
+  label: string | null;
+}
+
+export interface DecodedFrame {
+  timestamp_us: number;
+  can_id: number;
+  message: string;
+  signals: DecodedSignal[];
+}
+
+export interface CanDecodeReport {
+  dbc: string;
+  frames: number;
+  decoded: number;
+  /** IDs the DBC doesn't describe */
+  unknown_ids: string[];
+  messages: DecodedFrame[];
+}
+
 export interface CanSniffResult {
   routine_response: string | null;
   baseline_frames: CanSniffEntry[];
@@ -60,6 +108,124 @@ export interface CanSniffResult {
   summary: string;
 }
 
+export interface CanEvent {
+  /** Milliseconds since the start of the capture */
+  time_ms: number;
+  label: string;
+}
+
+export interface AnalysisOptions {
+  /** Only these IDs (empty = all) */
+  ids: number[];
+  min_frames: number;
+  event_window_ms: number;
+}
+
+export type ChecksumKind =
+  | "xor"
+  | "sum"
+  | "crc8_j1850"
+  | "crc8_j1850_zero"
+  | "crc8_autosar"
+  | "crc8_smbus";
+
+export interface IdAnalysis {
+  can_id: number;
+  extended: boolean;
+  frames: number;
+  dlc: number;
+  period_us: number | null;
+  constant: { index: number; value: number }[];
+  counters: {
+    byte: number;
+    bit: number;
+    length: number;
+    min: number;
+    max: number;
+    match_pct: number;
+  }[];
+  checksums: { byte: number; kind: ChecksumKind; from: number; to: number; match_pct: number }[];
+  fields: { start_bit: number; length: number; distinct: number; changes: number }[];
+  events: {
+    event: string;
+    time_ms: number;
+    started: boolean;
+    stopped: boolean;
+    fields: { start_bit: number; length: number; before: number[]; after: number[] }[];
+  }[];
+}
+
+export interface CanAnalysisReport {
+  frames: number;
+  ids: IdAnalysis[];
+  /** Draft DBC text */
+  dbc: string;
+  dbc_path: string | null;
+}
+
+export type SniffAction =
+  | { type: "routine"; routine_id: number; data?: number[] }
+  | { type: "write_did"; did: number; data: number[] }
+  | { type: "reset"; reset_type?: number }
+  | { type: "session"; session: number }
+  | { type: "raw"; request: number[] };
+
+export interface SniffOptions {
+  baseline_s: number;
+  during_s: number;
+  after_s: number;
+  period_tolerance_pct: number;
+  /** Capture file for all windows, format from the extension */
+  save: string | null;
+}
+
+export interface SniffWindowSummary {
+  frames: number;
+  ids: number;
+  duration_ms: number;
+}
+
+export interface SniffByteChange {
+  index: number;
+  baseline: number[];
+  during: number[];
+  after: number[];
+  new_values: number[];
+}
+
+export interface SniffIdDiff {
+  can_id: number;
+  extended: boolean;
+  change: "new" | "vanished" | "changed";
+  baseline_count: number;
+  during_count: number;
+  after_count: number;
+  baseline_period_us: number | null;
+  during_period_us: number | null;
+  after_period_us: number | null;
+  period_changed: boolean;
+  bytes: SniffByteChange[];
+}
+
+export interface SniffActionResult {
+  ecu_tx: number;
+  action: SniffAction;
+  exchange: {
+    request: string;
+    response: string | null;
+    error: string | null;
+    duration_ms: number;
+  };
+  baseline: SniffWindowSummary;
+  during: SniffWindowSummary;
+  after: SniffWindowSummary;
+  new_ids: number[];
+  vanished_ids: number[];
+  ids: SniffIdDiff[];
+  summary: string;
+  saved: CanLogInfo | null;
+}
+
 export interface RestoreCcfResult {
   success: boolean;
   steps: RestoreCcfStep[];
@@ -90,6 +256,8 @@ export interface PreFlightInfo {
   option_467_extracted: number | null;
   option_467_desc: string;
   warnings: string[];
+  /** CCF rules the GWM copy breaks; warnings and errors also land in `warnings` */
+  findings: RuleFinding[];
 }
 
 export interface PostFlightInfo {
@@ -106,4 +274,279 @@ export interface CcfCompareEntry {
   bcm: string | null;
   imc: string | null;
   mismatch: boolean;
+  /** CCF rules broken that involve this option */
+  findings: RuleFinding[];
+}
+
+export type Severity = "info" | "warning" | "error";
+
+/** An option value as a rule names it: raw, or a decode-table label */
+export type RuleValue = number | string;
+
+/** One condition of a CCF rule; without `ecu` it reads the ECU being checked */
+export type RuleCheck =
+  | { check: "option_in"; option: number; ecu?: string; values: RuleValue[] }
+  | { check: "option_not_in"; option: number; ecu?: string; values: RuleValue[] }
+  | { check: "same_on"; options: number[]; ecus: string[] }
+  | { check: "did_in"; did: string; ecu?: string; byte?: number; values: number[] }
+  | { check: "vin_matches"; did: string; ecu?: string };
+
+/** When every `when` check holds, every `require` check must */
+export interface CcfRule {
+  id: string;
+  severity: Severity;
+  description: string;
+  explanation: string;
+  when?: RuleCheck[];
+  require: RuleCheck[];
+}
+
+export interface CcfRules {
+  /** "built-in", or the rules file they were read from */
+  source: string;
+  rules: CcfRule[];
+}
+
+/** A rule the checked CCF breaks */
+export interface RuleFinding {
+  rule: string;
+  severity: Severity;
+  description: string;
+  /** The values that break it */
+  detail: string;
+  explanation: string;
+  /** Options involved */
+  options: number[];
+}
+
+export interface CcfCheckReport {
+  vin: string | null;
+  /** Decode table the rules' labels were resolved with */
+  table: string;
+  /** Where the rules came from */
+  rules: string;
+  /** Most severe first */
+  findings: RuleFinding[];
+  passed: number;
+  /** Rules needing a CCF or DID that wasn't read, or naming an unknown label */
+  skipped: SkippedRule[];
+}
+
+export interface SkippedRule {
+  rule: string;
+  severity: Severity;
+  reason: string;
+}
+
+/** A CCF decode table: built in, or imported from an SDD CCF_DATA EXML */
+export interface CcfTableInfo {
+  name: string;
+  vehicle_line: string | null;
+  model_year: number | null;
+  /** VINs starting with one of these use this table */
+  vin_prefixes: string[];
+  option_count: number;
+  builtin: boolean;
+}
+
+/** One option of the CCF decode table */
+export interface CcfOptionDef {
+  id: number;
+  name: string;
+  group: string;
+  mdfid?: string;
+  /** Index into the flat option array */
+  byte: number;
+  bit: number;
+  width: number;
+  /** Raw value → label */
+  values: Record<string, string>;
+}
+
+/** Option by number or decode-table name, value by number or label */
+export interface CcfEdit {
+  option: string;
+  value: string;
+}
+
+export interface CcfOptionChange {
+  option_id: number;
+  name: string;
+  raw_before: number;
+  raw_after: number;
+  before: string;
+  after: string;
+}
+
+export interface CcfEditResult {
+  ecu: string;
+  did: number;
+  vin: string | null;
+  /** Decode table the edits were resolved with */
+  table: string;
+  changes: CcfOptionChange[];
+  checksum_before: number;
+  checksum_after: number;
+  /** False for a preview, or when the image already matched */
+  written: boolean;
+  /** Backup of the image as read before writing, for undoCcfEdit */
+  backup: string | null;
+  /** CCF rules the resulting image breaks */
+  findings: RuleFinding[];
+}
+
+export type JobKind =
+  | { type: "restore_ccf"; sniff: boolean }
+  | { type: "can_sniff_routine" }
+  | { type: "sniff_action"; ecu: string; action: SniffAction; options?: Partial<SniffOptions> }
+  | { type: "scan_bcm" }
+  | { type: "scan_gwm" }
+  | { type: "scan_ipc" }
+  | { type: "replay_can"; path: string; options?: Partial<ReplayOptions> }
+  | { type: "monitor_can"; options?: Partial<MonitorOptions> };
+
+export interface ReplayOptions {
+  speed: number;
+  /** 0 = repeat until the job is cancelled */
+  loops: number;
+  include: number[];
+  exclude: number[];
+}
+
+export interface MonitorOptions {
+  buffer_frames: number;
+  update_ms: number;
+  bitrate: number;
+  /** Most raw frames per update event (0 = statistics only) */
+  stream_frames: number;
+  /** 0 = until stopped */
+  seconds: number;
+}
+
+export interface CanFrame {
+  timestamp_us: number;
+  can_id: number;
+  data: number[];
+  rx_status: number;
+}
+
+export interface IdStats {
+  can_id: number;
+  extended: boolean;
+  count: number;
+  data: number[];
+  /** Bytes of `data` that changed within the last second */
+  changed: boolean[];
+  last_us: number;
+  period_us: number | null;
+  jitter_us: number | null;
+}
+
+export interface MonitorSnapshot {
+  /** Set while the monitor job runs */
+  job_id: number | null;
+  elapsed_ms: number;
+  frames: number;
+  frames_per_sec: number;
+  bus_load_pct: number;
+  buffered: number;
+  overwritten: number;
+  ids: IdStats[];
+}
+
+/** Payload of the "can-monitor" event */
+export interface MonitorUpdate extends MonitorSnapshot {
+  new_frames: CanFrame[];
+  skipped_frames: number;
+}
+
+export interface DtcEntry {
+  code: string;
+  raw_hex: string;
+  status: number;
+  flags: string[];
+}
+
+/** One DID of an archived full scan */
+export interface ScannedDid {
+  did: number;
+  session: string;
+  /** Payload after the DID header; null when the read failed */
+  data: number[] | null;
+  error?: string;
+}
+
+export type SnapshotData =
+  | { kind: "ccf"; source: string; raw_bytes: number[] }
+  | { kind: "did_scan"; dids: ScannedDid[] }
+  | { kind: "identification"; entries: EcuInfoEntry[] }
+  | { kind: "dtcs"; dtcs: DtcEntry[] };
+
+/** An archived snapshot, listed without its data */
+export interface SnapshotInfo {
+  id: string;
+  vin: string;
+  ecu: string;
+  timestamp: string;
+  kind: SnapshotData["kind"];
+  summary: string;
+}
+
+export type Snapshot = Omit<SnapshotInfo, "kind" | "summary"> & SnapshotData;
+
+export interface ArchivedVehicle {
+  vin: string;
+  snapshots: number;
+  first_seen: string;
+  last_seen: string;
+}
+
+/** Option, DID or DTC that differs; null where missing on that side */
+export interface SnapshotChange {
+  key: string;
+  label: string;
+  before: string | null;
+  after: string | null;
+}
+
+export interface SnapshotDiff {
+  from: SnapshotInfo;
+  to: SnapshotInfo;
+  /** Decode table CCF options were named with */
+  table?: string;
+  changes: SnapshotChange[];
+}
+
+export interface ApiInfo {
+  url: string;
+  token: string;
+}
+
+export interface BridgeStatus {
+  addr: string;
+  token: string;
+  dll_path: string;
+}
+
+export type JobStatus = "running" | "completed" | "failed" | "cancelled";
+
+export interface JobInfo {
+  id: number;
+  kind: string;
+  status: JobStatus;
+  phase: string;
+  percent: number;
+  step: string;
+  started_at: string;
+  finished_at: string | null;
+  result: unknown | null;
+  error: string | null;
+}
+
+export interface JobProgress {
+  job_id: number;
+  status: JobStatus;
+  phase: string;
+  percent: number;
+  step: string;
 }